@@ -17,6 +17,10 @@ struct FuzzBomEntry {
     gid: u32,
     /// File size
     size: u64,
+    /// Modification time
+    mtime: u32,
+    /// cksum CRC
+    checksum: u32,
 }
 
 /// Safe path component that avoids problematic characters
@@ -80,13 +84,13 @@ impl FuzzBomEntry {
             .collect::<Vec<_>>()
             .join("/");
 
-        Some(iamawrapper::macos::bom::BomEntry {
-            path: PathBuf::from(path_str),
-            mode: self.mode.to_mode(),
-            uid: self.uid,
-            gid: self.gid,
-            size: if self.mode.is_dir() { 0 } else { self.size },
-        })
+        let size = if self.mode.is_dir() { 0 } else { self.size };
+        Some(
+            iamawrapper::macos::bom::BomEntry::new(PathBuf::from(path_str), self.mode.to_mode(), size)
+                .with_owner(self.uid, self.gid)
+                .with_mtime(self.mtime)
+                .with_checksum(self.checksum),
+        )
     }
 }
 
@@ -36,13 +36,11 @@ fuzz_target!(|data: &[u8]| {
             _ => 0o100644,
         };
 
-        entries.push(iamawrapper::macos::bom::BomEntry {
-            path: PathBuf::from(path_str),
+        entries.push(iamawrapper::macos::bom::BomEntry::new(
+            PathBuf::from(path_str),
             mode,
-            uid: 0,
-            gid: 80,
-            size: (i as u64).wrapping_mul(size_multiplier),
-        });
+            (i as u64).wrapping_mul(size_multiplier),
+        ));
     }
 
     if entries.is_empty() {
@@ -27,13 +27,14 @@ fuzz_target!(|config: BomConfig| {
     let num_top = (config.num_top_level % 20) as usize;
     for i in 0..num_top {
         let name = format!("file{}.txt", i);
-        entries.push(iamawrapper::macos::bom::BomEntry {
-            path: PathBuf::from(&name),
-            mode: 0o100644,
-            uid: config.seeds[idx % 8] as u32 * 100,
-            gid: 80,
-            size: (config.seeds[(idx + 1) % 8] as u64) * 1000,
-        });
+        entries.push(
+            iamawrapper::macos::bom::BomEntry::new(
+                PathBuf::from(&name),
+                0o100644,
+                (config.seeds[(idx + 1) % 8] as u64) * 1000,
+            )
+            .with_owner(config.seeds[idx % 8] as u32 * 100, 80),
+        );
         idx += 1;
     }
 
@@ -52,13 +53,11 @@ fuzz_target!(|config: BomConfig| {
             + &format!("/dir{}", d);
 
         // Add directory entry
-        entries.push(iamawrapper::macos::bom::BomEntry {
-            path: PathBuf::from(&dir_path),
-            mode: 0o040755,
-            uid: 0,
-            gid: 80,
-            size: 0,
-        });
+        entries.push(iamawrapper::macos::bom::BomEntry::new(
+            PathBuf::from(&dir_path),
+            0o040755,
+            0,
+        ));
 
         // Add files in directory
         for f in 0..files_per {
@@ -68,13 +67,11 @@ fuzz_target!(|config: BomConfig| {
             } else {
                 0o100755
             };
-            entries.push(iamawrapper::macos::bom::BomEntry {
-                path: PathBuf::from(&file_path),
+            entries.push(iamawrapper::macos::bom::BomEntry::new(
+                PathBuf::from(&file_path),
                 mode,
-                uid: 0,
-                gid: 80,
-                size: (config.seeds[(idx + f + 1) % 8] as u64) * 500,
-            });
+                (config.seeds[(idx + f + 1) % 8] as u64) * 500,
+            ));
         }
         idx += files_per + 1;
     }
@@ -1,11 +1,46 @@
 //! CLI argument parsing.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
-
+use clap_complete::Shell;
+use tracing::level_filters::LevelFilter;
+
+use crate::models::analyze::{AnalyzeRequest, DEFAULT_TOP_FILES};
+use crate::models::blocklist::BlocklistAction;
+use crate::models::build::{BuildContext, TargetKind};
+use crate::models::cache::CacheConfig;
+use crate::models::capture::CaptureFinishRequest;
+use crate::models::choco::ChocoPackRequest;
+use crate::models::compression::{Compression, CompressionMethod};
+use crate::models::config::Config;
+use crate::models::detection::{DetectionRule, DetectionRulesRequest, DigestAlgorithm};
+use crate::models::encryption::EncryptionOptions;
 use crate::models::error::{PackageError, PackageResult};
-use crate::models::package::{PackageRequest, UnpackRequest, Verbosity};
+use crate::models::glob::Glob;
+use crate::models::guardrails::SourceLimits;
+use crate::models::incremental::IncrementalOptions;
+use crate::models::naming::NameTemplate;
+use crate::models::package::{
+    PackageRequest, RepackRequest, Reproducible, UnpackRequest, Verbosity,
+};
+use crate::models::project::{ProjectBuild, ProjectImportRequest};
+use crate::models::retry::RetryPolicy;
+use crate::models::sbom::{SbomFormat, SbomOptions};
+use crate::models::scaffold::ScaffoldRequest;
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+use crate::models::transform::TransformRule;
+use crate::models::validation::DEFAULT_VALIDATION_TIMEOUT;
+use crate::models::watch::{WatchRequest, WatchRules};
+use crate::models::win32app::{
+    AppManifestRequest, AppSettings, Architecture, InstallContext, RestartBehavior, ReturnCode,
+};
+use crate::packager::capture::DEFAULT_STATE_FILE;
+use crate::packager::content::STDIN_TAR_LABEL;
+
+use super::logging::LogFormat;
 
 /// Cross-platform replacement for Microsoft Win32 Content Prep Tool
 #[derive(Parser, Debug)]
@@ -23,6 +58,35 @@ pub struct CliArgs {
     /// Silent mode - no console output at all
     #[arg(long = "silent", visible_alias = "qq", global = true)]
     pub silent: bool,
+
+    /// Treat warnings as errors (per-check overrides come from --config)
+    #[arg(long = "strict", global = true)]
+    pub strict: bool,
+
+    /// Configuration file (JSON or TOML) with strict mode settings, watch
+    /// rules and build targets
+    #[arg(long = "config", value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Lowest level logged: off, error, warn, info, debug or trace
+    #[arg(long = "log-level", value_name = "LEVEL", global = true, value_parser = str::parse::<LevelFilter>)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Append logs with timestamps and phase timings to this file
+    #[arg(long = "log-file", value_name = "FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// Log line format: text or json
+    #[arg(long = "log-format", value_name = "FORMAT", global = true, default_value = "text", value_parser = str::parse::<LogFormat>)]
+    pub log_format: LogFormat,
+
+    /// Folder for the run workspace (default: the system temp directory, e.g. $TMPDIR)
+    #[arg(long = "temp-dir", value_name = "DIR", global = true)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Interactive mode: neither offer nor remember the previous answers
+    #[arg(long = "no-history")]
+    pub no_history: bool,
 }
 
 /// Top-level commands
@@ -30,8 +94,37 @@ pub struct CliArgs {
 pub enum Commands {
     /// Create or extract Microsoft Intune packages (.intunewin)
     Intune(IntuneCommand),
-    /// Create macOS packages (.pkg)
+    /// Create or extract macOS packages (.pkg)
     Macos(MacosCommand),
+    /// Create Chocolatey packages (.nupkg)
+    Choco(ChocoCommand),
+    /// Create Linux packages (.deb, .rpm)
+    Linux(LinuxCommand),
+    /// Repackage by capturing changes to a directory tree
+    Capture(CaptureCommand),
+    /// Write a project file (settings, config, scripts, content manifest)
+    ExportProject(ExportProjectArgs),
+    /// Rebuild a package from a project file and its archived content
+    ImportProject(ImportProjectArgs),
+    /// Build, validate and unpack sample packages to check the installation
+    #[command(alias = "selftest")]
+    SelfTest,
+    /// Remove temp workspaces left behind by crashed runs
+    CleanWorkspaces(CleanWorkspacesArgs),
+    /// Show the type and embedded version record of a package
+    Inspect(InspectArgs),
+    /// Check the detached minisign signature of a package
+    VerifySignature(VerifySignatureArgs),
+    /// Package every application dropped into a folder as it appears
+    Watch(WatchArgs),
+    /// Build the packages of every target in the `build` section of
+    /// --config (e.g., an .intunewin and a .pkg of one app)
+    Build(BuildArgs),
+    /// Print the shell completion script for bash, zsh, fish, powershell
+    /// or elvish
+    Completions(CompletionsArgs),
+    /// Write a man page for every command to a folder
+    Manpages(ManpagesArgs),
 }
 
 /// Intune subcommand options
@@ -45,83 +138,1557 @@ pub struct IntuneCommand {
 #[derive(Subcommand, Debug, Clone)]
 pub enum IntuneAction {
     /// Create a new .intunewin package
-    Create(IntuneCreateArgs),
+    Create(Box<IntuneCreateArgs>),
     /// Extract an existing .intunewin package
     Extract(IntuneExtractArgs),
+    /// Check an .intunewin package without extracting it
+    Verify(IntuneVerifyArgs),
+    /// Show the Detection.xml metadata of an .intunewin without decrypting it
+    Inspect(IntuneInspectArgs),
+    /// List the files in an .intunewin without extracting it
+    List(IntuneListArgs),
+    /// Compare the files and Detection.xml of two .intunewin packages
+    Diff(IntuneDiffArgs),
+    /// Package every application listed in a manifest
+    Batch(BatchArgs),
+    /// Change the setup file or name of an existing .intunewin
+    Repack(IntuneRepackArgs),
+    /// Generate detection rules (JSON) for an .intunewin package
+    Rules(IntuneRulesArgs),
+    /// Generate the Graph win32LobApp body (JSON) for an .intunewin package
+    Manifest(Box<IntuneManifestArgs>),
+    /// Report duplicate files, the largest files and the expected
+    /// compression of a source folder before packaging it
+    Analyze(IntuneAnalyzeArgs),
+    /// Generate Install.ps1/Uninstall.ps1 wrappers for an .exe installer
+    /// into the source folder
+    Scaffold(IntuneScaffoldArgs),
+}
+
+/// Arguments for creating Intune packages
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneCreateArgs {
+    /// Source folder containing files to package, a .zip, .tar, .tar.gz or
+    /// .tgz archive of it, or the URL of one (https://host/app.zip#sha256=HEX)
+    #[arg(
+        short = 'c',
+        long = "content",
+        required_unless_present = "content_stdin_tar"
+    )]
+    pub content_folder: Option<PathBuf>,
+
+    /// Read the content as a tar stream from stdin instead of a folder
+    #[arg(long = "content-stdin-tar", conflicts_with = "content_folder")]
+    pub content_stdin_tar: bool,
+
+    /// Setup file name within source folder
+    #[arg(short = 's', long = "setup")]
+    pub setup_file: String,
+
+    /// Output folder for .intunewin file ('-' writes the package to stdout)
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "name")]
+    pub output_name: Option<String>,
+
+    /// Output filename template, e.g. "{setup_stem}-{version}-{date}"
+    /// (variables: setup_stem, version, date, git_sha)
+    #[arg(
+        long = "name-template",
+        value_name = "TEMPLATE",
+        conflicts_with = "output_name"
+    )]
+    pub name_template: Option<NameTemplate>,
+
+    /// Application name recorded in Detection.xml (default: the setup file)
+    #[arg(long = "display-name", value_name = "NAME")]
+    pub display_name: Option<String>,
+
+    /// ToolVersion recorded in Detection.xml (default: 1.8.6.0)
+    #[arg(long = "tool-version", value_name = "VERSION")]
+    pub tool_version: Option<String>,
+
+    /// Known-bad file hash list (CSV or JSON) to check content against
+    #[arg(long = "blocklist")]
+    pub blocklist: Option<PathBuf>,
+
+    /// Warn instead of failing when a blocklisted file is found
+    #[arg(long = "blocklist-warn", requires = "blocklist")]
+    pub blocklist_warn: bool,
+
+    /// Tag recorded in the package metadata (repeatable, e.g., build=1234)
+    #[arg(long = "content-tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub content_tags: Vec<(String, String)>,
+
+    /// JSON file of the app's dependencies and superseded apps to bundle
+    #[arg(long = "relationships", value_name = "FILE")]
+    pub relationships: Option<PathBuf>,
+
+    /// Rewrite matching files as they are packaged (repeatable, e.g., *.ps1=crlf)
+    #[arg(long = "transform", value_name = "GLOB=TRANSFORM", value_parser = TransformRule::parse)]
+    pub transforms: Vec<TransformRule>,
+
+    /// Only package files matching the pattern (repeatable, e.g., *.dll)
+    #[arg(long = "include", value_name = "GLOB", value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+
+    /// Leave out files matching the pattern (repeatable, e.g., *.pdb)
+    #[arg(long = "exclude", value_name = "GLOB", value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
+
+    /// Build a byte-identical package from identical inputs
+    #[arg(long = "reproducible")]
+    pub reproducible: bool,
+
+    /// Derive the encryption keys from this seed instead of the content
+    #[arg(
+        long = "reproducible-seed",
+        value_name = "SEED",
+        requires = "reproducible"
+    )]
+    pub reproducible_seed: Option<String>,
+
+    /// Encrypt with the keys in this JSON file instead of random ones
+    #[arg(
+        long = "keys-file",
+        value_name = "FILE",
+        conflicts_with_all = ["reproducible", "keys_from_env"]
+    )]
+    pub keys_file: Option<PathBuf>,
+
+    /// Encrypt with the keys in IAMAWRAPPER_ENCRYPTION_KEYS instead of random ones
+    #[arg(long = "keys-from-env", conflicts_with = "reproducible")]
+    pub keys_from_env: bool,
+
+    /// Hash the content for Detection.xml's FileDigest with SHA256
+    /// (default), SHA384 or SHA512
+    #[arg(long = "digest-algorithm", value_name = "ALGORITHM", value_parser = str::parse::<DigestAlgorithm>)]
+    pub digest_algorithm: Option<DigestAlgorithm>,
+
+    /// Sign the package with this minisign secret key, writing
+    /// `<package>.minisig` next to it
+    #[arg(long = "sign-key", value_name = "FILE")]
+    pub sign_key: Option<PathBuf>,
+
+    /// Write the setup .exe's version information to `<package>.setup.json`
+    #[arg(long = "setup-manifest")]
+    pub setup_manifest: bool,
+
+    /// Write an SBOM of the content to `<package>.cdx.json` or `.spdx.json`
+    #[arg(long = "sbom", value_name = "FORMAT", value_parser = str::parse::<SbomFormat>)]
+    pub sbom: Option<SbomFormat>,
+
+    /// Store the SBOM in the package's metadata folder (CycloneDX unless
+    /// --sbom names a format)
+    #[arg(long = "embed-sbom")]
+    pub embed_sbom: bool,
+
+    /// Keep compressed files here and reuse them for unchanged files
+    #[arg(long = "cache-dir", value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Record the source and settings digests here; when the source is
+    /// unchanged since, re-encrypt the previous package's content instead
+    /// of compressing it again
+    #[arg(long = "state-file", value_name = "FILE")]
+    pub state_file: Option<PathBuf>,
+
+    /// Keep the previous package when neither the source nor the settings
+    /// changed since the state file was written
+    #[arg(long = "skip-unchanged", requires = "state_file")]
+    pub skip_unchanged: bool,
+
+    /// Write the content paths that would not install on Windows to a JSON
+    /// report
+    #[arg(long = "path-report", value_name = "FILE")]
+    pub path_report: Option<PathBuf>,
+
+    /// Compress content as stored or deflate (default: deflate). Builds
+    /// with the zstd feature also accept zstd, which Intune cannot extract:
+    /// such packages only unpack with this tool
+    #[arg(long = "compression", value_name = "METHOD", value_parser = str::parse::<CompressionMethod>)]
+    pub compression: Option<CompressionMethod>,
+
+    /// Level from 0 (fastest) to 9 (smallest; default: 6), or 1 to 22
+    /// (default: 3) for zstd
+    #[arg(long = "level", value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=22))]
+    pub level: Option<u32>,
+
+    /// Allow a filesystem root or home directory as the source folder
+    #[arg(long = "i-know-what-im-doing")]
+    pub i_know_what_im_doing: bool,
+
+    /// Ask for confirmation above this many files
+    #[arg(long = "max-files", value_name = "N")]
+    pub max_files: Option<usize>,
+
+    /// Ask for confirmation above this total size in GB
+    #[arg(long = "max-size", value_name = "GB")]
+    pub max_size_gb: Option<f64>,
+
+    /// Package even when the file count or size limits are exceeded
+    #[arg(long = "force")]
+    pub force: bool,
+
+    /// Warn (or fail under --strict) when the package may grow past this
+    /// size, e.g. 8GB (default: 30GB, the Intune maximum)
+    #[arg(long = "size-limit", value_name = "SIZE")]
+    pub size_limit: Option<ByteSize>,
+
+    /// Collect and check the content and print what would be packaged,
+    /// without writing anything
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Print the dry run report as JSON
+    #[arg(long = "json", requires = "dry_run")]
+    pub json: bool,
+
+    /// Retry source reads and output writes up to N times after transient
+    /// I/O errors (e.g. on SMB or NFS shares), with backoff
+    #[arg(long = "io-retries", value_name = "N", default_value_t = 0)]
+    pub io_retries: u32,
+
+    /// Re-open the produced package and verify it can be read back
+    #[arg(long = "post-validate")]
+    pub post_validate: bool,
+
+    /// Fail post-validation if it takes longer than this many seconds
+    #[arg(long = "post-validate-timeout", value_name = "SECS", default_value_t = DEFAULT_VALIDATION_TIMEOUT.as_secs(), requires = "post_validate")]
+    pub post_validate_timeout: u64,
+}
+
+/// Parse a `KEY=VALUE` argument.
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", s))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing key in '{}'", s));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Arguments for extracting Intune packages
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneExtractArgs {
+    /// Input .intunewin file to extract
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// Output folder for extracted files
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+
+    /// Write the content into an AES-encrypted `<package>.zip` in the output
+    /// folder instead of loose files (password in IAMAWRAPPER_ZIP_PASSWORD)
+    #[arg(long = "extract-to-zip")]
+    pub extract_to_zip: bool,
+}
+
+/// Environment variable holding the keys for `--keys-from-env`, as JSON.
+pub const ENCRYPTION_KEYS_ENV: &str = "IAMAWRAPPER_ENCRYPTION_KEYS";
+
+/// Environment variable holding the password of an encrypted `--sign-key`.
+pub const SIGN_KEY_PASSWORD_ENV: &str = "IAMAWRAPPER_SIGN_KEY_PASSWORD";
+
+/// Environment variable holding the password of `--extract-to-zip` archives.
+pub const ZIP_PASSWORD_ENV: &str = "IAMAWRAPPER_ZIP_PASSWORD";
+
+/// Arguments for verifying Intune packages
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneVerifyArgs {
+    /// Input .intunewin file to verify
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// Print the result as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for inspecting Intune package metadata
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneInspectArgs {
+    /// The .intunewin file to inspect
+    pub input_file: PathBuf,
+
+    /// Print the encryption and MAC keys instead of redacting them
+    #[arg(long = "show-secrets")]
+    pub show_secrets: bool,
+
+    /// Print the metadata as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for listing the content of an Intune package
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneListArgs {
+    /// The .intunewin file to list
+    pub input_file: PathBuf,
+
+    /// Print the file table as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for comparing Intune packages
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneDiffArgs {
+    /// Package to compare against (e.g., the version in production)
+    pub old: PathBuf,
+
+    /// Package to compare
+    pub new: PathBuf,
+
+    /// Print the differences as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for analyzing a source folder
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneAnalyzeArgs {
+    /// Source folder that would be packaged
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Setup file name within source folder
+    #[arg(short = 's', long = "setup")]
+    pub setup_file: String,
+
+    /// Only analyze files matching the pattern (repeatable, e.g., *.dll)
+    #[arg(long = "include", value_name = "GLOB", value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+
+    /// Leave out files matching the pattern (repeatable, e.g., *.pdb)
+    #[arg(long = "exclude", value_name = "GLOB", value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
+
+    /// Number of largest files to list
+    #[arg(long = "top", value_name = "N", default_value_t = DEFAULT_TOP_FILES)]
+    pub top: usize,
+
+    /// Print the analysis as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for generating PowerShell wrappers
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneScaffoldArgs {
+    /// Source folder receiving Install.ps1 and Uninstall.ps1
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// The .exe installer within the source folder
+    #[arg(short = 's', long = "setup")]
+    pub setup_file: String,
+
+    /// Arguments for a silent install (e.g., "/S")
+    #[arg(
+        long = "install-args",
+        value_name = "ARGS",
+        default_value = "",
+        allow_hyphen_values = true
+    )]
+    pub install_args: String,
+
+    /// Arguments for a silent uninstall
+    #[arg(
+        long = "uninstall-args",
+        value_name = "ARGS",
+        default_value = "",
+        allow_hyphen_values = true
+    )]
+    pub uninstall_args: String,
+
+    /// Uninstaller, in the source folder or on the device (e.g.,
+    /// "%ProgramFiles%\App\uninst.exe"); defaults to the setup file
+    #[arg(long = "uninstall-file", value_name = "FILE")]
+    pub uninstall_file: Option<String>,
+
+    /// Application name for logs and the tag file (default: setup file name)
+    #[arg(long = "name")]
+    pub name: Option<String>,
+
+    /// Installer exit code to report as success (repeatable; 0 and 1707
+    /// always are)
+    #[arg(
+        long = "success-code",
+        value_name = "CODE",
+        allow_negative_numbers = true
+    )]
+    pub success_codes: Vec<i32>,
+
+    /// Replace existing wrappers
+    #[arg(short = 'f', long = "force")]
+    pub force: bool,
+}
+
+/// Arguments for repacking Intune packages
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneRepackArgs {
+    /// Package to modify
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+
+    /// Output path for the rewritten .intunewin file
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    /// Setup file to record; must be at the root of the package content
+    #[arg(long = "set-setup-file", value_name = "FILE")]
+    pub setup_file: Option<String>,
+
+    /// Application name to record in Detection.xml
+    #[arg(long = "set-name", value_name = "NAME")]
+    pub name: Option<String>,
+}
+
+/// Arguments for packaging every application in a manifest
+#[derive(Parser, Debug, Clone)]
+pub struct BatchArgs {
+    /// Manifest listing the applications (.csv, .json or .toml)
+    pub manifest: PathBuf,
+
+    /// Package this many applications at once (0 = one per CPU)
+    #[arg(short = 'j', long = "jobs", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Output folder for applications whose entry names none
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: Option<PathBuf>,
+
+    /// Print the summary as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+/// Arguments for generating detection rules
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneRulesArgs {
+    /// Input .intunewin file whose setup file the rules are derived from
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// Output JSON file (default: `<package>.detection.json` next to the package)
+    #[arg(short = 'o', long = "output")]
+    pub output_file: Option<PathBuf>,
+
+    /// Detect the app by a file or folder (repeatable, full path)
+    #[arg(long = "file", value_name = "PATH", value_parser = DetectionRule::file)]
+    pub files: Vec<DetectionRule>,
+
+    /// Detect the app by a registry key or value (repeatable)
+    #[arg(
+        long = "registry",
+        value_name = "KEY[:VALUE[=DATA]]",
+        value_parser = DetectionRule::registry
+    )]
+    pub registry: Vec<DetectionRule>,
+}
+
+/// Arguments for generating a Win32 app manifest; unset values come from
+/// the `app` section of `--config`
+#[derive(Parser, Debug, Clone)]
+pub struct IntuneManifestArgs {
+    /// Input .intunewin file the manifest describes
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// Output JSON file (default: `<package>.app.json` next to the package)
+    #[arg(short = 'o', long = "output")]
+    pub output_file: Option<PathBuf>,
+
+    /// Name shown in Intune (default: the MSI's ProductName or the package name)
+    #[arg(long = "display-name")]
+    pub display_name: Option<String>,
+
+    /// Description (default: the display name)
+    #[arg(long = "description")]
+    pub description: Option<String>,
+
+    /// Publisher (default: the MSI's Manufacturer)
+    #[arg(long = "publisher")]
+    pub publisher: Option<String>,
+
+    /// Version shown in Intune (default: the MSI's ProductVersion)
+    #[arg(long = "app-version", value_name = "VERSION")]
+    pub app_version: Option<String>,
+
+    /// Developer
+    #[arg(long = "developer")]
+    pub developer: Option<String>,
+
+    /// Owner
+    #[arg(long = "owner")]
+    pub owner: Option<String>,
+
+    /// Notes
+    #[arg(long = "notes")]
+    pub notes: Option<String>,
+
+    /// Information URL
+    #[arg(long = "information-url", value_name = "URL")]
+    pub information_url: Option<String>,
+
+    /// Privacy URL
+    #[arg(long = "privacy-url", value_name = "URL")]
+    pub privacy_url: Option<String>,
+
+    /// Install command (default for an MSI: msiexec /i)
+    #[arg(long = "install-command", value_name = "COMMAND")]
+    pub install_command: Option<String>,
+
+    /// Uninstall command (default for an MSI: msiexec /x with the product code)
+    #[arg(long = "uninstall-command", value_name = "COMMAND")]
+    pub uninstall_command: Option<String>,
+
+    /// Account the commands run as: system (default) or user
+    #[arg(long = "install-context", value_name = "CONTEXT", value_parser = str::parse::<InstallContext>)]
+    pub install_context: Option<InstallContext>,
+
+    /// Restart behavior: return-code (default), allow, suppress or force
+    #[arg(long = "restart", value_name = "BEHAVIOR", value_parser = str::parse::<RestartBehavior>)]
+    pub restart_behavior: Option<RestartBehavior>,
+
+    /// Minutes the installer may run (default: 60)
+    #[arg(long = "max-run-time", value_name = "MINUTES")]
+    pub max_run_time: Option<u32>,
+
+    /// Architecture the app installs on (repeatable: x86, x64, arm64;
+    /// default: x64 and x86)
+    #[arg(long = "architecture", value_name = "ARCH", value_parser = str::parse::<Architecture>)]
+    pub architectures: Vec<Architecture>,
+
+    /// Minimum Windows release, e.g. 1607 (default) or 21H2
+    #[arg(long = "min-os", value_name = "RELEASE")]
+    pub min_os: Option<String>,
+
+    /// Minimum free disk space in MB
+    #[arg(long = "min-disk-space", value_name = "MB")]
+    pub min_disk_space: Option<u32>,
+
+    /// Minimum memory in MB
+    #[arg(long = "min-memory", value_name = "MB")]
+    pub min_memory: Option<u32>,
+
+    /// Installer exit code and its type (repeatable, replaces the defaults:
+    /// success, soft-reboot, hard-reboot, retry or failed)
+    #[arg(long = "return-code", value_name = "CODE=TYPE", value_parser = ReturnCode::parse)]
+    pub return_codes: Vec<ReturnCode>,
+
+    /// Detect the app by a file or folder (repeatable, full path)
+    #[arg(long = "file", value_name = "PATH", value_parser = DetectionRule::file)]
+    pub files: Vec<DetectionRule>,
+
+    /// Detect the app by a registry key or value (repeatable)
+    #[arg(
+        long = "registry",
+        value_name = "KEY[:VALUE[=DATA]]",
+        value_parser = DetectionRule::registry
+    )]
+    pub registry: Vec<DetectionRule>,
+}
+
+/// Capture subcommand options
+#[derive(Parser, Debug, Clone)]
+pub struct CaptureCommand {
+    #[command(subcommand)]
+    pub action: CaptureAction,
+}
+
+/// Capture actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum CaptureAction {
+    /// Snapshot a directory tree before running an installer
+    Start(CaptureStartArgs),
+    /// Diff the tree against the snapshot and package added/changed files
+    Finish(CaptureFinishArgs),
+}
+
+/// Arguments for starting a capture
+#[derive(Parser, Debug, Clone)]
+pub struct CaptureStartArgs {
+    /// Directory tree to watch (e.g., the vendor's install location)
+    #[arg(short = 'p', long = "path")]
+    pub path: PathBuf,
+
+    /// Snapshot file to write
+    #[arg(long = "state", default_value = DEFAULT_STATE_FILE)]
+    pub state_file: PathBuf,
+
+    /// Allow a filesystem root or home directory as the watched path
+    #[arg(long = "i-know-what-im-doing")]
+    pub i_know_what_im_doing: bool,
+}
+
+/// Arguments for finishing a capture
+#[derive(Parser, Debug, Clone)]
+pub struct CaptureFinishArgs {
+    /// Setup file: an existing file copied into the package root, or the
+    /// path of a captured file relative to the watched directory
+    #[arg(short = 's', long = "setup")]
+    pub setup_file: String,
+
+    /// Output folder for .intunewin file
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "name")]
+    pub output_name: Option<String>,
+
+    /// Snapshot file written by 'capture start'
+    #[arg(long = "state", default_value = DEFAULT_STATE_FILE)]
+    pub state_file: PathBuf,
+}
+
+/// Arguments for exporting a project
+#[derive(Parser, Debug, Clone)]
+pub struct ExportProjectArgs {
+    /// Project file to write
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    #[command(subcommand)]
+    pub target: ExportTarget,
+}
+
+/// Package type recorded in a project
+#[derive(Subcommand, Debug, Clone)]
+pub enum ExportTarget {
+    /// Settings for an .intunewin package
+    Intune(ExportIntuneArgs),
+    /// Settings for a macOS package
+    Macos(ExportMacosArgs),
+}
+
+/// Intune build settings for a project
+#[derive(Parser, Debug, Clone)]
+pub struct ExportIntuneArgs {
+    /// Source folder containing files to package
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Setup file name within source folder
+    #[arg(short = 's', long = "setup")]
+    pub setup_file: String,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "name")]
+    pub output_name: Option<String>,
+
+    /// Tag recorded in the package metadata (repeatable, e.g., build=1234)
+    #[arg(long = "content-tag", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub content_tags: Vec<(String, String)>,
+}
+
+/// macOS build settings for a project
+#[derive(Parser, Debug, Clone)]
+pub struct ExportMacosArgs {
+    /// Source folder containing files to package
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Package identifier (reverse-DNS format, e.g., com.company.app)
+    #[arg(long = "identifier")]
+    pub identifier: String,
+
+    /// Package version (e.g., 1.0.0)
+    #[arg(long = "version")]
+    pub version: String,
+
+    /// Installation location (default: /)
+    #[arg(long = "install-location", default_value = "/")]
+    pub install_location: String,
+
+    /// Scripts folder containing preinstall/postinstall scripts
+    #[arg(long = "scripts")]
+    pub scripts_folder: Option<PathBuf>,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "name")]
+    pub output_name: Option<String>,
+
+    /// Keep setuid/setgid/sticky bits (stripped with a warning otherwise)
+    #[arg(long = "allow-special-modes")]
+    pub allow_special_modes: bool,
+}
+
+/// Arguments for importing a project
+#[derive(Parser, Debug, Clone)]
+pub struct ImportProjectArgs {
+    /// Project file written by 'export-project'
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+
+    /// Folder holding the archived content
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Output folder for the rebuilt package
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+}
+
+/// Arguments for purging leftover workspaces
+#[derive(Parser, Debug, Clone)]
+pub struct CleanWorkspacesArgs {
+    /// Only remove workspaces of runs started at least this many hours ago
+    #[arg(long = "older-than", value_name = "HOURS", default_value_t = 24)]
+    pub older_than_hours: u64,
+
+    /// List the workspaces that would be removed without removing them
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+/// Arguments for printing shell completions
+#[derive(Parser, Debug, Clone)]
+pub struct CompletionsArgs {
+    /// Shell to complete in
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+/// Arguments for writing man pages
+#[derive(Parser, Debug, Clone)]
+pub struct ManpagesArgs {
+    /// Folder for the pages (created if missing)
+    pub dir: PathBuf,
+}
+
+/// Arguments for watching a drop folder
+#[derive(Parser, Debug, Clone)]
+pub struct WatchArgs {
+    /// Drop folder; each subfolder is one application
+    #[arg(short = 'd', long = "drop")]
+    pub drop_folder: PathBuf,
+
+    /// Output folder (or share) for packages and per-job logs
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+
+    /// Seconds between two scans of the drop folder (overrides the config)
+    #[arg(long = "poll", value_name = "SECONDS")]
+    pub poll: Option<u64>,
+
+    /// Seconds a subfolder must stay unchanged before it is packaged
+    /// (overrides the config)
+    #[arg(long = "settle", value_name = "SECONDS")]
+    pub settle: Option<u64>,
+
+    /// Package what is in the drop folder now and exit
+    #[arg(long = "once")]
+    pub once: bool,
+}
+
+impl WatchArgs {
+    /// Build a watch request from the arguments and the config's rules.
+    pub fn to_watch_request(
+        &self,
+        mut rules: WatchRules,
+        verbosity: Verbosity,
+        strictness: &Strictness,
+    ) -> WatchRequest {
+        if let Some(poll) = self.poll {
+            rules.poll = Duration::from_secs(poll.max(1));
+        }
+        if let Some(settle) = self.settle {
+            rules.settle = Duration::from_secs(settle);
+        }
+        WatchRequest::new(&self.drop_folder, &self.output_folder)
+            .with_rules(rules)
+            .with_verbosity(verbosity)
+            .with_strictness(strictness.clone())
+            .with_once(self.once)
+    }
+}
+
+/// Arguments for building the targets of the configuration
+#[derive(Parser, Debug, Clone)]
+pub struct BuildArgs {
+    /// Build only this target: intune or macos (repeatable; default: every
+    /// configured target)
+    #[arg(short = 't', long = "target", value_name = "TARGET", value_parser = str::parse::<TargetKind>)]
+    pub targets: Vec<TargetKind>,
+
+    /// Output folder for targets the configuration gives none
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: Option<PathBuf>,
+
+    /// Print the summary as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+impl BuildArgs {
+    /// Build a context resolving the configuration's paths against `base`.
+    pub fn to_build_context(
+        &self,
+        base: &Path,
+        verbosity: Verbosity,
+        strictness: &Strictness,
+    ) -> BuildContext {
+        let mut context = BuildContext::new(base)
+            .with_targets(self.targets.clone())
+            .with_verbosity(verbosity)
+            .with_strictness(strictness.clone());
+        if let Some(output) = &self.output_folder {
+            context = context.with_output_folder(output);
+        }
+        context
+    }
+}
+
+/// Arguments for inspecting a package
+#[derive(Parser, Debug, Clone)]
+pub struct InspectArgs {
+    /// Package file (.intunewin or .pkg)
+    #[arg(
+        value_name = "FILE",
+        required_unless_present = "input_option",
+        conflicts_with = "input_option"
+    )]
+    pub input: Option<PathBuf>,
+
+    /// Package file, as an option (same as FILE)
+    #[arg(short = 'i', long = "input", value_name = "FILE", hide = true)]
+    pub input_option: Option<PathBuf>,
+
+    /// Print the report as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+impl InspectArgs {
+    /// The package file, given positionally or with `-i`.
+    pub fn input(&self) -> &Path {
+        self.input
+            .as_deref()
+            .or(self.input_option.as_deref())
+            .expect("clap requires an input file")
+    }
+}
+
+/// Arguments for checking the signature of a package
+#[derive(Parser, Debug, Clone)]
+pub struct VerifySignatureArgs {
+    /// Signed package file
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+
+    /// Minisign public key file
+    #[arg(short = 'p', long = "public-key", value_name = "FILE")]
+    pub public_key: PathBuf,
+
+    /// Signature file [default: <input>.minisig]
+    #[arg(short = 'x', long = "signature", value_name = "FILE")]
+    pub signature: Option<PathBuf>,
+}
+
+/// macOS subcommand options
+#[derive(Parser, Debug, Clone)]
+pub struct MacosCommand {
+    #[command(subcommand)]
+    pub action: MacosAction,
+}
+
+/// macOS actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum MacosAction {
+    /// Create a macOS flat package (.pkg)
+    Pkg(Box<MacosPkgArgs>),
+    /// Build a disk image (.dmg) from a folder
+    Dmg(MacosDmgArgs),
+    /// Change the title, license or resources of an existing .pkg
+    Repack(MacosRepackArgs),
+    /// Add or replace the install scripts of an existing .pkg
+    Rewrap(MacosRewrapArgs),
+    /// Extract an existing .pkg (payload, scripts and metadata)
+    Extract(MacosExtractArgs),
+    /// Check an existing .pkg: archive, payloads, Bom and versions
+    Validate(MacosValidateArgs),
+    /// Notarize a signed .pkg with Apple and staple the ticket to it
+    Notarize(MacosNotarizeArgs),
+    /// Package every application listed in a manifest
+    Batch(BatchArgs),
+}
+
+/// Arguments for creating macOS packages (T030)
+#[derive(Parser, Debug, Clone)]
+pub struct MacosPkgArgs {
+    /// Source folder containing files to package, a .zip, .tar, .tar.gz or
+    /// .tgz archive of it, or the URL of one (https://host/app.zip#sha256=HEX)
+    #[arg(
+        short = 'c',
+        long = "content",
+        required_unless_present_any = ["component", "arch_payloads", "no_payload"]
+    )]
+    pub content_folder: Option<PathBuf>,
+
+    /// Application bundle (.app) to package as a component
+    #[arg(
+        long = "component",
+        value_name = "APP",
+        conflicts_with = "content_folder"
+    )]
+    pub component: Option<PathBuf>,
+
+    /// Payload folder built for one architecture (repeatable, e.g.,
+    /// arm64=build/arm64); each becomes a component installed only on
+    /// Macs of that architecture
+    #[arg(
+        long = "arch-payload",
+        value_name = "ARCH=DIR",
+        conflicts_with_all = ["content_folder", "component", "sbom", "embed_sbom"]
+    )]
+    pub arch_payloads: Vec<String>,
+
+    /// Build a payload-free package that only runs its scripts, like
+    /// `pkgbuild --nopayload`
+    #[arg(
+        long = "nopayload",
+        conflicts_with_all = ["content_folder", "component", "arch_payloads", "sbom", "embed_sbom"]
+    )]
+    pub no_payload: bool,
+
+    /// Output path for .pkg file (the output folder with --name-template)
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    /// Output filename template, e.g. "{identifier}-{version}-{git_sha}"
+    /// (variables: identifier, version, date, git_sha)
+    #[arg(
+        long = "name-template",
+        value_name = "TEMPLATE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub name_template: Option<NameTemplate>,
+
+    /// Package identifier (reverse-DNS format, e.g., com.company.app;
+    /// default for --component: CFBundleIdentifier)
+    #[arg(long = "identifier", required_unless_present = "component")]
+    pub identifier: Option<String>,
+
+    /// Package version (e.g., 1.0.0; default for --component:
+    /// CFBundleShortVersionString)
+    #[arg(long = "version", required_unless_present = "component")]
+    pub version: Option<String>,
+
+    /// Installation location (default: /, or /Applications for --component)
+    #[arg(long = "install-location")]
+    pub install_location: Option<String>,
+
+    /// Scripts folder containing preinstall/postinstall scripts
+    #[arg(long = "scripts")]
+    pub scripts_folder: Option<PathBuf>,
+
+    /// Preinstall script file, or '-' to read it from stdin
+    #[arg(long = "preinstall-script", value_name = "FILE")]
+    pub preinstall_script: Option<PathBuf>,
+
+    /// Postinstall script file, or '-' to read it from stdin
+    #[arg(long = "postinstall-script", value_name = "FILE")]
+    pub postinstall_script: Option<PathBuf>,
+
+    /// Run a bundled script before installation (repeatable: kill-app,
+    /// kill-app=NAME or remove-previous)
+    #[arg(long = "script-template", value_name = "TEMPLATE")]
+    pub script_templates: Vec<String>,
+
+    /// Known-bad file hash list (CSV or JSON) to check content against
+    #[arg(long = "blocklist")]
+    pub blocklist: Option<PathBuf>,
+
+    /// Warn instead of failing when a blocklisted file is found
+    #[arg(long = "blocklist-warn", requires = "blocklist")]
+    pub blocklist_warn: bool,
+
+    /// Keep setuid/setgid/sticky bits (stripped with a warning otherwise)
+    #[arg(long = "allow-special-modes")]
+    pub allow_special_modes: bool,
+
+    /// Rewrite matching files as they are packaged (repeatable, e.g., *.sh=lf)
+    #[arg(long = "transform", value_name = "GLOB=TRANSFORM", value_parser = TransformRule::parse)]
+    pub transforms: Vec<TransformRule>,
+
+    /// Only package files matching the pattern (repeatable, e.g., *.dll)
+    #[arg(long = "include", value_name = "GLOB", value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+
+    /// Leave out files matching the pattern (repeatable, e.g., *.pdb)
+    #[arg(long = "exclude", value_name = "GLOB", value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
+
+    /// Compress content as stored or deflate (default: deflate)
+    #[arg(long = "compression", value_name = "METHOD", value_parser = str::parse::<CompressionMethod>)]
+    pub compression: Option<CompressionMethod>,
+
+    /// Deflate level from 0 (fastest) to 9 (smallest; default: 6)
+    #[arg(long = "level", value_name = "0-9", value_parser = clap::value_parser!(u32).range(0..=9))]
+    pub level: Option<u32>,
+
+    /// Write an SBOM of the payload to `<package>.cdx.json` or `.spdx.json`
+    #[arg(long = "sbom", value_name = "FORMAT", value_parser = str::parse::<SbomFormat>)]
+    pub sbom: Option<SbomFormat>,
+
+    /// Store the SBOM in the package next to Distribution (CycloneDX unless
+    /// --sbom names a format)
+    #[arg(long = "embed-sbom")]
+    pub embed_sbom: bool,
+
+    /// Allow a filesystem root or home directory as the source folder
+    #[arg(long = "i-know-what-im-doing")]
+    pub i_know_what_im_doing: bool,
+
+    /// Ask for confirmation above this many files
+    #[arg(long = "max-files", value_name = "N")]
+    pub max_files: Option<usize>,
+
+    /// Ask for confirmation above this total size in GB
+    #[arg(long = "max-size", value_name = "GB")]
+    pub max_size_gb: Option<f64>,
+
+    /// Package even when the file count or size limits are exceeded
+    #[arg(long = "force")]
+    pub force: bool,
+
+    /// Collect and check the payload and print what would be packaged,
+    /// without writing anything
+    #[arg(long = "dry-run", conflicts_with = "arch_payloads")]
+    pub dry_run: bool,
+
+    /// Print the dry run report as JSON
+    #[arg(long = "json", requires = "dry_run")]
+    pub json: bool,
+
+    /// Retry source reads and output writes up to N times after transient
+    /// I/O errors (e.g. on SMB or NFS shares), with backoff
+    #[arg(long = "io-retries", value_name = "N", default_value_t = 0)]
+    pub io_retries: u32,
+
+    /// Re-open the produced package and verify it can be read back
+    #[arg(long = "post-validate")]
+    pub post_validate: bool,
+
+    /// Fail post-validation if it takes longer than this many seconds
+    #[arg(long = "post-validate-timeout", value_name = "SECS", default_value_t = DEFAULT_VALIDATION_TIMEOUT.as_secs(), requires = "post_validate")]
+    pub post_validate_timeout: u64,
+
+    /// Sign with this identity: a PKCS#12 file (password in
+    /// IAMAWRAPPER_SIGN_PASSWORD) or a PEM private key
+    #[arg(long = "sign-identity", value_name = "FILE")]
+    pub sign_identity: Option<PathBuf>,
+
+    /// PEM certificate chain, leaf first, for a PEM --sign-identity
+    #[arg(long = "sign-cert", value_name = "FILE", requires = "sign_identity")]
+    pub sign_cert: Option<PathBuf>,
+
+    /// Title the Installer shows (default: the identifier)
+    #[arg(long = "title")]
+    pub title: Option<String>,
+
+    /// Welcome text (RTF, TXT or HTML) shown first by the Installer
+    #[arg(
+        long = "welcome",
+        value_name = "FILE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub welcome: Option<PathBuf>,
+
+    /// License (RTF, TXT or HTML) the user has to agree to
+    #[arg(
+        long = "license",
+        value_name = "FILE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub license: Option<PathBuf>,
+
+    /// Read Me (RTF, TXT or HTML) shown before installing
+    #[arg(long = "readme", value_name = "FILE", conflicts_with = "arch_payloads")]
+    pub readme: Option<PathBuf>,
+
+    /// Conclusion (RTF, TXT or HTML) shown once the install is done
+    #[arg(
+        long = "conclusion",
+        value_name = "FILE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub conclusion: Option<PathBuf>,
+
+    /// Image drawn in the bottom left corner of the Installer's window
+    #[arg(
+        long = "background",
+        value_name = "FILE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub background: Option<PathBuf>,
+
+    /// Folder copied into the package's Resources; files named welcome,
+    /// license, readme, conclusion or background (any extension, also in
+    /// `<language>.lproj` folders) are shown unless given on their own
+    #[arg(
+        long = "resources",
+        value_name = "DIR",
+        conflicts_with = "arch_payloads"
+    )]
+    pub resources: Option<PathBuf>,
+
+    /// Oldest macOS version the package installs on (e.g., 12.0)
+    #[arg(
+        long = "min-os-version",
+        value_name = "VERSION",
+        conflicts_with = "arch_payloads"
+    )]
+    pub min_os_version: Option<String>,
+
+    /// Whether users can customize the install: never (default), allow or
+    /// always
+    #[arg(
+        long = "customize",
+        value_name = "MODE",
+        conflicts_with = "arch_payloads"
+    )]
+    pub customize: Option<String>,
+
+    /// Where the package can be installed (repeatable: system, user or
+    /// anywhere; default: system)
+    #[arg(
+        long = "domain",
+        value_name = "DOMAIN",
+        conflicts_with = "arch_payloads"
+    )]
+    pub domains: Vec<String>,
+}
+
+/// Environment variable holding the password of a PKCS#12 signing identity.
+pub const SIGN_PASSWORD_ENV: &str = "IAMAWRAPPER_SIGN_PASSWORD";
+
+/// Arguments for repacking an existing macOS package
+#[derive(Parser, Debug, Clone)]
+pub struct MacosRepackArgs {
+    /// Package to modify
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+
+    /// Output path for the rewritten .pkg file
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    /// Installer title shown in the macOS Installer
+    #[arg(long = "set-title", value_name = "TITLE")]
+    pub title: Option<String>,
+
+    /// License file (RTF, TXT or HTML) shown before installation
+    #[arg(long = "license", value_name = "FILE")]
+    pub license: Option<PathBuf>,
+
+    /// Add or replace a file in the package Resources (repeatable)
+    #[arg(long = "resource", value_name = "FILE")]
+    pub resources: Vec<PathBuf>,
+
+    /// Carry an existing signature over instead of stripping it
+    #[arg(long = "keep-signature")]
+    pub keep_signature: bool,
+
+    /// Sign the result with this identity (see `macos pkg --sign-identity`)
+    #[arg(
+        long = "sign-identity",
+        value_name = "FILE",
+        conflicts_with = "keep_signature"
+    )]
+    pub sign_identity: Option<PathBuf>,
+
+    /// PEM certificate chain, leaf first, for a PEM --sign-identity
+    #[arg(long = "sign-cert", value_name = "FILE", requires = "sign_identity")]
+    pub sign_cert: Option<PathBuf>,
+}
+
+/// Arguments for rewrapping an existing macOS package
+#[derive(Parser, Debug, Clone)]
+pub struct MacosRewrapArgs {
+    /// Package to modify
+    #[arg(short = 'i', long = "input")]
+    pub input: PathBuf,
+
+    /// Folder of scripts (preinstall, postinstall, helpers) to add or replace
+    #[arg(long = "scripts", value_name = "FOLDER")]
+    pub scripts_folder: PathBuf,
+
+    /// Output path for the rewritten .pkg file [default: <input>-rewrapped.pkg]
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Identifier of the component to change, when the package has several
+    #[arg(long = "component", value_name = "IDENTIFIER")]
+    pub component: Option<String>,
+
+    /// Sign the result with this identity (see `macos pkg --sign-identity`)
+    #[arg(long = "sign-identity", value_name = "FILE")]
+    pub sign_identity: Option<PathBuf>,
+
+    /// PEM certificate chain, leaf first, for a PEM --sign-identity
+    #[arg(long = "sign-cert", value_name = "FILE", requires = "sign_identity")]
+    pub sign_cert: Option<PathBuf>,
+}
+
+/// Arguments for extracting macOS packages
+#[derive(Parser, Debug, Clone)]
+pub struct MacosExtractArgs {
+    /// Input .pkg file to extract
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// Output folder for extracted files
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+}
+
+/// Arguments for validating macOS packages
+#[derive(Parser, Debug, Clone)]
+pub struct MacosValidateArgs {
+    /// Input .pkg file to validate
+    #[arg(
+        value_name = "FILE",
+        required_unless_present = "input_option",
+        conflicts_with = "input_option"
+    )]
+    pub input_file: Option<PathBuf>,
+
+    /// Input .pkg file, as an option (same as FILE)
+    #[arg(short = 'i', long = "input", value_name = "FILE", hide = true)]
+    pub input_option: Option<PathBuf>,
+
+    /// Print the report as JSON
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+impl MacosValidateArgs {
+    /// The package file, given positionally or with `-i`.
+    pub fn input(&self) -> &Path {
+        self.input_file
+            .as_deref()
+            .or(self.input_option.as_deref())
+            .expect("clap requires an input file")
+    }
+}
+
+/// Arguments for building disk images
+#[derive(Parser, Debug, Clone)]
+pub struct MacosDmgArgs {
+    /// Folder whose contents become the root of the volume
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Output path for .dmg file
+    #[arg(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    /// Name the volume mounts as (default: the content folder's name)
+    #[arg(long = "volume-name", value_name = "NAME")]
+    pub volume_name: Option<String>,
+
+    /// Image format: UDZO (compressed) or UDRO (uncompressed)
+    #[arg(long = "format", value_name = "FORMAT", default_value = "UDZO")]
+    pub format: String,
+
+    /// Plain-text license to accept before the image mounts
+    #[arg(long = "license", value_name = "FILE")]
+    pub license: Option<PathBuf>,
+}
+
+/// Arguments for notarizing macOS packages
+#[derive(Parser, Debug, Clone)]
+pub struct MacosNotarizeArgs {
+    /// Signed .pkg file to notarize; the ticket is stapled to it in place
+    #[arg(short = 'i', long = "input")]
+    pub input_file: PathBuf,
+
+    /// App Store Connect API key ID
+    #[arg(long = "key-id", value_name = "ID")]
+    pub key_id: String,
+
+    /// App Store Connect issuer ID
+    #[arg(long = "issuer", value_name = "UUID")]
+    pub issuer_id: String,
+
+    /// App Store Connect API private key (`AuthKey_<id>.p8`)
+    #[arg(long = "key", value_name = "FILE")]
+    pub key: PathBuf,
+
+    /// Give up waiting for Apple's verdict after this many minutes
+    #[arg(long = "timeout", value_name = "MINUTES", default_value_t = 60)]
+    pub timeout_minutes: u64,
+
+    /// Leave the package as it is once accepted
+    #[arg(long = "no-staple")]
+    pub no_staple: bool,
+}
+
+/// Chocolatey subcommand options
+#[derive(Parser, Debug, Clone)]
+pub struct ChocoCommand {
+    #[command(subcommand)]
+    pub action: ChocoAction,
 }
 
-/// Arguments for creating Intune packages
+/// Chocolatey actions
+#[derive(Subcommand, Debug, Clone)]
+pub enum ChocoAction {
+    /// Create a Chocolatey package (.nupkg)
+    Pack(ChocoPackArgs),
+}
+
+/// Arguments for creating Chocolatey packages
 #[derive(Parser, Debug, Clone)]
-pub struct IntuneCreateArgs {
+pub struct ChocoPackArgs {
     /// Source folder containing files to package
     #[arg(short = 'c', long = "content")]
     pub content_folder: PathBuf,
 
-    /// Setup file name within source folder
+    /// Installer run by chocolateyInstall.ps1 (.exe, .msi, .msu or .ps1)
     #[arg(short = 's', long = "setup")]
     pub setup_file: String,
 
-    /// Output folder for .intunewin file
+    /// Output folder for the .nupkg file
     #[arg(short = 'o', long = "output")]
     pub output_folder: PathBuf,
 
-    /// Custom output filename (optional, without extension)
-    #[arg(short = 'n', long = "name")]
-    pub output_name: Option<String>,
-}
+    /// Package id (e.g., myapp)
+    #[arg(long = "id")]
+    pub id: String,
 
-/// Arguments for extracting Intune packages
-#[derive(Parser, Debug, Clone)]
-pub struct IntuneExtractArgs {
-    /// Input .intunewin file to extract
-    #[arg(short = 'i', long = "input")]
-    pub input_file: PathBuf,
+    /// Package version (e.g., 1.2.0)
+    #[arg(long = "version")]
+    pub version: String,
 
-    /// Output folder for extracted files
-    #[arg(short = 'o', long = "output")]
-    pub output_folder: PathBuf,
+    /// Display title (default: the id)
+    #[arg(long = "title")]
+    pub title: Option<String>,
+
+    /// Package authors (default: Unknown)
+    #[arg(long = "authors")]
+    pub authors: Option<String>,
+
+    /// Package description (default: the title)
+    #[arg(long = "description")]
+    pub description: Option<String>,
+
+    /// Arguments for a silent install (default: /qn /norestart for MSIs)
+    #[arg(long = "silent-args", value_name = "ARGS", allow_hyphen_values = true)]
+    pub silent_args: Option<String>,
+
+    /// Only package files matching the pattern (repeatable, e.g., *.dll)
+    #[arg(long = "include", value_name = "GLOB", value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+
+    /// Leave out files matching the pattern (repeatable, e.g., *.pdb)
+    #[arg(long = "exclude", value_name = "GLOB", value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
 }
 
-/// macOS subcommand options
+/// Linux subcommand options
 #[derive(Parser, Debug, Clone)]
-pub struct MacosCommand {
+pub struct LinuxCommand {
     #[command(subcommand)]
-    pub action: MacosAction,
+    pub action: LinuxAction,
 }
 
-/// macOS actions
+/// Linux actions
 #[derive(Subcommand, Debug, Clone)]
-pub enum MacosAction {
-    /// Create a macOS flat package (.pkg)
-    Pkg(MacosPkgArgs),
+pub enum LinuxAction {
+    /// Create a Debian binary package (.deb)
+    Deb(LinuxDebArgs),
+    /// Create an RPM binary package (.rpm)
+    Rpm(LinuxRpmArgs),
 }
 
-/// Arguments for creating macOS packages (T030)
+/// Arguments for creating Debian packages
 #[derive(Parser, Debug, Clone)]
-pub struct MacosPkgArgs {
+pub struct LinuxDebArgs {
     /// Source folder containing files to package
     #[arg(short = 'c', long = "content")]
     pub content_folder: PathBuf,
 
-    /// Output path for .pkg file
+    /// Output folder for the .deb file
     #[arg(short = 'o', long = "output")]
-    pub output: PathBuf,
+    pub output_folder: PathBuf,
 
-    /// Package identifier (reverse-DNS format, e.g., com.company.app)
-    #[arg(long = "identifier")]
-    pub identifier: String,
+    /// Package name (e.g., contoso-agent)
+    #[arg(long = "name")]
+    pub name: String,
 
-    /// Package version (e.g., 1.0.0)
+    /// Package version (e.g., 1.2.0-1)
     #[arg(long = "version")]
     pub version: String,
 
+    /// Debian architecture (e.g., amd64, arm64; default: all)
+    #[arg(long = "arch")]
+    pub architecture: Option<String>,
+
     /// Installation location (default: /)
     #[arg(long = "install-location", default_value = "/")]
-    pub install_location: String,
+    pub install_location: PathBuf,
 
-    /// Scripts folder containing preinstall/postinstall scripts
+    /// Maintainer (e.g., "Contoso IT <it@contoso.com>")
+    #[arg(long = "maintainer")]
+    pub maintainer: Option<String>,
+
+    /// Package description; the first line is the synopsis (default: the name)
+    #[arg(long = "description")]
+    pub description: Option<String>,
+
+    /// Dependency relation (repeatable, e.g., "libc6 (>= 2.31)")
+    #[arg(long = "depends", value_name = "RELATION")]
+    pub depends: Vec<String>,
+
+    /// Archive section (e.g., admin)
+    #[arg(long = "section")]
+    pub section: Option<String>,
+
+    /// Priority (e.g., optional)
+    #[arg(long = "priority")]
+    pub priority: Option<String>,
+
+    /// Homepage URL
+    #[arg(long = "homepage")]
+    pub homepage: Option<String>,
+
+    /// Scripts folder containing preinst/postinst/prerm/postrm scripts
+    #[arg(long = "scripts")]
+    pub scripts_folder: Option<PathBuf>,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "output-name")]
+    pub output_name: Option<String>,
+
+    /// Keep setuid/setgid/sticky bits (stripped with a warning otherwise)
+    #[arg(long = "allow-special-modes")]
+    pub allow_special_modes: bool,
+
+    /// Allow a filesystem root or home directory as the source folder
+    #[arg(long = "i-know-what-im-doing")]
+    pub i_know_what_im_doing: bool,
+}
+
+/// Arguments for creating RPM packages
+#[derive(Parser, Debug, Clone)]
+pub struct LinuxRpmArgs {
+    /// Source folder containing files to package
+    #[arg(short = 'c', long = "content")]
+    pub content_folder: PathBuf,
+
+    /// Output folder for the .rpm file
+    #[arg(short = 'o', long = "output")]
+    pub output_folder: PathBuf,
+
+    /// Package name (e.g., contoso-agent)
+    #[arg(long = "name")]
+    pub name: String,
+
+    /// Package version (e.g., 1.2.0)
+    #[arg(long = "version")]
+    pub version: String,
+
+    /// Package release (default: 1)
+    #[arg(long = "release")]
+    pub release: Option<String>,
+
+    /// Package epoch
+    #[arg(long = "epoch")]
+    pub epoch: Option<u32>,
+
+    /// RPM architecture (e.g., x86_64, aarch64; default: noarch)
+    #[arg(long = "arch")]
+    pub architecture: Option<String>,
+
+    /// Installation location (default: /)
+    #[arg(long = "install-location", default_value = "/")]
+    pub install_location: PathBuf,
+
+    /// One-line summary (default: the name)
+    #[arg(long = "summary")]
+    pub summary: Option<String>,
+
+    /// Package description (default: the summary)
+    #[arg(long = "description")]
+    pub description: Option<String>,
+
+    /// License (default: Unspecified)
+    #[arg(long = "license")]
+    pub license: Option<String>,
+
+    /// Project URL
+    #[arg(long = "url")]
+    pub url: Option<String>,
+
+    /// Requirement (repeatable, e.g., "glibc >= 2.17")
+    #[arg(long = "requires", value_name = "RELATION")]
+    pub requires: Vec<String>,
+
+    /// Scripts folder containing pre/post/preun/postun scriptlets
     #[arg(long = "scripts")]
     pub scripts_folder: Option<PathBuf>,
+
+    /// Custom output filename (optional, without extension)
+    #[arg(short = 'n', long = "output-name")]
+    pub output_name: Option<String>,
+
+    /// Keep setuid/setgid/sticky bits (stripped with a warning otherwise)
+    #[arg(long = "allow-special-modes")]
+    pub allow_special_modes: bool,
+
+    /// Allow a filesystem root or home directory as the source folder
+    #[arg(long = "i-know-what-im-doing")]
+    pub i_know_what_im_doing: bool,
+}
+
+/// Map the `--blocklist-warn` flag to a blocklist action.
+pub fn blocklist_action(warn: bool) -> BlocklistAction {
+    if warn {
+        BlocklistAction::Warn
+    } else {
+        BlocklistAction::Fail
+    }
+}
+
+/// Build SBOM options from `--sbom` and `--embed-sbom`; embedding alone
+/// uses the default format.
+pub fn sbom_options(format: Option<SbomFormat>, embed: bool) -> Option<SbomOptions> {
+    match format {
+        Some(format) => Some(SbomOptions::sidecar(format).with_embed(embed)),
+        None if embed => Some(SbomOptions::embedded(SbomFormat::default())),
+        None => None,
+    }
+}
+
+/// Build compression settings from `--compression` and `--level`; a level
+/// alone implies deflate.
+pub fn compression(method: Option<CompressionMethod>, level: Option<u32>) -> Compression {
+    Compression {
+        method: method.unwrap_or_default(),
+        level,
+    }
+}
+
+/// Build source limits from the optional CLI overrides.
+pub fn source_limits(max_files: Option<usize>, max_size_gb: Option<f64>) -> SourceLimits {
+    let mut limits = SourceLimits::default();
+    if let Some(max_files) = max_files {
+        limits.max_files = max_files;
+    }
+    if let Some(gb) = max_size_gb {
+        limits.max_bytes = ByteSize::from_gib(gb).bytes();
+    }
+    limits
 }
 
 // Legacy CLI support - keep existing flat structure for backwards compatibility
@@ -170,6 +1737,21 @@ impl CliArgs {
             Verbosity::Normal
         }
     }
+
+    /// The config file, or the defaults without `--config`.
+    pub fn config(&self) -> PackageResult<Config> {
+        match &self.config {
+            Some(path) => Config::load(path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Strictness from the config file, with `--strict` switching it on.
+    pub fn strictness(&self) -> PackageResult<Strictness> {
+        let mut strictness = self.config()?.strictness;
+        strictness.strict |= self.strict;
+        Ok(strictness)
+    }
 }
 
 impl LegacyCliArgs {
@@ -260,21 +1842,138 @@ impl LegacyCliArgs {
 impl IntuneCreateArgs {
     /// Convert to package request.
     pub fn to_package_request(&self, verbosity: Verbosity) -> PackageRequest {
+        let content_folder = self
+            .content_folder
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(STDIN_TAR_LABEL));
         let mut request = PackageRequest::new(
-            self.content_folder.clone(),
+            content_folder,
             self.setup_file.clone(),
             self.output_folder.clone(),
         )
-        .with_verbosity(verbosity);
+        .with_verbosity(verbosity)
+        .with_allow_dangerous_source(self.i_know_what_im_doing)
+        .with_source_limits(source_limits(self.max_files, self.max_size_gb))
+        .with_force(self.force)
+        .with_setup_manifest(self.setup_manifest)
+        .with_compression(compression(self.compression, self.level))
+        .with_encryption(
+            EncryptionOptions::default()
+                .with_digest_algorithm(self.digest_algorithm.unwrap_or_default()),
+        )
+        .with_io_retry(RetryPolicy::io(self.io_retries));
+
+        if let Some(limit) = self.size_limit {
+            request.set_size_limit(limit);
+        }
+        if self.post_validate {
+            request = request.with_post_validate(Duration::from_secs(self.post_validate_timeout));
+        }
 
         if let Some(name) = &self.output_name {
             request = request.with_output_name(name.clone());
         }
+        request.set_name_template(self.name_template.clone());
+        request.set_display_name(self.display_name.clone());
+        request.set_tool_version(self.tool_version.clone());
+
+        if let Some(dir) = &self.cache_dir {
+            request = request.with_cache(CacheConfig::new(dir));
+        }
+        if let Some(path) = &self.state_file {
+            request = request.with_incremental(
+                IncrementalOptions::new(path).with_skip_unchanged(self.skip_unchanged),
+            );
+        }
+        request.set_path_report(self.path_report.clone());
+
+        if let Some(sbom) = sbom_options(self.sbom, self.embed_sbom) {
+            request = request.with_sbom(sbom);
+        }
+
+        if let Some(blocklist) = &self.blocklist {
+            request =
+                request.with_blocklist(blocklist.clone(), blocklist_action(self.blocklist_warn));
+        }
+
+        for (key, value) in &self.content_tags {
+            request = request.with_content_tag(key.clone(), value.clone());
+        }
+
+        if let Some(relationships) = &self.relationships {
+            request = request.with_relationships(relationships.clone());
+        }
+
+        for rule in &self.transforms {
+            request = request.with_transform(rule.clone());
+        }
+
+        for glob in &self.include {
+            request = request.with_include(glob.clone());
+        }
+
+        for glob in &self.exclude {
+            request = request.with_exclude(glob.clone());
+        }
+
+        if self.reproducible {
+            request = request.with_reproducible(match &self.reproducible_seed {
+                Some(seed) => Reproducible::Seed(seed.clone().into()),
+                None => Reproducible::ContentDigest,
+            });
+        }
 
         request
     }
 }
 
+impl ExportTarget {
+    /// Content folder and build settings to record.
+    pub fn to_build(&self) -> (&PathBuf, ProjectBuild) {
+        match self {
+            ExportTarget::Intune(args) => (
+                &args.content_folder,
+                ProjectBuild::Intune {
+                    setup_file: args.setup_file.clone(),
+                    output_name: args.output_name.clone(),
+                    content_tags: args.content_tags.clone(),
+                },
+            ),
+            ExportTarget::Macos(args) => (
+                &args.content_folder,
+                ProjectBuild::Macos {
+                    identifier: args.identifier.clone(),
+                    version: args.version.clone(),
+                    install_location: args.install_location.clone(),
+                    output_name: args.output_name.clone(),
+                    allow_special_modes: args.allow_special_modes,
+                },
+            ),
+        }
+    }
+
+    /// Scripts folder to bundle, if any.
+    pub fn scripts_folder(&self) -> Option<&PathBuf> {
+        match self {
+            ExportTarget::Intune(_) => None,
+            ExportTarget::Macos(args) => args.scripts_folder.as_ref(),
+        }
+    }
+}
+
+impl ImportProjectArgs {
+    /// Convert to project import request.
+    pub fn to_import_request(&self, verbosity: Verbosity, strict: bool) -> ProjectImportRequest {
+        ProjectImportRequest::new(
+            self.input.clone(),
+            self.content_folder.clone(),
+            self.output_folder.clone(),
+        )
+        .with_strict(strict)
+        .with_verbosity(verbosity)
+    }
+}
+
 impl IntuneExtractArgs {
     /// Convert to unpack request.
     pub fn to_unpack_request(&self, verbosity: Verbosity) -> UnpackRequest {
@@ -283,6 +1982,257 @@ impl IntuneExtractArgs {
     }
 }
 
+impl IntuneAnalyzeArgs {
+    /// Convert to analyze request.
+    pub fn to_analyze_request(&self) -> AnalyzeRequest {
+        let mut request = AnalyzeRequest::new(self.content_folder.clone(), self.setup_file.clone())
+            .with_top(self.top);
+        for glob in &self.include {
+            request = request.with_include(glob.clone());
+        }
+        for glob in &self.exclude {
+            request = request.with_exclude(glob.clone());
+        }
+        request
+    }
+}
+
+impl IntuneScaffoldArgs {
+    /// Convert to scaffold request.
+    pub fn to_scaffold_request(&self) -> ScaffoldRequest {
+        let mut request = ScaffoldRequest::new(
+            self.content_folder.clone(),
+            self.setup_file.clone(),
+            self.install_args.clone(),
+        )
+        .with_uninstall(self.uninstall_file.clone(), self.uninstall_args.clone())
+        .with_overwrite(self.force);
+        if let Some(name) = &self.name {
+            request = request.with_app_name(name.clone());
+        }
+        for code in &self.success_codes {
+            request = request.with_success_code(*code);
+        }
+        request
+    }
+}
+
+impl IntuneRepackArgs {
+    /// Convert to repack request.
+    pub fn to_repack_request(&self, verbosity: Verbosity) -> RepackRequest {
+        let mut request =
+            RepackRequest::new(self.input.clone(), self.output.clone()).with_verbosity(verbosity);
+        if let Some(setup_file) = &self.setup_file {
+            request = request.with_setup_file(setup_file.clone());
+        }
+        if let Some(name) = &self.name {
+            request = request.with_name(name.clone());
+        }
+        request
+    }
+}
+
+impl IntuneRulesArgs {
+    /// Convert to detection rules request.
+    pub fn to_rules_request(&self) -> DetectionRulesRequest {
+        let mut request = DetectionRulesRequest::new(self.input_file.clone());
+        for rule in self.files.iter().chain(&self.registry) {
+            request.add_rule(rule.clone());
+        }
+        if let Some(output_file) = &self.output_file {
+            request.set_output_file(output_file);
+        }
+        request
+    }
+}
+
+impl IntuneManifestArgs {
+    /// Convert to a manifest request, with the flags overriding the
+    /// configured app settings.
+    pub fn to_manifest_request(&self, mut settings: AppSettings) -> AppManifestRequest {
+        settings.merge(AppSettings {
+            display_name: self.display_name.clone(),
+            description: self.description.clone(),
+            publisher: self.publisher.clone(),
+            version: self.app_version.clone(),
+            developer: self.developer.clone(),
+            owner: self.owner.clone(),
+            notes: self.notes.clone(),
+            information_url: self.information_url.clone(),
+            privacy_url: self.privacy_url.clone(),
+            install_command: self.install_command.clone(),
+            uninstall_command: self.uninstall_command.clone(),
+            install_context: self.install_context,
+            restart_behavior: self.restart_behavior,
+            max_run_time_minutes: self.max_run_time,
+            architectures: self.architectures.clone(),
+            minimum_windows_release: self.min_os.clone(),
+            minimum_disk_space_mb: self.min_disk_space,
+            minimum_memory_mb: self.min_memory,
+            return_codes: self.return_codes.clone(),
+        });
+
+        let mut request = AppManifestRequest::new(self.input_file.clone()).with_settings(settings);
+        for rule in self.files.iter().chain(&self.registry) {
+            request.add_rule(rule.clone());
+        }
+        if let Some(output_file) = &self.output_file {
+            request.set_output_file(output_file);
+        }
+        request
+    }
+}
+
+impl ChocoPackArgs {
+    /// Convert to Chocolatey pack request.
+    pub fn to_pack_request(&self, verbosity: Verbosity) -> ChocoPackRequest {
+        let mut request = ChocoPackRequest::new(
+            self.content_folder.clone(),
+            self.setup_file.clone(),
+            self.id.clone(),
+            self.version.clone(),
+            self.output_folder.clone(),
+        )
+        .with_verbosity(verbosity);
+
+        if let Some(title) = &self.title {
+            request = request.with_title(title.clone());
+        }
+        if let Some(authors) = &self.authors {
+            request = request.with_authors(authors.clone());
+        }
+        if let Some(description) = &self.description {
+            request = request.with_description(description.clone());
+        }
+        if let Some(args) = &self.silent_args {
+            request = request.with_silent_args(args.clone());
+        }
+
+        for glob in &self.include {
+            request = request.with_include(glob.clone());
+        }
+
+        for glob in &self.exclude {
+            request = request.with_exclude(glob.clone());
+        }
+
+        request
+    }
+}
+
+#[cfg(feature = "linux")]
+impl LinuxDebArgs {
+    /// Convert to Debian package request.
+    pub fn to_deb_request(&self, verbosity: Verbosity) -> crate::models::linux::DebRequest {
+        let mut request = crate::models::linux::DebRequest::new(
+            self.content_folder.clone(),
+            self.name.clone(),
+            self.version.clone(),
+            self.output_folder.clone(),
+        )
+        .with_install_location(self.install_location.clone())
+        .with_allow_special_modes(self.allow_special_modes)
+        .with_allow_dangerous_source(self.i_know_what_im_doing)
+        .with_verbosity(verbosity);
+
+        if let Some(architecture) = &self.architecture {
+            request.set_architecture(architecture.clone());
+        }
+        if let Some(maintainer) = &self.maintainer {
+            request.set_maintainer(maintainer.clone());
+        }
+        if let Some(description) = &self.description {
+            request.set_description(description.clone());
+        }
+        for relation in &self.depends {
+            request.add_depends(relation.clone());
+        }
+        if let Some(section) = &self.section {
+            request.set_section(section.clone());
+        }
+        if let Some(priority) = &self.priority {
+            request.set_priority(priority.clone());
+        }
+        if let Some(homepage) = &self.homepage {
+            request.set_homepage(homepage.clone());
+        }
+        if let Some(folder) = &self.scripts_folder {
+            request.set_scripts_folder(folder.clone());
+        }
+        if let Some(name) = &self.output_name {
+            request.set_output_name(name.clone());
+        }
+        request
+    }
+}
+
+#[cfg(feature = "linux")]
+impl LinuxRpmArgs {
+    /// Convert to RPM package request.
+    pub fn to_rpm_request(&self, verbosity: Verbosity) -> crate::models::linux::RpmRequest {
+        let mut request = crate::models::linux::RpmRequest::new(
+            self.content_folder.clone(),
+            self.name.clone(),
+            self.version.clone(),
+            self.output_folder.clone(),
+        )
+        .with_install_location(self.install_location.clone())
+        .with_allow_special_modes(self.allow_special_modes)
+        .with_allow_dangerous_source(self.i_know_what_im_doing)
+        .with_verbosity(verbosity);
+
+        if let Some(release) = &self.release {
+            request.set_release(release.clone());
+        }
+        if let Some(epoch) = self.epoch {
+            request.set_epoch(epoch);
+        }
+        if let Some(architecture) = &self.architecture {
+            request.set_architecture(architecture.clone());
+        }
+        if let Some(summary) = &self.summary {
+            request.set_summary(summary.clone());
+        }
+        if let Some(description) = &self.description {
+            request.set_description(description.clone());
+        }
+        if let Some(license) = &self.license {
+            request.set_license(license.clone());
+        }
+        if let Some(url) = &self.url {
+            request.set_url(url.clone());
+        }
+        for relation in &self.requires {
+            request.add_requires(relation.clone());
+        }
+        if let Some(folder) = &self.scripts_folder {
+            request.set_scripts_folder(folder.clone());
+        }
+        if let Some(name) = &self.output_name {
+            request.set_output_name(name.clone());
+        }
+        request
+    }
+}
+
+impl CaptureFinishArgs {
+    /// Convert to capture finish request.
+    pub fn to_finish_request(&self, verbosity: Verbosity) -> CaptureFinishRequest {
+        let mut request = CaptureFinishRequest::new(
+            self.state_file.clone(),
+            self.setup_file.clone(),
+            self.output_folder.clone(),
+        )
+        .with_verbosity(verbosity);
+
+        if let Some(name) = &self.output_name {
+            request = request.with_output_name(name.clone());
+        }
+
+        request
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -467,10 +2417,46 @@ mod tests {
     #[test]
     fn test_intune_create_args_to_request() {
         let args = IntuneCreateArgs {
-            content_folder: PathBuf::from("/source"),
+            content_folder: Some(PathBuf::from("/source")),
+            content_stdin_tar: false,
             setup_file: "setup.exe".to_string(),
             output_folder: PathBuf::from("/output"),
             output_name: Some("MyApp".to_string()),
+            name_template: None,
+            display_name: Some("My App".to_string()),
+            tool_version: Some("1.8.4.0".to_string()),
+            blocklist: Some(PathBuf::from("/hashes.json")),
+            blocklist_warn: true,
+            content_tags: vec![("build".to_string(), "42".to_string())],
+            relationships: Some(PathBuf::from("/relationships.json")),
+            transforms: vec![TransformRule::parse("*.ps1=crlf").unwrap()],
+            include: Vec::new(),
+            exclude: vec![Glob::new(".git").unwrap()],
+            reproducible: true,
+            reproducible_seed: None,
+            keys_file: None,
+            keys_from_env: false,
+            digest_algorithm: Some(DigestAlgorithm::Sha512),
+            sign_key: None,
+            setup_manifest: true,
+            sbom: Some(SbomFormat::Spdx),
+            embed_sbom: true,
+            cache_dir: Some(PathBuf::from("/cache")),
+            state_file: Some(PathBuf::from("/state.json")),
+            skip_unchanged: true,
+            path_report: Some(PathBuf::from("/paths.json")),
+            compression: None,
+            level: Some(9),
+            i_know_what_im_doing: false,
+            max_files: None,
+            max_size_gb: None,
+            force: false,
+            size_limit: None,
+            dry_run: false,
+            json: false,
+            io_retries: 0,
+            post_validate: false,
+            post_validate_timeout: 300,
         };
 
         let request = args.to_package_request(Verbosity::Quiet);
@@ -478,7 +2464,59 @@ mod tests {
         assert_eq!(request.setup_file, "setup.exe");
         assert_eq!(request.output_folder, PathBuf::from("/output"));
         assert_eq!(request.output_name, Some("MyApp".to_string()));
+        assert_eq!(request.display_name, Some("My App".to_string()));
+        assert_eq!(request.tool_version, Some("1.8.4.0".to_string()));
         assert_eq!(request.verbosity, Verbosity::Quiet);
+        assert_eq!(request.blocklist, Some(PathBuf::from("/hashes.json")));
+        assert_eq!(request.blocklist_action, BlocklistAction::Warn);
+        assert_eq!(
+            request.content_tags,
+            vec![("build".to_string(), "42".to_string())]
+        );
+        assert_eq!(
+            request.relationships,
+            Some(PathBuf::from("/relationships.json"))
+        );
+        assert_eq!(request.transforms.len(), 1);
+        assert_eq!(request.transforms[0].glob.as_str(), "*.ps1");
+        assert_eq!(request.filter.exclude, vec![Glob::new(".git").unwrap()]);
+        assert_eq!(request.reproducible, Some(Reproducible::ContentDigest));
+        assert_eq!(request.encryption.digest_algorithm, DigestAlgorithm::Sha512);
+        assert!(request.setup_manifest);
+        assert_eq!(
+            request.sbom,
+            Some(SbomOptions::sidecar(SbomFormat::Spdx).with_embed(true))
+        );
+        assert_eq!(request.cache, Some(CacheConfig::new("/cache")));
+        assert_eq!(
+            request.incremental,
+            Some(IncrementalOptions::new("/state.json").with_skip_unchanged(true))
+        );
+        assert_eq!(request.path_report, Some(PathBuf::from("/paths.json")));
+        assert_eq!(request.compression, Compression::default().with_level(9));
+    }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("git=abc=def").unwrap(),
+            ("git".to_string(), "abc=def".to_string())
+        );
+        assert_eq!(
+            parse_key_value("ticket=").unwrap(),
+            ("ticket".to_string(), String::new())
+        );
+        assert!(parse_key_value("novalue").is_err());
+        assert!(parse_key_value("=value").is_err());
+    }
+
+    #[test]
+    fn test_source_limits_overrides() {
+        assert_eq!(source_limits(None, None), SourceLimits::default());
+
+        let limits = source_limits(Some(10), Some(0.5));
+        assert_eq!(limits.max_files, 10);
+        assert_eq!(limits.max_bytes, 512 * 1024 * 1024);
     }
 
     #[test]
@@ -486,11 +2524,20 @@ mod tests {
         let args = IntuneExtractArgs {
             input_file: PathBuf::from("/test.intunewin"),
             output_folder: PathBuf::from("/extracted"),
+            extract_to_zip: false,
         };
 
         let request = args.to_unpack_request(Verbosity::Normal);
         assert_eq!(request.input_file, PathBuf::from("/test.intunewin"));
         assert_eq!(request.output_folder, PathBuf::from("/extracted"));
         assert_eq!(request.verbosity, Verbosity::Normal);
+        assert_eq!(request.archive_path(), None);
+
+        let request = request.with_zip_password("secret");
+        assert_eq!(
+            request.archive_path(),
+            Some(PathBuf::from("/extracted/test.zip"))
+        );
+        assert!(!format!("{:?}", request).contains("secret"));
     }
 }
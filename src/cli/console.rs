@@ -0,0 +1,77 @@
+//! Console detection for runs without a terminal.
+//!
+//! Under a Windows service account, a scheduled task or CI there is no
+//! console: progress bars are not drawn, prompts are never shown and
+//! anything that would have asked the user fails with an error instead.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+
+use crate::models::error::{PackageError, PackageResult};
+
+/// Environment variable that forces non-terminal behavior when set to
+/// anything but empty or `0`.
+pub const NO_TTY_ENV: &str = "IAMAWRAPPER_NO_TTY";
+
+/// Whether `value` of [`NO_TTY_ENV`] asks for non-terminal behavior.
+fn no_tty_requested(value: Option<&OsStr>) -> bool {
+    value.is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+fn no_tty_forced() -> bool {
+    no_tty_requested(env::var_os(NO_TTY_ENV).as_deref())
+}
+
+/// Whether the user can be prompted (stdin and stderr are terminals).
+pub fn can_prompt() -> bool {
+    !no_tty_forced() && io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Whether progress bars can be drawn (stderr is a terminal).
+pub fn can_draw_progress() -> bool {
+    !no_tty_forced() && io::stderr().is_terminal()
+}
+
+/// Whether the terminal can redraw lists in place, which the directory
+/// browser and the fuzzy file picker need; `TERM=dumb` terminals get plain
+/// text prompts instead.
+pub fn can_browse() -> bool {
+    can_prompt() && env::var_os("TERM").map_or(true, |term| term != "dumb")
+}
+
+/// Fail unless the user can be prompted.
+pub fn require_prompt() -> PackageResult<()> {
+    if can_prompt() {
+        Ok(())
+    } else {
+        Err(PackageError::InvalidArgument {
+            reason: "Interactive mode needs a terminal; pass a subcommand and its arguments"
+                .to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_tty_requested() {
+        assert!(!no_tty_requested(None));
+        assert!(!no_tty_requested(Some(OsStr::new(""))));
+        assert!(!no_tty_requested(Some(OsStr::new("0"))));
+        assert!(no_tty_requested(Some(OsStr::new("1"))));
+        assert!(no_tty_requested(Some(OsStr::new("true"))));
+    }
+
+    #[test]
+    fn test_no_console_never_prompts() {
+        // Test harness output is captured, so stderr is never a terminal
+        if !io::stderr().is_terminal() {
+            assert!(!can_prompt());
+            assert!(!can_draw_progress());
+            assert!(require_prompt().is_err());
+        }
+    }
+}
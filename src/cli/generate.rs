@@ -0,0 +1,50 @@
+//! Shell completions and man pages generated from the argument definitions,
+//! so they always cover every command and flag.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Command, CommandFactory};
+use clap_complete::Shell;
+use clap_mangen::Man;
+
+use super::args::CliArgs;
+use crate::models::error::{PackageError, PackageResult};
+
+/// Name of the binary that completions and man pages are generated for.
+const BIN_NAME: &str = "iamawrapper";
+
+/// Write the completion script for `shell` to `out`.
+pub fn write_completions(shell: Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut CliArgs::command(), BIN_NAME, out);
+}
+
+/// Write a man page for the binary and one for each of its commands to
+/// `dir`, e.g. `iamawrapper.1` and `iamawrapper-intune-create.1`.
+///
+/// Returns the pages written, the binary's first.
+pub fn write_man_pages(dir: &Path) -> PackageResult<Vec<PathBuf>> {
+    fs::create_dir_all(dir).map_err(|e| PackageError::output_folder(dir.to_path_buf(), e))?;
+
+    // Building names each command after its parents, e.g. iamawrapper-intune
+    let mut cmd = CliArgs::command().disable_help_subcommand(true);
+    cmd.build();
+
+    let mut written = Vec::new();
+    write_pages(&cmd, dir, &mut written)?;
+    Ok(written)
+}
+
+fn write_pages(cmd: &Command, dir: &Path, written: &mut Vec<PathBuf>) -> PackageResult<()> {
+    let man = Man::new(cmd.clone());
+    let path = dir.join(man.get_filename());
+    man.generate_to(dir)
+        .map_err(|e| PackageError::output_write(path.clone(), e))?;
+    written.push(path);
+
+    for subcommand in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        write_pages(subcommand, dir, written)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Answers remembered between interactive sessions.
+//!
+//! The last source folder, output folder, identifier prefix and install
+//! location are stored as JSON in the user's configuration folder and
+//! offered as defaults next time. `--no-history` neither reads nor writes
+//! the file.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::json::{self, JsonValue};
+use crate::models::error::{PackageError, PackageResult};
+
+/// Name of the history file in the configuration folder.
+const HISTORY_FILE: &str = "interactive.json";
+
+/// Answers given in the last interactive session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InteractiveHistory {
+    /// Folder that was packaged
+    pub source_folder: Option<PathBuf>,
+    /// Folder the package was written to
+    pub output_folder: Option<PathBuf>,
+    /// Identifier up to and including its last dot, e.g. `com.company.`
+    pub identifier_prefix: Option<String>,
+    /// Install location of a macOS package
+    pub install_location: Option<PathBuf>,
+}
+
+impl InteractiveHistory {
+    /// Remember the prefix of `identifier`, e.g. `com.company.` for
+    /// `com.company.app`.
+    pub fn set_identifier(&mut self, identifier: &str) {
+        self.identifier_prefix = identifier
+            .rfind('.')
+            .map(|dot| identifier[..=dot].to_string());
+    }
+
+    /// Read the history from `path`; a missing or unreadable file gives an
+    /// empty history.
+    pub fn load_from(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| json::parse(&text).ok())
+            .map(|value| Self::from_json_value(&value))
+            .unwrap_or_default()
+    }
+
+    /// Write the history to `path`, creating its folder.
+    pub fn save_to(&self, path: &Path) -> PackageResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+        }
+        fs::write(path, self.to_json_value().to_pretty_string())
+            .map_err(|e| PackageError::output_write(path.to_path_buf(), e))
+    }
+
+    /// The history as a JSON object; unset answers are left out.
+    pub fn to_json_value(&self) -> JsonValue {
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| JsonValue::from(path.to_string_lossy().into_owned()))
+        };
+        let members = [
+            ("source_folder", path(&self.source_folder)),
+            ("output_folder", path(&self.output_folder)),
+            (
+                "identifier_prefix",
+                self.identifier_prefix.clone().map(JsonValue::from),
+            ),
+            ("install_location", path(&self.install_location)),
+        ];
+        JsonValue::Object(
+            members
+                .into_iter()
+                .filter_map(|(key, value)| Some((key.to_string(), value?)))
+                .collect(),
+        )
+    }
+
+    /// Read a history from a JSON object, ignoring unknown members.
+    pub fn from_json_value(value: &JsonValue) -> Self {
+        let string = |key| value.get(key).and_then(JsonValue::as_str);
+        Self {
+            source_folder: string("source_folder").map(PathBuf::from),
+            output_folder: string("output_folder").map(PathBuf::from),
+            identifier_prefix: string("identifier_prefix").map(String::from),
+            install_location: string("install_location").map(PathBuf::from),
+        }
+    }
+}
+
+/// Where the history is stored: `iamawrapper/interactive.json` in the
+/// user's configuration folder, if there is one.
+pub fn history_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("iamawrapper").join(HISTORY_FILE))
+}
+
+/// The user's configuration folder: `%APPDATA%` on Windows,
+/// `~/Library/Application Support` on macOS and `$XDG_CONFIG_HOME` (or
+/// `~/.config`) elsewhere.
+fn config_dir() -> Option<PathBuf> {
+    let non_empty = |name| env::var_os(name).filter(|value| !value.is_empty());
+    if cfg!(windows) {
+        non_empty("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_history_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config/iamawrapper/interactive.json");
+        assert_eq!(
+            InteractiveHistory::load_from(&path),
+            InteractiveHistory::default()
+        );
+
+        let mut history = InteractiveHistory {
+            source_folder: Some(PathBuf::from("/src/My App")),
+            output_folder: Some(PathBuf::from("/out")),
+            install_location: Some(PathBuf::from("/Applications")),
+            ..InteractiveHistory::default()
+        };
+        history.set_identifier("com.company.app");
+        assert_eq!(history.identifier_prefix.as_deref(), Some("com.company."));
+
+        history.save_to(&path).unwrap();
+        assert_eq!(InteractiveHistory::load_from(&path), history);
+
+        // A damaged file is ignored rather than failing the session
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(
+            InteractiveHistory::load_from(&path),
+            InteractiveHistory::default()
+        );
+    }
+
+    #[test]
+    fn test_history_leaves_out_unset_answers() {
+        let history = InteractiveHistory {
+            output_folder: Some(PathBuf::from("out")),
+            ..InteractiveHistory::default()
+        };
+        let value = history.to_json_value();
+        assert!(value.get("source_folder").is_none());
+        assert_eq!(InteractiveHistory::from_json_value(&value), history);
+
+        let mut history = InteractiveHistory::default();
+        history.set_identifier("app");
+        assert_eq!(history.identifier_prefix, None);
+    }
+}
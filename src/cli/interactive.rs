@@ -1,15 +1,19 @@
 //! Interactive mode prompts.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use dialoguer::{Confirm, Input, Select};
+use dialoguer::{Confirm, FuzzySelect, Input, Select};
+use walkdir::WalkDir;
 
 use crate::models::error::{PackageError, PackageResult};
 #[cfg(feature = "macos")]
 use crate::models::macos::MacosPkgRequest;
 use crate::models::package::{PackageRequest, Verbosity};
 
+use super::console::{can_browse, require_prompt};
+use super::history::InteractiveHistory;
+
 /// Target platform for package creation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -21,14 +25,23 @@ pub enum Platform {
 }
 
 /// Result of interactive prompts - either an Intune or macOS package request.
+///
+/// Both requests are large, so they are boxed.
 pub enum InteractiveResult {
     /// Intune package request
-    Intune(PackageRequest),
+    Intune(Box<PackageRequest>),
     /// macOS package request
     #[cfg(feature = "macos")]
-    MacOS(MacosPkgRequest),
+    MacOS(Box<MacosPkgRequest>),
 }
 
+/// Rows of a list shown at once by the directory browser and file picker.
+const LIST_ROWS: usize = 15;
+
+/// Extensions of files that are likely setup files, listed first by the
+/// file picker.
+const SETUP_EXTENSIONS: &[&str] = &["exe", "msi", "ps1", "cmd", "bat"];
+
 /// Get the list of platform options for display.
 pub fn platform_options() -> Vec<&'static str> {
     vec!["Microsoft Intune (.intunewin)", "macOS Flat Package (.pkg)"]
@@ -82,6 +95,8 @@ pub fn validate_version(version: &str) -> Result<(), String> {
 
 /// Prompt for platform selection.
 pub fn prompt_platform() -> PackageResult<Platform> {
+    require_prompt()?;
+
     let options = platform_options();
 
     let selection = Select::new()
@@ -100,6 +115,16 @@ pub fn prompt_platform() -> PackageResult<Platform> {
 
 /// Run interactive mode with platform selection and return the appropriate request.
 pub fn run_interactive_with_platform() -> PackageResult<InteractiveResult> {
+    run_interactive_with_history(&mut InteractiveHistory::default())
+}
+
+/// Run interactive mode with platform selection, offering the answers in
+/// `history` as defaults and recording the new ones in it.
+pub fn run_interactive_with_history(
+    history: &mut InteractiveHistory,
+) -> PackageResult<InteractiveResult> {
+    require_prompt()?;
+
     println!("iamawrapper v{}", env!("CARGO_PKG_VERSION"));
     println!("Interactive Mode\n");
 
@@ -108,13 +133,13 @@ pub fn run_interactive_with_platform() -> PackageResult<InteractiveResult> {
 
     match platform {
         Platform::Intune => {
-            let request = run_interactive_intune()?;
-            Ok(InteractiveResult::Intune(request))
+            let request = run_interactive_intune(history)?;
+            Ok(InteractiveResult::Intune(Box::new(request)))
         }
         #[cfg(feature = "macos")]
         Platform::MacOS => {
-            let request = run_interactive_macos()?;
-            Ok(InteractiveResult::MacOS(request))
+            let request = run_interactive_macos(history)?;
+            Ok(InteractiveResult::MacOS(Box::new(request)))
         }
         #[cfg(not(feature = "macos"))]
         Platform::MacOS => Err(PackageError::InvalidArgument {
@@ -125,21 +150,23 @@ pub fn run_interactive_with_platform() -> PackageResult<InteractiveResult> {
 
 /// Run interactive mode for Intune packages (legacy).
 pub fn run_interactive() -> PackageResult<PackageRequest> {
-    run_interactive_intune()
+    run_interactive_intune(&mut InteractiveHistory::default())
 }
 
 /// Run interactive mode for Intune package creation.
-fn run_interactive_intune() -> PackageResult<PackageRequest> {
+fn run_interactive_intune(history: &mut InteractiveHistory) -> PackageResult<PackageRequest> {
+    require_prompt()?;
+
     println!("Microsoft Intune Package (.intunewin)\n");
 
     // Prompt for source folder
-    let source_folder = prompt_source_folder()?;
+    let source_folder = prompt_source_folder(history.source_folder.as_deref())?;
 
     // List files and prompt for setup file
     let setup_file = prompt_setup_file(&source_folder)?;
 
     // Prompt for output folder
-    let output_folder = prompt_output_folder()?;
+    let output_folder = prompt_output_folder(history.output_folder.as_deref())?;
 
     // Check if output file exists
     let request = PackageRequest::new(
@@ -182,6 +209,9 @@ fn run_interactive_intune() -> PackageResult<PackageRequest> {
 
     println!();
 
+    history.source_folder = Some(source_folder.clone());
+    history.output_folder = Some(output_folder.clone());
+
     Ok(
         PackageRequest::new(source_folder, setup_file, output_folder)
             .with_verbosity(Verbosity::Normal),
@@ -190,23 +220,25 @@ fn run_interactive_intune() -> PackageResult<PackageRequest> {
 
 /// Run interactive mode for macOS package creation.
 #[cfg(feature = "macos")]
-pub fn run_interactive_macos() -> PackageResult<MacosPkgRequest> {
+pub fn run_interactive_macos(history: &mut InteractiveHistory) -> PackageResult<MacosPkgRequest> {
+    require_prompt()?;
+
     println!("macOS Flat Package (.pkg)\n");
 
     // Prompt for source folder
-    let source_folder = prompt_source_folder()?;
+    let source_folder = prompt_source_folder(history.source_folder.as_deref())?;
 
     // Prompt for identifier
-    let identifier = prompt_macos_identifier()?;
+    let identifier = prompt_macos_identifier(history.identifier_prefix.as_deref())?;
 
     // Prompt for version
     let version = prompt_macos_version()?;
 
     // Prompt for output folder
-    let output_folder = prompt_output_folder()?;
+    let output_folder = prompt_output_folder(history.output_folder.as_deref())?;
 
     // Prompt for optional install location
-    let install_location = prompt_macos_install_location()?;
+    let install_location = prompt_macos_install_location(history.install_location.as_deref())?;
 
     // Prompt for optional scripts folder
     let scripts_folder = prompt_macos_scripts_folder()?;
@@ -266,15 +298,24 @@ pub fn run_interactive_macos() -> PackageResult<MacosPkgRequest> {
 
     println!();
 
+    history.source_folder = Some(source_folder);
+    history.output_folder = Some(output_folder);
+    history.set_identifier(&identifier);
+    history.install_location = Some(install_location);
+
     Ok(request)
 }
 
 /// Prompt for macOS package identifier.
 #[cfg(feature = "macos")]
-fn prompt_macos_identifier() -> PackageResult<String> {
+fn prompt_macos_identifier(prefix: Option<&str>) -> PackageResult<String> {
     loop {
-        let input: String = Input::new()
-            .with_prompt("Package identifier (e.g., com.company.app)")
+        let mut prompt =
+            Input::<String>::new().with_prompt("Package identifier (e.g., com.company.app)");
+        if let Some(prefix) = prefix {
+            prompt = prompt.with_initial_text(prefix);
+        }
+        let input = prompt
             .interact_text()
             .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
 
@@ -311,10 +352,15 @@ fn prompt_macos_version() -> PackageResult<String> {
 
 /// Prompt for macOS package install location.
 #[cfg(feature = "macos")]
-fn prompt_macos_install_location() -> PackageResult<PathBuf> {
+fn prompt_macos_install_location(last: Option<&Path>) -> PackageResult<PathBuf> {
+    let default = last.map_or_else(
+        || "/Applications".to_string(),
+        |path| path.display().to_string(),
+    );
     let input: String = Input::new()
-        .with_prompt("Install location (press Enter for /Applications)")
-        .default("/Applications".to_string())
+        .with_prompt(format!("Install location (press Enter for {})", default))
+        .default(default)
+        .show_default(false)
         .interact_text()
         .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
 
@@ -373,10 +419,114 @@ fn prompt_macos_scripts_folder() -> PackageResult<Option<PathBuf>> {
     }
 }
 
-fn prompt_source_folder() -> PackageResult<PathBuf> {
+/// An input prompt offering `last` as the default, if it is still a folder.
+fn folder_input(prompt: &str, last: Option<&Path>) -> Input<'static, String> {
+    let input = Input::new().with_prompt(prompt);
+    match last.filter(|path| path.is_dir()) {
+        Some(path) => input.default(path.display().to_string()),
+        None => input,
+    }
+}
+
+/// Subfolders of `dir` that the directory browser offers, sorted by name;
+/// hidden folders are left out.
+pub fn list_subfolders(dir: &Path) -> Vec<String> {
+    let mut folders: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.starts_with('.'))
+                .collect()
+        })
+        .unwrap_or_default();
+    folders.sort_by_key(|name| name.to_lowercase());
+    folders
+}
+
+/// Files under `source_folder` that the file picker offers, as relative
+/// paths with forward slashes: likely setup files first, then files nearer
+/// the top, then by name.
+pub fn list_setup_candidates(source_folder: &Path) -> Vec<String> {
+    let mut files: Vec<String> = WalkDir::new(source_folder)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(source_folder).ok()?;
+            Some(relative.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    files.sort_by_cached_key(|file| {
+        let extension = Path::new(file)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        (
+            !SETUP_EXTENSIONS.contains(&extension.as_str()),
+            file.matches('/').count(),
+            file.to_lowercase(),
+        )
+    });
+    files
+}
+
+/// Let the user pick a folder by moving through the tree from `start` (or
+/// the current folder).
+///
+/// Returns `None` when the user would rather type the path.
+fn browse_folder(prompt: &str, start: Option<&Path>) -> PackageResult<Option<PathBuf>> {
+    // Absolute, so ".." can move above the start
+    let current = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut dir = match start.filter(|path| path.is_dir()) {
+        Some(path) => current.join(path),
+        None => current,
+    };
+
     loop {
-        let input: String = Input::new()
-            .with_prompt("Source folder path")
+        let subfolders = list_subfolders(&dir);
+        let mut items = vec!["[Use this folder]".to_string(), "[Type a path]".to_string()];
+        let has_parent = dir.parent().is_some();
+        if has_parent {
+            items.push("..".to_string());
+        }
+        items.extend(subfolders.iter().map(|name| format!("{}/", name)));
+
+        let selection = Select::new()
+            .with_prompt(format!("{}: {}", prompt, dir.display()))
+            .items(&items)
+            .default(0)
+            .max_length(LIST_ROWS)
+            .interact()
+            .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
+
+        match selection {
+            0 => return Ok(Some(dir)),
+            1 => return Ok(None),
+            2 if has_parent => {
+                if let Some(parent) = dir.parent() {
+                    dir = parent.to_path_buf();
+                }
+            }
+            n => {
+                let offset = if has_parent { 3 } else { 2 };
+                dir = dir.join(&subfolders[n - offset]);
+            }
+        }
+    }
+}
+
+fn prompt_source_folder(last: Option<&Path>) -> PackageResult<PathBuf> {
+    if can_browse() {
+        if let Some(path) = browse_folder("Source folder", last)? {
+            return Ok(path);
+        }
+    }
+
+    loop {
+        let input: String = folder_input("Source folder path", last)
             .interact_text()
             .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
 
@@ -397,16 +547,9 @@ fn prompt_source_folder() -> PackageResult<PathBuf> {
 }
 
 fn prompt_setup_file(source_folder: &PathBuf) -> PackageResult<String> {
-    // List files in the source folder (non-recursive, just top level)
-    let mut files: Vec<String> = fs::read_dir(source_folder)
-        .map_err(|e| PackageError::SourceReadError {
-            path: source_folder.clone(),
-            reason: e.to_string(),
-        })?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| entry.path().is_file())
-        .map(|entry| entry.file_name().to_string_lossy().to_string())
-        .collect();
+    // Fail on an unreadable folder rather than offering an empty list
+    fs::read_dir(source_folder).map_err(|e| PackageError::source_read(source_folder.clone(), e))?;
+    let files = list_setup_candidates(source_folder);
 
     if files.is_empty() {
         return Err(PackageError::SourceFolderEmpty {
@@ -414,22 +557,41 @@ fn prompt_setup_file(source_folder: &PathBuf) -> PackageResult<String> {
         });
     }
 
-    files.sort();
+    if can_browse() {
+        let selection = FuzzySelect::new()
+            .with_prompt("Select setup file (type to search)")
+            .items(&files)
+            .default(0)
+            .max_length(LIST_ROWS)
+            .interact()
+            .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
+        return Ok(files[selection].clone());
+    }
 
-    let selection = Select::new()
-        .with_prompt("Select setup file")
-        .items(&files)
-        .default(0)
-        .interact()
-        .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
+    loop {
+        let input: String = Input::new()
+            .with_prompt("Setup file, relative to the source folder")
+            .default(files[0].clone())
+            .interact_text()
+            .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
 
-    Ok(files[selection].clone())
+        let file = input.trim().replace('\\', "/");
+        if source_folder.join(&file).is_file() {
+            return Ok(file);
+        }
+        eprintln!("Error: File does not exist in the source folder: {}", file);
+    }
 }
 
-fn prompt_output_folder() -> PackageResult<PathBuf> {
+fn prompt_output_folder(last: Option<&Path>) -> PackageResult<PathBuf> {
+    if can_browse() {
+        if let Some(path) = browse_folder("Output folder", last)? {
+            return Ok(path);
+        }
+    }
+
     loop {
-        let input: String = Input::new()
-            .with_prompt("Output folder path")
+        let input: String = folder_input("Output folder path", last)
             .interact_text()
             .map_err(|e| PackageError::Io(std::io::Error::other(e)))?;
 
@@ -479,6 +641,30 @@ mod tests {
         assert!(files.contains(&"readme.txt".to_string()));
     }
 
+    #[test]
+    fn test_list_setup_candidates() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path();
+        fs::create_dir_all(source.join("bin/x64")).unwrap();
+        fs::create_dir_all(source.join(".git")).unwrap();
+        File::create(source.join("readme.txt")).unwrap();
+        File::create(source.join("bin/x64/Setup.EXE")).unwrap();
+        File::create(source.join("install.ps1")).unwrap();
+        File::create(source.join("bin/app.dll")).unwrap();
+
+        assert_eq!(
+            list_setup_candidates(source),
+            [
+                "install.ps1",
+                "bin/x64/Setup.EXE",
+                "readme.txt",
+                "bin/app.dll"
+            ]
+        );
+        assert_eq!(list_subfolders(source), ["bin"]);
+        assert!(list_subfolders(&source.join("missing")).is_empty());
+    }
+
     // T055: Test for platform selection
     #[test]
     fn test_platform_enum_variants() {
@@ -0,0 +1,175 @@
+//! Log output (`--log-level`, `--log-file`, `--log-format`).
+//!
+//! The library reports through `tracing`: spans around the package, zip,
+//! encrypt, write and validate phases, and events when a phase ends. The
+//! CLI sends its warnings and errors through the same channel. On the
+//! console they keep their plain `Warning: ...` form; a log file gets
+//! timestamps, levels, the enclosing spans and, when a span closes, the
+//! time spent in it. Results (summaries, reports, JSON) are not logs and
+//! stay on stdout.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::models::error::{PackageError, PackageResult};
+
+use super::args::CliArgs;
+
+/// Target of the CLI's own events, shown down to notices on the console.
+const CLI_TARGET: &str = "iamawrapper::cli";
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "unknown log format '{}' (expected text or json)",
+                s
+            )),
+        }
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Install the log subscriber for a run.
+///
+/// Without `--log-file`, `--log-level` and `--log-format` apply to stderr.
+/// With it, the file gets every event down to `--log-level` (default
+/// `info`) and the console keeps its usual warnings. Nothing is written
+/// to the console in silent mode.
+pub fn init(args: &CliArgs) -> PackageResult<()> {
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+
+    if let Some(path) = &args.log_file {
+        let level = args.log_level.unwrap_or(LevelFilter::INFO);
+        layers.push(file_layer(path, args.log_format, level)?);
+    }
+
+    if !args.verbosity().suppress_output() {
+        let filter = match (&args.log_file, args.log_level) {
+            (None, Some(level)) => Targets::new().with_default(level),
+            _ => console_filter(),
+        };
+        layers.push(
+            if args.log_file.is_none() && args.log_format == LogFormat::Json {
+                json_layer(io::stderr).with_filter(filter).boxed()
+            } else {
+                console_layer().with_filter(filter).boxed()
+            },
+        );
+    }
+
+    // A subscriber set earlier (e.g. by an embedding program) is kept
+    let _ = tracing::subscriber::set_global_default(Registry::default().with(layers));
+    Ok(())
+}
+
+/// Notices from the CLI, warnings and errors from everything.
+fn console_filter() -> Targets {
+    Targets::new()
+        .with_target(CLI_TARGET, LevelFilter::INFO)
+        .with_default(LevelFilter::WARN)
+}
+
+fn console_layer() -> impl Layer<Registry> + Send + Sync {
+    tracing_subscriber::fmt::layer()
+        .event_format(ConsoleFormat)
+        .with_writer(io::stderr)
+}
+
+fn json_layer<W>(writer: W) -> impl Layer<Registry> + Send + Sync
+where
+    W: for<'w> tracing_subscriber::fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(writer)
+}
+
+/// Append to the log file at `path`.
+fn file_layer(path: &Path, format: LogFormat, level: LevelFilter) -> PackageResult<BoxedLayer> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| PackageError::output_write(path.to_path_buf(), e))?;
+    let writer = Mutex::new(file);
+
+    Ok(match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(writer)
+            .with_filter(level)
+            .boxed(),
+        LogFormat::Json => json_layer(writer).with_filter(level).boxed(),
+    })
+}
+
+/// Console lines as the CLI always printed them: `Warning: <message>`.
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "Error",
+            Level::WARN => "Warning",
+            Level::INFO => "Notice",
+            Level::DEBUG => "Debug",
+            Level::TRACE => "Trace",
+        };
+        write!(writer, "{}: ", prefix)?;
+        ctx.format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("Text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -1,242 +1,2725 @@
 //! Command-line interface module.
 
 pub mod args;
+pub mod console;
+pub mod generate;
+pub mod history;
 pub mod interactive;
+pub mod logging;
 
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 
+use dialoguer::Confirm;
+use tracing::{error, info_span, warn};
+use zeroize::Zeroizing;
+
+use crate::json::JsonValue;
+use crate::models::batch::{BatchManifest, BatchOptions, BatchOutcome, BatchReport};
+use crate::models::blocklist::BlocklistMatch;
+#[cfg(feature = "zstd")]
+use crate::models::compression::CompressionMethod;
+use crate::models::config::Config;
+use crate::models::detection::{DigestAlgorithm, REDACTED};
+use crate::models::diff::ChangeKind;
+use crate::models::dry_run::DryRunReport;
+use crate::models::encryption::{KeySource, ProvidedKeys};
 use crate::models::error::{PackageError, PackageResult, exit_codes};
-use crate::models::package::Verbosity;
-use crate::packager::{package, unpack};
+use crate::models::guardrails::SizeEstimate;
+use crate::models::listing::listing_json;
+use crate::models::package::{
+    IntuneWinPackage, PackageRequest, PathIssue, Reproducible, SkippedEntry, SparseFile, Verbosity,
+};
+use crate::models::project::ProjectImportResult;
+use crate::models::relationships::AppRelationships;
+#[cfg(feature = "macos")]
+use crate::models::retry::RetryPolicy;
+use crate::models::sbom::Sbom;
+use crate::models::selftest::StageOutcome;
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+use crate::models::transform::TransformRecord;
+use crate::models::validation::{CheckOutcome, ValidationReport, VerifyCheck};
+use crate::models::watch::WatchJob;
+#[cfg(feature = "macos")]
+use crate::packager::archive::SourceArchive;
+use crate::packager::batch::{load_batch_manifest, package_batch};
+use crate::packager::build::build;
+use crate::packager::capture::{capture_finish, capture_start};
+use crate::packager::choco::pack;
+use crate::packager::content::{STDIN_TAR_LABEL, TarContent};
+use crate::packager::detection::detection_rules;
+use crate::packager::diff::diff;
+use crate::packager::inspect::inspect;
+use crate::packager::project::{export_project, import_project};
+use crate::packager::repack::repack;
+use crate::packager::selftest::self_test;
+use crate::packager::source::describe_location;
+#[cfg(feature = "macos")]
+use crate::packager::source::is_remote;
+use crate::packager::workspace::{clean_workspaces, run_id, set_temp_dir, workspace_root};
+use crate::packager::{
+    analyze, app_manifest, dry_run, dry_run_content, inspect_detection, inspect_relationships,
+    list_contents, package, package_content, scaffold, unpack, verify, watch,
+};
 
-use self::args::{CliArgs, Commands, IntuneAction, MacosAction, MacosPkgArgs};
-use self::interactive::{InteractiveResult, run_interactive_with_platform};
+use crate::signing::{PublicKey, SecretKey, Signature, default_trusted_comment, signature_path};
+
+use self::args::{
+    BatchArgs, CaptureAction, ChocoAction, CliArgs, Commands, IntuneAction, LinuxAction,
+    MacosAction, MacosDmgArgs, MacosExtractArgs, MacosNotarizeArgs, MacosPkgArgs, MacosRepackArgs,
+    MacosRewrapArgs, MacosValidateArgs,
+};
+use self::generate::{write_completions, write_man_pages};
+use self::history::{InteractiveHistory, history_path};
+use self::interactive::{InteractiveResult, run_interactive_with_history};
 
 /// Run the CLI application.
 pub fn run(args: CliArgs) -> ExitCode {
     let verbosity = args.verbosity();
 
-    let result = match &args.command {
-        Some(Commands::Intune(intune_cmd)) => run_intune_command(intune_cmd, verbosity),
-        Some(Commands::Macos(macos_cmd)) => run_macos_command(macos_cmd, verbosity),
-        None => {
-            // No subcommand - enter interactive mode if not in quiet/silent mode
-            if args.quiet || args.silent {
-                Err(PackageError::InvalidArgument {
-                    reason: "No command specified. Use 'intune' or 'macos' subcommand.".to_string(),
-                })
-            } else {
-                run_interactive_mode()
-            }
+    if let Err(e) = logging::init(&args) {
+        if !verbosity.suppress_output() {
+            eprintln!("Error: {}", e);
         }
-    };
+        return ExitCode::from(e.exit_code() as u8);
+    }
+    let _span = info_span!("run", id = %run_id()).entered();
+
+    let result = args
+        .temp_dir
+        .as_ref()
+        .map_or(Ok(()), set_temp_dir)
+        .and_then(|()| args.strictness())
+        .and_then(|strictness| match &args.command {
+            Some(Commands::Intune(intune_cmd)) => {
+                run_intune_command(intune_cmd, &args, verbosity, &strictness)
+            }
+            Some(Commands::Macos(macos_cmd)) => {
+                run_macos_command(macos_cmd, verbosity, &strictness)
+            }
+            Some(Commands::Choco(choco_cmd)) => run_choco_command(choco_cmd, verbosity),
+            Some(Commands::Linux(linux_cmd)) => {
+                run_linux_command(linux_cmd, verbosity, &strictness)
+            }
+            Some(Commands::Capture(capture_cmd)) => {
+                run_capture_command(capture_cmd, verbosity, &strictness)
+            }
+            Some(Commands::ExportProject(export_args)) => {
+                run_export_project(export_args, verbosity, &strictness)
+            }
+            Some(Commands::ImportProject(import_args)) => {
+                run_import_project(import_args, verbosity, args.strict)
+            }
+            Some(Commands::SelfTest) => run_self_test(verbosity),
+            Some(Commands::CleanWorkspaces(clean_args)) => {
+                run_clean_workspaces(clean_args, verbosity)
+            }
+            Some(Commands::Inspect(inspect_args)) => run_inspect(inspect_args, verbosity),
+            Some(Commands::VerifySignature(verify_args)) => {
+                run_verify_signature(verify_args, verbosity)
+            }
+            Some(Commands::Completions(completions_args)) => {
+                write_completions(completions_args.shell, &mut io::stdout());
+                Ok(())
+            }
+            Some(Commands::Manpages(manpages_args)) => run_manpages(manpages_args, verbosity),
+            Some(Commands::Watch(watch_args)) => args
+                .config()
+                .and_then(|config| run_watch(watch_args, config, verbosity, &strictness)),
+            Some(Commands::Build(build_args)) => {
+                run_build(build_args, &args, verbosity, &strictness)
+            }
+            None => {
+                // No subcommand - enter interactive mode if not in quiet/silent mode
+                if args.quiet || args.silent {
+                    Err(PackageError::InvalidArgument {
+                        reason: "No command specified. Use 'intune' or 'macos' subcommand."
+                            .to_string(),
+                    })
+                } else {
+                    run_interactive_mode(args.no_history)
+                }
+            }
+        });
 
     match result {
         Ok(_) => ExitCode::from(exit_codes::SUCCESS as u8),
         Err(e) => {
-            let exit_code = e.exit_code();
-            if !matches!(verbosity, Verbosity::Silent) {
-                eprintln!("Error: {}", e);
-            }
-            ExitCode::from(exit_code as u8)
+            error!("{} (run {})", e, run_id());
+            ExitCode::from(e.exit_code() as u8)
         }
     }
 }
 
-fn run_intune_command(cmd: &args::IntuneCommand, verbosity: Verbosity) -> PackageResult<()> {
+fn run_intune_command(
+    cmd: &args::IntuneCommand,
+    cli: &CliArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
     match &cmd.action {
-        IntuneAction::Create(create_args) => run_intune_create(create_args, verbosity),
+        IntuneAction::Create(create_args) => run_intune_create(create_args, verbosity, strictness),
         IntuneAction::Extract(extract_args) => run_intune_extract(extract_args, verbosity),
+        IntuneAction::Verify(verify_args) => run_intune_verify(verify_args, verbosity),
+        IntuneAction::Inspect(inspect_args) => run_intune_inspect(inspect_args, verbosity),
+        IntuneAction::List(list_args) => run_intune_list(list_args, verbosity),
+        IntuneAction::Diff(diff_args) => run_intune_diff(diff_args, verbosity),
+        IntuneAction::Batch(batch_args) => {
+            let manifest = load_batch_manifest(&batch_args.manifest)?;
+            let options = batch_options(batch_args, verbosity, strictness);
+            if verbosity == Verbosity::Normal && !batch_args.json {
+                print_batch_start(batch_args, &manifest, &options);
+            }
+            print_batch_report(
+                batch_args.json,
+                &package_batch(&manifest, &options),
+                verbosity,
+                "application(s) packaged",
+            )
+        }
+        IntuneAction::Repack(repack_args) => run_intune_repack(repack_args, verbosity),
+        IntuneAction::Rules(rules_args) => run_intune_rules(rules_args, verbosity),
+        IntuneAction::Manifest(manifest_args) => cli
+            .config()
+            .and_then(|config| run_intune_manifest(manifest_args, config, verbosity)),
+        IntuneAction::Analyze(analyze_args) => run_intune_analyze(analyze_args, verbosity),
+        IntuneAction::Scaffold(scaffold_args) => run_intune_scaffold(scaffold_args, verbosity),
+    }
+}
+
+fn run_choco_command(cmd: &args::ChocoCommand, verbosity: Verbosity) -> PackageResult<()> {
+    match &cmd.action {
+        ChocoAction::Pack(pack_args) => run_choco_pack(pack_args, verbosity),
     }
 }
 
-fn run_intune_create(args: &args::IntuneCreateArgs, verbosity: Verbosity) -> PackageResult<()> {
-    let request = args.to_package_request(verbosity);
+fn run_choco_pack(args: &args::ChocoPackArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let request = args.to_pack_request(verbosity);
+
+    if matches!(verbosity, Verbosity::Normal) {
+        println!(
+            "Chocolatey Package Builder v{}\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        println!("Run ID: {}", run_id());
+        println!("Source folder: {}", request.source_folder.display());
+        println!("Setup file: {}", request.setup_file);
+        println!("Package: {} {}", request.id, request.version);
+        println!("Output folder: {}", request.output_folder.display());
+        println!();
+    }
+
+    let result = pack(&request)?;
 
     match verbosity {
         Verbosity::Normal => {
-            println!("IntuneWin Packager v{}\n", env!("CARGO_PKG_VERSION"));
-            println!("Source folder: {}", request.source_folder.display());
-            println!("Setup file: {}", request.setup_file);
-            println!("Output folder: {}", request.output_folder.display());
-            println!();
+            println!("\nPackage created successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            println!("  {} files included", result.file_count);
+            println!(
+                "  Creation time: {:.2}s",
+                result.creation_time.as_secs_f64()
+            );
+        }
+        Verbosity::Quiet => println!("{}", result.output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+fn run_linux_command(
+    cmd: &args::LinuxCommand,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    match &cmd.action {
+        LinuxAction::Deb(deb_args) => run_linux_deb(deb_args, verbosity, strictness),
+        LinuxAction::Rpm(rpm_args) => run_linux_rpm(rpm_args, verbosity, strictness),
+    }
+}
+
+#[cfg(feature = "linux")]
+fn run_linux_deb(
+    args: &args::LinuxDebArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let request = args
+        .to_deb_request(verbosity)
+        .with_strictness(strictness.clone());
+
+    if matches!(verbosity, Verbosity::Normal) {
+        println!("Debian Package Builder v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Source folder: {}", request.source_folder.display());
+        println!(
+            "Package: {} {} ({})",
+            request.name, request.version, request.architecture
+        );
+        println!("Install location: {}", request.install_location.display());
+        println!("Output folder: {}", request.output_folder.display());
+        println!();
+    }
 
-            let result = package(&request)?;
+    let result = crate::linux::package(&request)?;
 
+    match verbosity {
+        Verbosity::Normal => {
             println!("\nPackage created successfully:");
             println!(
-                "  {} ({:.2} MB)",
+                "  {} ({})",
                 result.output_path.display(),
-                result.package_size as f64 / 1_048_576.0
+                result.package_size
+            );
+            println!(
+                "  {} files included ({} KB installed)",
+                result.file_count, result.installed_kib
             );
+            if !result.scripts.is_empty() {
+                println!("  Maintainer scripts: {}", result.scripts.join(", "));
+            }
             println!(
                 "  Creation time: {:.2}s",
                 result.creation_time.as_secs_f64()
             );
+            print_special_modes(&result.special_mode_files, request.allow_special_modes);
+            print_skipped_entries(&result.skipped_entries);
         }
         Verbosity::Quiet => {
-            let result = package(&request)?;
+            print_special_modes(&result.special_mode_files, request.allow_special_modes);
+            print_skipped_entries(&result.skipped_entries);
             println!("{}", result.output_path.display());
         }
-        Verbosity::Silent => {
-            let _result = package(&request)?;
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "linux"))]
+fn run_linux_deb(
+    _args: &args::LinuxDebArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "Linux packaging is not enabled. Build with --features linux".to_string(),
+    })
+}
+
+#[cfg(feature = "linux")]
+fn run_linux_rpm(
+    args: &args::LinuxRpmArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let request = args
+        .to_rpm_request(verbosity)
+        .with_strictness(strictness.clone());
+
+    if matches!(verbosity, Verbosity::Normal) {
+        println!("RPM Package Builder v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Source folder: {}", request.source_folder.display());
+        println!(
+            "Package: {} {} ({})",
+            request.name,
+            request.evr(),
+            request.architecture
+        );
+        println!("Install location: {}", request.install_location.display());
+        println!("Output folder: {}", request.output_folder.display());
+        println!();
+    }
+
+    let result = crate::linux::rpm::package(&request)?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("\nPackage created successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            println!(
+                "  {} files included ({} installed)",
+                result.file_count, result.installed_size
+            );
+            if !result.scripts.is_empty() {
+                println!("  Scriptlets: {}", result.scripts.join(", "));
+            }
+            println!(
+                "  Creation time: {:.2}s",
+                result.creation_time.as_secs_f64()
+            );
+            print_special_modes(&result.special_mode_files, request.allow_special_modes);
+            print_skipped_entries(&result.skipped_entries);
         }
+        Verbosity::Quiet => {
+            print_special_modes(&result.special_mode_files, request.allow_special_modes);
+            print_skipped_entries(&result.skipped_entries);
+            println!("{}", result.output_path.display());
+        }
+        Verbosity::Silent => {}
     }
 
     Ok(())
 }
 
-fn run_intune_extract(args: &args::IntuneExtractArgs, verbosity: Verbosity) -> PackageResult<()> {
-    let request = args.to_unpack_request(verbosity);
+#[cfg(not(feature = "linux"))]
+fn run_linux_rpm(
+    _args: &args::LinuxRpmArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "Linux packaging is not enabled. Build with --features linux".to_string(),
+    })
+}
+
+fn run_capture_command(
+    cmd: &args::CaptureCommand,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    match &cmd.action {
+        CaptureAction::Start(start_args) => run_capture_start(start_args, verbosity),
+        CaptureAction::Finish(finish_args) => {
+            run_capture_finish(finish_args, verbosity, strictness)
+        }
+    }
+}
+
+fn run_capture_start(args: &args::CaptureStartArgs, verbosity: Verbosity) -> PackageResult<()> {
+    if !verbosity.suppress_output() {
+        println!("Snapshotting {}...", args.path.display());
+    }
+
+    let snapshot = capture_start(&args.path, &args.state_file, args.i_know_what_im_doing)?;
+
+    if !verbosity.suppress_output() {
+        println!(
+            "Captured {} files; state saved to {}",
+            snapshot.entries.len(),
+            args.state_file.display()
+        );
+        println!("Run the installer, then 'iamawrapper capture finish -s <setup> -o <output>'.");
+    }
+
+    Ok(())
+}
+
+fn run_capture_finish(
+    args: &args::CaptureFinishArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let request = args
+        .to_finish_request(verbosity)
+        .with_strictness(strictness.clone());
 
     match verbosity {
         Verbosity::Normal => {
-            println!("IntuneWin Unpacker v{}\n", env!("CARGO_PKG_VERSION"));
-            println!("Input file: {}", request.input_file.display());
+            println!("IntuneWin Capture v{}\n", env!("CARGO_PKG_VERSION"));
+            println!("Run ID: {}", run_id());
+            println!("State file: {}", request.state_file.display());
+            println!("Setup file: {}", request.setup_file);
             println!("Output folder: {}", request.output_folder.display());
             println!();
 
-            let result = unpack(&request)?;
+            let result = capture_finish(&request)?;
 
-            println!("\nPackage extracted successfully:");
-            println!("  {} files extracted", result.file_count);
+            println!("\nCapture packaged successfully:");
             println!(
-                "  Total size: {:.2} MB",
-                result.total_size as f64 / 1_048_576.0
+                "  {} added, {} changed, {} removed (removals are not packaged)",
+                result.diff.added.len(),
+                result.diff.changed.len(),
+                result.diff.removed.len()
             );
             println!(
-                "  Extraction time: {:.2}s",
-                result.unpack_time.as_secs_f64()
+                "  {} ({})",
+                result.package.output_path.display(),
+                result.package.package_size
+            );
+            println!(
+                "  Creation time: {:.2}s",
+                result.package.creation_time.as_secs_f64()
             );
-            println!("  Setup file: {}", result.setup_file);
         }
         Verbosity::Quiet => {
-            let result = unpack(&request)?;
-            println!("{}", result.output_folder.display());
+            let result = capture_finish(&request)?;
+            println!("{}", result.package.output_path.display());
         }
         Verbosity::Silent => {
-            let _result = unpack(&request)?;
+            let _result = capture_finish(&request)?;
         }
     }
 
     Ok(())
 }
 
-fn run_macos_command(cmd: &args::MacosCommand, verbosity: Verbosity) -> PackageResult<()> {
-    match &cmd.action {
-        MacosAction::Pkg(pkg_args) => run_macos_pkg(pkg_args, verbosity),
+fn run_export_project(
+    args: &args::ExportProjectArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let (content_folder, build) = args.target.to_build();
+    let config = Config {
+        strictness: strictness.clone(),
+        ..Config::default()
+    };
+
+    let project = export_project(
+        content_folder,
+        build,
+        config,
+        args.target.scripts_folder().map(|p| p.as_path()),
+        &args.output,
+    )?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("Project exported: {}", args.output.display());
+            println!(
+                "  {} files in manifest, {} script(s) bundled",
+                project.files.len(),
+                project.scripts.len()
+            );
+            println!("  Archive the content folder alongside the project file");
+        }
+        Verbosity::Quiet => println!("{}", args.output.display()),
+        Verbosity::Silent => {}
     }
+
+    Ok(())
 }
 
-#[cfg(feature = "macos")]
-fn run_macos_pkg(args: &MacosPkgArgs, verbosity: Verbosity) -> PackageResult<()> {
-    use crate::macos;
-    use crate::models::macos::MacosPkgRequest;
-    use std::path::PathBuf;
+fn run_import_project(
+    args: &args::ImportProjectArgs,
+    verbosity: Verbosity,
+    strict: bool,
+) -> PackageResult<()> {
+    let request = args.to_import_request(verbosity, strict);
 
-    // Validate source folder exists
-    if !args.content_folder.exists() {
-        return Err(PackageError::SourceFolderNotFound {
-            path: args.content_folder.clone(),
-        });
+    if verbosity == Verbosity::Normal {
+        println!("Project: {}", request.project_file.display());
+        println!("Content folder: {}", request.content_folder.display());
+        println!();
     }
 
-    // Check source folder is not empty
-    let is_empty = args
-        .content_folder
-        .read_dir()
-        .map(|mut i| i.next().is_none())
-        .unwrap_or(true);
-    if is_empty {
-        return Err(PackageError::SourceFolderEmpty {
-            path: args.content_folder.clone(),
-        });
+    let (output_path, package_size) = match import_project(&request)? {
+        ProjectImportResult::Intune(result) => (result.output_path, result.package_size),
+        #[cfg(feature = "macos")]
+        ProjectImportResult::Macos(result) => (result.output_path, result.package_size),
+    };
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("\nContent matches the project manifest");
+            println!("Package rebuilt successfully:");
+            println!("  {} ({})", output_path.display(), package_size);
+        }
+        Verbosity::Quiet => println!("{}", output_path.display()),
+        Verbosity::Silent => {}
     }
 
-    // Determine output folder and filename from output path
-    let output_folder = args
-        .output
-        .parent()
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("."));
-    let output_name = args
-        .output
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .map(String::from);
+    Ok(())
+}
 
-    // Create request
-    let mut request = MacosPkgRequest::new(
-        args.content_folder.clone(),
-        args.identifier.clone(),
-        args.version.clone(),
-        output_folder,
-    )
-    .with_install_location(PathBuf::from(&args.install_location))
-    .with_verbosity(verbosity);
+fn run_self_test(verbosity: Verbosity) -> PackageResult<()> {
+    let report = self_test()?;
 
-    if let Some(name) = output_name {
-        request = request.with_output_name(name);
+    if verbosity != Verbosity::Silent {
+        for stage in &report.stages {
+            let duration = stage.duration.as_secs_f64();
+            match &stage.outcome {
+                StageOutcome::Pass => println!("PASS  {} ({:.2}s)", stage.name, duration),
+                StageOutcome::Fail(reason) => {
+                    println!("FAIL  {} ({:.2}s): {}", stage.name, duration, reason)
+                }
+                StageOutcome::Skipped => println!("SKIP  {}", stage.name),
+            }
+        }
     }
 
-    if let Some(scripts) = &args.scripts_folder {
-        request = request.with_scripts_folder(scripts.clone());
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(PackageError::SelfTestFailed {
+            failures: report.failures(),
+        })
+    }
+}
+
+fn run_clean_workspaces(
+    args: &args::CleanWorkspacesArgs,
+    verbosity: Verbosity,
+) -> PackageResult<()> {
+    let root = workspace_root();
+    let older_than = Duration::from_secs(args.older_than_hours * 60 * 60);
+    let removed = clean_workspaces(&root, older_than, args.dry_run)?;
+
+    if !verbosity.suppress_output() {
+        let verb = if args.dry_run {
+            "Would remove"
+        } else {
+            "Removed"
+        };
+        for path in &removed {
+            println!("{} {}", verb, path.display());
+        }
+        if verbosity == Verbosity::Normal {
+            println!(
+                "{} {} workspace(s) under {}",
+                verb,
+                removed.len(),
+                root.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_manpages(args: &args::ManpagesArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let written = write_man_pages(&args.dir)?;
+
+    if verbosity == Verbosity::Normal {
+        for path in &written {
+            println!("Wrote {}", path.display());
+        }
+        println!("{} man page(s) in {}", written.len(), args.dir.display());
+    }
+
+    Ok(())
+}
+
+fn run_build(
+    args: &args::BuildArgs,
+    cli: &CliArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let config_path = cli
+        .config
+        .as_deref()
+        .ok_or_else(|| PackageError::InvalidArgument {
+            reason: "build reads its targets from --config FILE (e.g., app.toml)".to_string(),
+        })?;
+    let config = cli.config()?;
+    let base = config_path.parent().unwrap_or(Path::new(""));
+    let context = args.to_build_context(base, verbosity, strictness);
+
+    if verbosity == Verbosity::Normal && !args.json {
+        println!("Run ID: {}", run_id());
+        println!(
+            "Building {} from {}\n",
+            config.build.name().unwrap_or("the application"),
+            config_path.display()
+        );
+    }
+    let report = build(&config.build, &context)?;
+    print_batch_report(args.json, &report, verbosity, "target(s) built")
+}
+
+fn run_watch(
+    args: &args::WatchArgs,
+    config: Config,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let request = args.to_watch_request(config.watch, verbosity, strictness);
+
+    if verbosity == Verbosity::Normal {
+        println!("Run ID: {}", run_id());
+        if request.once {
+            println!("Packaging {}", request.drop_folder.display());
+        } else {
+            println!(
+                "Watching {} (every {}s, settle {}s)",
+                request.drop_folder.display(),
+                request.rules.poll.as_secs(),
+                request.rules.settle.as_secs()
+            );
+        }
+        println!("Output: {}\n", request.output_folder.display());
+    }
+
+    let mut total = 0;
+    let mut failures = 0;
+    watch(
+        &request,
+        |job| {
+            total += 1;
+            if !job.succeeded() {
+                failures += 1;
+            }
+            print_watch_job(job, verbosity);
+        },
+        &|| false,
+    )?;
+
+    if verbosity == Verbosity::Normal && request.once {
+        println!(
+            "\n{} of {} application(s) packaged",
+            total - failures,
+            total
+        );
+    }
+    if failures > 0 {
+        return Err(PackageError::BatchFailed { failures, total });
+    }
+    Ok(())
+}
+
+/// Print the outcome of one drop folder job.
+fn print_watch_job(job: &WatchJob, verbosity: Verbosity) {
+    match (&job.outcome, verbosity) {
+        (_, Verbosity::Silent) => {}
+        (
+            BatchOutcome::Packaged {
+                output_path,
+                package_size,
+            },
+            Verbosity::Normal,
+        ) => println!(
+            "{}  ok      {:>7.2}s  {} ({})",
+            job.name,
+            job.duration.as_secs_f64(),
+            output_path.display(),
+            package_size
+        ),
+        (BatchOutcome::Packaged { output_path, .. }, _) => println!("{}", output_path.display()),
+        (BatchOutcome::Failed(error), Verbosity::Normal) => println!(
+            "{}  FAILED  {:>7.2}s  {} (log: {})",
+            job.name,
+            job.duration.as_secs_f64(),
+            error,
+            job.log_path.display()
+        ),
+        (BatchOutcome::Failed(error), _) => error!("{}: {}", job.name, error),
+    }
+}
+
+fn run_inspect(args: &args::InspectArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let report = inspect(args.input())?;
+
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    if args.json {
+        println!("{}", report.to_json());
+        return Ok(());
+    }
+
+    println!("Package: {}", report.path.display());
+    println!("Kind: {}", report.kind);
+    match &report.metadata {
+        Some(metadata) => {
+            println!("Tool version: {}", metadata.tool_version);
+            println!("Schema version: {}", metadata.schema_version);
+            println!("Created: {} (Unix time)", metadata.created);
+            println!("Manifest SHA-256: {}", metadata.manifest_sha256);
+        }
+        None => println!("No artifact record (built by another tool or an older version)"),
+    }
+
+    Ok(())
+}
+
+fn run_intune_create(
+    args: &args::IntuneCreateArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    let mut request = args
+        .to_package_request(verbosity)
+        .with_strictness(strictness.clone());
+    if let Some(keys) = provided_keys(args)? {
+        request.encryption.keys = KeySource::Provided(keys);
+    }
+
+    // A tar stream can only be read once, so buffer it before packaging
+    let stdin_content = if args.content_stdin_tar {
+        Some(TarContent::read_from(
+            io::stdin().lock(),
+            STDIN_TAR_LABEL,
+            &request.setup_file,
+        )?)
+    } else {
+        None
+    };
+    #[cfg(feature = "zstd")]
+    if request.compression.method == CompressionMethod::Zstd && verbosity != Verbosity::Silent {
+        warn!(
+            "zstd-compressed content cannot be installed by Intune; only 'intune extract' unpacks it"
+        );
+    }
+
+    if args.dry_run {
+        let report = match &stdin_content {
+            Some(content) => dry_run_content(&request, content),
+            None => dry_run(&request),
+        }?;
+        return print_dry_run(&report, verbosity, args.json);
+    }
+
+    let sign_key = args.sign_key.as_deref().map(sign_key).transpose()?;
+    if sign_key.is_some() && request.writes_to_stdout() {
+        return Err(PackageError::InvalidArgument {
+            reason: "--sign-key cannot sign a package written to stdout".to_string(),
+        });
     }
+    let create = |request: &PackageRequest| {
+        let result = match &stdin_content {
+            Some(content) => package_content(request, content),
+            None => package(request),
+        }?;
+        if let Some(key) = &sign_key {
+            sign_package(key, &result.output_path)?;
+        }
+        Ok(result)
+    };
+
+    // With `-o -` stdout carries the package, so people read stderr
+    let mut out: Box<dyn Write> = if request.writes_to_stdout() {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    };
 
     match verbosity {
         Verbosity::Normal => {
-            println!("macOS Package Builder v{}\n", env!("CARGO_PKG_VERSION"));
-            println!("Source folder: {}", request.source_folder.display());
-            println!("Identifier: {}", request.identifier);
-            println!("Version: {}", request.version);
-            println!("Install location: {}", request.install_location.display());
-            println!();
+            writeln!(out, "IntuneWin Packager v{}\n", env!("CARGO_PKG_VERSION"))?;
+            writeln!(out, "Run ID: {}", run_id())?;
+            if args.content_stdin_tar {
+                writeln!(out, "Source: tar stream on stdin")?;
+            } else {
+                writeln!(
+                    out,
+                    "Source folder: {}",
+                    describe_location(&request.source_folder)
+                )?;
+            }
+            writeln!(out, "Setup file: {}", request.setup_file)?;
+            if request.writes_to_stdout() {
+                writeln!(out, "Output: stdout")?;
+            } else {
+                writeln!(out, "Output folder: {}", request.output_folder.display())?;
+            }
+            match &request.reproducible {
+                Some(Reproducible::ContentDigest) => {
+                    writeln!(out, "Reproducible: keys derived from the content")?
+                }
+                Some(Reproducible::Seed(_)) => {
+                    writeln!(out, "Reproducible: keys derived from the seed")?
+                }
+                None => {}
+            }
+            if let KeySource::Provided(_) = &request.encryption.keys {
+                writeln!(out, "Encryption: provided keys")?;
+            }
+            if request.encryption.digest_algorithm != DigestAlgorithm::default() {
+                writeln!(out, "File digest: {}", request.encryption.digest_algorithm)?;
+            }
+            if let Some(key) = &sign_key {
+                writeln!(out, "Signing key: {}", key.key_id())?;
+            }
+            writeln!(out)?;
 
-            let result = macos::package(request)?;
+            let result = with_limit_confirmation(|force| {
+                create(&request.clone().with_force(request.force || force))
+            })?;
 
-            println!("\nPackage created successfully:");
-            println!(
-                "  {} ({:.2} MB)",
+            writeln!(out, "\nPackage created successfully:")?;
+            writeln!(
+                out,
+                "  {} ({})",
                 result.output_path.display(),
-                result.package_size as f64 / 1_048_576.0
-            );
-            println!("  {} files included", result.file_count);
-            println!(
+                result.package_size
+            )?;
+            writeln!(
+                out,
+                "  {} files, {} directories included",
+                result.file_count, result.dir_count
+            )?;
+            writeln!(
+                out,
                 "  Creation time: {:.2}s",
                 result.creation_time.as_secs_f64()
-            );
+            )?;
+            if sign_key.is_some() {
+                writeln!(
+                    out,
+                    "  Signature: {}",
+                    signature_path(&result.output_path).display()
+                )?;
+            }
+            print_setup_info(&mut out, &result, request.setup_manifest)?;
+            print_sbom(&mut out, result.sbom.as_ref(), result.sbom_path.as_deref())?;
+            if let Some(cache) = &result.cache {
+                writeln!(
+                    out,
+                    "  Cache: {} file(s) reused ({}), {} added",
+                    cache.hits, cache.reused, cache.misses
+                )?;
+            }
+            if let Some(outcome) = result.incremental {
+                writeln!(out, "  Incremental: {}", outcome)?;
+            }
+            print_content_tags(&mut out, &result.content_tags)?;
+            if let Some(relationships) = &result.relationships {
+                print_relationships(&mut out, relationships, "  ")?;
+            }
+            print_transforms(&mut out, &result.transforms)?;
+            print_blocklist_matches(&mut out, &result.blocklist_matches, verbosity)?;
+            print_sparse_files(&result.sparse_files);
+            print_skipped_entries(&result.skipped_entries);
+            print_path_issues(&result.path_issues);
+            print_size_estimate(&result.size_estimate);
+            print_validation(&mut out, result.validation.as_ref())?;
         }
         Verbosity::Quiet => {
-            let result = macos::package(request)?;
-            println!("{}", result.output_path.display());
+            let result = create(&request)?;
+            print_blocklist_matches(&mut out, &result.blocklist_matches, verbosity)?;
+            print_sparse_files(&result.sparse_files);
+            print_skipped_entries(&result.skipped_entries);
+            print_path_issues(&result.path_issues);
+            print_size_estimate(&result.size_estimate);
+            if !request.writes_to_stdout() {
+                writeln!(out, "{}", result.output_path.display())?;
+            }
         }
         Verbosity::Silent => {
-            let _result = macos::package(request)?;
+            let _result = create(&request)?;
         }
     }
 
     Ok(())
 }
 
-#[cfg(not(feature = "macos"))]
-fn run_macos_pkg(_args: &MacosPkgArgs, _verbosity: Verbosity) -> PackageResult<()> {
-    Err(PackageError::InvalidArgument {
-        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+/// Keys from `--keys-file` or `--keys-from-env`. They are never taken from
+/// the command line, where the process list would show them.
+fn provided_keys(args: &args::IntuneCreateArgs) -> PackageResult<Option<ProvidedKeys>> {
+    let (source, text) = if let Some(path) = &args.keys_file {
+        let text = fs::read_to_string(path).map_err(|e| PackageError::InvalidArgument {
+            reason: format!("Could not read the keys file '{}': {}", path.display(), e),
+        })?;
+        (format!("'{}'", path.display()), text)
+    } else if args.keys_from_env {
+        let text = std::env::var(args::ENCRYPTION_KEYS_ENV).map_err(|_| {
+            PackageError::InvalidArgument {
+                reason: format!(
+                    "--keys-from-env needs the keys in {}",
+                    args::ENCRYPTION_KEYS_ENV
+                ),
+            }
+        })?;
+        (args::ENCRYPTION_KEYS_ENV.to_string(), text)
+    } else {
+        return Ok(None);
+    };
+
+    let text = Zeroizing::new(text);
+    ProvidedKeys::parse(&text)
+        .map(Some)
+        .map_err(|reason| PackageError::InvalidArgument {
+            reason: format!("Invalid keys in {}: {}", source, reason),
+        })
+}
+
+/// The `--sign-key` secret key, decrypted with the password in
+/// IAMAWRAPPER_SIGN_KEY_PASSWORD if it is encrypted.
+fn sign_key(path: &Path) -> PackageResult<SecretKey> {
+    let text =
+        fs::read_to_string(path)
+            .map(Zeroizing::new)
+            .map_err(|e| PackageError::SigningError {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            })?;
+    let password = std::env::var(args::SIGN_KEY_PASSWORD_ENV)
+        .ok()
+        .map(Zeroizing::new);
+    if password.is_none() && SecretKey::is_encrypted(&text) {
+        return Err(PackageError::InvalidArgument {
+            reason: format!(
+                "--sign-key '{}' is encrypted; put its password in {}",
+                path.display(),
+                args::SIGN_KEY_PASSWORD_ENV
+            ),
+        });
+    }
+    SecretKey::parse(&text, password.as_ref().map(|p| p.as_str())).map_err(|reason| {
+        PackageError::SigningError {
+            path: path.to_path_buf(),
+            reason,
+        }
     })
 }
 
-fn run_interactive_mode() -> PackageResult<()> {
-    let result = run_interactive_with_platform()?;
+/// Sign the package at `path`, writing the signature next to it.
+fn sign_package(key: &SecretKey, path: &Path) -> PackageResult<PathBuf> {
+    let signature = key.sign_file(path, &default_trusted_comment(path))?;
+    let signature_path = signature_path(path);
+    signature.save(&signature_path)?;
+    Ok(signature_path)
+}
+
+fn run_verify_signature(
+    args: &args::VerifySignatureArgs,
+    verbosity: Verbosity,
+) -> PackageResult<()> {
+    let public_key = PublicKey::load(&args.public_key)?;
+    let path = args
+        .signature
+        .clone()
+        .unwrap_or_else(|| signature_path(&args.input));
+    let signature = Signature::load(&path)?;
+    public_key.verify_file(&args.input, &signature)?;
+
+    if !verbosity.suppress_output() {
+        println!(
+            "Signature and comment signature verified (key {})",
+            public_key.key_id()
+        );
+        println!("Trusted comment: {}", signature.trusted_comment());
+    }
+    Ok(())
+}
+
+fn run_intune_extract(args: &args::IntuneExtractArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let mut request = args.to_unpack_request(verbosity);
+    if args.extract_to_zip {
+        // Read from the environment so it stays out of the process list
+        let password = Zeroizing::new(std::env::var(args::ZIP_PASSWORD_ENV).map_err(|_| {
+            PackageError::InvalidArgument {
+                reason: format!(
+                    "--extract-to-zip needs the archive password in {}",
+                    args::ZIP_PASSWORD_ENV
+                ),
+            }
+        })?);
+        request = request.with_zip_password(password.as_str());
+    }
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("IntuneWin Unpacker v{}\n", env!("CARGO_PKG_VERSION"));
+            println!("Run ID: {}", run_id());
+            println!("Input file: {}", request.input_file.display());
+            println!("Output folder: {}", request.output_folder.display());
+            println!();
+
+            let result = unpack(&request)?;
+
+            println!("\nPackage extracted successfully:");
+            println!(
+                "  {} files, {} directories extracted",
+                result.file_count, result.dir_count
+            );
+            println!("  Total size: {}", result.total_size);
+            println!(
+                "  Extraction time: {:.2}s",
+                result.unpack_time.as_secs_f64()
+            );
+            println!("  Setup file: {}", result.setup_file);
+            if let Some(archive) = &result.archive {
+                println!("  Protected archive: {}", archive.display());
+            }
+            print_content_tags(&mut io::stdout(), &result.content_tags)?;
+        }
+        Verbosity::Quiet => {
+            let result = unpack(&request)?;
+            let path = result.archive.as_ref().unwrap_or(&result.output_folder);
+            println!("{}", path.display());
+        }
+        Verbosity::Silent => {
+            let _result = unpack(&request)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_intune_verify(args: &args::IntuneVerifyArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let result = verify(&args.input_file)?;
+
+    if !verbosity.suppress_output() {
+        if args.json {
+            println!("{}", result.to_json());
+        } else {
+            print_checks(&result.checks);
+        }
+    }
+
+    if result.passed() {
+        Ok(())
+    } else {
+        Err(PackageError::VerificationFailed {
+            failures: result.failures(),
+            path: result.path,
+        })
+    }
+}
+
+/// Print one `PASS`, `FAIL` or `SKIP` line per check.
+fn print_checks(checks: &[VerifyCheck]) {
+    for check in checks {
+        match &check.outcome {
+            CheckOutcome::Pass => println!("PASS  {}", check.name),
+            CheckOutcome::Fail(reason) => println!("FAIL  {}: {}", check.name, reason),
+            CheckOutcome::Skipped => println!("SKIP  {}", check.name),
+        }
+    }
+}
+
+fn run_intune_inspect(args: &args::IntuneInspectArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let metadata = inspect_detection(&args.input_file)?;
+    let relationships = inspect_relationships(&args.input_file)?;
+
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    if args.json {
+        let mut value = metadata.to_json_value(args.show_secrets);
+        if let JsonValue::Object(members) = &mut value {
+            members.push((
+                "relationships".to_string(),
+                relationships
+                    .as_ref()
+                    .map_or(JsonValue::Null, AppRelationships::to_json_value),
+            ));
+        }
+        println!("{}", value.to_pretty_string());
+        return Ok(());
+    }
+
+    let info = &metadata.encryption_info;
+    let secret = |value: String| {
+        if args.show_secrets {
+            value
+        } else {
+            REDACTED.to_string()
+        }
+    };
+    println!("Package: {}", args.input_file.display());
+    println!("Tool version: {}", metadata.tool_version);
+    println!("Name: {}", metadata.name);
+    println!("Setup file: {}", metadata.setup_file);
+    println!(
+        "Unencrypted size: {}",
+        ByteSize(metadata.unencrypted_content_size)
+    );
+    println!("Profile: {}", info.profile_identifier);
+    println!(
+        "File digest ({}): {}",
+        info.file_digest_algorithm,
+        info.file_digest_base64()
+    );
+    println!(
+        "Encryption key ({} bits): {}",
+        info.encryption_key.len() * 8,
+        secret(info.encryption_key_base64())
+    );
+    println!(
+        "MAC key ({} bits): {}",
+        info.mac_key.len() * 8,
+        secret(info.mac_key_base64())
+    );
+    println!("IV: {}", info.iv_base64());
+    println!("MAC: {}", info.mac_base64());
+    if let Some(msi) = metadata.msi_info() {
+        println!("MSI product: {} {}", msi.product_code, msi.product_version);
+    }
+    if let Some(relationships) = &relationships {
+        print_relationships(&mut io::stdout(), relationships, "")?;
+    }
+
+    Ok(())
+}
+
+fn run_intune_list(args: &args::IntuneListArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let entries = list_contents(&args.input_file)?;
+    let relationships = inspect_relationships(&args.input_file)?;
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    if args.json {
+        println!(
+            "{}",
+            listing_json(&args.input_file, &entries, relationships.as_ref())
+        );
+        return Ok(());
+    }
+
+    let mut files = 0;
+    let mut total = 0;
+    let mut stored = 0;
+    for entry in &entries {
+        if entry.is_dir {
+            println!("  {:>10}  {:8}  {:8}  {}", "", "", "", entry.path);
+            continue;
+        }
+        println!(
+            "  {:>10}  {}  {:8}  {}",
+            entry.size.to_string(),
+            entry.crc32_hex(),
+            entry.compression,
+            entry.path
+        );
+        files += 1;
+        total += entry.size.0;
+        stored += entry.compressed_size.0;
+    }
+
+    if verbosity == Verbosity::Normal {
+        println!(
+            "\n{} file(s), {} directory(ies), {} ({} compressed)",
+            files,
+            entries.len() - files,
+            ByteSize(total),
+            ByteSize(stored)
+        );
+        if let Some(relationships) = &relationships {
+            println!();
+            print_relationships(&mut io::stdout(), relationships, "")?;
+        }
+    }
+    Ok(())
+}
+
+fn run_intune_diff(args: &args::IntuneDiffArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let diff = diff(&args.old, &args.new)?;
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    if args.json {
+        println!("{}", diff.to_json());
+        return Ok(());
+    }
+
+    if !diff.detection.is_empty() {
+        println!("Detection.xml:");
+        for change in &diff.detection {
+            println!(
+                "  ~ {}: {} -> {}",
+                change.field,
+                change.old.as_deref().unwrap_or("(none)"),
+                change.new.as_deref().unwrap_or("(none)")
+            );
+        }
+    }
+    if !diff.files.is_empty() {
+        println!("Files:");
+        for change in &diff.files {
+            match (&change.old, &change.new) {
+                (Some(old), Some(new)) => println!(
+                    "  {} {} ({} -> {}, sha256 {} -> {})",
+                    change.kind.symbol(),
+                    change.path,
+                    old.size,
+                    new.size,
+                    &old.sha256[..12],
+                    &new.sha256[..12]
+                ),
+                (Some(file), None) | (None, Some(file)) => println!(
+                    "  {} {} ({}, sha256 {})",
+                    change.kind.symbol(),
+                    change.path,
+                    file.size,
+                    &file.sha256[..12]
+                ),
+                (None, None) => {}
+            }
+        }
+    }
+
+    if diff.is_empty() {
+        println!(
+            "Packages are identical ({} files, Detection.xml fields match)",
+            diff.unchanged
+        );
+    } else if verbosity == Verbosity::Normal {
+        println!(
+            "\n{} added, {} removed, {} changed, {} unchanged; {} Detection.xml field(s) changed",
+            diff.count(ChangeKind::Added),
+            diff.count(ChangeKind::Removed),
+            diff.count(ChangeKind::Changed),
+            diff.unchanged,
+            diff.detection.len()
+        );
+    }
+    Ok(())
+}
+
+fn run_intune_analyze(args: &args::IntuneAnalyzeArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let analysis = analyze(&args.to_analyze_request())?;
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    if args.json {
+        println!("{}", analysis.to_json());
+        return Ok(());
+    }
+
+    println!("Source: {}", analysis.source_folder.display());
+    println!(
+        "Files: {}, {} (compresses to about {}, {}%)",
+        analysis.file_count,
+        ByteSize(analysis.total_size),
+        ByteSize(analysis.estimated_compressed),
+        analysis.ratio_percent()
+    );
+
+    if analysis.duplicates.is_empty() {
+        println!("\nNo duplicate files");
+    } else {
+        println!(
+            "\nDuplicate files ({} in extra copies):",
+            ByteSize(analysis.duplicate_bytes())
+        );
+        for group in &analysis.duplicates {
+            println!(
+                "  {} x {} (sha256 {})",
+                group.paths.len(),
+                ByteSize(group.size),
+                &group.sha256[..12]
+            );
+            for path in &group.paths {
+                println!("    {}", path);
+            }
+        }
+    }
+
+    if !analysis.largest.is_empty() {
+        println!("\nLargest files:");
+        for file in &analysis.largest {
+            println!(
+                "  {:>10}  {} (compresses to about {}%)",
+                ByteSize(file.size).to_string(),
+                file.path,
+                file.ratio_percent()
+            );
+        }
+    }
+
+    if !analysis.path_issues.is_empty() {
+        println!("\nPaths that would not install on Windows:");
+        for issue in &analysis.path_issues {
+            println!("  {} ({})", issue.relative_path.display(), issue.problem);
+        }
+    }
+    Ok(())
+}
+
+fn run_intune_scaffold(args: &args::IntuneScaffoldArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let result = scaffold(&args.to_scaffold_request())?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("Wrappers written for {}:", result.app_name);
+            println!("  {}", result.install_script.display());
+            println!("  {}", result.uninstall_script.display());
+            println!();
+            println!("Install command:   {}", result.install_command());
+            println!("Uninstall command: {}", result.uninstall_command());
+            println!(
+                "Detection rule:    --file \"{}\" (for intune rules)",
+                result.tag_file
+            );
+        }
+        Verbosity::Quiet => {
+            println!("{}", result.install_script.display());
+            println!("{}", result.uninstall_script.display());
+        }
+        Verbosity::Silent => {}
+    }
+    Ok(())
+}
+
+fn run_intune_repack(args: &args::IntuneRepackArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let request = args.to_repack_request(verbosity);
+
+    if verbosity == Verbosity::Normal {
+        println!("IntuneWin Repacker v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Input: {}", request.input.display());
+        println!();
+    }
+
+    let result = repack(&request)?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("Package repacked successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            for change in &result.changes {
+                println!(
+                    "  ~ {}: {} -> {}",
+                    change.field,
+                    change.old.as_deref().unwrap_or("(none)"),
+                    change.new.as_deref().unwrap_or("(none)")
+                );
+            }
+            println!("  Encrypted with new keys");
+            println!("  Repack time: {:.2}s", result.repack_time.as_secs_f64());
+        }
+        Verbosity::Quiet => println!("{}", result.output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+fn run_intune_rules(args: &args::IntuneRulesArgs, verbosity: Verbosity) -> PackageResult<()> {
+    let request = args.to_rules_request();
+    let rules = detection_rules(&request)?;
+
+    let output_path = request.output_path();
+    std::fs::write(&output_path, rules.to_json())
+        .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("Setup file: {}", rules.setup_file);
+            if let Some(msi) = &rules.msi {
+                println!("  Product code: {}", msi.product_code);
+                println!("  Product version: {}", msi.product_version);
+            }
+            println!(
+                "{} detection rule(s) written to {}",
+                rules.rules.len(),
+                output_path.display()
+            );
+        }
+        Verbosity::Quiet => println!("{}", output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+fn run_intune_manifest(
+    args: &args::IntuneManifestArgs,
+    config: Config,
+    verbosity: Verbosity,
+) -> PackageResult<()> {
+    let request = args.to_manifest_request(config.app);
+    let manifest = app_manifest(&request)?;
+
+    let output_path = request.output_path();
+    std::fs::write(&output_path, manifest.to_json())
+        .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("App: {} ({})", manifest.display_name, manifest.publisher);
+            println!("  Setup file: {}", manifest.detection.setup_file);
+            println!("  Install: {}", manifest.install_command);
+            println!("  Uninstall: {}", manifest.uninstall_command);
+            println!(
+                "Win32 app manifest with {} detection rule(s) written to {}",
+                manifest.detection.rules.len(),
+                output_path.display()
+            );
+        }
+        Verbosity::Quiet => println!("{}", output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+fn run_macos_command(
+    cmd: &args::MacosCommand,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    match &cmd.action {
+        MacosAction::Pkg(pkg_args) => run_macos_pkg(pkg_args, verbosity, strictness),
+        MacosAction::Dmg(dmg_args) => run_macos_dmg(dmg_args, verbosity),
+        MacosAction::Repack(repack_args) => run_macos_repack(repack_args, verbosity, strictness),
+        MacosAction::Rewrap(rewrap_args) => run_macos_rewrap(rewrap_args, verbosity, strictness),
+        MacosAction::Extract(extract_args) => run_macos_extract(extract_args, verbosity),
+        MacosAction::Validate(validate_args) => run_macos_validate(validate_args, verbosity),
+        MacosAction::Notarize(notarize_args) => run_macos_notarize(notarize_args, verbosity),
+        MacosAction::Batch(batch_args) => run_macos_batch(batch_args, verbosity, strictness),
+    }
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_batch(
+    args: &BatchArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    use crate::packager::batch::package_macos_batch;
+
+    let manifest = load_batch_manifest(&args.manifest)?;
+    let options = batch_options(args, verbosity, strictness);
+    if verbosity == Verbosity::Normal && !args.json {
+        print_batch_start(args, &manifest, &options);
+    }
+    print_batch_report(
+        args.json,
+        &package_macos_batch(&manifest, &options),
+        verbosity,
+        "application(s) packaged",
+    )
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_batch(
+    _args: &BatchArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+fn batch_options(args: &BatchArgs, verbosity: Verbosity, strictness: &Strictness) -> BatchOptions {
+    let mut options = BatchOptions::default()
+        .with_jobs(args.jobs)
+        .with_verbosity(verbosity)
+        .with_strictness(strictness.clone());
+    if let Some(output) = &args.output_folder {
+        options = options.with_output_folder(output);
+    }
+    options
+}
+
+fn print_batch_start(args: &BatchArgs, manifest: &BatchManifest, options: &BatchOptions) {
+    let jobs = match options.jobs {
+        0 => "one per CPU".to_string(),
+        jobs => jobs.to_string(),
+    };
+    println!("Run ID: {}", run_id());
+    println!(
+        "Packaging {} application(s) from {} (jobs: {})\n",
+        manifest.items.len(),
+        args.manifest.display(),
+        jobs
+    );
+}
+
+/// Print the outcome of every item and fail when any item did; `done`
+/// ends the summary line (e.g., "application(s) packaged").
+fn print_batch_report(
+    json: bool,
+    report: &BatchReport,
+    verbosity: Verbosity,
+    done: &str,
+) -> PackageResult<()> {
+    if json && !verbosity.suppress_output() {
+        println!("{}", report.to_json());
+    } else if verbosity == Verbosity::Normal {
+        let width = report
+            .items
+            .iter()
+            .map(|item| item.name.chars().count())
+            .chain(std::iter::once(4))
+            .max()
+            .unwrap_or(4);
+        println!("{:<width$}  {:<6}  {:>8}  RESULT", "NAME", "STATUS", "TIME");
+        for item in &report.items {
+            let (status, result) = match &item.outcome {
+                BatchOutcome::Packaged {
+                    output_path,
+                    package_size,
+                } => (
+                    "ok",
+                    format!("{} ({})", output_path.display(), package_size),
+                ),
+                BatchOutcome::Failed(error) => ("FAILED", error.to_string()),
+            };
+            println!(
+                "{:<width$}  {:<6}  {:>7.2}s  {}",
+                item.name,
+                status,
+                item.duration.as_secs_f64(),
+                result
+            );
+        }
+        println!(
+            "\n{} of {} {} in {:.2}s",
+            report.items.len() - report.failed(),
+            report.items.len(),
+            done,
+            report.elapsed.as_secs_f64()
+        );
+    } else if verbosity == Verbosity::Quiet {
+        for item in &report.items {
+            match &item.outcome {
+                BatchOutcome::Packaged { output_path, .. } => {
+                    println!("{}", output_path.display())
+                }
+                BatchOutcome::Failed(error) => error!("{}: {}", item.name, error),
+            }
+        }
+    }
+
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(PackageError::BatchFailed {
+            failures: report.failed(),
+            total: report.items.len(),
+        })
+    }
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_pkg(
+    args: &MacosPkgArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    use crate::macos;
+    use crate::models::macos::{
+        self as macos_models, COMPONENT_INSTALL_LOCATION, DistributionOptions, MacosPkgRequest,
+    };
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    // Determine output folder and filename from output path; a name
+    // template names the file inside the output folder instead
+    let (output_folder, output_name) = if args.name_template.is_some() {
+        (args.output.clone(), None)
+    } else {
+        let output_folder = args
+            .output
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+        let output_name = args
+            .output
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(String::from);
+        (output_folder, output_name)
+    };
+
+    // Create request
+    let mut request = match (&args.component, &args.content_folder) {
+        (Some(app), _) => {
+            let bundle = macos::bundle::read_app_bundle(app)?;
+            let identifier = args
+                .identifier
+                .clone()
+                .unwrap_or_else(|| bundle.identifier.clone());
+            let version = match (&args.version, bundle.version()) {
+                (Some(version), _) => version.clone(),
+                (None, Some(version)) => version.to_string(),
+                (None, None) => {
+                    return Err(PackageError::InvalidBundle {
+                        path: app.clone(),
+                        reason: "Info.plist has no CFBundleShortVersionString or \
+                                 CFBundleVersion (use --version)"
+                            .to_string(),
+                    });
+                }
+            };
+            MacosPkgRequest::new(app.clone(), identifier, version, output_folder)
+                .with_component(true)
+                .with_install_location(COMPONENT_INSTALL_LOCATION)
+        }
+        (None, Some(content_folder)) => {
+            // Validate source folder exists; remote sources are checked as
+            // they are downloaded
+            if !content_folder.exists() && !is_remote(content_folder) {
+                return Err(PackageError::SourceFolderNotFound {
+                    path: content_folder.clone(),
+                });
+            }
+
+            // Check source folder is not empty; archives are checked as
+            // they are extracted
+            let is_empty = SourceArchive::detect(content_folder).is_none()
+                && !is_remote(content_folder)
+                && content_folder
+                    .read_dir()
+                    .map(|mut i| i.next().is_none())
+                    .unwrap_or(true);
+            if is_empty {
+                return Err(PackageError::SourceFolderEmpty {
+                    path: content_folder.clone(),
+                });
+            }
+
+            MacosPkgRequest::new(
+                content_folder.clone(),
+                args.identifier.clone().unwrap_or_default(),
+                args.version.clone().unwrap_or_default(),
+                output_folder,
+            )
+        }
+        // One component per --arch-payload, each with its own folder, or
+        // no payload at all
+        (None, None) => MacosPkgRequest::new(
+            PathBuf::new(),
+            args.identifier.clone().unwrap_or_default(),
+            args.version.clone().unwrap_or_default(),
+            output_folder,
+        )
+        .with_no_payload(args.no_payload),
+    };
+    if let Some(install_location) = &args.install_location {
+        request.set_install_location(PathBuf::from(install_location));
+    }
+    request = request
+        .with_allow_special_modes(args.allow_special_modes)
+        .with_allow_dangerous_source(args.i_know_what_im_doing)
+        .with_source_limits(args::source_limits(args.max_files, args.max_size_gb))
+        .with_force(args.force)
+        .with_compression(args::compression(args.compression, args.level))
+        .with_io_retry(RetryPolicy::io(args.io_retries))
+        .with_verbosity(verbosity)
+        .with_strictness(strictness.clone());
+
+    if args.post_validate {
+        request = request.with_post_validate(Duration::from_secs(args.post_validate_timeout));
+    }
+
+    if let Some(name) = output_name {
+        request = request.with_output_name(name);
+    }
+    request.set_name_template(args.name_template.clone());
+
+    if let Some(scripts) = &args.scripts_folder {
+        request = request.with_scripts_folder(scripts.clone());
+    }
+
+    if args.preinstall_script.as_deref() == Some(Path::new("-"))
+        && args.postinstall_script.as_deref() == Some(Path::new("-"))
+    {
+        return Err(PackageError::InvalidArgument {
+            reason: "Only one script can be read from stdin".to_string(),
+        });
+    }
+    if let Some(script) = &args.preinstall_script {
+        request = request.with_preinstall_script(script_source(script)?);
+    }
+    if let Some(script) = &args.postinstall_script {
+        request = request.with_postinstall_script(script_source(script)?);
+    }
+    for template in &args.script_templates {
+        let template = template
+            .parse()
+            .map_err(|reason| PackageError::InvalidArgument { reason })?;
+        request = request.with_script_template(template);
+    }
+
+    let mut distribution = DistributionOptions {
+        title: args.title.clone(),
+        welcome: args.welcome.clone(),
+        license: args.license.clone(),
+        readme: args.readme.clone(),
+        conclusion: args.conclusion.clone(),
+        background: args.background.clone(),
+        resources_dir: args.resources.clone(),
+        min_os_version: args.min_os_version.clone(),
+        ..DistributionOptions::default()
+    };
+    if let Some(customize) = &args.customize {
+        distribution.customize = customize
+            .parse()
+            .map_err(|reason| PackageError::InvalidArgument { reason })?;
+    }
+    for domain in &args.domains {
+        let domain = domain
+            .parse()
+            .map_err(|reason| PackageError::InvalidArgument { reason })?;
+        distribution = distribution.with_domain(domain);
+    }
+    request.set_distribution(distribution);
+
+    if let Some(blocklist) = &args.blocklist {
+        request = request.with_blocklist(
+            blocklist.clone(),
+            args::blocklist_action(args.blocklist_warn),
+        );
+    }
+
+    for rule in &args.transforms {
+        request = request.with_transform(rule.clone());
+    }
+
+    for glob in &args.include {
+        request = request.with_include(glob.clone());
+    }
+
+    for glob in &args.exclude {
+        request = request.with_exclude(glob.clone());
+    }
+
+    if let Some(sbom) = args::sbom_options(args.sbom, args.embed_sbom) {
+        request = request.with_sbom(sbom);
+    }
+
+    if let Some(signing) = signing(args.sign_identity.as_ref(), args.sign_cert.as_ref()) {
+        request = request.with_signing(signing);
+    }
+
+    if !args.arch_payloads.is_empty() {
+        let payloads = args
+            .arch_payloads
+            .iter()
+            .map(|spec| {
+                macos_models::ArchPayload::parse(spec)
+                    .map_err(|reason| PackageError::InvalidArgument { reason })
+            })
+            .collect::<PackageResult<Vec<_>>>()?;
+        let distribution =
+            macos_models::MacosDistributionRequest::for_architectures(&request, &payloads);
+        return run_macos_arch_distribution(&distribution, verbosity);
+    }
+
+    if args.dry_run {
+        return print_dry_run(&macos::dry_run(request)?, verbosity, args.json);
+    }
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("macOS Package Builder v{}\n", env!("CARGO_PKG_VERSION"));
+            println!("Run ID: {}", run_id());
+            if request.no_payload {
+                println!("Payload: none (scripts only)");
+            } else {
+                let source_label = if request.component {
+                    "Component"
+                } else {
+                    "Source folder"
+                };
+                println!(
+                    "{}: {}",
+                    source_label,
+                    describe_location(&request.source_folder)
+                );
+            }
+            println!("Identifier: {}", request.identifier);
+            println!("Version: {}", request.version);
+            if let Some(title) = &request.distribution.title {
+                println!("Title: {}", title);
+            }
+            println!("Install location: {}", request.install_location.display());
+            println!();
+
+            let result = with_limit_confirmation(|force| {
+                macos::package(request.clone().with_force(request.force || force))
+            })?;
+
+            println!("\nPackage created successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            if request.no_payload {
+                println!("  Payload-free: scripts only, no receipt is recorded");
+            } else {
+                println!(
+                    "  {} files, {} directories, {} symlinks included ({} KB installed)",
+                    result.payload.files,
+                    result.payload.directories,
+                    result.payload.symlinks,
+                    result.payload.install_kbytes
+                );
+            }
+            if let Some(subject) = &result.signed_by {
+                println!("  Signed by: {}", subject);
+            }
+            println!(
+                "  Creation time: {:.2}s",
+                result.creation_time.as_secs_f64()
+            );
+            print_sbom(
+                &mut io::stdout(),
+                result.sbom.as_ref(),
+                result.sbom_path.as_deref(),
+            )?;
+            print_transforms(&mut io::stdout(), &result.transforms)?;
+            print_blocklist_matches(&mut io::stdout(), &result.blocklist_matches, verbosity)?;
+            print_special_modes(&result.special_mode_files, result.special_modes_preserved);
+            print_sparse_files(&result.sparse_files);
+            print_skipped_entries(&result.skipped_entries);
+            print_validation(&mut io::stdout(), result.validation.as_ref())?;
+        }
+        Verbosity::Quiet => {
+            let result = macos::package(request)?;
+            print_blocklist_matches(&mut io::stdout(), &result.blocklist_matches, verbosity)?;
+            print_special_modes(&result.special_mode_files, result.special_modes_preserved);
+            print_sparse_files(&result.sparse_files);
+            print_skipped_entries(&result.skipped_entries);
+            println!("{}", result.output_path.display());
+        }
+        Verbosity::Silent => {
+            let _result = macos::package(request)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build a distribution with one component per `--arch-payload`.
+#[cfg(feature = "macos")]
+fn run_macos_arch_distribution(
+    request: &crate::models::macos::MacosDistributionRequest,
+    verbosity: Verbosity,
+) -> PackageResult<()> {
+    use crate::macos::distribution::package_distribution;
+
+    let build = |force| package_distribution(&request.clone().with_force(force));
+    match verbosity {
+        Verbosity::Normal => {
+            println!("macOS Package Builder v{}\n", env!("CARGO_PKG_VERSION"));
+            println!("Run ID: {}", run_id());
+            println!("Identifier: {}", request.identifier);
+            println!("Version: {}", request.version);
+            for component in &request.components {
+                println!(
+                    "Payload ({}): {}",
+                    component
+                        .host_architecture
+                        .map_or("any", |arch| arch.name()),
+                    component.source_folder.display()
+                );
+            }
+            println!();
+
+            let result = with_limit_confirmation(build)?;
+
+            println!("\nPackage created successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            for component in &result.components {
+                println!(
+                    "  {}: {} files ({})",
+                    component.identifier,
+                    component.file_count,
+                    component
+                        .host_architecture
+                        .map_or("any architecture", |arch| arch.name())
+                );
+            }
+            if let Some(subject) = &result.signed_by {
+                println!("  Signed by: {}", subject);
+            }
+            println!(
+                "  Creation time: {:.2}s",
+                result.creation_time.as_secs_f64()
+            );
+            for component in &result.components {
+                print_transforms(&mut io::stdout(), &component.transforms)?;
+                print_blocklist_matches(
+                    &mut io::stdout(),
+                    &component.blocklist_matches,
+                    verbosity,
+                )?;
+            }
+            print_validation(&mut io::stdout(), result.validation.as_ref())?;
+        }
+        Verbosity::Quiet => {
+            let result = build(false)?;
+            for component in &result.components {
+                print_blocklist_matches(
+                    &mut io::stdout(),
+                    &component.blocklist_matches,
+                    verbosity,
+                )?;
+            }
+            println!("{}", result.output_path.display());
+        }
+        Verbosity::Silent => {
+            build(false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Signing settings from `--sign-identity`/`--sign-cert`, with a PKCS#12
+/// password from the environment.
+#[cfg(feature = "macos")]
+fn signing(
+    identity: Option<&std::path::PathBuf>,
+    certificate: Option<&std::path::PathBuf>,
+) -> Option<crate::models::macos::MacosSigning> {
+    let mut signing = crate::models::macos::MacosSigning::new(identity?.clone());
+    if let Some(certificate) = certificate {
+        signing = signing.with_certificate(certificate.clone());
+    }
+    if let Ok(password) = std::env::var(args::SIGN_PASSWORD_ENV).map(Zeroizing::new) {
+        signing = signing.with_password(password.as_str());
+    }
+    Some(signing)
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_repack(
+    args: &MacosRepackArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    use crate::macos::repack::{PackageSigner, repack_with_signer};
+    use crate::macos::sign::Signer;
+    use crate::models::macos::MacosRepackRequest;
+
+    let mut request = MacosRepackRequest::new(args.input.clone(), args.output.clone())
+        .with_keep_signature(args.keep_signature)
+        .with_verbosity(verbosity)
+        .with_strictness(strictness.clone());
+    if let Some(title) = &args.title {
+        request = request.with_title(title.clone());
+    }
+    if let Some(license) = &args.license {
+        request = request.with_license(license.clone());
+    }
+    for resource in &args.resources {
+        request = request.with_resource(resource.clone());
+    }
+
+    if verbosity == Verbosity::Normal {
+        println!("macOS Package Repacker v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Input: {}", request.input.display());
+        println!();
+    }
+
+    let signer = signing(args.sign_identity.as_ref(), args.sign_cert.as_ref())
+        .map(|signing| Signer::load(&signing))
+        .transpose()?;
+    let result = repack_with_signer(&request, signer.as_ref().map(|s| s as &dyn PackageSigner))?;
+
+    // Losing a signature is never silent
+    if let Some(warning) = &result.signature_warning {
+        warn!("{}", warning);
+    }
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("\nPackage repacked successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            for resource in &result.resources {
+                println!("  + {}", resource);
+            }
+            if let Some(signer) = &signer {
+                println!("  Signed by: {}", signer.subject());
+            }
+            println!("  Repack time: {:.2}s", result.repack_time.as_secs_f64());
+        }
+        Verbosity::Quiet => println!("{}", result.output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_repack(
+    _args: &MacosRepackArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_rewrap(
+    args: &MacosRewrapArgs,
+    verbosity: Verbosity,
+    strictness: &Strictness,
+) -> PackageResult<()> {
+    use crate::macos::repack::PackageSigner;
+    use crate::macos::rewrap::rewrap_with_signer;
+    use crate::macos::sign::Signer;
+    use crate::models::macos::MacosRewrapRequest;
+
+    let mut request = MacosRewrapRequest::new(args.input.clone(), args.scripts_folder.clone())
+        .with_verbosity(verbosity)
+        .with_strictness(strictness.clone());
+    if let Some(output) = &args.output {
+        request = request.with_output(output.clone());
+    }
+    if let Some(component) = &args.component {
+        request = request.with_component(component.clone());
+    }
+
+    if verbosity == Verbosity::Normal {
+        println!("macOS Package Rewrapper v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Input: {}", request.input.display());
+        println!("Scripts: {}", request.scripts_folder.display());
+        println!();
+    }
+
+    let signer = signing(args.sign_identity.as_ref(), args.sign_cert.as_ref())
+        .map(|signing| Signer::load(&signing))
+        .transpose()?;
+    let result = rewrap_with_signer(&request, signer.as_ref().map(|s| s as &dyn PackageSigner))?;
+
+    // Losing a signature is never silent
+    if let Some(warning) = &result.signature_warning {
+        warn!("{}", warning);
+    }
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("\nPackage rewrapped successfully:");
+            println!(
+                "  {} ({})",
+                result.output_path.display(),
+                result.package_size
+            );
+            println!("  Component: {}", result.component);
+            for script in &result.added {
+                println!("  + {}", script);
+            }
+            for script in &result.replaced {
+                println!("  ~ {}", script);
+            }
+            if let Some(signer) = &signer {
+                println!("  Signed by: {}", signer.subject());
+            }
+            println!("  Rewrap time: {:.2}s", result.rewrap_time.as_secs_f64());
+        }
+        Verbosity::Quiet => println!("{}", result.output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_rewrap(
+    _args: &MacosRewrapArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_dmg(args: &MacosDmgArgs, verbosity: Verbosity) -> PackageResult<()> {
+    use crate::macos::dmg::create_dmg;
+    use crate::models::macos::MacosDmgRequest;
+
+    let format = args
+        .format
+        .parse()
+        .map_err(|reason| PackageError::InvalidArgument { reason })?;
+    let mut request = MacosDmgRequest::new(args.content_folder.clone(), args.output.clone())
+        .with_format(format)
+        .with_verbosity(verbosity);
+    if let Some(name) = &args.volume_name {
+        request = request.with_volume_name(name.clone());
+    }
+    if let Some(license) = &args.license {
+        request = request.with_license(license.clone());
+    }
+
+    if verbosity == Verbosity::Normal {
+        println!("macOS Disk Image Builder v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Content folder: {}", request.source_folder.display());
+        println!("Volume name: {}", request.volume_name());
+        println!("Format: {}", request.format);
+        println!();
+    }
+
+    let result = create_dmg(&request)?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("Disk image created successfully:");
+            println!("  {} ({})", result.output_path.display(), result.image_size);
+            println!(
+                "  {} files on a {} volume",
+                result.file_count, result.volume_size
+            );
+            println!("  SHA-256: {}", result.sha256);
+            println!(
+                "  Creation time: {:.2}s",
+                result.creation_time.as_secs_f64()
+            );
+        }
+        Verbosity::Quiet => println!("{}", result.output_path.display()),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_dmg(_args: &MacosDmgArgs, _verbosity: Verbosity) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_extract(args: &MacosExtractArgs, verbosity: Verbosity) -> PackageResult<()> {
+    use crate::macos::extract::extract;
+    use crate::models::macos::MacosExtractRequest;
+
+    let request = MacosExtractRequest::new(args.input_file.clone(), args.output_folder.clone())
+        .with_verbosity(verbosity);
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("macOS Package Extractor v{}\n", env!("CARGO_PKG_VERSION"));
+            println!("Run ID: {}", run_id());
+            println!("Input file: {}", request.input_file.display());
+            println!("Output folder: {}", request.output_folder.display());
+            println!();
+
+            let result = extract(&request)?;
+
+            println!("\nPackage extracted successfully:");
+            println!(
+                "  {} files, {} directories extracted",
+                result.file_count, result.dir_count
+            );
+            println!("  Total size: {}", result.total_size);
+            println!(
+                "  Extraction time: {:.2}s",
+                result.extract_time.as_secs_f64()
+            );
+            for archive in &result.archives {
+                println!("  Expanded: {}", archive.display());
+            }
+        }
+        Verbosity::Quiet => {
+            let result = extract(&request)?;
+            println!("{}", result.output_folder.display());
+        }
+        Verbosity::Silent => {
+            let _result = extract(&request)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_extract(_args: &MacosExtractArgs, _verbosity: Verbosity) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_validate(args: &MacosValidateArgs, verbosity: Verbosity) -> PackageResult<()> {
+    use crate::macos::validate::validate_pkg_report;
+
+    let report = validate_pkg_report(args.input())?;
+
+    if !verbosity.suppress_output() {
+        if args.json {
+            println!("{}", report.to_json());
+        } else {
+            print_checks(&report.checks);
+            for (identifier, version) in &report.components {
+                println!("Component: {} {}", identifier, version);
+            }
+        }
+    }
+
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(PackageError::VerificationFailed {
+            failures: report.failures(),
+            path: report.path,
+        })
+    }
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_validate(_args: &MacosValidateArgs, _verbosity: Verbosity) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(feature = "macos")]
+fn run_macos_notarize(args: &MacosNotarizeArgs, verbosity: Verbosity) -> PackageResult<()> {
+    use crate::macos::notarize::notarize;
+    use crate::models::macos::{NotarizeRequest, NotaryCredentials};
+
+    let credentials = NotaryCredentials::new(&args.key_id, &args.issuer_id, &args.key);
+    let request = NotarizeRequest::new(&args.input_file, credentials)
+        .with_timeout(Duration::from_secs(args.timeout_minutes * 60))
+        .with_staple(!args.no_staple)
+        .with_verbosity(verbosity);
+
+    if verbosity == Verbosity::Normal {
+        println!("macOS Package Notarizer v{}\n", env!("CARGO_PKG_VERSION"));
+        println!("Run ID: {}", run_id());
+        println!("Input file: {}", request.input_file.display());
+        println!("API key: {}", request.credentials.key_id);
+        println!();
+    }
+
+    let result = notarize(&request)?;
+
+    match verbosity {
+        Verbosity::Normal => {
+            println!("\nPackage notarized successfully:");
+            println!("  {}", result.input_file.display());
+            println!("  Submission ID: {}", result.submission_id);
+            match result.stapled_ticket {
+                Some(size) => println!("  Stapled ticket: {}", size),
+                None => println!("  Ticket not stapled"),
+            }
+            println!(
+                "  Notarization time: {:.2}s",
+                result.notarize_time.as_secs_f64()
+            );
+        }
+        Verbosity::Quiet => println!("{}", result.submission_id),
+        Verbosity::Silent => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_notarize(_args: &MacosNotarizeArgs, _verbosity: Verbosity) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+/// A script file, or the script read from stdin for `-`.
+#[cfg(feature = "macos")]
+fn script_source(path: &std::path::Path) -> PackageResult<crate::models::macos::ScriptSource> {
+    use crate::models::macos::ScriptSource;
+    use std::io::Read;
+
+    if path.as_os_str() != "-" {
+        return Ok(ScriptSource::File(path.to_path_buf()));
+    }
+    let mut script = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut script)
+        .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    Ok(ScriptSource::Inline(script))
+}
+
+/// Report setuid/setgid/sticky handling on stderr so it is never silent.
+#[cfg(any(feature = "macos", feature = "linux"))]
+fn print_special_modes(files: &[std::path::PathBuf], preserved: bool) {
+    if files.is_empty() {
+        return;
+    }
+
+    let paths = indented_list(files.iter().map(|p| p.display()));
+    if preserved {
+        tracing::info!(
+            "preserved setuid/setgid/sticky bits on {} entr(ies):{}",
+            files.len(),
+            paths
+        );
+    } else {
+        warn!(
+            "stripped setuid/setgid/sticky bits from {} entr(ies) \
+             (use --allow-special-modes to keep them):{}",
+            files.len(),
+            paths
+        );
+    }
+}
+
+#[cfg(not(feature = "macos"))]
+fn run_macos_pkg(
+    _args: &MacosPkgArgs,
+    _verbosity: Verbosity,
+    _strictness: &Strictness,
+) -> PackageResult<()> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+/// List content tags in a run summary.
+fn print_content_tags(out: &mut dyn Write, tags: &[(String, String)]) -> io::Result<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "  Content tags:")?;
+    for (key, value) in tags {
+        writeln!(out, "    {} = {}", key, value)?;
+    }
+    Ok(())
+}
+
+/// List an app's dependencies and superseded apps, each heading indented
+/// by `indent`.
+fn print_relationships(
+    out: &mut dyn Write,
+    relationships: &AppRelationships,
+    indent: &str,
+) -> io::Result<()> {
+    let describe = |app: &str, id: &Option<String>, version: &Option<String>| {
+        let mut text = app.to_string();
+        if let Some(version) = version {
+            text = format!("{} {}", text, version);
+        }
+        if let Some(id) = id {
+            text = format!("{} [{}]", text, id);
+        }
+        text
+    };
+
+    if !relationships.dependencies.is_empty() {
+        writeln!(out, "{}Dependencies:", indent)?;
+        for dependency in &relationships.dependencies {
+            writeln!(
+                out,
+                "{}  {} ({})",
+                indent,
+                describe(&dependency.app, &dependency.id, &dependency.version),
+                if dependency.auto_install {
+                    "installed if missing"
+                } else {
+                    "detected only"
+                }
+            )?;
+        }
+    }
+    if !relationships.supersedes.is_empty() {
+        writeln!(out, "{}Supersedes:", indent)?;
+        for superseded in &relationships.supersedes {
+            writeln!(
+                out,
+                "{}  {} ({})",
+                indent,
+                describe(&superseded.app, &superseded.id, &superseded.version),
+                if superseded.uninstall {
+                    "uninstalled first"
+                } else {
+                    "updated in place"
+                }
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the setup file's version information and where it was written.
+fn print_setup_info(
+    out: &mut dyn Write,
+    result: &IntuneWinPackage,
+    manifest_requested: bool,
+) -> io::Result<()> {
+    if let Some(info) = &result.setup_info {
+        writeln!(out, "  Setup: {}", info.summary())?;
+    }
+    match &result.setup_manifest {
+        Some(path) => writeln!(out, "  Setup manifest: {}", path.display()),
+        None if manifest_requested => writeln!(
+            out,
+            "  Setup manifest: not written (no version information in the setup file)"
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Summarize the SBOM and where it was written.
+fn print_sbom(out: &mut dyn Write, sbom: Option<&Sbom>, path: Option<&Path>) -> io::Result<()> {
+    let Some(sbom) = sbom else {
+        return Ok(());
+    };
+    let place = match path {
+        Some(path) => path.display().to_string(),
+        None => "embedded".to_string(),
+    };
+    writeln!(
+        out,
+        "  SBOM: {} ({} file(s), {} product(s))",
+        place,
+        sbom.files.len(),
+        sbom.products.len()
+    )
+}
+
+/// Warn about special entries that were left out of the package.
+fn print_skipped_entries(entries: &[SkippedEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+
+    warn!(
+        "skipped {} entr(ies) that cannot be packaged:{}",
+        entries.len(),
+        indented_list(entries.iter().map(|entry| format!(
+            "{} ({})",
+            entry.relative_path.display(),
+            entry.kind
+        )))
+    );
+}
+
+/// Warn about content paths that would not install on Windows.
+fn print_path_issues(issues: &[PathIssue]) {
+    if issues.is_empty() {
+        return;
+    }
+
+    warn!(
+        "{} path(s) would not install on Windows:{}",
+        issues.len(),
+        indented_list(issues.iter().map(|issue| format!(
+            "{} ({})",
+            issue.relative_path.display(),
+            issue.problem
+        )))
+    );
+}
+
+/// Print what a dry run would package, then its warnings.
+fn print_dry_run(report: &DryRunReport, verbosity: Verbosity, json: bool) -> PackageResult<()> {
+    let mut out = io::stdout();
+    if json {
+        if !verbosity.suppress_output() {
+            writeln!(out, "{}", report.to_json())?;
+        }
+        return Ok(());
+    }
+    if verbosity == Verbosity::Normal {
+        writeln!(out, "Dry run: nothing was written")?;
+        let exists = if report.output_exists {
+            " (already exists)"
+        } else {
+            ""
+        };
+        writeln!(out, "  Output: {}{}", report.output_path.display(), exists)?;
+        writeln!(
+            out,
+            "  {} files, {} directories ({})",
+            report.file_count, report.dir_count, report.total_size
+        )?;
+        if let Some(estimate) = &report.size_estimate {
+            writeln!(
+                out,
+                "  Package size: at most {} (limit: {})",
+                estimate.projected, estimate.limit
+            )?;
+        }
+        writeln!(
+            out,
+            "  Estimated time: {:.0}s",
+            report.estimated_time.as_secs_f64().ceil()
+        )?;
+        for (label, value) in &report.metadata {
+            writeln!(out, "  {}: {}", label, value)?;
+        }
+    }
+    print_blocklist_matches(&mut out, &report.blocklist_matches, verbosity)?;
+    if verbosity.suppress_output() {
+        return Ok(());
+    }
+    print_sparse_files(&report.sparse_files);
+    print_skipped_entries(&report.skipped_entries);
+    print_path_issues(&report.path_issues);
+    if let Some(estimate) = &report.size_estimate {
+        print_size_estimate(estimate);
+    }
+    if verbosity == Verbosity::Quiet {
+        writeln!(out, "{}", report.output_path.display())?;
+    }
+    Ok(())
+}
+
+/// Warn when the package may be larger than its size limit.
+fn print_size_estimate(estimate: &SizeEstimate) {
+    if estimate.exceeds_limit() {
+        warn!(
+            "The package may reach {} with its content uncompressed, above the size limit of {}",
+            estimate.projected, estimate.limit
+        );
+    }
+}
+
+/// Warn about sparse files, which packages store and install at full size.
+fn print_sparse_files(files: &[SparseFile]) {
+    if files.is_empty() {
+        return;
+    }
+
+    let lines = files.iter().map(|file| match file.allocated {
+        Some(allocated) => format!(
+            "{} ({}, {} allocated in the source)",
+            file.relative_path.display(),
+            ByteSize(file.size),
+            ByteSize(allocated)
+        ),
+        None => format!("{} ({})", file.relative_path.display(), ByteSize(file.size)),
+    });
+    warn!(
+        "{} sparse file(s) will be fully allocated when installed:{}",
+        files.len(),
+        indented_list(lines)
+    );
+}
+
+/// One indented line per item, each starting with a line break, to list
+/// the subjects of a warning in a single log event.
+fn indented_list<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| format!("\n  {}", item))
+        .collect()
+}
+
+/// List the files rewritten by transform rules in a run summary.
+fn print_transforms(out: &mut dyn Write, records: &[TransformRecord]) -> io::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "  Transformed files:")?;
+    for record in records {
+        writeln!(
+            out,
+            "    {} ({})",
+            record.path.display(),
+            record.transformers.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+/// Report blocklist matches from a warn-mode run.
+///
+/// Normal mode lists them in the run summary on `out`; quiet mode writes
+/// them to stderr so stdout stays machine-readable.
+fn print_blocklist_matches(
+    out: &mut dyn Write,
+    matches: &[BlocklistMatch],
+    verbosity: Verbosity,
+) -> io::Result<()> {
+    if matches.is_empty() || verbosity.suppress_output() {
+        return Ok(());
+    }
+
+    let lines = matches.iter().map(|m| match &m.description {
+        Some(description) => format!("  {} ({}): {}", m.path.display(), m.sha256, description),
+        None => format!("  {} ({})", m.path.display(), m.sha256),
+    });
+
+    if verbosity == Verbosity::Normal {
+        writeln!(
+            out,
+            "  Warning: {} blocklisted file(s) packaged:",
+            matches.len()
+        )?;
+        for line in lines {
+            writeln!(out, "  {}", line)?;
+        }
+    } else {
+        warn!(
+            "{} blocklisted file(s) packaged:{}",
+            matches.len(),
+            lines.map(|line| format!("\n{}", line)).collect::<String>()
+        );
+    }
+    Ok(())
+}
+
+fn print_validation(out: &mut dyn Write, report: Option<&ValidationReport>) -> io::Result<()> {
+    if let Some(report) = report {
+        writeln!(
+            out,
+            "  Post-validation passed ({}) in {:.2}s",
+            report.checks.join(", "),
+            report.duration.as_secs_f64()
+        )?;
+    }
+    Ok(())
+}
+
+/// Run a packaging step, asking to continue if the source exceeds the limits.
+///
+/// `run` is called with `force = false` first; on a size-limit error and an
+/// interactive terminal the user is asked and `run(true)` is retried.
+/// Without a terminal the error is returned as is.
+fn with_limit_confirmation<T>(mut run: impl FnMut(bool) -> PackageResult<T>) -> PackageResult<T> {
+    match run(false) {
+        Err(PackageError::SourceTooLarge { path, reason }) if console::can_prompt() => {
+            let proceed = Confirm::new()
+                .with_prompt(format!(
+                    "Source folder {} is unusually large ({}). Package anyway?",
+                    path.display(),
+                    reason
+                ))
+                .default(false)
+                .interact()
+                .map_err(|e| PackageError::Io(io::Error::other(e)))?;
+
+            if !proceed {
+                return Err(PackageError::Cancelled);
+            }
+            run(true)
+        }
+        other => other,
+    }
+}
+
+fn run_interactive_mode(no_history: bool) -> PackageResult<()> {
+    let path = if no_history { None } else { history_path() };
+    let mut history = path
+        .as_deref()
+        .map(InteractiveHistory::load_from)
+        .unwrap_or_default();
+    let result = run_interactive_with_history(&mut history)?;
+
+    // Remembering answers is a convenience, so failing to is not an error
+    if let Some(path) = &path {
+        if let Err(e) = history.save_to(path) {
+            warn!("Could not remember the answers: {}", e);
+        }
+    }
 
     match result {
         InteractiveResult::Intune(request) => {
-            let result = package(&request)?;
+            let result =
+                with_limit_confirmation(|force| package(&request.clone().with_force(force)))?;
 
             println!("\nPackage created successfully:");
             println!(
-                "  {} ({:.2} MB)",
+                "  {} ({})",
                 result.output_path.display(),
-                result.package_size as f64 / 1_048_576.0
+                result.package_size
             );
             println!(
                 "  Creation time: {:.2}s",
@@ -247,19 +2730,23 @@ fn run_interactive_mode() -> PackageResult<()> {
         InteractiveResult::MacOS(request) => {
             use crate::macos;
 
-            let result = macos::package(request)?;
+            let result =
+                with_limit_confirmation(|force| macos::package(request.clone().with_force(force)))?;
 
             println!("\nPackage created successfully:");
             println!(
-                "  {} ({:.2} MB)",
+                "  {} ({})",
                 result.output_path.display(),
-                result.package_size as f64 / 1_048_576.0
+                result.package_size
             );
             println!("  {} files included", result.file_count);
             println!(
                 "  Creation time: {:.2}s",
                 result.creation_time.as_secs_f64()
             );
+            print_special_modes(&result.special_mode_files, result.special_modes_preserved);
+            print_sparse_files(&result.sparse_files);
+            print_skipped_entries(&result.skipped_entries);
         }
     }
 
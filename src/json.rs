@@ -0,0 +1,428 @@
+//! Minimal JSON reader and writer.
+//!
+//! Used for the small JSON side files the packager consumes and produces
+//! (blocklists, manifests, reports). Supports the full JSON grammar but
+//! keeps numbers as `f64` and objects as ordered key/value lists.
+
+use std::fmt::Write;
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Object members in document order
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Look up a member of an object by key.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Get the string value, if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get the numeric value as u64, if this is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    /// Get the boolean value, if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get the array elements, if this is an array.
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Get the object members, if this is an object.
+    pub fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            JsonValue::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+
+    /// Serialize with 2-space indentation.
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) {
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(n) => write_number(out, *n),
+            JsonValue::String(s) => write_string(out, s),
+            JsonValue::Array(items) if items.is_empty() => out.push_str("[]"),
+            JsonValue::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    item.write_pretty(out, indent + 1);
+                    if i + 1 < items.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                push_indent(out, indent);
+                out.push(']');
+            }
+            JsonValue::Object(members) if members.is_empty() => out.push_str("{}"),
+            JsonValue::Object(members) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    push_indent(out, indent + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 1);
+                    if i + 1 < members.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                push_indent(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(s: &str) -> Self {
+        JsonValue::String(s.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> Self {
+        JsonValue::String(s)
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(n: u64) -> Self {
+        JsonValue::Number(n as f64)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(b: bool) -> Self {
+        JsonValue::Bool(b)
+    }
+}
+
+fn push_indent(out: &mut String, level: usize) {
+    for _ in 0..level {
+        out.push_str("  ");
+    }
+}
+
+fn write_number(out: &mut String, n: f64) {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        let _ = write!(out, "{}", n as i64);
+    } else if n.is_finite() {
+        let _ = write!(out, "{}", n);
+    } else {
+        out.push_str("null");
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Deepest nesting of arrays and objects [`parse`] accepts.
+///
+/// The parser is recursive, so documents read from packages or downloads
+/// must not be able to exhaust the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// Parse a JSON document.
+pub fn parse(input: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {
+        chars: input.char_indices().peekable(),
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if let Some((pos, c)) = parser.chars.next() {
+        return Err(format!("Unexpected '{}' at offset {}", c, pos));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some((_, c)) if c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((pos, c)) => Err(format!(
+                "Expected '{}' but found '{}' at offset {}",
+                expected, c, pos
+            )),
+            None => Err(format!("Expected '{}' but reached end of input", expected)),
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result<(), String> {
+        for expected in literal.chars() {
+            self.expect(expected)?;
+        }
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        match self.chars.peek().copied() {
+            Some((pos, '{' | '[')) if self.depth >= MAX_DEPTH => Err(format!(
+                "Nesting deeper than {} levels at offset {}",
+                MAX_DEPTH, pos
+            )),
+            Some((_, '{')) => self.nested(Self::parse_object),
+            Some((_, '[')) => self.nested(Self::parse_array),
+            Some((_, '"')) => self.parse_string().map(JsonValue::String),
+            Some((_, 't')) => self.expect_literal("true").map(|_| JsonValue::Bool(true)),
+            Some((_, 'f')) => self.expect_literal("false").map(|_| JsonValue::Bool(false)),
+            Some((_, 'n')) => self.expect_literal("null").map(|_| JsonValue::Null),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some((pos, c)) => Err(format!("Unexpected '{}' at offset {}", c, pos)),
+            None => Err("Unexpected end of input".to_string()),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<JsonValue, String>,
+    ) -> Result<JsonValue, String> {
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if matches!(self.chars.peek(), Some((_, '}'))) {
+            self.chars.next();
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(JsonValue::Object(members)),
+                Some((pos, c)) => return Err(format!("Unexpected '{}' at offset {}", c, pos)),
+                None => return Err("Unterminated object".to_string()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if matches!(self.chars.peek(), Some((_, ']'))) {
+            self.chars.next();
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(JsonValue::Array(items)),
+                Some((pos, c)) => return Err(format!("Unexpected '{}' at offset {}", c, pos)),
+                None => return Err("Unterminated array".to_string()),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => s.push('"'),
+                    Some((_, '\\')) => s.push('\\'),
+                    Some((_, '/')) => s.push('/'),
+                    Some((_, 'b')) => s.push('\u{8}'),
+                    Some((_, 'f')) => s.push('\u{c}'),
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'u')) => s.push(self.parse_unicode_escape()?),
+                    Some((pos, c)) => {
+                        return Err(format!("Invalid escape '\\{}' at offset {}", c, pos));
+                    }
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some((_, c)) => s.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let (pos, c) = self.chars.next().ok_or("Unterminated unicode escape")?;
+            let digit = c
+                .to_digit(16)
+                .ok_or_else(|| format!("Invalid hex digit '{}' at offset {}", c, pos))?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+        if (0xD800..0xDC00).contains(&high) {
+            self.expect_literal("\\u")?;
+            let low = self.parse_hex4()?;
+            let combined = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+            return char::from_u32(combined).ok_or_else(|| "Invalid surrogate pair".to_string());
+        }
+        char::from_u32(high).ok_or_else(|| format!("Invalid unicode escape {:04x}", high))
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let mut text = String::new();
+        while let Some((_, c)) = self.chars.peek().copied() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                text.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        text.parse::<f64>()
+            .map(JsonValue::Number)
+            .map_err(|_| format!("Invalid number '{}'", text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(parse("null").unwrap(), JsonValue::Null);
+        assert_eq!(parse(" true ").unwrap(), JsonValue::Bool(true));
+        assert_eq!(parse("42").unwrap().as_u64(), Some(42));
+        assert_eq!(parse("\"a\\nb\"").unwrap().as_str(), Some("a\nb"));
+        assert_eq!(parse("\"\\u00e9\"").unwrap().as_str(), Some("é"));
+    }
+
+    #[test]
+    fn test_parse_nested() {
+        let value = parse(r#"{"a": [1, 2, {"b": "c"}], "d": {}}"#).unwrap();
+        let items = value.get("a").unwrap().as_array().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2].get("b").unwrap().as_str(), Some("c"));
+        assert_eq!(value.get("d").unwrap().as_object().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("").is_err());
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{\"a\" 1}").is_err());
+        assert!(parse("[] x").is_err());
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&nested).is_ok());
+
+        let too_deep = format!(
+            "{}1{}",
+            "[".repeat(MAX_DEPTH + 1),
+            "]".repeat(MAX_DEPTH + 1)
+        );
+        assert!(parse(&too_deep).unwrap_err().contains("Nesting deeper"));
+
+        // Must fail cleanly rather than overflow the stack
+        assert!(parse(&"[".repeat(1_000_000)).is_err());
+        assert!(parse(&"{\"a\":".repeat(1_000_000)).is_err());
+    }
+
+    #[test]
+    fn test_pretty_roundtrip() {
+        let value = JsonValue::Object(vec![
+            ("name".to_string(), "setup \"x\".exe".into()),
+            ("size".to_string(), 1024u64.into()),
+            (
+                "tags".to_string(),
+                JsonValue::Array(vec!["a".into(), JsonValue::Null]),
+            ),
+        ]);
+        let text = value.to_pretty_string();
+        assert!(text.contains("  \"size\": 1024"));
+        assert_eq!(parse(&text).unwrap(), value);
+    }
+}
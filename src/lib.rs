@@ -3,15 +3,57 @@
 //! Cross-platform packaging tool for:
 //! - Microsoft Intune (.intunewin files)
 //! - macOS flat packages (.pkg files)
+//! - Debian and RPM packages (.deb and .rpm files)
+//! - Chocolatey packages (.nupkg files)
+//!
+//! # Features
+//!
+//! - `intune` (default): `.intunewin` creation and extraction
+//! - `macos` (default): macOS flat package creation
+//! - `linux` (default): Debian (`.deb`) and RPM (`.rpm`) package creation
+//! - `cli`: the `iamawrapper` binary, progress bars and prompts. Off by
+//!   default, so embedders only build the library; embedders that need
+//!   fewer formats can also disable default features, e.g.
+//!   `default-features = false, features = ["intune"]`.
+//! - `async`: `package_async()`/`unpack_async()` for tokio runtimes, and
+//!   cancellation tokens on the request structs.
+//! - `xar` (implied by `macos`): the XAR archive reader and writer on its
+//!   own, as [`xar`].
+//! - `xar-bzip2`: bzip2-encoded XAR entries.
+//! - `signing` (implied by `cli`): detached minisign signatures over
+//!   finished packages, as [`signing`].
+//! - `s3`, `azure`: `s3://` and `azblob://` source locations, see
+//!   [`packager::source`].
 
+#[cfg(feature = "cli")]
 pub mod cli;
-#[cfg(feature = "macos")]
+pub mod json;
+#[cfg(feature = "linux")]
+pub mod linux;
+// Only the `xar` and `cpio` modules are compiled without the `macos`
+// feature
+#[cfg(any(feature = "xar", feature = "linux"))]
 pub mod macos;
 pub mod models;
 pub mod packager;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod toml;
 
-pub use models::error::{PackageError, PackageResult};
+#[cfg(feature = "xar")]
+pub use macos::xar;
+pub use models::choco::{ChocoPackRequest, ChocoPackResult};
+pub use models::dry_run::DryRunReport;
+pub use models::error::{ErrorCode, PackageError, PackageResult};
+#[cfg(feature = "linux")]
+pub use models::linux::{DebRequest, DebResult, RpmRequest, RpmResult};
 #[cfg(feature = "macos")]
-pub use models::macos::{MacosPkgRequest, MacosPkgResult};
-pub use models::package::{IntuneWinPackage, PackageRequest, SourcePackage, Verbosity};
-pub use packager::package;
+pub use models::macos::{MacosPkgRequest, MacosPkgRequestBuilder, MacosPkgResult};
+pub use models::package::{
+    IntuneWinPackage, PackageRequest, PackageRequestBuilder, SourcePackage, Verbosity,
+};
+pub use models::size::ByteSize;
+pub use packager::content::MemoryContent;
+pub use packager::{dry_run, package, package_to_writer};
+#[cfg(feature = "async")]
+pub use packager::{package_async, unpack_async};
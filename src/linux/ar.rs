@@ -0,0 +1,129 @@
+//! Unix `ar` archives, the container of Debian packages.
+//!
+//! An archive is the `!<arch>\n` magic followed by members, each a 60-byte
+//! text header (name, modification time, owner, group, octal mode and
+//! decimal size, all space padded) and its data, padded to an even length
+//! with `\n`. Debian packages only use short member names, so neither the
+//! GNU nor the BSD long name extension is written.
+
+use std::io::{self, Write};
+
+/// Magic at the start of every archive.
+pub const AR_MAGIC: &[u8; 8] = b"!<arch>\n";
+
+/// Longest member name the header holds.
+pub const MAX_NAME_LENGTH: usize = 16;
+
+/// Writes an `ar` archive member by member.
+pub struct ArWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ArWriter<W> {
+    /// Start an archive, writing its magic to `inner`.
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(AR_MAGIC)?;
+        Ok(Self { inner })
+    }
+
+    /// Append a member owned by root with the given mode and time.
+    pub fn append(&mut self, name: &str, data: &[u8], mode: u32, mtime: u64) -> io::Result<()> {
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH || name.contains(['/', ' ']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid ar member name '{}'", name),
+            ));
+        }
+
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8o}{:<10}`\n",
+            name,
+            mtime,
+            0,
+            0,
+            mode,
+            data.len()
+        );
+        debug_assert_eq!(header.len(), 60);
+        self.inner.write_all(header.as_bytes())?;
+        self.inner.write_all(data)?;
+        if data.len() % 2 == 1 {
+            self.inner.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Finish the archive and return the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Read the members of an archive as names and data.
+pub fn read_members(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut rest = data
+        .strip_prefix(AR_MAGIC.as_slice())
+        .ok_or("Not an ar archive")?;
+    let mut members = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 60 || &rest[58..60] != b"`\n" {
+            return Err(format!("Truncated header of member {}", members.len() + 1));
+        }
+        let field = |range: std::ops::Range<usize>| {
+            String::from_utf8_lossy(&rest[range]).trim_end().to_string()
+        };
+        let name = field(0..16).trim_end_matches('/').to_string();
+        let size: usize = field(48..58)
+            .parse()
+            .map_err(|_| format!("Invalid size of member '{}'", name))?;
+        let end = 60usize
+            .checked_add(size)
+            .filter(|end| *end <= rest.len())
+            .ok_or_else(|| format!("Member '{}' is truncated", name))?;
+        members.push((name, rest[60..end].to_vec()));
+        rest = &rest[(end + size % 2).min(rest.len())..];
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ar_round_trip() {
+        let mut writer = ArWriter::new(Vec::new()).unwrap();
+        writer
+            .append("debian-binary", b"2.0\n", 0o100644, 1)
+            .unwrap();
+        writer.append("odd", b"abc", 0o100644, 1).unwrap();
+        let data = writer.finish().unwrap();
+
+        assert!(data.starts_with(
+            b"!<arch>\ndebian-binary   1           0     0     100644  4         `\n2.0\n"
+        ));
+        // Odd-sized members are padded
+        assert_eq!(data.len(), 8 + 60 + 4 + 60 + 4);
+        assert_eq!(
+            read_members(&data).unwrap(),
+            vec![
+                ("debian-binary".to_string(), b"2.0\n".to_vec()),
+                ("odd".to_string(), b"abc".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ar_errors() {
+        let mut writer = ArWriter::new(Vec::new()).unwrap();
+        assert!(
+            writer
+                .append("a-very-long-member-name", b"", 0o644, 0)
+                .is_err()
+        );
+        assert!(writer.append("dir/name", b"", 0o644, 0).is_err());
+        assert!(read_members(b"not an archive").is_err());
+        assert!(read_members(b"!<arch>\nshort").is_err());
+    }
+}
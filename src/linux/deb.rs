@@ -0,0 +1,537 @@
+//! Debian binary package (.deb) creation.
+//!
+//! A `.deb` is an [`ar`](super::ar) archive of three members, in this
+//! order:
+//!
+//! - `debian-binary`: the format version, `2.0`
+//! - `control.tar.gz`: `control`, `md5sums` and the maintainer scripts
+//! - `data.tar.xz`: the files, under `./` as they are installed
+//!
+//! Entries are owned by root and keep their modes (setuid/setgid/sticky
+//! bits only when allowed) and modification times; symbolic links are kept
+//! as links. Hard links within the source are stored as separate files.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path};
+use std::time::Instant;
+
+use flate2::Compression as GzCompression;
+use flate2::write::GzEncoder;
+use tar::{EntryType, Header};
+use tracing::{info, info_span};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::linux::{DebRequest, DebResult, MAINTAINER_SCRIPTS};
+use crate::models::retry::RetryPolicy;
+use crate::models::size::ByteSize;
+use crate::packager::output::write_atomically;
+use crate::packager::retry::read_file;
+use crate::packager::special::{effective_mode, read_link_target};
+
+use super::ar::ArWriter;
+use super::payload::{Payload, path_bytes};
+
+/// Content of the `debian-binary` member.
+pub const DEBIAN_BINARY: &[u8] = b"2.0\n";
+
+/// Longest name a tar header holds without a GNU long name entry.
+const TAR_NAME_LENGTH: usize = 100;
+
+/// Create a Debian package from the given request.
+pub fn package(request: &DebRequest) -> PackageResult<DebResult> {
+    let start_time = Instant::now();
+    let _span = info_span!("package_deb", name = %request.name).entered();
+
+    request.validate()?;
+    let scripts = collect_scripts(request)?;
+
+    let payload = info_span!("payload").in_scope(|| Payload::collect(&request.source_folder))?;
+    let special_mode_files = payload.check(&request.strictness, request.allow_special_modes)?;
+
+    let (data_tar, md5sums) = write_data_tar(request, &payload)?;
+    let installed_kib = installed_kib(&payload);
+    let control = request.control(installed_kib);
+    let control_tar = write_control_tar(&control, &md5sums, &scripts, payload.mtime)?;
+    let data_xz = compress_xz(&data_tar)?;
+    info!(
+        files = payload.file_count(),
+        installed_kib, "Built control and data archives"
+    );
+
+    let members = [
+        ("debian-binary", DEBIAN_BINARY),
+        ("control.tar.gz", control_tar.as_slice()),
+        ("data.tar.xz", data_xz.as_slice()),
+    ];
+    let mut writer = ArWriter::new(Vec::new()).map_err(deb_error)?;
+    for (name, data) in members {
+        writer
+            .append(name, data, 0o100644, payload.mtime)
+            .map_err(deb_error)?;
+    }
+    let deb = writer.finish().map_err(deb_error)?;
+
+    let output_path = request.output_path();
+    if !request.output_folder.exists() {
+        fs::create_dir_all(&request.output_folder)
+            .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
+    }
+    if output_path.exists() && !request.verbosity.suppress_prompts() {
+        return Err(PackageError::OutputFileExists { path: output_path });
+    }
+    write_atomically(&output_path, &RetryPolicy::none(), |writer| {
+        writer
+            .write_all(&deb)
+            .map_err(|e| PackageError::output_write(output_path.clone(), e))
+    })?;
+    info!(path = %output_path.display(), bytes = deb.len(), "Wrote package");
+
+    Ok(DebResult {
+        package_size: ByteSize(deb.len() as u64),
+        output_path,
+        file_count: payload.file_count(),
+        installed_kib,
+        control,
+        scripts: scripts
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        special_mode_files,
+        skipped_entries: payload.skipped_entries,
+        creation_time: start_time.elapsed(),
+    })
+}
+
+/// `Installed-Size` as `dpkg-gencontrol` computes it: each file rounded
+/// up to KiB, and one KiB per other entry.
+fn installed_kib(payload: &Payload) -> u64 {
+    payload
+        .entries
+        .iter()
+        .map(|entry| match entry.is_dir() || entry.is_symlink() {
+            true => 1,
+            false => entry.size.div_ceil(1024),
+        })
+        .sum()
+}
+
+/// The maintainer scripts of the request's scripts folder, by name.
+fn collect_scripts(request: &DebRequest) -> PackageResult<Vec<(&'static str, Vec<u8>)>> {
+    let Some(folder) = &request.scripts_folder else {
+        return Ok(Vec::new());
+    };
+    if !folder.is_dir() {
+        return Err(PackageError::ScriptsFolderNotFound {
+            path: folder.clone(),
+        });
+    }
+
+    let mut scripts = Vec::new();
+    for name in MAINTAINER_SCRIPTS {
+        let path = folder.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let script = fs::read(&path).map_err(|e| PackageError::source_read(path.clone(), e))?;
+        if !script.starts_with(b"#!") {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Maintainer script '{}' must start with an interpreter line (#!)",
+                    path.display()
+                ),
+            });
+        }
+        scripts.push((*name, script));
+    }
+    if scripts.is_empty() {
+        return Err(PackageError::InvalidArgument {
+            reason: format!(
+                "No maintainer scripts ({}) found in '{}'",
+                MAINTAINER_SCRIPTS.join(", "),
+                folder.display()
+            ),
+        });
+    }
+    Ok(scripts)
+}
+
+/// Write the data tar, returning it with the `md5sums` of its files.
+fn write_data_tar(request: &DebRequest, payload: &Payload) -> PackageResult<(Vec<u8>, String)> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut md5sums = String::new();
+    let mtime = payload.mtime;
+
+    // The install location and the folders above it
+    let mut prefix = b"./".to_vec();
+    append_entry(
+        &mut builder,
+        &prefix,
+        EntryType::Directory,
+        0o755,
+        mtime,
+        &[],
+        None,
+    )
+    .map_err(deb_error)?;
+    for component in request.install_location.components() {
+        if let Component::Normal(name) = component {
+            prefix.extend_from_slice(&path_bytes(Path::new(name)));
+            prefix.push(b'/');
+            append_entry(
+                &mut builder,
+                &prefix,
+                EntryType::Directory,
+                0o755,
+                mtime,
+                &[],
+                None,
+            )
+            .map_err(deb_error)?;
+        }
+    }
+
+    for entry in &payload.entries {
+        let mut name = prefix.clone();
+        name.extend_from_slice(&path_bytes(&entry.relative_path));
+
+        if entry.is_dir() {
+            name.push(b'/');
+            let mode = effective_mode(entry.mode, request.allow_special_modes);
+            append_entry(
+                &mut builder,
+                &name,
+                EntryType::Directory,
+                mode,
+                entry.mtime,
+                &[],
+                None,
+            )
+            .map_err(deb_error)?;
+        } else if entry.is_symlink() {
+            let target = read_link_target(&entry.path)?;
+            append_entry(
+                &mut builder,
+                &name,
+                EntryType::Symlink,
+                0o777,
+                entry.mtime,
+                &[],
+                Some(&target),
+            )
+            .map_err(deb_error)?;
+        } else {
+            let data = read_file(&entry.path, &RetryPolicy::none())?;
+            let mode = effective_mode(entry.mode, request.allow_special_modes);
+            append_entry(
+                &mut builder,
+                &name,
+                EntryType::Regular,
+                mode,
+                entry.mtime,
+                &data,
+                None,
+            )
+            .map_err(deb_error)?;
+            md5sums.push_str(&format!(
+                "{:x}  {}\n",
+                md5::compute(&data),
+                String::from_utf8_lossy(&name[2..])
+            ));
+        }
+    }
+
+    let data = builder.into_inner().map_err(deb_error)?;
+    Ok((data, md5sums))
+}
+
+/// Write `control.tar.gz`.
+fn write_control_tar(
+    control: &str,
+    md5sums: &str,
+    scripts: &[(&str, Vec<u8>)],
+    mtime: u64,
+) -> PackageResult<Vec<u8>> {
+    let encoder = GzEncoder::new(Vec::new(), GzCompression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let mut files = vec![("control", control.as_bytes(), 0o644)];
+    if !md5sums.is_empty() {
+        files.push(("md5sums", md5sums.as_bytes(), 0o644));
+    }
+    files.extend(
+        scripts
+            .iter()
+            .map(|(name, script)| (*name, script.as_slice(), 0o755)),
+    );
+
+    append_entry(
+        &mut builder,
+        b"./",
+        EntryType::Directory,
+        0o755,
+        mtime,
+        &[],
+        None,
+    )
+    .map_err(deb_error)?;
+    for (name, data, mode) in files {
+        let name = format!("./{}", name);
+        append_entry(
+            &mut builder,
+            name.as_bytes(),
+            EntryType::Regular,
+            mode,
+            mtime,
+            data,
+            None,
+        )
+        .map_err(deb_error)?;
+    }
+    builder
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .map_err(deb_error)
+}
+
+/// Append an entry owned by root, with GNU long name records for names
+/// and link targets that do not fit the header.
+fn append_entry<W: Write>(
+    builder: &mut tar::Builder<W>,
+    name: &[u8],
+    kind: EntryType,
+    mode: u32,
+    mtime: u64,
+    data: &[u8],
+    link: Option<&[u8]>,
+) -> io::Result<()> {
+    let long_record = |builder: &mut tar::Builder<W>, kind, value: &[u8]| {
+        let mut header = Header::new_gnu();
+        header.as_old_mut().name[..13].copy_from_slice(b"././@LongLink");
+        header.set_entry_type(kind);
+        header.set_mode(0o644);
+        header.set_size(value.len() as u64 + 1);
+        header.set_cksum();
+        builder.append(&header, [value, b"\0"].concat().as_slice())
+    };
+
+    let mut header = Header::new_gnu();
+    if name.len() > TAR_NAME_LENGTH {
+        long_record(builder, EntryType::GNULongName, name)?;
+    }
+    let short = &name[..name.len().min(TAR_NAME_LENGTH)];
+    header.as_old_mut().name[..short.len()].copy_from_slice(short);
+    if let Some(link) = link {
+        if link.len() > TAR_NAME_LENGTH {
+            long_record(builder, EntryType::GNULongLink, link)?;
+        }
+        let short = &link[..link.len().min(TAR_NAME_LENGTH)];
+        header.as_old_mut().linkname[..short.len()].copy_from_slice(short);
+    }
+
+    header.set_entry_type(kind);
+    header.set_mode(mode);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root")?;
+    header.set_groupname("root")?;
+    header.set_mtime(mtime);
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data)
+}
+
+fn compress_xz(data: &[u8]) -> PackageResult<Vec<u8>> {
+    let mut output = Vec::new();
+    lzma_rs::xz_compress(&mut io::BufReader::new(data), &mut output).map_err(deb_error)?;
+    Ok(output)
+}
+
+fn deb_error(error: io::Error) -> PackageError {
+    PackageError::DebError {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::ar::read_members;
+    use crate::models::package::Verbosity;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// Names and contents of the entries of a tar archive.
+    fn tar_entries(data: impl Read) -> Vec<(String, u32, Vec<u8>)> {
+        let mut archive = tar::Archive::new(data);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+                let mode = entry.header().mode().unwrap();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (name, mode, data)
+            })
+            .collect()
+    }
+
+    fn create_source(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("bin")).unwrap();
+        fs::write(source.join("bin/agent"), "#!/bin/sh\necho agent\n").unwrap();
+        fs::write(source.join("agent.conf"), "interval=60\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(source.join("bin/agent"), fs::Permissions::from_mode(0o755))
+                .unwrap();
+            std::os::unix::fs::symlink("bin/agent", source.join("run")).unwrap();
+        }
+        source
+    }
+
+    #[test]
+    fn test_package_deb_layout() {
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+        let scripts = temp.path().join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        fs::write(scripts.join("postinst"), "#!/bin/sh\nexit 0\n").unwrap();
+
+        let request = DebRequest::new(&source, "contoso-agent", "1.2.0-1", temp.path().join("out"))
+            .with_architecture("amd64")
+            .with_install_location("/opt/contoso")
+            .with_depends("libc6")
+            .with_scripts_folder(&scripts)
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+        assert_eq!(
+            result.output_path,
+            temp.path().join("out/contoso-agent_1.2.0-1_amd64.deb")
+        );
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.scripts, ["postinst"]);
+
+        let deb = fs::read(&result.output_path).unwrap();
+        let members = read_members(&deb).unwrap();
+        let names: Vec<_> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["debian-binary", "control.tar.gz", "data.tar.xz"]);
+        assert_eq!(members[0].1, DEBIAN_BINARY);
+
+        let control = tar_entries(GzDecoder::new(members[1].1.as_slice()));
+        let control_names: Vec<_> = control.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(
+            control_names,
+            ["./", "./control", "./md5sums", "./postinst"]
+        );
+        assert_eq!(control[1].2, result.control.as_bytes());
+        assert!(result.control.contains("Depends: libc6\n"));
+        assert_eq!(control[3].1, 0o755);
+        let md5sums = String::from_utf8(control[2].2.clone()).unwrap();
+        assert!(md5sums.contains("  opt/contoso/bin/agent\n"));
+
+        let mut data = Vec::new();
+        lzma_rs::xz_decompress(&mut members[2].1.as_slice(), &mut data).unwrap();
+        let entries = tar_entries(data.as_slice());
+        let data_names: Vec<_> = entries.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert!(data_names.starts_with(&["./", "./opt/", "./opt/contoso/"]));
+        assert!(data_names.contains(&"./opt/contoso/bin/"));
+        let agent = entries
+            .iter()
+            .find(|(name, _, _)| name == "./opt/contoso/bin/agent")
+            .unwrap();
+        assert_eq!(agent.2, b"#!/bin/sh\necho agent\n");
+        #[cfg(unix)]
+        {
+            assert_eq!(agent.1, 0o755);
+            assert!(data_names.contains(&"./opt/contoso/run"));
+        }
+
+        // The existing package is kept without --quiet
+        assert!(matches!(
+            package(&request.clone().with_verbosity(Verbosity::Normal)),
+            Err(PackageError::OutputFileExists { .. })
+        ));
+    }
+
+    #[test]
+    fn test_package_deb_long_names() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        let deep = source.join("a".repeat(60)).join("b".repeat(60));
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("file.txt"), "data").unwrap();
+
+        let request = DebRequest::new(&source, "deep", "1.0", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+        let deb = fs::read(&result.output_path).unwrap();
+        let members = read_members(&deb).unwrap();
+        let mut data = Vec::new();
+        lzma_rs::xz_decompress(&mut members[2].1.as_slice(), &mut data).unwrap();
+        let expected = format!("./{}/{}/file.txt", "a".repeat(60), "b".repeat(60));
+        assert!(
+            tar_entries(data.as_slice())
+                .iter()
+                .any(|(name, _, data)| *name == expected && data == b"data")
+        );
+    }
+
+    #[test]
+    fn test_package_deb_scripts_errors() {
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+        let scripts = temp.path().join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        let request = DebRequest::new(&source, "agent", "1.0", temp.path().join("out"))
+            .with_scripts_folder(&scripts)
+            .with_verbosity(Verbosity::Silent);
+
+        assert!(
+            package(&request)
+                .unwrap_err()
+                .to_string()
+                .contains("No maintainer scripts")
+        );
+        fs::write(scripts.join("prerm"), "echo no interpreter").unwrap();
+        assert!(package(&request).unwrap_err().to_string().contains("#!"));
+        assert!(matches!(
+            package(&request.with_scripts_folder(temp.path().join("missing"))),
+            Err(PackageError::ScriptsFolderNotFound { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_package_deb_special_modes() {
+        use crate::models::strictness::Strictness;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+        fs::set_permissions(source.join("bin/agent"), fs::Permissions::from_mode(0o4755)).unwrap();
+        let request = DebRequest::new(&source, "agent", "1.0", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+
+        // Stripped with a warning by default, an error in strict mode
+        let result = package(&request).unwrap();
+        assert_eq!(result.special_mode_files, [PathBuf::from("bin/agent")]);
+        let strict = request.clone().with_strictness(Strictness::strict());
+        assert!(package(&strict).is_err());
+
+        let kept = package(&request.with_allow_special_modes(true)).unwrap();
+        let deb = fs::read(&kept.output_path).unwrap();
+        let members = read_members(&deb).unwrap();
+        let mut data = Vec::new();
+        lzma_rs::xz_decompress(&mut members[2].1.as_slice(), &mut data).unwrap();
+        assert!(
+            tar_entries(data.as_slice())
+                .iter()
+                .any(|(name, mode, _)| name == "./bin/agent" && *mode == 0o4755)
+        );
+    }
+}
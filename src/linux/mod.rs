@@ -0,0 +1,13 @@
+//! Linux package creation.
+//!
+//! Debian binary packages (`.deb`) are built by [`deb`] into an [`ar`]
+//! container and RPM packages (`.rpm`) by [`rpm`] around a newc CPIO
+//! payload, from the same source folders and with the same mode handling
+//! as macOS packages.
+
+pub mod ar;
+pub mod deb;
+mod payload;
+pub mod rpm;
+
+pub use self::deb::package;
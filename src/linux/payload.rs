@@ -0,0 +1,147 @@
+//! Source folder walk shared by the Debian and RPM writers.
+//!
+//! Entries are collected in name order with their Unix permissions and
+//! modification times; special files are left out as [`SkippedEntry`]s and
+//! symbolic links are kept as links where the platform can read them back.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::SkippedEntry;
+use crate::models::strictness::Strictness;
+use crate::packager::special::{
+    PRESERVE_SYMLINKS, SPECIAL_MODE_BITS, WalkItem, check_skipped, check_special_modes, classify,
+    unix_mode,
+};
+
+/// One entry of the source folder.
+pub(crate) struct PayloadEntry {
+    /// Path relative to the source folder
+    pub relative_path: PathBuf,
+    /// Source path
+    pub path: PathBuf,
+    pub file_type: fs::FileType,
+    /// Unix permissions, setuid/setgid/sticky bits included
+    pub mode: u32,
+    /// Size of a regular file, 0 otherwise
+    pub size: u64,
+    pub mtime: u64,
+}
+
+impl PayloadEntry {
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type.is_symlink()
+    }
+}
+
+/// The entries of the source folder, in name order.
+pub(crate) struct Payload {
+    pub entries: Vec<PayloadEntry>,
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Latest modification time, used for the package metadata
+    pub mtime: u64,
+}
+
+impl Payload {
+    /// Walk `source`.
+    pub fn collect(source: &Path) -> PackageResult<Self> {
+        let mut payload = Payload {
+            entries: Vec::new(),
+            skipped_entries: Vec::new(),
+            mtime: 0,
+        };
+
+        for result in WalkDir::new(source)
+            .min_depth(1)
+            .follow_links(!PRESERVE_SYMLINKS)
+            .sort_by_file_name()
+        {
+            let entry = match classify(source, result) {
+                WalkItem::Entry(entry) => entry,
+                WalkItem::Skipped(skipped) => {
+                    payload.skipped_entries.push(skipped);
+                    continue;
+                }
+                WalkItem::Error(e) => {
+                    let path = e.path().unwrap_or(source).to_path_buf();
+                    return Err(PackageError::source_read(path, e));
+                }
+            };
+            let path = entry.path();
+            let metadata = entry
+                .metadata()
+                .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+            let relative_path = path
+                .strip_prefix(source)
+                .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?
+                .to_path_buf();
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_secs());
+            payload.mtime = payload.mtime.max(mtime);
+            payload.entries.push(PayloadEntry {
+                relative_path,
+                path: path.to_path_buf(),
+                file_type: metadata.file_type(),
+                mode: unix_mode(&metadata) & 0o7777,
+                size: if metadata.is_file() {
+                    metadata.len()
+                } else {
+                    0
+                },
+                mtime,
+            });
+        }
+        Ok(payload)
+    }
+
+    /// Fail on skipped entries or special modes that are errors under
+    /// `strictness`, returning the entries with setuid/setgid/sticky bits.
+    pub fn check(
+        &self,
+        strictness: &Strictness,
+        allow_special_modes: bool,
+    ) -> PackageResult<Vec<PathBuf>> {
+        check_skipped(strictness, &self.skipped_entries)?;
+        let special_mode_files: Vec<PathBuf> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.mode & SPECIAL_MODE_BITS != 0 && !entry.is_symlink())
+            .map(|entry| entry.relative_path.clone())
+            .collect();
+        check_special_modes(strictness, allow_special_modes, &special_mode_files)?;
+        Ok(special_mode_files)
+    }
+
+    /// Number of regular files.
+    pub fn file_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.is_dir() && !entry.is_symlink())
+            .count()
+    }
+}
+
+/// `path` as raw bytes with `/` separators; on Unix byte for byte, so
+/// names that are not valid UTF-8 are installed under the same name.
+pub(crate) fn path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().replace('\\', "/").into_bytes()
+    }
+}
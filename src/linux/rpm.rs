@@ -0,0 +1,1057 @@
+//! RPM package (.rpm) creation.
+//!
+//! An RPM is four parts, in this order:
+//!
+//! - the lead: 96 bytes identifying the package, kept for old tools
+//! - the signature header: sizes and digests of what follows (the package
+//!   is not GPG-signed)
+//! - the header: name, version, dependencies, scriptlets and the metadata
+//!   of every file
+//! - the payload: a gzip-compressed newc [`cpio`](crate::macos::cpio) archive of the files,
+//!   under `./` as they are installed
+//!
+//! Both headers are version 4 headers with an immutable region, as
+//! `rpmbuild` writes them. Files are owned by root, keep their modes
+//! (setuid/setgid/sticky bits only when allowed) and modification times,
+//! and are listed in path order; symbolic links are kept as links. Hard
+//! links within the source are stored as separate files.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+
+use flate2::Compression as GzCompression;
+use flate2::write::GzEncoder;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tracing::{info, info_span};
+
+use crate::macos::cpio::{CpioFormat, CpioWriter, ROOT_GID, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG};
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::linux::{
+    DEFAULT_LICENSE, RPM_SCRIPTLETS, RpmRequest, RpmResult, split_rpm_relation,
+};
+use crate::models::retry::RetryPolicy;
+use crate::models::size::ByteSize;
+use crate::packager::output::write_atomically;
+use crate::packager::retry::read_file;
+use crate::packager::special::{effective_mode, read_link_target};
+
+use super::payload::{Payload, path_bytes};
+
+/// Magic at the start of the lead.
+pub const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+
+/// Size of the lead.
+pub const LEAD_SIZE: usize = 96;
+
+/// Magic and reserved bytes at the start of every header.
+pub const HEADER_MAGIC: [u8; 8] = [0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+
+/// Lead signature type: the signature is a header.
+const SIGNATURE_TYPE_HEADER: u16 = 5;
+
+/// Size of a region trailer and of every index entry.
+const INDEX_ENTRY_SIZE: usize = 16;
+
+/// Dependency flags (`RPMSENSE_*`).
+const SENSE_LESS: u32 = 1 << 1;
+const SENSE_GREATER: u32 = 1 << 2;
+const SENSE_EQUAL: u32 = 1 << 3;
+const SENSE_RPMLIB: u32 = 1 << 24;
+
+/// `FILEDIGESTALGO`/`PAYLOADDIGESTALGO` value of SHA-256.
+const DIGEST_SHA256: u32 = 8;
+
+/// Features of rpm the package relies on, required as `rpmlib(...)`.
+const RPMLIB_FEATURES: &[(&str, &str)] = &[
+    ("rpmlib(CompressedFileNames)", "3.0.4-1"),
+    ("rpmlib(FileDigests)", "4.6.0-1"),
+    ("rpmlib(PayloadFilesHavePrefix)", "4.0-1"),
+];
+
+/// Header tags, as numbered in `rpmtag.h`.
+pub mod tag {
+    pub const HEADER_SIGNATURES: u32 = 62;
+    pub const HEADER_IMMUTABLE: u32 = 63;
+    pub const HEADER_I18N_TABLE: u32 = 100;
+
+    // Signature header
+    pub const SIG_SHA1: u32 = 269;
+    pub const SIG_SHA256: u32 = 273;
+    pub const SIG_SIZE: u32 = 1000;
+    pub const SIG_MD5: u32 = 1004;
+    pub const SIG_PAYLOAD_SIZE: u32 = 1007;
+
+    // Header
+    pub const NAME: u32 = 1000;
+    pub const VERSION: u32 = 1001;
+    pub const RELEASE: u32 = 1002;
+    pub const EPOCH: u32 = 1003;
+    pub const SUMMARY: u32 = 1004;
+    pub const DESCRIPTION: u32 = 1005;
+    pub const BUILD_TIME: u32 = 1006;
+    pub const BUILD_HOST: u32 = 1007;
+    pub const SIZE: u32 = 1009;
+    pub const LICENSE: u32 = 1014;
+    pub const GROUP: u32 = 1016;
+    pub const URL: u32 = 1020;
+    pub const OS: u32 = 1021;
+    pub const ARCH: u32 = 1022;
+    pub const PRE_IN: u32 = 1023;
+    pub const POST_IN: u32 = 1024;
+    pub const PRE_UN: u32 = 1025;
+    pub const POST_UN: u32 = 1026;
+    pub const FILE_SIZES: u32 = 1028;
+    pub const FILE_MODES: u32 = 1030;
+    pub const FILE_RDEVS: u32 = 1033;
+    pub const FILE_MTIMES: u32 = 1034;
+    pub const FILE_DIGESTS: u32 = 1035;
+    pub const FILE_LINKTOS: u32 = 1036;
+    pub const FILE_FLAGS: u32 = 1037;
+    pub const FILE_USERNAME: u32 = 1039;
+    pub const FILE_GROUPNAME: u32 = 1040;
+    pub const SOURCE_RPM: u32 = 1044;
+    pub const FILE_VERIFY_FLAGS: u32 = 1045;
+    pub const PROVIDE_NAME: u32 = 1047;
+    pub const REQUIRE_FLAGS: u32 = 1048;
+    pub const REQUIRE_NAME: u32 = 1049;
+    pub const REQUIRE_VERSION: u32 = 1050;
+    pub const PRE_IN_PROG: u32 = 1085;
+    pub const POST_IN_PROG: u32 = 1086;
+    pub const PRE_UN_PROG: u32 = 1087;
+    pub const POST_UN_PROG: u32 = 1088;
+    pub const FILE_DEVICES: u32 = 1095;
+    pub const FILE_INODES: u32 = 1096;
+    pub const FILE_LANGS: u32 = 1097;
+    pub const PROVIDE_FLAGS: u32 = 1112;
+    pub const PROVIDE_VERSION: u32 = 1113;
+    pub const DIR_INDEXES: u32 = 1116;
+    pub const BASENAMES: u32 = 1117;
+    pub const DIRNAMES: u32 = 1118;
+    pub const PAYLOAD_FORMAT: u32 = 1124;
+    pub const PAYLOAD_COMPRESSOR: u32 = 1125;
+    pub const PAYLOAD_FLAGS: u32 = 1126;
+    pub const FILE_DIGEST_ALGO: u32 = 5011;
+    pub const PAYLOAD_DIGEST: u32 = 5092;
+    pub const PAYLOAD_DIGEST_ALGO: u32 = 5093;
+}
+
+/// Script and interpreter tags of each scriptlet in [`RPM_SCRIPTLETS`].
+const SCRIPTLET_TAGS: [(u32, u32); 4] = [
+    (tag::PRE_IN, tag::PRE_IN_PROG),
+    (tag::POST_IN, tag::POST_IN_PROG),
+    (tag::PRE_UN, tag::PRE_UN_PROG),
+    (tag::POST_UN, tag::POST_UN_PROG),
+];
+
+/// The value of a header entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderValue {
+    Int16(Vec<u16>),
+    Int32(Vec<u32>),
+    String(String),
+    Bin(Vec<u8>),
+    StringArray(Vec<String>),
+    /// A string in each locale of the I18N table (only `C` is written)
+    I18nString(Vec<String>),
+}
+
+impl HeaderValue {
+    fn type_code(&self) -> u32 {
+        match self {
+            HeaderValue::Int16(_) => 3,
+            HeaderValue::Int32(_) => 4,
+            HeaderValue::String(_) => 6,
+            HeaderValue::Bin(_) => 7,
+            HeaderValue::StringArray(_) => 8,
+            HeaderValue::I18nString(_) => 9,
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            HeaderValue::Int16(values) => values.len(),
+            HeaderValue::Int32(values) => values.len(),
+            HeaderValue::String(_) => 1,
+            HeaderValue::Bin(data) => data.len(),
+            HeaderValue::StringArray(values) | HeaderValue::I18nString(values) => values.len(),
+        }
+    }
+
+    fn alignment(&self) -> usize {
+        match self {
+            HeaderValue::Int16(_) => 2,
+            HeaderValue::Int32(_) => 4,
+            _ => 1,
+        }
+    }
+
+    fn write(&self, data: &mut Vec<u8>) {
+        let mut strings = |values: &[String]| {
+            for value in values {
+                data.extend_from_slice(value.as_bytes());
+                data.push(0);
+            }
+        };
+        match self {
+            HeaderValue::Int16(values) => {
+                values.iter().for_each(|v| data.extend(v.to_be_bytes()));
+            }
+            HeaderValue::Int32(values) => {
+                values.iter().for_each(|v| data.extend(v.to_be_bytes()));
+            }
+            HeaderValue::String(value) => strings(std::slice::from_ref(value)),
+            HeaderValue::Bin(value) => data.extend_from_slice(value),
+            HeaderValue::StringArray(values) | HeaderValue::I18nString(values) => strings(values),
+        }
+    }
+
+    /// The value as a single string, if it is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            HeaderValue::String(value) => Some(value),
+            HeaderValue::I18nString(values) => values.first().map(String::as_str),
+            _ => None,
+        }
+    }
+}
+
+/// Serialize a header with its entries in tag order, inside an immutable
+/// region tagged `region_tag`.
+fn write_header(mut entries: Vec<(u32, HeaderValue)>, region_tag: u32) -> Vec<u8> {
+    entries.sort_by_key(|(tag, _)| *tag);
+    let entry_count = entries.len() + 1;
+    let mut index = Vec::with_capacity(entry_count * INDEX_ENTRY_SIZE);
+    let mut data = Vec::new();
+    let index_entry = |index: &mut Vec<u8>, tag: u32, kind: u32, offset: i32, count: usize| {
+        index.extend(tag.to_be_bytes());
+        index.extend(kind.to_be_bytes());
+        index.extend(offset.to_be_bytes());
+        index.extend((count as u32).to_be_bytes());
+    };
+
+    let mut values = Vec::new();
+    for (tag, value) in &entries {
+        while data.len() % value.alignment() != 0 {
+            data.push(0);
+        }
+        index_entry(
+            &mut values,
+            *tag,
+            value.type_code(),
+            data.len() as i32,
+            value.count(),
+        );
+        value.write(&mut data);
+    }
+
+    // The region entry comes first and points at its trailer, a copy of
+    // itself whose offset is minus the size of the region's index
+    index_entry(
+        &mut index,
+        region_tag,
+        7,
+        data.len() as i32,
+        INDEX_ENTRY_SIZE,
+    );
+    index.extend(values);
+    let mut trailer = Vec::new();
+    index_entry(
+        &mut trailer,
+        region_tag,
+        7,
+        -((entry_count * INDEX_ENTRY_SIZE) as i32),
+        INDEX_ENTRY_SIZE,
+    );
+    data.extend(trailer);
+
+    let mut header = HEADER_MAGIC.to_vec();
+    header.extend((entry_count as u32).to_be_bytes());
+    header.extend((data.len() as u32).to_be_bytes());
+    header.extend(index);
+    header.extend(data);
+    header
+}
+
+/// The parts of an RPM package, as read by [`read_package`].
+#[derive(Debug)]
+pub struct RpmParts<'a> {
+    /// `name-version-release` from the lead
+    pub lead_name: String,
+    /// Entries of the signature header, in tag order
+    pub signature: Vec<(u32, HeaderValue)>,
+    /// Entries of the header, in tag order
+    pub header: Vec<(u32, HeaderValue)>,
+    /// The header as stored, which the signature digests cover
+    pub header_bytes: &'a [u8],
+    /// The compressed payload
+    pub payload: &'a [u8],
+}
+
+impl RpmParts<'_> {
+    /// The value of a header tag.
+    pub fn get(&self, tag: u32) -> Option<&HeaderValue> {
+        self.header.iter().find(|(t, _)| *t == tag).map(|(_, v)| v)
+    }
+
+    /// The value of a signature header tag.
+    pub fn signature(&self, tag: u32) -> Option<&HeaderValue> {
+        self.signature
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, v)| v)
+    }
+}
+
+/// Split an RPM package into its lead, headers and payload.
+pub fn read_package(data: &[u8]) -> Result<RpmParts<'_>, String> {
+    let lead = data.get(..LEAD_SIZE).ok_or("Truncated lead")?;
+    if lead[..4] != LEAD_MAGIC {
+        return Err("Not an RPM package".to_string());
+    }
+    let lead_name = String::from_utf8_lossy(&lead[10..76])
+        .trim_end_matches('\0')
+        .to_string();
+
+    let (signature, signature_len) = read_header(&data[LEAD_SIZE..], tag::HEADER_SIGNATURES)
+        .map_err(|e| format!("Signature header: {}", e))?;
+    let header_start = (LEAD_SIZE + signature_len).next_multiple_of(8);
+    let rest = data.get(header_start..).ok_or("Truncated signature")?;
+    let (header, header_len) =
+        read_header(rest, tag::HEADER_IMMUTABLE).map_err(|e| format!("Header: {}", e))?;
+
+    Ok(RpmParts {
+        lead_name,
+        signature,
+        header,
+        header_bytes: &rest[..header_len],
+        payload: &rest[header_len..],
+    })
+}
+
+/// Read a header and check its region, returning its entries (without
+/// the region) and its size.
+fn read_header(data: &[u8], region_tag: u32) -> Result<(Vec<(u32, HeaderValue)>, usize), String> {
+    if data.get(..8) != Some(HEADER_MAGIC.as_slice()) {
+        return Err("Bad magic".to_string());
+    }
+    let u32_at = |data: &[u8], pos: usize| -> Result<u32, String> {
+        data.get(pos..pos + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "Truncated".to_string())
+    };
+    let entry_count = u32_at(data, 8)? as usize;
+    let data_len = u32_at(data, 12)? as usize;
+    let store_start = 16 + entry_count * INDEX_ENTRY_SIZE;
+    let total = store_start + data_len;
+    let store = data.get(store_start..total).ok_or("Truncated")?;
+
+    let mut entries = Vec::new();
+    for i in 0..entry_count {
+        let pos = 16 + i * INDEX_ENTRY_SIZE;
+        let (tag, kind) = (u32_at(data, pos)?, u32_at(data, pos + 4)?);
+        let offset = u32_at(data, pos + 8)? as usize;
+        let count = u32_at(data, pos + 12)? as usize;
+        let at = store.get(offset..).ok_or("Offset out of range")?;
+
+        if i == 0 {
+            let trailer = at.get(..INDEX_ENTRY_SIZE).ok_or("Truncated region")?;
+            let trailer_offset = u32_at(trailer, 8)? as i32;
+            if tag != region_tag
+                || kind != 7
+                || u32_at(trailer, 0)? != region_tag
+                || trailer_offset != -((entry_count * INDEX_ENTRY_SIZE) as i32)
+                || offset + INDEX_ENTRY_SIZE != data_len
+            {
+                return Err("Bad immutable region".to_string());
+            }
+            continue;
+        }
+
+        let strings = |count: usize| -> Result<Vec<String>, String> {
+            at.split(|b| *b == 0)
+                .take(count)
+                .map(|s| String::from_utf8(s.to_vec()).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|strings| match strings.len() == count {
+                    true => Ok(strings),
+                    false => Err(format!("Truncated strings of tag {}", tag)),
+                })
+        };
+        let numbers = |size: usize| -> Result<Vec<u64>, String> {
+            if offset % size != 0 {
+                return Err(format!("Misaligned tag {}", tag));
+            }
+            let bytes = at.get(..count * size).ok_or("Truncated numbers")?;
+            Ok(bytes
+                .chunks(size)
+                .map(|c| c.iter().fold(0u64, |n, b| n << 8 | u64::from(*b)))
+                .collect())
+        };
+        let value = match kind {
+            3 => HeaderValue::Int16(numbers(2)?.into_iter().map(|n| n as u16).collect()),
+            4 => HeaderValue::Int32(numbers(4)?.into_iter().map(|n| n as u32).collect()),
+            6 => HeaderValue::String(strings(1)?.remove(0)),
+            7 => HeaderValue::Bin(at.get(..count).ok_or("Truncated data")?.to_vec()),
+            8 => HeaderValue::StringArray(strings(count)?),
+            9 => HeaderValue::I18nString(strings(count)?),
+            _ => return Err(format!("Unsupported type {} of tag {}", kind, tag)),
+        };
+        entries.push((tag, value));
+    }
+    Ok((entries, total))
+}
+
+/// Create an RPM package from the given request.
+pub fn package(request: &RpmRequest) -> PackageResult<RpmResult> {
+    let start_time = Instant::now();
+    let _span = info_span!("package_rpm", name = %request.name).entered();
+
+    request.validate()?;
+    let scripts = collect_scripts(request)?;
+
+    let payload = info_span!("payload").in_scope(|| Payload::collect(&request.source_folder))?;
+    let special_mode_files = payload.check(&request.strictness, request.allow_special_modes)?;
+    let files = collect_files(request, &payload)?;
+    let installed_size: u64 = files.iter().map(|file| u64::from(file.size)).sum();
+    let installed_size = u32::try_from(installed_size)
+        .map_err(|_| rpm_error("Payloads of 4 GiB or more are not supported"))?;
+
+    // Payload
+    let mut writer = CpioWriter::with_format(CpioFormat::Newc).with_gid(ROOT_GID);
+    for file in &files {
+        writer.set_mtime(u64::from(file.mtime));
+        writer.add(format!(".{}", file.path), &file.data, u32::from(file.mode))?;
+    }
+    let archive = writer.finish();
+    let archive_size = archive.len();
+    let mut encoder = GzEncoder::new(Vec::new(), GzCompression::best());
+    encoder
+        .write_all(&archive)
+        .map_err(|e| rpm_error(e.to_string()))?;
+    let compressed = encoder.finish().map_err(|e| rpm_error(e.to_string()))?;
+    drop(archive);
+
+    // Header
+    let header_entries = header_entries(request, &files, &scripts, installed_size, payload.mtime)
+        .into_iter()
+        .chain([
+            (
+                tag::PAYLOAD_DIGEST,
+                HeaderValue::StringArray(vec![hex::encode(Sha256::digest(&compressed))]),
+            ),
+            (
+                tag::PAYLOAD_DIGEST_ALGO,
+                HeaderValue::Int32(vec![DIGEST_SHA256]),
+            ),
+        ])
+        .collect();
+    let header = write_header(header_entries, tag::HEADER_IMMUTABLE);
+
+    // Signature over the header and payload
+    let signed_size = u32::try_from(header.len() + compressed.len())
+        .map_err(|_| rpm_error("Packages of 4 GiB or more are not supported"))?;
+    let mut md5 = md5::Context::new();
+    md5.consume(&header);
+    md5.consume(&compressed);
+    let signature = write_header(
+        vec![
+            (
+                tag::SIG_SHA1,
+                HeaderValue::String(hex::encode(Sha1::digest(&header))),
+            ),
+            (
+                tag::SIG_SHA256,
+                HeaderValue::String(hex::encode(Sha256::digest(&header))),
+            ),
+            (tag::SIG_SIZE, HeaderValue::Int32(vec![signed_size])),
+            (tag::SIG_MD5, HeaderValue::Bin(md5.compute().0.to_vec())),
+            (
+                tag::SIG_PAYLOAD_SIZE,
+                HeaderValue::Int32(vec![archive_size as u32]),
+            ),
+        ],
+        tag::HEADER_SIGNATURES,
+    );
+
+    let mut rpm = lead(request);
+    rpm.extend(&signature);
+    rpm.resize(rpm.len().next_multiple_of(8), 0);
+    rpm.extend(&header);
+    rpm.extend(&compressed);
+    info!(
+        files = files.len(),
+        installed_size, "Built header and payload"
+    );
+
+    let output_path = request.output_path();
+    if !request.output_folder.exists() {
+        fs::create_dir_all(&request.output_folder)
+            .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
+    }
+    if output_path.exists() && !request.verbosity.suppress_prompts() {
+        return Err(PackageError::OutputFileExists { path: output_path });
+    }
+    write_atomically(&output_path, &RetryPolicy::none(), |writer| {
+        writer
+            .write_all(&rpm)
+            .map_err(|e| PackageError::output_write(output_path.clone(), e))
+    })?;
+    info!(path = %output_path.display(), bytes = rpm.len(), "Wrote package");
+
+    Ok(RpmResult {
+        package_size: ByteSize(rpm.len() as u64),
+        output_path,
+        file_count: payload.file_count(),
+        installed_size: ByteSize(u64::from(installed_size)),
+        scripts: scripts
+            .into_iter()
+            .map(|script| script.name.to_string())
+            .collect(),
+        special_mode_files,
+        skipped_entries: payload.skipped_entries,
+        creation_time: start_time.elapsed(),
+    })
+}
+
+/// One installed path with its header metadata and payload data.
+struct RpmFile {
+    /// Absolute install path
+    path: String,
+    /// Type and permission bits
+    mode: u16,
+    /// File size, or the length of a link target
+    size: u32,
+    mtime: u32,
+    /// Contents of a file, or the target of a link
+    data: Vec<u8>,
+    /// SHA-256 of a file's contents, empty otherwise
+    digest: String,
+}
+
+impl RpmFile {
+    fn link_target(&self) -> &str {
+        match u32::from(self.mode) & S_IFMT {
+            S_IFLNK => std::str::from_utf8(&self.data).unwrap_or_default(),
+            _ => "",
+        }
+    }
+}
+
+/// The payload entries under the install location, in path order.
+fn collect_files(request: &RpmRequest, payload: &Payload) -> PackageResult<Vec<RpmFile>> {
+    let utf8 = |bytes: Vec<u8>, what: &str| {
+        String::from_utf8(bytes).map_err(|e| {
+            rpm_error(format!(
+                "{} '{}' is not valid UTF-8, which RPM headers require",
+                what,
+                String::from_utf8_lossy(e.as_bytes())
+            ))
+        })
+    };
+    let prefix = utf8(path_bytes(&request.install_location), "Install location")?;
+    let prefix = prefix.trim_end_matches('/');
+
+    let mut files = Vec::with_capacity(payload.entries.len());
+    for entry in &payload.entries {
+        let relative = utf8(path_bytes(&entry.relative_path), "Path")?;
+        let path = format!("{}/{}", prefix, relative);
+        let mode = effective_mode(entry.mode, request.allow_special_modes);
+        let mtime = entry.mtime.min(u64::from(u32::MAX)) as u32;
+
+        let (mode, data, digest) = if entry.is_dir() {
+            (S_IFDIR | mode, Vec::new(), String::new())
+        } else if entry.is_symlink() {
+            let target = read_link_target(&entry.path)?;
+            utf8(target.clone(), "Link target")?;
+            (S_IFLNK | 0o777, target, String::new())
+        } else {
+            let data = read_file(&entry.path, &RetryPolicy::none())?;
+            let digest = hex::encode(Sha256::digest(&data));
+            (S_IFREG | mode, data, digest)
+        };
+        let size = u32::try_from(data.len()).map_err(|_| {
+            rpm_error(format!(
+                "'{}' is 4 GiB or larger",
+                entry.relative_path.display()
+            ))
+        })?;
+        files.push(RpmFile {
+            path,
+            mode: mode as u16,
+            size,
+            mtime,
+            data,
+            digest,
+        });
+    }
+
+    // rpm looks files up by path, as rpmbuild sorts them
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// A scriptlet of the request's scripts folder.
+struct Scriptlet {
+    name: &'static str,
+    script: String,
+    /// Interpreter and its arguments, from the `#!` line or `/bin/sh`
+    interpreter: Vec<String>,
+}
+
+/// The scriptlets of the request's scripts folder, by name.
+fn collect_scripts(request: &RpmRequest) -> PackageResult<Vec<Scriptlet>> {
+    let Some(folder) = &request.scripts_folder else {
+        return Ok(Vec::new());
+    };
+    if !folder.is_dir() {
+        return Err(PackageError::ScriptsFolderNotFound {
+            path: folder.clone(),
+        });
+    }
+
+    let mut scripts = Vec::new();
+    for name in RPM_SCRIPTLETS {
+        let path = folder.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let script = fs::read(&path).map_err(|e| PackageError::source_read(path.clone(), e))?;
+        let script = String::from_utf8(script).map_err(|_| PackageError::InvalidArgument {
+            reason: format!("Scriptlet '{}' is not valid UTF-8", path.display()),
+        })?;
+        let interpreter = match script.lines().next().and_then(|l| l.strip_prefix("#!")) {
+            Some(line) if !line.trim().is_empty() => {
+                line.split_whitespace().map(String::from).collect()
+            }
+            _ => vec!["/bin/sh".to_string()],
+        };
+        scripts.push(Scriptlet {
+            name,
+            script,
+            interpreter,
+        });
+    }
+    if scripts.is_empty() {
+        return Err(PackageError::InvalidArgument {
+            reason: format!(
+                "No scriptlets ({}) found in '{}'",
+                RPM_SCRIPTLETS.join(", "),
+                folder.display()
+            ),
+        });
+    }
+    Ok(scripts)
+}
+
+/// The header entries describing the package, its scriptlets and files.
+fn header_entries(
+    request: &RpmRequest,
+    files: &[RpmFile],
+    scripts: &[Scriptlet],
+    installed_size: u32,
+    build_time: u64,
+) -> Vec<(u32, HeaderValue)> {
+    let string = |value: &str| HeaderValue::String(value.to_string());
+    let i18n = |value: &str| HeaderValue::I18nString(vec![value.to_string()]);
+    let summary = request.summary();
+    let mut entries = vec![
+        (
+            tag::HEADER_I18N_TABLE,
+            HeaderValue::StringArray(vec!["C".to_string()]),
+        ),
+        (tag::NAME, string(&request.name)),
+        (tag::VERSION, string(&request.version)),
+        (tag::RELEASE, string(&request.release)),
+        (tag::SUMMARY, i18n(summary)),
+        (
+            tag::DESCRIPTION,
+            i18n(request.description.as_deref().unwrap_or(summary)),
+        ),
+        (
+            tag::BUILD_TIME,
+            HeaderValue::Int32(vec![build_time.min(u64::from(u32::MAX)) as u32]),
+        ),
+        (tag::BUILD_HOST, string("localhost")),
+        (tag::SIZE, HeaderValue::Int32(vec![installed_size])),
+        (
+            tag::LICENSE,
+            string(request.license.as_deref().unwrap_or(DEFAULT_LICENSE)),
+        ),
+        (tag::GROUP, i18n("Unspecified")),
+        (tag::OS, string("linux")),
+        (tag::ARCH, string(&request.architecture)),
+        (
+            tag::SOURCE_RPM,
+            string(&format!(
+                "{}-{}-{}.src.rpm",
+                request.name, request.version, request.release
+            )),
+        ),
+        (
+            tag::PROVIDE_NAME,
+            HeaderValue::StringArray(vec![request.name.clone()]),
+        ),
+        (tag::PROVIDE_FLAGS, HeaderValue::Int32(vec![SENSE_EQUAL])),
+        (
+            tag::PROVIDE_VERSION,
+            HeaderValue::StringArray(vec![request.evr()]),
+        ),
+        (tag::PAYLOAD_FORMAT, string("cpio")),
+        (tag::PAYLOAD_COMPRESSOR, string("gzip")),
+        (tag::PAYLOAD_FLAGS, string("9")),
+    ];
+    if let Some(epoch) = request.epoch {
+        entries.push((tag::EPOCH, HeaderValue::Int32(vec![epoch])));
+    }
+    if let Some(url) = &request.url {
+        entries.push((tag::URL, string(url)));
+    }
+
+    // Requirements, then the rpm features the package relies on
+    let (mut names, mut flags, mut versions) = (Vec::new(), Vec::new(), Vec::new());
+    for relation in &request.requires {
+        if let Some((name, version)) = split_rpm_relation(relation) {
+            let (sense, version) = match version {
+                Some((op, version)) => (relation_flags(op), version),
+                None => (0, ""),
+            };
+            names.push(name.to_string());
+            flags.push(sense);
+            versions.push(version.to_string());
+        }
+    }
+    for (name, version) in RPMLIB_FEATURES {
+        names.push(name.to_string());
+        flags.push(SENSE_RPMLIB | SENSE_LESS | SENSE_EQUAL);
+        versions.push(version.to_string());
+    }
+    entries.extend([
+        (tag::REQUIRE_NAME, HeaderValue::StringArray(names)),
+        (tag::REQUIRE_FLAGS, HeaderValue::Int32(flags)),
+        (tag::REQUIRE_VERSION, HeaderValue::StringArray(versions)),
+    ]);
+
+    for scriptlet in scripts {
+        let index = RPM_SCRIPTLETS
+            .iter()
+            .position(|name| *name == scriptlet.name)
+            .unwrap_or_default();
+        let (script_tag, prog_tag) = SCRIPTLET_TAGS[index];
+        let prog = match scriptlet.interpreter.as_slice() {
+            [interpreter] => HeaderValue::String(interpreter.clone()),
+            interpreter => HeaderValue::StringArray(interpreter.to_vec()),
+        };
+        entries.push((script_tag, string(&scriptlet.script)));
+        entries.push((prog_tag, prog));
+    }
+
+    if files.is_empty() {
+        return entries;
+    }
+
+    // Paths are stored as a directory index and a base name
+    let mut dirnames: Vec<String> = Vec::new();
+    let mut dir_lookup: HashMap<&str, u32> = HashMap::new();
+    let mut dir_indexes = Vec::with_capacity(files.len());
+    let mut basenames = Vec::with_capacity(files.len());
+    for file in files {
+        let split = file.path.rfind('/').map_or(0, |i| i + 1);
+        let (dirname, basename) = file.path.split_at(split);
+        let index = *dir_lookup.entry(dirname).or_insert_with(|| {
+            dirnames.push(dirname.to_string());
+            dirnames.len() as u32 - 1
+        });
+        dir_indexes.push(index);
+        basenames.push(basename.to_string());
+    }
+
+    let count = files.len();
+    let strings =
+        |f: &dyn Fn(&RpmFile) -> String| HeaderValue::StringArray(files.iter().map(f).collect());
+    entries.extend([
+        (
+            tag::FILE_SIZES,
+            HeaderValue::Int32(files.iter().map(|f| f.size).collect()),
+        ),
+        (
+            tag::FILE_MODES,
+            HeaderValue::Int16(files.iter().map(|f| f.mode).collect()),
+        ),
+        (tag::FILE_RDEVS, HeaderValue::Int16(vec![0; count])),
+        (
+            tag::FILE_MTIMES,
+            HeaderValue::Int32(files.iter().map(|f| f.mtime).collect()),
+        ),
+        (tag::FILE_DIGESTS, strings(&|f| f.digest.clone())),
+        (tag::FILE_LINKTOS, strings(&|f| f.link_target().to_string())),
+        (tag::FILE_FLAGS, HeaderValue::Int32(vec![0; count])),
+        (tag::FILE_USERNAME, strings(&|_| "root".to_string())),
+        (tag::FILE_GROUPNAME, strings(&|_| "root".to_string())),
+        (
+            tag::FILE_VERIFY_FLAGS,
+            HeaderValue::Int32(vec![u32::MAX; count]),
+        ),
+        (tag::FILE_DEVICES, HeaderValue::Int32(vec![1; count])),
+        (
+            tag::FILE_INODES,
+            HeaderValue::Int32((1..=count as u32).collect()),
+        ),
+        (tag::FILE_LANGS, strings(&|_| String::new())),
+        (tag::DIR_INDEXES, HeaderValue::Int32(dir_indexes)),
+        (tag::BASENAMES, HeaderValue::StringArray(basenames)),
+        (tag::DIRNAMES, HeaderValue::StringArray(dirnames)),
+        (
+            tag::FILE_DIGEST_ALGO,
+            HeaderValue::Int32(vec![DIGEST_SHA256]),
+        ),
+    ]);
+    entries
+}
+
+/// `RPMSENSE_*` flags of a comparison operator.
+fn relation_flags(op: &str) -> u32 {
+    match op {
+        "<" => SENSE_LESS,
+        "<=" => SENSE_LESS | SENSE_EQUAL,
+        ">=" => SENSE_GREATER | SENSE_EQUAL,
+        ">" => SENSE_GREATER,
+        _ => SENSE_EQUAL,
+    }
+}
+
+/// The lead: magic, format 3.0, binary package, architecture number,
+/// `name-version-release`, Linux, and a header-style signature.
+fn lead(request: &RpmRequest) -> Vec<u8> {
+    let arch_number: u16 = match request.architecture.as_str() {
+        "x86_64" | "i386" | "i486" | "i586" | "i686" | "noarch" => 1,
+        "aarch64" => 19,
+        _ => 0,
+    };
+    let mut name = format!("{}-{}-{}", request.name, request.version, request.release).into_bytes();
+    name.resize(66, 0);
+    name[65] = 0;
+
+    let mut lead = Vec::with_capacity(LEAD_SIZE);
+    lead.extend(LEAD_MAGIC);
+    lead.extend([3, 0]);
+    lead.extend(0u16.to_be_bytes());
+    lead.extend(arch_number.to_be_bytes());
+    lead.extend(name);
+    lead.extend(1u16.to_be_bytes());
+    lead.extend(SIGNATURE_TYPE_HEADER.to_be_bytes());
+    lead.resize(LEAD_SIZE, 0);
+    lead
+}
+
+fn rpm_error(reason: impl Into<String>) -> PackageError {
+    PackageError::RpmError {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::cpio::read_payload;
+    use crate::models::package::Verbosity;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn create_source(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("bin")).unwrap();
+        fs::write(source.join("bin/agent"), "#!/bin/sh\necho agent\n").unwrap();
+        fs::write(source.join("agent.conf"), "interval=60\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(source.join("bin/agent"), fs::Permissions::from_mode(0o755))
+                .unwrap();
+            std::os::unix::fs::symlink("bin/agent", source.join("run")).unwrap();
+        }
+        source
+    }
+
+    fn strings(value: Option<&HeaderValue>) -> Vec<String> {
+        match value {
+            Some(HeaderValue::StringArray(values)) => values.clone(),
+            other => panic!("not a string array: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let entries = vec![
+            (tag::NAME, HeaderValue::String("agent".to_string())),
+            (tag::FILE_MODES, HeaderValue::Int16(vec![0o100644, 0o40755])),
+            (tag::SIZE, HeaderValue::Int32(vec![42])),
+            (tag::SIG_MD5, HeaderValue::Bin(vec![1, 2, 3])),
+            (
+                tag::BASENAMES,
+                HeaderValue::StringArray(vec!["a".to_string(), String::new()]),
+            ),
+            (
+                tag::SUMMARY,
+                HeaderValue::I18nString(vec!["Agent".to_string()]),
+            ),
+        ];
+        let header = write_header(entries.clone(), tag::HEADER_IMMUTABLE);
+        let (mut read, len) = read_header(&header, tag::HEADER_IMMUTABLE).unwrap();
+        assert_eq!(len, header.len());
+
+        let mut expected = entries;
+        expected.sort_by_key(|(tag, _)| *tag);
+        read.sort_by_key(|(tag, _)| *tag);
+        assert_eq!(read, expected);
+
+        // The region belongs to one header type
+        assert!(read_header(&header, tag::HEADER_SIGNATURES).is_err());
+    }
+
+    #[test]
+    fn test_package_rpm_layout() {
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+        let scripts = temp.path().join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        fs::write(scripts.join("post"), "systemctl daemon-reload\n").unwrap();
+        fs::write(scripts.join("preun"), "#!/usr/bin/env bash\nexit 0\n").unwrap();
+
+        let request = RpmRequest::new(&source, "contoso-agent", "1.2.0", temp.path().join("out"))
+            .with_release("3")
+            .with_epoch(1)
+            .with_architecture("x86_64")
+            .with_install_location("/opt/contoso")
+            .with_requires("glibc >= 2.17")
+            .with_requires("curl")
+            .with_scripts_folder(&scripts)
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+        assert_eq!(
+            result.output_path,
+            temp.path().join("out/contoso-agent-1.2.0-3.x86_64.rpm")
+        );
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.scripts, ["post", "preun"]);
+
+        let rpm = fs::read(&result.output_path).unwrap();
+        let parts = read_package(&rpm).unwrap();
+        assert_eq!(parts.lead_name, "contoso-agent-1.2.0-3");
+        assert_eq!(
+            parts.get(tag::NAME).and_then(HeaderValue::as_str),
+            Some("contoso-agent")
+        );
+        assert_eq!(parts.get(tag::EPOCH), Some(&HeaderValue::Int32(vec![1])));
+        assert_eq!(
+            parts.get(tag::SOURCE_RPM).and_then(HeaderValue::as_str),
+            Some("contoso-agent-1.2.0-3.src.rpm")
+        );
+
+        // Signature digests cover the header and payload
+        assert_eq!(
+            parts
+                .signature(tag::SIG_SHA256)
+                .and_then(HeaderValue::as_str),
+            Some(hex::encode(Sha256::digest(parts.header_bytes)).as_str())
+        );
+        assert_eq!(
+            parts.signature(tag::SIG_SIZE),
+            Some(&HeaderValue::Int32(vec![
+                (parts.header_bytes.len() + parts.payload.len()) as u32
+            ]))
+        );
+        let md5 = md5::compute([parts.header_bytes, parts.payload].concat());
+        assert_eq!(
+            parts.signature(tag::SIG_MD5),
+            Some(&HeaderValue::Bin(md5.0.to_vec()))
+        );
+
+        // Requirements keep their order, before the rpmlib features
+        let requires = strings(parts.get(tag::REQUIRE_NAME));
+        assert_eq!(requires[..2], ["glibc", "curl"]);
+        assert_eq!(strings(parts.get(tag::REQUIRE_VERSION))[..2], ["2.17", ""]);
+        assert_eq!(strings(parts.get(tag::PROVIDE_VERSION)), ["1:1.2.0-3"]);
+
+        // Scriptlets and their interpreters
+        assert_eq!(
+            parts.get(tag::POST_IN_PROG).and_then(HeaderValue::as_str),
+            Some("/bin/sh")
+        );
+        assert_eq!(
+            strings(parts.get(tag::PRE_UN_PROG)),
+            ["/usr/bin/env", "bash"]
+        );
+        assert!(parts.get(tag::PRE_IN).is_none());
+
+        // Files are listed in path order, matching the payload
+        let dirnames = strings(parts.get(tag::DIRNAMES));
+        let basenames = strings(parts.get(tag::BASENAMES));
+        let Some(HeaderValue::Int32(indexes)) = parts.get(tag::DIR_INDEXES) else {
+            panic!("no directory indexes");
+        };
+        let paths: Vec<String> = basenames
+            .iter()
+            .zip(indexes)
+            .map(|(base, index)| format!("{}{}", dirnames[*index as usize], base))
+            .collect();
+        let records = read_payload(parts.payload).unwrap();
+        let payload_paths: Vec<String> = records.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            payload_paths,
+            paths.iter().map(|p| format!(".{}", p)).collect::<Vec<_>>()
+        );
+        assert!(paths.contains(&"/opt/contoso/bin/agent".to_string()));
+
+        let agent = paths
+            .iter()
+            .position(|p| p == "/opt/contoso/bin/agent")
+            .unwrap();
+        assert_eq!(records[agent].data, b"#!/bin/sh\necho agent\n");
+        assert_eq!(
+            strings(parts.get(tag::FILE_DIGESTS))[agent],
+            hex::encode(Sha256::digest(b"#!/bin/sh\necho agent\n"))
+        );
+        #[cfg(unix)]
+        {
+            let run = paths.iter().position(|p| p == "/opt/contoso/run").unwrap();
+            assert!(records[run].is_symlink());
+            assert_eq!(strings(parts.get(tag::FILE_LINKTOS))[run], "bin/agent");
+            assert_eq!(records[agent].permissions(), 0o755);
+        }
+    }
+
+    #[test]
+    fn test_package_rpm_errors() {
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+        let request = RpmRequest::new(&source, "agent", "1.0", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+
+        let empty = temp.path().join("scripts");
+        fs::create_dir(&empty).unwrap();
+        assert!(
+            package(&request.clone().with_scripts_folder(&empty))
+                .unwrap_err()
+                .to_string()
+                .contains("No scriptlets")
+        );
+        assert!(matches!(
+            package(
+                &request
+                    .clone()
+                    .with_scripts_folder(temp.path().join("missing"))
+            ),
+            Err(PackageError::ScriptsFolderNotFound { .. })
+        ));
+
+        // The existing package is kept without --quiet
+        package(&request).unwrap();
+        assert!(matches!(
+            package(&request.with_verbosity(Verbosity::Normal)),
+            Err(PackageError::OutputFileExists { .. })
+        ));
+    }
+}
@@ -1,15 +1,22 @@
-//! Bill of Materials (BOM) file generation.
+//! Bill of Materials (BOM) file generation and reading.
 //!
 //! Cross-platform implementation of macOS BOM format.
 //! BOM files contain the manifest of all files in a macOS package.
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::models::PackageError;
 
 /// Entry for BOM file.
+///
+/// Built with [`BomEntry::new`] outside this crate, so fields can be added
+/// without breaking callers.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct BomEntry {
     /// File path relative to install location
     pub path: PathBuf,
@@ -19,16 +26,150 @@ pub struct BomEntry {
     pub uid: u32,
     /// Group ID (always 80 for packages)
     pub gid: u32,
-    /// File size in bytes
+    /// File size in bytes (the target's length for symbolic links)
     pub size: u64,
+    /// Target of a symbolic link
+    pub link_name: Option<Vec<u8>>,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: u32,
+    /// POSIX `cksum` CRC of the contents (of the target for symbolic
+    /// links, 0 for directories), which the installer verifies
+    pub checksum: u32,
+}
+
+impl BomEntry {
+    /// Entry for `path` owned by root:admin, like pkgbuild writes.
+    pub fn new(path: impl Into<PathBuf>, mode: u32, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            mode,
+            uid: 0,
+            gid: 80,
+            size,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
+        }
+    }
+
+    /// Set the owner and group IDs.
+    pub fn with_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// Make this a symbolic link to `target`.
+    pub fn with_link_name(mut self, target: impl Into<Vec<u8>>) -> Self {
+        self.link_name = Some(target.into());
+        self
+    }
+
+    /// Set the modification time, in seconds since the Unix epoch.
+    pub fn with_mtime(mut self, mtime: u32) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Set the `cksum` CRC of the contents (see [`cksum`]).
+    pub fn with_checksum(mut self, checksum: u32) -> Self {
+        self.checksum = checksum;
+        self
+    }
 }
 
 // BOM file type constants
 const TYPE_FILE: u8 = 1;
 const TYPE_DIR: u8 = 2;
-// const TYPE_LINK: u8 = 3;
+const TYPE_LINK: u8 = 3;
 // const TYPE_DEV: u8 = 4;
 
+/// CRC table of the POSIX `cksum` polynomial (0x04C11DB7, MSB first).
+const CKSUM_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Running POSIX `cksum` CRC, the checksum `mkbom` records for files.
+#[derive(Debug, Clone, Default)]
+pub struct Cksum {
+    crc: u32,
+    len: u64,
+}
+
+impl Cksum {
+    /// Start an empty checksum.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `data` to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.push(byte);
+        }
+        self.len += data.len() as u64;
+    }
+
+    /// The checksum; like `cksum`, the length is mixed in at the end.
+    pub fn finish(mut self) -> u32 {
+        let mut len = self.len;
+        while len > 0 {
+            self.push(len as u8);
+            len >>= 8;
+        }
+        !self.crc
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.crc = (self.crc << 8) ^ CKSUM_TABLE[((self.crc >> 24) as u8 ^ byte) as usize];
+    }
+}
+
+/// POSIX `cksum` CRC of `data`.
+pub fn cksum(data: &[u8]) -> u32 {
+    let mut sum = Cksum::new();
+    sum.update(data);
+    sum.finish()
+}
+
+/// POSIX `cksum` CRC of the file at `path`, read in chunks.
+fn cksum_file(path: &Path) -> Result<u32, PackageError> {
+    let read_error = |e: std::io::Error| PackageError::source_read(path.to_path_buf(), e);
+    let mut file = File::open(path).map_err(read_error)?;
+    let mut sum = Cksum::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).map_err(read_error)?;
+        if n == 0 {
+            return Ok(sum.finish());
+        }
+        sum.update(&buf[..n]);
+    }
+}
+
+/// Seconds since the Unix epoch as stored in a BOM (32 bits, clamped).
+fn bom_time(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| u32::try_from(d.as_secs()).unwrap_or(u32::MAX))
+        .unwrap_or(0)
+}
+
 /// BOM file writer - cross-platform implementation.
 struct BomWriter {
     /// Data blocks stored in the BOM
@@ -164,19 +305,24 @@ impl BomWriter {
 fn build_path_info2(entry: Option<&BomEntry>) -> Vec<u8> {
     let mut data = Vec::new();
 
-    let (file_type, mode, uid, gid, size) = match entry {
+    let (file_type, mode, uid, gid, mtime, size, checksum) = match entry {
         Some(e) => {
-            let is_dir = e.mode & 0o170000 == 0o040000;
-            let file_type = if is_dir { TYPE_DIR } else { TYPE_FILE };
+            let file_type = match e.mode & 0o170000 {
+                0o040000 => TYPE_DIR,
+                0o120000 => TYPE_LINK,
+                _ => TYPE_FILE,
+            };
             (
                 file_type,
                 (e.mode & 0xFFFF) as u16,
                 e.uid,
                 e.gid,
+                e.mtime,
                 e.size as u32,
+                e.checksum,
             )
         }
-        None => (TYPE_DIR, 0o40755, 0, 80, 0),
+        None => (TYPE_DIR, 0o40755, 0, 80, 0, 0, 0),
     };
 
     data.push(file_type);
@@ -185,11 +331,20 @@ fn build_path_info2(entry: Option<&BomEntry>) -> Vec<u8> {
     BomWriter::write_u16_be(&mut data, mode);
     BomWriter::write_u32_be(&mut data, uid);
     BomWriter::write_u32_be(&mut data, gid);
-    BomWriter::write_u32_be(&mut data, 0); // modtime
+    BomWriter::write_u32_be(&mut data, mtime);
     BomWriter::write_u32_be(&mut data, size);
     data.push(1); // unknown1
-    BomWriter::write_u32_be(&mut data, 0); // checksum
-    BomWriter::write_u32_be(&mut data, 0); // linkNameLength
+    BomWriter::write_u32_be(&mut data, checksum);
+
+    // linkNameLength counts the null terminator
+    match entry.and_then(|e| e.link_name.as_ref()) {
+        Some(link_name) if file_type == TYPE_LINK => {
+            BomWriter::write_u32_be(&mut data, link_name.len() as u32 + 1);
+            data.extend_from_slice(link_name);
+            data.push(0);
+        }
+        _ => BomWriter::write_u32_be(&mut data, 0),
+    }
 
     data
 }
@@ -335,24 +490,188 @@ fn bom_err(e: impl std::fmt::Display) -> PackageError {
     }
 }
 
+/// A path listed in a BOM, as [`read_bom`] returns it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BomRecord {
+    /// Path relative to the install location (`.` for the root)
+    pub path: String,
+    /// Unix mode (includes file type bits)
+    pub mode: u32,
+    /// File size in bytes
+    pub size: u64,
+    /// POSIX `cksum` CRC recorded for the contents
+    pub checksum: u32,
+}
+
+/// Bytes of the BOM block at `index`, checked against the file size.
+fn bom_block<'a>(data: &'a [u8], table: &[u8], index: u32) -> Result<&'a [u8], PackageError> {
+    let entry = 4 + index as usize * 8;
+    let field = |at: usize| {
+        table
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| bom_err(format!("Block {} is not in the block table", index)))
+    };
+    let (offset, len) = (field(entry)?, field(entry + 4)?);
+    data.get(offset..offset + len)
+        .ok_or_else(|| bom_err(format!("Block {} lies outside the file", index)))
+}
+
+fn be_u32(block: &[u8], at: usize) -> Result<u32, PackageError> {
+    block
+        .get(at..at + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| bom_err("Truncated BOM block"))
+}
+
+fn be_u16(block: &[u8], at: usize) -> Result<u16, PackageError> {
+    block
+        .get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| bom_err("Truncated BOM block"))
+}
+
+/// Read the paths listed in a BOM, checking its header, block table and
+/// `Paths` tree on the way.
+pub fn read_bom(data: &[u8]) -> Result<Vec<BomRecord>, PackageError> {
+    if !data.starts_with(b"BOMStore") || be_u32(data, 8)? != 1 {
+        return Err(bom_err("Not a BOMStore file"));
+    }
+    let index_offset = be_u32(data, 16)? as usize;
+    let index_len = be_u32(data, 20)? as usize;
+    let vars_offset = be_u32(data, 24)? as usize;
+    let vars_len = be_u32(data, 28)? as usize;
+    let table = data
+        .get(index_offset..index_offset + index_len)
+        .ok_or_else(|| bom_err("Block table lies outside the file"))?;
+    let vars = data
+        .get(vars_offset..vars_offset + vars_len)
+        .ok_or_else(|| bom_err("Variables lie outside the file"))?;
+
+    // Find the Paths variable
+    let mut paths_tree = None;
+    let mut pos = 4;
+    for _ in 0..be_u32(vars, 0)? {
+        let index = be_u32(vars, pos)?;
+        let name_len = *vars
+            .get(pos + 4)
+            .ok_or_else(|| bom_err("Truncated variables"))? as usize;
+        let name = vars
+            .get(pos + 5..pos + 5 + name_len)
+            .ok_or_else(|| bom_err("Truncated variables"))?;
+        if name == b"Paths" {
+            paths_tree = Some(index);
+        }
+        pos += 5 + name_len;
+    }
+    let paths_tree = paths_tree.ok_or_else(|| bom_err("BOM has no Paths variable"))?;
+
+    let tree = bom_block(data, table, paths_tree)?;
+    if !tree.starts_with(b"tree") {
+        return Err(bom_err("Paths is not a tree"));
+    }
+    let path_count = be_u32(tree, 16)? as usize;
+
+    // Descend to the first leaf, then follow the leaves in order
+    let mut node_index = be_u32(tree, 8)?;
+    let mut node = bom_block(data, table, node_index)?;
+    while be_u16(node, 0)? == 0 {
+        node_index = be_u32(node, 12)?;
+        node = bom_block(data, table, node_index)?;
+    }
+    let mut files: Vec<(u32, u32, String, &[u8])> = Vec::new();
+    loop {
+        for i in 0..be_u16(node, 2)? as usize {
+            let info1 = bom_block(data, table, be_u32(node, 12 + i * 8)?)?;
+            let file = bom_block(data, table, be_u32(node, 16 + i * 8)?)?;
+            let name = file.get(4..).unwrap_or_default();
+            let name = name.split(|&b| b == 0).next().unwrap_or_default();
+            files.push((
+                be_u32(info1, 0)?,
+                be_u32(file, 0)?,
+                String::from_utf8_lossy(name).into_owned(),
+                bom_block(data, table, be_u32(info1, 4)?)?,
+            ));
+            if files.len() > path_count {
+                return Err(bom_err("Paths tree holds more paths than it declares"));
+            }
+        }
+        let forward = be_u32(node, 4)?;
+        if forward == 0 {
+            break;
+        }
+        node = bom_block(data, table, forward)?;
+    }
+    if files.len() != path_count {
+        return Err(bom_err(format!(
+            "Paths tree declares {} paths but holds {}",
+            path_count,
+            files.len()
+        )));
+    }
+
+    // Join each name with its parents'
+    let mut paths: HashMap<u32, String> = HashMap::new();
+    let mut records = Vec::with_capacity(files.len());
+    for (id, parent, name, info2) in &files {
+        let path = match paths.get(parent) {
+            Some(parent) if parent != "." => format!("{}/{}", parent, name),
+            Some(_) | None if *parent <= 1 => name.clone(),
+            _ => return Err(bom_err(format!("'{}' is listed before its parent", name))),
+        };
+        paths.insert(*id, path.clone());
+        records.push(BomRecord {
+            path,
+            mode: u32::from(be_u16(info2, 4)?),
+            size: u64::from(be_u32(info2, 18)?),
+            checksum: be_u32(info2, 23)?,
+        });
+    }
+    Ok(records)
+}
+
 /// Create a BOM file by scanning a directory.
+///
+/// setuid/setgid/sticky bits are only kept when `allow_special_modes` is set.
 #[cfg(unix)]
-pub fn create_bom_from_directory(path: &std::path::Path) -> Result<Vec<u8>, PackageError> {
+pub fn create_bom_from_directory(
+    path: &std::path::Path,
+    allow_special_modes: bool,
+) -> Result<Vec<u8>, PackageError> {
+    use crate::macos::payload::{effective_mode, read_link_target};
+    use crate::packager::special::{WalkItem, classify};
     use std::os::unix::fs::MetadataExt;
     use walkdir::WalkDir;
 
     let mut entries = Vec::new();
-    for entry in WalkDir::new(path).min_depth(1) {
-        let entry = entry.map_err(bom_err)?;
+    for result in WalkDir::new(path).min_depth(1) {
+        let entry = match classify(path, result) {
+            WalkItem::Entry(entry) => entry,
+            WalkItem::Skipped(_) => continue,
+            WalkItem::Error(e) => return Err(bom_err(e)),
+        };
         let rel_path = entry.path().strip_prefix(path).map_err(bom_err)?;
         let metadata = entry.metadata().map_err(bom_err)?;
+        let link_name = if metadata.file_type().is_symlink() {
+            Some(read_link_target(entry.path())?)
+        } else {
+            None
+        };
+        let checksum = match &link_name {
+            Some(target) => cksum(target),
+            None if metadata.is_file() => cksum_file(entry.path())?,
+            None => 0,
+        };
 
         entries.push(BomEntry {
             path: rel_path.to_path_buf(),
-            mode: metadata.mode(),
+            mode: effective_mode(metadata.mode(), allow_special_modes),
             uid: metadata.uid(),
             gid: metadata.gid(),
             size: metadata.len(),
+            link_name,
+            mtime: metadata.modified().map(bom_time).unwrap_or(0),
+            checksum,
         });
     }
 
@@ -360,8 +679,13 @@ pub fn create_bom_from_directory(path: &std::path::Path) -> Result<Vec<u8>, Pack
 }
 
 /// Create a BOM file by scanning a directory (Windows version).
+///
+/// Windows has no special mode bits, so `_allow_special_modes` has no effect.
 #[cfg(windows)]
-pub fn create_bom_from_directory(path: &std::path::Path) -> Result<Vec<u8>, PackageError> {
+pub fn create_bom_from_directory(
+    path: &std::path::Path,
+    _allow_special_modes: bool,
+) -> Result<Vec<u8>, PackageError> {
     use walkdir::WalkDir;
 
     let mut entries = Vec::new();
@@ -378,12 +702,21 @@ pub fn create_bom_from_directory(path: &std::path::Path) -> Result<Vec<u8>, Pack
             0o100644
         };
 
+        let checksum = if metadata.is_file() {
+            cksum_file(entry.path())?
+        } else {
+            0
+        };
+
         entries.push(BomEntry {
             path: rel_path.to_path_buf(),
             mode,
             uid: 0,
             gid: 80,
             size: metadata.len(),
+            link_name: None,
+            mtime: metadata.modified().map(bom_time).unwrap_or(0),
+            checksum,
         });
     }
 
@@ -413,6 +746,68 @@ mod tests {
             .any(|w| &w[..needle_bytes.len()] == needle_bytes && w[needle_bytes.len()] == 0)
     }
 
+    // ==================== Checksum tests ====================
+
+    #[test]
+    fn test_cksum_matches_posix_cksum() {
+        // Values printed by `printf ... | cksum`
+        assert_eq!(cksum(b""), 4294967295);
+        assert_eq!(cksum(b"hello\n"), 3015617425);
+        assert_eq!(cksum(b"target.txt"), 2503535117);
+
+        let mut sum = Cksum::new();
+        sum.update(b"hel");
+        sum.update(b"lo\n");
+        assert_eq!(sum.finish(), 3015617425);
+    }
+
+    #[test]
+    fn test_path_info2_records_mtime_and_checksum() {
+        let entry = BomEntry {
+            path: PathBuf::from("hello.txt"),
+            mode: 0o100644,
+            uid: 0,
+            gid: 80,
+            size: 6,
+            link_name: None,
+            mtime: 1_700_000_000,
+            checksum: 3015617425,
+        };
+        let data = build_path_info2(Some(&entry));
+
+        // type, unknown0, architecture, mode, uid, gid, then modtime
+        assert_eq!(read_u32_be(&data, 14), 1_700_000_000);
+        assert_eq!(read_u32_be(&data, 18), 6);
+        // size is followed by one unknown byte, then the checksum
+        assert_eq!(read_u32_be(&data, 23), 3015617425);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bom_from_directory_records_mtime_and_checksum() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("hello.txt");
+        std::fs::write(&file, b"hello\n").unwrap();
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        std::os::unix::fs::symlink("target.txt", temp.path().join("link")).unwrap();
+
+        let bom_data = create_bom_from_directory(temp.path(), false).unwrap();
+        let contains_u32 = |value: u32| {
+            bom_data
+                .windows(4)
+                .any(|w| w == value.to_be_bytes().as_slice())
+        };
+        assert!(contains_u32(1_700_000_000));
+        assert!(contains_u32(3015617425));
+        assert!(contains_u32(2503535117));
+    }
+
     // ==================== Error handling tests ====================
 
     #[test]
@@ -440,6 +835,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
         assert_eq!(
@@ -457,6 +855,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -472,6 +873,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -506,6 +910,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -531,6 +938,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -562,6 +972,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -598,6 +1011,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -622,6 +1038,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -644,6 +1063,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 100,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("file2.txt"),
@@ -651,6 +1073,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 200,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -665,6 +1090,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_bom_roundtrip() {
+        let entry = |path: &str, mode: u32, size: u64, checksum: u32| BomEntry {
+            path: PathBuf::from(path),
+            mode,
+            uid: 0,
+            gid: 80,
+            size,
+            link_name: None,
+            mtime: 0,
+            checksum,
+        };
+        let bom_data = create_bom(&[
+            entry("Contents", 0o040755, 0, 0),
+            entry("Contents/MacOS/myapp", 0o100755, 1000, 0xdead_beef),
+            entry("README", 0o100644, 12, 7),
+        ])
+        .unwrap();
+
+        let records = read_bom(&bom_data).unwrap();
+        let paths: Vec<&str> = records.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                ".",
+                "Contents",
+                "Contents/MacOS",
+                "Contents/MacOS/myapp",
+                "README"
+            ]
+        );
+        assert_eq!(records[3].mode, 0o100755);
+        assert_eq!(records[3].size, 1000);
+        assert_eq!(records[3].checksum, 0xdead_beef);
+
+        assert!(read_bom(b"not a bom").is_err());
+        assert!(read_bom(&bom_data[..bom_data.len() - 8]).is_err());
+    }
+
     #[test]
     fn test_bom_nested_directory_structure() {
         let bom_data = create_bom(&[
@@ -674,6 +1138,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 0,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("Contents/MacOS"),
@@ -681,6 +1148,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 0,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("Contents/MacOS/myapp"),
@@ -688,6 +1158,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 1000,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -715,6 +1188,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 10,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -736,6 +1212,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 50000,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
         assert!(bom_data.len() > 50, "BOM should handle deep paths");
@@ -757,6 +1236,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 10,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("dir/file2.txt"),
@@ -764,6 +1246,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 20,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("dir/file3.txt"),
@@ -771,6 +1256,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 30,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -790,6 +1278,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 10,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("dir2/file.txt"),
@@ -797,6 +1288,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 20,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -815,6 +1309,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }];
         let result = create_bom(&entries);
         assert!(
@@ -832,6 +1329,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 0,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("mydir/file.txt"),
@@ -839,6 +1339,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 100,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -857,6 +1360,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 1000,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("readonly"),
@@ -864,6 +1370,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 500,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ];
         let result = create_bom(&entries);
@@ -883,6 +1392,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 100,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("directory"),
@@ -890,6 +1402,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 0,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -907,6 +1422,9 @@ mod tests {
             uid: 1000,
             gid: 1000,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }];
         let result = create_bom(&entries);
         assert!(result.is_ok(), "BOM should accept custom uid/gid values");
@@ -923,6 +1441,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -937,6 +1458,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }]);
         assert!(result.is_ok(), "BOM should handle spaces in filenames");
 
@@ -952,6 +1476,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }]);
         assert!(result.is_ok(), "BOM should handle unicode filenames");
     }
@@ -965,6 +1492,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: i as u64,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             })
             .collect();
 
@@ -986,6 +1516,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 4_294_967_295, // Max u32
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }]);
         assert!(result.is_ok(), "BOM should handle large file sizes");
     }
@@ -998,6 +1531,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 0,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }]);
         assert!(result.is_ok(), "BOM should handle zero-size files");
     }
@@ -1011,6 +1547,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 100,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("binary"),
@@ -1018,6 +1557,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 50000,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ]);
         assert!(result.is_ok(), "BOM should handle executable files");
@@ -1033,6 +1575,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -1062,6 +1607,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 10,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("file2.txt"),
@@ -1069,6 +1617,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 20,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("file3.txt"),
@@ -1076,6 +1627,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 30,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ])
         .unwrap();
@@ -1106,6 +1660,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -1130,6 +1687,9 @@ mod tests {
             uid: 0,
             gid: 80,
             size: 5,
+            link_name: None,
+            mtime: 0,
+            checksum: 0,
         }])
         .unwrap();
 
@@ -1158,7 +1718,7 @@ mod tests {
         let mut file = File::create(&file_path).unwrap();
         file.write_all(b"hello").unwrap();
 
-        let result = create_bom_from_directory(temp_dir.path());
+        let result = create_bom_from_directory(temp_dir.path(), false);
         assert!(result.is_ok(), "Should create BOM from directory");
 
         let bom_data = result.unwrap();
@@ -1185,7 +1745,7 @@ mod tests {
         let mut f2 = File::create(&file2).unwrap();
         f2.write_all(b"nested").unwrap();
 
-        let result = create_bom_from_directory(temp_dir.path());
+        let result = create_bom_from_directory(temp_dir.path(), false);
         assert!(result.is_ok(), "Should create BOM from nested directory");
 
         let bom_data = result.unwrap();
@@ -1201,13 +1761,77 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         // Empty directory - no files
 
-        let result = create_bom_from_directory(temp_dir.path());
+        let result = create_bom_from_directory(temp_dir.path(), false);
         // Should fail because no entries
         assert!(result.is_err(), "Empty directory should fail");
     }
 
     // ==================== Determinism test ====================
 
+    #[cfg(unix)]
+    #[test]
+    fn test_create_bom_from_directory_special_modes() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let helper = temp_dir.path().join("helper");
+        fs::write(&helper, "binary").unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o4755)).unwrap();
+
+        // PathInfo2 stores the mode as a big-endian u16
+        let setuid_mode = (0o104755u16).to_be_bytes();
+        let plain_mode = (0o100755u16).to_be_bytes();
+
+        let stripped = create_bom_from_directory(temp_dir.path(), false).unwrap();
+        assert!(stripped.windows(2).any(|w| w == plain_mode));
+        assert!(!stripped.windows(2).any(|w| w == setuid_mode));
+
+        let preserved = create_bom_from_directory(temp_dir.path(), true).unwrap();
+        assert!(preserved.windows(2).any(|w| w == setuid_mode));
+    }
+
+    #[test]
+    fn test_bom_symlink_entry() {
+        let bom_data = create_bom(&[BomEntry {
+            path: PathBuf::from("Versions/Current"),
+            mode: 0o120755,
+            uid: 0,
+            gid: 80,
+            size: 1,
+            link_name: Some(b"A".to_vec()),
+            mtime: 0,
+            checksum: 0,
+        }])
+        .unwrap();
+
+        // type 3, then the link name (with terminator) after checksum
+        let mut link_info = vec![TYPE_LINK, 1, 0, 3];
+        link_info.extend_from_slice(&0o120755u16.to_be_bytes());
+        assert!(bom_data.windows(6).any(|w| w == link_info));
+        let mut link_name = 2u32.to_be_bytes().to_vec();
+        link_name.extend_from_slice(b"A\0");
+        assert!(bom_data.windows(6).any(|w| w == link_name));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_bom_from_directory_symlinks() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("A")).unwrap();
+        std::os::unix::fs::symlink("A", temp_dir.path().join("Current")).unwrap();
+
+        let bom_data = create_bom_from_directory(temp_dir.path(), false).unwrap();
+        let mut link_name = 2u32.to_be_bytes().to_vec();
+        link_name.extend_from_slice(b"A\0");
+        assert!(bom_data.windows(6).any(|w| w == link_name));
+        assert!(find_string_in_bom(&bom_data, "Current"));
+    }
+
     #[test]
     fn test_bom_deterministic_output() {
         let entries = vec![
@@ -1217,6 +1841,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 10,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
             BomEntry {
                 path: PathBuf::from("file2.txt"),
@@ -1224,6 +1851,9 @@ mod tests {
                 uid: 0,
                 gid: 80,
                 size: 20,
+                link_name: None,
+                mtime: 0,
+                checksum: 0,
             },
         ];
 
@@ -1282,6 +1912,9 @@ mod tests {
                     uid,
                     gid,
                     size,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 })
         }
 
@@ -1421,6 +2054,9 @@ mod tests {
                     uid: 0,
                     gid: 80,
                     size,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 };
                 let result = create_bom(&[entry]);
                 prop_assert!(result.is_ok(), "BOM failed with size {}", size);
@@ -1435,6 +2071,9 @@ mod tests {
                     uid,
                     gid,
                     size: 100,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 };
                 let result = create_bom(&[entry]);
                 prop_assert!(result.is_ok(), "BOM failed with uid={}, gid={}", uid, gid);
@@ -1450,6 +2089,9 @@ mod tests {
                     uid: 0,
                     gid: 80,
                     size: 10,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 };
                 let result = create_bom(&[entry]);
                 prop_assert!(result.is_ok(), "BOM failed with depth {}", depth);
@@ -1476,6 +2118,9 @@ mod tests {
                         uid: 0,
                         gid: 80,
                         size: i as u64,
+                        link_name: None,
+                        mtime: 0,
+                        checksum: 0,
                     })
                     .collect();
 
@@ -1499,6 +2144,9 @@ mod tests {
                         uid: 0,
                         gid: 80,
                         size: 0,
+                        link_name: None,
+                        mtime: 0,
+                        checksum: 0,
                     });
                 }
 
@@ -1510,6 +2158,9 @@ mod tests {
                         uid: 0,
                         gid: 80,
                         size: i as u64 * 100,
+                        link_name: None,
+                        mtime: 0,
+                        checksum: 0,
                     });
                 }
 
@@ -1528,6 +2179,9 @@ mod tests {
                     uid: (i % 1000) as u32,
                     gid: 80,
                     size: i as u64 * 1000,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 })
                 .collect();
 
@@ -1551,6 +2205,9 @@ mod tests {
                     uid: 0,
                     gid: 80,
                     size: 5,
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 };
                 let result = create_bom(&[entry]);
                 assert!(result.is_ok(), "Failed for path length {}", len);
@@ -1572,6 +2229,9 @@ mod tests {
                     uid: 0,
                     gid: 80,
                     size: if mode & 0o170000 == 0o040000 { 0 } else { 100 },
+                    link_name: None,
+                    mtime: 0,
+                    checksum: 0,
                 };
                 let result = create_bom(&[entry]);
                 assert!(result.is_ok(), "Failed for mode {:o}", mode);
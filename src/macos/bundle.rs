@@ -0,0 +1,364 @@
+//! Application bundles packaged as components.
+//!
+//! Like `pkgbuild --component`, a `.app` folder is installed as a whole
+//! under the install location, and PackageInfo names it by its
+//! `CFBundleIdentifier` so the receipt database tracks the installed app.
+//! `Contents/Info.plist` may be an XML or binary (`bplist00`) property list;
+//! only top-level string values are read.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::SourceFilter;
+use crate::models::macos::AppBundle;
+use crate::packager::workspace::{StagingDir, stage_tree, staged_copy_size};
+
+/// Location of the property list inside a bundle.
+pub const INFO_PLIST: &str = "Contents/Info.plist";
+
+/// Read the identifier and versions of the `.app` bundle at `path`.
+pub fn read_app_bundle(path: &Path) -> PackageResult<AppBundle> {
+    let fail = |reason: String| PackageError::InvalidBundle {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let is_app = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("app"));
+    if !is_app || !path.is_dir() {
+        return Err(fail("not an .app bundle folder".to_string()));
+    }
+
+    let data = fs::read(path.join(INFO_PLIST))
+        .map_err(|e| fail(format!("cannot read {}: {}", INFO_PLIST, e)))?;
+    let mut values = parse_info_plist(&data).map_err(|e| fail(format!("{}: {}", INFO_PLIST, e)))?;
+
+    let identifier = values
+        .remove("CFBundleIdentifier")
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| fail(format!("{} has no CFBundleIdentifier", INFO_PLIST)))?;
+
+    Ok(AppBundle {
+        path: path.to_path_buf(),
+        identifier,
+        short_version: values.remove("CFBundleShortVersionString"),
+        bundle_version: values.remove("CFBundleVersion"),
+    })
+}
+
+/// Top-level string values of an XML or binary property list.
+pub fn parse_info_plist(data: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    if data.starts_with(b"bplist00") {
+        parse_binary_plist(data)
+    } else {
+        parse_xml_plist(data)
+    }
+}
+
+/// Stage `bundle` as the only entry of a payload root.
+pub(crate) fn stage_bundle(bundle: &AppBundle) -> PackageResult<StagingDir> {
+    let staging = StagingDir::create_with_space("component", staged_copy_size(&bundle.path))?;
+    stage_tree(
+        &bundle.path,
+        &staging.path.join(bundle.name()),
+        &BTreeMap::new(),
+        &SourceFilter::default(),
+    )?;
+    Ok(staging)
+}
+
+fn parse_xml_plist(data: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    let mut reader = Reader::from_reader(data);
+    let mut buf = Vec::new();
+    let mut values = BTreeMap::new();
+    // Element names from the root down; the top-level dict is ["plist", "dict"]
+    let mut elements: Vec<Vec<u8>> = Vec::new();
+    let mut key: Option<String> = None;
+    let mut text = String::new();
+    let mut seen_dict = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                elements.push(e.name().as_ref().to_vec());
+                text.clear();
+            }
+            Ok(Event::Text(e)) => {
+                let unescaped = e.unescape().map_err(|e| e.to_string())?;
+                text.push_str(&unescaped);
+            }
+            Ok(Event::Empty(e)) => {
+                let top_level = elements.len() == 2;
+                if top_level && e.name().as_ref() == b"string" {
+                    if let Some(key) = key.take() {
+                        values.insert(key, String::new());
+                    }
+                } else if top_level {
+                    key = None;
+                }
+            }
+            Ok(Event::End(_)) => {
+                let name = elements.pop().unwrap_or_default();
+                match (elements.len(), name.as_slice()) {
+                    (1, b"dict") => seen_dict = true,
+                    (2, b"key") => key = Some(text.trim().to_string()),
+                    (2, b"string") => {
+                        if let Some(key) = key.take() {
+                            values.insert(key, text.clone());
+                        }
+                    }
+                    (2, _) => key = None,
+                    _ => {}
+                }
+                text.clear();
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(format!("invalid property list: {}", e)),
+        }
+        buf.clear();
+    }
+
+    if !seen_dict {
+        return Err("property list has no top-level dictionary".to_string());
+    }
+    Ok(values)
+}
+
+/// Big-endian unsigned integer of `size` bytes at `offset`.
+fn read_uint(data: &[u8], offset: usize, size: usize) -> Result<u64, String> {
+    let bytes = offset
+        .checked_add(size)
+        .and_then(|end| data.get(offset..end))
+        .filter(|_| size <= 8)
+        .ok_or_else(|| "truncated binary property list".to_string())?;
+    Ok(bytes
+        .iter()
+        .fold(0, |value, b| (value << 8) | u64::from(*b)))
+}
+
+fn parse_binary_plist(data: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    let truncated = || "truncated binary property list".to_string();
+    let trailer = data.len().checked_sub(32).ok_or_else(truncated)?;
+    let offset_size = data[trailer + 6] as usize;
+    let ref_size = data[trailer + 7] as usize;
+    let object_count = read_uint(data, trailer + 8, 8)?;
+    let top_object = read_uint(data, trailer + 16, 8)?;
+    let offset_table = read_uint(data, trailer + 24, 8)? as usize;
+
+    let object_offset = |index: u64| -> Result<usize, String> {
+        if index >= object_count {
+            return Err(truncated());
+        }
+        let entry = (index as usize)
+            .checked_mul(offset_size)
+            .and_then(|n| n.checked_add(offset_table))
+            .ok_or_else(truncated)?;
+        Ok(read_uint(data, entry, offset_size)? as usize)
+    };
+
+    // Object length from the marker's low nibble, or the integer after it
+    let length = |offset: usize| -> Result<(usize, usize), String> {
+        let marker = *data.get(offset).ok_or_else(truncated)?;
+        if marker & 0x0f != 0x0f {
+            return Ok(((marker & 0x0f) as usize, offset + 1));
+        }
+        let int_marker = *data.get(offset + 1).ok_or_else(truncated)?;
+        if int_marker & 0xf0 != 0x10 {
+            return Err("invalid object length in binary property list".to_string());
+        }
+        let size = 1usize << (int_marker & 0x0f);
+        Ok((
+            read_uint(data, offset + 2, size)? as usize,
+            offset + 2 + size,
+        ))
+    };
+
+    let string = |index: u64| -> Result<Option<String>, String> {
+        let offset = object_offset(index)?;
+        let (count, start) = length(offset)?;
+        let bytes = |len: usize| {
+            start
+                .checked_add(len)
+                .and_then(|end| data.get(start..end))
+                .ok_or_else(truncated)
+        };
+        match data[offset] >> 4 {
+            // ASCII string
+            0x5 => Ok(Some(String::from_utf8_lossy(bytes(count)?).into_owned())),
+            // UTF-16BE string
+            0x6 => {
+                let units: Vec<u16> = bytes(count.checked_mul(2).ok_or_else(truncated)?)?
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                Ok(Some(String::from_utf16_lossy(&units)))
+            }
+            _ => Ok(None),
+        }
+    };
+
+    let root = object_offset(top_object)?;
+    let (count, refs) = length(root)?;
+    if data[root] >> 4 != 0xd {
+        return Err("property list has no top-level dictionary".to_string());
+    }
+    if count > data.len() {
+        return Err(truncated());
+    }
+
+    let mut values = BTreeMap::new();
+    for i in 0..count {
+        let key_ref = read_uint(data, refs + i * ref_size, ref_size)?;
+        let value_ref = read_uint(data, refs + (count + i) * ref_size, ref_size)?;
+        if let (Some(key), Some(value)) = (string(key_ref)?, string(value_ref)?) {
+            values.insert(key, value);
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const INFO_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleDocumentTypes</key>
+    <array>
+        <dict>
+            <key>CFBundleIdentifier</key>
+            <string>com.example.nested</string>
+        </dict>
+    </array>
+    <key>CFBundleIdentifier</key>
+    <string>com.example.myapp</string>
+    <key>LSRequiresNativeExecution</key>
+    <true/>
+    <key>CFBundleShortVersionString</key>
+    <string>2.1 &amp; beta</string>
+    <key>CFBundleVersion</key>
+    <string>210</string>
+</dict>
+</plist>
+"#;
+
+    /// Binary plist of {"CFBundleIdentifier": "com.example.myapp",
+    /// "CFBundleVersion": "7"} with an integer-length key.
+    fn binary_plist() -> Vec<u8> {
+        let mut data = b"bplist00".to_vec();
+        let mut offsets = Vec::new();
+
+        offsets.push(data.len());
+        data.extend([0xd2, 1, 2, 3, 4]);
+        offsets.push(data.len());
+        data.extend([0x5f, 0x10, 18]);
+        data.extend(b"CFBundleIdentifier");
+        offsets.push(data.len());
+        data.push(0x5f);
+        data.extend([0x10, 15]);
+        data.extend(b"CFBundleVersion");
+        offsets.push(data.len());
+        data.push(0x6f);
+        data.extend([0x10, 17]);
+        for unit in "com.example.myapp".encode_utf16() {
+            data.extend(unit.to_be_bytes());
+        }
+        offsets.push(data.len());
+        data.extend([0x51, b'7']);
+
+        let offset_table = data.len();
+        data.extend(offsets.iter().map(|&o| o as u8));
+        data.extend([0; 6]);
+        data.extend([1, 1]);
+        data.extend((offsets.len() as u64).to_be_bytes());
+        data.extend(0u64.to_be_bytes());
+        data.extend((offset_table as u64).to_be_bytes());
+        data
+    }
+
+    fn create_app(temp: &TempDir, plist: &[u8]) -> std::path::PathBuf {
+        let app = temp.path().join("MyApp.app");
+        fs::create_dir_all(app.join("Contents/MacOS")).unwrap();
+        fs::write(app.join(INFO_PLIST), plist).unwrap();
+        fs::write(app.join("Contents/MacOS/MyApp"), "binary").unwrap();
+        app
+    }
+
+    #[test]
+    fn test_parse_xml_plist_top_level_strings() {
+        let values = parse_info_plist(INFO_XML.as_bytes()).unwrap();
+        assert_eq!(values["CFBundleIdentifier"], "com.example.myapp");
+        assert_eq!(values["CFBundleShortVersionString"], "2.1 & beta");
+        assert_eq!(values["CFBundleVersion"], "210");
+        assert!(!values.contains_key("LSRequiresNativeExecution"));
+
+        assert!(parse_info_plist(b"<plist></plist>").is_err());
+    }
+
+    #[test]
+    fn test_parse_binary_plist() {
+        let values = parse_info_plist(&binary_plist()).unwrap();
+        assert_eq!(values["CFBundleIdentifier"], "com.example.myapp");
+        assert_eq!(values["CFBundleVersion"], "7");
+
+        let truncated = &binary_plist()[..40];
+        assert!(parse_info_plist(truncated).is_err());
+    }
+
+    #[test]
+    fn test_read_app_bundle() {
+        let temp = TempDir::new().unwrap();
+        let app = create_app(&temp, INFO_XML.as_bytes());
+
+        let bundle = read_app_bundle(&app).unwrap();
+        assert_eq!(bundle.name(), "MyApp.app");
+        assert_eq!(bundle.identifier, "com.example.myapp");
+        assert_eq!(bundle.version(), Some("2.1 & beta"));
+
+        let bundle = read_app_bundle(&create_app(&temp, &binary_plist())).unwrap();
+        assert_eq!(bundle.version(), Some("7"));
+    }
+
+    #[test]
+    fn test_read_app_bundle_rejects_non_bundles() {
+        let temp = TempDir::new().unwrap();
+        let err = read_app_bundle(temp.path()).unwrap_err();
+        assert!(err.to_string().contains("not an .app bundle"));
+
+        let app = temp.path().join("Empty.app");
+        fs::create_dir(&app).unwrap();
+        let err = read_app_bundle(&app).unwrap_err();
+        assert!(matches!(err, PackageError::InvalidBundle { .. }));
+
+        let app = create_app(&temp, b"<plist><dict></dict></plist>");
+        let err = read_app_bundle(&app).unwrap_err();
+        assert!(err.to_string().contains("no CFBundleIdentifier"));
+    }
+
+    #[test]
+    fn test_stage_bundle() {
+        let temp = TempDir::new().unwrap();
+        let bundle = read_app_bundle(&create_app(&temp, INFO_XML.as_bytes())).unwrap();
+
+        let staging = stage_bundle(&bundle).unwrap();
+        let entries: Vec<_> = fs::read_dir(&staging.path)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["MyApp.app"]);
+        assert_eq!(
+            fs::read(staging.path.join("MyApp.app/Contents/MacOS/MyApp")).unwrap(),
+            b"binary"
+        );
+    }
+}
@@ -1,16 +1,29 @@
-//! CPIO archive wrapper for macOS package payloads.
+//! CPIO archive wrapper for macOS package and RPM payloads.
 //!
-//! macOS packages use CPIO odc (portable ASCII) format for payloads.
+//! macOS packages use CPIO odc (portable ASCII) format for payloads; very
+//! large payloads fall back to newc (see [`CpioFormat`]). RPM payloads are
+//! always newc, owned by root:root, with each entry's own modification
+//! time.
+//!
+//! Hard links share an inode number and link count; only the first link
+//! carries the file data, later ones are stored with a size of zero.
 
-use std::io::Write;
+use std::collections::HashMap;
+use std::io::{Read, Write};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use flate2::Compression;
+use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 
 use crate::models::PackageError;
+use crate::models::size::ByteSize;
 
 /// File entry for CPIO archive: (path, data, mode)
+///
+/// A mode with the [`S_IFLNK`] type bits makes the entry a symbolic link
+/// whose data is the link target, one with [`S_IFDIR`] a directory (without
+/// data); any other mode is a regular file.
 pub type CpioEntry = (String, Vec<u8>, u32);
 
 /// Root UID for macOS packages
@@ -19,13 +32,101 @@ const ROOT_UID: u32 = 0;
 /// Wheel GID for macOS packages (admin group)
 const WHEEL_GID: u32 = 80;
 
+/// Root GID, the group of RPM payloads
+pub const ROOT_GID: u32 = 0;
+
 /// Regular file type bits
-const S_IFREG: u32 = 0o100000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// File type mask
+pub const S_IFMT: u32 = 0o170000;
+
+/// Directory type bits
+pub const S_IFDIR: u32 = 0o040000;
+
+/// Symbolic link type bits
+pub const S_IFLNK: u32 = 0o120000;
+
+/// Magic of an odc header
+const ODC_MAGIC: &[u8; 6] = b"070707";
+
+/// Length of an odc header
+const ODC_HEADER_LEN: usize = 76;
+
+/// Name of the entry that ends an archive
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// Magic of a newc header
+const NEWC_MAGIC: &[u8; 6] = b"070701";
 
-/// CPIO odc header format (76 bytes ASCII).
+/// Length of a newc header
+const NEWC_HEADER_LEN: usize = 110;
+
+/// Longest path macOS accepts, in bytes (`PATH_MAX` without the
+/// terminator).
+pub const MAX_PATH_BYTES: usize = 1023;
+
+/// Longest file name macOS accepts, in bytes (`NAME_MAX`).
+pub const MAX_NAME_BYTES: usize = 255;
+
+/// Largest value of a 6-digit octal odc field, e.g. the inode number.
+const ODC_MAX_SHORT: u64 = 0o777_777;
+
+/// Largest value of an 11-digit octal odc field: files must stay below
+/// 8 GiB.
+const ODC_MAX_LONG: u64 = 0o77_777_777_777;
+
+/// Header format of a CPIO archive.
 ///
-/// Format: magic(6) + dev(6) + ino(6) + mode(6) + uid(6) + gid(6) +
-///         nlink(6) + rdev(6) + mtime(11) + namesize(6) + filesize(11)
+/// Payloads use odc, like `pkgbuild`. Its 6-digit octal inode field runs
+/// out after 262,143 entries, so larger payloads are written as newc,
+/// whose 8-digit hex fields hold 4 billion entries but limit files to
+/// 4 GiB. Both are read by the macOS Installer and `cpio`/`ditto`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CpioFormat {
+    /// Portable ASCII (`070707`) with octal fields
+    #[default]
+    Odc,
+    /// SVR4 (`070701`) with hex fields, without checksums
+    Newc,
+}
+
+impl CpioFormat {
+    /// The format for an archive of `entries` entries: odc if its inode
+    /// numbers fit, newc otherwise.
+    pub fn for_entries(entries: usize) -> Self {
+        if entries as u64 <= ODC_MAX_SHORT {
+            CpioFormat::Odc
+        } else {
+            CpioFormat::Newc
+        }
+    }
+
+    /// Largest file the format can hold, in bytes.
+    pub fn max_file_size(self) -> u64 {
+        match self {
+            CpioFormat::Odc => ODC_MAX_LONG,
+            CpioFormat::Newc => u64::from(u32::MAX),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CpioFormat::Odc => "odc",
+            CpioFormat::Newc => "newc",
+        }
+    }
+}
+
+/// A CPIO header; serialized as odc (76 bytes) or newc (110 bytes).
+///
+/// odc: magic(6) + dev(6) + ino(6) + mode(6) + uid(6) + gid(6) +
+///      nlink(6) + rdev(6) + mtime(11) + namesize(6) + filesize(11),
+///      all octal
+///
+/// newc: magic(6) + ino + mode + uid + gid + nlink + mtime + filesize +
+///       devmajor + devminor + rdevmajor + rdevminor + namesize + check,
+///       8 hex digits each
 struct CpioHeader {
     dev: u32,
     ino: u32,
@@ -40,22 +141,20 @@ struct CpioHeader {
 }
 
 impl CpioHeader {
-    /// Create a new CPIO header for a file.
-    fn for_file(mode: u32, size: u64, name_len: usize, ino: u32) -> Self {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-
+    /// Create a new CPIO header for a file, directory or symbolic link.
+    fn for_file(mode: u32, size: u64, name_len: usize, ino: u32, nlink: u32, mtime: u64) -> Self {
         Self {
             dev: 0,
             ino,
-            mode: S_IFREG | (mode & 0o7777),
+            mode: match mode & S_IFMT {
+                S_IFLNK | S_IFDIR => mode & (S_IFMT | 0o7777),
+                _ => S_IFREG | (mode & 0o7777),
+            },
             uid: ROOT_UID,
             gid: WHEEL_GID,
-            nlink: 1,
+            nlink,
             rdev: 0,
-            mtime: now,
+            mtime,
             namesize: (name_len + 1) as u32, // +1 for null terminator
             filesize: size,
         }
@@ -77,78 +176,285 @@ impl CpioHeader {
         }
     }
 
-    /// Serialize to 76-byte ASCII octal format.
-    fn to_bytes(&self) -> Vec<u8> {
-        format!(
-            "{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
-            0o70707, // magic
-            self.dev,
-            self.ino,
-            self.mode,
-            self.uid,
-            self.gid,
-            self.nlink,
-            self.rdev,
-            self.mtime,
-            self.namesize,
-            self.filesize,
-        )
+    /// The first field that does not fit `format`, with its value.
+    fn overflow(&self, format: CpioFormat) -> Option<(&'static str, u64)> {
+        let (short, long) = match format {
+            CpioFormat::Odc => (ODC_MAX_SHORT, ODC_MAX_LONG),
+            CpioFormat::Newc => (u64::from(u32::MAX), u64::from(u32::MAX)),
+        };
+        [
+            ("inode number", u64::from(self.ino), short),
+            ("link count", u64::from(self.nlink), short),
+            ("name size", u64::from(self.namesize), short),
+            ("modification time", self.mtime, long),
+            ("file size", self.filesize, long),
+        ]
+        .into_iter()
+        .find(|(_, value, max)| value > max)
+        .map(|(field, value, _)| (field, value))
+    }
+
+    /// Serialize in `format`; the caller checks [`overflow`](Self::overflow)
+    /// first.
+    fn to_bytes(&self, format: CpioFormat) -> Vec<u8> {
+        match format {
+            CpioFormat::Odc => format!(
+                "{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:06o}{:011o}{:06o}{:011o}",
+                0o70707, // magic
+                self.dev,
+                self.ino,
+                self.mode,
+                self.uid,
+                self.gid,
+                self.nlink,
+                self.rdev,
+                self.mtime,
+                self.namesize,
+                self.filesize,
+            ),
+            CpioFormat::Newc => format!(
+                "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+                self.ino,
+                self.mode,
+                self.uid,
+                self.gid,
+                self.nlink,
+                self.mtime,
+                self.filesize,
+                0, // devmajor
+                self.dev,
+                0, // rdevmajor
+                self.rdev,
+                self.namesize,
+                0, // check
+            ),
+        }
         .into_bytes()
     }
 }
 
-/// Create a CPIO archive in odc format.
+/// Zero bytes that pad `len` to a multiple of four in newc archives.
+fn newc_padding(format: CpioFormat, len: usize) -> &'static [u8] {
+    match format {
+        CpioFormat::Odc => &[],
+        CpioFormat::Newc => &[0; 3][..(4 - len % 4) % 4],
+    }
+}
+
+/// Check `name` is a path macOS can create: no NUL bytes, at most
+/// [`MAX_PATH_BYTES`] long, with no component over [`MAX_NAME_BYTES`].
+pub fn validate_entry_name(name: &[u8]) -> Result<(), PackageError> {
+    let display = String::from_utf8_lossy(name);
+    if name.contains(&0) {
+        return Err(cpio_err(format!("Path '{}' contains a NUL byte", display)));
+    }
+    if name.len() > MAX_PATH_BYTES {
+        return Err(cpio_err(format!(
+            "Path '{}...' is {} bytes long; macOS paths are limited to {} bytes",
+            String::from_utf8_lossy(&name[..60]),
+            name.len(),
+            MAX_PATH_BYTES
+        )));
+    }
+    if let Some(component) = name
+        .split(|b| *b == b'/')
+        .find(|component| component.len() > MAX_NAME_BYTES)
+    {
+        return Err(cpio_err(format!(
+            "'{}' in '{}' is {} bytes long; macOS file names are limited to {} bytes",
+            String::from_utf8_lossy(component),
+            display,
+            component.len(),
+            MAX_NAME_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// Builds a CPIO archive entry by entry.
 ///
-/// Each entry is (path, data, mode). Files will have uid=0, gid=80.
-pub fn create_cpio_archive(entries: &[CpioEntry]) -> Result<Vec<u8>, PackageError> {
-    let mut output = Vec::new();
-    let mut ino: u32 = 1;
+/// Entries get consecutive inode numbers, uid=0 and gid=80 (see
+/// [`with_gid`](Self::with_gid)), and the time the writer was created
+/// (see [`set_mtime`](Self::set_mtime)). Paths are taken as raw bytes, so
+/// names that are not valid UTF-8 are stored unchanged.
+#[derive(Debug)]
+pub struct CpioWriter {
+    output: Vec<u8>,
+    next_ino: u32,
+    mtime: u64,
+    gid: u32,
+    format: CpioFormat,
+}
 
-    for (path, data, mode) in entries {
-        // Write header
-        let header = CpioHeader::for_file(*mode, data.len() as u64, path.len(), ino);
-        output.extend_from_slice(&header.to_bytes());
+impl CpioWriter {
+    /// Create an empty odc archive.
+    pub fn new() -> Self {
+        Self::with_format(CpioFormat::Odc)
+    }
 
-        // Write filename with null terminator
-        output.extend_from_slice(path.as_bytes());
-        output.push(0);
+    /// Create an empty archive in `format`.
+    pub fn with_format(format: CpioFormat) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            output: Vec::new(),
+            next_ino: 1,
+            mtime: now,
+            gid: WHEEL_GID,
+            format,
+        }
+    }
 
-        // Write file data
-        output.extend_from_slice(data);
+    /// Set the group of the entries (e.g., [`ROOT_GID`]).
+    pub fn with_gid(mut self, gid: u32) -> Self {
+        self.gid = gid;
+        self
+    }
 
-        ino += 1;
+    /// Set the modification time of the entries added from now on
+    /// (seconds since the epoch).
+    pub fn set_mtime(&mut self, mtime: u64) -> &mut Self {
+        self.mtime = mtime;
+        self
     }
 
-    // Write trailer
-    let trailer = CpioHeader::trailer();
-    output.extend_from_slice(&trailer.to_bytes());
-    output.extend_from_slice(b"TRAILER!!!\0");
+    /// Header format of the archive.
+    pub fn format(&self) -> CpioFormat {
+        self.format
+    }
+
+    /// Add a file, directory or symbolic link (see [`CpioEntry`]).
+    pub fn add(
+        &mut self,
+        path: impl AsRef<[u8]>,
+        data: &[u8],
+        mode: u32,
+    ) -> Result<(), PackageError> {
+        self.add_linked(path, data, mode, 1).map(|_| ())
+    }
+
+    /// Add the first of `nlink` hard links to a file, returning the inode
+    /// number to pass to [`add_hard_link`](Self::add_hard_link).
+    pub fn add_linked(
+        &mut self,
+        path: impl AsRef<[u8]>,
+        data: &[u8],
+        mode: u32,
+        nlink: u32,
+    ) -> Result<u32, PackageError> {
+        let ino = self.next_ino;
+        self.write_entry(path.as_ref(), data, mode, ino, nlink)?;
+        self.next_ino += 1;
+        Ok(ino)
+    }
+
+    /// Add a further hard link to the file added as `ino`; the data is not
+    /// stored again.
+    pub fn add_hard_link(
+        &mut self,
+        path: impl AsRef<[u8]>,
+        mode: u32,
+        ino: u32,
+        nlink: u32,
+    ) -> Result<(), PackageError> {
+        self.write_entry(path.as_ref(), &[], mode, ino, nlink)
+    }
 
-    Ok(output)
+    /// End the archive with its trailer.
+    pub fn finish(mut self) -> Vec<u8> {
+        let trailer = CpioHeader::trailer();
+        self.output
+            .extend_from_slice(&trailer.to_bytes(self.format));
+        self.output.extend_from_slice(b"TRAILER!!!\0");
+        let len = self.output.len();
+        self.output
+            .extend_from_slice(newc_padding(self.format, len));
+        self.output
+    }
+
+    fn write_entry(
+        &mut self,
+        path: &[u8],
+        data: &[u8],
+        mode: u32,
+        ino: u32,
+        nlink: u32,
+    ) -> Result<(), PackageError> {
+        validate_entry_name(path)?;
+        let mut header =
+            CpioHeader::for_file(mode, data.len() as u64, path.len(), ino, nlink, self.mtime);
+        header.gid = self.gid;
+        if let Some((field, value)) = header.overflow(self.format) {
+            return Err(cpio_err(match field {
+                "file size" => format!(
+                    "'{}' is {} bytes; files in a {} payload must be smaller than {}",
+                    String::from_utf8_lossy(path),
+                    value,
+                    self.format.name(),
+                    ByteSize(self.format.max_file_size() + 1)
+                ),
+                _ => format!(
+                    "The {} of '{}' ({}) does not fit a {} CPIO header",
+                    field,
+                    String::from_utf8_lossy(path),
+                    value,
+                    self.format.name()
+                ),
+            }));
+        }
+        self.output.extend_from_slice(&header.to_bytes(self.format));
+
+        // Write filename with null terminator
+        self.output.extend_from_slice(path);
+        self.output.push(0);
+        let len = self.output.len();
+        self.output
+            .extend_from_slice(newc_padding(self.format, len));
+
+        self.output.extend_from_slice(data);
+        let len = self.output.len();
+        self.output
+            .extend_from_slice(newc_padding(self.format, len));
+        Ok(())
+    }
 }
 
-/// Create a CPIO archive with directory support.
-pub fn create_cpio_archive_with_dirs(
-    _entries: &[(String, u32, bool)],
-) -> Result<Vec<u8>, PackageError> {
-    // For now, just use the regular implementation
-    // Directories are typically not needed for basic payloads
-    Err(PackageError::CpioError {
-        reason: "Directory support not yet implemented".to_string(),
-    })
+impl Default for CpioWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Create a CPIO archive, in odc format unless there are too many entries
+/// (see [`CpioFormat::for_entries`]).
+///
+/// Each entry is (path, data, mode). Files will have uid=0, gid=80.
+pub fn create_cpio_archive(entries: &[CpioEntry]) -> Result<Vec<u8>, PackageError> {
+    let mut writer = CpioWriter::with_format(CpioFormat::for_entries(entries.len()));
+    for (path, data, mode) in entries {
+        writer.add(path, data, *mode)?;
+    }
+    Ok(writer.finish())
 }
 
 /// Create a gzip-compressed CPIO payload.
 ///
 /// This is the main function for creating macOS package payloads.
 pub fn create_payload(entries: &[CpioEntry]) -> Result<Vec<u8>, PackageError> {
-    // Create uncompressed CPIO archive
-    let cpio_data = create_cpio_archive(entries)?;
+    compress_payload(
+        &create_cpio_archive(entries)?,
+        Compression::default().level(),
+    )
+}
 
-    // Compress with gzip
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+/// Gzip-compress a CPIO archive into a payload at `level` (0-9).
+pub fn compress_payload(cpio_data: &[u8], level: u32) -> Result<Vec<u8>, PackageError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
     encoder
-        .write_all(&cpio_data)
+        .write_all(cpio_data)
         .map_err(|e| PackageError::CpioError {
             reason: e.to_string(),
         })?;
@@ -158,6 +464,169 @@ pub fn create_payload(entries: &[CpioEntry]) -> Result<Vec<u8>, PackageError> {
     })
 }
 
+/// An entry read from a CPIO archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpioRecord {
+    /// Path as stored (usually starting with `./`), lossily as UTF-8
+    pub path: String,
+    /// Path as stored, byte for byte
+    pub raw_path: Vec<u8>,
+    /// Type and permission bits
+    pub mode: u32,
+    /// Modification time (seconds since the epoch)
+    pub mtime: u64,
+    /// File contents, or the target of a symbolic link
+    pub data: Vec<u8>,
+    /// Path of an earlier entry this one is a hard link to
+    pub hard_link: Option<String>,
+}
+
+impl CpioRecord {
+    /// Whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    /// Whether the entry is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// Permission bits of the entry.
+    pub fn permissions(&self) -> u32 {
+        self.mode & 0o7777
+    }
+}
+
+/// Read the entries of an odc or newc CPIO archive, up to its trailer.
+pub fn read_cpio_archive(data: &[u8]) -> Result<Vec<CpioRecord>, PackageError> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    // First path seen for each (dev, ino) of a multiply linked file
+    let mut links: HashMap<(u64, u64), String> = HashMap::new();
+
+    loop {
+        let magic = data
+            .get(pos..pos + 6)
+            .ok_or_else(|| cpio_err(format!("Truncated header at offset {}", pos)))?;
+        let format = match magic {
+            m if m == ODC_MAGIC => CpioFormat::Odc,
+            m if m == NEWC_MAGIC => CpioFormat::Newc,
+            _ => {
+                return Err(cpio_err(format!(
+                    "Not an odc or newc CPIO header at offset {}",
+                    pos
+                )));
+            }
+        };
+        let header_len = match format {
+            CpioFormat::Odc => ODC_HEADER_LEN,
+            CpioFormat::Newc => NEWC_HEADER_LEN,
+        };
+        let header = data
+            .get(pos..pos + header_len)
+            .ok_or_else(|| cpio_err(format!("Truncated header at offset {}", pos)))?;
+
+        let (dev, ino, mode, nlink, mtime, name_size, file_size) = match format {
+            CpioFormat::Odc => (
+                octal_field(header, 6, 6)?,
+                octal_field(header, 12, 6)?,
+                octal_field(header, 18, 6)? as u32,
+                octal_field(header, 36, 6)?,
+                octal_field(header, 48, 11)?,
+                octal_field(header, 59, 6)? as usize,
+                octal_field(header, 65, 11)? as usize,
+            ),
+            CpioFormat::Newc => (
+                (hex_field(header, 62)? << 32) | hex_field(header, 70)?,
+                hex_field(header, 6)?,
+                hex_field(header, 14)? as u32,
+                hex_field(header, 38)?,
+                hex_field(header, 46)?,
+                hex_field(header, 94)? as usize,
+                hex_field(header, 54)? as usize,
+            ),
+        };
+        pos += header_len;
+
+        let raw_name = data
+            .get(pos..pos + name_size)
+            .ok_or_else(|| cpio_err("Truncated entry name"))?;
+        let raw_name = raw_name.strip_suffix(&[0]).unwrap_or(raw_name).to_vec();
+        let name = String::from_utf8_lossy(&raw_name).into_owned();
+        pos += name_size;
+        pos += newc_padding(format, pos).len();
+
+        if name == TRAILER_NAME {
+            return Ok(records);
+        }
+
+        let contents = data
+            .get(pos..pos + file_size)
+            .ok_or_else(|| cpio_err(format!("Truncated contents of '{}'", name)))?;
+        pos += file_size;
+        pos += newc_padding(format, pos).len();
+
+        let hard_link = if nlink > 1 && mode & S_IFMT == S_IFREG {
+            match links.get(&(dev, ino)) {
+                Some(first) => Some(first.clone()),
+                None => {
+                    links.insert((dev, ino), name.clone());
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        records.push(CpioRecord {
+            path: name,
+            raw_path: raw_name,
+            mode,
+            mtime,
+            data: contents.to_vec(),
+            hard_link,
+        });
+    }
+}
+
+/// Read a package payload: a CPIO archive, gzip-compressed or not.
+pub fn read_payload(data: &[u8]) -> Result<Vec<CpioRecord>, PackageError> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut cpio_data = Vec::new();
+        GzDecoder::new(data)
+            .read_to_end(&mut cpio_data)
+            .map_err(|e| cpio_err(format!("Cannot decompress payload: {}", e)))?;
+        read_cpio_archive(&cpio_data)
+    } else if data.starts_with(b"pbzx") {
+        Err(cpio_err("pbzx-compressed payloads are not supported"))
+    } else {
+        read_cpio_archive(data)
+    }
+}
+
+/// Parse an octal header field.
+fn octal_field(header: &[u8], start: usize, len: usize) -> Result<u64, PackageError> {
+    std::str::from_utf8(&header[start..start + len])
+        .ok()
+        .and_then(|text| u64::from_str_radix(text, 8).ok())
+        .ok_or_else(|| cpio_err("Invalid octal field in header"))
+}
+
+/// Parse an 8-digit hex newc header field.
+fn hex_field(header: &[u8], start: usize) -> Result<u64, PackageError> {
+    std::str::from_utf8(&header[start..start + 8])
+        .ok()
+        .and_then(|text| u64::from_str_radix(text, 16).ok())
+        .ok_or_else(|| cpio_err("Invalid hex field in header"))
+}
+
+fn cpio_err(reason: impl Into<String>) -> PackageError {
+    PackageError::CpioError {
+        reason: reason.into(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -299,4 +768,135 @@ mod tests {
             "Subdirectory path must be preserved"
         );
     }
+
+    #[test]
+    fn test_read_payload_round_trip() {
+        let payload = create_payload(&[
+            ("bin/tool".to_string(), b"#!/bin/sh".to_vec(), 0o755),
+            ("share/readme.txt".to_string(), Vec::new(), 0o644),
+        ])
+        .unwrap();
+        let records = read_payload(&payload).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].path, "bin/tool");
+        assert_eq!(records[0].data, b"#!/bin/sh");
+        assert_eq!(records[0].permissions(), 0o755);
+        assert!(!records[0].is_dir() && !records[0].is_symlink());
+        assert!(records[1].data.is_empty());
+    }
+
+    #[test]
+    fn test_read_cpio_uncompressed_and_truncated() {
+        let archive =
+            create_cpio_archive(&[("a.txt".to_string(), b"hello".to_vec(), 0o644)]).unwrap();
+        assert_eq!(read_payload(&archive).unwrap()[0].data, b"hello");
+
+        // Cut inside the trailer header
+        assert!(matches!(
+            read_cpio_archive(&archive[..archive.len() - 20]),
+            Err(PackageError::CpioError { .. })
+        ));
+        assert!(read_payload(b"pbzx....").is_err());
+    }
+
+    #[test]
+    fn test_writer_directories_and_hard_links() {
+        let mut writer = CpioWriter::new();
+        writer.add(".", &[], S_IFDIR | 0o755).unwrap();
+        writer.add("./bin", &[], S_IFDIR | 0o750).unwrap();
+        let ino = writer
+            .add_linked("./bin/tool", b"binary", 0o755, 2)
+            .unwrap();
+        writer
+            .add_hard_link("./bin/tool-alias", 0o755, ino, 2)
+            .unwrap();
+        let records = read_cpio_archive(&writer.finish()).unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert!(records[0].is_dir() && records[1].is_dir());
+        assert_eq!(records[1].permissions(), 0o750);
+        assert_eq!(records[2].data, b"binary");
+        assert_eq!(records[2].hard_link, None);
+        assert!(records[3].data.is_empty());
+        assert_eq!(records[3].hard_link.as_deref(), Some("./bin/tool"));
+    }
+
+    #[test]
+    fn test_newc_round_trip() {
+        assert_eq!(CpioFormat::for_entries(1000), CpioFormat::Odc);
+        assert_eq!(CpioFormat::for_entries(300_000), CpioFormat::Newc);
+
+        let mut writer = CpioWriter::with_format(CpioFormat::Newc);
+        writer.add(".", &[], S_IFDIR | 0o755).unwrap();
+        writer.add("./odd.txt", b"12345", 0o640).unwrap();
+        let ino = writer.add_linked("./a", b"ab", 0o644, 2).unwrap();
+        writer.add_hard_link("./b", 0o644, ino, 2).unwrap();
+        let archive = writer.finish();
+        assert_eq!(&archive[..6], b"070701");
+        assert_eq!(archive.len() % 4, 0);
+
+        let records = read_cpio_archive(&archive).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].path, "./odd.txt");
+        assert_eq!(records[1].data, b"12345");
+        assert_eq!(records[1].permissions(), 0o640);
+        assert_eq!(records[3].hard_link.as_deref(), Some("./a"));
+    }
+
+    #[test]
+    fn test_newc_owner_and_mtime() {
+        let mut writer = CpioWriter::with_format(CpioFormat::Newc).with_gid(ROOT_GID);
+        writer.set_mtime(1_700_000_000);
+        writer.add("./a", b"a", 0o644).unwrap();
+        writer.set_mtime(1_600_000_000);
+        writer.add("./b", b"b", 0o644).unwrap();
+        let archive = writer.finish();
+
+        // gid is the fourth newc field
+        assert_eq!(&archive[30..38], b"00000000");
+        let records = read_cpio_archive(&archive).unwrap();
+        assert_eq!(records[0].mtime, 1_700_000_000);
+        assert_eq!(records[1].mtime, 1_600_000_000);
+    }
+
+    #[test]
+    fn test_writer_rejects_invalid_names() {
+        let mut writer = CpioWriter::new();
+        let long_name = format!("./{}", "a".repeat(MAX_NAME_BYTES + 1));
+        let deep_path = format!("./{}", "dir/".repeat(300));
+        for name in [long_name.as_bytes(), deep_path.as_bytes(), b"./nul\0name"] {
+            assert!(matches!(
+                writer.add(name, b"", 0o644),
+                Err(PackageError::CpioError { .. })
+            ));
+        }
+
+        // Names need not be UTF-8
+        writer.add(b"./caf\xe9.txt", b"latin-1", 0o644).unwrap();
+        let records = read_cpio_archive(&writer.finish()).unwrap();
+        assert_eq!(records[0].raw_path, b"./caf\xe9.txt");
+    }
+
+    #[test]
+    fn test_header_field_overflow() {
+        let header = CpioHeader::for_file(0o644, 8 << 30, 10, 1, 1, 0);
+        assert_eq!(
+            header.overflow(CpioFormat::Odc),
+            Some(("file size", 8 << 30))
+        );
+        assert_eq!(
+            header.overflow(CpioFormat::Newc),
+            Some(("file size", 8 << 30))
+        );
+        let header = CpioHeader::for_file(0o644, 5 << 30, 10, 1, 1, 0);
+        assert_eq!(header.overflow(CpioFormat::Odc), None);
+
+        let header = CpioHeader::for_file(0o644, 0, 10, 0o1_000_000, 1, 0);
+        assert_eq!(
+            header.overflow(CpioFormat::Odc),
+            Some(("inode number", 0o1_000_000))
+        );
+        assert_eq!(header.overflow(CpioFormat::Newc), None);
+    }
 }
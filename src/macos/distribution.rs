@@ -0,0 +1,245 @@
+//! Product packages combining several components, as `productbuild
+//! --package` creates them.
+//!
+//! Each component is built exactly like the single component of
+//! [`package`](super::package) and stored in its own `<identifier>.pkg`
+//! directory, so it keeps its install location, scripts and filters. The
+//! Distribution lists one hidden choice per component under the default
+//! choice, installing them in request order. Components limited to one
+//! architecture (see [`MacosDistributionRequest::for_architectures`]) are
+//! only selected on Macs of that architecture.
+
+use std::path::Path;
+use std::time::Instant;
+
+use crate::models::artifact::ArtifactMetadata;
+use crate::models::error::PackageResult;
+use crate::models::macos::{
+    DistributionComponent, MacosDistributionRequest, MacosDistributionResult,
+};
+use crate::models::retry::RetryPolicy;
+use crate::packager::manifest::ManifestHasher;
+use crate::packager::metadata::generate_transforms_xml;
+use crate::packager::validate::validate_with_timeout;
+
+use super::xml::{DistributionRef, generate_product_distribution};
+use super::{TRANSFORMS_FILE, build_component, sign, validate, write_package, xar};
+
+/// Create a distribution package from the given request.
+pub fn package_distribution(
+    request: &MacosDistributionRequest,
+) -> PackageResult<MacosDistributionResult> {
+    let start = Instant::now();
+
+    request.validate()?;
+
+    // Load the signing identity before doing any work
+    let signer = request
+        .signing
+        .as_ref()
+        .map(sign::Signer::load)
+        .transpose()?;
+
+    let mut xar = xar::XarBuilder::new();
+    let mut manifest = ManifestHasher::default();
+    let mut refs = Vec::with_capacity(request.components.len());
+    let mut components = Vec::with_capacity(request.components.len());
+
+    for component_request in &request.components {
+        // Hash each payload under its identifier so identical files in two
+        // components still change the artifact digest
+        let component = build_component(
+            component_request,
+            &mut manifest,
+            Path::new(&component_request.identifier),
+        )?;
+
+        let dir = format!("{}.pkg", component_request.identifier);
+        component.add_to(&mut xar, &dir)?;
+        if !component.transforms.is_empty() {
+            xar.add_file(
+                &format!("{}/{}", dir, TRANSFORMS_FILE),
+                generate_transforms_xml(&component.transforms)?.into_bytes(),
+            )?;
+        }
+
+        refs.push(DistributionRef {
+            identifier: component_request.identifier.clone(),
+            version: component_request.version.clone(),
+            install_kbytes: component.summary.install_kbytes,
+            path: dir,
+            host_architecture: component_request.host_architecture,
+        });
+        components.push(DistributionComponent {
+            identifier: component_request.identifier.clone(),
+            version: component_request.version.clone(),
+            install_location: component_request.install_location.clone(),
+            host_architecture: component_request.host_architecture,
+            file_count: component.summary.files,
+            transforms: component.transforms,
+            blocklist_matches: component.blocklist_matches,
+        });
+    }
+
+    let artifact = ArtifactMetadata::new(manifest.finish());
+    xar.set_artifact(artifact.clone());
+    xar.add_file(
+        "Distribution",
+        generate_product_distribution(request.title(), &refs)?.into_bytes(),
+    )?;
+
+    let output_path = request.output_path();
+    let package_size = write_package(xar, signer.as_ref(), &output_path, &RetryPolicy::none())?;
+    let creation_time = start.elapsed();
+
+    let validation = match request.post_validate {
+        Some(timeout) => Some(validate_with_timeout(
+            &output_path,
+            timeout,
+            validate::validate_pkg,
+        )?),
+        None => None,
+    };
+
+    Ok(MacosDistributionResult {
+        output_path,
+        package_size,
+        components,
+        artifact,
+        signed_by: signer.map(|s| s.subject().to_string()),
+        validation,
+        creation_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::xar::XarArchive;
+    use crate::models::error::PackageError;
+    use crate::models::macos::{ArchPayload, HostArchitecture, MacosPkgRequest};
+    use crate::models::package::Verbosity;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn component(temp: &TempDir, identifier: &str, location: &str) -> MacosPkgRequest {
+        let source = temp.path().join(identifier);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("file.txt"), identifier).unwrap();
+        MacosPkgRequest::new(source, identifier, "1.0.0", temp.path())
+            .with_install_location(location)
+            .with_verbosity(Verbosity::Silent)
+    }
+
+    #[test]
+    fn test_package_distribution() {
+        let temp = TempDir::new().unwrap();
+        let request = MacosDistributionRequest::new("com.example.suite", "2.0", temp.path())
+            .with_title("Example Suite")
+            .with_component(component(&temp, "com.example.app", "/Applications"))
+            .with_component(component(&temp, "com.example.tools", "/usr/local/bin"))
+            .with_post_validate(Duration::from_secs(30));
+        let result = package_distribution(&request).unwrap();
+
+        assert_eq!(
+            result.output_path,
+            temp.path().join("com.example.suite-2.0.pkg")
+        );
+        assert_eq!(result.components.len(), 2);
+        assert_eq!(result.components[1].file_count, 1);
+        assert!(result.validation.is_some());
+
+        let archive = XarArchive::read(&fs::read(&result.output_path).unwrap()).unwrap();
+        let distribution =
+            String::from_utf8(archive.file("Distribution").unwrap().to_vec()).unwrap();
+        assert!(distribution.contains("<title>Example Suite</title>"));
+        assert!(distribution.contains("#com.example.app.pkg"));
+        assert!(distribution.contains("#com.example.tools.pkg"));
+
+        let packageinfo = String::from_utf8(
+            archive
+                .file("com.example.tools.pkg/PackageInfo")
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(packageinfo.contains(r#"install-location="/usr/local/bin""#));
+        assert!(archive.file("com.example.app.pkg/Payload").is_some());
+        assert!(archive.file("base.pkg/Payload").is_none());
+
+        let report = crate::macos::validate::validate_pkg_report(&result.output_path).unwrap();
+        assert!(report.passed(), "{:?}", report.first_failure());
+        assert_eq!(report.components.len(), 2);
+    }
+
+    #[test]
+    fn test_package_distribution_per_architecture() {
+        let temp = TempDir::new().unwrap();
+        let payload = |arch: HostArchitecture| {
+            let source = temp.path().join(arch.name());
+            fs::create_dir_all(&source).unwrap();
+            fs::write(source.join("tool"), arch.name()).unwrap();
+            ArchPayload {
+                arch,
+                source_folder: source,
+            }
+        };
+        let base = MacosPkgRequest::new("", "com.example.tool", "1.0", temp.path())
+            .with_install_location("/usr/local/bin")
+            .with_verbosity(Verbosity::Silent);
+        let request = MacosDistributionRequest::for_architectures(
+            &base,
+            &[
+                payload(HostArchitecture::Arm64),
+                payload(HostArchitecture::X86_64),
+            ],
+        );
+        let result = package_distribution(&request).unwrap();
+
+        assert_eq!(
+            result.output_path,
+            temp.path().join("com.example.tool-1.0.pkg")
+        );
+        assert_eq!(
+            result.components[1].host_architecture,
+            Some(HostArchitecture::X86_64)
+        );
+
+        let archive = XarArchive::read(&fs::read(&result.output_path).unwrap()).unwrap();
+        let distribution =
+            String::from_utf8(archive.file("Distribution").unwrap().to_vec()).unwrap();
+        assert!(distribution.contains("hostArchitectures=\"arm64,x86_64\""));
+        assert!(distribution.contains("#com.example.tool.arm64.pkg"));
+        assert!(distribution.contains("hw.optional.arm64"));
+        let packageinfo = String::from_utf8(
+            archive
+                .file("com.example.tool.x86_64.pkg/PackageInfo")
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(packageinfo.contains(r#"identifier="com.example.tool.x86_64""#));
+        assert!(archive.file("com.example.tool.arm64.pkg/Payload").is_some());
+    }
+
+    #[test]
+    fn test_distribution_request_checks() {
+        let temp = TempDir::new().unwrap();
+        let empty = MacosDistributionRequest::new("com.example.suite", "1.0", temp.path());
+        assert!(matches!(
+            package_distribution(&empty),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        let duplicate = empty
+            .clone()
+            .with_component(component(&temp, "com.example.app", "/Applications"))
+            .with_component(component(&temp, "com.example.app", "/Applications"));
+        assert!(matches!(
+            package_distribution(&duplicate),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+        assert!(!duplicate.output_path().exists());
+    }
+}
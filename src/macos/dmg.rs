@@ -0,0 +1,522 @@
+//! Disk images (.dmg) built from a folder.
+//!
+//! The volume is an ISO 9660 file system (see [`super::iso`]) stored as a
+//! UDIF image, the format `hdiutil` writes: the volume split into chunks
+//! (zlib-compressed for UDZO), a `blkx` table locating every chunk, and a
+//! `koly` trailer pointing at both. A license adds the `LPic`, `STR#` and
+//! `TEXT` resources the Finder shows before it mounts the image.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::macos::{DmgFormat, MacosDmgRequest, MacosDmgResult};
+use crate::models::size::ByteSize;
+use crate::packager::blocklist::sha256_file;
+
+use super::iso::IsoImage;
+
+/// UDIF sectors are 512 bytes, whatever the file system inside.
+const UDIF_SECTOR_SIZE: u64 = 512;
+
+/// Sectors per chunk (1 MiB, as `hdiutil` uses for UDZO).
+const CHUNK_SECTORS: u64 = 2048;
+
+const CHUNK_SIZE: usize = (UDIF_SECTOR_SIZE * CHUNK_SECTORS) as usize;
+
+/// `blkx` chunk types.
+const CHUNK_ZERO_FILL: u32 = 0x0000_0000;
+const CHUNK_RAW: u32 = 0x0000_0001;
+const CHUNK_ZLIB: u32 = 0x8000_0005;
+const CHUNK_TERMINATOR: u32 = 0xFFFF_FFFF;
+
+/// Checksum type for CRC-32 in UDIF checksum fields.
+const CHECKSUM_CRC32: u32 = 2;
+
+/// Resource ID of the license resources (the first language).
+const LICENSE_RESOURCE_ID: u16 = 5000;
+
+/// Button labels and prompt of the license dialog, in `STR#` order.
+const LICENSE_STRINGS: [&str; 6] = [
+    "English",
+    "Agree",
+    "Disagree",
+    "Print",
+    "Save...",
+    "If you agree with the terms of this license, click \"Agree\" to access the software. \
+     If you do not agree, press \"Disagree.\"",
+];
+
+/// Build the disk image described by `request`.
+pub fn create_dmg(request: &MacosDmgRequest) -> PackageResult<MacosDmgResult> {
+    let start = Instant::now();
+    request.validate()?;
+
+    let volume_name = request.volume_name();
+    let license = match &request.license {
+        Some(path) => {
+            Some(fs::read_to_string(path).map_err(|e| PackageError::source_read(path.clone(), e))?)
+        }
+        None => None,
+    };
+    let image = IsoImage::from_folder(&request.source_folder, &volume_name)?;
+
+    let output = &request.output;
+    if let Some(parent) = output.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+        }
+    }
+
+    let written = write_image(&image, request.format, license.as_deref(), output);
+    if let Err(e) = written {
+        let _ = fs::remove_file(output);
+        return Err(e);
+    }
+
+    let write_error = |e: io::Error| PackageError::output_write(output.clone(), e);
+    let image_size = fs::metadata(output).map_err(write_error)?.len();
+    let sha256 = sha256_file(output).map_err(write_error)?;
+
+    Ok(MacosDmgResult {
+        output_path: output.clone(),
+        image_size: ByteSize(image_size),
+        volume_size: ByteSize(image.size()),
+        volume_name,
+        format: request.format,
+        file_count: image.file_count(),
+        sha256,
+        creation_time: start.elapsed(),
+    })
+}
+
+fn write_image(
+    image: &IsoImage,
+    format: DmgFormat,
+    license: Option<&str>,
+    output: &Path,
+) -> PackageResult<()> {
+    let write_error = |e: io::Error| PackageError::output_write(output.to_path_buf(), e);
+
+    let file = File::create(output).map_err(write_error)?;
+    let mut udif = UdifWriter::new(BufWriter::new(file), format);
+    image.write_to(&mut udif, output)?;
+    udif.finish(license)
+        .and_then(|mut out| out.flush())
+        .map_err(write_error)
+}
+
+/// One entry of the `blkx` table.
+struct Chunk {
+    kind: u32,
+    sector: u64,
+    sectors: u64,
+    offset: u64,
+    length: u64,
+}
+
+/// Writes the data fork of a UDIF image chunk by chunk.
+struct UdifWriter<W: Write> {
+    out: W,
+    format: DmgFormat,
+    buffer: Vec<u8>,
+    chunks: Vec<Chunk>,
+    /// Bytes of the data fork written so far
+    written: u64,
+    /// Image sectors consumed so far
+    sectors: u64,
+    /// CRC-32 of the data fork as stored
+    data_crc: Crc,
+    /// CRC-32 of the image data before compression
+    image_crc: Crc,
+}
+
+impl<W: Write> UdifWriter<W> {
+    fn new(out: W, format: DmgFormat) -> Self {
+        Self {
+            out,
+            format,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            chunks: Vec::new(),
+            written: 0,
+            sectors: 0,
+            data_crc: Crc::new(),
+            image_crc: Crc::new(),
+        }
+    }
+
+    /// Store the buffered data as one chunk.
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let tail = self.buffer.len() % UDIF_SECTOR_SIZE as usize;
+        if tail != 0 {
+            let padded = self.buffer.len() + UDIF_SECTOR_SIZE as usize - tail;
+            self.buffer.resize(padded, 0);
+        }
+        self.image_crc.update(&self.buffer);
+
+        let compressed;
+        let (kind, data): (u32, &[u8]) = if self.buffer.iter().all(|&b| b == 0) {
+            (CHUNK_ZERO_FILL, &[])
+        } else if self.format == DmgFormat::Udzo {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&self.buffer)?;
+            compressed = encoder.finish()?;
+            // Incompressible chunks are stored, as hdiutil does
+            if compressed.len() < self.buffer.len() {
+                (CHUNK_ZLIB, &compressed)
+            } else {
+                (CHUNK_RAW, &self.buffer)
+            }
+        } else {
+            (CHUNK_RAW, &self.buffer)
+        };
+
+        self.out.write_all(data)?;
+        self.data_crc.update(data);
+        let sectors = self.buffer.len() as u64 / UDIF_SECTOR_SIZE;
+        self.chunks.push(Chunk {
+            kind,
+            sector: self.sectors,
+            sectors,
+            offset: self.written,
+            length: data.len() as u64,
+        });
+        self.written += data.len() as u64;
+        self.sectors += sectors;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Write the resources and the trailer, returning the inner writer.
+    fn finish(mut self, license: Option<&str>) -> io::Result<W> {
+        self.flush_chunk()?;
+        self.chunks.push(Chunk {
+            kind: CHUNK_TERMINATOR,
+            sector: self.sectors,
+            sectors: 0,
+            offset: self.written,
+            length: 0,
+        });
+
+        let image_crc = self.image_crc.sum();
+        let plist = resource_plist(&self.blkx_table(image_crc), license);
+        self.out.write_all(plist.as_bytes())?;
+
+        // The master checksum covers the checksums of the blkx tables
+        let mut master = Crc::new();
+        master.update(&image_crc.to_be_bytes());
+
+        let mut koly = Vec::with_capacity(512);
+        koly.extend_from_slice(b"koly");
+        put_u32(&mut koly, 4); // version
+        put_u32(&mut koly, 512); // header size
+        put_u32(&mut koly, 1); // flags: flattened
+        put_u64(&mut koly, 0); // running data fork offset
+        put_u64(&mut koly, 0); // data fork offset
+        put_u64(&mut koly, self.written);
+        put_u64(&mut koly, 0); // resource fork offset
+        put_u64(&mut koly, 0); // resource fork length
+        put_u32(&mut koly, 1); // segment number
+        put_u32(&mut koly, 1); // segment count
+        koly.extend_from_slice(&rand::random::<[u8; 16]>());
+        put_checksum(&mut koly, self.data_crc.sum());
+        put_u64(&mut koly, self.written); // plist offset
+        put_u64(&mut koly, plist.len() as u64);
+        koly.resize(koly.len() + 120, 0);
+        put_checksum(&mut koly, master.sum());
+        put_u32(&mut koly, 1); // image variant: device image
+        put_u64(&mut koly, self.sectors);
+        koly.resize(512, 0);
+        self.out.write_all(&koly)?;
+
+        Ok(self.out)
+    }
+
+    /// The `mish` table describing every chunk.
+    fn blkx_table(&self, image_crc: u32) -> Vec<u8> {
+        let mut table = Vec::with_capacity(204 + 40 * self.chunks.len());
+        table.extend_from_slice(b"mish");
+        put_u32(&mut table, 1); // version
+        put_u64(&mut table, 0); // first sector
+        put_u64(&mut table, self.sectors);
+        put_u64(&mut table, 0); // data offset
+        put_u32(&mut table, (CHUNK_SECTORS + 8) as u32); // buffers needed
+        put_u32(&mut table, 0); // block descriptors
+        table.resize(table.len() + 24, 0);
+        put_checksum(&mut table, image_crc);
+        put_u32(&mut table, self.chunks.len() as u32);
+        for chunk in &self.chunks {
+            put_u32(&mut table, chunk.kind);
+            put_u32(&mut table, 0); // comment
+            put_u64(&mut table, chunk.sector);
+            put_u64(&mut table, chunk.sectors);
+            put_u64(&mut table, chunk.offset);
+            put_u64(&mut table, chunk.length);
+        }
+        table
+    }
+}
+
+impl<W: Write> Write for UdifWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = (CHUNK_SIZE - self.buffer.len()).min(buf.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.flush_chunk()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// The XML property list holding the image's resources.
+fn resource_plist(blkx: &[u8], license: Option<&str>) -> String {
+    let name = "whole disk (ISO 9660 : 0)";
+    let mut resources = String::new();
+
+    if let Some(text) = license {
+        let id = LICENSE_RESOURCE_ID.to_string();
+        // One language (English, region 0), at the first resource ID
+        let lpic = [0u8, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        resources.push_str(&resource("LPic", &[("", &id, &lpic)]));
+        resources.push_str(&resource("STR#", &[("English", &id, &license_strings())]));
+        resources.push_str(&resource("TEXT", &[("English", &id, &mac_text(text))]));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \t<key>resource-fork</key>\n\
+         \t<dict>\n{}\
+         \t\t<key>blkx</key>\n\
+         \t\t<array>\n\
+         \t\t\t<dict>\n\
+         \t\t\t\t<key>Attributes</key>\n\
+         \t\t\t\t<string>0x0050</string>\n\
+         \t\t\t\t<key>CFName</key>\n\
+         \t\t\t\t<string>{name}</string>\n\
+         \t\t\t\t<key>Data</key>\n\
+         \t\t\t\t<data>{}</data>\n\
+         \t\t\t\t<key>ID</key>\n\
+         \t\t\t\t<string>-1</string>\n\
+         \t\t\t\t<key>Name</key>\n\
+         \t\t\t\t<string>{name}</string>\n\
+         \t\t\t</dict>\n\
+         \t\t</array>\n\
+         \t</dict>\n\
+         </dict>\n\
+         </plist>\n",
+        resources,
+        BASE64.encode(blkx),
+    )
+}
+
+/// A resource type with its `(name, id, data)` entries.
+fn resource(kind: &str, entries: &[(&str, &str, &[u8])]) -> String {
+    let mut xml = format!("\t\t<key>{}</key>\n\t\t<array>\n", kind);
+    for (name, id, data) in entries {
+        xml.push_str(&format!(
+            "\t\t\t<dict>\n\
+             \t\t\t\t<key>Attributes</key>\n\
+             \t\t\t\t<string>0x0000</string>\n\
+             \t\t\t\t<key>Data</key>\n\
+             \t\t\t\t<data>{}</data>\n\
+             \t\t\t\t<key>ID</key>\n\
+             \t\t\t\t<string>{}</string>\n\
+             \t\t\t\t<key>Name</key>\n\
+             \t\t\t\t<string>{}</string>\n\
+             \t\t\t</dict>\n",
+            BASE64.encode(data),
+            id,
+            name
+        ));
+    }
+    xml.push_str("\t\t</array>\n");
+    xml
+}
+
+/// The `STR#` resource: a count, then Pascal strings.
+fn license_strings() -> Vec<u8> {
+    let mut data = (LICENSE_STRINGS.len() as u16).to_be_bytes().to_vec();
+    for string in LICENSE_STRINGS {
+        data.push(string.len() as u8);
+        data.extend_from_slice(string.as_bytes());
+    }
+    data
+}
+
+/// License text as the classic Mac OS `TEXT` resource expects it:
+/// carriage returns, and `?` for anything outside ASCII.
+fn mac_text(text: &str) -> Vec<u8> {
+    text.replace("\r\n", "\n")
+        .chars()
+        .map(|c| match c {
+            '\n' => b'\r',
+            c if c.is_ascii() => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+/// A UDIF checksum field holding a CRC-32.
+fn put_checksum(buffer: &mut Vec<u8>, crc: u32) {
+    put_u32(buffer, CHECKSUM_CRC32);
+    put_u32(buffer, 32);
+    put_u32(buffer, crc);
+    buffer.resize(buffer.len() + 31 * 4, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn be_u32(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    fn be_u64(data: &[u8], at: usize) -> u64 {
+        u64::from_be_bytes(data[at..at + 8].try_into().unwrap())
+    }
+
+    /// Expand a UDIF image back into the volume it holds, and return the
+    /// volume with the resource plist.
+    fn expand(image: &[u8]) -> (Vec<u8>, String) {
+        let koly = &image[image.len() - 512..];
+        assert_eq!(&koly[..4], b"koly");
+        let xml_offset = be_u64(koly, 216) as usize;
+        let xml_length = be_u64(koly, 224) as usize;
+        let sectors = be_u64(koly, 492);
+        let plist = String::from_utf8(image[xml_offset..xml_offset + xml_length].to_vec()).unwrap();
+
+        let blkx_start = plist.find("<key>blkx</key>").unwrap();
+        let data_start = plist[blkx_start..].find("<data>").unwrap() + blkx_start + 6;
+        let data_end = plist[data_start..].find("</data>").unwrap() + data_start;
+        let mish = BASE64.decode(&plist[data_start..data_end]).unwrap();
+        assert_eq!(&mish[..4], b"mish");
+        assert_eq!(be_u64(&mish, 16), sectors);
+
+        let mut volume = Vec::new();
+        let count = be_u32(&mish, 200) as usize;
+        for i in 0..count {
+            let chunk = &mish[204 + 40 * i..244 + 40 * i];
+            let sectors = be_u64(chunk, 16) as usize;
+            let offset = be_u64(chunk, 24) as usize;
+            let length = be_u64(chunk, 32) as usize;
+            let data = &image[offset..offset + length];
+            match be_u32(chunk, 0) {
+                CHUNK_ZERO_FILL => volume.resize(volume.len() + sectors * 512, 0),
+                CHUNK_RAW => volume.extend_from_slice(data),
+                CHUNK_ZLIB => {
+                    ZlibDecoder::new(data).read_to_end(&mut volume).unwrap();
+                }
+                CHUNK_TERMINATOR => break,
+                other => panic!("unexpected chunk type {:x}", other),
+            }
+        }
+        assert_eq!(volume.len() as u64, sectors * 512);
+        (volume, plist)
+    }
+
+    fn source(temp: &TempDir) -> std::path::PathBuf {
+        let source = temp.path().join("Tool");
+        fs::create_dir_all(source.join("bin")).unwrap();
+        fs::write(source.join("bin/tool"), vec![7u8; 3 * CHUNK_SIZE / 2]).unwrap();
+        fs::write(source.join("README"), "hello").unwrap();
+        source
+    }
+
+    #[test]
+    fn test_create_dmg_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let source = source(&temp);
+        let output = temp.path().join("out/tool.dmg");
+
+        let result = create_dmg(&MacosDmgRequest::new(&source, &output)).unwrap();
+        assert_eq!(result.volume_name, "Tool");
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.sha256.len(), 64);
+
+        let image = fs::read(&output).unwrap();
+        assert_eq!(result.image_size.0, image.len() as u64);
+        assert!(result.image_size < result.volume_size);
+        let (volume, plist) = expand(&image);
+        assert_eq!(volume.len() as u64, result.volume_size.0);
+        assert_eq!(&volume[16 * 2048 + 1..16 * 2048 + 6], b"CD001");
+        assert_eq!(&volume[16 * 2048 + 40..16 * 2048 + 44], b"Tool");
+        assert!(!plist.contains("LPic"));
+
+        // The data fork checksum matches what was stored
+        let koly = &image[image.len() - 512..];
+        let mut crc = Crc::new();
+        crc.update(&image[..be_u64(koly, 32) as usize]);
+        assert_eq!(be_u32(koly, 80), CHECKSUM_CRC32);
+        assert_eq!(be_u32(koly, 88), crc.sum());
+    }
+
+    #[test]
+    fn test_create_dmg_uncompressed_with_license() {
+        let temp = TempDir::new().unwrap();
+        let source = source(&temp);
+        let license = temp.path().join("LICENSE.txt");
+        fs::write(&license, "Terms\nof use \u{2014} v1\n").unwrap();
+        let output = temp.path().join("tool.dmg");
+
+        let request = MacosDmgRequest::new(&source, &output)
+            .with_volume_name("Tool Installer")
+            .with_format(DmgFormat::Udro)
+            .with_license(&license);
+        let result = create_dmg(&request).unwrap();
+        assert!(result.image_size > result.volume_size);
+
+        let (volume, plist) = expand(&fs::read(&output).unwrap());
+        assert_eq!(volume.len() as u64, result.volume_size.0);
+        for kind in ["LPic", "STR#", "TEXT"] {
+            assert!(plist.contains(&format!("<key>{}</key>", kind)), "{}", kind);
+        }
+        assert!(plist.contains(&BASE64.encode(b"Terms\rof use ? v1\r")));
+    }
+
+    #[test]
+    fn test_create_dmg_invalid_volume_name() {
+        let temp = TempDir::new().unwrap();
+        let source = source(&temp);
+        let output = temp.path().join("tool.dmg");
+
+        for name in ["", "A volume name far longer than 32 chars", "Caf\u{e9}"] {
+            let request = MacosDmgRequest::new(&source, &output).with_volume_name(name);
+            assert!(matches!(
+                create_dmg(&request),
+                Err(PackageError::InvalidArgument { .. })
+            ));
+        }
+        assert!(!output.exists());
+    }
+}
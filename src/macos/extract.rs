@@ -0,0 +1,301 @@
+//! Extract a `.pkg` into a folder, like `pkgutil --expand-full`.
+//!
+//! Every XAR entry is written under the output folder at its archive path.
+//! `Payload` and `Scripts` archives are expanded into folders of the same
+//! name instead, so `base.pkg/Payload/` holds the installed file tree and
+//! `base.pkg/Scripts/` the install scripts. Metadata (`Distribution`,
+//! `PackageInfo`, `Bom`, resources) is written as stored.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::macos::{MacosExtractRequest, MacosExtractResult};
+use crate::models::size::ByteSize;
+use crate::packager::sanitize::{contained_path, sanitize_entry_name};
+
+use super::cpio::{CpioRecord, read_payload};
+use super::xar::{EntryType, XarArchive};
+
+/// XAR entries holding a CPIO archive to expand.
+const ARCHIVE_ENTRIES: &[&str] = &["Payload", "Scripts"];
+
+/// Running totals of an extraction.
+#[derive(Debug, Default)]
+struct Totals {
+    file_count: usize,
+    dir_count: usize,
+    total_size: u64,
+}
+
+/// Extract the package in `request`.
+pub fn extract(request: &MacosExtractRequest) -> PackageResult<MacosExtractResult> {
+    let start = Instant::now();
+    request.validate()?;
+
+    let input = &request.input_file;
+    let data = fs::read(input).map_err(|e| PackageError::source_read(input.clone(), e))?;
+    let archive = XarArchive::read(&data)?;
+
+    let output_folder = &request.output_folder;
+    create_dir(output_folder)?;
+
+    let mut totals = Totals::default();
+    let mut archives = Vec::new();
+    for entry in archive.entries() {
+        let relative = sanitize_entry_name(&entry.path)?;
+        let path = contained_path(output_folder, &entry.path)?;
+        match entry.entry_type {
+            EntryType::Directory => {
+                create_dir(&path)?;
+                totals.dir_count += 1;
+            }
+            EntryType::File if ARCHIVE_ENTRIES.contains(&entry.name.as_str()) => {
+                let records = read_payload(&entry.data)?;
+                create_dir(&path)?;
+                totals.dir_count += 1;
+                expand_records(&records, &path, &mut totals)?;
+                archives.push(relative);
+            }
+            EntryType::File => {
+                write_file(&path, &entry.data, None)?;
+                totals.file_count += 1;
+                totals.total_size += entry.data.len() as u64;
+            }
+        }
+    }
+
+    Ok(MacosExtractResult {
+        output_folder: output_folder.clone(),
+        file_count: totals.file_count,
+        dir_count: totals.dir_count,
+        total_size: ByteSize(totals.total_size),
+        archives,
+        extract_time: start.elapsed(),
+    })
+}
+
+/// Write the entries of a CPIO archive under `root`.
+///
+/// Symbolic links are created last, so no entry can be written through a
+/// link planted earlier in the same archive; each link's own location is
+/// checked again as it is created. Hard links are recreated as such.
+fn expand_records(records: &[CpioRecord], root: &Path, totals: &mut Totals) -> PackageResult<()> {
+    let mut links = Vec::new();
+    for record in records {
+        if sanitize_entry_name(&record.path)?.as_os_str().is_empty() {
+            continue;
+        }
+
+        if record.is_symlink() {
+            links.push(record);
+            continue;
+        }
+        let path = contained_path(root, &record.path)?;
+        if let Some(target) = &record.hard_link {
+            // The first link was written earlier in the archive
+            let target = contained_path(root, target)?;
+            fs::hard_link(&target, &path)
+                .map_err(|e| PackageError::output_write(path.clone(), e))?;
+            totals.file_count += 1;
+        } else if record.is_dir() {
+            create_dir(&path)?;
+            totals.dir_count += 1;
+        } else {
+            write_file(&path, &record.data, Some(record.permissions()))?;
+            totals.file_count += 1;
+            totals.total_size += record.data.len() as u64;
+        }
+    }
+
+    for record in links {
+        let path = contained_path(root, &record.path)?;
+        create_symlink(&path, &record.data)?;
+        totals.file_count += 1;
+    }
+    Ok(())
+}
+
+fn create_dir(path: &Path) -> PackageResult<()> {
+    fs::create_dir_all(path).map_err(|e| PackageError::output_folder(path.to_path_buf(), e))
+}
+
+/// Write a file, creating its parent folders and applying `mode` (Unix).
+fn write_file(path: &Path, data: &[u8], mode: Option<u32>) -> PackageResult<()> {
+    if let Some(parent) = path.parent() {
+        create_dir(parent)?;
+    }
+    let write_err = |e: std::io::Error| PackageError::output_write(path.to_path_buf(), e);
+    fs::write(path, data).map_err(write_err)?;
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        // Special bits are never restored on the build machine
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777)).map_err(write_err)?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+/// Create a symbolic link; other platforms get a file holding the target.
+fn create_symlink(path: &Path, target: &[u8]) -> PackageResult<()> {
+    if let Some(parent) = path.parent() {
+        create_dir(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)
+            .map_err(|e| PackageError::output_write(path.to_path_buf(), e))
+    }
+    #[cfg(not(unix))]
+    {
+        write_file(path, target, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::cpio::create_payload;
+    use crate::macos::package;
+    use crate::macos::xar::XarBuilder;
+    use crate::models::macos::MacosPkgRequest;
+    use crate::models::package::Verbosity;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_created_package() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("bin")).unwrap();
+        fs::write(source.join("bin/tool"), "#!/bin/sh").unwrap();
+        fs::write(source.join("readme.txt"), "hello").unwrap();
+        let scripts = temp.path().join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        fs::write(scripts.join("postinstall"), "#!/bin/sh\nexit 0").unwrap();
+
+        let request = MacosPkgRequest::new(source, "com.example.tool", "1.0.0", temp.path())
+            .with_scripts_folder(&scripts)
+            .with_verbosity(Verbosity::Silent);
+        let pkg = package(request).unwrap().output_path;
+
+        let output = temp.path().join("expanded");
+        let result = extract(&MacosExtractRequest::new(&pkg, &output)).unwrap();
+
+        assert_eq!(
+            result.archives,
+            vec![
+                PathBuf::from("base.pkg/Payload"),
+                PathBuf::from("base.pkg/Scripts")
+            ]
+        );
+        assert_eq!(
+            fs::read(output.join("base.pkg/Payload/bin/tool")).unwrap(),
+            b"#!/bin/sh"
+        );
+        assert!(output.join("base.pkg/Payload/readme.txt").is_file());
+        assert!(output.join("base.pkg/PackageInfo").is_file());
+        assert!(output.join("Distribution").is_file());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(output.join("base.pkg/Scripts/postinstall"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_recreates_hard_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::write(source.join("lib/libacme.dylib"), "library").unwrap();
+        fs::hard_link(
+            source.join("lib/libacme.dylib"),
+            source.join("lib/libacme.1.dylib"),
+        )
+        .unwrap();
+
+        let request = MacosPkgRequest::new(source, "com.example.lib", "1.0.0", temp.path())
+            .with_verbosity(Verbosity::Silent);
+        let pkg = package(request).unwrap().output_path;
+        let output = temp.path().join("expanded");
+        extract(&MacosExtractRequest::new(&pkg, &output)).unwrap();
+
+        let first = fs::metadata(output.join("base.pkg/Payload/lib/libacme.dylib")).unwrap();
+        let second = fs::metadata(output.join("base.pkg/Payload/lib/libacme.1.dylib")).unwrap();
+        assert_eq!(first.ino(), second.ino());
+        assert_eq!(first.len(), 7);
+    }
+
+    #[test]
+    fn test_extract_rejects_escaping_paths() {
+        let temp = TempDir::new().unwrap();
+        let payload =
+            create_payload(&[("../escape.txt".to_string(), b"x".to_vec(), 0o644)]).unwrap();
+        let mut builder = XarBuilder::new();
+        builder
+            .add_file("PackageInfo", b"<pkg-info/>".to_vec())
+            .unwrap();
+        builder.add_file("Payload", payload).unwrap();
+        let mut data = Vec::new();
+        builder.finish(&mut data).unwrap();
+        let pkg = temp.path().join("evil.pkg");
+        fs::write(&pkg, data).unwrap();
+
+        let output = temp.path().join("out");
+        assert!(matches!(
+            extract(&MacosExtractRequest::new(&pkg, &output)),
+            Err(PackageError::UnsafePath { .. })
+        ));
+        assert!(!temp.path().join("escape.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_rejects_writes_through_links() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("readme.txt"), "hello").unwrap();
+        let request = MacosPkgRequest::new(source, "com.example.tool", "1.0.0", temp.path())
+            .with_verbosity(Verbosity::Silent);
+        let pkg = package(request).unwrap().output_path;
+
+        // A link left in the output folder must not redirect the extraction
+        let outside = temp.path().join("outside");
+        let output = temp.path().join("out");
+        fs::create_dir(&outside).unwrap();
+        fs::create_dir(&output).unwrap();
+        std::os::unix::fs::symlink(&outside, output.join("base.pkg")).unwrap();
+
+        assert!(matches!(
+            extract(&MacosExtractRequest::new(&pkg, &output)),
+            Err(PackageError::UnsafePath { .. })
+        ));
+        assert_eq!(fs::read_dir(&outside).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_extract_missing_input() {
+        let temp = TempDir::new().unwrap();
+        let request = MacosExtractRequest::new(temp.path().join("none.pkg"), temp.path());
+        assert!(matches!(
+            extract(&request),
+            Err(PackageError::SourceReadError { .. })
+        ));
+    }
+}
@@ -0,0 +1,759 @@
+//! ISO 9660 file systems with Rock Ridge extensions.
+//!
+//! Disk images carry their volume as an ISO 9660 file system, which macOS
+//! mounts natively. Rock Ridge entries keep the real file names, Unix modes
+//! and symbolic links, so application bundles keep their executables and
+//! framework links. Only the directory structure is held in memory: file
+//! contents are streamed from the source when the image is written.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::time::civil_time;
+
+/// Size of an ISO 9660 logical block.
+pub const SECTOR_SIZE: u64 = 2048;
+
+/// Blocks reserved ahead of the volume descriptors.
+const SYSTEM_AREA_SECTORS: u32 = 16;
+
+/// A directory record's length is a single byte.
+const MAX_RECORD_LEN: usize = 255;
+
+/// Longest base name and extension of a generated ISO 9660 identifier.
+const MAX_BASE_LEN: usize = 24;
+const MAX_EXTENSION_LEN: usize = 5;
+
+/// Rock Ridge extension announced in the root directory.
+const RRIP_ID: &[u8] = b"RRIP_1991A";
+const RRIP_DESCRIPTOR: &[u8] = b"POSIX FILE SYSTEM SEMANTICS";
+const RRIP_SOURCE: &[u8] = b"RRIP 1.09";
+
+enum NodeKind {
+    Directory { children: Vec<usize> },
+    File { source: PathBuf, size: u64 },
+    Symlink { target: String },
+}
+
+struct Node {
+    /// Name on the mounted volume (Rock Ridge `NM`)
+    name: String,
+    /// ISO 9660 identifier, unique within the parent directory
+    identifier: Vec<u8>,
+    parent: usize,
+    kind: NodeKind,
+    mode: u32,
+    mtime: u64,
+    /// First block of the node's data (files and directories)
+    extent: u32,
+    /// Length of the node's data in bytes
+    length: u32,
+}
+
+/// The layout of an ISO 9660 volume built from a folder.
+pub struct IsoImage {
+    volume_name: String,
+    nodes: Vec<Node>,
+    /// Directories in path table order (breadth first), root first
+    directories: Vec<usize>,
+    path_table_size: u32,
+    path_table_sectors: u32,
+    total_sectors: u32,
+    created: u64,
+}
+
+impl IsoImage {
+    /// Lay out a volume named `volume_name` holding the contents of `source`.
+    pub fn from_folder(source: &Path, volume_name: &str) -> PackageResult<Self> {
+        let read_error = |path: &Path, reason: String| PackageError::SourceReadError {
+            path: path.to_path_buf(),
+            reason,
+            source: None,
+        };
+
+        let root = fs::metadata(source).map_err(|e| read_error(source, e.to_string()))?;
+        let mut nodes = vec![Node {
+            name: String::new(),
+            identifier: vec![0],
+            parent: 0,
+            kind: NodeKind::Directory {
+                children: Vec::new(),
+            },
+            mode: 0o040000 | permissions(&root, 0o755),
+            mtime: mtime(&root),
+            extent: 0,
+            length: 0,
+        }];
+        let mut directories_by_path = HashMap::from([(source.to_path_buf(), 0)]);
+
+        let walker = WalkDir::new(source)
+            .min_depth(1)
+            .follow_links(false)
+            .sort_by_file_name();
+        for entry in walker {
+            let entry = entry.map_err(|e| {
+                let path = e.path().unwrap_or(source).to_path_buf();
+                read_error(&path, e.to_string())
+            })?;
+            let path = entry.path();
+            let metadata = entry
+                .metadata()
+                .map_err(|e| read_error(path, e.to_string()))?;
+            let name = entry
+                .file_name()
+                .to_str()
+                .ok_or_else(|| read_error(path, "File name is not valid UTF-8".to_string()))?
+                .to_string();
+
+            let file_type = entry.file_type();
+            let (kind, mode) = if file_type.is_dir() {
+                let kind = NodeKind::Directory {
+                    children: Vec::new(),
+                };
+                (kind, 0o040000 | permissions(&metadata, 0o755))
+            } else if file_type.is_file() {
+                if metadata.len() > u64::from(u32::MAX) {
+                    return Err(read_error(
+                        path,
+                        "Files of 4 GiB or more cannot be stored on the volume".to_string(),
+                    ));
+                }
+                let kind = NodeKind::File {
+                    source: path.to_path_buf(),
+                    size: metadata.len(),
+                };
+                (kind, 0o100000 | permissions(&metadata, 0o644))
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(path).map_err(|e| read_error(path, e.to_string()))?;
+                let target = target
+                    .to_str()
+                    .ok_or_else(|| read_error(path, "Link target is not valid UTF-8".to_string()))?
+                    .to_string();
+                (NodeKind::Symlink { target }, 0o120755)
+            } else {
+                // Sockets, FIFOs and devices have no place on a disk image
+                continue;
+            };
+
+            let parent = path
+                .parent()
+                .and_then(|parent| directories_by_path.get(parent))
+                .copied()
+                .ok_or_else(|| read_error(path, "Parent directory not found".to_string()))?;
+            let index = nodes.len();
+            if matches!(kind, NodeKind::Directory { .. }) {
+                directories_by_path.insert(path.to_path_buf(), index);
+            }
+            nodes.push(Node {
+                name,
+                identifier: Vec::new(),
+                parent,
+                kind,
+                mode,
+                mtime: mtime(&metadata),
+                extent: 0,
+                length: 0,
+            });
+            if let NodeKind::Directory { children } = &mut nodes[parent].kind {
+                children.push(index);
+            }
+        }
+
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut image = Self {
+            volume_name: volume_name.to_string(),
+            nodes,
+            directories: Vec::new(),
+            path_table_size: 0,
+            path_table_sectors: 0,
+            total_sectors: 0,
+            created,
+        };
+        image.assign_identifiers();
+        image.layout()?;
+        Ok(image)
+    }
+
+    /// Size of the volume in bytes.
+    pub fn size(&self) -> u64 {
+        u64::from(self.total_sectors) * SECTOR_SIZE
+    }
+
+    /// Number of regular files on the volume.
+    pub fn file_count(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::File { .. }))
+            .count()
+    }
+
+    /// Write the volume to `out`, reading file contents from the source.
+    ///
+    /// `output` names the destination in write errors.
+    pub fn write_to<W: Write>(&self, out: &mut W, output: &Path) -> PackageResult<()> {
+        let write_error = |e: std::io::Error| PackageError::output_write(output.to_path_buf(), e);
+        let mut sectors = vec![0u8; SYSTEM_AREA_SECTORS as usize * SECTOR_SIZE as usize];
+        sectors.extend_from_slice(&self.primary_volume_descriptor());
+        let mut terminator = vec![0u8; SECTOR_SIZE as usize];
+        terminator[..7].copy_from_slice(b"\xffCD001\x01");
+        sectors.extend_from_slice(&terminator);
+
+        for big_endian in [false, true] {
+            let table = self.path_table(big_endian);
+            sectors.extend_from_slice(&table);
+            pad_to_sector(&mut sectors, table.len());
+        }
+        out.write_all(&sectors).map_err(write_error)?;
+
+        for &directory in &self.directories {
+            let mut extent = Vec::with_capacity(self.nodes[directory].length as usize);
+            for record in self.directory_records(directory)? {
+                let used = extent.len() % SECTOR_SIZE as usize;
+                if used + record.len() > SECTOR_SIZE as usize {
+                    extent.resize(extent.len() + SECTOR_SIZE as usize - used, 0);
+                }
+                extent.extend_from_slice(&record);
+            }
+            extent.resize(self.nodes[directory].length as usize, 0);
+            out.write_all(&extent).map_err(write_error)?;
+        }
+
+        let mut buffer = vec![0u8; 64 * 1024];
+        for node in &self.nodes {
+            let NodeKind::File { source, size } = &node.kind else {
+                continue;
+            };
+            let read_error = |reason: String| PackageError::SourceReadError {
+                path: source.clone(),
+                reason,
+                source: None,
+            };
+            let mut file = File::open(source)
+                .map_err(|e| read_error(e.to_string()))?
+                .take(*size);
+            let mut copied = 0u64;
+            loop {
+                let n = file
+                    .read(&mut buffer)
+                    .map_err(|e| read_error(e.to_string()))?;
+                if n == 0 {
+                    break;
+                }
+                out.write_all(&buffer[..n]).map_err(write_error)?;
+                copied += n as u64;
+            }
+            if copied != *size {
+                return Err(read_error(format!(
+                    "File changed while packaging ({} of {} bytes read)",
+                    copied, size
+                )));
+            }
+            let tail = (copied % SECTOR_SIZE) as usize;
+            if tail != 0 {
+                out.write_all(&[0u8; SECTOR_SIZE as usize][tail..])
+                    .map_err(write_error)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Give every node an identifier unique among its siblings and sort
+    /// each directory by them, as ISO 9660 requires.
+    fn assign_identifiers(&mut self) {
+        for index in 0..self.nodes.len() {
+            let NodeKind::Directory { children } = &self.nodes[index].kind else {
+                continue;
+            };
+            let mut children = children.clone();
+            let mut taken = HashSet::new();
+            for &child in &children {
+                let node = &self.nodes[child];
+                let is_file = !matches!(node.kind, NodeKind::Directory { .. });
+                let identifier = unique_identifier(&node.name, is_file, &taken);
+                taken.insert(identifier.clone());
+                self.nodes[child].identifier = identifier;
+            }
+            children.sort_by(|a, b| self.nodes[*a].identifier.cmp(&self.nodes[*b].identifier));
+            self.nodes[index].kind = NodeKind::Directory { children };
+        }
+    }
+
+    /// Assign blocks: descriptors, path tables, directories, then files.
+    fn layout(&mut self) -> PackageResult<()> {
+        self.directories = vec![0];
+        let mut next = 0;
+        while next < self.directories.len() {
+            let subdirectories: Vec<usize> = self
+                .children(self.directories[next])
+                .iter()
+                .copied()
+                .filter(|&child| matches!(self.nodes[child].kind, NodeKind::Directory { .. }))
+                .collect();
+            self.directories.extend(subdirectories);
+            next += 1;
+        }
+
+        self.path_table_size = self
+            .directories
+            .iter()
+            .map(|&d| {
+                let len = self.nodes[d].identifier.len();
+                (8 + len + len % 2) as u32
+            })
+            .sum();
+        self.path_table_sectors = sectors_for(u64::from(self.path_table_size));
+
+        let mut sector = SYSTEM_AREA_SECTORS + 2 + 2 * self.path_table_sectors;
+        for i in 0..self.directories.len() {
+            let directory = self.directories[i];
+            let mut length = 0usize;
+            for record in self.directory_records(directory)? {
+                let used = length % SECTOR_SIZE as usize;
+                if used + record.len() > SECTOR_SIZE as usize {
+                    length += SECTOR_SIZE as usize - used;
+                }
+                length += record.len();
+            }
+            let blocks = sectors_for(length as u64);
+            let node = &mut self.nodes[directory];
+            node.extent = sector;
+            node.length = blocks * SECTOR_SIZE as u32;
+            sector += blocks;
+        }
+
+        for node in &mut self.nodes {
+            if let NodeKind::File { size, .. } = node.kind {
+                node.length = size as u32;
+                if size > 0 {
+                    node.extent = sector;
+                    sector = sector.checked_add(sectors_for(size)).ok_or_else(|| {
+                        PackageError::InvalidArgument {
+                            reason: "Content is too large for a disk image".to_string(),
+                        }
+                    })?;
+                }
+            }
+        }
+        self.total_sectors = sector;
+        Ok(())
+    }
+
+    fn children(&self, directory: usize) -> &[usize] {
+        match &self.nodes[directory].kind {
+            NodeKind::Directory { children } => children,
+            _ => &[],
+        }
+    }
+
+    /// The `.`, `..` and child records of `directory`.
+    fn directory_records(&self, directory: usize) -> PackageResult<Vec<Vec<u8>>> {
+        let node = &self.nodes[directory];
+        let parent = &self.nodes[node.parent];
+
+        let mut own_entries = Vec::new();
+        if directory == 0 {
+            own_entries.extend_from_slice(&[b'S', b'P', 7, 1, 0xBE, 0xEF, 0]);
+            own_entries.extend_from_slice(&[
+                b'E',
+                b'R',
+                (8 + RRIP_ID.len() + RRIP_DESCRIPTOR.len() + RRIP_SOURCE.len()) as u8,
+                1,
+                RRIP_ID.len() as u8,
+                RRIP_DESCRIPTOR.len() as u8,
+                RRIP_SOURCE.len() as u8,
+                1,
+            ]);
+            own_entries.extend_from_slice(RRIP_ID);
+            own_entries.extend_from_slice(RRIP_DESCRIPTOR);
+            own_entries.extend_from_slice(RRIP_SOURCE);
+        }
+        own_entries.extend_from_slice(&self.posix_entry(directory));
+
+        let mut records = vec![
+            directory_record(&[0], node, &own_entries),
+            directory_record(&[1], parent, &self.posix_entry(node.parent)),
+        ];
+        for &child in self.children(directory) {
+            let child_node = &self.nodes[child];
+            let mut entries = self.posix_entry(child).to_vec();
+            entries.extend_from_slice(&[b'N', b'M', (5 + child_node.name.len()) as u8, 1, 0]);
+            entries.extend_from_slice(child_node.name.as_bytes());
+            if let NodeKind::Symlink { target } = &child_node.kind {
+                entries.extend_from_slice(&symlink_entry(target));
+            }
+
+            let record = directory_record(&child_node.identifier, child_node, &entries);
+            if record.len() > MAX_RECORD_LEN {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "The name or link target of '{}' is too long for a disk image volume",
+                        child_node.name
+                    ),
+                });
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Rock Ridge `PX` entry: mode, link count and owner of a node.
+    fn posix_entry(&self, index: usize) -> [u8; 36] {
+        let node = &self.nodes[index];
+        let links = match &node.kind {
+            NodeKind::Directory { children } => {
+                2 + children
+                    .iter()
+                    .filter(|&&c| matches!(self.nodes[c].kind, NodeKind::Directory { .. }))
+                    .count() as u32
+            }
+            _ => 1,
+        };
+
+        let mut entry = [0u8; 36];
+        entry[..4].copy_from_slice(&[b'P', b'X', 36, 1]);
+        entry[4..12].copy_from_slice(&both_endian_u32(node.mode));
+        entry[12..20].copy_from_slice(&both_endian_u32(links));
+        // uid and gid stay 0: the mounting user owns the files
+        entry
+    }
+
+    fn path_table(&self, big_endian: bool) -> Vec<u8> {
+        let mut numbers = HashMap::new();
+        let mut table = Vec::with_capacity(self.path_table_size as usize);
+        for (i, &directory) in self.directories.iter().enumerate() {
+            numbers.insert(directory, i as u16 + 1);
+            let node = &self.nodes[directory];
+            let parent = numbers.get(&node.parent).copied().unwrap_or(1);
+            table.push(node.identifier.len() as u8);
+            table.push(0);
+            if big_endian {
+                table.extend_from_slice(&node.extent.to_be_bytes());
+                table.extend_from_slice(&parent.to_be_bytes());
+            } else {
+                table.extend_from_slice(&node.extent.to_le_bytes());
+                table.extend_from_slice(&parent.to_le_bytes());
+            }
+            table.extend_from_slice(&node.identifier);
+            if node.identifier.len() % 2 == 1 {
+                table.push(0);
+            }
+        }
+        table
+    }
+
+    fn primary_volume_descriptor(&self) -> Vec<u8> {
+        let mut pvd = vec![0u8; SECTOR_SIZE as usize];
+        pvd[..7].copy_from_slice(b"\x01CD001\x01");
+        put_padded(&mut pvd[8..40], b"");
+        put_padded(&mut pvd[40..72], self.volume_name.as_bytes());
+        pvd[80..88].copy_from_slice(&both_endian_u32(self.total_sectors));
+        pvd[120..124].copy_from_slice(&both_endian_u16(1));
+        pvd[124..128].copy_from_slice(&both_endian_u16(1));
+        pvd[128..132].copy_from_slice(&both_endian_u16(SECTOR_SIZE as u16));
+        pvd[132..140].copy_from_slice(&both_endian_u32(self.path_table_size));
+        let l_table = SYSTEM_AREA_SECTORS + 2;
+        pvd[140..144].copy_from_slice(&l_table.to_le_bytes());
+        pvd[148..152].copy_from_slice(&(l_table + self.path_table_sectors).to_be_bytes());
+        pvd[156..190].copy_from_slice(&directory_record(&[0], &self.nodes[0], &[]));
+        put_padded(&mut pvd[190..574], b"");
+        put_padded(&mut pvd[574..702], b"IAMAWRAPPER");
+        put_padded(&mut pvd[702..813], b"");
+        let created = volume_date(self.created);
+        pvd[813..830].copy_from_slice(&created);
+        pvd[830..847].copy_from_slice(&created);
+        // No expiration or effective date
+        pvd[847..863].copy_from_slice(b"0000000000000000");
+        pvd[864..880].copy_from_slice(b"0000000000000000");
+        pvd[881] = 1;
+        pvd
+    }
+}
+
+/// A directory record for `node` named `identifier`, with system use
+/// `entries` (Rock Ridge).
+fn directory_record(identifier: &[u8], node: &Node, entries: &[u8]) -> Vec<u8> {
+    let padding = usize::from(identifier.len() % 2 == 0);
+    let len = 33 + identifier.len() + padding + entries.len();
+
+    let mut record = Vec::with_capacity(len);
+    record.push(len as u8);
+    record.push(0);
+    record.extend_from_slice(&both_endian_u32(node.extent));
+    record.extend_from_slice(&both_endian_u32(node.length));
+    record.extend_from_slice(&record_date(node.mtime));
+    record.push(match node.kind {
+        NodeKind::Directory { .. } => 0x02,
+        _ => 0,
+    });
+    record.extend_from_slice(&[0, 0]);
+    record.extend_from_slice(&both_endian_u16(1));
+    record.push(identifier.len() as u8);
+    record.extend_from_slice(identifier);
+    record.resize(record.len() + padding, 0);
+    record.extend_from_slice(entries);
+    record
+}
+
+/// Rock Ridge `SL` entry for a link to `target`.
+fn symlink_entry(target: &str) -> Vec<u8> {
+    let mut components = Vec::new();
+    if target.starts_with('/') {
+        components.extend_from_slice(&[0x08, 0]);
+    }
+    for component in target.split('/').filter(|c| !c.is_empty()) {
+        match component {
+            "." => components.extend_from_slice(&[0x02, 0]),
+            ".." => components.extend_from_slice(&[0x04, 0]),
+            name => {
+                components.push(0);
+                components.push(name.len() as u8);
+                components.extend_from_slice(name.as_bytes());
+            }
+        }
+    }
+
+    let mut entry = vec![b'S', b'L', (5 + components.len()) as u8, 1, 0];
+    entry.extend_from_slice(&components);
+    entry
+}
+
+/// An ISO 9660 identifier for `name` not in `taken`: upper-case
+/// d-characters, with a `;1` version for files.
+fn unique_identifier(name: &str, is_file: bool, taken: &HashSet<Vec<u8>>) -> Vec<u8> {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if is_file && dot > 0 => (&name[..dot], &name[dot + 1..]),
+        _ => (name, ""),
+    };
+    let max_base = if is_file { MAX_BASE_LEN } else { 31 };
+    let base = d_characters(stem, max_base);
+    let extension = d_characters(extension, MAX_EXTENSION_LEN);
+
+    let build = |base: &str| -> Vec<u8> {
+        let base = if base.is_empty() { "_" } else { base };
+        match is_file {
+            true => format!("{}.{};1", base, extension).into_bytes(),
+            false => base.as_bytes().to_vec(),
+        }
+    };
+    let mut identifier = build(&base);
+    let mut counter = 1;
+    while taken.contains(&identifier) {
+        let suffix = format!("_{}", counter);
+        let kept: String = base.chars().take(max_base - suffix.len()).collect();
+        identifier = build(&format!("{}{}", kept, suffix));
+        counter += 1;
+    }
+    identifier
+}
+
+fn d_characters(text: &str, max_len: usize) -> String {
+    text.chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            c @ ('A'..='Z' | '0'..='9' | '_') => c,
+            _ => '_',
+        })
+        .take(max_len)
+        .collect()
+}
+
+fn permissions(metadata: &Metadata, default: u32) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = default;
+        metadata.permissions().mode() & 0o7777
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        default
+    }
+}
+
+fn mtime(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn sectors_for(bytes: u64) -> u32 {
+    bytes.div_ceil(SECTOR_SIZE) as u32
+}
+
+fn pad_to_sector(buffer: &mut Vec<u8>, written: usize) {
+    let tail = written % SECTOR_SIZE as usize;
+    if tail != 0 {
+        buffer.resize(buffer.len() + SECTOR_SIZE as usize - tail, 0);
+    }
+}
+
+fn put_padded(field: &mut [u8], value: &[u8]) {
+    field.fill(b' ');
+    field[..value.len()].copy_from_slice(value);
+}
+
+fn both_endian_u16(value: u16) -> [u8; 4] {
+    let mut bytes = [0u8; 4];
+    bytes[..2].copy_from_slice(&value.to_le_bytes());
+    bytes[2..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+fn both_endian_u32(value: u32) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&value.to_le_bytes());
+    bytes[4..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// Seven-byte directory record date (UTC).
+fn record_date(secs: u64) -> [u8; 7] {
+    let [year, month, day, hour, minute, second] = civil_time(secs);
+    [
+        year.saturating_sub(1900).min(255) as u8,
+        month as u8,
+        day as u8,
+        hour as u8,
+        minute as u8,
+        second as u8,
+        0,
+    ]
+}
+
+/// Seventeen-byte volume descriptor date (UTC).
+fn volume_date(secs: u64) -> [u8; 17] {
+    let [year, month, day, hour, minute, second] = civil_time(secs);
+    let text = format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}00",
+        year, month, day, hour, minute, second
+    );
+    let mut date = [0u8; 17];
+    date[..16].copy_from_slice(text.as_bytes());
+    date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Read the directory at block `extent` into (identifier, length, system use) tuples.
+    fn read_directory(image: &[u8], extent: u32, length: u32) -> Vec<(Vec<u8>, u32, u32, Vec<u8>)> {
+        let start = extent as usize * SECTOR_SIZE as usize;
+        let data = &image[start..start + length as usize];
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let len = data[pos] as usize;
+            if len == 0 {
+                pos = (pos / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            let record = &data[pos..pos + len];
+            let id_len = record[32] as usize;
+            let su_start = 33 + id_len + usize::from(id_len % 2 == 0);
+            records.push((
+                record[33..33 + id_len].to_vec(),
+                u32::from_le_bytes(record[2..6].try_into().unwrap()),
+                u32::from_le_bytes(record[10..14].try_into().unwrap()),
+                record[su_start..].to_vec(),
+            ));
+            pos += len;
+        }
+        records
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_iso_image_layout() {
+        let temp = TempDir::new().unwrap();
+        let app = temp.path().join("My Tool.app/Contents");
+        fs::create_dir_all(&app).unwrap();
+        fs::write(app.join("Info.plist"), "<plist/>").unwrap();
+        fs::write(temp.path().join("README.txt"), vec![b'x'; 5000]).unwrap();
+        fs::write(temp.path().join("readme.TXT"), "clash").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/Applications", temp.path().join("Applications")).unwrap();
+
+        let image = IsoImage::from_folder(temp.path(), "My Tool").unwrap();
+        assert_eq!(image.file_count(), 3);
+        let mut bytes = Vec::new();
+        image.write_to(&mut bytes, Path::new("out.iso")).unwrap();
+        assert_eq!(bytes.len() as u64, image.size());
+
+        let pvd = &bytes[16 * 2048..17 * 2048];
+        assert_eq!(&pvd[1..6], b"CD001");
+        assert_eq!(&pvd[40..47], b"My Tool");
+        assert_eq!(
+            u32::from_le_bytes(pvd[80..84].try_into().unwrap()) as u64 * SECTOR_SIZE,
+            image.size()
+        );
+        assert_eq!(bytes[17 * 2048], 0xff);
+
+        let root_extent = u32::from_le_bytes(pvd[158..162].try_into().unwrap());
+        let root_length = u32::from_le_bytes(pvd[166..170].try_into().unwrap());
+        let root = read_directory(&bytes, root_extent, root_length);
+        // ., .. and the children sorted by identifier
+        let ids: Vec<_> = root
+            .iter()
+            .map(|r| String::from_utf8_lossy(&r.0).into_owned())
+            .collect();
+        #[cfg(unix)]
+        assert_eq!(
+            &ids[2..],
+            &[
+                "APPLICATIONS.;1",
+                "MY_TOOL_APP",
+                "README.TXT;1",
+                "README_1.TXT;1"
+            ]
+        );
+        assert!(root[0].3.starts_with(b"SP\x07\x01\xbe\xef"));
+        assert!(contains(&root[0].3, RRIP_ID));
+
+        // Rock Ridge keeps the real names and contents stay intact
+        let readme = root.iter().find(|r| contains(&r.3, b"README.txt")).unwrap();
+        assert_eq!(readme.2, 5000);
+        let start = readme.1 as usize * 2048;
+        assert!(bytes[start..start + 5000].iter().all(|&b| b == b'x'));
+        assert!(root.iter().any(|r| contains(&r.3, b"My Tool.app")));
+        #[cfg(unix)]
+        {
+            let link = root.iter().find(|r| contains(&r.3, b"SL")).unwrap();
+            assert!(contains(&link.3, b"\x00\x0cApplications"));
+        }
+    }
+
+    #[test]
+    fn test_unique_identifier() {
+        let mut taken = HashSet::new();
+        let id = unique_identifier("libfoo.1.dylib", true, &taken);
+        assert_eq!(id, b"LIBFOO_1.DYLIB;1");
+        taken.insert(id);
+        assert_eq!(
+            unique_identifier("libFOO.1.dylib", true, &taken),
+            b"LIBFOO_1_1.DYLIB;1"
+        );
+        assert_eq!(
+            unique_identifier("Frameworks", false, &taken),
+            b"FRAMEWORKS"
+        );
+        assert_eq!(unique_identifier(".hidden", true, &taken), b"_HIDDEN.;1");
+    }
+}
@@ -6,128 +6,778 @@
 #[cfg(feature = "macos")]
 pub mod bom;
 #[cfg(feature = "macos")]
+pub mod bundle;
+#[cfg(any(feature = "macos", feature = "linux"))]
 pub mod cpio;
 #[cfg(feature = "macos")]
+pub mod distribution;
+#[cfg(feature = "macos")]
+pub mod dmg;
+#[cfg(feature = "macos")]
+pub mod extract;
+#[cfg(feature = "macos")]
+pub use crate::packager::http;
+#[cfg(feature = "macos")]
+pub mod iso;
+#[cfg(feature = "macos")]
+pub mod notarize;
+#[cfg(feature = "macos")]
 pub mod payload;
 #[cfg(feature = "macos")]
+pub mod repack;
+#[cfg(feature = "macos")]
+pub mod resources;
+#[cfg(feature = "macos")]
+pub mod rewrap;
+#[cfg(feature = "macos")]
+pub mod sign;
+#[cfg(feature = "macos")]
+pub mod validate;
+#[cfg(feature = "xar")]
 pub mod xar;
 #[cfg(feature = "macos")]
 pub mod xml;
 
+#[cfg(feature = "macos")]
+use std::collections::BTreeMap;
 #[cfg(feature = "macos")]
 use std::fs;
 #[cfg(feature = "macos")]
-use std::time::Instant;
+use std::io::Write;
+#[cfg(feature = "macos")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "macos")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "macos")]
+use tracing::{info, info_span};
 
 #[cfg(feature = "macos")]
 use crate::models::PackageError;
 #[cfg(feature = "macos")]
-use crate::models::macos::{MacosPkgRequest, MacosPkgResult};
+use crate::models::artifact::ArtifactMetadata;
+#[cfg(feature = "macos")]
+use crate::models::blocklist::BlocklistMatch;
+#[cfg(feature = "macos")]
+use crate::models::dry_run::DryRunReport;
+#[cfg(feature = "macos")]
+use crate::models::error::PackageResult;
+#[cfg(feature = "macos")]
+use crate::models::macos::{
+    AppBundle, MacosPkgRequest, MacosPkgResult, PackagePayload, PayloadSummary,
+};
+#[cfg(feature = "macos")]
+use crate::models::package::{SkippedEntry, SparseFile};
+#[cfg(feature = "macos")]
+use crate::models::progress::ProgressStats;
+#[cfg(feature = "macos")]
+use crate::models::retry::RetryPolicy;
+#[cfg(feature = "macos")]
+use crate::models::sbom::Sbom;
+#[cfg(feature = "macos")]
+use crate::models::size::ByteSize;
+#[cfg(feature = "macos")]
+use crate::models::transform::TransformRecord;
+#[cfg(feature = "macos")]
+use crate::packager::archive::{SourceArchive, extract_source};
+#[cfg(feature = "macos")]
+use crate::packager::blocklist::check_blocklist;
+#[cfg(feature = "macos")]
+use crate::packager::content::FolderContent;
+#[cfg(feature = "macos")]
+use crate::packager::manifest::ManifestHasher;
+#[cfg(feature = "macos")]
+use crate::packager::metadata::generate_transforms_xml;
+#[cfg(feature = "macos")]
+use crate::packager::output::write_atomically;
+#[cfg(feature = "macos")]
+use crate::packager::sbom::{add_blocklist_matches, scan_content, write_sidecar};
+#[cfg(feature = "macos")]
+use crate::packager::source::fetch_source;
+#[cfg(feature = "macos")]
+use crate::packager::special::{check_skipped, check_special_modes};
+#[cfg(feature = "macos")]
+use crate::packager::transform::stage_transformed;
+#[cfg(feature = "macos")]
+use crate::packager::validate::validate_with_timeout;
+#[cfg(feature = "macos")]
+use crate::packager::workspace::{StagingDir, stage_tree, staged_copy_size};
+
+/// Name of the optional record of transformed files in the package.
+#[cfg(feature = "macos")]
+pub const TRANSFORMS_FILE: &str = "Transforms.xml";
+
+/// Name of the optional embedded SBOM in the package.
+#[cfg(feature = "macos")]
+pub const SBOM_FILE: &str = "SBOM.json";
+
+/// Folder of the Installer's welcome, license and readme files.
+#[cfg(feature = "macos")]
+pub const RESOURCES_DIR: &str = "Resources";
 
 /// Create a macOS flat package (.pkg) from the given request.
 #[cfg(feature = "macos")]
 pub fn package(request: MacosPkgRequest) -> Result<MacosPkgResult, PackageError> {
     let start = Instant::now();
+    let _span = info_span!("package", identifier = %request.identifier).entered();
 
-    // Collect files from source folder
-    let payload_data = payload::collect_files(&request.source_folder)?;
-    let file_count = payload_data.files.len();
-
-    // Check for scripts
-    let (has_preinstall, has_postinstall, scripts_archive) =
-        if let Some(ref scripts_folder) = request.scripts_folder {
-            // Validate scripts folder exists
-            if !scripts_folder.exists() {
-                return Err(PackageError::ScriptsFolderNotFound {
-                    path: scripts_folder.clone(),
-                });
-            }
+    request.validate()?;
 
-            let scripts_info = payload::collect_scripts(scripts_folder)?;
+    // Load the signing identity before doing any work
+    let signer = request
+        .signing
+        .as_ref()
+        .map(sign::Signer::load)
+        .transpose()?;
 
-            // Create scripts archive if any scripts found
-            let archive = if scripts_info.has_preinstall || scripts_info.has_postinstall {
-                Some(payload::create_scripts_archive(scripts_folder)?)
-            } else {
-                None
-            };
+    // Identify the payload content for fleet tooling
+    let mut manifest = ManifestHasher::default();
+    let mut component = info_span!("payload")
+        .in_scope(|| build_component(&request, &mut manifest, Path::new("")))?;
+    info!(
+        files = component.summary.files,
+        bytes = component.summary.file_bytes,
+        "Built payload"
+    );
+    let artifact = ArtifactMetadata::new(manifest.finish());
+    if let Some(sbom) = &mut component.sbom {
+        sbom.created = artifact.created;
+    }
 
-            (
-                scripts_info.has_preinstall,
-                scripts_info.has_postinstall,
-                archive,
-            )
-        } else {
-            (false, false, None)
+    let resources = resources::collect_resources(&request.distribution)?;
+    let distribution_xml = xml::generate_custom_distribution(
+        &request.identifier,
+        &request.version,
+        component.summary.install_kbytes,
+        &request.distribution,
+        &resources.elements,
+    )?;
+
+    // Build outer XAR archive (flat package structure)
+    let mut outer_xar = xar::XarBuilder::new();
+    outer_xar.set_artifact(artifact.clone());
+    outer_xar.add_file("Distribution", distribution_xml.into_bytes())?;
+    component.add_to(&mut outer_xar, "base.pkg")?;
+
+    // Files the Distribution's welcome, license etc. refer to
+    for file in &resources.files {
+        let data = fs::read(&file.source)
+            .map_err(|e| PackageError::source_read(file.source.clone(), e))?;
+        outer_xar.add_nested_file(&format!("{}/{}", RESOURCES_DIR, file.path), data)?;
+    }
+
+    // Record transformed files next to Distribution
+    if !component.transforms.is_empty() {
+        outer_xar.add_file(
+            TRANSFORMS_FILE,
+            generate_transforms_xml(&component.transforms)?.into_bytes(),
+        )?;
+    }
+    if let (Some(sbom), Some(options)) = (&component.sbom, &request.sbom) {
+        if options.embed {
+            outer_xar.add_file(SBOM_FILE, sbom.render(options.format).into_bytes())?;
+        }
+    }
+
+    let output_path = request.output_path();
+    let package_size = info_span!("write", signed = signer.is_some())
+        .in_scope(|| write_package(outer_xar, signer.as_ref(), &output_path, &request.io_retry))?;
+    let creation_time = start.elapsed();
+    info!(
+        path = %output_path.display(),
+        bytes = package_size.bytes(),
+        "Wrote package"
+    );
+
+    let sbom_path = match (&component.sbom, &request.sbom) {
+        (Some(sbom), Some(options)) if options.sidecar => {
+            Some(write_sidecar(sbom, options.format, &output_path)?)
+        }
+        _ => None,
+    };
+
+    // Prove the artifact can be read back
+    let validation =
+        match request.post_validate {
+            Some(timeout) => Some(info_span!("validate").in_scope(|| {
+                validate_with_timeout(&output_path, timeout, validate::validate_pkg)
+            })?),
+            None => None,
         };
 
+    Ok(MacosPkgResult {
+        output_path,
+        package_size,
+        file_count: component.summary.files,
+        payload: component.summary,
+        artifact,
+        signed_by: signer.map(|s| s.subject().to_string()),
+        bundle: component.bundle,
+        creation_time,
+        blocklist_matches: component.blocklist_matches,
+        special_mode_files: component.special_mode_files,
+        special_modes_preserved: request.allow_special_modes,
+        validation,
+        transforms: component.transforms,
+        sparse_files: component.sparse_files,
+        skipped_entries: component.skipped_entries,
+        stats: ProgressStats {
+            files_processed: component.summary.files as u64,
+            bytes_read: ByteSize(component.summary.file_bytes),
+            bytes_written: package_size,
+        },
+        sbom: component.sbom,
+        sbom_path,
+    })
+}
+
+/// Collect and check the request's payload as [`package`] does, without
+/// compressing or writing anything.
+///
+/// The signing identity and resources are loaded and the same checks run,
+/// so a dry run fails where the run would before its payload is built.
+/// Include/exclude patterns and transforms still stage a copy of the
+/// payload in the temp workspace, removed before this returns.
+#[cfg(feature = "macos")]
+pub fn dry_run(request: MacosPkgRequest) -> PackageResult<DryRunReport> {
+    let _span = info_span!("dry_run", identifier = %request.identifier).entered();
+
+    request.validate()?;
+    if let Some(signing) = &request.signing {
+        sign::Signer::load(signing)?;
+    }
+    resources::collect_resources(&request.distribution)?;
+
+    let mut metadata = vec![
+        ("identifier".to_string(), request.identifier.clone()),
+        ("version".to_string(), request.version.clone()),
+        (
+            "install_location".to_string(),
+            request.install_location.display().to_string(),
+        ),
+    ];
+    let output_path = request.output_path();
+    let mut report = DryRunReport {
+        output_exists: output_path.exists(),
+        output_path,
+        file_count: 0,
+        dir_count: 0,
+        total_size: ByteSize(0),
+        size_estimate: None,
+        estimated_time: Duration::ZERO,
+        metadata: Vec::new(),
+        blocklist_matches: Vec::new(),
+        sparse_files: Vec::new(),
+        skipped_entries: Vec::new(),
+        path_issues: Vec::new(),
+    };
+    if request.no_payload {
+        report.metadata = metadata;
+        return Ok(report);
+    }
+
+    let collected = collect_payload(&request)?;
+    if let Some(app) = &collected.app {
+        metadata.push(("bundle".to_string(), app.name()));
+    }
+    let total_size = ByteSize(collected.summary.file_bytes);
+    Ok(DryRunReport {
+        file_count: collected.summary.files,
+        dir_count: collected.summary.directories,
+        total_size,
+        estimated_time: DryRunReport::estimate_time(total_size),
+        metadata,
+        blocklist_matches: collected.blocklist_matches,
+        sparse_files: collected.payload_data.sparse_files,
+        skipped_entries: collected.payload_data.skipped_entries,
+        ..report
+    })
+}
+
+/// A component package built in memory, ready to be added to a XAR.
+#[cfg(feature = "macos")]
+pub(crate) struct Component {
+    pub(crate) packageinfo_xml: String,
+    /// The Bom, absent from payload-free components
+    pub(crate) bom: Option<Vec<u8>>,
+    /// The gzip-compressed CPIO payload, absent from payload-free components
+    pub(crate) payload: Option<Vec<u8>>,
+    pub(crate) scripts: Option<Vec<u8>>,
+    pub(crate) summary: PayloadSummary,
+    pub(crate) bundle: Option<AppBundle>,
+    pub(crate) blocklist_matches: Vec<BlocklistMatch>,
+    pub(crate) special_mode_files: Vec<PathBuf>,
+    pub(crate) transforms: Vec<TransformRecord>,
+    pub(crate) sparse_files: Vec<SparseFile>,
+    pub(crate) skipped_entries: Vec<SkippedEntry>,
+    pub(crate) sbom: Option<Sbom>,
+}
+
+#[cfg(feature = "macos")]
+impl Component {
+    /// Add the component's files under the directory `dir`.
+    pub(crate) fn add_to(&self, xar: &mut xar::XarBuilder, dir: &str) -> PackageResult<()> {
+        xar.add_directory(dir)?;
+        if let Some(bom) = &self.bom {
+            xar.add_file(&format!("{}/Bom", dir), bom.clone())?;
+        }
+        if let Some(payload) = &self.payload {
+            xar.add_file(&format!("{}/Payload", dir), payload.clone())?;
+        }
+        xar.add_file(
+            &format!("{}/PackageInfo", dir),
+            self.packageinfo_xml.clone().into_bytes(),
+        )?;
+        if let Some(scripts) = &self.scripts {
+            xar.add_file(&format!("{}/Scripts", dir), scripts.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/// Build the payload, BOM, scripts and PackageInfo of `request`, adding
+/// its payload files to `manifest` under `prefix`.
+#[cfg(feature = "macos")]
+pub(crate) fn build_component(
+    request: &MacosPkgRequest,
+    manifest: &mut ManifestHasher,
+    prefix: &Path,
+) -> PackageResult<Component> {
+    if request.no_payload {
+        return build_payload_free_component(request);
+    }
+
+    let CollectedPayload {
+        staging: _staging,
+        source_folder,
+        app,
+        transforms,
+        payload_data,
+        summary,
+        blocklist_matches,
+        special_mode_files,
+    } = collect_payload(request)?;
+    let source_folder = source_folder.as_path();
+
+    // Gather scripts from the folder, on their own and from templates
+    let (scripts_archive, has_preinstall, has_postinstall) = gather_scripts(request, app.as_ref())?;
+
     // Generate XML files
     let packageinfo_xml = xml::generate_packageinfo(
         &request.identifier,
         &request.version,
         request.install_location.to_str().unwrap_or("/"),
-        payload_data.total_size / 1024, // Convert to KB
-        file_count,
+        summary.install_kbytes,
+        summary.number_of_files,
         has_preinstall,
         has_postinstall,
+        app.as_ref(),
+    )?;
+
+    // Create CPIO payload (gzip compressed)
+    let payload_bytes = payload::create_payload(
+        source_folder,
+        request.allow_special_modes,
+        &request.compression,
+        &request.io_retry,
+    )?;
+
+    // Create BOM
+    let bom_bytes = bom::create_bom_from_directory(source_folder, request.allow_special_modes)?;
+
+    manifest.add_content(
+        &FolderContent::new(source_folder),
+        payload_data
+            .files
+            .iter()
+            .filter(|f| f.is_file())
+            .map(|f| f.relative_path.clone()),
+        prefix,
+    )?;
+
+    let sbom = match &request.sbom {
+        Some(_) => {
+            let mut sbom =
+                Sbom::new(&request.identifier, 0).with_version(Some(request.version.clone()));
+            scan_content(
+                &mut sbom,
+                &FolderContent::new(source_folder),
+                payload_data
+                    .files
+                    .iter()
+                    .filter(|f| f.is_file())
+                    .map(|f| f.relative_path.clone()),
+                prefix,
+            )?;
+            add_blocklist_matches(&mut sbom, &blocklist_matches, prefix);
+            Some(sbom)
+        }
+        None => None,
+    };
+
+    Ok(Component {
+        packageinfo_xml,
+        bom: Some(bom_bytes),
+        payload: Some(payload_bytes),
+        scripts: scripts_archive,
+        summary,
+        bundle: app,
+        blocklist_matches,
+        special_mode_files,
+        transforms,
+        sparse_files: payload_data.sparse_files,
+        skipped_entries: payload_data.skipped_entries,
+        sbom,
+    })
+}
+
+/// A payload collected and checked, before anything is compressed.
+#[cfg(feature = "macos")]
+pub(crate) struct CollectedPayload {
+    /// Staging copies `source_folder` may point into, removed on drop
+    pub(crate) staging: Vec<StagingDir>,
+    /// Folder the payload is read from
+    pub(crate) source_folder: PathBuf,
+    pub(crate) app: Option<AppBundle>,
+    pub(crate) transforms: Vec<TransformRecord>,
+    pub(crate) payload_data: PackagePayload,
+    pub(crate) summary: PayloadSummary,
+    pub(crate) blocklist_matches: Vec<BlocklistMatch>,
+    pub(crate) special_mode_files: Vec<PathBuf>,
+}
+
+/// Stage and collect the payload of `request`, then run the checks that
+/// need no payload: source limits, the blocklist and special modes.
+#[cfg(feature = "macos")]
+pub(crate) fn collect_payload(request: &MacosPkgRequest) -> PackageResult<CollectedPayload> {
+    // A remote source is downloaded, and an archive extracted keeping
+    // modes and links
+    let fetched = fetch_source(&request.source_folder)?;
+    let extracted = SourceArchive::detect(fetched.path())
+        .map(|format| extract_source(fetched.path(), format))
+        .transpose()?;
+    let source_root = extracted
+        .as_ref()
+        .map_or(fetched.path(), |staging| staging.path.as_path());
+
+    // A component's payload root holds the bundle itself
+    let app = request
+        .component
+        .then(|| bundle::read_app_bundle(source_root))
+        .transpose()?;
+    let component = app.as_ref().map(bundle::stage_bundle).transpose()?;
+    let payload_root = match &component {
+        Some(staging) => staging.path.as_path(),
+        None => source_root,
+    };
+
+    // Stage only the files kept by the include/exclude patterns
+    let filtered = if request.filter.is_empty() {
+        None
+    } else {
+        let staging = StagingDir::create_with_space("filter", staged_copy_size(payload_root))?;
+        stage_tree(
+            payload_root,
+            &staging.path,
+            &BTreeMap::new(),
+            &request.filter,
+        )?;
+        Some(staging)
+    };
+    let payload_root = filtered
+        .as_ref()
+        .map_or(payload_root, |staging| staging.path.as_path());
+
+    // Rewrite files matched by transform rules in a staging copy
+    let staged = stage_transformed(payload_root, &request.transforms)?;
+    let (source_folder, transforms) = match &staged {
+        Some((staging, records)) => (staging.path.as_path(), records.clone()),
+        None => (payload_root, Vec::new()),
+    };
+
+    // Collect files from source folder
+    let payload_data = payload::collect_files(source_folder)?;
+    let summary = payload_data.summary();
+    check_skipped(&request.strictness, &payload_data.skipped_entries)?;
+
+    // Guard against accidental monster packages
+    if !request.force {
+        request
+            .source_limits
+            .check(&request.source_folder, summary.files, summary.file_bytes)?;
+    }
+
+    // Check payload against the known-bad hash list
+    let blocklist_matches = match &request.blocklist {
+        Some(blocklist_path) => check_blocklist(
+            blocklist_path,
+            request.blocklist_action.under(&request.strictness),
+            &FolderContent::new(source_folder),
+            payload_data
+                .files
+                .iter()
+                .filter(|f| f.is_file())
+                .map(|f| f.relative_path.as_path()),
+        )?,
+        None => Vec::new(),
+    };
+
+    // Entries whose setuid/setgid/sticky bits need an explicit decision
+    let special_mode_files: Vec<_> = payload_data
+        .files
+        .iter()
+        .filter(|f| f.mode & payload::SPECIAL_MODE_BITS != 0)
+        .map(|f| f.relative_path.clone())
+        .collect();
+    check_special_modes(
+        &request.strictness,
+        request.allow_special_modes,
+        &special_mode_files,
     )?;
 
-    let distribution_xml = xml::generate_distribution(
+    let source_folder = source_folder.to_path_buf();
+    let staging = extracted
+        .into_iter()
+        .chain(component)
+        .chain(filtered)
+        .chain(staged.map(|(staging, _)| staging))
+        .collect();
+    Ok(CollectedPayload {
+        staging,
+        source_folder,
+        app,
+        transforms,
+        payload_data,
+        summary,
+        blocklist_matches,
+        special_mode_files,
+    })
+}
+
+/// Build a component that only carries scripts, without a Bom or Payload.
+#[cfg(feature = "macos")]
+fn build_payload_free_component(request: &MacosPkgRequest) -> PackageResult<Component> {
+    let (scripts, has_preinstall, has_postinstall) = gather_scripts(request, None)?;
+    if scripts.is_none() {
+        return Err(PackageError::NoScriptsFound {
+            path: request.scripts_folder.clone().unwrap_or_default(),
+        });
+    }
+
+    let packageinfo_xml = xml::generate_payload_free_packageinfo(
         &request.identifier,
-        &request.identifier, // Use identifier as title for now
         &request.version,
-        payload_data.total_size / 1024,
+        request.install_location.to_str().unwrap_or("/"),
+        has_preinstall,
+        has_postinstall,
     )?;
 
-    // Create CPIO payload (gzip compressed)
-    let payload_bytes = payload::create_payload(&request.source_folder)?;
+    Ok(Component {
+        packageinfo_xml,
+        bom: None,
+        payload: None,
+        scripts,
+        summary: PayloadSummary::default(),
+        bundle: None,
+        blocklist_matches: Vec::new(),
+        special_mode_files: Vec::new(),
+        transforms: Vec::new(),
+        sparse_files: Vec::new(),
+        skipped_entries: Vec::new(),
+        sbom: None,
+    })
+}
 
-    // Create BOM
-    let bom_bytes = bom::create_bom_from_directory(&request.source_folder)?;
+/// Build the Scripts archive of a component from the request's scripts
+/// folder, single scripts and templates, returning it with whether a
+/// preinstall and a postinstall script exist.
+#[cfg(feature = "macos")]
+fn gather_scripts(
+    request: &MacosPkgRequest,
+    app: Option<&AppBundle>,
+) -> PackageResult<(Option<Vec<u8>>, bool, bool)> {
+    let scripts = payload::InstallScripts {
+        folder: request.scripts_folder.clone(),
+        preinstall: request.preinstall_script.clone(),
+        postinstall: request.postinstall_script.clone(),
+        templates: request.script_templates.clone(),
+    };
+    let info = scripts.info()?;
+    if !info.has_preinstall && !info.has_postinstall {
+        return Ok((None, false, false));
+    }
 
-    // Build outer XAR archive (flat package structure)
-    let mut outer_xar = xar::XarBuilder::new();
-    outer_xar.add_file("Distribution", distribution_xml.into_bytes())?;
-    outer_xar.add_directory("base.pkg")?;
-    outer_xar.add_file("base.pkg/Bom", bom_bytes.clone())?;
-    outer_xar.add_file("base.pkg/Payload", payload_bytes.clone())?;
-    outer_xar.add_file("base.pkg/PackageInfo", packageinfo_xml.into_bytes())?;
+    let app_name = app.map(|bundle| bundle.name().trim_end_matches(".app").to_string());
+    let context = payload::TemplateContext {
+        identifier: &request.identifier,
+        app_name: app_name.as_deref(),
+    };
+    let archive = payload::create_scripts_archive(&scripts, &context)?;
+    Ok((Some(archive), info.has_preinstall, info.has_postinstall))
+}
 
-    // Add scripts archive if present
-    if let Some(scripts_bytes) = scripts_archive {
-        outer_xar.add_file("base.pkg/Scripts", scripts_bytes)?;
+/// A Scripts archive merged from an existing one and a folder.
+#[cfg(feature = "macos")]
+#[derive(Debug)]
+pub(crate) struct MergedScripts {
+    /// The new gzip-compressed CPIO archive
+    pub(crate) archive: Vec<u8>,
+    /// Files from the folder that were not in the archive
+    pub(crate) added: Vec<String>,
+    /// Files from the folder that replaced archive entries
+    pub(crate) replaced: Vec<String>,
+    pub(crate) has_preinstall: bool,
+    pub(crate) has_postinstall: bool,
+}
+
+/// Merge the files in `folder` into an existing Scripts archive, or into
+/// a new one when `existing` is `None`.
+///
+/// A file replaces the archive entry with the same path (`./` prefixes
+/// aside) and every other entry is kept. Files from the folder are stored
+/// with mode 0755, like the scripts of a new package, and follow the
+/// archive's path style.
+#[cfg(feature = "macos")]
+pub(crate) fn merge_scripts(
+    existing: Option<&[u8]>,
+    folder: &Path,
+) -> PackageResult<MergedScripts> {
+    if !folder.is_dir() {
+        return Err(PackageError::ScriptsFolderNotFound {
+            path: folder.to_path_buf(),
+        });
     }
 
-    // Write to bytes
-    use std::io::Cursor;
-    let mut pkg_data = Cursor::new(Vec::new());
-    outer_xar.finish(&mut pkg_data)?;
-    let pkg_data = pkg_data.into_inner();
+    let records = existing
+        .map(cpio::read_payload)
+        .transpose()?
+        .unwrap_or_default();
+    let dot_paths = records
+        .iter()
+        .any(|r| r.path == "." || r.path.starts_with("./"));
 
-    // Ensure output directory exists
-    if let Some(parent) = request.output_path().parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent).map_err(|e| PackageError::OutputFolderCreationFailed {
-                path: parent.to_path_buf(),
-                reason: e.to_string(),
-            })?;
+    // Entries by path without the "./" prefix, in archive order; hard
+    // links get their own copy of the data
+    let mut entries: Vec<(String, cpio::CpioEntry)> = records
+        .iter()
+        .map(|record| {
+            let data = match &record.hard_link {
+                Some(first) => records
+                    .iter()
+                    .find(|r| &r.path == first)
+                    .map(|r| r.data.clone())
+                    .unwrap_or_default(),
+                None => record.data.clone(),
+            };
+            (
+                script_key(&record.path),
+                (record.path.clone(), data, record.mode),
+            )
+        })
+        .collect();
+
+    let mut added = Vec::new();
+    let mut replaced = Vec::new();
+    for entry in walkdir::WalkDir::new(folder)
+        .min_depth(1)
+        .sort_by_file_name()
+    {
+        let entry = entry.map_err(|e| PackageError::source_read(folder.to_path_buf(), e))?;
+        let path = entry.path();
+        let key = path
+            .strip_prefix(folder)
+            .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let (data, mode) = if entry.file_type().is_dir() {
+            (Vec::new(), cpio::S_IFDIR | 0o755)
+        } else if entry.file_type().is_file() {
+            let data =
+                fs::read(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+            (data, 0o755)
+        } else {
+            continue;
+        };
+
+        let stored = if dot_paths {
+            format!("./{}", key)
+        } else {
+            key.clone()
+        };
+        let is_file = entry.file_type().is_file();
+        match entries.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, slot)) => {
+                if is_file {
+                    replaced.push(key);
+                }
+                *slot = (stored, data, mode);
+            }
+            None => {
+                if is_file {
+                    added.push(key.clone());
+                }
+                entries.push((key, (stored, data, mode)));
+            }
         }
     }
 
-    // Write output file
-    let output_path = request.output_path();
-    fs::write(&output_path, &pkg_data).map_err(|e| PackageError::OutputWriteError {
-        path: output_path.clone(),
-        reason: e.to_string(),
-    })?;
+    if added.is_empty() && replaced.is_empty() {
+        return Err(PackageError::NoScriptsFound {
+            path: folder.to_path_buf(),
+        });
+    }
 
-    let creation_time = start.elapsed();
-    let package_size = pkg_data.len() as u64;
+    let has_script = |name: &str| {
+        entries
+            .iter()
+            .any(|(key, (_, _, mode))| key == name && mode & cpio::S_IFMT != cpio::S_IFDIR)
+    };
+    let has_preinstall = has_script("preinstall");
+    let has_postinstall = has_script("postinstall");
+    let entries: Vec<cpio::CpioEntry> = entries.into_iter().map(|(_, entry)| entry).collect();
 
-    Ok(MacosPkgResult {
-        output_path,
-        package_size,
-        file_count,
-        creation_time,
+    Ok(MergedScripts {
+        archive: cpio::create_payload(&entries)?,
+        added,
+        replaced,
+        has_preinstall,
+        has_postinstall,
     })
 }
+
+/// Path of a Scripts archive entry without its `./` prefix.
+#[cfg(feature = "macos")]
+fn script_key(path: &str) -> String {
+    path.strip_prefix("./").unwrap_or(path).to_string()
+}
+
+/// Assemble `xar`, signed by `signer` if given, and write it to
+/// `output_path`, returning the package size.
+#[cfg(feature = "macos")]
+pub(crate) fn write_package(
+    mut xar: xar::XarBuilder,
+    signer: Option<&sign::Signer>,
+    output_path: &Path,
+    retry: &RetryPolicy,
+) -> PackageResult<ByteSize> {
+    let mut pkg_data = Vec::new();
+    match signer {
+        Some(signer) => xar.finish_signed(&mut pkg_data, signer)?,
+        None => xar.finish(&mut pkg_data)?,
+    }
+
+    // Ensure output directory exists
+    if let Some(parent) = output_path.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+        }
+    }
+
+    write_atomically(output_path, retry, |writer| {
+        writer
+            .write_all(&pkg_data)
+            .map_err(|e| PackageError::output_write(output_path, e))
+    })?;
+    Ok(ByteSize(pkg_data.len() as u64))
+}
@@ -2,26 +2,42 @@
 //!
 //! Combines file collection with CPIO archive and gzip compression.
 
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use walkdir::WalkDir;
 
-use crate::macos::cpio::{CpioEntry, create_payload as create_cpio_payload};
+use crate::macos::cpio::{
+    CpioEntry, CpioFormat, CpioWriter, S_IFDIR, S_IFLNK, compress_payload,
+    create_payload as create_cpio_payload,
+};
 use crate::models::PackageError;
-use crate::models::macos::PackagePayload;
+use crate::models::compression::Compression;
+use crate::models::macos::{PackagePayload, ScriptSource, ScriptTemplate};
+use crate::models::retry::RetryPolicy;
+use crate::packager::retry::read_file;
+use crate::packager::sparse::detect_sparse;
+pub(crate) use crate::packager::special::read_link_target;
+use crate::packager::special::{PRESERVE_SYMLINKS, WalkItem, classify, unix_mode};
+pub use crate::packager::special::{SPECIAL_MODE_BITS, effective_mode};
 
 /// Collect files from a source directory.
 ///
-/// Returns a PackagePayload containing metadata about all files.
+/// Returns a PackagePayload containing metadata about all files, with
+/// directories and symbolic links marked by their mode type bits.
 pub fn collect_files(source_folder: &Path) -> Result<PackagePayload, PackageError> {
     let mut payload = PackagePayload::new();
 
-    for entry in WalkDir::new(source_folder)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
+    for result in WalkDir::new(source_folder).follow_links(!PRESERVE_SYMLINKS) {
+        let entry = match classify(source_folder, result) {
+            WalkItem::Entry(entry) => entry,
+            WalkItem::Skipped(skipped) => {
+                payload.skipped_entries.push(skipped);
+                continue;
+            }
+            WalkItem::Error(_) => continue,
+        };
         let path = entry.path();
 
         // Skip the root directory itself
@@ -32,34 +48,25 @@ pub fn collect_files(source_folder: &Path) -> Result<PackagePayload, PackageErro
         // Get relative path
         let relative_path = path
             .strip_prefix(source_folder)
-            .map_err(|e| PackageError::SourceReadError {
-                path: path.to_path_buf(),
-                reason: e.to_string(),
-            })?
+            .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?
             .to_path_buf();
 
         // Get metadata
         let metadata = entry
             .metadata()
-            .map_err(|e| PackageError::SourceReadError {
-                path: path.to_path_buf(),
-                reason: e.to_string(),
-            })?;
-
-        // Get mode (default to 0o644 for files, 0o755 for directories)
-        #[cfg(unix)]
-        let mode = {
-            use std::os::unix::fs::PermissionsExt;
-            metadata.permissions().mode()
-        };
-        #[cfg(not(unix))]
-        let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+            .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
 
+        let mode = unix_mode(&metadata);
         if metadata.is_file() {
+            if let Some(sparse) = detect_sparse(&relative_path, &metadata) {
+                payload.sparse_files.push(sparse);
+            }
             payload.add_file(relative_path, metadata.len(), mode);
         } else if metadata.is_dir() {
-            // Add directory with size 0
-            payload.add_file(relative_path, 0, mode | 0o040000);
+            payload.add_directory(relative_path, mode);
+        } else if metadata.file_type().is_symlink() {
+            // Size is the length of the link target, as in the BOM
+            payload.add_symlink(relative_path, metadata.len(), mode);
         }
     }
 
@@ -67,61 +74,124 @@ pub fn collect_files(source_folder: &Path) -> Result<PackagePayload, PackageErro
 }
 
 /// Create a gzip-compressed CPIO payload from a source directory.
-pub fn create_payload(source_folder: &Path) -> Result<Vec<u8>, PackageError> {
-    let mut entries: Vec<CpioEntry> = Vec::new();
-
+///
+/// Like the BOM, the payload has a record for the install location itself
+/// (`.`) and for every directory, with paths starting with `./`. Hard links
+/// within the source (Unix) are stored once and linked by inode.
+///
+/// setuid/setgid/sticky bits are only kept when `allow_special_modes` is set.
+/// Stored `compression` still yields a gzip stream, at level 0. Files are
+/// read with `retry`, so transient errors on network shares are retried.
+pub fn create_payload(
+    source_folder: &Path,
+    allow_special_modes: bool,
+    compression: &Compression,
+    retry: &RetryPolicy,
+) -> Result<Vec<u8>, PackageError> {
+    let mut entries = Vec::new();
     for entry in WalkDir::new(source_folder)
-        .follow_links(true)
+        .min_depth(1)
+        .follow_links(!PRESERVE_SYMLINKS)
         .into_iter()
-        .filter_map(|e| e.ok())
+        .filter_map(|result| match classify(source_folder, result) {
+            WalkItem::Entry(entry) => Some(entry),
+            WalkItem::Skipped(_) | WalkItem::Error(_) => None,
+        })
     {
-        let path = entry.path();
+        let metadata = entry
+            .metadata()
+            .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))?;
+        entries.push((entry, metadata));
+    }
 
-        // Skip the root directory itself
-        if path == source_folder {
-            continue;
-        }
+    // Links to the same file within the payload, by (device, inode)
+    let mut link_counts: HashMap<(u64, u64), u32> = HashMap::new();
+    for key in entries
+        .iter()
+        .filter_map(|(_, metadata)| hard_link_key(metadata))
+    {
+        *link_counts.entry(key).or_default() += 1;
+    }
+    let mut linked_inodes: HashMap<(u64, u64), u32> = HashMap::new();
+
+    let mut writer = CpioWriter::with_format(CpioFormat::for_entries(entries.len() + 1));
+    writer.add(".", &[], S_IFDIR | 0o755)?;
+
+    for (entry, metadata) in &entries {
+        let path = entry.path();
 
         // Get relative path
-        let relative_path =
-            path.strip_prefix(source_folder)
-                .map_err(|e| PackageError::SourceReadError {
-                    path: path.to_path_buf(),
-                    reason: e.to_string(),
-                })?;
+        let relative_path = path
+            .strip_prefix(source_folder)
+            .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+        let name = entry_name(relative_path);
 
-        // Get metadata
-        let metadata = entry
-            .metadata()
-            .map_err(|e| PackageError::SourceReadError {
-                path: path.to_path_buf(),
-                reason: e.to_string(),
-            })?;
+        let mode = unix_mode(metadata) & 0o7777;
 
-        // Skip directories - CPIO will infer them from paths
         if metadata.is_dir() {
+            writer.add(
+                &name,
+                &[],
+                S_IFDIR | effective_mode(mode, allow_special_modes),
+            )?;
             continue;
         }
 
-        // Get mode (default to 0o644 for files)
-        #[cfg(unix)]
-        let mode = {
-            use std::os::unix::fs::PermissionsExt;
-            metadata.permissions().mode() & 0o7777
-        };
-        #[cfg(not(unix))]
-        let mode = 0o644;
+        // Archive links with their target path instead of following them
+        if metadata.file_type().is_symlink() {
+            writer.add(&name, &read_link_target(path)?, S_IFLNK | (mode & 0o777))?;
+            continue;
+        }
+
+        let mode = effective_mode(mode, allow_special_modes);
+        let key = hard_link_key(metadata);
+        let nlink = key.map_or(1, |key| link_counts[&key]);
+        if let Some(&ino) = key.and_then(|key| linked_inodes.get(&key)) {
+            writer.add_hard_link(&name, mode, ino, nlink)?;
+            continue;
+        }
 
         // Read file contents
-        let content = fs::read(path).map_err(|e| PackageError::SourceReadError {
-            path: path.to_path_buf(),
-            reason: e.to_string(),
-        })?;
+        let content = read_file(path, retry)?;
 
-        entries.push((relative_path.to_string_lossy().to_string(), content, mode));
+        let ino = writer.add_linked(&name, &content, mode, nlink)?;
+        if let Some(key) = key {
+            linked_inodes.insert(key, ino);
+        }
     }
 
-    create_cpio_payload(&entries)
+    compress_payload(&writer.finish(), compression.effective_level())
+}
+
+/// Device and inode of a regular file with more than one link.
+#[cfg(unix)]
+fn hard_link_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Payload path of `relative`, as raw bytes: `./` followed by the path
+/// with `/` separators.
+///
+/// On Unix the name is kept byte for byte, so file names that are not
+/// valid UTF-8 are installed under the same name.
+fn entry_name(relative: &Path) -> Vec<u8> {
+    let mut name = b"./".to_vec();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        name.extend_from_slice(relative.as_os_str().as_bytes());
+    }
+    #[cfg(not(unix))]
+    {
+        name.extend_from_slice(relative.to_string_lossy().replace('\\', "/").as_bytes());
+    }
+    name
 }
 
 /// Scripts found in a scripts folder.
@@ -156,38 +226,182 @@ pub fn collect_scripts(scripts_folder: &Path) -> Result<ScriptsInfo, PackageErro
     })
 }
 
+/// Install scripts of a component: a scripts folder, scripts given on
+/// their own and bundled templates.
+#[derive(Debug, Clone, Default)]
+pub struct InstallScripts {
+    /// Folder holding `preinstall` and/or `postinstall`
+    pub folder: Option<PathBuf>,
+    /// Preinstall script given on its own
+    pub preinstall: Option<ScriptSource>,
+    /// Postinstall script given on its own
+    pub postinstall: Option<ScriptSource>,
+    /// Templates run before the preinstall script
+    pub templates: Vec<ScriptTemplate>,
+}
+
+/// Values filled into script templates.
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateContext<'a> {
+    /// Package identifier, whose receipt `remove-previous` looks up
+    pub identifier: &'a str,
+    /// Application quit by `kill-app` when it names none
+    pub app_name: Option<&'a str>,
+}
+
+/// Name the package's own preinstall script is stored under when templates
+/// run first; the generated `preinstall` runs it last.
+pub const PREINSTALL_MAIN: &str = "preinstall.main";
+
+impl InstallScripts {
+    /// Scripts found in `folder`.
+    pub fn from_folder(folder: impl Into<PathBuf>) -> Self {
+        Self {
+            folder: Some(folder.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Which of the scripts will be in the archive.
+    pub fn info(&self) -> Result<ScriptsInfo, PackageError> {
+        let folder = match &self.folder {
+            Some(folder) => collect_scripts(folder)?,
+            None => ScriptsInfo {
+                has_preinstall: false,
+                has_postinstall: false,
+            },
+        };
+        Ok(ScriptsInfo {
+            has_preinstall: folder.has_preinstall
+                || self.preinstall.is_some()
+                || !self.templates.is_empty(),
+            has_postinstall: folder.has_postinstall || self.postinstall.is_some(),
+        })
+    }
+
+    /// Content of the `preinstall` or `postinstall` script given on its own
+    /// or in the folder; giving both is an error.
+    fn read(
+        &self,
+        name: &str,
+        source: Option<&ScriptSource>,
+    ) -> Result<Option<Vec<u8>>, PackageError> {
+        let in_folder = self
+            .folder
+            .as_ref()
+            .map(|folder| folder.join(name))
+            .filter(|path| path.is_file());
+        match (source, in_folder) {
+            (Some(_), Some(path)) => Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "The {} script is given twice: on its own and as {}",
+                    name,
+                    path.display()
+                ),
+            }),
+            (Some(source), None) => source.read().map(Some),
+            (None, Some(path)) => fs::read(&path)
+                .map(Some)
+                .map_err(|e| PackageError::source_read(path, e)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Quote `value` for a POSIX shell.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Shell commands of `template`.
+fn render_template(
+    template: &ScriptTemplate,
+    context: &TemplateContext<'_>,
+) -> Result<String, PackageError> {
+    match template {
+        ScriptTemplate::KillApp(app) => {
+            let app = app.as_deref().or(context.app_name).ok_or_else(|| {
+                PackageError::InvalidArgument {
+                    reason: "The kill-app template needs an application name".to_string(),
+                }
+            })?;
+            Ok(format!(
+                r#"app={}
+if /usr/bin/pgrep -xq "$app"; then
+    /usr/bin/osascript -e "tell application \"$app\" to quit" >/dev/null 2>&1 || true
+    sleep 2
+    /usr/bin/pkill -x "$app" || true
+fi
+"#,
+                shell_quote(app)
+            ))
+        }
+        ScriptTemplate::RemovePrevious => Ok(format!(
+            r#"pkg_id={}
+volume="${{3:-/}}"
+if /usr/sbin/pkgutil --volume "$volume" --pkg-info "$pkg_id" >/dev/null 2>&1; then
+    location=$(/usr/sbin/pkgutil --volume "$volume" --pkg-info "$pkg_id" | /usr/bin/sed -n 's/^location: //p')
+    root="${{volume%/}}/${{location#/}}"
+    /usr/sbin/pkgutil --volume "$volume" --only-files --files "$pkg_id" | while IFS= read -r file; do
+        /bin/rm -f "${{root%/}}/$file"
+    done
+    /usr/sbin/pkgutil --volume "$volume" --forget "$pkg_id" >/dev/null
+fi
+"#,
+            shell_quote(context.identifier)
+        )),
+    }
+}
+
 /// Create a gzip-compressed CPIO archive for scripts.
 ///
-/// Scripts are always given mode 0755 (executable).
-pub fn create_scripts_archive(scripts_folder: &Path) -> Result<Vec<u8>, PackageError> {
-    let scripts_info = collect_scripts(scripts_folder)?;
-
+/// Scripts are always given mode 0755 (executable). With templates, a
+/// generated `preinstall` runs them in order and then the package's own
+/// preinstall script, stored as [`PREINSTALL_MAIN`].
+pub fn create_scripts_archive(
+    scripts: &InstallScripts,
+    context: &TemplateContext<'_>,
+) -> Result<Vec<u8>, PackageError> {
     let mut entries: Vec<CpioEntry> = Vec::new();
 
     // Always use mode 0755 for scripts (executable)
     const SCRIPT_MODE: u32 = 0o755;
 
-    if scripts_info.has_preinstall {
-        let preinstall_path = scripts_folder.join("preinstall");
-        let content = fs::read(&preinstall_path).map_err(|e| PackageError::SourceReadError {
-            path: preinstall_path.clone(),
-            reason: e.to_string(),
-        })?;
-        entries.push(("preinstall".to_string(), content, SCRIPT_MODE));
-    }
-
-    if scripts_info.has_postinstall {
-        let postinstall_path = scripts_folder.join("postinstall");
-        let content = fs::read(&postinstall_path).map_err(|e| PackageError::SourceReadError {
-            path: postinstall_path.clone(),
-            reason: e.to_string(),
-        })?;
+    let preinstall = scripts.read("preinstall", scripts.preinstall.as_ref())?;
+    if scripts.templates.is_empty() {
+        if let Some(content) = preinstall {
+            entries.push(("preinstall".to_string(), content, SCRIPT_MODE));
+        }
+    } else {
+        let mut generated =
+            String::from("#!/bin/sh\n# Generated by iamawrapper from script templates\n");
+        for template in &scripts.templates {
+            generated.push_str(&format!("\n# {}\n", template.name()));
+            generated.push_str(&render_template(template, context)?);
+        }
+        if let Some(content) = preinstall {
+            generated.push_str(&format!(
+                "\nexec \"$(dirname \"$0\")/{}\" \"$@\"\n",
+                PREINSTALL_MAIN
+            ));
+            entries.push((PREINSTALL_MAIN.to_string(), content, SCRIPT_MODE));
+        } else {
+            generated.push_str("\nexit 0\n");
+        }
+        entries.push((
+            "preinstall".to_string(),
+            generated.into_bytes(),
+            SCRIPT_MODE,
+        ));
+    }
+
+    if let Some(content) = scripts.read("postinstall", scripts.postinstall.as_ref())? {
         entries.push(("postinstall".to_string(), content, SCRIPT_MODE));
     }
 
     if entries.is_empty() {
         return Err(PackageError::NoScriptsFound {
-            path: scripts_folder.to_path_buf(),
+            path: scripts.folder.clone().unwrap_or_default(),
         });
     }
 
@@ -201,6 +415,11 @@ mod tests {
     use std::io::Write;
     use tempfile::TempDir;
 
+    const CONTEXT: TemplateContext<'static> = TemplateContext {
+        identifier: "com.example.tool",
+        app_name: None,
+    };
+
     #[test]
     fn test_collect_files_basic() {
         let temp_dir = TempDir::new().unwrap();
@@ -234,6 +453,24 @@ mod tests {
         assert!(payload.file_count() >= 1);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_separates_kinds() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("lib")).unwrap();
+        fs::write(temp_dir.path().join("lib/tool"), vec![0u8; 5000]).unwrap();
+        std::os::unix::fs::symlink("lib/tool", temp_dir.path().join("tool")).unwrap();
+
+        let summary = collect_files(temp_dir.path()).unwrap().summary();
+        assert_eq!(
+            (summary.files, summary.directories, summary.symlinks),
+            (1, 1, 1)
+        );
+        assert_eq!(summary.file_bytes, 5000);
+        assert_eq!(summary.install_kbytes, 8);
+        assert_eq!(summary.number_of_files, 4);
+    }
+
     #[test]
     fn test_create_payload_basic() {
         let temp_dir = TempDir::new().unwrap();
@@ -242,13 +479,48 @@ mod tests {
         let mut file = File::create(&file_path).unwrap();
         file.write_all(b"hello").unwrap();
 
-        let payload = create_payload(temp_dir.path()).unwrap();
+        let payload = create_payload(
+            temp_dir.path(),
+            false,
+            &Compression::default(),
+            &RetryPolicy::none(),
+        )
+        .unwrap();
 
         // Should be gzip compressed (starts with gzip magic)
         assert_eq!(payload[0], 0x1f);
         assert_eq!(payload[1], 0x8b);
     }
 
+    #[test]
+    fn test_create_payload_stored() {
+        use crate::macos::cpio::read_payload;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("log.txt"), "line\n".repeat(10_000)).unwrap();
+
+        let deflated = create_payload(
+            temp_dir.path(),
+            false,
+            &Compression::default(),
+            &RetryPolicy::none(),
+        )
+        .unwrap();
+        let stored = create_payload(
+            temp_dir.path(),
+            false,
+            &Compression::stored(),
+            &RetryPolicy::none(),
+        )
+        .unwrap();
+        assert!(stored.len() > 50_000);
+        assert!(deflated.len() < 1_000);
+        assert_eq!(
+            read_payload(&stored).unwrap(),
+            read_payload(&deflated).unwrap()
+        );
+    }
+
     #[test]
     fn test_create_payload_multiple_files() {
         let temp_dir = TempDir::new().unwrap();
@@ -256,7 +528,13 @@ mod tests {
         fs::write(temp_dir.path().join("file1.txt"), "content1").unwrap();
         fs::write(temp_dir.path().join("file2.txt"), "content2").unwrap();
 
-        let payload = create_payload(temp_dir.path()).unwrap();
+        let payload = create_payload(
+            temp_dir.path(),
+            false,
+            &Compression::default(),
+            &RetryPolicy::none(),
+        )
+        .unwrap();
 
         // Verify it's valid gzip
         assert_eq!(payload[0], 0x1f);
@@ -274,6 +552,178 @@ mod tests {
         assert!(content.contains("file2.txt"));
     }
 
+    #[test]
+    fn test_effective_mode() {
+        assert_eq!(effective_mode(0o104755, false), 0o100755);
+        assert_eq!(effective_mode(0o104755, true), 0o104755);
+        assert_eq!(effective_mode(0o041777, false), 0o040777);
+        assert_eq!(effective_mode(0o100644, false), 0o100644);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_payload_keeps_symlinks() {
+        use crate::macos::cpio::read_payload;
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let framework = temp_dir.path().join("Acme.framework");
+        fs::create_dir_all(framework.join("Versions/A")).unwrap();
+        fs::write(framework.join("Versions/A/Acme"), "binary").unwrap();
+        symlink("A", framework.join("Versions/Current")).unwrap();
+        symlink("Versions/Current/Acme", framework.join("Acme")).unwrap();
+
+        let payload = collect_files(temp_dir.path()).unwrap();
+        let links: Vec<_> = payload.files.iter().filter(|f| f.is_symlink()).collect();
+        assert_eq!(links.len(), 2);
+        assert!(links.iter().all(|f| !f.is_file()));
+        assert_eq!(payload.files.iter().filter(|f| f.is_file()).count(), 1);
+
+        let records = read_payload(
+            &create_payload(
+                temp_dir.path(),
+                false,
+                &Compression::default(),
+                &RetryPolicy::none(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let current = records
+            .iter()
+            .find(|r| r.path == "./Acme.framework/Versions/Current")
+            .unwrap();
+        assert!(current.is_symlink());
+        assert_eq!(current.data, b"A");
+        // Root, three directories, the binary and two links
+        assert_eq!(records.len(), 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_payload_special_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let helper = temp_dir.path().join("helper");
+        fs::write(&helper, "binary").unwrap();
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o4755)).unwrap();
+
+        let read_mode = |payload: Vec<u8>| {
+            let records = crate::macos::cpio::read_payload(&payload).unwrap();
+            records
+                .iter()
+                .find(|r| r.path == "./helper")
+                .unwrap()
+                .permissions()
+        };
+
+        assert_eq!(
+            read_mode(
+                create_payload(
+                    temp_dir.path(),
+                    false,
+                    &Compression::default(),
+                    &RetryPolicy::none()
+                )
+                .unwrap()
+            ),
+            0o755
+        );
+        assert_eq!(
+            read_mode(
+                create_payload(
+                    temp_dir.path(),
+                    true,
+                    &Compression::default(),
+                    &RetryPolicy::none()
+                )
+                .unwrap()
+            ),
+            0o4755
+        );
+    }
+
+    #[test]
+    fn test_create_payload_directory_records() {
+        use crate::macos::cpio::read_payload;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("empty")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub/file.txt"), "content").unwrap();
+
+        let records = read_payload(
+            &create_payload(
+                temp_dir.path(),
+                false,
+                &Compression::default(),
+                &RetryPolicy::none(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let paths: Vec<_> = records.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths[0], ".");
+        assert!(paths.contains(&"./empty") && paths.contains(&"./sub/file.txt"));
+        // The same paths as the BOM: the root plus every walked entry
+        assert_eq!(records.len(), 4);
+        assert!(records.iter().find(|r| r.path == "./sub").unwrap().is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_payload_hard_links() {
+        use crate::macos::cpio::read_payload;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.bin"), "shared data").unwrap();
+        fs::hard_link(temp_dir.path().join("a.bin"), temp_dir.path().join("b.bin")).unwrap();
+
+        let records = read_payload(
+            &create_payload(
+                temp_dir.path(),
+                false,
+                &Compression::default(),
+                &RetryPolicy::none(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let with_data: Vec<_> = records.iter().filter(|r| !r.data.is_empty()).collect();
+        assert_eq!(with_data.len(), 1);
+        let link = records.iter().find(|r| r.hard_link.is_some()).unwrap();
+        assert_eq!(link.hard_link.as_deref(), Some(with_data[0].path.as_str()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_payload_keeps_non_utf8_names() {
+        use crate::macos::cpio::read_payload;
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let name = OsStr::from_bytes(b"r\xe9sum\xe9.txt");
+        if fs::write(temp_dir.path().join(name), "cv").is_err() {
+            // The file system only accepts UTF-8 names
+            return;
+        }
+
+        let records = read_payload(
+            &create_payload(
+                temp_dir.path(),
+                false,
+                &Compression::default(),
+                &RetryPolicy::none(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let record = records.iter().find(|r| r.data == b"cv").unwrap();
+        assert_eq!(record.raw_path, b"./r\xe9sum\xe9.txt");
+    }
+
     // T044: Unit tests for collect_scripts
     #[test]
     fn test_collect_scripts_both() {
@@ -352,7 +802,9 @@ mod tests {
 
         fs::write(temp_dir.path().join("preinstall"), "#!/bin/bash\necho test").unwrap();
 
-        let archive = create_scripts_archive(temp_dir.path()).unwrap();
+        let archive =
+            create_scripts_archive(&InstallScripts::from_folder(temp_dir.path()), &CONTEXT)
+                .unwrap();
 
         // Decompress and check mode in CPIO header
         use flate2::read::GzDecoder;
@@ -379,7 +831,8 @@ mod tests {
         // Create a file that's not preinstall or postinstall
         fs::write(temp_dir.path().join("other.sh"), "#!/bin/bash").unwrap();
 
-        let result = create_scripts_archive(temp_dir.path());
+        let result =
+            create_scripts_archive(&InstallScripts::from_folder(temp_dir.path()), &CONTEXT);
         assert!(matches!(result, Err(PackageError::NoScriptsFound { .. })));
     }
 
@@ -394,7 +847,9 @@ mod tests {
         )
         .unwrap();
 
-        let archive = create_scripts_archive(temp_dir.path()).unwrap();
+        let archive =
+            create_scripts_archive(&InstallScripts::from_folder(temp_dir.path()), &CONTEXT)
+                .unwrap();
 
         // Verify it's gzip
         assert_eq!(archive[0], 0x1f);
@@ -414,4 +869,107 @@ mod tests {
             "Should contain postinstall"
         );
     }
+
+    fn archive_files(archive: &[u8]) -> Vec<(String, String)> {
+        crate::macos::cpio::read_payload(archive)
+            .unwrap()
+            .into_iter()
+            .filter(|r| !r.data.is_empty())
+            .map(|r| (r.path, String::from_utf8(r.data).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_create_scripts_archive_inline_scripts() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("cleanup.sh");
+        fs::write(&script, "#!/bin/sh\necho post").unwrap();
+
+        let scripts = InstallScripts {
+            preinstall: Some(ScriptSource::Inline(b"#!/bin/sh\necho pre".to_vec())),
+            postinstall: Some(ScriptSource::File(script)),
+            ..InstallScripts::default()
+        };
+        let info = scripts.info().unwrap();
+        assert!(info.has_preinstall && info.has_postinstall);
+
+        let files = archive_files(&create_scripts_archive(&scripts, &CONTEXT).unwrap());
+        assert_eq!(
+            files,
+            vec![
+                ("preinstall".to_string(), "#!/bin/sh\necho pre".to_string()),
+                (
+                    "postinstall".to_string(),
+                    "#!/bin/sh\necho post".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_scripts_archive_script_given_twice() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("preinstall"), "#!/bin/sh").unwrap();
+
+        let mut scripts = InstallScripts::from_folder(temp_dir.path());
+        scripts.preinstall = Some(ScriptSource::Inline(b"#!/bin/sh".to_vec()));
+        assert!(matches!(
+            create_scripts_archive(&scripts, &CONTEXT),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_create_scripts_archive_templates() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("preinstall"), "#!/bin/sh\necho pre").unwrap();
+
+        let mut scripts = InstallScripts::from_folder(temp_dir.path());
+        scripts.templates = vec![
+            ScriptTemplate::KillApp(Some("Acme's App".to_string())),
+            ScriptTemplate::RemovePrevious,
+        ];
+        let files = archive_files(&create_scripts_archive(&scripts, &CONTEXT).unwrap());
+
+        assert_eq!(
+            files[0],
+            (
+                PREINSTALL_MAIN.to_string(),
+                "#!/bin/sh\necho pre".to_string()
+            )
+        );
+        let (name, generated) = &files[1];
+        assert_eq!(name, "preinstall");
+        assert!(generated.starts_with("#!/bin/sh\n"));
+        assert!(generated.contains("app='Acme'\\''s App'"));
+        assert!(generated.contains("pkg_id='com.example.tool'"));
+        assert!(generated.find("# kill-app") < generated.find("# remove-previous"));
+        assert!(generated.trim_end().ends_with("/preinstall.main\" \"$@\""));
+
+        // Templates alone still make a preinstall script
+        let scripts = InstallScripts {
+            templates: vec![ScriptTemplate::RemovePrevious],
+            ..InstallScripts::default()
+        };
+        assert!(scripts.info().unwrap().has_preinstall);
+        let files = archive_files(&create_scripts_archive(&scripts, &CONTEXT).unwrap());
+        assert_eq!(files.len(), 1);
+        assert!(files[0].1.ends_with("exit 0\n"));
+    }
+
+    #[test]
+    fn test_kill_app_template_needs_name() {
+        let scripts = InstallScripts {
+            templates: vec![ScriptTemplate::KillApp(None)],
+            ..InstallScripts::default()
+        };
+        assert!(create_scripts_archive(&scripts, &CONTEXT).is_err());
+
+        let context = TemplateContext {
+            app_name: Some("Acme"),
+            ..CONTEXT
+        };
+        let files = archive_files(&create_scripts_archive(&scripts, &context).unwrap());
+        assert!(files[0].1.contains("app='Acme'"));
+    }
 }
@@ -0,0 +1,269 @@
+//! Rewrite the Distribution and resources of an existing package.
+//!
+//! Vendor packages can be branded (title, license, extra resources)
+//! without touching their payloads: the XAR is read, the Distribution
+//! and `Resources/` entries are swapped or added, and the archive is
+//! written back. Component packages (a bare `PackageInfo` + `Payload`)
+//! are wrapped into a product archive first, since only a Distribution
+//! can carry a title or license.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::macos::{MacosRepackRequest, MacosRepackResult};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Check;
+
+use super::RESOURCES_DIR;
+use super::xar::{SignatureMode, XarArchive};
+use super::xml::{generate_distribution, parse_packageinfo, update_distribution};
+
+/// Directory a component package is moved into when it is wrapped.
+const COMPONENT_DIR: &str = "base.pkg";
+
+/// Hook that signs a finished package.
+///
+/// Repacking always invalidates an existing signature; a signer passed to
+/// [`repack_with_signer`] receives the unsigned package bytes and returns
+/// the signed ones.
+pub trait PackageSigner {
+    /// Sign a complete package.
+    fn sign(&self, package: Vec<u8>) -> PackageResult<Vec<u8>>;
+}
+
+/// Repack a package without re-signing it.
+pub fn repack(request: &MacosRepackRequest) -> PackageResult<MacosRepackResult> {
+    repack_with_signer(request, None)
+}
+
+/// Repack a package, signing the result with `signer` when given.
+pub fn repack_with_signer(
+    request: &MacosRepackRequest,
+    signer: Option<&dyn PackageSigner>,
+) -> PackageResult<MacosRepackResult> {
+    let start = Instant::now();
+
+    request.validate()?;
+
+    let input = read_file(&request.input)?;
+    let mut archive = XarArchive::read(&input)?;
+    let was_signed = archive.is_signed();
+
+    let license_name = request.license.as_deref().map(file_name).transpose()?;
+    if request.title.is_some() || license_name.is_some() {
+        let distribution = distribution_xml(&mut archive)?;
+        let updated = update_distribution(
+            &distribution,
+            request.title.as_deref(),
+            license_name.as_deref(),
+        )?;
+        archive.set_file("Distribution", updated.into_bytes())?;
+    }
+
+    let mut resources = Vec::new();
+    for path in request.license.iter().chain(&request.resources) {
+        let archive_path = format!("{}/{}", RESOURCES_DIR, file_name(path)?);
+        archive.set_file(&archive_path, read_file(path)?)?;
+        resources.push(archive_path);
+    }
+
+    let mode = match (signer.is_some(), request.keep_signature) {
+        (false, true) => SignatureMode::Preserve,
+        _ => SignatureMode::Strip,
+    };
+    let repacked = archive.repack(mode)?;
+
+    let (data, signed, signature_warning) = match signer {
+        Some(signer) => (signer.sign(repacked.data)?, true, None),
+        None => (
+            repacked.data,
+            repacked.signatures_kept > 0,
+            repacked.warning,
+        ),
+    };
+
+    if let Some(warning) = &signature_warning {
+        request
+            .strictness
+            .enforce(Check::Signature, || warning.clone())?;
+    }
+
+    if let Some(parent) = request
+        .output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+    }
+    fs::write(&request.output, &data)
+        .map_err(|e| PackageError::output_write(request.output.clone(), e))?;
+
+    Ok(MacosRepackResult {
+        output_path: request.output.clone(),
+        package_size: ByteSize(data.len() as u64),
+        resources,
+        was_signed,
+        signed,
+        signature_warning,
+        repack_time: start.elapsed(),
+    })
+}
+
+/// The archive's Distribution, wrapping a component package if needed.
+fn distribution_xml(archive: &mut XarArchive) -> PackageResult<String> {
+    if let Some(xml) = archive.file("Distribution") {
+        return utf8(xml, "Distribution");
+    }
+
+    let package_info = archive
+        .file("PackageInfo")
+        .ok_or_else(|| PackageError::XarError {
+            reason: "Package has neither a Distribution nor a PackageInfo".to_string(),
+        })?;
+    let info = parse_packageinfo(&utf8(package_info, "PackageInfo")?)?;
+
+    archive.nest_root(COMPONENT_DIR)?;
+    generate_distribution(
+        &info.identifier,
+        &info.identifier,
+        &info.version,
+        info.install_kbytes,
+    )
+}
+
+fn utf8(data: &[u8], name: &str) -> PackageResult<String> {
+    String::from_utf8(data.to_vec()).map_err(|_| PackageError::XmlError {
+        reason: format!("{} is not valid UTF-8", name),
+        source: None,
+    })
+}
+
+fn read_file(path: &Path) -> PackageResult<Vec<u8>> {
+    fs::read(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))
+}
+
+fn file_name(path: &Path) -> PackageResult<String> {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| PackageError::InvalidArgument {
+            reason: format!("'{}' has no file name", path.display()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::package;
+    use crate::macos::xar::{XarBuilder, XarSignature};
+    use crate::models::macos::MacosPkgRequest;
+    use crate::models::package::Verbosity;
+    use tempfile::TempDir;
+
+    fn build_pkg(temp: &TempDir) -> std::path::PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("app.txt"), "hello").unwrap();
+
+        let request = MacosPkgRequest::new(source, "com.example.app", "1.0.0", temp.path())
+            .with_verbosity(Verbosity::Silent);
+        package(request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_repack_title_and_license() {
+        let temp = TempDir::new().unwrap();
+        let input = build_pkg(&temp);
+        let license = temp.path().join("EULA.rtf");
+        fs::write(&license, "{\\rtf1 terms}").unwrap();
+        let output = temp.path().join("out/branded.pkg");
+
+        let request = MacosRepackRequest::new(&input, &output)
+            .with_title("Example App")
+            .with_license(&license);
+        let result = repack(&request).unwrap();
+
+        assert_eq!(result.resources, vec!["Resources/EULA.rtf"]);
+        assert!(!result.signed);
+
+        let archive = XarArchive::read(&fs::read(&output).unwrap()).unwrap();
+        let distribution =
+            String::from_utf8(archive.file("Distribution").unwrap().to_vec()).unwrap();
+        assert!(distribution.contains("<title>Example App</title>"));
+        assert!(distribution.contains(r#"<license file="EULA.rtf"/>"#));
+        assert_eq!(
+            archive.file("Resources/EULA.rtf"),
+            Some(&b"{\\rtf1 terms}"[..])
+        );
+        assert!(crate::macos::validate::validate_pkg(&output).is_ok());
+    }
+
+    #[test]
+    fn test_repack_component_package() {
+        let temp = TempDir::new().unwrap();
+        let product = XarArchive::read(&fs::read(build_pkg(&temp)).unwrap()).unwrap();
+
+        // A component package is base.pkg's contents at the archive root
+        let mut builder = XarBuilder::new();
+        for name in ["PackageInfo", "Bom", "Payload"] {
+            let data = product.file(&format!("base.pkg/{}", name)).unwrap();
+            builder.add_file(name, data.to_vec()).unwrap();
+        }
+        let mut component = Vec::new();
+        builder.finish(&mut component).unwrap();
+        let input = temp.path().join("component.pkg");
+        fs::write(&input, component).unwrap();
+
+        let output = temp.path().join("product.pkg");
+        repack(&MacosRepackRequest::new(&input, &output).with_title("Wrapped")).unwrap();
+
+        let archive = XarArchive::read(&fs::read(&output).unwrap()).unwrap();
+        assert!(archive.file("base.pkg/Payload").is_some());
+        assert!(archive.file("PackageInfo").is_none());
+        assert!(crate::macos::validate::validate_pkg(&output).is_ok());
+    }
+
+    #[test]
+    fn test_repack_signer_hook() {
+        struct Marker;
+        impl PackageSigner for Marker {
+            fn sign(&self, package: Vec<u8>) -> PackageResult<Vec<u8>> {
+                let mut archive = XarArchive::read(&package)?;
+                assert!(!archive.is_signed());
+                archive.set_file("Resources/signed", Vec::new())?;
+                Ok(archive.repack(SignatureMode::Strip)?.data)
+            }
+        }
+
+        let temp = TempDir::new().unwrap();
+        let input = temp.path().join("signed.pkg");
+        let mut builder = XarBuilder::new();
+        builder
+            .add_file("Distribution", b"<installer-gui-script/>".to_vec())
+            .unwrap();
+        builder.set_signatures(vec![XarSignature {
+            element: "signature".to_string(),
+            style: "RSA".to_string(),
+            certificates: Vec::new(),
+            data: vec![1; 16],
+        }]);
+        let mut data = Vec::new();
+        builder.finish(&mut data).unwrap();
+        fs::write(&input, data).unwrap();
+
+        let output = temp.path().join("out.pkg");
+        let request = MacosRepackRequest::new(&input, &output).with_title("T");
+
+        let unsigned = repack(&request).unwrap();
+        assert!(unsigned.was_signed && !unsigned.signed);
+        assert!(unsigned.signature_warning.is_some());
+
+        let signed = repack_with_signer(&request, Some(&Marker)).unwrap();
+        assert!(signed.signed);
+        assert!(signed.signature_warning.is_none());
+        let archive = XarArchive::read(&fs::read(&output).unwrap()).unwrap();
+        assert!(archive.file("Resources/signed").is_some());
+    }
+}
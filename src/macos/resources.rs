@@ -0,0 +1,197 @@
+//! Files the Installer shows, stored under `Resources` in a package.
+//!
+//! The Installer looks up the file a Distribution element names (e.g.
+//! `<welcome file="Welcome.html"/>`) in `Resources/<language>.lproj/` for
+//! the user's language first, then in `Resources/`. A resources folder is
+//! copied as it is, so localized variants keep working; files set on their
+//! own are added at the top of `Resources` and take precedence.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::macos::{DistributionOptions, RESOURCE_ELEMENTS};
+
+/// A file stored in `Resources`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceFile {
+    /// Path relative to `Resources`, with `/` separators
+    pub path: String,
+    /// File to read the content from
+    pub source: PathBuf,
+}
+
+/// The resource files of a package and the Distribution elements showing
+/// them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallerResources {
+    /// Files stored in `Resources`, in path order
+    pub files: Vec<ResourceFile>,
+    /// Distribution elements with the file name each refers to, in
+    /// [`RESOURCE_ELEMENTS`] order
+    pub elements: Vec<(&'static str, String)>,
+}
+
+/// Collect the resource files of `options`: the resources folder, if any,
+/// then the welcome, license, readme, conclusion and background files.
+///
+/// An element without a file of its own shows the file of the folder named
+/// after it, e.g. `License.rtf` or `en.lproj/license.txt` for `license`.
+pub fn collect_resources(options: &DistributionOptions) -> PackageResult<InstallerResources> {
+    let mut files = BTreeMap::new();
+    let mut candidates: BTreeMap<&'static str, BTreeSet<String>> = BTreeMap::new();
+
+    if let Some(dir) = &options.resources_dir {
+        for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+            let entry = entry.map_err(|e| PackageError::source_read(dir.clone(), e))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !entry.file_type().is_file() || name.starts_with('.') {
+                continue;
+            }
+            let relative = relative_path(dir, entry.path());
+            if let Some(element) = element_for(&relative) {
+                candidates.entry(element).or_default().insert(name);
+            }
+            files.insert(relative, entry.path().to_path_buf());
+        }
+    }
+
+    let explicit: BTreeMap<&'static str, String> = options
+        .resources()
+        .into_iter()
+        .map(|(element, path)| {
+            let name = file_name(path);
+            files.insert(name.clone(), path.clone());
+            (element, name)
+        })
+        .collect();
+
+    let mut elements = Vec::new();
+    for element in RESOURCE_ELEMENTS.iter().copied() {
+        if let Some(name) = explicit.get(element) {
+            elements.push((element, name.clone()));
+            continue;
+        }
+        let Some(names) = candidates.get(element) else {
+            continue;
+        };
+        if names.len() > 1 {
+            let names: Vec<_> = names.iter().map(String::as_str).collect();
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "The resources folder has several {} files ({}); keep one name",
+                    element,
+                    names.join(", ")
+                ),
+            });
+        }
+        elements.extend(names.iter().next().map(|name| (element, name.clone())));
+    }
+
+    Ok(InstallerResources {
+        files: files
+            .into_iter()
+            .map(|(path, source)| ResourceFile { path, source })
+            .collect(),
+        elements,
+    })
+}
+
+/// The element a file in the resources folder is shown by: one named after
+/// it, at the top of the folder or in a `.lproj` folder.
+fn element_for(relative: &str) -> Option<&'static str> {
+    let name = match relative.split_once('/') {
+        None => relative,
+        Some((folder, name)) if folder.ends_with(".lproj") && !name.contains('/') => name,
+        Some(_) => return None,
+    };
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    RESOURCE_ELEMENTS
+        .iter()
+        .copied()
+        .find(|element| stem.eq_ignore_ascii_case(element))
+}
+
+/// `path` relative to `dir`, with `/` separators.
+fn relative_path(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    relative
+        .iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// File name of `path`, lossily as UTF-8.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_resources_from_folder() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("resources");
+        fs::create_dir_all(dir.join("de.lproj")).unwrap();
+        fs::create_dir_all(dir.join("en.lproj")).unwrap();
+        fs::write(dir.join("welcome.html"), "<p>Hi</p>").unwrap();
+        fs::write(dir.join("Background.png"), "png").unwrap();
+        fs::write(dir.join(".DS_Store"), "").unwrap();
+        fs::write(dir.join("de.lproj/License.rtf"), "Lizenz").unwrap();
+        fs::write(dir.join("en.lproj/License.rtf"), "License").unwrap();
+        let own_license = temp.path().join("EULA.txt");
+        fs::write(&own_license, "EULA").unwrap();
+
+        let options = DistributionOptions::default().with_resources_dir(&dir);
+        let resources = collect_resources(&options).unwrap();
+        let paths: Vec<_> = resources.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "Background.png",
+                "de.lproj/License.rtf",
+                "en.lproj/License.rtf",
+                "welcome.html"
+            ]
+        );
+        assert_eq!(
+            resources.elements,
+            [
+                ("welcome", "welcome.html".to_string()),
+                ("license", "License.rtf".to_string()),
+                ("background", "Background.png".to_string()),
+            ]
+        );
+
+        // A file set on its own replaces the folder's
+        let resources = collect_resources(&options.with_license(&own_license)).unwrap();
+        assert!(
+            resources
+                .elements
+                .contains(&("license", "EULA.txt".to_string()))
+        );
+        assert!(resources.files.iter().any(|f| f.source == own_license));
+    }
+
+    #[test]
+    fn test_collect_resources_rejects_ambiguous_names() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("readme.txt"), "a").unwrap();
+        fs::write(temp.path().join("ReadMe.html"), "b").unwrap();
+
+        let options = DistributionOptions::default().with_resources_dir(temp.path());
+        assert!(matches!(
+            collect_resources(&options),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,357 @@
+//! Add or replace the install scripts of an existing package.
+//!
+//! A vendor package often needs one more step, such as a postinstall that
+//! provisions a license. The XAR is read, the Scripts archive of one
+//! component is merged with a folder of scripts (a file replaces the entry
+//! with the same path and every other entry is kept), its PackageInfo is
+//! pointed at the resulting `preinstall`/`postinstall`, and the archive is
+//! written back. Payloads, BOMs and the Distribution are left as they are.
+//! Both product archives and bare component packages are accepted.
+
+use std::fs;
+use std::time::Instant;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::macos::{MacosRewrapRequest, MacosRewrapResult};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Check;
+
+use super::merge_scripts;
+use super::repack::PackageSigner;
+use super::xar::{EntryType, SignatureMode, XarArchive};
+use super::xml::{parse_packageinfo, update_packageinfo_scripts};
+
+/// Rewrap a package without signing it.
+pub fn rewrap(request: &MacosRewrapRequest) -> PackageResult<MacosRewrapResult> {
+    rewrap_with_signer(request, None)
+}
+
+/// Rewrap a package, signing the result with `signer` when given.
+///
+/// Changing the scripts always invalidates an existing signature.
+pub fn rewrap_with_signer(
+    request: &MacosRewrapRequest,
+    signer: Option<&dyn PackageSigner>,
+) -> PackageResult<MacosRewrapResult> {
+    let start = Instant::now();
+
+    request.validate()?;
+
+    let input = fs::read(&request.input)
+        .map_err(|e| PackageError::source_read(request.input.clone(), e))?;
+    let mut archive = XarArchive::read(&input)?;
+    let was_signed = archive.is_signed();
+
+    let (dir, identifier) = find_component(&archive, request.component.as_deref())?;
+    let scripts_path = entry_path(&dir, "Scripts");
+    let packageinfo_path = entry_path(&dir, "PackageInfo");
+
+    let merged = merge_scripts(archive.file(&scripts_path), &request.scripts_folder)?;
+    let packageinfo = utf8(
+        archive.file(&packageinfo_path).unwrap_or_default(),
+        &packageinfo_path,
+    )?;
+    let packageinfo =
+        update_packageinfo_scripts(&packageinfo, merged.has_preinstall, merged.has_postinstall)?;
+    archive.set_file(&scripts_path, merged.archive)?;
+    archive.set_file(&packageinfo_path, packageinfo.into_bytes())?;
+
+    let repacked = archive.repack(SignatureMode::Strip)?;
+    let (data, signed, signature_warning) = match signer {
+        Some(signer) => (signer.sign(repacked.data)?, true, None),
+        None => (repacked.data, false, repacked.warning),
+    };
+
+    if let Some(warning) = &signature_warning {
+        request
+            .strictness
+            .enforce(Check::Signature, || warning.clone())?;
+    }
+
+    let output_path = request.output_path();
+    if let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+    }
+    fs::write(&output_path, &data)
+        .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+
+    Ok(MacosRewrapResult {
+        output_path,
+        package_size: ByteSize(data.len() as u64),
+        component: identifier,
+        added: merged.added,
+        replaced: merged.replaced,
+        was_signed,
+        signed,
+        signature_warning,
+        rewrap_time: start.elapsed(),
+    })
+}
+
+/// Directory (empty for a component package) and identifier of the
+/// component to change: the one named `identifier`, or the only one.
+fn find_component(
+    archive: &XarArchive,
+    identifier: Option<&str>,
+) -> PackageResult<(String, String)> {
+    let mut components = Vec::new();
+    for entry in archive.entries() {
+        if entry.entry_type != EntryType::File || entry.name != "PackageInfo" {
+            continue;
+        }
+        let dir = entry
+            .path
+            .strip_suffix("PackageInfo")
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string();
+        let info = parse_packageinfo(&utf8(&entry.data, &entry.path)?)?;
+        components.push((dir, info.identifier));
+    }
+
+    let names = || {
+        components
+            .iter()
+            .map(|(_, id)| id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match identifier {
+        Some(identifier) => components
+            .iter()
+            .find(|(_, id)| id == identifier)
+            .cloned()
+            .ok_or_else(|| PackageError::InvalidArgument {
+                reason: format!(
+                    "Package has no component '{}' (components: {})",
+                    identifier,
+                    names()
+                ),
+            }),
+        None => match components.len() {
+            0 => Err(PackageError::XarError {
+                reason: "Package has no component (no PackageInfo found)".to_string(),
+            }),
+            1 => Ok(components.remove(0)),
+            _ => Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Package has {} components ({}); choose one with --component",
+                    components.len(),
+                    names()
+                ),
+            }),
+        },
+    }
+}
+
+fn entry_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn utf8(data: &[u8], name: &str) -> PackageResult<String> {
+    String::from_utf8(data.to_vec()).map_err(|_| PackageError::XmlError {
+        reason: format!("{} is not valid UTF-8", name),
+        source: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::cpio::read_payload;
+    use crate::macos::package;
+    use crate::macos::xar::XarBuilder;
+    use crate::models::macos::{MacosDistributionRequest, MacosPkgRequest};
+    use crate::models::package::Verbosity;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    fn pkg_request(temp: &TempDir, identifier: &str) -> MacosPkgRequest {
+        let source = temp.path().join(identifier);
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("app.txt"), identifier).unwrap();
+        MacosPkgRequest::new(source, identifier, "1.0.0", temp.path())
+            .with_verbosity(Verbosity::Silent)
+    }
+
+    fn scripts(temp: &TempDir, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let folder = temp.path().join(name);
+        for (path, content) in files {
+            let path = folder.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        folder
+    }
+
+    fn script_names(archive: &XarArchive, path: &str) -> Vec<(String, u32)> {
+        read_payload(archive.file(path).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.path.clone(), r.permissions()))
+            .collect()
+    }
+
+    fn read(path: &Path) -> XarArchive {
+        XarArchive::read(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_rewrap_adds_and_replaces_scripts() {
+        let temp = TempDir::new().unwrap();
+        let vendor_scripts = scripts(&temp, "vendor", &[("preinstall", "#!/bin/sh\nold\n")]);
+        let input =
+            package(pkg_request(&temp, "com.vendor.app").with_scripts_folder(vendor_scripts))
+                .unwrap()
+                .output_path;
+
+        let folder = scripts(
+            &temp,
+            "ours",
+            &[
+                ("preinstall", "#!/bin/sh\nnew\n"),
+                ("postinstall", "#!/bin/sh\nlicense\n"),
+                ("lib/helper.sh", "echo helper\n"),
+            ],
+        );
+        let request = MacosRewrapRequest::new(&input, &folder);
+        let result = rewrap(&request).unwrap();
+
+        assert_eq!(
+            result.output_path,
+            temp.path().join("com.vendor.app-1.0.0-rewrapped.pkg")
+        );
+        assert_eq!(result.component, "com.vendor.app");
+        assert_eq!(result.replaced, vec!["preinstall"]);
+        assert_eq!(result.added, vec!["lib/helper.sh", "postinstall"]);
+        assert!(!result.signed);
+
+        let archive = read(&result.output_path);
+        let entries = script_names(&archive, "base.pkg/Scripts");
+        assert!(entries.contains(&("postinstall".to_string(), 0o755)));
+        assert!(entries.contains(&("lib/helper.sh".to_string(), 0o755)));
+        let preinstall = read_payload(archive.file("base.pkg/Scripts").unwrap())
+            .unwrap()
+            .into_iter()
+            .find(|r| r.path == "preinstall")
+            .unwrap();
+        assert_eq!(preinstall.data, b"#!/bin/sh\nnew\n");
+
+        let packageinfo =
+            String::from_utf8(archive.file("base.pkg/PackageInfo").unwrap().to_vec()).unwrap();
+        assert!(packageinfo.contains(r#"<preinstall file="./preinstall"/>"#));
+        assert!(packageinfo.contains(r#"<postinstall file="./postinstall"/>"#));
+        assert_eq!(
+            archive.file("base.pkg/Payload"),
+            read(&input).file("base.pkg/Payload")
+        );
+        assert!(crate::macos::validate::validate_pkg(&result.output_path).is_ok());
+    }
+
+    #[test]
+    fn test_rewrap_component_package() {
+        let temp = TempDir::new().unwrap();
+        let product = read(
+            &package(pkg_request(&temp, "com.vendor.tool"))
+                .unwrap()
+                .output_path,
+        );
+
+        // A component package is base.pkg's contents at the archive root,
+        // here with pkgbuild-style "./" paths in its Scripts archive
+        let mut builder = XarBuilder::new();
+        for name in ["PackageInfo", "Bom", "Payload"] {
+            let data = product.file(&format!("base.pkg/{}", name)).unwrap();
+            builder.add_file(name, data.to_vec()).unwrap();
+        }
+        let existing = crate::macos::cpio::create_payload(&[
+            (
+                ".".to_string(),
+                Vec::new(),
+                crate::macos::cpio::S_IFDIR | 0o755,
+            ),
+            ("./preinstall".to_string(), b"#!/bin/sh\n".to_vec(), 0o755),
+        ])
+        .unwrap();
+        builder.add_file("Scripts", existing).unwrap();
+        let mut component = Vec::new();
+        builder.finish(&mut component).unwrap();
+        let input = temp.path().join("component.pkg");
+        fs::write(&input, component).unwrap();
+
+        let folder = scripts(&temp, "ours", &[("postinstall", "#!/bin/sh\n")]);
+        let output = temp.path().join("out/wrapped.pkg");
+        let result =
+            rewrap(&MacosRewrapRequest::new(&input, &folder).with_output(&output)).unwrap();
+        assert_eq!(result.added, vec!["postinstall"]);
+
+        let archive = read(&output);
+        let names: Vec<_> = script_names(&archive, "Scripts")
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(names, vec![".", "./preinstall", "./postinstall"]);
+        let packageinfo = String::from_utf8(archive.file("PackageInfo").unwrap().to_vec()).unwrap();
+        assert!(packageinfo.contains(r#"<preinstall file="./preinstall"/>"#));
+        assert!(packageinfo.contains(r#"<postinstall file="./postinstall"/>"#));
+    }
+
+    #[test]
+    fn test_rewrap_chooses_component() {
+        let temp = TempDir::new().unwrap();
+        let request = MacosDistributionRequest::new("com.vendor.suite", "2.0", temp.path())
+            .with_component(pkg_request(&temp, "com.vendor.app"))
+            .with_component(pkg_request(&temp, "com.vendor.helper"));
+        let input = crate::macos::distribution::package_distribution(&request)
+            .unwrap()
+            .output_path;
+        let folder = scripts(&temp, "ours", &[("postinstall", "#!/bin/sh\n")]);
+
+        let ambiguous = MacosRewrapRequest::new(&input, &folder);
+        assert!(matches!(
+            rewrap(&ambiguous),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            rewrap(&ambiguous.clone().with_component("com.vendor.other")),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        let result = rewrap(&ambiguous.with_component("com.vendor.helper")).unwrap();
+        assert_eq!(result.component, "com.vendor.helper");
+        let archive = read(&result.output_path);
+        assert!(archive.file("com.vendor.helper.pkg/Scripts").is_some());
+        assert!(archive.file("com.vendor.app.pkg/Scripts").is_none());
+    }
+
+    #[test]
+    fn test_rewrap_request_checks() {
+        let temp = TempDir::new().unwrap();
+        let input = package(pkg_request(&temp, "com.vendor.app"))
+            .unwrap()
+            .output_path;
+
+        let empty = temp.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        assert!(matches!(
+            rewrap(&MacosRewrapRequest::new(&input, &empty)),
+            Err(PackageError::NoScriptsFound { .. })
+        ));
+        assert!(matches!(
+            rewrap(&MacosRewrapRequest::new(
+                &input,
+                temp.path().join("missing")
+            )),
+            Err(PackageError::ScriptsFolderNotFound { .. })
+        ));
+        assert!(matches!(
+            rewrap(&MacosRewrapRequest::new(&input, &empty).with_output(&input)),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+//! Signing flat packages with a Developer ID Installer identity.
+//!
+//! Produces the same signature as `productsign`: an RSA PKCS#1 v1.5
+//! signature over the SHA-1 TOC checksum, stored in the heap right after
+//! the checksum, with the certificate chain in the TOC's `<KeyInfo>`.
+//! Gatekeeper only accepts it if the chain leads to Apple's root, so the
+//! identity has to be a real Developer ID Installer certificate.
+
+use std::fs;
+use std::path::Path;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha1::Sha1;
+use x509_cert::Certificate;
+use x509_cert::der::referenced::OwnedToRef;
+use x509_cert::der::{Decode, Encode};
+
+use crate::models::PackageError;
+use crate::models::error::PackageResult;
+use crate::models::macos::MacosSigning;
+
+use super::repack::PackageSigner;
+use super::xar::{TocSigner, XarArchive, XarSignature};
+
+/// A private key with its certificate chain, ready to sign archives.
+pub struct Signer {
+    key: RsaPrivateKey,
+    /// DER certificates, leaf first
+    certificates: Vec<Vec<u8>>,
+    subject: String,
+}
+
+impl Signer {
+    /// Load the identity described by `signing`.
+    pub fn load(signing: &MacosSigning) -> Result<Self, PackageError> {
+        let fail = |path: &Path, reason: String| PackageError::SigningError {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let read = |path: &Path| fs::read(path).map_err(|e| fail(path, e.to_string()));
+
+        let identity = read(&signing.identity)?;
+        if !identity.starts_with(b"-----BEGIN") {
+            if signing.certificate.is_some() {
+                return Err(fail(
+                    &signing.identity,
+                    "a PKCS#12 identity already contains its certificates".to_string(),
+                ));
+            }
+            let password = signing.password.as_ref().map_or("", |p| p.as_str());
+            return Self::from_pkcs12(&identity, password)
+                .map_err(|reason| fail(&signing.identity, reason));
+        }
+
+        let certificate_path = signing.certificate.as_deref().ok_or_else(|| {
+            fail(
+                &signing.identity,
+                "a PEM key needs its certificate chain (--sign-cert)".to_string(),
+            )
+        })?;
+        let key = String::from_utf8_lossy(&identity);
+        let chain = read(certificate_path)?;
+        Self::from_pem(&key, &chain).map_err(|reason| fail(&signing.identity, reason))
+    }
+
+    /// Build a signer from a PKCS#12 file.
+    pub fn from_pkcs12(data: &[u8], password: &str) -> Result<Self, String> {
+        let keystore = p12_keystore::KeyStore::from_pkcs12(data, password)
+            .map_err(|e| format!("cannot read PKCS#12 file: {}", e))?;
+        let (_, chain) = keystore
+            .private_key_chain()
+            .ok_or_else(|| "PKCS#12 file has no private key".to_string())?;
+
+        let key = RsaPrivateKey::from_pkcs8_der(chain.key())
+            .map_err(|e| format!("private key is not an RSA key: {}", e))?;
+        let certificates = chain
+            .chain()
+            .iter()
+            .map(|c| Certificate::from_der(c.as_der()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid certificate: {}", e))?;
+        Self::new(key, certificates)
+    }
+
+    /// Build a signer from a PEM private key (PKCS#8 or PKCS#1) and a PEM
+    /// certificate chain, leaf first.
+    pub fn from_pem(key: &str, chain: &[u8]) -> Result<Self, String> {
+        let key = RsaPrivateKey::from_pkcs8_pem(key)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(key))
+            .map_err(|e| format!("cannot read RSA private key: {}", e))?;
+        // load_pem_chain does not cope with input holding no PEM block
+        if !chain.windows(10).any(|w| w == b"-----BEGIN") {
+            return Err("no certificate found".to_string());
+        }
+        let certificates = Certificate::load_pem_chain(chain)
+            .map_err(|e| format!("cannot read certificate chain: {}", e))?;
+        Self::new(key, certificates)
+    }
+
+    fn new(key: RsaPrivateKey, certificates: Vec<Certificate>) -> Result<Self, String> {
+        let leaf = certificates
+            .first()
+            .ok_or_else(|| "no certificate found".to_string())?;
+
+        let leaf_key =
+            RsaPublicKey::try_from(leaf.tbs_certificate.subject_public_key_info.owned_to_ref())
+                .map_err(|e| format!("certificate does not hold an RSA key: {}", e))?;
+        if leaf_key != RsaPublicKey::from(&key) {
+            return Err("private key does not match the certificate".to_string());
+        }
+
+        Ok(Self {
+            subject: leaf.tbs_certificate.subject.to_string(),
+            certificates: certificates
+                .iter()
+                .map(|c| c.to_der())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("cannot encode certificate: {}", e))?,
+            key,
+        })
+    }
+
+    /// Subject of the leaf certificate.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+}
+
+impl TocSigner for Signer {
+    fn signature_template(&self) -> XarSignature {
+        XarSignature {
+            element: "signature".to_string(),
+            style: "RSA".to_string(),
+            certificates: self.certificates.iter().map(|c| BASE64.encode(c)).collect(),
+            data: vec![0; self.key.size()],
+        }
+    }
+
+    fn sign(&self, toc_checksum: &[u8]) -> Result<Vec<u8>, PackageError> {
+        self.key
+            .sign(Pkcs1v15Sign::new::<Sha1>(), toc_checksum)
+            .map_err(|e| PackageError::XarError {
+                reason: format!("Signing failed: {}", e),
+            })
+    }
+}
+
+impl PackageSigner for Signer {
+    fn sign(&self, package: Vec<u8>) -> PackageResult<Vec<u8>> {
+        XarArchive::read(&package)?.repack_signed(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::xar::{SignatureMode, XarBuilder};
+    use sha1::Digest;
+
+    const KEY: &str = include_str!("../../tests/fixtures/signing/key.pem");
+    const CERT: &[u8] = include_bytes!("../../tests/fixtures/signing/cert.pem");
+    const IDENTITY: &[u8] = include_bytes!("../../tests/fixtures/signing/identity.p12");
+
+    fn signed_archive(signer: &Signer) -> Vec<u8> {
+        let mut builder = XarBuilder::new();
+        builder
+            .add_file("Distribution", b"<installer-gui-script/>".to_vec())
+            .unwrap();
+        let mut output = Vec::new();
+        builder.finish_signed(&mut output, signer).unwrap();
+        output
+    }
+
+    fn verify(signer: &Signer, data: &[u8]) {
+        let archive = XarArchive::read(data).unwrap();
+        let signature = &archive.signatures()[0];
+        assert_eq!(signature.style, "RSA");
+
+        // The signed data is the checksum of the compressed TOC
+        let toc_length = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+        let checksum = Sha1::digest(&data[28..28 + toc_length]);
+        RsaPublicKey::from(&signer.key)
+            .verify(Pkcs1v15Sign::new::<Sha1>(), &checksum, &signature.data)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_signature_verifies_against_toc_checksum() {
+        let signer = Signer::from_pem(KEY, CERT).unwrap();
+        assert!(signer.subject().contains("Developer ID Installer"));
+
+        let data = signed_archive(&signer);
+        verify(&signer, &data);
+        let archive = XarArchive::read(&data).unwrap();
+        assert_eq!(archive.signatures()[0].certificates.len(), 1);
+        assert_eq!(
+            archive.file("Distribution"),
+            Some(&b"<installer-gui-script/>"[..])
+        );
+    }
+
+    #[test]
+    fn test_package_signer_replaces_signature() {
+        let signer = Signer::from_pem(KEY, CERT).unwrap();
+        let mut archive = XarArchive::read(&signed_archive(&signer)).unwrap();
+        archive
+            .set_file("Resources/readme.txt", b"hi".to_vec())
+            .unwrap();
+        let unsigned = archive.repack(SignatureMode::Strip).unwrap().data;
+
+        let resigned = PackageSigner::sign(&signer, unsigned).unwrap();
+        verify(&signer, &resigned);
+        let archive = XarArchive::read(&resigned).unwrap();
+        assert_eq!(archive.signatures().len(), 1);
+        assert_eq!(archive.file("Resources/readme.txt"), Some(&b"hi"[..]));
+    }
+
+    #[test]
+    fn test_pkcs12_identity() {
+        let signer = Signer::from_pkcs12(IDENTITY, "secret").unwrap();
+        assert_eq!(
+            signer.subject(),
+            Signer::from_pem(KEY, CERT).unwrap().subject()
+        );
+
+        assert!(Signer::from_pkcs12(IDENTITY, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_key() {
+        let other = RsaPrivateKey::new(&mut rand::thread_rng(), 512).unwrap();
+        let certificates = Certificate::load_pem_chain(CERT).unwrap();
+        let err = Signer::new(other, certificates).err().unwrap();
+        assert!(err.contains("does not match"));
+
+        let err = Signer::from_pem(KEY, b"").err().unwrap();
+        assert!(err.contains("no certificate"));
+    }
+}
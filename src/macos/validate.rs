@@ -0,0 +1,598 @@
+//! Validation of `.pkg` files.
+//!
+//! [`validate_pkg`] runs after a package is built and stops at the first
+//! problem: it re-reads the XAR container (header, TOC decompression and
+//! checksum, the archived checksum of every heap entry) and checks that
+//! the entries the macOS Installer requires are present.
+//!
+//! [`validate_pkg_report`] backs `macos validate`. It runs the same checks
+//! and then decodes what they leave out: the Payload and Scripts archives,
+//! each Bom against its payload, and the identifiers and versions of the
+//! PackageInfo documents against the Distribution. Every check is reported
+//! on its own, like `intune verify`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Instant;
+
+use flate2::read::ZlibDecoder;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use sha1::{Digest, Sha1};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::validation::{CheckOutcome, PkgValidationReport, ValidationReport, VerifyCheck};
+
+use super::bom::read_bom;
+use super::cpio::{CpioRecord, read_payload};
+use super::xar::{EntryType, XarArchive};
+use super::xml::{parse_distribution_refs, parse_packageinfo};
+
+/// Entry names every product package must contain.
+const REQUIRED_ENTRIES: &[&str] = &["Distribution", "PackageInfo"];
+
+/// Entry names a package must contain unless it is payload-free: one with
+/// Scripts but neither a Payload nor a Bom.
+const PAYLOAD_ENTRIES: &[&str] = &["Payload", "Bom"];
+
+/// Names of the checks of [`validate_pkg_report`], in the order they run.
+pub const PKG_CHECKS: [&str; 8] = [
+    "structure",
+    "toc checksum",
+    "entry checksums",
+    "required entries",
+    "payload",
+    "scripts",
+    "bom",
+    "package info",
+];
+
+/// A heap entry described by the TOC.
+#[derive(Debug, Default)]
+struct HeapEntry {
+    name: String,
+    offset: u64,
+    length: u64,
+    checksum: String,
+}
+
+/// The parts of a XAR file its header locates.
+struct XarLayout<'a> {
+    /// Compressed TOC, as the checksum covers it
+    compressed_toc: &'a [u8],
+    toc: String,
+    /// Heap, starting with the TOC checksum
+    heap: &'a [u8],
+}
+
+/// Validate a `.pkg` file, failing at the first problem.
+pub fn validate_pkg(path: &Path) -> PackageResult<ValidationReport> {
+    let start = Instant::now();
+    let fail = |reason: String| PackageError::ValidationFailed {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mut checks = Vec::new();
+
+    let data = fs::read(path).map_err(|e| fail(format!("Cannot open package: {}", e)))?;
+    let layout = read_layout(&data).map_err(fail)?;
+    checks.push("structure");
+
+    check_toc_checksum(&layout).map_err(fail)?;
+    checks.push("toc checksum");
+
+    let entries = check_entry_checksums(&layout).map_err(fail)?;
+    checks.push("entry checksums");
+
+    check_required_entries(&entries).map_err(fail)?;
+    checks.push("required entries");
+
+    Ok(ValidationReport {
+        path: path.to_path_buf(),
+        checks,
+        entry_count: entries.len(),
+        duration: start.elapsed(),
+    })
+}
+
+/// Run every check of `macos validate` against a `.pkg` file.
+///
+/// Only an unreadable file is an error; failed checks are reported in the
+/// result, and checks that depend on a failed one are reported as skipped.
+pub fn validate_pkg_report(path: &Path) -> PackageResult<PkgValidationReport> {
+    let start = Instant::now();
+    let data = fs::read(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    let mut checks = Checks::default();
+
+    if let Some(layout) = checks.run("structure", || read_layout(&data)) {
+        checks.run("toc checksum", || check_toc_checksum(&layout));
+        if let Some(entries) = checks.run("entry checksums", || check_entry_checksums(&layout)) {
+            checks.entry_count = entries.len();
+            checks.run("required entries", || check_required_entries(&entries));
+        }
+        match XarArchive::read(&data) {
+            Ok(archive) => check_components(&mut checks, &archive),
+            Err(e) => checks.push("payload", CheckOutcome::Fail(e.to_string())),
+        }
+    }
+
+    Ok(checks.finish(path, start))
+}
+
+/// Check results and what the checks found out about the package.
+#[derive(Default)]
+struct Checks {
+    checks: Vec<VerifyCheck>,
+    components: Vec<(String, String)>,
+    entry_count: usize,
+    payload_entries: usize,
+}
+
+impl Checks {
+    fn run<T>(
+        &mut self,
+        name: &'static str,
+        check: impl FnOnce() -> Result<T, String>,
+    ) -> Option<T> {
+        match check() {
+            Ok(value) => {
+                self.push(name, CheckOutcome::Pass);
+                Some(value)
+            }
+            Err(reason) => {
+                self.push(name, CheckOutcome::Fail(reason));
+                None
+            }
+        }
+    }
+
+    fn push(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.checks.push(VerifyCheck { name, outcome });
+    }
+
+    /// Report every check that did not run as skipped.
+    fn finish(mut self, path: &Path, start: Instant) -> PkgValidationReport {
+        for name in PKG_CHECKS {
+            if !self.checks.iter().any(|c| c.name == name) {
+                self.push(name, CheckOutcome::Skipped);
+            }
+        }
+        self.checks
+            .sort_by_key(|c| PKG_CHECKS.iter().position(|name| *name == c.name));
+
+        PkgValidationReport {
+            path: path.to_path_buf(),
+            checks: self.checks,
+            components: self.components,
+            entry_count: self.entry_count,
+            payload_entries: self.payload_entries,
+            duration: start.elapsed(),
+        }
+    }
+}
+
+/// Locate the TOC and heap and decompress the TOC.
+fn read_layout(data: &[u8]) -> Result<XarLayout<'_>, String> {
+    if data.len() < 28 || &data[0..4] != b"xar!" {
+        return Err("Missing XAR header".to_string());
+    }
+
+    let header_size = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let toc_compressed = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+    let toc_uncompressed = u64::from_be_bytes(data[16..24].try_into().unwrap());
+    let heap_start = header_size.saturating_add(toc_compressed);
+    if heap_start.saturating_add(20) > data.len() {
+        return Err("TOC extends past end of file".to_string());
+    }
+
+    let compressed_toc = &data[header_size..heap_start];
+    let mut toc = String::new();
+    ZlibDecoder::new(compressed_toc)
+        .read_to_string(&mut toc)
+        .map_err(|e| format!("Cannot decompress TOC: {}", e))?;
+    if toc.len() as u64 != toc_uncompressed {
+        return Err("TOC length does not match header".to_string());
+    }
+
+    Ok(XarLayout {
+        compressed_toc,
+        toc,
+        heap: &data[heap_start..],
+    })
+}
+
+fn check_toc_checksum(layout: &XarLayout<'_>) -> Result<(), String> {
+    if Sha1::digest(layout.compressed_toc).as_slice() != &layout.heap[..20] {
+        return Err("TOC checksum mismatch".to_string());
+    }
+    Ok(())
+}
+
+/// Check the archived checksum of every heap entry.
+fn check_entry_checksums(layout: &XarLayout<'_>) -> Result<Vec<HeapEntry>, String> {
+    let heap = layout.heap;
+    let entries = parse_heap_entries(&layout.toc)?;
+    for entry in &entries {
+        let end = entry.offset.saturating_add(entry.length);
+        if end > heap.len() as u64 {
+            return Err(format!("Entry '{}' extends past end of heap", entry.name));
+        }
+        let digest = hex::encode(Sha1::digest(&heap[entry.offset as usize..end as usize]));
+        if !digest.eq_ignore_ascii_case(&entry.checksum) {
+            return Err(format!("Checksum mismatch for '{}'", entry.name));
+        }
+    }
+    Ok(entries)
+}
+
+fn check_required_entries(entries: &[HeapEntry]) -> Result<(), String> {
+    let has_entry = |name: &str| entries.iter().any(|e| e.name == name);
+    let payload_free = has_entry("Scripts") && !PAYLOAD_ENTRIES.iter().any(|n| has_entry(n));
+    let payload_entries = if payload_free {
+        &[][..]
+    } else {
+        PAYLOAD_ENTRIES
+    };
+    for required in REQUIRED_ENTRIES.iter().chain(payload_entries) {
+        if !has_entry(required) {
+            return Err(format!("Missing required entry '{}'", required));
+        }
+    }
+    Ok(())
+}
+
+/// The entries of one component package: the root of a component package,
+/// or a `<name>.pkg` folder of a product package.
+#[derive(Default)]
+struct Component<'a> {
+    package_info: Option<&'a [u8]>,
+    payload: Option<&'a [u8]>,
+    scripts: Option<&'a [u8]>,
+    bom: Option<&'a [u8]>,
+}
+
+/// Decode the archives and metadata of every component.
+fn check_components(checks: &mut Checks, archive: &XarArchive) {
+    let mut components: BTreeMap<&str, Component<'_>> = BTreeMap::new();
+    for entry in archive.entries() {
+        if entry.entry_type != EntryType::File {
+            continue;
+        }
+        let folder = &entry.path[..entry.path.len() - entry.name.len()];
+        let slot = match entry.name.as_str() {
+            "PackageInfo" => &mut components.entry(folder).or_default().package_info,
+            "Payload" => &mut components.entry(folder).or_default().payload,
+            "Scripts" => &mut components.entry(folder).or_default().scripts,
+            "Bom" => &mut components.entry(folder).or_default().bom,
+            _ => continue,
+        };
+        *slot = Some(entry.data.as_slice());
+    }
+
+    let payloads = checks.run("payload", || {
+        let mut payloads = BTreeMap::new();
+        for (folder, component) in &components {
+            if let Some(data) = component.payload {
+                let records = read_payload(data)
+                    .map_err(|e| format!("Cannot read '{}Payload': {}", folder, e))?;
+                payloads.insert(*folder, records);
+            }
+        }
+        Ok(payloads)
+    });
+    if let Some(payloads) = &payloads {
+        checks.payload_entries = payloads.values().map(Vec::len).sum();
+    }
+
+    checks.run("scripts", || {
+        for (folder, component) in &components {
+            if let Some(data) = component.scripts {
+                read_payload(data)
+                    .map_err(|e| format!("Cannot read '{}Scripts': {}", folder, e))?;
+            }
+        }
+        Ok(())
+    });
+
+    if let Some(payloads) = &payloads {
+        checks.run("bom", || {
+            for (folder, component) in &components {
+                match (component.bom, payloads.get(folder)) {
+                    (Some(bom), Some(records)) => check_bom(folder, bom, records)?,
+                    (Some(_), None) => {
+                        return Err(format!("'{}Bom' has no Payload to describe", folder));
+                    }
+                    (None, Some(_)) => return Err(format!("'{}Payload' has no Bom", folder)),
+                    (None, None) => {}
+                }
+            }
+            Ok(())
+        });
+    }
+
+    if let Some(found) = checks.run("package info", || check_package_info(archive, &components)) {
+        checks.components = found;
+    }
+}
+
+/// Check that a Bom is readable and lists exactly the paths of its payload.
+fn check_bom(folder: &str, bom: &[u8], payload: &[CpioRecord]) -> Result<(), String> {
+    let listed = read_bom(bom).map_err(|e| format!("Cannot read '{}Bom': {}", folder, e))?;
+    let listed: BTreeSet<&str> = listed.iter().map(|r| r.path.as_str()).collect();
+    let archived: BTreeSet<&str> = payload
+        .iter()
+        .map(|r| match r.path.strip_prefix("./") {
+            Some(path) => path,
+            None => r.path.as_str(),
+        })
+        .collect();
+
+    if let Some(path) = archived.difference(&listed).next() {
+        return Err(format!(
+            "'{}' is in '{}Payload' but not in its Bom",
+            path, folder
+        ));
+    }
+    if let Some(path) = listed.difference(&archived).next() {
+        return Err(format!(
+            "'{}' is in '{}Bom' but not in its Payload",
+            path, folder
+        ));
+    }
+    Ok(())
+}
+
+/// Read every PackageInfo and check its identifier and version against the
+/// Distribution, when there is one.
+fn check_package_info(
+    archive: &XarArchive,
+    components: &BTreeMap<&str, Component<'_>>,
+) -> Result<Vec<(String, String)>, String> {
+    let refs = match archive.file("Distribution") {
+        Some(data) => {
+            let xml = std::str::from_utf8(data)
+                .map_err(|e| format!("Distribution is not UTF-8: {}", e))?;
+            Some(parse_distribution_refs(xml).map_err(|e| format!("Distribution: {}", e))?)
+        }
+        None => None,
+    };
+
+    let mut found = Vec::new();
+    for (folder, component) in components {
+        let Some(data) = component.package_info else {
+            continue;
+        };
+        let info = std::str::from_utf8(data)
+            .map_err(|e| e.to_string())
+            .and_then(|xml| parse_packageinfo(xml).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Cannot read '{}PackageInfo': {}", folder, e))?;
+        if let Some(refs) = &refs {
+            match refs.iter().find(|(id, _)| *id == info.identifier) {
+                Some((_, version)) if *version != info.version => {
+                    return Err(format!(
+                        "'{}' is version {} in its PackageInfo but {} in the Distribution",
+                        info.identifier, info.version, version
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(format!(
+                        "The Distribution does not list '{}'",
+                        info.identifier
+                    ));
+                }
+            }
+        }
+        found.push((info.identifier, info.version));
+    }
+
+    if let Some(refs) = &refs {
+        if let Some((id, _)) = refs
+            .iter()
+            .find(|(id, _)| !found.iter().any(|(f, _)| f == id))
+        {
+            return Err(format!(
+                "The Distribution lists '{}', which has no PackageInfo",
+                id
+            ));
+        }
+    }
+    Ok(found)
+}
+
+/// Collect the data-bearing `<file>` entries of a TOC.
+fn parse_heap_entries(toc: &str) -> Result<Vec<HeapEntry>, String> {
+    let mut reader = Reader::from_str(toc);
+    let mut entries = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    let mut current: Option<HeapEntry> = None;
+    let mut element = String::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                element = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                match element.as_str() {
+                    "file" => names.push(String::new()),
+                    "data" => current = Some(HeapEntry::default()),
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(t)) => {
+                let text = t.unescape().map_err(|e| e.to_string())?.into_owned();
+                match (element.as_str(), current.as_mut()) {
+                    ("name", _) => {
+                        if let Some(name) = names.last_mut() {
+                            *name = text;
+                        }
+                    }
+                    ("offset", Some(entry)) => entry.offset = parse_number(&text)?,
+                    ("length", Some(entry)) => entry.length = parse_number(&text)?,
+                    ("archived-checksum", Some(entry)) => entry.checksum = text,
+                    _ => {}
+                }
+            }
+            Ok(Event::End(e)) => {
+                match e.name().as_ref() {
+                    b"data" => {
+                        if let Some(mut entry) = current.take() {
+                            entry.name = names.last().cloned().unwrap_or_default();
+                            entries.push(entry);
+                        }
+                    }
+                    b"file" => {
+                        names.pop();
+                    }
+                    _ => {}
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid TOC XML: {}", e)),
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("Invalid number '{}' in TOC", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macos::package;
+    use crate::macos::xar::SignatureMode;
+    use crate::models::macos::MacosPkgRequest;
+    use crate::models::package::Verbosity;
+    use tempfile::TempDir;
+
+    fn build_pkg(temp: &TempDir) -> std::path::PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("app.txt"), "hello").unwrap();
+
+        let request = MacosPkgRequest::new(
+            source,
+            "com.example.app".to_string(),
+            "1.0.0".to_string(),
+            temp.path().join("out"),
+        )
+        .with_verbosity(Verbosity::Silent);
+        package(request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_validate_pkg() {
+        let temp = TempDir::new().unwrap();
+        let path = build_pkg(&temp);
+
+        let report = validate_pkg(&path).unwrap();
+        assert_eq!(
+            report.checks,
+            vec![
+                "structure",
+                "toc checksum",
+                "entry checksums",
+                "required entries"
+            ]
+        );
+        assert!(report.entry_count >= REQUIRED_ENTRIES.len());
+    }
+
+    #[test]
+    fn test_validate_pkg_payload_free() {
+        let temp = TempDir::new().unwrap();
+        let scripts = temp.path().join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        fs::write(scripts.join("postinstall"), "#!/bin/sh\nexit 0\n").unwrap();
+
+        let request = MacosPkgRequest::new(
+            std::path::PathBuf::new(),
+            "com.example.defaults".to_string(),
+            "1.0.0".to_string(),
+            temp.path().join("out"),
+        )
+        .with_no_payload(true)
+        .with_scripts_folder(scripts)
+        .with_verbosity(Verbosity::Silent);
+        let path = package(request).unwrap().output_path;
+
+        assert!(validate_pkg(&path).is_ok());
+        assert!(validate_pkg_report(&path).unwrap().passed());
+    }
+
+    #[test]
+    fn test_validate_pkg_report() {
+        let temp = TempDir::new().unwrap();
+        let path = build_pkg(&temp);
+
+        let report = validate_pkg_report(&path).unwrap();
+        assert!(report.passed(), "{:?}", report.first_failure());
+        let names: Vec<&str> = report.checks.iter().map(|c| c.name).collect();
+        assert_eq!(names, PKG_CHECKS);
+        assert_eq!(
+            report.components,
+            vec![("com.example.app".to_string(), "1.0.0".to_string())]
+        );
+        assert!(report.payload_entries >= 2);
+        assert!(report.to_json().contains("\"passed\": true"));
+    }
+
+    #[test]
+    fn test_validate_pkg_report_finds_inconsistencies() {
+        let temp = TempDir::new().unwrap();
+        let path = build_pkg(&temp);
+        let rewrite = |entry: &str, change: &dyn Fn(Vec<u8>) -> Vec<u8>| {
+            let mut archive = XarArchive::read(&fs::read(&path).unwrap()).unwrap();
+            let data = change(archive.file(entry).unwrap().to_vec());
+            archive.replace_file(entry, data).unwrap();
+            let output = temp.path().join("changed.pkg");
+            let repacked = archive.repack(SignatureMode::Strip);
+            fs::write(&output, repacked.unwrap().data).unwrap();
+            validate_pkg_report(&output).unwrap()
+        };
+
+        let report = rewrite("base.pkg/PackageInfo", &|data| {
+            String::from_utf8(data)
+                .unwrap()
+                .replace("version=\"1.0.0\"", "version=\"1.0.1\"")
+                .into_bytes()
+        });
+        assert_eq!(report.failures(), 1);
+        let (name, reason) = report.first_failure().unwrap();
+        assert_eq!(name, "package info");
+        assert!(reason.contains("1.0.1"), "{}", reason);
+
+        let report = rewrite("base.pkg/Payload", &|_| b"not an archive".to_vec());
+        assert_eq!(report.first_failure().unwrap().0, "payload");
+        let bom = report.checks.iter().find(|c| c.name == "bom").unwrap();
+        assert_eq!(bom.outcome, CheckOutcome::Skipped);
+
+        fs::write(&path, "not a package").unwrap();
+        let report = validate_pkg_report(&path).unwrap();
+        assert_eq!(report.failures(), PKG_CHECKS.len());
+        assert_eq!(report.first_failure().unwrap().0, "structure");
+    }
+
+    #[test]
+    fn test_validate_pkg_corrupted_heap() {
+        let temp = TempDir::new().unwrap();
+        let path = build_pkg(&temp);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(
+            validate_pkg(&path),
+            Err(PackageError::ValidationFailed { .. })
+        ));
+    }
+}
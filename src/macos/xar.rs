@@ -1,18 +1,51 @@
-//! XAR archive format writer for macOS packages.
+//! XAR archive format reader and writer for macOS packages.
 //!
 //! XAR (eXtensible ARchive) is the container format for .pkg files.
 //! Structure: 28-byte header + zlib-compressed XML TOC + heap (file data)
+//!
+//! Signed archives (productsign) keep their signature blocks in the heap
+//! directly after the TOC checksum. The signature covers the TOC checksum,
+//! so any change to the archive invalidates it; repacking either carries
+//! the block over for re-signing or strips it (see [`SignatureMode`]).
+//!
+//! The module does not depend on the rest of the macOS support and is
+//! available on its own as `iamawrapper::xar` with the `xar` feature, e.g.
+//! to write `.xip` files or other generic archives:
+//!
+//! ```
+//! use iamawrapper::xar::{XarArchive, XarBuilder, XarEncoding, XarMetadata};
+//!
+//! let mut builder = XarBuilder::new();
+//! builder.add_directory("docs")?;
+//! builder.add_file_with(
+//!     "docs/notes.txt",
+//!     b"Hello".to_vec(),
+//!     XarEncoding::Gzip,
+//!     XarMetadata::default().with_mode(0o644).with_mtime(1_700_000_000),
+//! )?;
+//! let mut data = Vec::new();
+//! builder.finish(&mut data)?;
+//!
+//! let archive = XarArchive::read(&data)?;
+//! assert_eq!(archive.file("docs/notes.txt"), Some(&b"Hello"[..]));
+//! # Ok::<(), iamawrapper::PackageError>(())
+//! ```
 
+use std::borrow::Cow;
 use std::fmt::Display;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use flate2::Compression;
+use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
-use quick_xml::Writer;
+use quick_xml::events::attributes::Attribute;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
 use sha1::{Digest, Sha1};
 
 use crate::models::PackageError;
+use crate::models::artifact::{ARTIFACT_ELEMENT, ARTIFACT_NAMESPACE, ArtifactMetadata};
+use crate::models::time::{iso8601, parse_iso8601};
 
 /// Convert any displayable error into a PackageError::XarError.
 fn xar_err<E: Display>(e: E) -> PackageError {
@@ -84,6 +117,182 @@ pub enum EntryType {
     Directory,
 }
 
+/// How a file's data is stored in the heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum XarEncoding {
+    /// Stored as is (`application/octet-stream`)
+    #[default]
+    None,
+    /// zlib-compressed (`application/x-gzip`, which xar uses for zlib
+    /// streams)
+    Gzip,
+    /// bzip2-compressed (`application/x-bzip2`)
+    #[cfg(feature = "xar-bzip2")]
+    Bzip2,
+}
+
+/// Encoding style of bzip2-compressed entries.
+const BZIP2_STYLE: &str = "application/x-bzip2";
+
+impl XarEncoding {
+    /// The `style` attribute of the TOC's `<encoding>` element.
+    pub fn style(self) -> &'static str {
+        match self {
+            XarEncoding::None => "application/octet-stream",
+            XarEncoding::Gzip => "application/x-gzip",
+            #[cfg(feature = "xar-bzip2")]
+            XarEncoding::Bzip2 => BZIP2_STYLE,
+        }
+    }
+
+    /// The encoding with TOC style `style`; a missing style means none.
+    fn from_style(style: &str, path: &str) -> Result<Self, PackageError> {
+        match style {
+            "" | "application/octet-stream" => Ok(XarEncoding::None),
+            "application/x-gzip" => Ok(XarEncoding::Gzip),
+            #[cfg(feature = "xar-bzip2")]
+            BZIP2_STYLE => Ok(XarEncoding::Bzip2),
+            #[cfg(not(feature = "xar-bzip2"))]
+            BZIP2_STYLE => Err(xar_err(format!(
+                "'{}' is bzip2-encoded, which needs the 'xar-bzip2' feature",
+                path
+            ))),
+            other => Err(xar_err(format!(
+                "Unsupported encoding '{}' for '{}'",
+                other, path
+            ))),
+        }
+    }
+
+    /// `data` as stored in the heap.
+    fn encode(self, data: &[u8]) -> Result<Cow<'_, [u8]>, PackageError> {
+        match self {
+            XarEncoding::None => Ok(Cow::Borrowed(data)),
+            XarEncoding::Gzip => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data).map_err(xar_err)?;
+                Ok(Cow::Owned(encoder.finish().map_err(xar_err)?))
+            }
+            #[cfg(feature = "xar-bzip2")]
+            XarEncoding::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data).map_err(xar_err)?;
+                Ok(Cow::Owned(encoder.finish().map_err(xar_err)?))
+            }
+        }
+    }
+
+    /// Undo the encoding of a heap entry.
+    fn decode(self, archived: &[u8], path: &str) -> Result<Vec<u8>, PackageError> {
+        let mut data = Vec::new();
+        let result = match self {
+            XarEncoding::None => return Ok(archived.to_vec()),
+            XarEncoding::Gzip => ZlibDecoder::new(archived).read_to_end(&mut data),
+            #[cfg(feature = "xar-bzip2")]
+            XarEncoding::Bzip2 => bzip2::read::BzDecoder::new(archived).read_to_end(&mut data),
+        };
+        result.map_err(|e| xar_err(format!("Cannot decompress '{}': {}", path, e)))?;
+        Ok(data)
+    }
+}
+
+/// Permissions, ownership and modification time recorded for an entry.
+///
+/// Fields left unset are not written to the TOC; extractors then use their
+/// own defaults (usually the extracting user and the current time).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XarMetadata {
+    /// Unix permission bits, e.g. `0o644`
+    pub mode: Option<u32>,
+    /// Owner user ID
+    pub uid: Option<u32>,
+    /// Owner group ID
+    pub gid: Option<u32>,
+    /// Owner user name
+    pub user: Option<String>,
+    /// Owner group name
+    pub group: Option<String>,
+    /// Modification time in seconds since the Unix epoch
+    pub mtime: Option<u64>,
+}
+
+impl XarMetadata {
+    /// Set the Unix permission bits.
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the owner's user and group IDs.
+    pub fn with_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Set the owner's user and group names.
+    pub fn with_owner_names(mut self, user: impl Into<String>, group: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Set the modification time (seconds since the Unix epoch).
+    pub fn with_mtime(mut self, mtime: u64) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// TOC elements for the fields that are set, in the order xar writes
+    /// them.
+    fn elements(&self) -> Vec<(&'static str, String)> {
+        let mut elements = Vec::new();
+        if let Some(mode) = self.mode {
+            elements.push(("mode", format!("{:04o}", mode)));
+        }
+        if let Some(uid) = self.uid {
+            elements.push(("uid", uid.to_string()));
+        }
+        if let Some(user) = &self.user {
+            elements.push(("user", user.clone()));
+        }
+        if let Some(gid) = self.gid {
+            elements.push(("gid", gid.to_string()));
+        }
+        if let Some(group) = &self.group {
+            elements.push(("group", group.clone()));
+        }
+        if let Some(mtime) = self.mtime {
+            elements.push(("mtime", iso8601(mtime)));
+        }
+        elements
+    }
+
+    /// Record the TOC element `name` of a `<file>`; other elements are
+    /// ignored.
+    fn set(&mut self, name: &str, text: &str) -> Result<(), PackageError> {
+        match name {
+            "mode" => {
+                let mode = u32::from_str_radix(text, 8)
+                    .map_err(|_| xar_err(format!("Invalid mode '{}' in TOC", text)))?;
+                self.mode = Some(mode);
+            }
+            "uid" => self.uid = Some(parse_number(text)? as u32),
+            "gid" => self.gid = Some(parse_number(text)? as u32),
+            "user" => self.user = Some(text.to_string()),
+            "group" => self.group = Some(text.to_string()),
+            "mtime" => {
+                let mtime = parse_iso8601(text)
+                    .ok_or_else(|| xar_err(format!("Invalid mtime '{}' in TOC", text)))?;
+                self.mtime = Some(mtime);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 /// An entry (file or directory) in the XAR archive.
 #[derive(Debug, Clone)]
 pub struct XarEntry {
@@ -103,12 +312,44 @@ pub struct XarEntry {
     pub id: u64,
     /// Parent entry ID (0 for root-level entries)
     pub parent_id: Option<u64>,
+    /// How the data is stored in the heap
+    pub encoding: XarEncoding,
+    /// Permissions, ownership and modification time
+    pub metadata: XarMetadata,
+}
+
+/// XML digital signature namespace used by `<KeyInfo>`.
+const XMLDSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+
+/// A signature block from the TOC together with its heap bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XarSignature {
+    /// TOC element name: `signature` (RSA) or `x-signature` (CMS)
+    pub element: String,
+    /// Signature style attribute, e.g. `RSA` or `CMS`
+    pub style: String,
+    /// Base64 DER certificates, leaf first
+    pub certificates: Vec<String>,
+    /// Raw signature bytes from the heap
+    pub data: Vec<u8>,
+}
+
+/// Produces the signature over an archive's TOC checksum.
+pub trait TocSigner {
+    /// The signature block to write, with `data` zero-filled to the exact
+    /// length [`TocSigner::sign`] returns.
+    fn signature_template(&self) -> XarSignature;
+
+    /// Sign the SHA-1 TOC checksum.
+    fn sign(&self, toc_checksum: &[u8]) -> Result<Vec<u8>, PackageError>;
 }
 
 /// Builder for XAR archives.
 #[derive(Debug)]
 pub struct XarBuilder {
     entries: Vec<XarEntry>,
+    signatures: Vec<XarSignature>,
+    artifact: Option<ArtifactMetadata>,
     next_id: u64,
 }
 
@@ -117,46 +358,150 @@ impl XarBuilder {
     pub fn new() -> Self {
         Self {
             entries: Vec::new(),
+            signatures: Vec::new(),
+            artifact: None,
             next_id: 1,
         }
     }
 
-    /// Add a file to the archive.
+    /// Write these signature blocks into the TOC and heap.
+    pub fn set_signatures(&mut self, signatures: Vec<XarSignature>) -> &mut Self {
+        self.signatures = signatures;
+        self
+    }
+
+    /// Write this artifact record into the TOC.
+    pub fn set_artifact(&mut self, artifact: ArtifactMetadata) -> &mut Self {
+        self.artifact = Some(artifact);
+        self
+    }
+
+    /// Heap bytes before the first file: TOC checksum plus signatures.
+    fn heap_prefix_len(&self) -> u64 {
+        Self::SHA1_SIZE
+            + self
+                .signatures
+                .iter()
+                .map(|s| s.data.len() as u64)
+                .sum::<u64>()
+    }
+
+    /// Add a file to the archive, stored as is.
     pub fn add_file(&mut self, path: &str, data: Vec<u8>) -> Result<(), PackageError> {
-        let name = path.rsplit('/').next().unwrap_or(path).to_string();
-        let parent_id = self.find_parent_id(path);
+        self.add_file_with(path, data, XarEncoding::None, XarMetadata::default())
+    }
 
-        self.entries.push(XarEntry {
-            name,
-            path: path.to_string(),
-            entry_type: EntryType::File,
-            data,
-            offset: 0,
-            checksum: String::new(),
-            id: self.next_id,
-            parent_id,
-        });
-        self.next_id += 1;
+    /// Add a file stored with `encoding` and recorded with `metadata`.
+    pub fn add_file_with(
+        &mut self,
+        path: &str,
+        data: Vec<u8>,
+        encoding: XarEncoding,
+        metadata: XarMetadata,
+    ) -> Result<(), PackageError> {
+        self.push(path, EntryType::File, data, encoding, metadata);
         Ok(())
     }
 
+    /// Add a file with the content of `reader`, returning its size.
+    ///
+    /// The TOC in front of the heap records every entry's size and
+    /// checksums, so the content is held in memory until the archive is
+    /// written.
+    pub fn add_file_from_reader<R: Read>(
+        &mut self,
+        path: &str,
+        mut reader: R,
+        encoding: XarEncoding,
+        metadata: XarMetadata,
+    ) -> Result<u64, PackageError> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| xar_err(format!("Cannot read '{}': {}", path, e)))?;
+        let size = data.len() as u64;
+        self.add_file_with(path, data, encoding, metadata)?;
+        Ok(size)
+    }
+
+    /// Add a file, and any of its parent directories not added yet.
+    pub fn add_nested_file(&mut self, path: &str, data: Vec<u8>) -> Result<(), PackageError> {
+        let mut prefix = String::new();
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let Some((name, dirs)) = components.split_last() else {
+            return Err(xar_err("Empty entry path"));
+        };
+
+        for dir in dirs {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(dir);
+
+            match self.entries.iter().find(|e| e.path == prefix) {
+                Some(entry) if entry.entry_type == EntryType::Directory => {}
+                Some(_) => {
+                    return Err(xar_err(format!(
+                        "Cannot add '{}': '{}' is a file",
+                        path, prefix
+                    )));
+                }
+                None => self.add_directory(&prefix)?,
+            }
+        }
+
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        prefix.push_str(name);
+        self.add_file(&prefix, data)
+    }
+
     /// Add a directory to the archive.
     pub fn add_directory(&mut self, path: &str) -> Result<(), PackageError> {
+        self.add_directory_with(path, XarMetadata::default())
+    }
+
+    /// Add a directory recorded with `metadata`.
+    pub fn add_directory_with(
+        &mut self,
+        path: &str,
+        metadata: XarMetadata,
+    ) -> Result<(), PackageError> {
+        self.push(
+            path,
+            EntryType::Directory,
+            Vec::new(),
+            XarEncoding::None,
+            metadata,
+        );
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        path: &str,
+        entry_type: EntryType,
+        data: Vec<u8>,
+        encoding: XarEncoding,
+        metadata: XarMetadata,
+    ) {
         let name = path.rsplit('/').next().unwrap_or(path).to_string();
         let parent_id = self.find_parent_id(path);
 
         self.entries.push(XarEntry {
             name,
             path: path.to_string(),
-            entry_type: EntryType::Directory,
-            data: Vec::new(),
+            entry_type,
+            data,
             offset: 0,
             checksum: String::new(),
             id: self.next_id,
             parent_id,
+            encoding,
+            metadata,
         });
         self.next_id += 1;
-        Ok(())
     }
 
     /// Find parent directory ID for a path.
@@ -176,6 +521,11 @@ impl XarBuilder {
 
     /// Generate the TOC XML for the archive.
     pub fn generate_toc_xml(&self) -> Result<String, PackageError> {
+        self.toc_xml(&encode_entries(&self.entries)?)
+    }
+
+    /// The TOC XML for entries stored as `archived`.
+    fn toc_xml(&self, archived: &[Cow<'_, [u8]>]) -> Result<String, PackageError> {
         let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
 
         writer
@@ -189,7 +539,9 @@ impl XarBuilder {
             .map_err(xar_err)?;
 
         self.write_toc_checksum(&mut writer)?;
-        self.write_toc_entries(&mut writer, None, Self::SHA1_SIZE)?;
+        self.write_signatures(&mut writer)?;
+        self.write_artifact(&mut writer)?;
+        self.write_toc_entries(&mut writer, archived, None, self.heap_prefix_len())?;
 
         writer
             .write_event(Event::End(BytesEnd::new("toc")))
@@ -205,12 +557,13 @@ impl XarBuilder {
     fn write_toc_entries<W: Write>(
         &self,
         writer: &mut Writer<W>,
+        archived: &[Cow<'_, [u8]>],
         parent_id: Option<u64>,
         heap_offset: u64,
     ) -> Result<u64, PackageError> {
         let mut current_offset = heap_offset;
 
-        for entry in &self.entries {
+        for (entry, stored) in self.entries.iter().zip(archived) {
             if entry.parent_id != parent_id {
                 continue;
             }
@@ -228,6 +581,9 @@ impl XarBuilder {
                 EntryType::Directory => "directory",
             };
             self.write_simple_element(writer, "type", type_str)?;
+            for (tag, value) in entry.metadata.elements() {
+                self.write_simple_element(writer, tag, &value)?;
+            }
 
             if entry.entry_type == EntryType::File {
                 writer
@@ -236,14 +592,21 @@ impl XarBuilder {
 
                 self.write_simple_element(writer, "offset", &current_offset.to_string())?;
                 self.write_simple_element(writer, "size", &entry.data.len().to_string())?;
-                self.write_simple_element(writer, "length", &entry.data.len().to_string())?;
-
-                let checksum = Self::compute_sha1(&entry.data);
-                self.write_checksum_element(writer, "extracted-checksum", &checksum)?;
-                self.write_checksum_element(writer, "archived-checksum", &checksum)?;
+                self.write_simple_element(writer, "length", &stored.len().to_string())?;
+
+                self.write_checksum_element(
+                    writer,
+                    "extracted-checksum",
+                    &Self::compute_sha1(&entry.data),
+                )?;
+                self.write_checksum_element(
+                    writer,
+                    "archived-checksum",
+                    &Self::compute_sha1(stored),
+                )?;
 
                 let mut encoding = BytesStart::new("encoding");
-                encoding.push_attribute(("style", "application/octet-stream"));
+                encoding.push_attribute(("style", entry.encoding.style()));
                 writer
                     .write_event(Event::Empty(encoding))
                     .map_err(xar_err)?;
@@ -252,11 +615,12 @@ impl XarBuilder {
                     .write_event(Event::End(BytesEnd::new("data")))
                     .map_err(xar_err)?;
 
-                current_offset += entry.data.len() as u64;
+                current_offset += stored.len() as u64;
             }
 
             if entry.entry_type == EntryType::Directory {
-                current_offset = self.write_toc_entries(writer, Some(entry.id), current_offset)?;
+                current_offset =
+                    self.write_toc_entries(writer, archived, Some(entry.id), current_offset)?;
             }
 
             writer
@@ -304,6 +668,63 @@ impl XarBuilder {
         Ok(())
     }
 
+    /// Write signature elements, placed in the heap after the TOC checksum.
+    fn write_signatures<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), PackageError> {
+        let mut offset = Self::SHA1_SIZE;
+
+        for signature in &self.signatures {
+            let mut start = BytesStart::new(signature.element.as_str());
+            start.push_attribute(("style", signature.style.as_str()));
+            writer.write_event(Event::Start(start)).map_err(xar_err)?;
+
+            self.write_simple_element(writer, "offset", &offset.to_string())?;
+            self.write_simple_element(writer, "size", &signature.data.len().to_string())?;
+
+            let mut key_info = BytesStart::new("KeyInfo");
+            key_info.push_attribute(("xmlns", XMLDSIG_NS));
+            writer
+                .write_event(Event::Start(key_info))
+                .map_err(xar_err)?;
+            writer
+                .write_event(Event::Start(BytesStart::new("X509Data")))
+                .map_err(xar_err)?;
+            for certificate in &signature.certificates {
+                self.write_simple_element(writer, "X509Certificate", certificate)?;
+            }
+            writer
+                .write_event(Event::End(BytesEnd::new("X509Data")))
+                .map_err(xar_err)?;
+            writer
+                .write_event(Event::End(BytesEnd::new("KeyInfo")))
+                .map_err(xar_err)?;
+
+            writer
+                .write_event(Event::End(BytesEnd::new(signature.element.as_str())))
+                .map_err(xar_err)?;
+            offset += signature.data.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Write the artifact record, if any, before the file entries.
+    fn write_artifact<W: Write>(&self, writer: &mut Writer<W>) -> Result<(), PackageError> {
+        let Some(artifact) = &self.artifact else {
+            return Ok(());
+        };
+
+        let mut element = BytesStart::new(ARTIFACT_ELEMENT);
+        element.push_attribute(("xmlns", ARTIFACT_NAMESPACE));
+        writer.write_event(Event::Start(element)).map_err(xar_err)?;
+        for (name, value) in artifact.fields() {
+            self.write_simple_element(writer, name, &value)?;
+        }
+        writer
+            .write_event(Event::End(BytesEnd::new(ARTIFACT_ELEMENT)))
+            .map_err(xar_err)?;
+        Ok(())
+    }
+
     /// Write a simple text element.
     fn write_simple_element<W: Write>(
         &self,
@@ -333,7 +754,27 @@ impl XarBuilder {
 
     /// Finish building and write the archive.
     pub fn finish<W: Write>(&mut self, writer: &mut W) -> Result<(), PackageError> {
-        let toc_xml = self.generate_toc_xml()?;
+        self.write_archive(writer, None)
+    }
+
+    /// Finish building and write the archive signed by `signer`, replacing
+    /// any signature blocks set before.
+    pub fn finish_signed<W: Write>(
+        &mut self,
+        writer: &mut W,
+        signer: &dyn TocSigner,
+    ) -> Result<(), PackageError> {
+        self.signatures = vec![signer.signature_template()];
+        self.write_archive(writer, Some(signer))
+    }
+
+    fn write_archive<W: Write>(
+        &mut self,
+        writer: &mut W,
+        signer: Option<&dyn TocSigner>,
+    ) -> Result<(), PackageError> {
+        let archived = encode_entries(&self.entries)?;
+        let toc_xml = self.toc_xml(&archived)?;
         let toc_uncompressed = toc_xml.as_bytes();
 
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
@@ -351,9 +792,43 @@ impl XarBuilder {
             .map_err(|e| xar_err(format!("Failed to decode TOC checksum: {}", e)))?;
         writer.write_all(&toc_checksum_bytes).map_err(xar_err)?;
 
-        for entry in &self.entries {
-            if entry.entry_type == EntryType::File {
-                writer.write_all(&entry.data).map_err(xar_err)?;
+        // The TOC already records each signature's size, so a signer must
+        // fill exactly the space it reserved
+        if let Some(signer) = signer {
+            let data = signer.sign(&toc_checksum_bytes)?;
+            if data.len() != self.signatures[0].data.len() {
+                return Err(xar_err(format!(
+                    "Signature is {} bytes, {} were reserved",
+                    data.len(),
+                    self.signatures[0].data.len()
+                )));
+            }
+            self.signatures[0].data = data;
+        }
+        for signature in &self.signatures {
+            writer.write_all(&signature.data).map_err(xar_err)?;
+        }
+
+        self.write_heap_entries(writer, &archived, None)
+    }
+
+    /// Write file data in the same order `write_toc_entries` assigns offsets.
+    fn write_heap_entries<W: Write>(
+        &self,
+        writer: &mut W,
+        archived: &[Cow<'_, [u8]>],
+        parent_id: Option<u64>,
+    ) -> Result<(), PackageError> {
+        for (entry, stored) in self.entries.iter().zip(archived) {
+            if entry.parent_id != parent_id {
+                continue;
+            }
+
+            match entry.entry_type {
+                EntryType::File => writer.write_all(stored).map_err(xar_err)?,
+                EntryType::Directory => {
+                    self.write_heap_entries(writer, archived, Some(entry.id))?
+                }
             }
         }
 
@@ -367,6 +842,470 @@ impl Default for XarBuilder {
     }
 }
 
+/// The heap data of each of `entries`, in order.
+fn encode_entries(entries: &[XarEntry]) -> Result<Vec<Cow<'_, [u8]>>, PackageError> {
+    entries
+        .iter()
+        .map(|entry| entry.encoding.encode(&entry.data))
+        .collect()
+}
+
+/// What to do with an existing signature when repacking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignatureMode {
+    /// Remove signature blocks (the archive becomes unsigned)
+    #[default]
+    Strip,
+    /// Carry signature blocks over unchanged
+    Preserve,
+}
+
+/// Outcome of [`XarArchive::repack`].
+#[derive(Debug)]
+pub struct XarRepack {
+    /// The rewritten archive
+    pub data: Vec<u8>,
+    /// Number of signature blocks written to the new archive
+    pub signatures_kept: usize,
+    /// Why the result is not validly signed, when the input was
+    pub warning: Option<String>,
+}
+
+/// A XAR archive read from bytes, ready to modify and repack.
+#[derive(Debug)]
+pub struct XarArchive {
+    entries: Vec<XarEntry>,
+    signatures: Vec<XarSignature>,
+    artifact: Option<ArtifactMetadata>,
+    original: Vec<u8>,
+    modified: bool,
+}
+
+/// A `<file>` element while its TOC subtree is being read.
+#[derive(Debug, Default)]
+struct PendingEntry {
+    index: usize,
+    data_offset: Option<u64>,
+    data_length: u64,
+    encoding: String,
+}
+
+/// A signature element while its TOC subtree is being read.
+#[derive(Debug, Default)]
+struct PendingSignature {
+    signature: Option<XarSignature>,
+    offset: u64,
+    size: u64,
+}
+
+impl XarArchive {
+    /// Parse a XAR archive.
+    pub fn read(data: &[u8]) -> Result<Self, PackageError> {
+        if data.len() < XAR_HEADER_SIZE as usize || &data[0..4] != XAR_MAGIC {
+            return Err(xar_err("Missing XAR header"));
+        }
+
+        let header_size = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let toc_compressed = u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize;
+        let heap_start = header_size
+            .checked_add(toc_compressed)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| xar_err("TOC extends past end of file"))?;
+
+        let mut toc = String::new();
+        ZlibDecoder::new(&data[header_size..heap_start])
+            .read_to_string(&mut toc)
+            .map_err(|e| xar_err(format!("Cannot decompress TOC: {}", e)))?;
+
+        let mut archive = Self::parse_toc(&toc, &data[heap_start..])?;
+        archive.original = data.to_vec();
+        Ok(archive)
+    }
+
+    /// Entries in TOC order (parents before children).
+    pub fn entries(&self) -> &[XarEntry] {
+        &self.entries
+    }
+
+    /// Artifact record found in the TOC, if any.
+    pub fn artifact(&self) -> Option<&ArtifactMetadata> {
+        self.artifact.as_ref()
+    }
+
+    /// Signature blocks found in the TOC.
+    pub fn signatures(&self) -> &[XarSignature] {
+        &self.signatures
+    }
+
+    /// Whether the archive carries any signature.
+    pub fn is_signed(&self) -> bool {
+        !self.signatures.is_empty()
+    }
+
+    /// SHA-1 of the compressed TOC, the checksum stored at the start of
+    /// the heap. Signatures and notarization tickets are bound to it.
+    pub fn toc_checksum(&self) -> [u8; 20] {
+        let header_size = u16::from_be_bytes([self.original[4], self.original[5]]) as usize;
+        let toc_compressed = u64::from_be_bytes(self.original[8..16].try_into().unwrap()) as usize;
+        Sha1::digest(&self.original[header_size..header_size + toc_compressed]).into()
+    }
+
+    /// Data of the file at `path`, if present.
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|e| e.path == path && e.entry_type == EntryType::File)
+            .map(|e| e.data.as_slice())
+    }
+
+    /// Replace the contents of an existing file.
+    pub fn replace_file(&mut self, path: &str, data: Vec<u8>) -> Result<(), PackageError> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.path == path && e.entry_type == EntryType::File)
+            .ok_or_else(|| xar_err(format!("Entry '{}' not found in archive", path)))?;
+
+        entry.data = data;
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Replace a file, or add it (and any missing parent directories).
+    pub fn set_file(&mut self, path: &str, data: Vec<u8>) -> Result<(), PackageError> {
+        if self.file(path).is_some() {
+            return self.replace_file(path, data);
+        }
+
+        let mut parent_id = None;
+        let mut prefix = String::new();
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        let Some((name, dirs)) = components.split_last() else {
+            return Err(xar_err("Empty entry path"));
+        };
+
+        for dir in dirs {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(dir);
+
+            let existing = self.entries.iter().find(|e| e.path == prefix);
+            parent_id = Some(match existing {
+                Some(entry) if entry.entry_type == EntryType::Directory => entry.id,
+                Some(_) => return Err(xar_err(format!("'{}' is not a directory", prefix))),
+                None => self.push_entry(dir, &prefix, EntryType::Directory, Vec::new(), parent_id),
+            });
+        }
+
+        let full_path = if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        self.push_entry(name, &full_path, EntryType::File, data, parent_id);
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Move every entry into a new top-level directory `dir`.
+    ///
+    /// Used to wrap a component package's files (`PackageInfo`, `Payload`,
+    /// ...) as `dir/...` inside a product archive.
+    pub fn nest_root(&mut self, dir: &str) -> Result<(), PackageError> {
+        if self.entries.iter().any(|e| e.path == dir) {
+            return Err(xar_err(format!("Entry '{}' already exists", dir)));
+        }
+
+        let dir_id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        for entry in &mut self.entries {
+            entry.path = format!("{}/{}", dir, entry.path);
+            if entry.parent_id.is_none() {
+                entry.parent_id = Some(dir_id);
+            }
+        }
+        self.push_entry(dir, dir, EntryType::Directory, Vec::new(), None);
+        self.modified = true;
+        Ok(())
+    }
+
+    fn push_entry(
+        &mut self,
+        name: &str,
+        path: &str,
+        entry_type: EntryType,
+        data: Vec<u8>,
+        parent_id: Option<u64>,
+    ) -> u64 {
+        let id = self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+        self.entries.push(XarEntry {
+            name: name.to_string(),
+            path: path.to_string(),
+            entry_type,
+            data,
+            offset: 0,
+            checksum: String::new(),
+            id,
+            parent_id,
+            encoding: XarEncoding::None,
+            metadata: XarMetadata::default(),
+        });
+        id
+    }
+
+    /// Write the archive back out.
+    ///
+    /// An unmodified archive repacked with [`SignatureMode::Preserve`] is
+    /// returned byte for byte, so its signature stays valid. Otherwise the
+    /// TOC is regenerated; preserved signature blocks keep their
+    /// certificate chain but no longer verify until the package is
+    /// re-signed, and the returned warning says so.
+    pub fn repack(self, mode: SignatureMode) -> Result<XarRepack, PackageError> {
+        if mode == SignatureMode::Preserve && !self.modified {
+            return Ok(XarRepack {
+                signatures_kept: self.signatures.len(),
+                data: self.original,
+                warning: None,
+            });
+        }
+
+        let warning = match (self.is_signed(), mode) {
+            (false, _) => None,
+            (true, SignatureMode::Strip) => Some(
+                "Existing signature was removed; sign the package again before distribution"
+                    .to_string(),
+            ),
+            (true, SignatureMode::Preserve) => Some(
+                "Existing signature was carried over but no longer matches the modified \
+                 contents; sign the package again before distribution"
+                    .to_string(),
+            ),
+        };
+
+        let signatures = match mode {
+            SignatureMode::Preserve => self.signatures,
+            SignatureMode::Strip => Vec::new(),
+        };
+        let signatures_kept = signatures.len();
+
+        let mut builder = XarBuilder {
+            next_id: self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            entries: self.entries,
+            signatures,
+            artifact: self.artifact,
+        };
+        let mut data = Vec::new();
+        builder.finish(&mut data)?;
+
+        Ok(XarRepack {
+            data,
+            signatures_kept,
+            warning,
+        })
+    }
+
+    /// Rewrite the archive signed by `signer`, replacing any existing
+    /// signature.
+    pub fn repack_signed(self, signer: &dyn TocSigner) -> Result<Vec<u8>, PackageError> {
+        let mut builder = XarBuilder {
+            next_id: self.entries.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+            entries: self.entries,
+            signatures: Vec::new(),
+            artifact: self.artifact,
+        };
+        let mut data = Vec::new();
+        builder.finish_signed(&mut data, signer)?;
+        Ok(data)
+    }
+
+    /// Build entries and signatures from the TOC, reading data from `heap`.
+    fn parse_toc(toc: &str, heap: &[u8]) -> Result<Self, PackageError> {
+        let mut reader = Reader::from_str(toc);
+        let mut entries: Vec<XarEntry> = Vec::new();
+        let mut signatures = Vec::new();
+        let mut files: Vec<PendingEntry> = Vec::new();
+        let mut signature: Option<PendingSignature> = None;
+        let mut artifact = None;
+        let mut artifact_fields: Option<Vec<(String, String)>> = None;
+        let mut elements: Vec<String> = Vec::new();
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| xar_err(format!("Invalid TOC XML: {}", e)))?;
+
+            match event {
+                Event::Start(e) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                    match name.as_str() {
+                        "file" => {
+                            let parent = files.last().map(|f| entries[f.index].id);
+                            entries.push(XarEntry {
+                                name: String::new(),
+                                path: String::new(),
+                                entry_type: EntryType::File,
+                                data: Vec::new(),
+                                offset: 0,
+                                checksum: String::new(),
+                                id: entries.len() as u64 + 1,
+                                parent_id: parent,
+                                encoding: XarEncoding::None,
+                                metadata: XarMetadata::default(),
+                            });
+                            files.push(PendingEntry {
+                                index: entries.len() - 1,
+                                ..PendingEntry::default()
+                            });
+                        }
+                        "signature" | "x-signature" if files.is_empty() => {
+                            signature = Some(PendingSignature {
+                                signature: Some(XarSignature {
+                                    element: name.clone(),
+                                    style: style_attribute(&e)?,
+                                    certificates: Vec::new(),
+                                    data: Vec::new(),
+                                }),
+                                ..PendingSignature::default()
+                            });
+                        }
+                        ARTIFACT_ELEMENT if files.is_empty() => artifact_fields = Some(Vec::new()),
+                        _ => {}
+                    }
+                    elements.push(name);
+                }
+                Event::Empty(e)
+                    if e.name().as_ref() == b"encoding"
+                        && elements.last().map(String::as_str) == Some("data") =>
+                {
+                    if let Some(file) = files.last_mut() {
+                        file.encoding = style_attribute(&e)?;
+                    }
+                }
+                Event::Text(t) => {
+                    let text = t.unescape().map_err(xar_err)?;
+                    let text = text.trim();
+                    let current = elements.last().map(String::as_str).unwrap_or("");
+
+                    if let Some(pending) = signature.as_mut() {
+                        match current {
+                            "offset" => pending.offset = parse_number(text)?,
+                            "size" => pending.size = parse_number(text)?,
+                            "X509Certificate" => {
+                                if let Some(sig) = pending.signature.as_mut() {
+                                    sig.certificates.push(text.split_whitespace().collect());
+                                }
+                            }
+                            _ => {}
+                        }
+                    } else if let Some(fields) = artifact_fields.as_mut() {
+                        if current != ARTIFACT_ELEMENT {
+                            fields.push((current.to_string(), text.to_string()));
+                        }
+                    } else if let Some(file) = files.last_mut() {
+                        let entry = &mut entries[file.index];
+                        match current {
+                            "name" if parent_is(&elements, "file") => entry.name = text.to_string(),
+                            "type" if parent_is(&elements, "file") => {
+                                entry.entry_type = if text == "directory" {
+                                    EntryType::Directory
+                                } else {
+                                    EntryType::File
+                                };
+                            }
+                            "offset" if parent_is(&elements, "data") => {
+                                file.data_offset = Some(parse_number(text)?)
+                            }
+                            "length" if parent_is(&elements, "data") => {
+                                file.data_length = parse_number(text)?
+                            }
+                            name if parent_is(&elements, "file") => {
+                                entry.metadata.set(name, text)?
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Event::End(e) => {
+                    elements.pop();
+                    match e.name().as_ref() {
+                        b"file" => {
+                            let file = files.pop().expect("balanced <file> elements");
+                            // Ancestors are still open, so build the path from their names
+                            let path: Vec<&str> = files
+                                .iter()
+                                .chain([&file])
+                                .map(|f| entries[f.index].name.as_str())
+                                .collect();
+                            let path = path.join("/");
+                            let entry = &mut entries[file.index];
+                            entry.path = path;
+                            if let Some(offset) = file.data_offset {
+                                let archived = heap_slice(heap, offset, file.data_length)?;
+                                entry.encoding =
+                                    XarEncoding::from_style(&file.encoding, &entry.path)?;
+                                entry.data = entry.encoding.decode(archived, &entry.path)?;
+                            }
+                        }
+                        b"signature" | b"x-signature" if files.is_empty() => {
+                            if let Some(mut pending) = signature.take() {
+                                if let Some(mut sig) = pending.signature.take() {
+                                    sig.data =
+                                        heap_slice(heap, pending.offset, pending.size)?.to_vec();
+                                    signatures.push(sig);
+                                }
+                            }
+                        }
+                        name if name == ARTIFACT_ELEMENT.as_bytes() && files.is_empty() => {
+                            if let Some(fields) = artifact_fields.take() {
+                                let fields = fields.iter().map(|(n, v)| (n.as_str(), v.as_str()));
+                                artifact =
+                                    Some(ArtifactMetadata::from_fields(fields).map_err(xar_err)?);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            entries,
+            signatures,
+            artifact,
+            original: Vec::new(),
+            modified: false,
+        })
+    }
+}
+
+/// Whether the element enclosing the current one is `name`.
+fn parent_is(elements: &[String], name: &str) -> bool {
+    elements.len() >= 2 && elements[elements.len() - 2] == name
+}
+
+fn style_attribute(element: &BytesStart<'_>) -> Result<String, PackageError> {
+    let style: Option<Attribute<'_>> = element.try_get_attribute("style").map_err(xar_err)?;
+    match style {
+        Some(attr) => Ok(attr.unescape_value().map_err(xar_err)?.into_owned()),
+        None => Ok(String::new()),
+    }
+}
+
+fn parse_number(text: &str) -> Result<u64, PackageError> {
+    text.parse()
+        .map_err(|_| xar_err(format!("Invalid number '{}' in TOC", text)))
+}
+
+fn heap_slice(heap: &[u8], offset: u64, length: u64) -> Result<&[u8], PackageError> {
+    offset
+        .checked_add(length)
+        .filter(|end| *end <= heap.len() as u64)
+        .map(|end| &heap[offset as usize..end as usize])
+        .ok_or_else(|| xar_err("Heap entry extends past end of file"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -497,4 +1436,278 @@ mod tests {
             "Multi-file archive should have substantial size"
         );
     }
+
+    // Reader and signature-aware repack
+    fn sample_archive(signatures: Vec<XarSignature>) -> Vec<u8> {
+        let mut builder = XarBuilder::new();
+        builder.add_directory("base.pkg").unwrap();
+        builder
+            .add_file("base.pkg/Payload", b"payload".to_vec())
+            .unwrap();
+        builder
+            .add_file("Distribution", b"<installer-gui-script/>".to_vec())
+            .unwrap();
+        builder.set_signatures(signatures);
+        let mut output = Vec::new();
+        builder.finish(&mut output).unwrap();
+        output
+    }
+
+    fn sample_signature() -> XarSignature {
+        XarSignature {
+            element: "signature".to_string(),
+            style: "RSA".to_string(),
+            certificates: vec!["MIIBleaf".to_string(), "MIIBroot".to_string()],
+            data: vec![0xab; 256],
+        }
+    }
+
+    #[test]
+    fn test_xar_read_roundtrip() {
+        let archive = XarArchive::read(&sample_archive(Vec::new())).unwrap();
+
+        assert!(!archive.is_signed());
+        assert_eq!(archive.file("base.pkg/Payload"), Some(&b"payload"[..]));
+        assert_eq!(
+            archive.file("Distribution"),
+            Some(&b"<installer-gui-script/>"[..])
+        );
+        assert_eq!(archive.entries()[0].entry_type, EntryType::Directory);
+    }
+
+    #[test]
+    fn test_xar_artifact_roundtrip() {
+        let artifact = ArtifactMetadata::new("cd".repeat(32));
+        let mut builder = XarBuilder::new();
+        builder.add_file("Distribution", b"<x/>".to_vec()).unwrap();
+        builder.set_signatures(vec![sample_signature()]);
+        builder.set_artifact(artifact.clone());
+        let toc = builder.generate_toc_xml().unwrap();
+        assert!(toc.contains("<ArtifactInfo xmlns=\"urn:iamawrapper:artifact\">"));
+
+        let mut output = Vec::new();
+        builder.finish(&mut output).unwrap();
+        let archive = XarArchive::read(&output).unwrap();
+        assert_eq!(archive.artifact(), Some(&artifact));
+        assert_eq!(archive.file("Distribution"), Some(&b"<x/>"[..]));
+
+        // The record survives modification and repacking
+        let mut archive = archive;
+        archive
+            .replace_file("Distribution", b"<y/>".to_vec())
+            .unwrap();
+        let repacked = archive.repack(SignatureMode::Strip).unwrap();
+        let reread = XarArchive::read(&repacked.data).unwrap();
+        assert_eq!(reread.artifact(), Some(&artifact));
+        assert_eq!(
+            XarArchive::read(&sample_archive(Vec::new()))
+                .unwrap()
+                .artifact(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_xar_read_signature() {
+        let archive = XarArchive::read(&sample_archive(vec![sample_signature()])).unwrap();
+
+        assert_eq!(archive.signatures(), &[sample_signature()]);
+        // Files still resolve past the signature bytes in the heap
+        assert_eq!(archive.file("base.pkg/Payload"), Some(&b"payload"[..]));
+    }
+
+    #[test]
+    fn test_xar_repack_unmodified_keeps_bytes() {
+        let original = sample_archive(vec![sample_signature()]);
+        let archive = XarArchive::read(&original).unwrap();
+
+        let repacked = archive.repack(SignatureMode::Preserve).unwrap();
+        assert_eq!(repacked.data, original);
+        assert_eq!(repacked.signatures_kept, 1);
+        assert!(repacked.warning.is_none());
+    }
+
+    #[test]
+    fn test_xar_repack_modified_signature_modes() {
+        let original = sample_archive(vec![sample_signature()]);
+
+        let mut archive = XarArchive::read(&original).unwrap();
+        archive
+            .replace_file("Distribution", b"<new/>".to_vec())
+            .unwrap();
+        let stripped = archive.repack(SignatureMode::Strip).unwrap();
+        assert!(stripped.warning.is_some());
+        let reread = XarArchive::read(&stripped.data).unwrap();
+        assert!(!reread.is_signed());
+        assert_eq!(reread.file("Distribution"), Some(&b"<new/>"[..]));
+
+        let mut archive = XarArchive::read(&original).unwrap();
+        archive
+            .replace_file("Distribution", b"<new/>".to_vec())
+            .unwrap();
+        let preserved = archive.repack(SignatureMode::Preserve).unwrap();
+        assert!(preserved.warning.is_some());
+        let reread = XarArchive::read(&preserved.data).unwrap();
+        assert_eq!(reread.signatures(), &[sample_signature()]);
+        assert_eq!(reread.file("base.pkg/Payload"), Some(&b"payload"[..]));
+    }
+
+    #[test]
+    fn test_xar_replace_missing_entry() {
+        let mut archive = XarArchive::read(&sample_archive(Vec::new())).unwrap();
+        assert!(
+            archive
+                .replace_file("Resources/en.lproj", Vec::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_xar_set_file_creates_directories() {
+        let mut archive = XarArchive::read(&sample_archive(Vec::new())).unwrap();
+        archive
+            .set_file("Resources/en.lproj/License.rtf", b"{\\rtf1}".to_vec())
+            .unwrap();
+        archive
+            .set_file("Resources/background.png", b"png".to_vec())
+            .unwrap();
+
+        let repacked = archive.repack(SignatureMode::Strip).unwrap();
+        let reread = XarArchive::read(&repacked.data).unwrap();
+        assert_eq!(
+            reread.file("Resources/en.lproj/License.rtf"),
+            Some(&b"{\\rtf1}"[..])
+        );
+        assert_eq!(reread.file("Resources/background.png"), Some(&b"png"[..]));
+        assert_eq!(reread.file("base.pkg/Payload"), Some(&b"payload"[..]));
+    }
+
+    #[test]
+    fn test_xar_builder_nested_file() {
+        let mut builder = XarBuilder::new();
+        builder
+            .add_nested_file("Resources/en.lproj/Welcome.html", b"<p>Hi</p>".to_vec())
+            .unwrap();
+        builder
+            .add_nested_file("Resources/background.png", b"png".to_vec())
+            .unwrap();
+        assert!(
+            builder
+                .add_nested_file("Resources/background.png/x", Vec::new())
+                .is_err()
+        );
+        let mut output = Vec::new();
+        builder.finish(&mut output).unwrap();
+
+        let archive = XarArchive::read(&output).unwrap();
+        assert_eq!(
+            archive.file("Resources/en.lproj/Welcome.html"),
+            Some(&b"<p>Hi</p>"[..])
+        );
+        assert_eq!(archive.file("Resources/background.png"), Some(&b"png"[..]));
+        let directories = archive
+            .entries()
+            .iter()
+            .filter(|e| e.entry_type == EntryType::Directory)
+            .count();
+        assert_eq!(directories, 2);
+    }
+
+    #[test]
+    fn test_xar_encodings_roundtrip() {
+        let text = b"compressible ".repeat(100);
+        let encodings = [
+            XarEncoding::None,
+            XarEncoding::Gzip,
+            #[cfg(feature = "xar-bzip2")]
+            XarEncoding::Bzip2,
+        ];
+
+        let mut builder = XarBuilder::new();
+        for (i, encoding) in encodings.iter().enumerate() {
+            builder
+                .add_file_with(
+                    &format!("file{}", i),
+                    text.clone(),
+                    *encoding,
+                    XarMetadata::default(),
+                )
+                .unwrap();
+        }
+        let toc = builder.generate_toc_xml().unwrap();
+        assert!(toc.contains(r#"<encoding style="application/x-gzip"/>"#));
+        assert!(toc.contains(&format!("<size>{}</size>", text.len())));
+        let mut output = Vec::new();
+        builder.finish(&mut output).unwrap();
+        assert!(output.len() < text.len() * encodings.len());
+
+        let archive = XarArchive::read(&output).unwrap();
+        for (i, encoding) in encodings.iter().enumerate() {
+            let entry = &archive.entries()[i];
+            assert_eq!(entry.data, text);
+            assert_eq!(entry.encoding, *encoding);
+        }
+
+        // Repacking keeps each entry's encoding
+        let repacked = archive.repack(SignatureMode::Strip).unwrap();
+        let reread = XarArchive::read(&repacked.data).unwrap();
+        assert_eq!(reread.entries()[1].encoding, XarEncoding::Gzip);
+        assert_eq!(reread.file("file1"), Some(&text[..]));
+    }
+
+    #[cfg(not(feature = "xar-bzip2"))]
+    #[test]
+    fn test_xar_bzip2_needs_feature() {
+        let err = XarEncoding::from_style("application/x-bzip2", "Payload").unwrap_err();
+        assert!(err.to_string().contains("'xar-bzip2' feature"));
+    }
+
+    #[test]
+    fn test_xar_metadata_roundtrip() {
+        let metadata = XarMetadata::default()
+            .with_mode(0o755)
+            .with_owner(0, 80)
+            .with_owner_names("root", "admin")
+            .with_mtime(1_700_000_000);
+
+        let mut builder = XarBuilder::new();
+        builder.add_directory_with("bin", metadata.clone()).unwrap();
+        let size = builder
+            .add_file_from_reader(
+                "bin/tool",
+                Cursor::new(b"#!/bin/sh\n".to_vec()),
+                XarEncoding::None,
+                metadata.clone().with_mode(0o4755),
+            )
+            .unwrap();
+        assert_eq!(size, 10);
+        let toc = builder.generate_toc_xml().unwrap();
+        assert!(toc.contains("<mode>0755</mode>"));
+        assert!(toc.contains("<mode>4755</mode>"));
+        assert!(toc.contains("<mtime>2023-11-14T22:13:20Z</mtime>"));
+        let mut output = Vec::new();
+        builder.finish(&mut output).unwrap();
+
+        let archive = XarArchive::read(&output).unwrap();
+        assert_eq!(archive.entries()[0].metadata, metadata);
+        assert_eq!(archive.entries()[1].metadata.mode, Some(0o4755));
+        assert_eq!(archive.file("bin/tool"), Some(&b"#!/bin/sh\n"[..]));
+    }
+
+    #[test]
+    fn test_xar_set_file_in_earlier_directory() {
+        let mut archive = XarArchive::read(&sample_archive(Vec::new())).unwrap();
+        archive
+            .set_file("base.pkg/Scripts", b"scripts".to_vec())
+            .unwrap();
+
+        let repacked = archive.repack(SignatureMode::Strip).unwrap();
+        let reread = XarArchive::read(&repacked.data).unwrap();
+        assert_eq!(reread.file("base.pkg/Scripts"), Some(&b"scripts"[..]));
+        assert_eq!(
+            reread.file("Distribution"),
+            Some(&b"<installer-gui-script/>"[..])
+        );
+        assert_eq!(reread.file("base.pkg/Payload"), Some(&b"payload"[..]));
+    }
 }
@@ -1,19 +1,25 @@
 //! XML document generation for macOS packages.
 //!
-//! Generates PackageInfo and Distribution XML files.
+//! Generates PackageInfo and Distribution XML files (for a single component
+//! or several, as `productbuild` does), and rewrites the Distribution and
+//! PackageInfo of an existing package.
 
 use crate::models::PackageError;
-use quick_xml::Writer;
+use crate::models::macos::{AppBundle, DistributionOptions, HostArchitecture, InstallDomain};
+use quick_xml::events::attributes::Attribute;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::collections::BTreeSet;
 use std::io::Cursor;
 
 type XmlWriter = Writer<Cursor<Vec<u8>>>;
 
+/// Architectures a package installs on unless its components are limited.
+const ALL_ARCHITECTURES: &str = "x86_64,arm64";
+
 /// Convert any error to PackageError::XmlError.
-fn xml_err<E: std::fmt::Display>(e: E) -> PackageError {
-    PackageError::XmlError {
-        reason: e.to_string(),
-    }
+fn xml_err<E: std::error::Error + Send + Sync + 'static>(e: E) -> PackageError {
+    PackageError::xml(e)
 }
 
 /// Write an event to the XML writer.
@@ -68,9 +74,13 @@ fn create_xml_writer() -> Result<XmlWriter, PackageError> {
 /// * `version` - Package version (e.g., "1.0.0")
 /// * `install_location` - Installation target path
 /// * `install_kbytes` - Total size in KB
-/// * `num_files` - Number of files
+/// * `num_files` - Number of BOM paths, see [`PackagePayload::number_of_files`]
+///
+/// [`PackagePayload::number_of_files`]: crate::models::macos::PackagePayload::number_of_files
 /// * `has_preinstall` - Whether preinstall script exists
 /// * `has_postinstall` - Whether postinstall script exists
+/// * `bundle` - Application bundle installed as a component, if any
+#[allow(clippy::too_many_arguments)]
 pub fn generate_packageinfo(
     identifier: &str,
     version: &str,
@@ -79,17 +89,15 @@ pub fn generate_packageinfo(
     num_files: usize,
     has_preinstall: bool,
     has_postinstall: bool,
+    bundle: Option<&AppBundle>,
 ) -> Result<String, PackageError> {
     let mut writer = create_xml_writer()?;
 
     // <pkg-info> root element
-    let mut pkg_info = BytesStart::new("pkg-info");
-    pkg_info.push_attribute(("format-version", "2"));
-    pkg_info.push_attribute(("identifier", identifier));
-    pkg_info.push_attribute(("version", version));
-    pkg_info.push_attribute(("install-location", install_location));
-    pkg_info.push_attribute(("auth", "root"));
-    write(&mut writer, Event::Start(pkg_info))?;
+    write(
+        &mut writer,
+        Event::Start(pkg_info_element(identifier, version, install_location)),
+    )?;
 
     // <payload> element
     let mut payload = BytesStart::new("payload");
@@ -98,23 +106,95 @@ pub fn generate_packageinfo(
     write(&mut writer, Event::Empty(payload))?;
 
     // <scripts> element (if any scripts exist)
-    if has_preinstall || has_postinstall {
-        write(&mut writer, Event::Start(BytesStart::new("scripts")))?;
+    write_scripts_element(&mut writer, has_preinstall, has_postinstall)?;
 
-        if has_preinstall {
-            write_empty_element(&mut writer, "preinstall", "file", "./preinstall")?;
-        }
-        if has_postinstall {
-            write_empty_element(&mut writer, "postinstall", "file", "./postinstall")?;
-        }
-
-        write(&mut writer, Event::End(BytesEnd::new("scripts")))?;
+    if let Some(bundle) = bundle {
+        write_bundle_elements(&mut writer, bundle)?;
     }
 
     write(&mut writer, Event::End(BytesEnd::new("pkg-info")))?;
     finalize(writer)
 }
 
+/// Generate the PackageInfo of a payload-free package, as `pkgbuild
+/// --nopayload` writes it: without a `<payload>` element, so the
+/// Installer neither expects a Bom or Payload nor records a receipt.
+pub fn generate_payload_free_packageinfo(
+    identifier: &str,
+    version: &str,
+    install_location: &str,
+    has_preinstall: bool,
+    has_postinstall: bool,
+) -> Result<String, PackageError> {
+    let mut writer = create_xml_writer()?;
+    write(
+        &mut writer,
+        Event::Start(pkg_info_element(identifier, version, install_location)),
+    )?;
+    write_scripts_element(&mut writer, has_preinstall, has_postinstall)?;
+    write(&mut writer, Event::End(BytesEnd::new("pkg-info")))?;
+    finalize(writer)
+}
+
+/// The `<pkg-info>` root element of a PackageInfo.
+fn pkg_info_element<'a>(
+    identifier: &'a str,
+    version: &'a str,
+    install_location: &'a str,
+) -> BytesStart<'a> {
+    let mut pkg_info = BytesStart::new("pkg-info");
+    pkg_info.push_attribute(("format-version", "2"));
+    pkg_info.push_attribute(("identifier", identifier));
+    pkg_info.push_attribute(("version", version));
+    pkg_info.push_attribute(("install-location", install_location));
+    pkg_info.push_attribute(("auth", "root"));
+    pkg_info
+}
+
+/// Write the `<scripts>` element listing the scripts that exist, if any.
+fn write_scripts_element(
+    writer: &mut XmlWriter,
+    has_preinstall: bool,
+    has_postinstall: bool,
+) -> Result<(), PackageError> {
+    if !has_preinstall && !has_postinstall {
+        return Ok(());
+    }
+    write(writer, Event::Start(BytesStart::new("scripts")))?;
+    if has_preinstall {
+        write_empty_element(writer, "preinstall", "file", "./preinstall")?;
+    }
+    if has_postinstall {
+        write_empty_element(writer, "postinstall", "file", "./postinstall")?;
+    }
+    write(writer, Event::End(BytesEnd::new("scripts")))
+}
+
+/// Write the `<bundle>` description of a component and the lists that
+/// reference it, as `pkgbuild --component` does.
+///
+/// The bundle is not listed under `<relocate>`, so it is always installed
+/// at the install location even if a copy exists elsewhere.
+fn write_bundle_elements(writer: &mut XmlWriter, bundle: &AppBundle) -> Result<(), PackageError> {
+    let mut elem = BytesStart::new("bundle");
+    elem.push_attribute(("path", format!("./{}", bundle.name()).as_str()));
+    elem.push_attribute(("id", bundle.identifier.as_str()));
+    if let Some(version) = &bundle.short_version {
+        elem.push_attribute(("CFBundleShortVersionString", version.as_str()));
+    }
+    if let Some(version) = &bundle.bundle_version {
+        elem.push_attribute(("CFBundleVersion", version.as_str()));
+    }
+    write(writer, Event::Empty(elem))?;
+
+    for list in ["bundle-version", "upgrade-bundle", "strict-identifier"] {
+        write(writer, Event::Start(BytesStart::new(list)))?;
+        write_empty_element(writer, "bundle", "id", &bundle.identifier)?;
+        write(writer, Event::End(BytesEnd::new(list)))?;
+    }
+    Ok(())
+}
+
 /// Generate Distribution XML document.
 ///
 /// # Arguments
@@ -128,6 +208,157 @@ pub fn generate_distribution(
     version: &str,
     install_kbytes: u64,
 ) -> Result<String, PackageError> {
+    generate_custom_distribution(
+        identifier,
+        version,
+        install_kbytes,
+        &DistributionOptions::default().with_title(title),
+        &[],
+    )
+}
+
+/// Generate a Distribution XML document with the title and install options
+/// of `options`; the title defaults to `identifier`.
+///
+/// `resources` pairs Distribution elements (`welcome`, `license`, ...)
+/// with the name of the file each shows, expected under `Resources/` in
+/// the package (see [`collect_resources`](super::resources::collect_resources)).
+pub fn generate_custom_distribution(
+    identifier: &str,
+    version: &str,
+    install_kbytes: u64,
+    options: &DistributionOptions,
+    resources: &[(&str, String)],
+) -> Result<String, PackageError> {
+    let title = options.title.as_deref().unwrap_or(identifier);
+    let mut writer = create_distribution_writer(title, ALL_ARCHITECTURES, options, resources)?;
+
+    // <choices-outline>
+    write(
+        &mut writer,
+        Event::Start(BytesStart::new("choices-outline")),
+    )?;
+    write_empty_element(&mut writer, "line", "choice", "default")?;
+    write(&mut writer, Event::End(BytesEnd::new("choices-outline")))?;
+
+    // <choice>
+    let mut choice = BytesStart::new("choice");
+    choice.push_attribute(("id", "default"));
+    choice.push_attribute(("visible", "false"));
+    choice.push_attribute(("title", title));
+    write(&mut writer, Event::Start(choice))?;
+    write_empty_element(&mut writer, "pkg-ref", "id", identifier)?;
+    write(&mut writer, Event::End(BytesEnd::new("choice")))?;
+
+    // <pkg-ref> with details
+    write_pkg_ref(&mut writer, identifier, version, install_kbytes, "base.pkg")?;
+
+    write(
+        &mut writer,
+        Event::End(BytesEnd::new("installer-gui-script")),
+    )?;
+    finalize(writer)
+}
+
+/// A component package listed in a product Distribution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistributionRef {
+    /// Component package identifier
+    pub identifier: String,
+    /// Component package version
+    pub version: String,
+    /// Installed size in KB
+    pub install_kbytes: u64,
+    /// Directory of the component in the archive (e.g., `com.example.app.pkg`)
+    pub path: String,
+    /// Architecture the component is limited to
+    pub host_architecture: Option<HostArchitecture>,
+}
+
+/// Generate a Distribution XML document for several component packages.
+///
+/// Laid out like `productbuild --synthesize`: a hidden choice per
+/// component, nested under a `default` choice and installed in order.
+/// The choice of a component limited to one architecture is only selected
+/// on Macs of that architecture. When every component is limited,
+/// `hostArchitectures` lists just their architectures, so the Installer
+/// refuses to run on any other.
+pub fn generate_product_distribution(
+    title: &str,
+    components: &[DistributionRef],
+) -> Result<String, PackageError> {
+    let limited: Option<BTreeSet<HostArchitecture>> = components
+        .iter()
+        .map(|component| component.host_architecture)
+        .collect();
+    let host_architectures = match limited {
+        Some(architectures) if !architectures.is_empty() => architectures
+            .iter()
+            .map(|arch| arch.name())
+            .collect::<Vec<_>>()
+            .join(","),
+        _ => ALL_ARCHITECTURES.to_string(),
+    };
+    let mut writer = create_distribution_writer(
+        title,
+        &host_architectures,
+        &DistributionOptions::default(),
+        &[],
+    )?;
+
+    // <choices-outline>
+    write(
+        &mut writer,
+        Event::Start(BytesStart::new("choices-outline")),
+    )?;
+    let mut default_line = BytesStart::new("line");
+    default_line.push_attribute(("choice", "default"));
+    write(&mut writer, Event::Start(default_line))?;
+    for component in components {
+        write_empty_element(&mut writer, "line", "choice", &component.identifier)?;
+    }
+    write(&mut writer, Event::End(BytesEnd::new("line")))?;
+    write(&mut writer, Event::End(BytesEnd::new("choices-outline")))?;
+
+    // <choice> per component
+    write_empty_element(&mut writer, "choice", "id", "default")?;
+    for component in components {
+        let mut choice = BytesStart::new("choice");
+        choice.push_attribute(("id", component.identifier.as_str()));
+        choice.push_attribute(("visible", "false"));
+        if let Some(arch) = component.host_architecture {
+            choice.push_attribute(("selected", arch.condition()));
+        }
+        write(&mut writer, Event::Start(choice))?;
+        write_empty_element(&mut writer, "pkg-ref", "id", &component.identifier)?;
+        write(&mut writer, Event::End(BytesEnd::new("choice")))?;
+    }
+
+    for component in components {
+        write_pkg_ref(
+            &mut writer,
+            &component.identifier,
+            &component.version,
+            component.install_kbytes,
+            &component.path,
+        )?;
+    }
+
+    write(
+        &mut writer,
+        Event::End(BytesEnd::new("installer-gui-script")),
+    )?;
+    finalize(writer)
+}
+
+/// Start a Distribution document: root element, title, resources, options,
+/// domains and the allowed macOS versions.
+fn create_distribution_writer(
+    title: &str,
+    host_architectures: &str,
+    settings: &DistributionOptions,
+    resources: &[(&str, String)],
+) -> Result<XmlWriter, PackageError> {
     let mut writer = create_xml_writer()?;
 
     // <installer-gui-script> root element
@@ -138,53 +369,288 @@ pub fn generate_distribution(
     // <title>
     write_text_element(&mut writer, "title", title)?;
 
+    // <welcome>, <license>, <readme>, <conclusion> and <background> in
+    // Resources; the background is drawn unscaled in the bottom left corner,
+    // clear of the panes' text
+    for (element, name) in resources {
+        let mut start = BytesStart::new(*element);
+        start.push_attribute(("file", name.as_str()));
+        if *element == "background" {
+            start.push_attribute(("alignment", "bottomleft"));
+            start.push_attribute(("scaling", "none"));
+        }
+        write(&mut writer, Event::Empty(start))?;
+    }
+
     // <options>
     let mut options = BytesStart::new("options");
-    options.push_attribute(("customize", "never"));
+    options.push_attribute(("customize", settings.customize.name()));
     options.push_attribute(("require-scripts", "false"));
-    options.push_attribute(("hostArchitectures", "x86_64,arm64"));
+    options.push_attribute(("hostArchitectures", host_architectures));
     write(&mut writer, Event::Empty(options))?;
 
     // <domains>
     let mut domains = BytesStart::new("domains");
-    domains.push_attribute(("enable_anywhere", "false"));
-    domains.push_attribute(("enable_currentUserHome", "false"));
-    domains.push_attribute(("enable_localSystem", "true"));
+    for domain in [
+        InstallDomain::Anywhere,
+        InstallDomain::CurrentUserHome,
+        InstallDomain::LocalSystem,
+    ] {
+        let enabled = if settings.enables(domain) {
+            "true"
+        } else {
+            "false"
+        };
+        domains.push_attribute((domain.attribute(), enabled));
+    }
     write(&mut writer, Event::Empty(domains))?;
 
-    // <choices-outline>
-    write(
-        &mut writer,
-        Event::Start(BytesStart::new("choices-outline")),
-    )?;
-    write_empty_element(&mut writer, "line", "choice", "default")?;
-    write(&mut writer, Event::End(BytesEnd::new("choices-outline")))?;
+    // <allowed-os-versions>
+    if let Some(version) = &settings.min_os_version {
+        write(
+            &mut writer,
+            Event::Start(BytesStart::new("allowed-os-versions")),
+        )?;
+        write_empty_element(&mut writer, "os-version", "min", version)?;
+        write(
+            &mut writer,
+            Event::End(BytesEnd::new("allowed-os-versions")),
+        )?;
+    }
 
-    // <choice>
-    let mut choice = BytesStart::new("choice");
-    choice.push_attribute(("id", "default"));
-    choice.push_attribute(("visible", "false"));
-    choice.push_attribute(("title", title));
-    write(&mut writer, Event::Start(choice))?;
-    write_empty_element(&mut writer, "pkg-ref", "id", identifier)?;
-    write(&mut writer, Event::End(BytesEnd::new("choice")))?;
+    Ok(writer)
+}
 
-    // <pkg-ref> with details
+/// Write the `<pkg-ref>` locating a component in the archive.
+fn write_pkg_ref(
+    writer: &mut XmlWriter,
+    identifier: &str,
+    version: &str,
+    install_kbytes: u64,
+    path: &str,
+) -> Result<(), PackageError> {
     let mut pkg_ref = BytesStart::new("pkg-ref");
     pkg_ref.push_attribute(("id", identifier));
     pkg_ref.push_attribute(("version", version));
     pkg_ref.push_attribute(("installKBytes", install_kbytes.to_string().as_str()));
-    write(&mut writer, Event::Start(pkg_ref))?;
-    write(&mut writer, Event::Text(BytesText::new("#base.pkg")))?;
-    write(&mut writer, Event::End(BytesEnd::new("pkg-ref")))?;
+    write(writer, Event::Start(pkg_ref))?;
+    write(writer, Event::Text(BytesText::new(&format!("#{}", path))))?;
+    write(writer, Event::End(BytesEnd::new("pkg-ref")))
+}
+
+/// Fields of a component package's PackageInfo needed to describe it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageInfoSummary {
+    /// Package identifier
+    pub identifier: String,
+    /// Package version
+    pub version: String,
+    /// Installed size in KB
+    pub install_kbytes: u64,
+}
+
+/// Read identifier, version and installed size from a PackageInfo document.
+pub fn parse_packageinfo(xml: &str) -> Result<PackageInfoSummary, PackageError> {
+    let mut reader = Reader::from_str(xml);
+    let mut identifier = None;
+    let mut version = String::new();
+    let mut install_kbytes = 0;
+
+    loop {
+        match reader.read_event().map_err(xml_err)? {
+            Event::Start(e) | Event::Empty(e) => match e.name().as_ref() {
+                b"pkg-info" => {
+                    identifier = attribute(&e, "identifier")?;
+                    version = attribute(&e, "version")?.unwrap_or_default();
+                }
+                b"payload" => {
+                    install_kbytes = attribute(&e, "installKBytes")?
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0);
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(PackageInfoSummary {
+        identifier: identifier.ok_or_else(|| PackageError::XmlError {
+            reason: "PackageInfo has no identifier".to_string(),
+            source: None,
+        })?,
+        version,
+        install_kbytes,
+    })
+}
+
+/// Identifier and version of each `<pkg-ref>` in a Distribution that gives
+/// a version, as the one locating a component does.
+pub fn parse_distribution_refs(xml: &str) -> Result<Vec<(String, String)>, PackageError> {
+    let mut reader = Reader::from_str(xml);
+    let mut refs = Vec::new();
+
+    loop {
+        match reader.read_event().map_err(xml_err)? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"pkg-ref" => {
+                if let (Some(id), Some(version)) = (attribute(&e, "id")?, attribute(&e, "version")?)
+                {
+                    refs.push((id, version));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(refs)
+}
+
+fn attribute(element: &BytesStart<'_>, name: &str) -> Result<Option<String>, PackageError> {
+    match element.try_get_attribute(name).map_err(xml_err)? {
+        Some(attr) => Ok(Some(attr.unescape_value().map_err(xml_err)?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+/// Rewrite an existing PackageInfo, listing the given scripts in its
+/// `<scripts>` element.
+///
+/// The element is replaced in place when present and appended to the root
+/// element otherwise; everything else is passed through unchanged.
+pub fn update_packageinfo_scripts(
+    xml: &str,
+    has_preinstall: bool,
+    has_postinstall: bool,
+) -> Result<String, PackageError> {
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut depth = 0usize;
+    let mut scripts_done = false;
+
+    loop {
+        let event = reader.read_event().map_err(xml_err)?;
+        match event {
+            Event::Start(e) if depth == 1 && e.name().as_ref() == b"scripts" => {
+                reader.read_to_end(e.name()).map_err(xml_err)?;
+                if !scripts_done {
+                    write_scripts_element(&mut writer, has_preinstall, has_postinstall)?;
+                    scripts_done = true;
+                }
+            }
+            Event::Empty(e) if depth == 1 && e.name().as_ref() == b"scripts" => {
+                if !scripts_done {
+                    write_scripts_element(&mut writer, has_preinstall, has_postinstall)?;
+                    scripts_done = true;
+                }
+            }
+            Event::Start(e) => {
+                depth += 1;
+                write(&mut writer, Event::Start(e))?;
+            }
+            Event::End(e) => {
+                if depth == 1 && !scripts_done {
+                    write_scripts_element(&mut writer, has_preinstall, has_postinstall)?;
+                    scripts_done = true;
+                }
+                depth = depth.saturating_sub(1);
+                write(&mut writer, Event::End(e))?;
+            }
+            Event::Eof => break,
+            other => write(&mut writer, other)?,
+        }
+    }
 
-    write(
-        &mut writer,
-        Event::End(BytesEnd::new("installer-gui-script")),
-    )?;
     finalize(writer)
 }
 
+/// Rewrite an existing Distribution, setting `<title>` and `<license>`.
+///
+/// Elements are replaced in place when present and appended to the root
+/// element otherwise; everything else is passed through unchanged.
+pub fn update_distribution(
+    xml: &str,
+    title: Option<&str>,
+    license_file: Option<&str>,
+) -> Result<String, PackageError> {
+    let mut reader = Reader::from_str(xml);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut depth = 0usize;
+    let mut title_done = title.is_none();
+    let mut license_done = license_file.is_none();
+
+    loop {
+        let event = reader.read_event().map_err(xml_err)?;
+        match event {
+            Event::Start(e) if depth == 1 && e.name().as_ref() == b"title" && !title_done => {
+                reader.read_to_end(e.name()).map_err(xml_err)?;
+                write_text_element(&mut writer, "title", title.unwrap_or_default())?;
+                title_done = true;
+            }
+            Event::Start(e) if depth == 1 && e.name().as_ref() == b"license" && !license_done => {
+                reader.read_to_end(e.name()).map_err(xml_err)?;
+                write(
+                    &mut writer,
+                    Event::Empty(license_element(&e, license_file)?),
+                )?;
+                license_done = true;
+            }
+            Event::Empty(e) if depth == 1 && e.name().as_ref() == b"license" && !license_done => {
+                write(
+                    &mut writer,
+                    Event::Empty(license_element(&e, license_file)?),
+                )?;
+                license_done = true;
+            }
+            Event::Start(e) => {
+                depth += 1;
+                write(&mut writer, Event::Start(e))?;
+            }
+            Event::End(e) => {
+                if depth == 1 {
+                    if let (false, Some(title)) = (title_done, title) {
+                        write_text_element(&mut writer, "title", title)?;
+                    }
+                    if !license_done {
+                        write(
+                            &mut writer,
+                            Event::Empty(license_element(
+                                &BytesStart::new("license"),
+                                license_file,
+                            )?),
+                        )?;
+                    }
+                    title_done = true;
+                    license_done = true;
+                }
+                depth = depth.saturating_sub(1);
+                write(&mut writer, Event::End(e))?;
+            }
+            Event::Eof => break,
+            other => write(&mut writer, other)?,
+        }
+    }
+
+    finalize(writer)
+}
+
+/// Copy a `<license>` element, pointing its `file` attribute at `file`.
+fn license_element(
+    original: &BytesStart<'_>,
+    file: Option<&str>,
+) -> Result<BytesStart<'static>, PackageError> {
+    let mut element = BytesStart::new("license");
+    for attr in original.attributes() {
+        let attr: Attribute<'_> = attr.map_err(xml_err)?;
+        if attr.key.as_ref() != b"file" {
+            element.push_attribute(attr);
+        }
+    }
+    element.push_attribute(("file", file.unwrap_or_default()));
+    Ok(element.into_owned())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,8 +658,8 @@ mod tests {
     // T011: PackageInfo XML tests
     #[test]
     fn test_packageinfo_xml_declaration() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(
             xml.starts_with("<?xml"),
             "PackageInfo must start with XML declaration"
@@ -202,8 +668,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_root_element() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(
             xml.contains("<pkg-info"),
             "Must have <pkg-info> root element"
@@ -213,8 +679,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_format_version() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(
             xml.contains("format-version=\"2\""),
             "format-version must be 2"
@@ -223,8 +689,17 @@ mod tests {
 
     #[test]
     fn test_packageinfo_identifier() {
-        let xml = generate_packageinfo("com.example.myapp", "1.0.0", "/", 1024, 10, false, false)
-            .unwrap();
+        let xml = generate_packageinfo(
+            "com.example.myapp",
+            "1.0.0",
+            "/",
+            1024,
+            10,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert!(
             xml.contains("identifier=\"com.example.myapp\""),
             "identifier must be present"
@@ -233,8 +708,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_version() {
-        let xml =
-            generate_packageinfo("com.test.app", "2.5.3", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "2.5.3", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(xml.contains("version=\"2.5.3\""), "version must be present");
     }
 
@@ -248,6 +723,7 @@ mod tests {
             10,
             false,
             false,
+            None,
         )
         .unwrap();
         assert!(
@@ -256,17 +732,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_packageinfo_component_bundle() {
+        let bundle = AppBundle {
+            path: "/build/MyApp.app".into(),
+            identifier: "com.example.myapp".to_string(),
+            short_version: Some("2.1".to_string()),
+            bundle_version: None,
+        };
+        let xml = generate_packageinfo(
+            "com.example.myapp.pkg",
+            "2.1",
+            "/Applications",
+            1024,
+            10,
+            false,
+            false,
+            Some(&bundle),
+        )
+        .unwrap();
+
+        assert!(xml.contains(
+            r#"<bundle path="./MyApp.app" id="com.example.myapp" CFBundleShortVersionString="2.1"/>"#
+        ));
+        for list in ["bundle-version", "upgrade-bundle", "strict-identifier"] {
+            assert!(
+                xml.contains(&format!("<{}>", list)),
+                "{} must reference the bundle",
+                list
+            );
+        }
+        assert!(!xml.contains("relocate"));
+        assert_eq!(
+            xml.matches(r#"<bundle id="com.example.myapp"/>"#).count(),
+            3
+        );
+
+        let plain =
+            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+                .unwrap();
+        assert!(!plain.contains("<bundle"));
+    }
+
     #[test]
     fn test_packageinfo_auth() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(xml.contains("auth=\"root\""), "auth must be 'root'");
     }
 
     #[test]
     fn test_packageinfo_payload() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 2048, 25, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 2048, 25, false, false, None)
+            .unwrap();
         assert!(xml.contains("<payload"), "Must have <payload> element");
         assert!(
             xml.contains("installKBytes=\"2048\""),
@@ -280,8 +798,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_without_scripts() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, false, None)
+            .unwrap();
         assert!(
             !xml.contains("<scripts>"),
             "Should not have <scripts> without scripts"
@@ -290,8 +808,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_with_preinstall() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, true, false).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, true, false, None)
+            .unwrap();
         assert!(
             xml.contains("<scripts>"),
             "Must have <scripts> with preinstall"
@@ -304,8 +822,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_with_postinstall() {
-        let xml =
-            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, true).unwrap();
+        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, false, true, None)
+            .unwrap();
         assert!(
             xml.contains("<scripts>"),
             "Must have <scripts> with postinstall"
@@ -318,7 +836,8 @@ mod tests {
 
     #[test]
     fn test_packageinfo_with_both_scripts() {
-        let xml = generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, true, true).unwrap();
+        let xml =
+            generate_packageinfo("com.test.app", "1.0.0", "/", 1024, 10, true, true, None).unwrap();
         assert!(xml.contains("<preinstall"), "Must have <preinstall>");
         assert!(xml.contains("<postinstall"), "Must have <postinstall>");
     }
@@ -370,6 +889,44 @@ mod tests {
         assert!(xml.contains("<domains"), "Must have <domains> element");
     }
 
+    #[test]
+    fn test_custom_distribution() {
+        use crate::macos::resources::collect_resources;
+        use crate::models::macos::CustomizeMode;
+
+        let options = DistributionOptions::default()
+            .with_welcome("/tmp/Welcome.rtf")
+            .with_license("/tmp/EULA.txt")
+            .with_background("/tmp/logo.png")
+            .with_min_os_version("12.0")
+            .with_customize(CustomizeMode::Allow)
+            .with_domain(InstallDomain::CurrentUserHome);
+        let resources = collect_resources(&options).unwrap();
+        let xml = generate_custom_distribution(
+            "com.test.app",
+            "1.0.0",
+            1024,
+            &options,
+            &resources.elements,
+        )
+        .unwrap();
+        assert!(xml.contains("<title>com.test.app</title>"));
+        assert!(xml.contains(r#"<welcome file="Welcome.rtf"/>"#));
+        assert!(xml.contains(r#"<license file="EULA.txt"/>"#));
+        assert!(!xml.contains("<readme"));
+        assert!(xml.contains(r#"<background file="logo.png" alignment="bottomleft""#));
+        assert!(xml.contains(r#"customize="allow""#));
+        assert!(xml.contains(
+            r#"enable_anywhere="false" enable_currentUserHome="true" enable_localSystem="false""#
+        ));
+        assert!(xml.contains(r#"<os-version min="12.0"/>"#));
+
+        let default = generate_distribution("com.test.app", "My App", "1.0.0", 1024).unwrap();
+        assert!(default.contains(r#"customize="never""#));
+        assert!(default.contains(r#"enable_currentUserHome="false" enable_localSystem="true""#));
+        assert!(!default.contains("allowed-os-versions"));
+    }
+
     #[test]
     fn test_distribution_choices_outline() {
         let xml = generate_distribution("com.test.app", "My App", "1.0.0", 1024).unwrap();
@@ -414,4 +971,160 @@ mod tests {
             "pkg-ref must reference #base.pkg"
         );
     }
+
+    #[test]
+    fn test_product_distribution() {
+        let component = |identifier: &str, kbytes| DistributionRef {
+            identifier: identifier.to_string(),
+            version: "1.0".to_string(),
+            install_kbytes: kbytes,
+            path: format!("{}.pkg", identifier),
+            host_architecture: None,
+        };
+        let xml = generate_product_distribution(
+            "Suite",
+            &[
+                component("com.test.app", 100),
+                component("com.test.helper", 20),
+            ],
+        )
+        .unwrap();
+
+        assert!(xml.contains("<title>Suite</title>"));
+        assert!(xml.contains("<line choice=\"com.test.app\"/>"));
+        assert!(xml.contains("<line choice=\"com.test.helper\"/>"));
+        assert!(xml.contains("<choice id=\"com.test.helper\" visible=\"false\">"));
+        assert!(xml.contains(
+            "<pkg-ref id=\"com.test.app\" version=\"1.0\" installKBytes=\"100\">#com.test.app.pkg</pkg-ref>"
+        ));
+        assert!(xml.contains("#com.test.helper.pkg</pkg-ref>"));
+        assert!(!xml.contains("#base.pkg"));
+        assert!(xml.contains("hostArchitectures=\"x86_64,arm64\""));
+        assert!(!xml.contains("selected="));
+    }
+
+    #[test]
+    fn test_product_distribution_per_architecture() {
+        let component = |arch: HostArchitecture| DistributionRef {
+            identifier: format!("com.test.app.{}", arch),
+            version: "1.0".to_string(),
+            install_kbytes: 100,
+            path: format!("com.test.app.{}.pkg", arch),
+            host_architecture: Some(arch),
+        };
+        let xml = generate_product_distribution(
+            "App",
+            &[
+                component(HostArchitecture::X86_64),
+                component(HostArchitecture::Arm64),
+            ],
+        )
+        .unwrap();
+
+        assert!(xml.contains("hostArchitectures=\"arm64,x86_64\""));
+        assert!(xml.contains("<line choice=\"com.test.app.arm64\"/>"));
+
+        // The conditions survive escaping in the attribute
+        let mut reader = Reader::from_str(&xml);
+        let mut conditions = Vec::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(e) if e.name().as_ref() == b"choice" => {
+                    conditions.push(attribute(&e, "selected").unwrap());
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            conditions,
+            vec![
+                Some(HostArchitecture::X86_64.condition().to_string()),
+                Some(HostArchitecture::Arm64.condition().to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_distribution_replaces_and_adds() {
+        let xml = generate_distribution("com.test.app", "Old Title", "1.0.0", 1024).unwrap();
+
+        let updated = update_distribution(&xml, Some("New & Title"), Some("EULA.rtf")).unwrap();
+        assert!(updated.contains("<title>New &amp; Title</title>"));
+        assert!(!updated.contains("Old Title</title>"));
+        assert!(updated.contains(r#"<license file="EULA.rtf"/>"#));
+        assert!(updated.contains("<pkg-ref id=\"com.test.app\""));
+        assert_eq!(updated.matches("<title>").count(), 1);
+    }
+
+    #[test]
+    fn test_update_distribution_existing_license() {
+        let xml = r#"<installer-gui-script><license file="old.txt" mime-type="text/plain"/></installer-gui-script>"#;
+
+        let updated = update_distribution(xml, None, Some("EULA.rtf")).unwrap();
+        assert_eq!(
+            updated,
+            r#"<installer-gui-script><license mime-type="text/plain" file="EULA.rtf"/></installer-gui-script>"#
+        );
+        assert_eq!(update_distribution(xml, None, None).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_generate_payload_free_packageinfo() {
+        let xml = generate_payload_free_packageinfo("com.test.defaults", "1.0", "/", false, true)
+            .unwrap();
+        assert!(xml.contains(r#"identifier="com.test.defaults""#));
+        assert!(xml.contains(r#"<postinstall file="./postinstall"/>"#));
+        assert!(!xml.contains("<payload"));
+        assert!(!xml.contains("<preinstall"));
+        assert_eq!(parse_packageinfo(&xml).unwrap().install_kbytes, 0);
+    }
+
+    #[test]
+    fn test_update_packageinfo_scripts() {
+        let xml = r#"<pkg-info identifier="com.vendor.app"><payload numberOfFiles="3"/><scripts><preinstall file="./preinstall"/></scripts><bundle-version/></pkg-info>"#;
+
+        let updated = update_packageinfo_scripts(xml, true, true).unwrap();
+        assert_eq!(
+            updated,
+            r#"<pkg-info identifier="com.vendor.app"><payload numberOfFiles="3"/><scripts><preinstall file="./preinstall"/><postinstall file="./postinstall"/></scripts><bundle-version/></pkg-info>"#
+        );
+
+        // Added at the end when the component had no scripts
+        let xml = r#"<pkg-info identifier="com.vendor.app"><payload/></pkg-info>"#;
+        let updated = update_packageinfo_scripts(xml, false, true).unwrap();
+        assert_eq!(
+            updated,
+            r#"<pkg-info identifier="com.vendor.app"><payload/><scripts><postinstall file="./postinstall"/></scripts></pkg-info>"#
+        );
+        assert_eq!(
+            parse_packageinfo(&updated).unwrap().identifier,
+            "com.vendor.app"
+        );
+    }
+
+    #[test]
+    fn test_parse_packageinfo() {
+        let xml = generate_packageinfo("com.test.app", "2.1.0", "/", 2048, 10, false, false, None)
+            .unwrap();
+
+        assert_eq!(
+            parse_packageinfo(&xml).unwrap(),
+            PackageInfoSummary {
+                identifier: "com.test.app".to_string(),
+                version: "2.1.0".to_string(),
+                install_kbytes: 2048,
+            }
+        );
+        assert!(parse_packageinfo("<pkg-info/>").is_err());
+    }
+
+    #[test]
+    fn test_parse_distribution_refs() {
+        let xml = generate_distribution("com.test.app", "Test", "2.1.0", 2048).unwrap();
+        assert_eq!(
+            parse_distribution_refs(&xml).unwrap(),
+            vec![("com.test.app".to_string(), "2.1.0".to_string())]
+        );
+    }
 }
@@ -0,0 +1,225 @@
+//! Source folder analysis before packaging (`intune analyze`).
+
+use std::path::PathBuf;
+
+use crate::json::JsonValue;
+use crate::models::glob::{Glob, SourceFilter};
+use crate::models::package::PathIssue;
+
+/// Number of largest files reported unless told otherwise.
+pub const DEFAULT_TOP_FILES: usize = 10;
+
+/// Request to analyze a source folder.
+#[derive(Debug, Clone)]
+pub struct AnalyzeRequest {
+    /// Folder that would be packaged
+    pub source_folder: PathBuf,
+    /// Setup file, relative to the source folder
+    pub setup_file: String,
+    /// Which source files would be packaged
+    pub filter: SourceFilter,
+    /// Number of largest files to report
+    pub top: usize,
+}
+
+impl AnalyzeRequest {
+    /// Create a request for the same folder and setup file as a package.
+    pub fn new(source_folder: impl Into<PathBuf>, setup_file: impl Into<String>) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            setup_file: setup_file.into(),
+            filter: SourceFilter::default(),
+            top: DEFAULT_TOP_FILES,
+        }
+    }
+
+    /// Only analyze files matching `glob` (may be called repeatedly).
+    pub fn with_include(mut self, glob: Glob) -> Self {
+        self.filter.include.push(glob);
+        self
+    }
+
+    /// Leave out files matching `glob` (may be called repeatedly).
+    pub fn with_exclude(mut self, glob: Glob) -> Self {
+        self.filter.exclude.push(glob);
+        self
+    }
+
+    /// Set the number of largest files to report.
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = top;
+        self
+    }
+}
+
+/// Files with identical content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// SHA-256 of the content (hex)
+    pub sha256: String,
+    /// Size of each copy
+    pub size: u64,
+    /// Paths of the copies, with `/` separators, sorted
+    pub paths: Vec<String>,
+}
+
+impl DuplicateGroup {
+    /// Bytes that packaging the copies beyond the first adds.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// Size of one source file and how well it is expected to compress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEstimate {
+    /// Path with `/` separators
+    pub path: String,
+    /// Size on disk
+    pub size: u64,
+    /// Estimated size once deflated
+    pub estimated_compressed: u64,
+}
+
+impl FileEstimate {
+    /// Estimated compressed size as a percentage of the original size.
+    pub fn ratio_percent(&self) -> u64 {
+        percent(self.estimated_compressed, self.size)
+    }
+}
+
+/// What a source folder holds, as far as package size is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceAnalysis {
+    /// Folder analyzed
+    pub source_folder: PathBuf,
+    /// Number of files that would be packaged
+    pub file_count: usize,
+    /// Total size of those files
+    pub total_size: u64,
+    /// Estimated size of the compressed content
+    pub estimated_compressed: u64,
+    /// Files with identical content, most wasted bytes first
+    pub duplicates: Vec<DuplicateGroup>,
+    /// Paths that would not install on Windows
+    pub path_issues: Vec<PathIssue>,
+    /// Largest files, largest first
+    pub largest: Vec<FileEstimate>,
+}
+
+impl SourceAnalysis {
+    /// Bytes taken up by duplicate copies.
+    pub fn duplicate_bytes(&self) -> u64 {
+        self.duplicates.iter().map(DuplicateGroup::wasted).sum()
+    }
+
+    /// Estimated compressed size as a percentage of the total size.
+    pub fn ratio_percent(&self) -> u64 {
+        percent(self.estimated_compressed, self.total_size)
+    }
+
+    /// Serialize the analysis as JSON.
+    pub fn to_json(&self) -> String {
+        let duplicates = self
+            .duplicates
+            .iter()
+            .map(|group| {
+                JsonValue::Object(vec![
+                    ("sha256".to_string(), group.sha256.clone().into()),
+                    ("size".to_string(), group.size.into()),
+                    ("wasted".to_string(), group.wasted().into()),
+                    (
+                        "paths".to_string(),
+                        JsonValue::Array(group.paths.iter().map(|p| p.clone().into()).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        let largest = self
+            .largest
+            .iter()
+            .map(|file| {
+                JsonValue::Object(vec![
+                    ("path".to_string(), file.path.clone().into()),
+                    ("size".to_string(), file.size.into()),
+                    (
+                        "estimated_compressed".to_string(),
+                        file.estimated_compressed.into(),
+                    ),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            (
+                "source_folder".to_string(),
+                self.source_folder.to_string_lossy().into_owned().into(),
+            ),
+            ("file_count".to_string(), (self.file_count as u64).into()),
+            ("total_size".to_string(), self.total_size.into()),
+            (
+                "estimated_compressed".to_string(),
+                self.estimated_compressed.into(),
+            ),
+            ("duplicate_bytes".to_string(), self.duplicate_bytes().into()),
+            ("duplicates".to_string(), JsonValue::Array(duplicates)),
+            ("largest".to_string(), JsonValue::Array(largest)),
+            (
+                "path_issues".to_string(),
+                JsonValue::Array(
+                    self.path_issues
+                        .iter()
+                        .map(PathIssue::to_json_value)
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_pretty_string()
+    }
+}
+
+fn percent(part: u64, whole: u64) -> u64 {
+    if whole == 0 {
+        100
+    } else {
+        ((part as u128 * 100) / whole as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_duplicate_bytes() {
+        let analysis = SourceAnalysis {
+            source_folder: PathBuf::from("src"),
+            file_count: 4,
+            total_size: 400,
+            estimated_compressed: 100,
+            duplicates: vec![DuplicateGroup {
+                sha256: "ab".repeat(32),
+                size: 100,
+                paths: vec!["a/x.dll".into(), "b/x.dll".into(), "c/x.dll".into()],
+            }],
+            largest: Vec::new(),
+            path_issues: Vec::new(),
+        };
+
+        assert_eq!(analysis.duplicate_bytes(), 200);
+        assert_eq!(analysis.ratio_percent(), 25);
+
+        let value = json::parse(&analysis.to_json()).unwrap();
+        assert_eq!(value.get("duplicate_bytes").unwrap().as_u64(), Some(200));
+        assert_eq!(
+            value.get("duplicates").unwrap().as_array().unwrap()[0]
+                .get("paths")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
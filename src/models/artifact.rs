@@ -0,0 +1,189 @@
+//! Artifact metadata: the version record embedded in every package.
+//!
+//! Both artifact types carry the same record so fleet tooling can treat
+//! them alike: `.intunewin` files as `Metadata/ArtifactInfo.xml` in the
+//! outer ZIP, `.pkg` files as an `<ArtifactInfo>` element in the XAR TOC.
+//! Both use [`ARTIFACT_ELEMENT`] in the [`ARTIFACT_NAMESPACE`] namespace
+//! with one child element per field, so other readers can skip it safely.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::json::JsonValue;
+
+/// Version of the artifact record layout.
+pub const ARTIFACT_SCHEMA_VERSION: u64 = 1;
+
+/// Name of the element holding the record.
+pub const ARTIFACT_ELEMENT: &str = "ArtifactInfo";
+
+/// XML namespace of the record.
+pub const ARTIFACT_NAMESPACE: &str = "urn:iamawrapper:artifact";
+
+/// The version record of a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactMetadata {
+    /// Version of the tool that built the package
+    pub tool_version: String,
+    /// Version of the record layout
+    pub schema_version: u64,
+    /// Seconds since the Unix epoch when the package was built
+    pub created: u64,
+    /// SHA-256 of the packaged content's manifest
+    pub manifest_sha256: String,
+}
+
+impl ArtifactMetadata {
+    /// Record for a package built now by this tool.
+    pub fn new(manifest_sha256: impl Into<String>) -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: ARTIFACT_SCHEMA_VERSION,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            manifest_sha256: manifest_sha256.into(),
+        }
+    }
+
+    /// Child element names and values, in the order they are written.
+    pub fn fields(&self) -> [(&'static str, String); 4] {
+        [
+            ("ToolVersion", self.tool_version.clone()),
+            ("SchemaVersion", self.schema_version.to_string()),
+            ("Created", self.created.to_string()),
+            ("ManifestSha256", self.manifest_sha256.clone()),
+        ]
+    }
+
+    /// Rebuild a record from child element names and values.
+    ///
+    /// Unknown fields are ignored so newer schema versions stay readable.
+    pub fn from_fields<'a>(
+        fields: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut tool_version = None;
+        let mut schema_version = None;
+        let mut created = None;
+        let mut manifest_sha256 = None;
+
+        let number = |name: &str, value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("{} is not a number: '{}'", name, value))
+        };
+
+        for (name, value) in fields {
+            match name {
+                "ToolVersion" => tool_version = Some(value.to_string()),
+                "SchemaVersion" => schema_version = Some(number(name, value)?),
+                "Created" => created = Some(number(name, value)?),
+                "ManifestSha256" => manifest_sha256 = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        let missing = |name: &str| format!("{} is missing {}", ARTIFACT_ELEMENT, name);
+        Ok(Self {
+            tool_version: tool_version.ok_or_else(|| missing("ToolVersion"))?,
+            schema_version: schema_version.ok_or_else(|| missing("SchemaVersion"))?,
+            created: created.ok_or_else(|| missing("Created"))?,
+            manifest_sha256: manifest_sha256.ok_or_else(|| missing("ManifestSha256"))?,
+        })
+    }
+
+    /// The record as a JSON object.
+    pub fn to_json_value(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("tool_version".to_string(), self.tool_version.clone().into()),
+            ("schema_version".to_string(), self.schema_version.into()),
+            ("created".to_string(), self.created.into()),
+            (
+                "manifest_sha256".to_string(),
+                self.manifest_sha256.clone().into(),
+            ),
+        ])
+    }
+}
+
+/// Type of a package file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// `.intunewin` package
+    Intune,
+    /// macOS flat package
+    MacosPkg,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArtifactKind::Intune => "intunewin",
+            ArtifactKind::MacosPkg => "pkg",
+        })
+    }
+}
+
+/// What `inspect` found in a package file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactReport {
+    /// The inspected file
+    pub path: PathBuf,
+    /// Package type
+    pub kind: ArtifactKind,
+    /// The embedded record; `None` for packages built by other tools or
+    /// older versions
+    pub metadata: Option<ArtifactMetadata>,
+}
+
+impl ArtifactReport {
+    /// Serialize the report as JSON.
+    pub fn to_json(&self) -> String {
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata.to_json_value(),
+            None => JsonValue::Null,
+        };
+        JsonValue::Object(vec![
+            (
+                "path".to_string(),
+                self.path.to_string_lossy().into_owned().into(),
+            ),
+            ("kind".to_string(), self.kind.to_string().into()),
+            ("metadata".to_string(), metadata),
+        ])
+        .to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_roundtrip() {
+        let metadata = ArtifactMetadata::new("ab".repeat(32));
+        assert_eq!(metadata.schema_version, ARTIFACT_SCHEMA_VERSION);
+        assert_eq!(metadata.tool_version, env!("CARGO_PKG_VERSION"));
+
+        let fields = metadata.fields();
+        let parsed = ArtifactMetadata::from_fields(
+            fields
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .chain([("FutureField", "x")]),
+        )
+        .unwrap();
+        assert_eq!(parsed, metadata);
+    }
+
+    #[test]
+    fn test_from_fields_rejects_incomplete_records() {
+        let err = ArtifactMetadata::from_fields([("ToolVersion", "1.0.0")]).unwrap_err();
+        assert!(err.contains("SchemaVersion"));
+
+        let err = ArtifactMetadata::from_fields([("Created", "yesterday")]).unwrap_err();
+        assert!(err.contains("not a number"));
+    }
+}
@@ -0,0 +1,639 @@
+//! Batch manifests listing several applications to package
+//! (`intune batch`, `macos batch`).
+//!
+//! A manifest is CSV (a header row naming the fields), JSON (an array of
+//! objects, or an object with an `apps` array) or TOML (`[[apps]]` tables):
+//!
+//! ```toml
+//! [[apps]]
+//! name = "7-Zip"
+//! source = "apps/7zip"
+//! setup = "7z2301-x64.msi"
+//! ```
+//!
+//! Relative paths are resolved against the manifest's folder.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::json::{self, JsonValue};
+use crate::models::error::PackageError;
+#[cfg(feature = "macos")]
+use crate::models::macos::MacosPkgRequest;
+use crate::models::package::{PackageRequest, Verbosity};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+use crate::toml;
+
+/// Fields an `intune batch` item may set.
+pub const INTUNE_FIELDS: &[&str] = &["name", "source", "setup", "output", "output_name"];
+
+/// Fields a `macos batch` item may set.
+pub const MACOS_FIELDS: &[&str] = &[
+    "name",
+    "source",
+    "identifier",
+    "version",
+    "output",
+    "output_name",
+    "install_location",
+    "scripts",
+];
+
+/// Table holding the items of a TOML manifest, and their key in JSON.
+const ITEMS_KEY: &str = "apps";
+
+/// Syntax of a batch manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchFormat {
+    Csv,
+    Json,
+    Toml,
+}
+
+impl BatchFormat {
+    /// Pick the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(BatchFormat::Csv),
+            "json" => Some(BatchFormat::Json),
+            "toml" => Some(BatchFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// One application listed in a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchItem {
+    /// Field names and values, in manifest order
+    pub fields: Vec<(String, String)>,
+}
+
+impl BatchItem {
+    /// Value of a field; empty values count as unset.
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    /// An item from the members of a JSON object; `null` members are left
+    /// out.
+    pub fn from_json_members(members: &[(String, JsonValue)]) -> Result<Self, String> {
+        let mut item = BatchItem::default();
+        for (name, value) in members {
+            let value = match value {
+                JsonValue::String(value) => value.clone(),
+                JsonValue::Bool(value) => value.to_string(),
+                JsonValue::Number(value) => value.to_string(),
+                JsonValue::Null => continue,
+                _ => return Err(format!("'{}' must be a string, number or boolean", name)),
+            };
+            item.fields.push((name.clone(), value));
+        }
+        Ok(item)
+    }
+
+    /// Name shown in reports: the `name` field, else the source folder.
+    pub fn label(&self, index: usize) -> String {
+        self.get("name")
+            .or_else(|| self.get("source"))
+            .map(String::from)
+            .unwrap_or_else(|| format!("#{}", index + 1))
+    }
+
+    fn require(&self, field: &str) -> Result<&str, String> {
+        self.get(field)
+            .ok_or_else(|| format!("Missing required field '{}'", field))
+    }
+
+    /// A path field, relative to `base` unless absolute.
+    fn path(&self, field: &str, base: &Path) -> Option<PathBuf> {
+        self.get(field).map(|value| base.join(value))
+    }
+
+    fn check_fields(&self, allowed: &[&str]) -> Result<(), String> {
+        match self
+            .fields
+            .iter()
+            .find(|(name, _)| !allowed.contains(&name.as_str()))
+        {
+            Some((name, _)) => Err(format!(
+                "Unknown field '{}' (expected one of: {})",
+                name,
+                allowed.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Output folder of the item, or `default` when it names none.
+    fn output(&self, base: &Path, default: Option<&Path>) -> Result<PathBuf, String> {
+        self.path("output", base)
+            .or_else(|| default.map(Path::to_path_buf))
+            .ok_or_else(|| "Missing required field 'output' (or pass --output)".to_string())
+    }
+
+    /// Build the Intune request for this item.
+    pub fn to_package_request(
+        &self,
+        base: &Path,
+        default_output: Option<&Path>,
+    ) -> Result<PackageRequest, String> {
+        self.check_fields(INTUNE_FIELDS)?;
+        let mut request = PackageRequest::new(
+            base.join(self.require("source")?),
+            self.require("setup")?.to_string(),
+            self.output(base, default_output)?,
+        );
+        if let Some(name) = self.get("output_name") {
+            request.set_output_name(name);
+        }
+        Ok(request)
+    }
+
+    /// Build the macOS request for this item.
+    #[cfg(feature = "macos")]
+    pub fn to_macos_request(
+        &self,
+        base: &Path,
+        default_output: Option<&Path>,
+    ) -> Result<MacosPkgRequest, String> {
+        self.check_fields(MACOS_FIELDS)?;
+        let mut request = MacosPkgRequest::new(
+            base.join(self.require("source")?),
+            self.require("identifier")?,
+            self.require("version")?,
+            self.output(base, default_output)?,
+        );
+        if let Some(name) = self.get("output_name") {
+            request.set_output_name(name);
+        }
+        if let Some(location) = self.get("install_location") {
+            request.set_install_location(location);
+        }
+        if let Some(scripts) = self.path("scripts", base) {
+            request.set_scripts_folder(scripts);
+        }
+        Ok(request)
+    }
+}
+
+/// The applications listed in a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchManifest {
+    /// Folder relative paths are resolved against
+    pub base: PathBuf,
+    /// Items in manifest order
+    pub items: Vec<BatchItem>,
+}
+
+impl BatchManifest {
+    /// Parse a manifest; `base` stays empty.
+    pub fn parse(text: &str, format: BatchFormat) -> Result<Self, String> {
+        let text = text.trim_start_matches('\u{feff}');
+        let items = match format {
+            BatchFormat::Csv => parse_csv(text)?,
+            BatchFormat::Json => parse_json(text)?,
+            BatchFormat::Toml => parse_toml(text)?,
+        };
+        if items.is_empty() {
+            return Err("The manifest lists no applications".to_string());
+        }
+        Ok(Self {
+            base: PathBuf::new(),
+            items,
+        })
+    }
+
+    /// Resolve relative paths against `base`.
+    pub fn with_base(mut self, base: impl Into<PathBuf>) -> Self {
+        self.base = base.into();
+        self
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Vec<BatchItem>, String> {
+    let mut header: Option<Vec<String>> = None;
+    let mut items = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let values = split_csv_line(trimmed).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        let Some(names) = &header else {
+            header = Some(values.iter().map(|v| v.trim().to_string()).collect());
+            continue;
+        };
+        if values.len() != names.len() {
+            return Err(format!(
+                "Line {}: expected {} fields, found {}",
+                index + 1,
+                names.len(),
+                values.len()
+            ));
+        }
+        items.push(BatchItem {
+            fields: names.iter().cloned().zip(values).collect(),
+        });
+    }
+
+    Ok(items)
+}
+
+/// Split a CSV line; fields may be quoted, with `""` for a quote.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("Unterminated quoted field".to_string()),
+                }
+            }
+            while chars.peek().is_some_and(|c| *c != ',') {
+                chars.next();
+            }
+        } else {
+            while let Some(c) = chars.peek().filter(|c| **c != ',') {
+                field.push(*c);
+                chars.next();
+            }
+            field = field.trim_end().to_string();
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+fn parse_json(text: &str) -> Result<Vec<BatchItem>, String> {
+    let value = json::parse(text)?;
+    let entries = match &value {
+        JsonValue::Array(entries) => entries.as_slice(),
+        JsonValue::Object(_) => value
+            .get(ITEMS_KEY)
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| format!("Expected an '{}' array", ITEMS_KEY))?,
+        _ => return Err("Expected an array of applications".to_string()),
+    };
+
+    let mut items = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let members = entry
+            .as_object()
+            .ok_or_else(|| format!("Application #{} must be an object", index + 1))?;
+        let item = BatchItem::from_json_members(members)
+            .map_err(|e| format!("Application #{}: {}", index + 1, e))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Parse the TOML subset manifests use: `[[apps]]` tables of
+/// `key = value` lines with string, number or boolean values.
+fn parse_toml(text: &str) -> Result<Vec<BatchItem>, String> {
+    let table_header = format!("[[{}]]", ITEMS_KEY);
+    let mut items: Vec<BatchItem> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |reason: &str| format!("Line {}: {}", index + 1, reason);
+        let line = toml::strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            if line.replace(' ', "") != table_header {
+                return Err(error(&format!(
+                    "Only {} tables are supported, found {}",
+                    table_header, line
+                )));
+            }
+            items.push(BatchItem::default());
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("Expected 'key = value'"))?;
+        let item = items
+            .last_mut()
+            .ok_or_else(|| error(&format!("Keys must follow a {} header", table_header)))?;
+        let key = toml::parse_key(key.trim()).ok_or_else(|| error("Invalid key"))?;
+        let value = toml_value(value.trim()).ok_or_else(|| {
+            error(&format!(
+                "Value of '{}' must be a string, number or boolean",
+                key
+            ))
+        })?;
+        if item.fields.iter().any(|(name, _)| *name == key) {
+            return Err(error(&format!("Duplicate key '{}'", key)));
+        }
+        item.fields.push((key, value));
+    }
+
+    Ok(items)
+}
+
+fn toml_value(value: &str) -> Option<String> {
+    if let Some(string) = toml::parse_string(value) {
+        return Some(string);
+    }
+    if value == "true" || value == "false" || value.replace('_', "").parse::<f64>().is_ok() {
+        return Some(value.replace('_', ""));
+    }
+    None
+}
+
+/// Settings shared by every item of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Items packaged at once (0 = one per CPU)
+    pub jobs: usize,
+    /// Output folder for items that name none
+    pub output_folder: Option<PathBuf>,
+    /// Verbosity of the run; items themselves are packaged silently
+    pub verbosity: Verbosity,
+    /// Which warnings fail an item
+    pub strictness: Strictness,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            output_folder: None,
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+}
+
+impl BatchOptions {
+    /// Set how many items are packaged at once (0 = one per CPU).
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Set the output folder for items that name none.
+    pub fn with_output_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_folder = Some(path.into());
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail an item.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+}
+
+/// How one item of a batch ended.
+#[derive(Debug)]
+pub enum BatchOutcome {
+    /// The package was written
+    Packaged {
+        output_path: PathBuf,
+        package_size: ByteSize,
+    },
+    /// The item could not be packaged
+    Failed(PackageError),
+}
+
+/// Result of one item of a batch.
+#[derive(Debug)]
+pub struct BatchItemResult {
+    /// Name of the item (see [`BatchItem::label`])
+    pub name: String,
+    /// Package written, or why none was
+    pub outcome: BatchOutcome,
+    /// Time spent on the item
+    pub duration: Duration,
+}
+
+impl BatchItemResult {
+    /// Whether the package was written.
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, BatchOutcome::Packaged { .. })
+    }
+}
+
+/// Results of every item of a batch, in manifest order.
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Item results
+    pub items: Vec<BatchItemResult>,
+    /// Time taken for the whole batch
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// Number of items that failed.
+    pub fn failed(&self) -> usize {
+        self.items.iter().filter(|item| !item.succeeded()).count()
+    }
+
+    /// Whether every item was packaged.
+    pub fn passed(&self) -> bool {
+        self.failed() == 0
+    }
+
+    /// Serialize the report as JSON.
+    pub fn to_json(&self) -> String {
+        let items = self
+            .items
+            .iter()
+            .map(|item| {
+                let mut members = vec![
+                    ("name".to_string(), item.name.clone().into()),
+                    ("succeeded".to_string(), item.succeeded().into()),
+                ];
+                match &item.outcome {
+                    BatchOutcome::Packaged {
+                        output_path,
+                        package_size,
+                    } => {
+                        members.push((
+                            "output".to_string(),
+                            output_path.to_string_lossy().into_owned().into(),
+                        ));
+                        members.push(("size".to_string(), package_size.0.into()));
+                    }
+                    BatchOutcome::Failed(error) => {
+                        members.push(("error".to_string(), error.to_string().into()));
+                        members.push(("error_code".to_string(), error.error_code().name().into()));
+                    }
+                }
+                members.push((
+                    "milliseconds".to_string(),
+                    (item.duration.as_millis() as u64).into(),
+                ));
+                JsonValue::Object(members)
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            ("total".to_string(), (self.items.len() as u64).into()),
+            ("failed".to_string(), (self.failed() as u64).into()),
+            ("items".to_string(), JsonValue::Array(items)),
+        ])
+        .to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(item: &BatchItem) -> Vec<(&str, &str)> {
+        item.fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let manifest = BatchManifest::parse(
+            "name,source,setup\n# comment\n\"Tool, Inc\",apps/tool,setup.exe\nOther,apps/other,\"in \"\"q\"\".msi\"\n",
+            BatchFormat::Csv,
+        )
+        .unwrap();
+        assert_eq!(manifest.items.len(), 2);
+        assert_eq!(
+            fields(&manifest.items[0]),
+            vec![
+                ("name", "Tool, Inc"),
+                ("source", "apps/tool"),
+                ("setup", "setup.exe")
+            ]
+        );
+        assert_eq!(manifest.items[1].get("setup"), Some("in \"q\".msi"));
+
+        assert!(BatchManifest::parse("a,b\n1\n", BatchFormat::Csv).is_err());
+        assert!(BatchManifest::parse("a,b\n", BatchFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let text = r#"{"apps": [{"source": "a", "setup": "a.exe", "version": 2, "skip": null}]}"#;
+        let manifest = BatchManifest::parse(text, BatchFormat::Json).unwrap();
+        assert_eq!(
+            fields(&manifest.items[0]),
+            vec![("source", "a"), ("setup", "a.exe"), ("version", "2")]
+        );
+
+        let text = r#"[{"source": "a"}, {"source": "b"}]"#;
+        assert_eq!(
+            BatchManifest::parse(text, BatchFormat::Json)
+                .unwrap()
+                .items
+                .len(),
+            2
+        );
+        assert!(BatchManifest::parse(r#"[{"source": ["a"]}]"#, BatchFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let text = r#"
+# Applications
+[[apps]]
+name = "Tool #1" # trailing comment
+source = 'apps\tool'
+"quoted-key" = true
+
+[[ apps ]]
+source = "b"
+version = 1_000
+"#;
+        let manifest = BatchManifest::parse(text, BatchFormat::Toml).unwrap();
+        assert_eq!(
+            fields(&manifest.items[0]),
+            vec![
+                ("name", "Tool #1"),
+                ("source", "apps\\tool"),
+                ("quoted-key", "true")
+            ]
+        );
+        assert_eq!(manifest.items[1].get("version"), Some("1000"));
+
+        for bad in [
+            "source = \"a\"",
+            "[apps]\nsource = \"a\"",
+            "[[apps]]\nsource = [1]",
+            "[[apps]]\nsource = \"a\"\nsource = \"b\"",
+        ] {
+            assert!(
+                BatchManifest::parse(bad, BatchFormat::Toml).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_package_request() {
+        let item = BatchItem {
+            fields: vec![
+                ("source".to_string(), "apps/tool".to_string()),
+                ("setup".to_string(), "setup.exe".to_string()),
+                ("output_name".to_string(), "tool".to_string()),
+            ],
+        };
+        let base = Path::new("/manifests");
+        let request = item
+            .to_package_request(base, Some(Path::new("/out")))
+            .unwrap();
+        assert_eq!(request.source_folder, PathBuf::from("/manifests/apps/tool"));
+        assert_eq!(request.output_path(), PathBuf::from("/out/tool.intunewin"));
+        assert_eq!(item.label(0), "apps/tool");
+
+        assert!(item.to_package_request(base, None).is_err());
+        let mut unknown = item.clone();
+        unknown
+            .fields
+            .push(("identifier".to_string(), "com.x".to_string()));
+        assert!(
+            unknown
+                .to_package_request(base, Some(Path::new("/out")))
+                .unwrap_err()
+                .contains("Unknown field 'identifier'")
+        );
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            BatchFormat::from_path(Path::new("apps.TOML")),
+            Some(BatchFormat::Toml)
+        );
+        assert_eq!(BatchFormat::from_path(Path::new("apps.yaml")), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! Known-bad file hash lists.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::json::{self, JsonValue};
+use crate::models::strictness::{Check, Strictness};
+
+/// What to do when a blocklisted file is found in the content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlocklistAction {
+    /// Abort packaging
+    #[default]
+    Fail,
+    /// Package anyway and report the matches
+    Warn,
+}
+
+impl BlocklistAction {
+    /// The action to apply under `strictness`: warn mode fails when the
+    /// `blocklist` check is an error.
+    pub fn under(self, strictness: &Strictness) -> Self {
+        if strictness.is_error(Check::Blocklist) {
+            BlocklistAction::Fail
+        } else {
+            self
+        }
+    }
+}
+
+/// A content file whose hash matched a blocklist entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlocklistMatch {
+    /// Path relative to the source root
+    pub path: PathBuf,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: String,
+    /// Description from the blocklist entry, if any
+    pub description: Option<String>,
+}
+
+/// A set of known-bad SHA-256 hashes.
+///
+/// Accepts either CSV (`hash[,description]` per line, optional header,
+/// `#` comments) or JSON (an array of hash strings, an array of objects
+/// with `sha256`/`hash` and optional `description`/`name` keys, or an
+/// object wrapping such an array under `hashes`).
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    entries: HashMap<String, Option<String>>,
+}
+
+impl Blocklist {
+    /// Parse a blocklist, detecting JSON by its leading bracket.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let trimmed = text.trim_start_matches('\u{feff}').trim_start();
+        if trimmed.starts_with('[') || trimmed.starts_with('{') {
+            Self::parse_json(trimmed)
+        } else {
+            Self::parse_csv(trimmed)
+        }
+    }
+
+    /// Parse a CSV blocklist.
+    pub fn parse_csv(text: &str) -> Result<Self, String> {
+        let mut list = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.splitn(2, ',');
+            let hash = fields.next().unwrap_or("").trim().trim_matches('"');
+            let description = fields
+                .next()
+                .map(|d| d.trim().trim_matches('"').to_string())
+                .filter(|d| !d.is_empty());
+
+            if !is_sha256_hex(hash) {
+                // Tolerate a header row before the first hash
+                if list.is_empty() && !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    continue;
+                }
+                return Err(format!(
+                    "Line {}: '{}' is not a SHA-256 hash",
+                    index + 1,
+                    hash
+                ));
+            }
+
+            list.insert(hash, description);
+        }
+
+        Ok(list)
+    }
+
+    /// Parse a JSON blocklist.
+    pub fn parse_json(text: &str) -> Result<Self, String> {
+        let value = json::parse(text)?;
+        let items = match &value {
+            JsonValue::Array(items) => items.as_slice(),
+            JsonValue::Object(_) => value
+                .get("hashes")
+                .and_then(JsonValue::as_array)
+                .ok_or("Expected a 'hashes' array")?,
+            _ => return Err("Expected an array of hashes".to_string()),
+        };
+
+        let mut list = Self::default();
+        for item in items {
+            let (hash, description) = match item {
+                JsonValue::String(hash) => (hash.as_str(), None),
+                JsonValue::Object(_) => {
+                    let hash = item
+                        .get("sha256")
+                        .or_else(|| item.get("hash"))
+                        .and_then(JsonValue::as_str)
+                        .ok_or("Blocklist entry is missing a 'sha256' value")?;
+                    let description = item
+                        .get("description")
+                        .or_else(|| item.get("name"))
+                        .and_then(JsonValue::as_str)
+                        .map(String::from);
+                    (hash, description)
+                }
+                _ => return Err("Blocklist entries must be strings or objects".to_string()),
+            };
+
+            if !is_sha256_hex(hash) {
+                return Err(format!("'{}' is not a SHA-256 hash", hash));
+            }
+            list.insert(hash, description);
+        }
+
+        Ok(list)
+    }
+
+    fn insert(&mut self, hash: &str, description: Option<String>) {
+        self.entries.insert(hash.to_ascii_lowercase(), description);
+    }
+
+    /// Check a hex SHA-256 against the list.
+    ///
+    /// Returns `Some(description)` when the hash is blocklisted.
+    pub fn lookup(&self, sha256: &str) -> Option<Option<&str>> {
+        self.entries
+            .get(&sha256.to_ascii_lowercase())
+            .map(|d| d.as_deref())
+    }
+
+    /// Number of hashes in the list.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the list has no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_A: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    const HASH_B: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn test_parse_csv_with_header_and_comments() {
+        let text = format!(
+            "sha256,description\n# comment\n{},Known dropper\n{}\n",
+            HASH_A.to_uppercase(),
+            HASH_B
+        );
+        let list = Blocklist::parse(&text).unwrap();
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.lookup(HASH_A), Some(Some("Known dropper")));
+        assert_eq!(list.lookup(HASH_B), Some(None));
+    }
+
+    #[test]
+    fn test_parse_csv_invalid_hash() {
+        let text = format!("{}\nnot-a-hash\n", HASH_A);
+        assert!(Blocklist::parse(&text).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_variants() {
+        let list = Blocklist::parse(&format!("[\"{}\"]", HASH_A)).unwrap();
+        assert!(list.lookup(HASH_A).is_some());
+
+        let list = Blocklist::parse(&format!(
+            r#"{{"hashes": [{{"sha256": "{}", "description": "CVE-2024-0001"}}]}}"#,
+            HASH_B
+        ))
+        .unwrap();
+        assert_eq!(list.lookup(HASH_B), Some(Some("CVE-2024-0001")));
+        assert!(list.lookup(HASH_A).is_none());
+    }
+
+    #[test]
+    fn test_parse_json_invalid_entry() {
+        assert!(Blocklist::parse("[{\"name\": \"x\"}]").is_err());
+        assert!(Blocklist::parse("[42]").is_err());
+    }
+}
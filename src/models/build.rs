@@ -0,0 +1,312 @@
+//! Building one application for several platforms from a single
+//! configuration (`build`).
+//!
+//! The `build` section of the configuration file holds the settings its
+//! targets share, and a table per target with the fields of that format's
+//! batch manifest entry (see [`INTUNE_FIELDS`] and [`MACOS_FIELDS`]):
+//!
+//! ```toml
+//! [build]
+//! name = "Contoso Tool"
+//! version = "1.2.0"
+//! source = "dist"
+//! output = "out"
+//!
+//! [build.intune]
+//! source = "windows"
+//! setup = "setup.exe"
+//!
+//! [build.macos]
+//! source = "mac"
+//! identifier = "com.contoso.tool"
+//! ```
+//!
+//! A target's `source` is a folder under the shared one, so each platform
+//! can keep its own root. Shared `version`, `output` and `output_name`
+//! apply to every target that takes them and does not set its own.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::json::JsonValue;
+use crate::models::batch::{BatchItem, INTUNE_FIELDS, MACOS_FIELDS};
+use crate::models::package::Verbosity;
+use crate::models::strictness::Strictness;
+
+/// Settings of the `build` section shared by every target.
+pub const SHARED_FIELDS: &[&str] = &["name", "version", "source", "output", "output_name"];
+
+/// A package format `build` can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TargetKind {
+    /// Microsoft Intune `.intunewin`
+    IntuneWin,
+    /// macOS flat package `.pkg`
+    MacPkg,
+}
+
+impl TargetKind {
+    /// Every target, in build order.
+    pub const ALL: [TargetKind; 2] = [TargetKind::IntuneWin, TargetKind::MacPkg];
+
+    /// Name of the target's table in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            TargetKind::IntuneWin => "intune",
+            TargetKind::MacPkg => "macos",
+        }
+    }
+
+    /// Fields the target's table may set.
+    pub fn fields(self) -> &'static [&'static str] {
+        match self {
+            TargetKind::IntuneWin => INTUNE_FIELDS,
+            TargetKind::MacPkg => MACOS_FIELDS,
+        }
+    }
+}
+
+impl fmt::Display for TargetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TargetKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TargetKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown target '{}' (expected one of: {})",
+                    s,
+                    TargetKind::ALL.map(TargetKind::name).join(", ")
+                )
+            })
+    }
+}
+
+/// The `build` section of a configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildConfig {
+    /// Settings shared by the targets (see [`SHARED_FIELDS`])
+    pub shared: BatchItem,
+    /// Targets in configuration order, with the fields of their tables
+    pub targets: Vec<(TargetKind, BatchItem)>,
+}
+
+impl BuildConfig {
+    /// Read the `build` section of parsed configuration.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let members = value
+            .as_object()
+            .ok_or("'build' must be an object of settings and targets")?;
+
+        let mut config = Self::default();
+        let mut shared = Vec::new();
+        for (name, value) in members {
+            if let Ok(kind) = name.parse::<TargetKind>() {
+                if config.targets.iter().any(|(other, _)| *other == kind) {
+                    return Err(format!("Target 'build.{}' is listed twice", kind));
+                }
+                let table = value
+                    .as_object()
+                    .ok_or_else(|| format!("'build.{}' must be an object", kind))?;
+                let item = BatchItem::from_json_members(table)
+                    .map_err(|e| format!("build.{}: {}", kind, e))?;
+                config.targets.push((kind, item));
+            } else if SHARED_FIELDS.contains(&name.as_str()) {
+                shared.push((name.clone(), value.clone()));
+            } else {
+                return Err(format!(
+                    "Unknown key 'build.{}' (expected a target or one of: {})",
+                    name,
+                    SHARED_FIELDS.join(", ")
+                ));
+            }
+        }
+        config.shared =
+            BatchItem::from_json_members(&shared).map_err(|e| format!("build: {}", e))?;
+        Ok(config)
+    }
+
+    /// The application name, if set.
+    pub fn name(&self) -> Option<&str> {
+        self.shared.get("name")
+    }
+
+    /// The fields of a target's table completed with the shared settings:
+    /// its source under the shared one, and the shared fields it takes but
+    /// does not set.
+    pub fn target_item(&self, kind: TargetKind, item: &BatchItem) -> BatchItem {
+        let mut merged = BatchItem::default();
+        let source = match (self.shared.get("source"), item.get("source")) {
+            (Some(shared), Some(own)) => Some(Path::new(shared).join(own)),
+            (shared, own) => own.or(shared).map(PathBuf::from),
+        };
+        if let Some(source) = source {
+            merged
+                .fields
+                .push(("source".to_string(), source.to_string_lossy().into_owned()));
+        }
+        for (name, value) in &item.fields {
+            if name != "source" {
+                merged.fields.push((name.clone(), value.clone()));
+            }
+        }
+        for (name, value) in &self.shared.fields {
+            if name != "source"
+                && kind.fields().contains(&name.as_str())
+                && item.get(name).is_none()
+            {
+                merged.fields.push((name.clone(), value.clone()));
+            }
+        }
+        merged
+    }
+}
+
+impl From<&BuildConfig> for JsonValue {
+    fn from(config: &BuildConfig) -> Self {
+        let fields = |item: &BatchItem| {
+            item.fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_str().into()))
+                .collect::<Vec<_>>()
+        };
+        let mut members = fields(&config.shared);
+        for (kind, item) in &config.targets {
+            members.push((kind.name().to_string(), JsonValue::Object(fields(item))));
+        }
+        JsonValue::Object(members)
+    }
+}
+
+/// Settings of a build that apply to every target.
+#[derive(Debug, Clone)]
+pub struct BuildContext {
+    /// Folder relative paths are resolved against (the configuration's)
+    pub base: PathBuf,
+    /// Output folder for targets the configuration gives none
+    pub output_folder: Option<PathBuf>,
+    /// Targets to build; every configured one when empty
+    pub targets: Vec<TargetKind>,
+    /// Verbosity of the run; targets themselves are built silently
+    pub verbosity: Verbosity,
+    /// Which warnings fail a target
+    pub strictness: Strictness,
+}
+
+impl BuildContext {
+    /// Create a context resolving paths against `base`.
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            output_folder: None,
+            targets: Vec::new(),
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+
+    /// Set the output folder for targets the configuration gives none.
+    pub fn with_output_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_folder = Some(path.into());
+        self
+    }
+
+    /// Build only these targets.
+    pub fn with_targets(mut self, targets: Vec<TargetKind>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail a target.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml;
+
+    #[test]
+    fn test_build_config_targets() {
+        let value = toml::parse(
+            r#"
+[build]
+name = "Contoso Tool"
+version = "1.2.0"
+source = "dist"
+output = "out"
+
+[build.intune]
+source = "windows"
+setup = "setup.exe"
+
+[build.macos]
+identifier = "com.contoso.tool"
+version = "1.2.1"
+"#,
+        )
+        .unwrap();
+        let config = BuildConfig::from_json_value(value.get("build").unwrap()).unwrap();
+        assert_eq!(config.name(), Some("Contoso Tool"));
+        assert_eq!(config.targets.len(), 2);
+
+        // The Intune target takes no version
+        let (kind, item) = &config.targets[0];
+        let intune = config.target_item(*kind, item);
+        assert_eq!(
+            intune.get("source"),
+            Some(Path::new("dist").join("windows").to_str().unwrap())
+        );
+        assert_eq!(intune.get("output"), Some("out"));
+        assert_eq!(intune.get("version"), None);
+
+        // The macOS target keeps its own version and the shared root
+        let (kind, item) = &config.targets[1];
+        let macos = config.target_item(*kind, item);
+        assert_eq!(macos.get("source"), Some("dist"));
+        assert_eq!(macos.get("version"), Some("1.2.1"));
+        assert_eq!(
+            macos
+                .fields
+                .iter()
+                .filter(|(name, _)| name == "version")
+                .count(),
+            1
+        );
+
+        let json = JsonValue::from(&config);
+        assert_eq!(BuildConfig::from_json_value(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn test_build_config_errors() {
+        let parse = |text: &str| BuildConfig::from_json_value(&crate::json::parse(text).unwrap());
+        assert!(
+            parse(r#"{"windows": {}}"#)
+                .unwrap_err()
+                .contains("Unknown key")
+        );
+        assert!(parse(r#"{"intune": "x"}"#).is_err());
+        assert!(parse(r#"{"intune": {}, "Intune": {}}"#).is_err());
+        assert!(parse(r#"{"version": [1]}"#).is_err());
+        assert_eq!("MACOS".parse::<TargetKind>(), Ok(TargetKind::MacPkg));
+        assert!("msix".parse::<TargetKind>().is_err());
+    }
+}
@@ -0,0 +1,44 @@
+//! Settings and results of the compressed content cache.
+
+use std::path::PathBuf;
+
+use crate::models::size::ByteSize;
+
+/// Files below this size are compressed directly rather than cached.
+pub const DEFAULT_CACHE_MIN_FILE_SIZE: ByteSize = ByteSize(64 * 1024);
+
+/// Where compressed files are kept between runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Cache folder, created when missing
+    pub dir: PathBuf,
+    /// Smallest file stored in the cache
+    pub min_file_size: ByteSize,
+}
+
+impl CacheConfig {
+    /// Cache files of at least [`DEFAULT_CACHE_MIN_FILE_SIZE`] in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            min_file_size: DEFAULT_CACHE_MIN_FILE_SIZE,
+        }
+    }
+
+    /// Set the smallest file stored in the cache.
+    pub fn with_min_file_size(mut self, size: ByteSize) -> Self {
+        self.min_file_size = size;
+        self
+    }
+}
+
+/// How the cache was used while packaging.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Files copied from the cache without compressing them again
+    pub hits: u64,
+    /// Files compressed and added to the cache
+    pub misses: u64,
+    /// Uncompressed size of the files copied from the cache
+    pub reused: ByteSize,
+}
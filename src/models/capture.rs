@@ -0,0 +1,303 @@
+//! Data models for before/after capture repackaging.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::json::{self, JsonValue};
+use crate::models::package::{IntuneWinPackage, Verbosity};
+use crate::models::strictness::Strictness;
+
+/// Snapshot file format version.
+pub const SNAPSHOT_VERSION: u64 = 1;
+
+/// A single file recorded in a capture snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureEntry {
+    /// Path relative to the captured root
+    pub path: PathBuf,
+    /// File size in bytes
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: String,
+}
+
+impl CaptureEntry {
+    /// Parse an entry written by `JsonValue::from(&CaptureEntry)`.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let path = value
+            .get("path")
+            .and_then(JsonValue::as_str)
+            .ok_or("Snapshot entry is missing 'path'")?;
+        let size = value
+            .get("size")
+            .and_then(JsonValue::as_u64)
+            .ok_or("Snapshot entry is missing 'size'")?;
+        let sha256 = value
+            .get("sha256")
+            .and_then(JsonValue::as_str)
+            .ok_or("Snapshot entry is missing 'sha256'")?;
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            size,
+            sha256: sha256.to_string(),
+        })
+    }
+}
+
+impl From<&CaptureEntry> for JsonValue {
+    fn from(entry: &CaptureEntry) -> Self {
+        JsonValue::Object(vec![
+            (
+                "path".to_string(),
+                entry.path.to_string_lossy().replace('\\', "/").into(),
+            ),
+            ("size".to_string(), entry.size.into()),
+            ("sha256".to_string(), entry.sha256.as_str().into()),
+        ])
+    }
+}
+
+/// The state of a directory tree at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureSnapshot {
+    /// Absolute path of the captured root
+    pub root: PathBuf,
+    /// Seconds since the Unix epoch when the snapshot was taken
+    pub created: u64,
+    /// Files in the tree, sorted by path
+    pub entries: Vec<CaptureEntry>,
+}
+
+impl CaptureSnapshot {
+    /// Serialize the snapshot as JSON.
+    pub fn to_json(&self) -> String {
+        let files = self.entries.iter().map(JsonValue::from).collect();
+
+        JsonValue::Object(vec![
+            ("version".to_string(), SNAPSHOT_VERSION.into()),
+            (
+                "root".to_string(),
+                self.root.to_string_lossy().into_owned().into(),
+            ),
+            ("created".to_string(), self.created.into()),
+            ("files".to_string(), JsonValue::Array(files)),
+        ])
+        .to_pretty_string()
+    }
+
+    /// Parse a snapshot previously written by [`CaptureSnapshot::to_json`].
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value = json::parse(text)?;
+
+        let version = value
+            .get("version")
+            .and_then(JsonValue::as_u64)
+            .ok_or("Missing snapshot version")?;
+        if version != SNAPSHOT_VERSION {
+            return Err(format!("Unsupported snapshot version {}", version));
+        }
+
+        let root = value
+            .get("root")
+            .and_then(JsonValue::as_str)
+            .ok_or("Missing snapshot root")?;
+        let created = value
+            .get("created")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(0);
+        let files = value
+            .get("files")
+            .and_then(JsonValue::as_array)
+            .ok_or("Missing snapshot file list")?;
+
+        let entries = files
+            .iter()
+            .map(CaptureEntry::from_json_value)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            root: PathBuf::from(root),
+            created,
+            entries,
+        })
+    }
+
+    /// Compare this (earlier) snapshot with a later one.
+    pub fn diff(&self, after: &CaptureSnapshot) -> CaptureDiff {
+        let before: HashMap<&PathBuf, &CaptureEntry> =
+            self.entries.iter().map(|e| (&e.path, e)).collect();
+        let after_paths: HashMap<&PathBuf, &CaptureEntry> =
+            after.entries.iter().map(|e| (&e.path, e)).collect();
+
+        let mut diff = CaptureDiff::default();
+
+        for entry in &after.entries {
+            match before.get(&entry.path) {
+                None => diff.added.push(entry.path.clone()),
+                Some(old) if old.size != entry.size || old.sha256 != entry.sha256 => {
+                    diff.changed.push(entry.path.clone())
+                }
+                Some(_) => {}
+            }
+        }
+
+        for entry in &self.entries {
+            if !after_paths.contains_key(&entry.path) {
+                diff.removed.push(entry.path.clone());
+            }
+        }
+
+        diff.added.sort();
+        diff.changed.sort();
+        diff.removed.sort();
+        diff
+    }
+}
+
+/// Differences between two capture snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureDiff {
+    /// Files that did not exist at capture start
+    pub added: Vec<PathBuf>,
+    /// Files whose content changed since capture start
+    pub changed: Vec<PathBuf>,
+    /// Files that were deleted since capture start (reported, not packaged)
+    pub removed: Vec<PathBuf>,
+}
+
+impl CaptureDiff {
+    /// Files to stage: added and changed, sorted by path.
+    pub fn staged(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.added.iter().chain(&self.changed).cloned().collect();
+        files.sort();
+        files
+    }
+
+    /// Returns true if nothing was added or changed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Request to finish a capture and package the differences.
+#[derive(Debug, Clone)]
+pub struct CaptureFinishRequest {
+    /// Snapshot file written by `capture start`
+    pub state_file: PathBuf,
+    /// Setup file: an existing file to copy into the package root, or a
+    /// path (relative to the captured root) of a captured file
+    pub setup_file: String,
+    /// Path to the output folder where .intunewin will be created
+    pub output_folder: PathBuf,
+    /// Optional custom output filename (without extension)
+    pub output_name: Option<String>,
+    /// Verbosity level for output
+    pub verbosity: Verbosity,
+    /// Which warnings fail the run
+    pub strictness: Strictness,
+}
+
+impl CaptureFinishRequest {
+    /// Create a new capture finish request.
+    pub fn new(state_file: PathBuf, setup_file: String, output_folder: PathBuf) -> Self {
+        Self {
+            state_file,
+            setup_file,
+            output_folder,
+            output_name: None,
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+
+    /// Set custom output filename.
+    pub fn with_output_name(mut self, name: String) -> Self {
+        self.output_name = Some(name);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail the run.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+}
+
+/// Result of finishing a capture.
+#[derive(Debug, Clone)]
+pub struct CaptureResult {
+    /// Differences that were found
+    pub diff: CaptureDiff,
+    /// The package built from the staged differences
+    pub package: IntuneWinPackage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, sha256: &str) -> CaptureEntry {
+        CaptureEntry {
+            path: PathBuf::from(path),
+            size: 4,
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_json_roundtrip() {
+        let snapshot = CaptureSnapshot {
+            root: PathBuf::from("/opt/vendor"),
+            created: 1_700_000_000,
+            entries: vec![entry("bin/app", "aa"), entry("etc/app.conf", "bb")],
+        };
+
+        let parsed = CaptureSnapshot::from_json(&snapshot.to_json()).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_unknown_version() {
+        let text = r#"{"version": 99, "root": "/", "files": []}"#;
+        assert!(CaptureSnapshot::from_json(text).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = CaptureSnapshot {
+            root: PathBuf::from("/opt"),
+            created: 0,
+            entries: vec![
+                entry("same", "11"),
+                entry("edited", "22"),
+                entry("gone", "33"),
+            ],
+        };
+        let after = CaptureSnapshot {
+            root: PathBuf::from("/opt"),
+            created: 1,
+            entries: vec![
+                entry("same", "11"),
+                entry("edited", "99"),
+                entry("new", "44"),
+            ],
+        };
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added, vec![PathBuf::from("new")]);
+        assert_eq!(diff.changed, vec![PathBuf::from("edited")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("gone")]);
+        assert_eq!(
+            diff.staged(),
+            vec![PathBuf::from("edited"), PathBuf::from("new")]
+        );
+        assert!(!diff.is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Data models for Chocolatey package (.nupkg) creation.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::{Glob, SourceFilter};
+use crate::models::package::Verbosity;
+use crate::models::size::ByteSize;
+
+/// Folder of the package the content is placed in, as Chocolatey expects.
+pub const TOOLS_FOLDER: &str = "tools";
+
+/// Longest package id NuGet accepts.
+pub const MAX_ID_LENGTH: usize = 100;
+
+/// Request to create a Chocolatey package.
+#[derive(Debug, Clone)]
+pub struct ChocoPackRequest {
+    /// Source folder containing files to package
+    pub source_folder: PathBuf,
+    /// Installer run by `chocolateyInstall.ps1`, relative to the source folder
+    pub setup_file: String,
+    /// Package id (e.g., `myapp`)
+    pub id: String,
+    /// Package version (e.g., `1.2.0`)
+    pub version: String,
+    /// Output folder for the .nupkg file
+    pub output_folder: PathBuf,
+    /// Display title (default: the id)
+    pub title: Option<String>,
+    /// Package authors (default: `Unknown`)
+    pub authors: Option<String>,
+    /// Package description (default: the title)
+    pub description: Option<String>,
+    /// Arguments for a silent install (default: `/qn /norestart` for MSIs)
+    pub silent_args: Option<String>,
+    /// Verbosity level
+    pub verbosity: Verbosity,
+    /// Patterns selecting which source files are packaged
+    pub filter: SourceFilter,
+}
+
+impl ChocoPackRequest {
+    /// Create a new Chocolatey package request with required fields.
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        setup_file: impl Into<String>,
+        id: impl Into<String>,
+        version: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            setup_file: setup_file.into(),
+            id: id.into(),
+            version: version.into(),
+            output_folder: output_folder.into(),
+            title: None,
+            authors: None,
+            description: None,
+            silent_args: None,
+            verbosity: Verbosity::default(),
+            filter: SourceFilter::default(),
+        }
+    }
+
+    /// Set the display title.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    /// Set the package authors.
+    pub fn with_authors(mut self, authors: impl Into<String>) -> Self {
+        self.set_authors(authors);
+        self
+    }
+
+    /// Set the package description.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Set the arguments passed to the installer.
+    pub fn with_silent_args(mut self, args: impl Into<String>) -> Self {
+        self.set_silent_args(args);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.set_verbosity(verbosity);
+        self
+    }
+
+    /// Only package source files matching `glob` (or in folders matching it).
+    pub fn with_include(mut self, glob: Glob) -> Self {
+        self.add_include(glob);
+        self
+    }
+
+    /// Leave out source files matching `glob` (or in folders matching it).
+    pub fn with_exclude(mut self, glob: Glob) -> Self {
+        self.add_exclude(glob);
+        self
+    }
+
+    /// Set the display title in place.
+    pub fn set_title(&mut self, title: impl Into<String>) -> &mut Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the package authors in place.
+    pub fn set_authors(&mut self, authors: impl Into<String>) -> &mut Self {
+        self.authors = Some(authors.into());
+        self
+    }
+
+    /// Set the package description in place.
+    pub fn set_description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the arguments passed to the installer in place.
+    pub fn set_silent_args(&mut self, args: impl Into<String>) -> &mut Self {
+        self.silent_args = Some(args.into());
+        self
+    }
+
+    /// Set verbosity level in place.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> &mut Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Add an include pattern in place.
+    pub fn add_include(&mut self, glob: Glob) -> &mut Self {
+        self.filter.include.push(glob);
+        self
+    }
+
+    /// Add an exclude pattern in place.
+    pub fn add_exclude(&mut self, glob: Glob) -> &mut Self {
+        self.filter.exclude.push(glob);
+        self
+    }
+
+    /// Check the id and version against NuGet's rules.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !is_valid_id(&self.id) {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Invalid package id '{}': use letters, digits, '_', '.' and '-' \
+                     (at most {} characters, no leading, trailing or repeated separators)",
+                    self.id, MAX_ID_LENGTH
+                ),
+            });
+        }
+        if !is_valid_version(&self.version) {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Invalid package version '{}': expected 1 to 4 numbers separated by '.', \
+                     optionally followed by '-prerelease'",
+                    self.version
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// The display title.
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Get the output file path (`<id>.<version>.nupkg`, as `choco pack`
+    /// names it).
+    pub fn output_path(&self) -> PathBuf {
+        self.output_folder
+            .join(format!("{}.{}.nupkg", self.id, self.version))
+    }
+}
+
+/// Whether `id` is a valid NuGet package id.
+fn is_valid_id(id: &str) -> bool {
+    id.len() <= MAX_ID_LENGTH
+        && id.split(['.', '-']).all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// Whether `version` is a valid NuGet version (`1.2`, `1.2.3.4`,
+/// `1.0.0-beta.1`).
+fn is_valid_version(version: &str) -> bool {
+    let (release, prerelease) = match version.split_once('-') {
+        Some((release, prerelease)) => (release, Some(prerelease)),
+        None => (version, None),
+    };
+    let numbers: Vec<&str> = release.split('.').collect();
+    (1..=4).contains(&numbers.len())
+        && numbers
+            .iter()
+            .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        && prerelease.map_or(true, |p| {
+            !p.is_empty()
+                && p.split('.').all(|part| {
+                    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
+        })
+}
+
+/// Result of Chocolatey package creation.
+#[derive(Debug, Clone)]
+pub struct ChocoPackResult {
+    /// Path to the created .nupkg file
+    pub output_path: PathBuf,
+    /// Size of the created package
+    pub package_size: ByteSize,
+    /// Number of content files packaged under `tools/`
+    pub file_count: usize,
+    /// The generated install script
+    pub install_script: String,
+    /// Time taken to create the package
+    pub creation_time: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_ids() {
+        for id in ["myapp", "My.App", "my-app_2", "a"] {
+            assert!(is_valid_id(id), "{}", id);
+        }
+        for id in ["", ".app", "app.", "my..app", "my app", "app/1"] {
+            assert!(!is_valid_id(id), "{}", id);
+        }
+        assert!(!is_valid_id(&"a".repeat(MAX_ID_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_package_versions() {
+        for version in ["1", "1.2", "1.2.3.4", "1.0.0-beta", "2.0.0-rc.1"] {
+            assert!(is_valid_version(version), "{}", version);
+        }
+        for version in ["", "1.", "1.2.3.4.5", "v1.0", "1.0-", "1.0-beta..1"] {
+            assert!(!is_valid_version(version), "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_output_path_and_defaults() {
+        let request = ChocoPackRequest::new("/src", "setup.exe", "myapp", "1.2.0", "/out");
+        assert_eq!(
+            request.output_path(),
+            PathBuf::from("/out/myapp.1.2.0.nupkg")
+        );
+        assert_eq!(request.title(), "myapp");
+        assert_eq!(request.with_title("My App").title(), "My App");
+    }
+}
@@ -0,0 +1,215 @@
+//! How packaged content is compressed.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::error::{PackageError, PackageResult};
+
+/// Level used when none is given.
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// Highest compression level.
+pub const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+/// Zstandard level used when none is given.
+#[cfg(feature = "zstd")]
+pub const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+/// Highest Zstandard level.
+#[cfg(feature = "zstd")]
+pub const MAX_ZSTD_LEVEL: u32 = 22;
+
+/// Compression applied to content files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompressionMethod {
+    /// Store files as they are; fastest, and best for content that is
+    /// already compressed
+    Stored,
+    /// Deflate, readable by Intune and the macOS Installer
+    #[default]
+    Deflate,
+    /// Zstandard (ZIP method 93): faster and smaller than deflate for large
+    /// binaries, but only this tool extracts it; Intune cannot install such
+    /// a package
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl CompressionMethod {
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            CompressionMethod::Stored => "stored",
+            CompressionMethod::Deflate => "deflate",
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => "zstd",
+        }
+    }
+
+    /// Lowest and highest level the method accepts.
+    pub fn level_range(self) -> (u32, u32) {
+        match self {
+            CompressionMethod::Stored => (0, 0),
+            CompressionMethod::Deflate => (0, MAX_COMPRESSION_LEVEL),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => (1, MAX_ZSTD_LEVEL),
+        }
+    }
+}
+
+impl fmt::Display for CompressionMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CompressionMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "stored" | "store" | "none" => Ok(CompressionMethod::Stored),
+            "deflate" | "deflated" => Ok(CompressionMethod::Deflate),
+            #[cfg(feature = "zstd")]
+            "zstd" | "zstandard" => Ok(CompressionMethod::Zstd),
+            #[cfg(not(feature = "zstd"))]
+            "zstd" | "zstandard" => Err(
+                "Zstandard needs a build with the 'zstd' feature (and neither Intune nor the \
+                 macOS Installer can read it)"
+                    .to_string(),
+            ),
+            other => Err(format!(
+                "Invalid compression '{}' (expected 'stored' or 'deflate')",
+                other
+            )),
+        }
+    }
+}
+
+/// Compression method and level for content files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compression {
+    /// How files are compressed
+    pub method: CompressionMethod,
+    /// Level from 0 (fastest) to 9 (smallest) for deflate, or 1 to 22 for
+    /// Zstandard; the method's default level when unset
+    pub level: Option<u32>,
+}
+
+impl Compression {
+    /// Compress with `method` at its default level.
+    pub fn new(method: CompressionMethod) -> Self {
+        Self {
+            method,
+            level: None,
+        }
+    }
+
+    /// Store files without compressing them.
+    pub fn stored() -> Self {
+        Self::new(CompressionMethod::Stored)
+    }
+
+    /// Set the compression level.
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Level in effect; 0 when files are stored.
+    pub fn effective_level(&self) -> u32 {
+        match self.method {
+            CompressionMethod::Stored => 0,
+            CompressionMethod::Deflate => self.level.unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => self.level.unwrap_or(DEFAULT_ZSTD_LEVEL),
+        }
+    }
+
+    /// Whether this compresses like the default settings.
+    pub fn is_default(&self) -> bool {
+        self.method == CompressionMethod::Deflate
+            && self.effective_level() == DEFAULT_COMPRESSION_LEVEL
+    }
+
+    /// Check the level is in range and matches the method.
+    pub fn validate(&self) -> PackageResult<()> {
+        let (min, max) = self.method.level_range();
+        match (self.method, self.level) {
+            (CompressionMethod::Stored, Some(_)) => Err(PackageError::InvalidArgument {
+                reason: "A compression level needs deflate compression, not stored".to_string(),
+            }),
+            (_, Some(level)) if level < min || level > max => Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Compression level {} is out of range for {} ({}-{})",
+                    level, self.method, min, max
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.method {
+            CompressionMethod::Stored => f.write_str("stored"),
+            CompressionMethod::Deflate => write!(f, "deflate level {}", self.effective_level()),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => write!(f, "zstd level {}", self.effective_level()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!("stored".parse(), Ok(CompressionMethod::Stored));
+        assert_eq!("Deflate".parse(), Ok(CompressionMethod::Deflate));
+        #[cfg(not(feature = "zstd"))]
+        assert!(
+            "zstd"
+                .parse::<CompressionMethod>()
+                .unwrap_err()
+                .contains("'zstd' feature")
+        );
+        assert!("lzma".parse::<CompressionMethod>().is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_levels() {
+        let zstd = Compression::new("zstd".parse().unwrap());
+        assert_eq!(zstd.effective_level(), DEFAULT_ZSTD_LEVEL);
+        assert_eq!(zstd.with_level(19).to_string(), "zstd level 19");
+        assert!(!zstd.is_default());
+        assert!(zstd.with_level(22).validate().is_ok());
+        assert!(zstd.with_level(0).validate().is_err());
+        assert!(zstd.with_level(23).validate().is_err());
+        assert!(Compression::default().with_level(19).validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_level() {
+        assert!(Compression::default().with_level(9).validate().is_ok());
+        assert!(Compression::default().with_level(10).validate().is_err());
+        assert!(Compression::stored().with_level(1).validate().is_err());
+        assert!(Compression::stored().validate().is_ok());
+    }
+
+    #[test]
+    fn test_effective_level() {
+        assert!(Compression::default().is_default());
+        assert!(Compression::default().with_level(6).is_default());
+        assert!(!Compression::default().with_level(1).is_default());
+        assert_eq!(Compression::stored().effective_level(), 0);
+        assert_eq!(Compression::stored().to_string(), "stored");
+        assert_eq!(
+            Compression::default().with_level(9).to_string(),
+            "deflate level 9"
+        );
+    }
+}
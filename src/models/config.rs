@@ -0,0 +1,230 @@
+//! Optional JSON or TOML configuration file (`--config`).
+//!
+//! ```json
+//! {
+//!   "strict": true,
+//!   "checks": { "signature": "warn", "special-modes": "error" },
+//!   "watch": { "setup_files": ["setup.exe", "*.msi"], "settle_seconds": 30 },
+//!   "app": { "publisher": "ACME", "architectures": ["x64"] },
+//!   "build": { "source": "dist", "intune": { "setup": "setup.exe" } }
+//! }
+//! ```
+//!
+//! A `.toml` file holds the same settings as tables (`[checks]`,
+//! `[build.intune]`). Unknown top-level keys are ignored so newer files
+//! still load.
+
+use std::fs;
+use std::path::Path;
+
+use crate::json::{self, JsonValue};
+use crate::models::build::BuildConfig;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::strictness::{Check, CheckLevel, Strictness};
+use crate::models::watch::WatchRules;
+use crate::models::win32app::AppSettings;
+use crate::toml;
+
+/// Settings read from a configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Strict mode and per-check overrides
+    pub strictness: Strictness,
+    /// Drop folder rules for `watch`
+    pub watch: WatchRules,
+    /// Win32 app settings for `intune manifest`
+    pub app: AppSettings,
+    /// Targets for `build`
+    pub build: BuildConfig,
+}
+
+impl Config {
+    /// Load a configuration file (TOML for a `.toml` extension, else JSON).
+    pub fn load(path: &Path) -> PackageResult<Self> {
+        let invalid = |reason: String| PackageError::ConfigError {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let is_toml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        if is_toml {
+            Self::parse_toml(&text).map_err(invalid)
+        } else {
+            Self::parse(&text).map_err(invalid)
+        }
+    }
+
+    /// Parse configuration JSON.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::from_json_value(&json::parse(text)?)
+    }
+
+    /// Parse configuration TOML.
+    pub fn parse_toml(text: &str) -> Result<Self, String> {
+        Self::from_json_value(&toml::parse(text)?)
+    }
+
+    /// Read configuration from parsed JSON (e.g., embedded in a project file).
+    pub fn from_json_value(root: &JsonValue) -> Result<Self, String> {
+        if root.as_object().is_none() {
+            return Err("Configuration must be a JSON object".to_string());
+        }
+
+        let mut strictness = Strictness::default();
+        if let Some(strict) = root.get("strict") {
+            strictness.strict = strict.as_bool().ok_or("'strict' must be true or false")?;
+        }
+
+        if let Some(checks) = root.get("checks") {
+            let checks = checks
+                .as_object()
+                .ok_or("'checks' must be an object of check names to levels")?;
+            for (name, level) in checks {
+                let check: Check = name.parse()?;
+                let level: CheckLevel = level
+                    .as_str()
+                    .ok_or_else(|| format!("Level for '{}' must be a string", name))?
+                    .parse()?;
+                strictness = strictness.with_override(check, level);
+            }
+        }
+
+        let watch = match root.get("watch") {
+            Some(watch) => WatchRules::from_json_value(watch)?,
+            None => WatchRules::default(),
+        };
+
+        let app = match root.get("app") {
+            Some(app) => AppSettings::from_json_value(app)?,
+            None => AppSettings::default(),
+        };
+
+        let build = match root.get("build") {
+            Some(build) => BuildConfig::from_json_value(build)?,
+            None => BuildConfig::default(),
+        };
+
+        Ok(Self {
+            strictness,
+            watch,
+            app,
+            build,
+        })
+    }
+}
+
+impl From<&Config> for JsonValue {
+    fn from(config: &Config) -> Self {
+        let checks = config
+            .strictness
+            .overrides
+            .iter()
+            .map(|(check, level)| {
+                let level = match level {
+                    CheckLevel::Warn => "warn",
+                    CheckLevel::Error => "error",
+                };
+                (check.name().to_string(), level.into())
+            })
+            .collect();
+
+        let mut members = vec![
+            ("strict".to_string(), config.strictness.strict.into()),
+            ("checks".to_string(), JsonValue::Object(checks)),
+        ];
+        if config.watch != WatchRules::default() {
+            members.push(("watch".to_string(), (&config.watch).into()));
+        }
+        if config.app != AppSettings::default() {
+            members.push(("app".to_string(), (&config.app).into()));
+        }
+        if config.build != BuildConfig::default() {
+            members.push(("build".to_string(), (&config.build).into()));
+        }
+        JsonValue::Object(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config =
+            Config::parse(r#"{"strict": true, "checks": {"signature": "warn"}, "future": 1}"#)
+                .unwrap();
+        assert_eq!(config.watch, WatchRules::default());
+
+        assert!(config.strictness.strict);
+        assert_eq!(config.strictness.level(Check::Signature), CheckLevel::Warn);
+        assert_eq!(
+            Config::parse(&JsonValue::from(&config).to_pretty_string()),
+            Ok(config)
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(Config::parse("[]").is_err());
+        assert!(Config::parse(r#"{"strict": "yes"}"#).is_err());
+        assert!(Config::parse(r#"{"checks": {"unknown": "error"}}"#).is_err());
+        assert!(Config::parse(r#"{"checks": {"signature": "fatal"}}"#).is_err());
+        assert!(Config::parse(r#"{"watch": {"poll_seconds": -1}}"#).is_err());
+        assert!(Config::parse(r#"{"app": {"install_context": "admin"}}"#).is_err());
+        assert!(Config::parse(r#"{"build": {"intune": {"setup": []}}}"#).is_err());
+        assert!(Config::parse_toml("strict = yes").is_err());
+    }
+
+    #[test]
+    fn test_parse_config_app_settings() {
+        let config = Config::parse(r#"{"app": {"publisher": "ACME"}}"#).unwrap();
+
+        assert_eq!(config.app.publisher.as_deref(), Some("ACME"));
+        assert_eq!(
+            Config::parse(&JsonValue::from(&config).to_pretty_string()),
+            Ok(config)
+        );
+    }
+
+    #[test]
+    fn test_parse_config_watch_rules() {
+        let config = Config::parse(r#"{"watch": {"setup_files": ["*.msi"]}}"#).unwrap();
+
+        assert_eq!(config.watch.setup_files.len(), 1);
+        assert_eq!(
+            Config::parse(&JsonValue::from(&config).to_pretty_string()),
+            Ok(config)
+        );
+    }
+
+    #[test]
+    fn test_parse_config_toml() {
+        let config = Config::parse_toml(
+            r#"
+strict = true
+
+[checks]
+signature = "warn"
+
+[build]
+source = "dist"
+
+[build.intune]
+setup = "setup.exe"
+"#,
+        )
+        .unwrap();
+
+        assert!(config.strictness.strict);
+        assert_eq!(config.strictness.level(Check::Signature), CheckLevel::Warn);
+        assert_eq!(config.build.targets.len(), 1);
+        assert_eq!(
+            Config::parse(&JsonValue::from(&config).to_pretty_string()),
+            Ok(config)
+        );
+    }
+}
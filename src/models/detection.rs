@@ -1,9 +1,20 @@
-//! Detection metadata models for IntuneWin packages.
+//! Detection metadata and detection rule models for IntuneWin packages.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::json::JsonValue;
 
 /// Encryption information for the package.
-#[derive(Debug, Clone)]
+///
+/// The keys and IV are wiped from memory when the value is dropped, and the
+/// keys are left out of `Debug` output.
+#[derive(Clone)]
 pub struct EncryptionInfo {
     /// AES-256 encryption key (32 bytes)
     pub encryption_key: [u8; 32],
@@ -13,12 +24,78 @@ pub struct EncryptionInfo {
     pub iv: [u8; 16],
     /// HMAC-SHA256 of (IV || ciphertext)
     pub mac: [u8; 32],
-    /// SHA256 hash of the encrypted file
-    pub file_digest: [u8; 32],
+    /// Digest of the unencrypted content, as long as its algorithm's output
+    pub file_digest: Vec<u8>,
     /// Profile identifier (always "ProfileVersion1")
     pub profile_identifier: String,
-    /// Digest algorithm name (always "SHA256")
-    pub file_digest_algorithm: String,
+    /// Algorithm of `file_digest`
+    pub file_digest_algorithm: DigestAlgorithm,
+}
+
+/// Hash algorithms Detection.xml can name for the content digest.
+///
+/// Intune's own tool writes SHA256; the longer digests suit environments
+/// whose policy asks for them, such as FIPS setups requiring SHA-384.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DigestAlgorithm {
+    /// SHA-256, 32 bytes
+    #[default]
+    Sha256,
+    /// SHA-384, 48 bytes
+    Sha384,
+    /// SHA-512, 64 bytes
+    Sha512,
+}
+
+impl DigestAlgorithm {
+    /// Every supported algorithm.
+    pub const ALL: [DigestAlgorithm; 3] = [
+        DigestAlgorithm::Sha256,
+        DigestAlgorithm::Sha384,
+        DigestAlgorithm::Sha512,
+    ];
+
+    /// Name written to Detection.xml's FileDigestAlgorithm.
+    pub fn name(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "SHA256",
+            DigestAlgorithm::Sha384 => "SHA384",
+            DigestAlgorithm::Sha512 => "SHA512",
+        }
+    }
+
+    /// Length of the digest in bytes.
+    pub fn output_len(self) -> usize {
+        match self {
+            DigestAlgorithm::Sha256 => 32,
+            DigestAlgorithm::Sha384 => 48,
+            DigestAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+impl fmt::Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = String;
+
+    /// Accepts `SHA256`, `sha-256` and the like, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_uppercase().replace('-', "");
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported digest algorithm '{}' (expected SHA256, SHA384 or SHA512)",
+                    s
+                )
+            })
+    }
 }
 
 impl Default for EncryptionInfo {
@@ -28,13 +105,41 @@ impl Default for EncryptionInfo {
             mac_key: [0u8; 32],
             iv: [0u8; 16],
             mac: [0u8; 32],
-            file_digest: [0u8; 32],
+            file_digest: vec![0u8; DigestAlgorithm::Sha256.output_len()],
             profile_identifier: "ProfileVersion1".to_string(),
-            file_digest_algorithm: "SHA256".to_string(),
+            file_digest_algorithm: DigestAlgorithm::Sha256,
         }
     }
 }
 
+impl Zeroize for EncryptionInfo {
+    fn zeroize(&mut self) {
+        self.encryption_key.zeroize();
+        self.mac_key.zeroize();
+        self.iv.zeroize();
+    }
+}
+
+impl Drop for EncryptionInfo {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl fmt::Debug for EncryptionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionInfo")
+            .field("encryption_key", &REDACTED)
+            .field("mac_key", &REDACTED)
+            .field("iv", &self.iv_base64())
+            .field("mac", &self.mac_base64())
+            .field("file_digest", &self.file_digest_base64())
+            .field("profile_identifier", &self.profile_identifier)
+            .field("file_digest_algorithm", &self.file_digest_algorithm)
+            .finish()
+    }
+}
+
 impl EncryptionInfo {
     /// Create new encryption info with default profile settings.
     pub fn new() -> Self {
@@ -63,13 +168,14 @@ impl EncryptionInfo {
 
     /// Get Base64-encoded file digest.
     pub fn file_digest_base64(&self) -> String {
-        BASE64.encode(self.file_digest)
+        BASE64.encode(&self.file_digest)
     }
 
     /// Set encryption key from Base64 string.
     pub fn set_encryption_key_from_base64(&mut self, b64: &str) -> Result<(), String> {
         let decoded = BASE64
             .decode(b64)
+            .map(Zeroizing::new)
             .map_err(|e| format!("Invalid Base64 for encryption key: {}", e))?;
         if decoded.len() != 32 {
             return Err(format!(
@@ -85,6 +191,7 @@ impl EncryptionInfo {
     pub fn set_mac_key_from_base64(&mut self, b64: &str) -> Result<(), String> {
         let decoded = BASE64
             .decode(b64)
+            .map(Zeroizing::new)
             .map_err(|e| format!("Invalid Base64 for MAC key: {}", e))?;
         if decoded.len() != 32 {
             return Err(format!("MAC key must be 32 bytes, got {}", decoded.len()));
@@ -97,6 +204,7 @@ impl EncryptionInfo {
     pub fn set_iv_from_base64(&mut self, b64: &str) -> Result<(), String> {
         let decoded = BASE64
             .decode(b64)
+            .map(Zeroizing::new)
             .map_err(|e| format!("Invalid Base64 for IV: {}", e))?;
         if decoded.len() != 16 {
             return Err(format!("IV must be 16 bytes, got {}", decoded.len()));
@@ -118,25 +226,121 @@ impl EncryptionInfo {
     }
 
     /// Set file digest from Base64 string.
+    ///
+    /// Any supported digest length is accepted, since the algorithm may be
+    /// read afterwards; [`EncryptionInfo::check_file_digest`] matches them.
     pub fn set_file_digest_from_base64(&mut self, b64: &str) -> Result<(), String> {
         let decoded = BASE64
             .decode(b64)
             .map_err(|e| format!("Invalid Base64 for file digest: {}", e))?;
-        if decoded.len() != 32 {
+        if !DigestAlgorithm::ALL
+            .iter()
+            .any(|algorithm| algorithm.output_len() == decoded.len())
+        {
             return Err(format!(
-                "File digest must be 32 bytes, got {}",
+                "File digest must be 32, 48 or 64 bytes, got {}",
                 decoded.len()
             ));
         }
-        self.file_digest.copy_from_slice(&decoded);
+        self.file_digest = decoded;
+        Ok(())
+    }
+
+    /// Check that the file digest is as long as its algorithm's output.
+    pub fn check_file_digest(&self) -> Result<(), String> {
+        let expected = self.file_digest_algorithm.output_len();
+        if self.file_digest.len() != expected {
+            return Err(format!(
+                "{} file digest must be {} bytes, got {}",
+                self.file_digest_algorithm,
+                expected,
+                self.file_digest.len()
+            ));
+        }
         Ok(())
     }
 }
 
+/// Placeholder shown instead of key material unless secrets are requested.
+pub const REDACTED: &str = "<redacted>";
+
+/// ToolVersion written to Detection.xml unless overridden (matches the
+/// version of Microsoft's Content Prep Tool this format follows).
+pub const DEFAULT_TOOL_VERSION: &str = "1.8.6.0";
+
+/// An element of Detection.xml kept as written because this crate does not
+/// interpret it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlElement {
+    /// Element name, with its prefix if it has one
+    pub name: String,
+    /// Attributes in document order
+    pub attributes: Vec<(String, String)>,
+    /// Text content, trimmed
+    pub text: String,
+    /// Child elements in document order
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    /// Create an empty element.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the element's text.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    /// Add a child element.
+    pub fn with_child(mut self, child: XmlElement) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// The first child named `name`.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+/// Parts of a Detection.xml written by another tool that this crate does not
+/// interpret, such as the `MsiInfo` block of Microsoft's Content Prep Tool.
+///
+/// They are written back after the interpreted elements, so repacking a
+/// third-party package keeps them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DetectionExtra {
+    /// Attributes of `ApplicationInfo` besides `ToolVersion` and the
+    /// `xsd`/`xsi` namespace declarations
+    pub attributes: Vec<(String, String)>,
+    /// Other children of `ApplicationInfo`
+    pub elements: Vec<XmlElement>,
+    /// Other children of `EncryptionInfo`
+    pub encryption_elements: Vec<XmlElement>,
+}
+
+impl DetectionExtra {
+    /// Whether nothing was kept.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+            && self.elements.is_empty()
+            && self.encryption_elements.is_empty()
+    }
+}
+
 /// Metadata written to Detection.xml.
 #[derive(Debug, Clone)]
 pub struct DetectionMetadata {
-    /// Name of the application (setup filename)
+    /// Version of the packaging tool, recorded as the `ToolVersion`
+    /// attribute
+    pub tool_version: String,
+    /// Name of the application (setup filename unless overridden)
     pub name: String,
     /// Original uncompressed content size in bytes
     pub unencrypted_content_size: u64,
@@ -146,19 +350,423 @@ pub struct DetectionMetadata {
     pub setup_file: String,
     /// Encryption parameters
     pub encryption_info: EncryptionInfo,
+    /// Elements and attributes of a third-party Detection.xml kept as
+    /// written
+    pub extra: DetectionExtra,
 }
 
 impl DetectionMetadata {
     /// Create new detection metadata.
     pub fn new(setup_file: String, unencrypted_content_size: u64) -> Self {
         Self {
+            tool_version: DEFAULT_TOOL_VERSION.to_string(),
             name: setup_file.clone(),
             unencrypted_content_size,
             file_name: "IntunePackage.intunewin".to_string(),
             setup_file,
             encryption_info: EncryptionInfo::new(),
+            extra: DetectionExtra::default(),
         }
     }
+
+    /// The MSI identity recorded in an `MsiInfo` element, as Microsoft's
+    /// Content Prep Tool writes for MSI setup files.
+    pub fn msi_info(&self) -> Option<MsiInformation> {
+        let msi = self.extra.elements.iter().find(|e| e.name == "MsiInfo")?;
+        let get = |name: &str| {
+            msi.child(name)
+                .map(|e| e.text.clone())
+                .filter(|text| !text.is_empty())
+        };
+        Some(MsiInformation {
+            product_code: get("MsiProductCode")?,
+            product_version: get("MsiProductVersion")?,
+            upgrade_code: get("MsiUpgradeCode"),
+            product_name: None,
+            publisher: get("MsiPublisher"),
+        })
+    }
+
+    /// Serialize the metadata as JSON; the encryption and MAC keys are
+    /// replaced by [`REDACTED`] unless `show_secrets` is set.
+    pub fn to_json(&self, show_secrets: bool) -> String {
+        self.to_json_value(show_secrets).to_pretty_string()
+    }
+
+    /// The metadata as a JSON object, redacted as by [`Self::to_json`].
+    pub fn to_json_value(&self, show_secrets: bool) -> JsonValue {
+        let info = &self.encryption_info;
+        // Keys are only encoded when they are shown
+        let secret = |encode: fn(&EncryptionInfo) -> String| {
+            if show_secrets {
+                encode(info).into()
+            } else {
+                JsonValue::from(REDACTED)
+            }
+        };
+
+        let mut fields = vec![
+            ("tool_version".to_string(), self.tool_version.clone().into()),
+            ("name".to_string(), self.name.clone().into()),
+            ("setup_file".to_string(), self.setup_file.clone().into()),
+            ("file_name".to_string(), self.file_name.clone().into()),
+            (
+                "unencrypted_content_size".to_string(),
+                self.unencrypted_content_size.into(),
+            ),
+            (
+                "encryption".to_string(),
+                JsonValue::Object(vec![
+                    (
+                        "profile_identifier".to_string(),
+                        info.profile_identifier.clone().into(),
+                    ),
+                    (
+                        "file_digest_algorithm".to_string(),
+                        info.file_digest_algorithm.name().into(),
+                    ),
+                    ("file_digest".to_string(), info.file_digest_base64().into()),
+                    ("mac".to_string(), info.mac_base64().into()),
+                    ("iv".to_string(), info.iv_base64().into()),
+                    (
+                        "encryption_key_bits".to_string(),
+                        (info.encryption_key.len() as u64 * 8).into(),
+                    ),
+                    (
+                        "mac_key_bits".to_string(),
+                        (info.mac_key.len() as u64 * 8).into(),
+                    ),
+                    (
+                        "encryption_key".to_string(),
+                        secret(EncryptionInfo::encryption_key_base64),
+                    ),
+                    (
+                        "mac_key".to_string(),
+                        secret(EncryptionInfo::mac_key_base64),
+                    ),
+                ]),
+            ),
+        ];
+        if let Some(msi) = self.msi_info() {
+            let mut msi_fields = vec![
+                ("product_code".to_string(), msi.product_code.into()),
+                ("product_version".to_string(), msi.product_version.into()),
+            ];
+            if let Some(upgrade_code) = msi.upgrade_code {
+                msi_fields.push(("upgrade_code".to_string(), upgrade_code.into()));
+            }
+            if let Some(publisher) = msi.publisher {
+                msi_fields.push(("publisher".to_string(), publisher.into()));
+            }
+            fields.push(("msi_info".to_string(), JsonValue::Object(msi_fields)));
+        }
+        JsonValue::Object(fields)
+    }
+}
+
+/// A rule Intune evaluates on a device to decide whether a Win32 app is
+/// installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DetectionRule {
+    /// The MSI product code is registered (at `product_version` or later,
+    /// if given)
+    MsiProductCode {
+        product_code: String,
+        product_version: Option<String>,
+    },
+    /// A file or folder exists
+    File { path: String, name: String },
+    /// A registry key exists, or a value in it exists (and equals
+    /// `expected`, if given)
+    Registry {
+        key_path: String,
+        value_name: Option<String>,
+        expected: Option<String>,
+    },
+}
+
+impl DetectionRule {
+    /// A rule checking that the file or folder at a full Windows path
+    /// (e.g., `C:\Program Files\App\app.exe`) exists.
+    pub fn file(path: &str) -> Result<Self, String> {
+        let path = path.replace('/', "\\");
+        match path.trim_end_matches('\\').rsplit_once('\\') {
+            Some((folder, name)) if !folder.is_empty() && !name.is_empty() => Ok(Self::File {
+                path: folder.to_string(),
+                name: name.to_string(),
+            }),
+            _ => Err(format!(
+                "Invalid file rule '{}': expected a full path such as C:\\App\\app.exe",
+                path
+            )),
+        }
+    }
+
+    /// A registry rule from `KEY`, `KEY:VALUE` or `KEY:VALUE=DATA`.
+    ///
+    /// The key must start with a hive; `HKLM`, `HKCU`, `HKCR` and `HKU` are
+    /// expanded to their full names.
+    pub fn registry(spec: &str) -> Result<Self, String> {
+        let (key, value) = match spec.split_once(':') {
+            Some((key, value)) => (key, Some(value)),
+            None => (spec, None),
+        };
+        let (hive, rest) = key.split_once('\\').unwrap_or((key, ""));
+        let hive = match hive.to_ascii_uppercase().as_str() {
+            "HKLM" | "HKEY_LOCAL_MACHINE" => "HKEY_LOCAL_MACHINE",
+            "HKCU" | "HKEY_CURRENT_USER" => "HKEY_CURRENT_USER",
+            "HKCR" | "HKEY_CLASSES_ROOT" => "HKEY_CLASSES_ROOT",
+            "HKU" | "HKEY_USERS" => "HKEY_USERS",
+            _ => {
+                return Err(format!(
+                    "Invalid registry rule '{}': the key must start with a hive such as HKLM",
+                    spec
+                ));
+            }
+        };
+        let key_path = if rest.is_empty() {
+            hive.to_string()
+        } else {
+            format!("{}\\{}", hive, rest.trim_end_matches('\\'))
+        };
+
+        let (value_name, expected) = match value {
+            Some(value) => match value.split_once('=') {
+                Some((name, data)) => (Some(name.to_string()), Some(data.to_string())),
+                None => (Some(value.to_string()), None),
+            },
+            None => (None, None),
+        };
+        if value_name.as_deref() == Some("") {
+            return Err(format!(
+                "Invalid registry rule '{}': the value name is empty",
+                spec
+            ));
+        }
+        Ok(Self::Registry {
+            key_path,
+            value_name,
+            expected,
+        })
+    }
+
+    /// The rule as a Microsoft Graph `win32LobAppRule` object.
+    pub fn to_json_value(&self) -> JsonValue {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => value.clone().into(),
+            None => JsonValue::Null,
+        };
+        let members: Vec<(&str, JsonValue)> = match self {
+            Self::MsiProductCode {
+                product_code,
+                product_version,
+            } => vec![
+                (
+                    "@odata.type",
+                    "#microsoft.graph.win32LobAppProductCodeRule".into(),
+                ),
+                ("ruleType", "detection".into()),
+                ("productCode", product_code.clone().into()),
+                (
+                    "productVersionOperator",
+                    if product_version.is_some() {
+                        "greaterThanOrEqual"
+                    } else {
+                        "notConfigured"
+                    }
+                    .into(),
+                ),
+                ("productVersion", optional(product_version)),
+            ],
+            Self::File { path, name } => vec![
+                (
+                    "@odata.type",
+                    "#microsoft.graph.win32LobAppFileSystemRule".into(),
+                ),
+                ("ruleType", "detection".into()),
+                ("path", path.clone().into()),
+                ("fileOrFolderName", name.clone().into()),
+                ("check32BitOn64System", false.into()),
+                ("operationType", "exists".into()),
+                ("operator", "notConfigured".into()),
+                ("comparisonValue", JsonValue::Null),
+            ],
+            Self::Registry {
+                key_path,
+                value_name,
+                expected,
+            } => vec![
+                (
+                    "@odata.type",
+                    "#microsoft.graph.win32LobAppRegistryRule".into(),
+                ),
+                ("ruleType", "detection".into()),
+                ("check32BitOn64System", false.into()),
+                ("keyPath", key_path.clone().into()),
+                ("valueName", optional(value_name)),
+                (
+                    "operationType",
+                    if expected.is_some() {
+                        "string"
+                    } else {
+                        "exists"
+                    }
+                    .into(),
+                ),
+                (
+                    "operator",
+                    if expected.is_some() {
+                        "equal"
+                    } else {
+                        "notConfigured"
+                    }
+                    .into(),
+                ),
+                ("comparisonValue", optional(expected)),
+            ],
+        };
+        JsonValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+/// Identity of an MSI setup file, read from its `Property` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsiInformation {
+    /// `ProductCode` (a GUID in braces)
+    pub product_code: String,
+    /// `ProductVersion`
+    pub product_version: String,
+    /// `UpgradeCode`, if set
+    pub upgrade_code: Option<String>,
+    /// `ProductName`, if set
+    pub product_name: Option<String>,
+    /// `Manufacturer`, if set
+    pub publisher: Option<String>,
+}
+
+impl MsiInformation {
+    /// Pick the identity out of an MSI's properties; `None` without a
+    /// product code and version.
+    pub fn from_properties(properties: &BTreeMap<String, String>) -> Option<Self> {
+        let get = |name: &str| properties.get(name).cloned();
+        Some(Self {
+            product_code: get("ProductCode")?,
+            product_version: get("ProductVersion")?,
+            upgrade_code: get("UpgradeCode"),
+            product_name: get("ProductName"),
+            publisher: get("Manufacturer"),
+        })
+    }
+}
+
+/// Request to generate detection rules for an `.intunewin`.
+#[derive(Debug, Clone)]
+pub struct DetectionRulesRequest {
+    /// The .intunewin whose setup file the rules are derived from
+    pub input_file: PathBuf,
+    /// Rules added to the derived ones
+    pub rules: Vec<DetectionRule>,
+    /// Where to write the rules (default: next to the package)
+    pub output_file: Option<PathBuf>,
+}
+
+impl DetectionRulesRequest {
+    /// Create a new request for the package at `input_file`.
+    pub fn new(input_file: impl Into<PathBuf>) -> Self {
+        Self {
+            input_file: input_file.into(),
+            rules: Vec::new(),
+            output_file: None,
+        }
+    }
+
+    /// Add a rule to the derived ones.
+    pub fn with_rule(mut self, rule: DetectionRule) -> Self {
+        self.add_rule(rule);
+        self
+    }
+
+    /// Set where the rules are written.
+    pub fn with_output_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_output_file(path);
+        self
+    }
+
+    /// Add a rule to the derived ones in place.
+    pub fn add_rule(&mut self, rule: DetectionRule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Set where the rules are written in place.
+    pub fn set_output_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_file = Some(path.into());
+        self
+    }
+
+    /// Get the output file path (`<package>.detection.json` next to the
+    /// package unless set).
+    pub fn output_path(&self) -> PathBuf {
+        match &self.output_file {
+            Some(path) => path.clone(),
+            None => self.input_file.with_extension("detection.json"),
+        }
+    }
+}
+
+/// Detection rules generated for a package.
+#[derive(Debug, Clone)]
+pub struct DetectionRules {
+    /// Setup file the rules were derived from
+    pub setup_file: String,
+    /// Identity of the setup file, if it is an MSI
+    pub msi: Option<MsiInformation>,
+    /// The rules, derived ones first
+    pub rules: Vec<DetectionRule>,
+}
+
+impl DetectionRules {
+    /// Serialize as JSON, with `msiInformation` and `rules` in the shape
+    /// Microsoft Graph expects for a `win32LobApp`.
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => value.clone().into(),
+            None => JsonValue::Null,
+        };
+        let msi = match &self.msi {
+            Some(msi) => JsonValue::Object(vec![
+                ("productCode".to_string(), msi.product_code.clone().into()),
+                (
+                    "productVersion".to_string(),
+                    msi.product_version.clone().into(),
+                ),
+                ("upgradeCode".to_string(), optional(&msi.upgrade_code)),
+                ("productName".to_string(), optional(&msi.product_name)),
+                ("publisher".to_string(), optional(&msi.publisher)),
+            ]),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            ("setupFilePath".to_string(), self.setup_file.clone().into()),
+            ("msiInformation".to_string(), msi),
+            (
+                "rules".to_string(),
+                JsonValue::Array(
+                    self.rules
+                        .iter()
+                        .map(DetectionRule::to_json_value)
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_pretty_string()
+    }
 }
 
 #[cfg(test)]
@@ -169,12 +777,53 @@ mod tests {
     fn test_encryption_info_default() {
         let info = EncryptionInfo::default();
         assert_eq!(info.profile_identifier, "ProfileVersion1");
-        assert_eq!(info.file_digest_algorithm, "SHA256");
+        assert_eq!(info.file_digest_algorithm.name(), "SHA256");
+        assert_eq!(info.file_digest.len(), 32);
         assert_eq!(info.encryption_key.len(), 32);
         assert_eq!(info.mac_key.len(), 32);
         assert_eq!(info.iv.len(), 16);
     }
 
+    #[test]
+    fn test_digest_algorithm_names() {
+        for algorithm in DigestAlgorithm::ALL {
+            assert_eq!(algorithm.name().parse::<DigestAlgorithm>(), Ok(algorithm));
+        }
+        assert_eq!("sha-384".parse(), Ok(DigestAlgorithm::Sha384));
+        assert!("MD5".parse::<DigestAlgorithm>().is_err());
+
+        let mut info = EncryptionInfo::new();
+        info.set_file_digest_from_base64(&BASE64.encode([1u8; 48]))
+            .unwrap();
+        assert!(info.check_file_digest().is_err());
+        info.file_digest_algorithm = DigestAlgorithm::Sha384;
+        assert!(info.check_file_digest().is_ok());
+        assert!(
+            info.set_file_digest_from_base64(&BASE64.encode([1u8; 20]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_encryption_info_debug_redacts_keys() {
+        let mut info = EncryptionInfo::new();
+        info.encryption_key = [7u8; 32];
+        info.mac_key = [8u8; 32];
+        info.iv = [9u8; 16];
+
+        let debug = format!("{:?}", info);
+        assert!(!debug.contains(&info.encryption_key_base64()), "{}", debug);
+        assert!(!debug.contains(&info.mac_key_base64()), "{}", debug);
+        assert!(debug.contains(REDACTED));
+        assert!(debug.contains(&info.iv_base64()));
+
+        info.zeroize();
+        assert_eq!(info.encryption_key, [0u8; 32]);
+        assert_eq!(info.mac_key, [0u8; 32]);
+        assert_eq!(info.iv, [0u8; 16]);
+        assert_eq!(info.profile_identifier, "ProfileVersion1");
+    }
+
     #[test]
     fn test_base64_encoding() {
         let mut info = EncryptionInfo::new();
@@ -195,4 +844,98 @@ mod tests {
         assert_eq!(meta.file_name, "IntunePackage.intunewin");
         assert_eq!(meta.unencrypted_content_size, 1024);
     }
+
+    #[test]
+    fn test_detection_metadata_json_redacts_keys() {
+        let mut metadata = DetectionMetadata::new("setup.exe".to_string(), 42);
+        metadata.encryption_info.encryption_key = [7u8; 32];
+        let key = metadata.encryption_info.encryption_key_base64();
+
+        let redacted = metadata.to_json(false);
+        assert!(redacted.contains("\"encryption_key\": \"<redacted>\""));
+        assert!(redacted.contains("\"encryption_key_bits\": 256"));
+        assert!(!redacted.contains(&key));
+
+        let json = crate::json::parse(&metadata.to_json(true)).unwrap();
+        let encryption = json.get("encryption").unwrap();
+        assert_eq!(
+            encryption.get("encryption_key").and_then(JsonValue::as_str),
+            Some(key.as_str())
+        );
+        assert_eq!(
+            json.get("setup_file").and_then(JsonValue::as_str),
+            Some("setup.exe")
+        );
+    }
+
+    #[test]
+    fn test_file_rule() {
+        assert_eq!(
+            DetectionRule::file("C:\\Program Files\\App\\app.exe").unwrap(),
+            DetectionRule::File {
+                path: "C:\\Program Files\\App".to_string(),
+                name: "app.exe".to_string(),
+            }
+        );
+        assert!(DetectionRule::file("app.exe").is_err());
+    }
+
+    #[test]
+    fn test_registry_rule() {
+        assert_eq!(
+            DetectionRule::registry("HKLM\\SOFTWARE\\Acme\\App:Version=1.2").unwrap(),
+            DetectionRule::Registry {
+                key_path: "HKEY_LOCAL_MACHINE\\SOFTWARE\\Acme\\App".to_string(),
+                value_name: Some("Version".to_string()),
+                expected: Some("1.2".to_string()),
+            }
+        );
+        let rule = DetectionRule::registry("hkcu\\Software\\Acme").unwrap();
+        let json = rule.to_json_value();
+        assert_eq!(
+            json.get("keyPath").and_then(JsonValue::as_str),
+            Some("HKEY_CURRENT_USER\\Software\\Acme")
+        );
+        assert_eq!(
+            json.get("operationType").and_then(JsonValue::as_str),
+            Some("exists")
+        );
+        assert!(DetectionRule::registry("SOFTWARE\\Acme").is_err());
+        assert!(DetectionRule::registry("HKLM\\SOFTWARE\\Acme:").is_err());
+    }
+
+    #[test]
+    fn test_detection_rules_json() {
+        let rules = DetectionRules {
+            setup_file: "setup.msi".to_string(),
+            msi: None,
+            rules: vec![DetectionRule::MsiProductCode {
+                product_code: "{A}".to_string(),
+                product_version: Some("1.0".to_string()),
+            }],
+        };
+        let json = crate::json::parse(&rules.to_json()).unwrap();
+        let rule = &json.get("rules").unwrap().as_array().unwrap()[0];
+        assert_eq!(
+            rule.get("@odata.type").and_then(JsonValue::as_str),
+            Some("#microsoft.graph.win32LobAppProductCodeRule")
+        );
+        assert_eq!(
+            rule.get("productVersionOperator")
+                .and_then(JsonValue::as_str),
+            Some("greaterThanOrEqual")
+        );
+        assert_eq!(json.get("msiInformation"), Some(&JsonValue::Null));
+    }
+
+    #[test]
+    fn test_rules_output_path() {
+        let request = DetectionRulesRequest::new("/out/setup.intunewin");
+        assert_eq!(
+            request.output_path(),
+            PathBuf::from("/out/setup.detection.json")
+        );
+        let request = request.with_output_file("/tmp/rules.json");
+        assert_eq!(request.output_path(), PathBuf::from("/tmp/rules.json"));
+    }
 }
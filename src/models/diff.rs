@@ -0,0 +1,153 @@
+//! Differences between two `.intunewin` packages (`intune diff`).
+
+use std::path::PathBuf;
+
+use crate::json::JsonValue;
+use crate::models::size::ByteSize;
+
+/// How a file differs between the two packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Only in the new package
+    Added,
+    /// Only in the old package
+    Removed,
+    /// In both, with different content
+    Changed,
+}
+
+impl ChangeKind {
+    /// Name used in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        }
+    }
+
+    /// Marker in front of a path in text reports.
+    pub fn symbol(self) -> char {
+        match self {
+            ChangeKind::Added => '+',
+            ChangeKind::Removed => '-',
+            ChangeKind::Changed => '~',
+        }
+    }
+}
+
+/// Size and hash of a file in one of the packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSummary {
+    /// Uncompressed size
+    pub size: ByteSize,
+    /// SHA-256 of the content (hex)
+    pub sha256: String,
+}
+
+/// A file that differs between the packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path in the package content, with `/` separators
+    pub path: String,
+    /// How the file differs
+    pub kind: ChangeKind,
+    /// The file in the old package
+    pub old: Option<FileSummary>,
+    /// The file in the new package
+    pub new: Option<FileSummary>,
+}
+
+/// A Detection.xml field that differs between the packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    /// Element path below `ApplicationInfo`, e.g. `MsiInfo/MsiProductVersion`;
+    /// attributes end in `@name`
+    pub field: String,
+    /// Value in the old package
+    pub old: Option<String>,
+    /// Value in the new package
+    pub new: Option<String>,
+}
+
+/// Everything that differs between two packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageDiff {
+    /// The package compared against
+    pub old: PathBuf,
+    /// The package compared
+    pub new: PathBuf,
+    /// Changed Detection.xml fields, in document order
+    pub detection: Vec<FieldChange>,
+    /// Added, removed and changed files, sorted by path
+    pub files: Vec<FileChange>,
+    /// Files with the same content in both packages
+    pub unchanged: usize,
+}
+
+impl PackageDiff {
+    /// Whether the packages hold the same files and Detection.xml fields.
+    pub fn is_empty(&self) -> bool {
+        self.detection.is_empty() && self.files.is_empty()
+    }
+
+    /// Number of files that differ in the given way.
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.files.iter().filter(|f| f.kind == kind).count()
+    }
+
+    /// Serialize the differences as JSON.
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => value.clone().into(),
+            None => JsonValue::Null,
+        };
+        let summary = |file: &Option<FileSummary>| match file {
+            Some(file) => JsonValue::Object(vec![
+                ("size".to_string(), file.size.0.into()),
+                ("sha256".to_string(), file.sha256.clone().into()),
+            ]),
+            None => JsonValue::Null,
+        };
+
+        let detection = self
+            .detection
+            .iter()
+            .map(|change| {
+                JsonValue::Object(vec![
+                    ("field".to_string(), change.field.clone().into()),
+                    ("old".to_string(), optional(&change.old)),
+                    ("new".to_string(), optional(&change.new)),
+                ])
+            })
+            .collect();
+        let files = self
+            .files
+            .iter()
+            .map(|change| {
+                JsonValue::Object(vec![
+                    ("path".to_string(), change.path.clone().into()),
+                    ("change".to_string(), change.kind.name().into()),
+                    ("old".to_string(), summary(&change.old)),
+                    ("new".to_string(), summary(&change.new)),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            (
+                "old".to_string(),
+                self.old.to_string_lossy().into_owned().into(),
+            ),
+            (
+                "new".to_string(),
+                self.new.to_string_lossy().into_owned().into(),
+            ),
+            ("identical".to_string(), self.is_empty().into()),
+            ("detection".to_string(), JsonValue::Array(detection)),
+            ("files".to_string(), JsonValue::Array(files)),
+            ("unchanged".to_string(), (self.unchanged as u64).into()),
+        ])
+        .to_pretty_string()
+    }
+}
@@ -0,0 +1,173 @@
+//! What a package would hold, without building it (`--dry-run`).
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::json::JsonValue;
+use crate::models::blocklist::BlocklistMatch;
+use crate::models::guardrails::SizeEstimate;
+use crate::models::package::{PathIssue, SkippedEntry, SparseFile};
+use crate::models::size::ByteSize;
+
+/// Content packaged per second, used to estimate how long a run takes:
+/// reading, compressing and (for Intune) encrypting on one core.
+pub const ESTIMATED_THROUGHPUT: ByteSize = ByteSize(40 << 20);
+
+/// The outcome of collecting and checking a package's content without
+/// compressing or writing anything.
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    /// Where the package would be written
+    pub output_path: PathBuf,
+    /// Whether a file already exists there
+    pub output_exists: bool,
+    /// Files that would be packaged
+    pub file_count: usize,
+    /// Directories that would be packaged
+    pub dir_count: usize,
+    /// Total uncompressed size of the files
+    pub total_size: ByteSize,
+    /// Projected package size and its limit (Intune packages only)
+    pub size_estimate: Option<SizeEstimate>,
+    /// Rough time packaging would take, from [`ESTIMATED_THROUGHPUT`]
+    pub estimated_time: Duration,
+    /// What was detected about the setup, as label and value, e.g. the
+    /// setup file's product version or a bundle's identifier
+    pub metadata: Vec<(String, String)>,
+    /// Content files that matched the blocklist (warn mode only)
+    pub blocklist_matches: Vec<BlocklistMatch>,
+    /// Content files that are sparse on disk
+    pub sparse_files: Vec<SparseFile>,
+    /// Special entries in the source that would be left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Content paths that would not install on Windows (warn mode only)
+    pub path_issues: Vec<PathIssue>,
+}
+
+impl DryRunReport {
+    /// Rough time packaging `total_size` bytes takes.
+    pub fn estimate_time(total_size: ByteSize) -> Duration {
+        Duration::from_secs_f64(total_size.bytes() as f64 / ESTIMATED_THROUGHPUT.bytes() as f64)
+    }
+
+    /// Serialize the report as JSON.
+    pub fn to_json(&self) -> String {
+        let paths = |paths: Vec<&std::path::Path>| {
+            JsonValue::Array(
+                paths
+                    .into_iter()
+                    .map(|path| path.to_string_lossy().into_owned().into())
+                    .collect(),
+            )
+        };
+
+        let mut fields = vec![
+            (
+                "output_path".to_string(),
+                self.output_path.to_string_lossy().into_owned().into(),
+            ),
+            ("output_exists".to_string(), self.output_exists.into()),
+            ("file_count".to_string(), (self.file_count as u64).into()),
+            ("dir_count".to_string(), (self.dir_count as u64).into()),
+            ("total_size".to_string(), self.total_size.into()),
+        ];
+        if let Some(estimate) = &self.size_estimate {
+            fields.push(("projected_size".to_string(), estimate.projected.into()));
+            fields.push(("size_limit".to_string(), estimate.limit.into()));
+        }
+        fields.extend([
+            (
+                "estimated_seconds".to_string(),
+                self.estimated_time.as_secs().into(),
+            ),
+            (
+                "metadata".to_string(),
+                JsonValue::Object(
+                    self.metadata
+                        .iter()
+                        .map(|(label, value)| (label.clone(), value.clone().into()))
+                        .collect(),
+                ),
+            ),
+            (
+                "blocklist_matches".to_string(),
+                paths(
+                    self.blocklist_matches
+                        .iter()
+                        .map(|m| m.path.as_path())
+                        .collect(),
+                ),
+            ),
+            (
+                "sparse_files".to_string(),
+                paths(
+                    self.sparse_files
+                        .iter()
+                        .map(|f| f.relative_path.as_path())
+                        .collect(),
+                ),
+            ),
+            (
+                "skipped_entries".to_string(),
+                paths(
+                    self.skipped_entries
+                        .iter()
+                        .map(|e| e.relative_path.as_path())
+                        .collect(),
+                ),
+            ),
+            (
+                "path_issues".to_string(),
+                JsonValue::Array(
+                    self.path_issues
+                        .iter()
+                        .map(PathIssue::to_json_value)
+                        .collect(),
+                ),
+            ),
+        ]);
+        JsonValue::Object(fields).to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_dry_run_report_json() {
+        let report = DryRunReport {
+            output_path: PathBuf::from("out/setup.intunewin"),
+            output_exists: false,
+            file_count: 2,
+            dir_count: 1,
+            total_size: ByteSize(80 << 20),
+            size_estimate: Some(SizeEstimate {
+                projected: ByteSize((80 << 20) + 9000),
+                limit: ByteSize::GIB,
+            }),
+            estimated_time: DryRunReport::estimate_time(ByteSize(80 << 20)),
+            metadata: vec![("setup_file".to_string(), "setup.exe".to_string())],
+            blocklist_matches: Vec::new(),
+            sparse_files: Vec::new(),
+            skipped_entries: Vec::new(),
+            path_issues: Vec::new(),
+        };
+        assert_eq!(report.estimated_time, Duration::from_secs(2));
+
+        let value = json::parse(&report.to_json()).unwrap();
+        assert_eq!(value.get("file_count").and_then(JsonValue::as_u64), Some(2));
+        assert_eq!(
+            value.get("size_limit").and_then(JsonValue::as_u64),
+            Some(ByteSize::GIB.bytes())
+        );
+        assert_eq!(
+            value
+                .get("metadata")
+                .and_then(|m| m.get("setup_file"))
+                .and_then(JsonValue::as_str),
+            Some("setup.exe")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Where the keys a package is encrypted with come from.
+//!
+//! Keys are random by default. For key escrow, or to rebuild a package with
+//! the keys it was first built with, the caller can supply them instead.
+//! Supplied keys are read from JSON with the names `intune inspect --json
+//! --show-secrets` prints, either at the top level or under `encryption`:
+//!
+//! ```json
+//! { "encryption_key": "<base64, 32 bytes>", "mac_key": "<base64, 32 bytes>", "iv": "<base64, 16 bytes>" }
+//! ```
+//!
+//! The IV is optional and random when left out. Reusing an IV with the same
+//! key for different content shows where the content begins to differ, so
+//! it is best only given to rebuild the same content.
+
+use std::fmt;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use zeroize::Zeroize;
+
+use crate::json::{self, JsonValue};
+use crate::models::detection::DigestAlgorithm;
+
+/// Keys and IV supplied by the caller, wiped from memory when dropped and
+/// left out of `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProvidedKeys {
+    /// AES-256 encryption key
+    pub key: [u8; 32],
+    /// HMAC-SHA256 key
+    pub mac_key: [u8; 32],
+    /// AES initialization vector; random when `None`
+    pub iv: Option<[u8; 16]>,
+}
+
+impl ProvidedKeys {
+    /// Parse keys from JSON text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = json::parse(text.trim_start_matches('\u{feff}'))?;
+        Self::from_json_value(&value)
+    }
+
+    /// Read keys from a JSON object, or from its `encryption` member.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let value = value.get("encryption").unwrap_or(value);
+        if value.as_object().is_none() {
+            return Err("Keys must be a JSON object".to_string());
+        }
+
+        let keys = Self {
+            key: decode(value, "encryption_key")?.ok_or("'encryption_key' is missing")?,
+            mac_key: decode(value, "mac_key")?.ok_or("'mac_key' is missing")?,
+            iv: decode(value, "iv")?,
+        };
+        if keys.key == keys.mac_key {
+            return Err("The encryption and MAC keys must differ".to_string());
+        }
+        if keys.key == [0; 32] || keys.mac_key == [0; 32] {
+            return Err("A key is all zeros".to_string());
+        }
+        Ok(keys)
+    }
+}
+
+/// Decode the Base64 member `name` of `value` into exactly `N` bytes.
+fn decode<const N: usize>(value: &JsonValue, name: &str) -> Result<Option<[u8; N]>, String> {
+    let Some(member) = value.get(name) else {
+        return Ok(None);
+    };
+    let text = member
+        .as_str()
+        .ok_or_else(|| format!("'{}' must be a Base64 string", name))?;
+    let mut bytes = BASE64
+        .decode(text.trim())
+        .map_err(|e| format!("'{}' is not valid Base64: {}", name, e))?;
+
+    let result = <[u8; N]>::try_from(bytes.as_slice())
+        .map(Some)
+        .map_err(|_| format!("'{}' must be {} bytes, not {}", name, N, bytes.len()));
+    bytes.zeroize();
+    result
+}
+
+impl Drop for ProvidedKeys {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.mac_key.zeroize();
+        self.iv.zeroize();
+    }
+}
+
+impl fmt::Debug for ProvidedKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProvidedKeys(***)")
+    }
+}
+
+/// Where the keys a package is encrypted with come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KeySource {
+    /// Fresh random keys and IV, or keys derived for a reproducible package
+    #[default]
+    Random,
+    /// Keys supplied by the caller
+    Provided(ProvidedKeys),
+}
+
+/// How the content of a package is encrypted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncryptionOptions {
+    /// Random or caller-supplied keys
+    pub keys: KeySource,
+    /// Algorithm of the content digest recorded in Detection.xml
+    pub digest_algorithm: DigestAlgorithm,
+}
+
+impl EncryptionOptions {
+    /// Encrypt with keys supplied by the caller.
+    pub fn provided(keys: ProvidedKeys) -> Self {
+        Self {
+            keys: KeySource::Provided(keys),
+            ..Self::default()
+        }
+    }
+
+    /// Set the algorithm of the content digest.
+    pub fn with_digest_algorithm(mut self, algorithm: DigestAlgorithm) -> Self {
+        self.digest_algorithm = algorithm;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base64_of(byte: u8, len: usize) -> String {
+        BASE64.encode(vec![byte; len])
+    }
+
+    #[test]
+    fn test_provided_keys_parse() {
+        let text = format!(
+            r#"{{ "encryption_key": "{}", "mac_key": "{}" }}"#,
+            base64_of(1, 32),
+            base64_of(2, 32)
+        );
+        let keys = ProvidedKeys::parse(&text).unwrap();
+        assert_eq!(keys.key, [1; 32]);
+        assert_eq!(keys.mac_key, [2; 32]);
+        assert_eq!(keys.iv, None);
+        assert_eq!(format!("{:?}", keys), "ProvidedKeys(***)");
+
+        // The shape `intune inspect --json --show-secrets` prints
+        let text = format!(
+            r#"{{ "name": "setup.exe", "encryption": {{ "encryption_key": "{}", "mac_key": "{}", "iv": "{}", "mac": "x" }} }}"#,
+            base64_of(1, 32),
+            base64_of(2, 32),
+            base64_of(3, 16)
+        );
+        assert_eq!(ProvidedKeys::parse(&text).unwrap().iv, Some([3; 16]));
+    }
+
+    #[test]
+    fn test_provided_keys_rejects_bad_keys() {
+        let keys = |key: &str, mac_key: &str| {
+            ProvidedKeys::parse(&format!(
+                r#"{{ "encryption_key": "{}", "mac_key": "{}" }}"#,
+                key, mac_key
+            ))
+        };
+        assert!(
+            keys(&base64_of(1, 16), &base64_of(2, 32))
+                .unwrap_err()
+                .contains("32 bytes, not 16")
+        );
+        assert!(keys("not base64!", &base64_of(2, 32)).is_err());
+        assert!(keys(&base64_of(1, 32), &base64_of(1, 32)).is_err());
+        assert!(keys(&base64_of(0, 32), &base64_of(2, 32)).is_err());
+        assert!(ProvidedKeys::parse(r#"{ "mac_key": "AAAA" }"#).is_err());
+        assert!(ProvidedKeys::parse("[]").is_err());
+    }
+}
@@ -3,6 +3,9 @@
 use std::path::PathBuf;
 use thiserror::Error;
 
+use crate::models::size::ByteSize;
+use crate::models::suggest::format_suggestions;
+
 /// Exit codes matching CLI contract
 pub mod exit_codes {
     /// Success
@@ -21,8 +24,330 @@ pub mod exit_codes {
     pub const SCRIPTS_NOT_FOUND: i32 = 6;
     /// Operation cancelled by user
     pub const CANCELLED: i32 = 7;
+    /// Content matched a known-bad hash blocklist
+    pub const BLOCKED_CONTENT: i32 = 8;
+    /// A warning was promoted to an error by strict mode
+    pub const STRICT_VIOLATION: i32 = 9;
+}
+
+/// The ZIP operation that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipStage {
+    /// Adding content to the inner (encrypted) ZIP
+    WriteContent,
+    /// Writing the outer `.intunewin` ZIP
+    WritePackage,
+    /// Reading the decrypted inner ZIP
+    ReadContent,
+    /// Writing the password-protected archive of `--extract-to-zip`
+    WriteArchive,
+}
+
+impl std::fmt::Display for ZipStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ZipStage::WriteContent => "writing the content archive",
+            ZipStage::WritePackage => "writing the package",
+            ZipStage::ReadContent => "reading the content archive",
+            ZipStage::WriteArchive => "writing the protected archive",
+        })
+    }
+}
+
+fn format_entry(entry: &Option<String>) -> String {
+    match entry {
+        Some(entry) => format!(" (entry '{}')", entry),
+        None => String::new(),
+    }
+}
+
+/// Stable, machine-readable identifier of a [`PackageError`] variant.
+///
+/// Error messages may be reworded between releases; codes are not. Match
+/// on the code, or compare its [`name`](ErrorCode::name) (e.g.
+/// `setup_file_not_found`), instead of the message. New codes may be added
+/// in minor releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// Source folder not found or not accessible
+    SourceFolderNotFound,
+    /// Source folder is empty
+    SourceFolderEmpty,
+    /// Setup file not found in source folder
+    SetupFileNotFound,
+    /// Output folder creation failed
+    OutputFolderCreationFailed,
+    /// Output file already exists (non-quiet mode)
+    OutputFileExists,
+    /// Failed to read source file
+    SourceReadError,
+    /// Encryption error
+    EncryptionError,
+    /// Failed to write output
+    OutputWriteError,
+    /// ZIP error, with the stage and entry being processed
+    ZipError,
+    /// XML generation error
+    XmlError,
+    /// Invalid argument
+    InvalidArgument,
+    /// User cancelled operation
+    Cancelled,
+    /// Invalid .intunewin file
+    InvalidIntunewinFile,
+    /// Decryption error
+    DecryptionError,
+    /// HMAC verification failed
+    HmacVerificationFailed,
+    /// Invalid PKCS7 padding
+    InvalidPadding,
+    /// Blocklist file could not be read or parsed
+    BlocklistError,
+    /// Content contains a blocklisted file
+    BlockedContent,
+    /// Source folder is a filesystem root or home directory
+    DangerousSourceFolder,
+    /// Collected content exceeds the file count or size limits
+    SourceTooLarge,
+    /// The temp workspace has too little free space for the run
+    InsufficientSpace,
+    /// Produced package failed post-creation validation
+    ValidationFailed,
+    /// Package failed one or more `intune verify` or `macos validate`
+    /// checks
+    VerificationFailed,
+    /// Capture snapshot missing, invalid, or without changes
+    CaptureError,
+    /// Project file could not be written, read or reproduced
+    ProjectError,
+    /// A package-time file transformation failed
+    TransformError,
+    /// One or more self-test stages did not pass
+    SelfTestFailed,
+    /// Configuration file could not be read or parsed
+    ConfigError,
+    /// Batch manifest could not be read or parsed
+    BatchManifestError,
+    /// App relationships file could not be read or parsed
+    RelationshipsError,
+    /// One or more items of a batch were not packaged
+    BatchFailed,
+    /// Content cache folder or blob could not be used
+    CacheError,
+    /// An archive entry would be extracted outside the output folder
+    UnsafePath,
+    /// A check configured as an error failed
+    StrictViolation,
+    /// Scripts folder not found
+    ScriptsFolderNotFound,
+    /// Scripts folder has no valid scripts
+    NoScriptsFound,
+    /// XAR archive creation error
+    XarError,
+    /// CPIO archive creation error
+    CpioError,
+    /// BOM file creation error
+    BomError,
+    /// Signing identity could not be loaded or used
+    SigningError,
+    /// Detached signature is malformed or does not match the file
+    SignatureInvalid,
+    /// The notary service could not be reached, or did not accept the package
+    NotarizationError,
+    /// Application bundle is missing or has an unusable Info.plist
+    InvalidBundle,
+    /// A remote source could not be downloaded or failed its checksum
+    SourceDownloadError,
+    /// Debian package archive creation error
+    DebError,
+    /// RPM package creation error
+    RpmError,
+    /// I/O error wrapper
+    Io,
+}
+
+impl ErrorCode {
+    /// Every code, in declaration order.
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::SourceFolderNotFound,
+        ErrorCode::SourceFolderEmpty,
+        ErrorCode::SetupFileNotFound,
+        ErrorCode::OutputFolderCreationFailed,
+        ErrorCode::OutputFileExists,
+        ErrorCode::SourceReadError,
+        ErrorCode::EncryptionError,
+        ErrorCode::OutputWriteError,
+        ErrorCode::ZipError,
+        ErrorCode::XmlError,
+        ErrorCode::InvalidArgument,
+        ErrorCode::Cancelled,
+        ErrorCode::InvalidIntunewinFile,
+        ErrorCode::DecryptionError,
+        ErrorCode::HmacVerificationFailed,
+        ErrorCode::InvalidPadding,
+        ErrorCode::BlocklistError,
+        ErrorCode::BlockedContent,
+        ErrorCode::DangerousSourceFolder,
+        ErrorCode::SourceTooLarge,
+        ErrorCode::InsufficientSpace,
+        ErrorCode::ValidationFailed,
+        ErrorCode::VerificationFailed,
+        ErrorCode::CaptureError,
+        ErrorCode::ProjectError,
+        ErrorCode::TransformError,
+        ErrorCode::SelfTestFailed,
+        ErrorCode::ConfigError,
+        ErrorCode::BatchManifestError,
+        ErrorCode::RelationshipsError,
+        ErrorCode::BatchFailed,
+        ErrorCode::CacheError,
+        ErrorCode::UnsafePath,
+        ErrorCode::StrictViolation,
+        ErrorCode::ScriptsFolderNotFound,
+        ErrorCode::NoScriptsFound,
+        ErrorCode::XarError,
+        ErrorCode::CpioError,
+        ErrorCode::BomError,
+        ErrorCode::SigningError,
+        ErrorCode::SignatureInvalid,
+        ErrorCode::NotarizationError,
+        ErrorCode::InvalidBundle,
+        ErrorCode::SourceDownloadError,
+        ErrorCode::DebError,
+        ErrorCode::RpmError,
+        ErrorCode::Io,
+    ];
+
+    /// Serialized name of the code: the variant name in snake case.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::SourceFolderNotFound => "source_folder_not_found",
+            ErrorCode::SourceFolderEmpty => "source_folder_empty",
+            ErrorCode::SetupFileNotFound => "setup_file_not_found",
+            ErrorCode::OutputFolderCreationFailed => "output_folder_creation_failed",
+            ErrorCode::OutputFileExists => "output_file_exists",
+            ErrorCode::SourceReadError => "source_read_error",
+            ErrorCode::EncryptionError => "encryption_error",
+            ErrorCode::OutputWriteError => "output_write_error",
+            ErrorCode::ZipError => "zip_error",
+            ErrorCode::XmlError => "xml_error",
+            ErrorCode::InvalidArgument => "invalid_argument",
+            ErrorCode::Cancelled => "cancelled",
+            ErrorCode::InvalidIntunewinFile => "invalid_intunewin_file",
+            ErrorCode::DecryptionError => "decryption_error",
+            ErrorCode::HmacVerificationFailed => "hmac_verification_failed",
+            ErrorCode::InvalidPadding => "invalid_padding",
+            ErrorCode::BlocklistError => "blocklist_error",
+            ErrorCode::BlockedContent => "blocked_content",
+            ErrorCode::DangerousSourceFolder => "dangerous_source_folder",
+            ErrorCode::SourceTooLarge => "source_too_large",
+            ErrorCode::InsufficientSpace => "insufficient_space",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::VerificationFailed => "verification_failed",
+            ErrorCode::CaptureError => "capture_error",
+            ErrorCode::ProjectError => "project_error",
+            ErrorCode::TransformError => "transform_error",
+            ErrorCode::SelfTestFailed => "self_test_failed",
+            ErrorCode::ConfigError => "config_error",
+            ErrorCode::BatchManifestError => "batch_manifest_error",
+            ErrorCode::RelationshipsError => "relationships_error",
+            ErrorCode::BatchFailed => "batch_failed",
+            ErrorCode::CacheError => "cache_error",
+            ErrorCode::UnsafePath => "unsafe_path",
+            ErrorCode::StrictViolation => "strict_violation",
+            ErrorCode::ScriptsFolderNotFound => "scripts_folder_not_found",
+            ErrorCode::NoScriptsFound => "no_scripts_found",
+            ErrorCode::XarError => "xar_error",
+            ErrorCode::CpioError => "cpio_error",
+            ErrorCode::BomError => "bom_error",
+            ErrorCode::SigningError => "signing_error",
+            ErrorCode::SignatureInvalid => "signature_invalid",
+            ErrorCode::NotarizationError => "notarization_error",
+            ErrorCode::InvalidBundle => "invalid_bundle",
+            ErrorCode::SourceDownloadError => "source_download_error",
+            ErrorCode::DebError => "deb_error",
+            ErrorCode::RpmError => "rpm_error",
+            ErrorCode::Io => "io",
+        }
+    }
+
+    /// Process exit code the CLI uses for errors with this code.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::SourceFolderNotFound => exit_codes::ERROR,
+            ErrorCode::SourceFolderEmpty => exit_codes::EMPTY_SOURCE,
+            ErrorCode::SetupFileNotFound => exit_codes::SETUP_NOT_FOUND,
+            ErrorCode::OutputFolderCreationFailed => exit_codes::OUTPUT_ERROR,
+            ErrorCode::OutputFileExists => exit_codes::ERROR,
+            ErrorCode::SourceReadError => exit_codes::ERROR,
+            ErrorCode::EncryptionError => exit_codes::ERROR,
+            ErrorCode::OutputWriteError => exit_codes::OUTPUT_ERROR,
+            ErrorCode::ZipError => exit_codes::ERROR,
+            ErrorCode::XmlError => exit_codes::ERROR,
+            ErrorCode::InvalidArgument => exit_codes::INVALID_ARGS,
+            ErrorCode::Cancelled => exit_codes::ERROR,
+            ErrorCode::InvalidIntunewinFile => exit_codes::ERROR,
+            ErrorCode::DecryptionError => exit_codes::ERROR,
+            ErrorCode::HmacVerificationFailed => exit_codes::ERROR,
+            ErrorCode::InvalidPadding => exit_codes::ERROR,
+            ErrorCode::BlocklistError => exit_codes::ERROR,
+            ErrorCode::BlockedContent => exit_codes::BLOCKED_CONTENT,
+            ErrorCode::DangerousSourceFolder => exit_codes::INVALID_ARGS,
+            ErrorCode::SourceTooLarge => exit_codes::ERROR,
+            ErrorCode::InsufficientSpace => exit_codes::OUTPUT_ERROR,
+            ErrorCode::ValidationFailed => exit_codes::ERROR,
+            ErrorCode::VerificationFailed => exit_codes::ERROR,
+            ErrorCode::CaptureError => exit_codes::ERROR,
+            ErrorCode::ProjectError => exit_codes::ERROR,
+            ErrorCode::TransformError => exit_codes::ERROR,
+            ErrorCode::SelfTestFailed => exit_codes::ERROR,
+            ErrorCode::ConfigError => exit_codes::INVALID_ARGS,
+            ErrorCode::BatchManifestError => exit_codes::INVALID_ARGS,
+            ErrorCode::RelationshipsError => exit_codes::INVALID_ARGS,
+            ErrorCode::BatchFailed => exit_codes::ERROR,
+            ErrorCode::CacheError => exit_codes::ERROR,
+            ErrorCode::UnsafePath => exit_codes::ERROR,
+            ErrorCode::StrictViolation => exit_codes::STRICT_VIOLATION,
+            ErrorCode::ScriptsFolderNotFound => exit_codes::SCRIPTS_NOT_FOUND,
+            ErrorCode::NoScriptsFound => exit_codes::ERROR,
+            ErrorCode::XarError => exit_codes::ERROR,
+            ErrorCode::CpioError => exit_codes::ERROR,
+            ErrorCode::BomError => exit_codes::ERROR,
+            ErrorCode::SigningError => exit_codes::INVALID_ARGS,
+            ErrorCode::SignatureInvalid => exit_codes::ERROR,
+            ErrorCode::NotarizationError => exit_codes::ERROR,
+            ErrorCode::InvalidBundle => exit_codes::INVALID_ARGS,
+            ErrorCode::SourceDownloadError => exit_codes::ERROR,
+            ErrorCode::DebError => exit_codes::ERROR,
+            ErrorCode::RpmError => exit_codes::ERROR,
+            ErrorCode::Io => exit_codes::ERROR,
+        }
+    }
 }
 
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for ErrorCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|code| code.name() == s)
+            .ok_or_else(|| format!("Unknown error code '{}'", s))
+    }
+}
+
+/// Error wrapped by a [`PackageError`], returned by its
+/// [`source`](std::error::Error::source).
+pub type ErrorSource = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 /// Result type for package operations.
 pub type PackageResult<T> = Result<T, PackageError>;
 
@@ -38,12 +363,22 @@ pub enum PackageError {
     SourceFolderEmpty { path: PathBuf },
 
     /// Setup file not found in source folder
-    #[error("Setup file '{file}' not found in {folder}")]
-    SetupFileNotFound { file: String, folder: PathBuf },
+    #[error("Setup file '{file}' not found in {folder}{}", format_suggestions(.suggestions))]
+    SetupFileNotFound {
+        file: String,
+        folder: PathBuf,
+        /// Close matches found in the folder, best first
+        suggestions: Vec<String>,
+    },
 
     /// Output folder creation failed
     #[error("Failed to create output folder '{path}': {reason}")]
-    OutputFolderCreationFailed { path: PathBuf, reason: String },
+    OutputFolderCreationFailed {
+        path: PathBuf,
+        reason: String,
+        /// Underlying I/O error, if any
+        source: Option<ErrorSource>,
+    },
 
     /// Output file already exists (non-quiet mode)
     #[error("Output file already exists: {path}")]
@@ -51,7 +386,12 @@ pub enum PackageError {
 
     /// Failed to read source file
     #[error("Failed to read source file '{path}': {reason}")]
-    SourceReadError { path: PathBuf, reason: String },
+    SourceReadError {
+        path: PathBuf,
+        reason: String,
+        /// Underlying I/O error, if any
+        source: Option<ErrorSource>,
+    },
 
     /// Encryption error
     #[error("Encryption error: {reason}")]
@@ -59,15 +399,30 @@ pub enum PackageError {
 
     /// Failed to write output
     #[error("Failed to write output to '{path}': {reason}")]
-    OutputWriteError { path: PathBuf, reason: String },
+    OutputWriteError {
+        path: PathBuf,
+        reason: String,
+        /// Underlying I/O error, if any
+        source: Option<ErrorSource>,
+    },
 
-    /// ZIP creation error
-    #[error("ZIP creation error: {reason}")]
-    ZipError { reason: String },
+    /// ZIP error, with the stage and entry being processed
+    #[error("ZIP error while {stage}{}: {reason}", format_entry(.entry))]
+    ZipError {
+        stage: ZipStage,
+        entry: Option<String>,
+        reason: String,
+        /// Underlying ZIP or I/O error
+        source: Option<ErrorSource>,
+    },
 
     /// XML generation error
     #[error("XML generation error: {reason}")]
-    XmlError { reason: String },
+    XmlError {
+        reason: String,
+        /// Underlying XML, encoding or number parsing error, if any
+        source: Option<ErrorSource>,
+    },
 
     /// Invalid argument
     #[error("Invalid argument: {reason}")]
@@ -93,6 +448,96 @@ pub enum PackageError {
     #[error("Invalid padding in decrypted data")]
     InvalidPadding,
 
+    /// Blocklist file could not be read or parsed
+    #[error("Invalid blocklist '{path}': {reason}")]
+    BlocklistError { path: PathBuf, reason: String },
+
+    /// Content contains a blocklisted file
+    #[error("Blocked file '{path}' matches known-bad hash {sha256} ({count} match(es) total)")]
+    BlockedContent {
+        path: PathBuf,
+        sha256: String,
+        count: usize,
+    },
+
+    /// Source folder is a filesystem root or home directory
+    #[error("Refusing to package '{path}': {reason} (use --i-know-what-im-doing to override)")]
+    DangerousSourceFolder { path: PathBuf, reason: String },
+
+    /// Collected content exceeds the file count or size limits
+    #[error("Source folder '{path}' is unusually large: {reason} (use --force to package anyway)")]
+    SourceTooLarge { path: PathBuf, reason: String },
+
+    /// The temp workspace's volume has too little free space
+    #[error(
+        "Not enough free space for the temp workspace in '{path}': {needed} needed, {available} available (set TMPDIR or --temp-dir to use another volume)"
+    )]
+    InsufficientSpace {
+        path: PathBuf,
+        needed: ByteSize,
+        available: ByteSize,
+    },
+
+    /// Produced package failed post-creation validation
+    #[error("Post-validation of '{path}' failed: {reason}")]
+    ValidationFailed { path: PathBuf, reason: String },
+
+    /// Package failed one or more `intune verify` or `macos validate`
+    /// checks
+    #[error("Verification of '{path}' failed: {failures} check(s) did not pass")]
+    VerificationFailed { path: PathBuf, failures: usize },
+
+    /// Capture snapshot missing, invalid, or without changes
+    #[error("Capture error: {reason}")]
+    CaptureError { reason: String },
+
+    /// Project file could not be written, read or reproduced
+    #[error("Project error for '{path}': {reason}")]
+    ProjectError { path: PathBuf, reason: String },
+
+    /// A package-time file transformation failed
+    #[error("Failed to transform '{path}' with '{transformer}': {reason}")]
+    TransformError {
+        path: PathBuf,
+        transformer: String,
+        reason: String,
+    },
+
+    /// One or more self-test stages did not pass
+    #[error("Self-test failed: {failures} stage(s) did not pass")]
+    SelfTestFailed { failures: usize },
+
+    /// Configuration file could not be read or parsed
+    #[error("Invalid config file '{path}': {reason}")]
+    ConfigError { path: PathBuf, reason: String },
+
+    /// Batch manifest could not be read or parsed
+    #[error("Invalid batch manifest '{path}': {reason}")]
+    BatchManifestError { path: PathBuf, reason: String },
+
+    /// App relationships file could not be read or parsed
+    #[error("Invalid relationships file '{path}': {reason}")]
+    RelationshipsError { path: PathBuf, reason: String },
+
+    /// One or more items of a batch were not packaged
+    #[error("Batch failed: {failures} of {total} item(s) were not packaged")]
+    BatchFailed { failures: usize, total: usize },
+
+    /// Content cache folder or blob could not be used
+    #[error("Content cache error for '{path}': {reason}")]
+    CacheError { path: PathBuf, reason: String },
+
+    /// An archive entry would be extracted outside the output folder
+    #[error("Unsafe path '{path}' in archive: {reason}")]
+    UnsafePath { path: String, reason: String },
+
+    /// A check configured as an error failed
+    #[error("Strict mode: {message} (check '{check}')")]
+    StrictViolation {
+        check: &'static str,
+        message: String,
+    },
+
     // macOS package errors
     /// Scripts folder not found
     #[error("Scripts folder not found: {path}")]
@@ -114,40 +559,170 @@ pub enum PackageError {
     #[error("BOM file error: {reason}")]
     BomError { reason: String },
 
+    /// Signing identity could not be loaded or used
+    #[error("Signing identity '{path}': {reason}")]
+    SigningError { path: PathBuf, reason: String },
+
+    /// Detached signature is malformed or does not match the file
+    #[error("Signature of '{path}' is invalid: {reason}")]
+    SignatureInvalid { path: PathBuf, reason: String },
+
+    /// The notary service could not be reached, or did not accept the package
+    #[error("Notarization failed: {reason}")]
+    NotarizationError { reason: String },
+
+    /// Application bundle is missing or has an unusable Info.plist
+    #[error("Invalid app bundle '{path}': {reason}")]
+    InvalidBundle { path: PathBuf, reason: String },
+
+    /// A remote source could not be downloaded or failed its checksum
+    #[error("Failed to download source '{url}': {reason}")]
+    SourceDownloadError { url: String, reason: String },
+
+    // Linux package errors
+    /// Debian package archive creation error
+    #[error("Debian package error: {reason}")]
+    DebError { reason: String },
+
+    /// RPM package creation error
+    #[error("RPM package error: {reason}")]
+    RpmError { reason: String },
+
     /// I/O error wrapper
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 }
 
 impl PackageError {
-    /// Get the exit code for this error.
-    pub fn exit_code(&self) -> i32 {
+    /// A [`PackageError::ZipError`] for `entry` (`None` for the archive as a whole).
+    pub fn zip(
+        stage: ZipStage,
+        entry: Option<&str>,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        PackageError::ZipError {
+            stage,
+            entry: entry.map(String::from),
+            reason: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// A [`PackageError::SourceReadError`] caused by `error`.
+    pub fn source_read(
+        path: impl Into<PathBuf>,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        PackageError::SourceReadError {
+            path: path.into(),
+            reason: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// A [`PackageError::OutputWriteError`] caused by `error`.
+    pub fn output_write(
+        path: impl Into<PathBuf>,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        PackageError::OutputWriteError {
+            path: path.into(),
+            reason: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// A [`PackageError::OutputFolderCreationFailed`] caused by `error`.
+    pub fn output_folder(
+        path: impl Into<PathBuf>,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        PackageError::OutputFolderCreationFailed {
+            path: path.into(),
+            reason: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// A [`PackageError::XmlError`] caused by `error`.
+    pub fn xml(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        PackageError::XmlError {
+            reason: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// A [`PackageError::XmlError`] that wraps `error`, described as
+    /// `reason`.
+    pub fn xml_with(
+        reason: impl Into<String>,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        PackageError::XmlError {
+            reason: reason.into(),
+            source: Some(Box::new(error)),
+        }
+    }
+
+    /// Stable code identifying the kind of error.
+    pub fn error_code(&self) -> ErrorCode {
         match self {
-            PackageError::SourceFolderNotFound { .. } => exit_codes::ERROR,
-            PackageError::SourceFolderEmpty { .. } => exit_codes::EMPTY_SOURCE,
-            PackageError::SetupFileNotFound { .. } => exit_codes::SETUP_NOT_FOUND,
-            PackageError::OutputFolderCreationFailed { .. } => exit_codes::OUTPUT_ERROR,
-            PackageError::OutputFileExists { .. } => exit_codes::ERROR,
-            PackageError::SourceReadError { .. } => exit_codes::ERROR,
-            PackageError::EncryptionError { .. } => exit_codes::ERROR,
-            PackageError::OutputWriteError { .. } => exit_codes::OUTPUT_ERROR,
-            PackageError::ZipError { .. } => exit_codes::ERROR,
-            PackageError::XmlError { .. } => exit_codes::ERROR,
-            PackageError::InvalidArgument { .. } => exit_codes::INVALID_ARGS,
-            PackageError::Cancelled => exit_codes::ERROR,
-            PackageError::InvalidIntunewinFile { .. } => exit_codes::ERROR,
-            PackageError::DecryptionError { .. } => exit_codes::ERROR,
-            PackageError::HmacVerificationFailed => exit_codes::ERROR,
-            PackageError::InvalidPadding => exit_codes::ERROR,
-            // macOS errors
-            PackageError::ScriptsFolderNotFound { .. } => exit_codes::SCRIPTS_NOT_FOUND,
-            PackageError::NoScriptsFound { .. } => exit_codes::ERROR,
-            PackageError::XarError { .. } => exit_codes::ERROR,
-            PackageError::CpioError { .. } => exit_codes::ERROR,
-            PackageError::BomError { .. } => exit_codes::ERROR,
-            PackageError::Io(_) => exit_codes::ERROR,
+            PackageError::SourceFolderNotFound { .. } => ErrorCode::SourceFolderNotFound,
+            PackageError::SourceFolderEmpty { .. } => ErrorCode::SourceFolderEmpty,
+            PackageError::SetupFileNotFound { .. } => ErrorCode::SetupFileNotFound,
+            PackageError::OutputFolderCreationFailed { .. } => {
+                ErrorCode::OutputFolderCreationFailed
+            }
+            PackageError::OutputFileExists { .. } => ErrorCode::OutputFileExists,
+            PackageError::SourceReadError { .. } => ErrorCode::SourceReadError,
+            PackageError::EncryptionError { .. } => ErrorCode::EncryptionError,
+            PackageError::OutputWriteError { .. } => ErrorCode::OutputWriteError,
+            PackageError::ZipError { .. } => ErrorCode::ZipError,
+            PackageError::XmlError { .. } => ErrorCode::XmlError,
+            PackageError::InvalidArgument { .. } => ErrorCode::InvalidArgument,
+            PackageError::Cancelled => ErrorCode::Cancelled,
+            PackageError::InvalidIntunewinFile { .. } => ErrorCode::InvalidIntunewinFile,
+            PackageError::DecryptionError { .. } => ErrorCode::DecryptionError,
+            PackageError::HmacVerificationFailed => ErrorCode::HmacVerificationFailed,
+            PackageError::InvalidPadding => ErrorCode::InvalidPadding,
+            PackageError::BlocklistError { .. } => ErrorCode::BlocklistError,
+            PackageError::BlockedContent { .. } => ErrorCode::BlockedContent,
+            PackageError::DangerousSourceFolder { .. } => ErrorCode::DangerousSourceFolder,
+            PackageError::SourceTooLarge { .. } => ErrorCode::SourceTooLarge,
+            PackageError::InsufficientSpace { .. } => ErrorCode::InsufficientSpace,
+            PackageError::ValidationFailed { .. } => ErrorCode::ValidationFailed,
+            PackageError::VerificationFailed { .. } => ErrorCode::VerificationFailed,
+            PackageError::CaptureError { .. } => ErrorCode::CaptureError,
+            PackageError::ProjectError { .. } => ErrorCode::ProjectError,
+            PackageError::TransformError { .. } => ErrorCode::TransformError,
+            PackageError::SelfTestFailed { .. } => ErrorCode::SelfTestFailed,
+            PackageError::ConfigError { .. } => ErrorCode::ConfigError,
+            PackageError::BatchManifestError { .. } => ErrorCode::BatchManifestError,
+            PackageError::RelationshipsError { .. } => ErrorCode::RelationshipsError,
+            PackageError::BatchFailed { .. } => ErrorCode::BatchFailed,
+            PackageError::CacheError { .. } => ErrorCode::CacheError,
+            PackageError::UnsafePath { .. } => ErrorCode::UnsafePath,
+            PackageError::StrictViolation { .. } => ErrorCode::StrictViolation,
+            PackageError::ScriptsFolderNotFound { .. } => ErrorCode::ScriptsFolderNotFound,
+            PackageError::NoScriptsFound { .. } => ErrorCode::NoScriptsFound,
+            PackageError::XarError { .. } => ErrorCode::XarError,
+            PackageError::CpioError { .. } => ErrorCode::CpioError,
+            PackageError::BomError { .. } => ErrorCode::BomError,
+            PackageError::SigningError { .. } => ErrorCode::SigningError,
+            PackageError::SignatureInvalid { .. } => ErrorCode::SignatureInvalid,
+            PackageError::NotarizationError { .. } => ErrorCode::NotarizationError,
+            PackageError::InvalidBundle { .. } => ErrorCode::InvalidBundle,
+            PackageError::SourceDownloadError { .. } => ErrorCode::SourceDownloadError,
+            PackageError::DebError { .. } => ErrorCode::DebError,
+            PackageError::RpmError { .. } => ErrorCode::RpmError,
+            PackageError::Io(_) => ErrorCode::Io,
         }
     }
+
+    /// Get the exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        self.error_code().exit_code()
+    }
 }
 
 #[cfg(test)]
@@ -164,7 +739,9 @@ mod tests {
         let err = PackageError::SetupFileNotFound {
             file: "setup.exe".to_string(),
             folder: PathBuf::from("/tmp"),
+            suggestions: vec!["Setup.exe".to_string()],
         };
+        assert!(err.to_string().ends_with("(did you mean 'Setup.exe'?)"));
         assert_eq!(err.exit_code(), exit_codes::SETUP_NOT_FOUND);
 
         let err = PackageError::InvalidArgument {
@@ -175,7 +752,95 @@ mod tests {
         let err = PackageError::OutputWriteError {
             path: PathBuf::from("/tmp/out"),
             reason: "test".to_string(),
+            source: None,
         };
         assert_eq!(err.exit_code(), exit_codes::OUTPUT_ERROR);
+
+        let err = PackageError::zip(
+            ZipStage::WriteContent,
+            Some("data/app.dll"),
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "unexpected EOF"),
+        );
+        assert_eq!(
+            err.to_string(),
+            "ZIP error while writing the content archive (entry 'data/app.dll'): unexpected EOF"
+        );
+        let err = PackageError::zip(
+            ZipStage::WritePackage,
+            None,
+            std::io::Error::other("disk full"),
+        );
+        assert_eq!(
+            err.to_string(),
+            "ZIP error while writing the package: disk full"
+        );
+
+        let err = PackageError::StrictViolation {
+            check: "signature",
+            message: "signature removed".to_string(),
+        };
+        assert_eq!(err.exit_code(), exit_codes::STRICT_VIOLATION);
+    }
+
+    #[test]
+    fn test_error_code_names_are_stable() {
+        let names: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.name()).collect();
+        assert_eq!(names.len(), 47);
+        assert_eq!(names[0], "source_folder_not_found");
+        assert!(names.contains(&"hmac_verification_failed"));
+        assert!(names.contains(&"io"));
+
+        for code in ErrorCode::ALL {
+            assert_eq!(code.name().parse::<ErrorCode>().unwrap(), *code);
+            assert_eq!(code.to_string(), code.name());
+        }
+        assert!("no_such_error".parse::<ErrorCode>().is_err());
+
+        let err = PackageError::SourceFolderEmpty {
+            path: PathBuf::from("/tmp"),
+        };
+        assert_eq!(err.error_code(), ErrorCode::SourceFolderEmpty);
+        assert_eq!(err.error_code().exit_code(), err.exit_code());
+    }
+
+    #[test]
+    fn test_wrapped_errors_keep_their_source() {
+        use std::error::Error as _;
+
+        let io = || std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        let err = PackageError::source_read(PathBuf::from("/tmp/a"), io());
+        assert_eq!(
+            err.to_string(),
+            "Failed to read source file '/tmp/a': denied"
+        );
+        let source = err.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::PermissionDenied
+        );
+
+        assert!(
+            PackageError::output_write(PathBuf::from("/tmp/b"), io())
+                .source()
+                .is_some()
+        );
+        assert!(
+            PackageError::output_folder(PathBuf::from("/tmp"), io())
+                .source()
+                .is_some()
+        );
+        assert!(
+            PackageError::zip(ZipStage::ReadContent, None, io())
+                .source()
+                .is_some()
+        );
+        assert!(PackageError::xml(io()).source().is_some());
+        assert!(
+            PackageError::InvalidArgument {
+                reason: "test".to_string()
+            }
+            .source()
+            .is_none()
+        );
     }
 }
@@ -0,0 +1,204 @@
+//! Glob patterns over package-relative paths.
+//!
+//! `*` matches within one path segment, `?` one character, and `**` any
+//! number of whole segments. A pattern without `/` matches the file name at
+//! any depth (`*.ps1`); one with `/` matches the whole relative path
+//! (`config/*.json`, `**/bin/*`). Backslashes are treated as `/`.
+//! [`SourceFilter`] uses them to select which source files are packaged.
+
+use std::fmt;
+use std::path::Path;
+
+/// A compiled glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    segments: Vec<String>,
+    name_only: bool,
+}
+
+impl Glob {
+    /// Compile a pattern.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let normalized = pattern.replace('\\', "/");
+        let trimmed = normalized.trim_start_matches("./").trim_matches('/');
+        if trimmed.is_empty() {
+            return Err(format!("empty glob pattern '{}'", pattern));
+        }
+
+        let segments: Vec<String> = trimmed.split('/').map(String::from).collect();
+        if segments.iter().any(|s| s.contains("**") && s != "**") {
+            return Err(format!(
+                "'**' must be a whole path segment in '{}'",
+                pattern
+            ));
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            name_only: segments.len() == 1 && segments[0] != "**",
+            segments,
+        })
+    }
+
+    /// The pattern as given.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `path` (relative to the package root) matches.
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+
+        if self.name_only {
+            return parts
+                .last()
+                .is_some_and(|name| match_segment(&self.segments[0], name));
+        }
+
+        let segments: Vec<&str> = self.segments.iter().map(String::as_str).collect();
+        match_segments(&segments, &parts)
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Include and exclude patterns selecting which source files are packaged.
+///
+/// A path is excluded when it or one of its parent folders matches an
+/// exclude pattern, so `node_modules` leaves out the whole tree. When
+/// include patterns are given, only paths that (or whose parent folders)
+/// match one of them are kept. Exclusion wins over inclusion.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceFilter {
+    /// Patterns a path must match to be kept (all paths when empty)
+    pub include: Vec<Glob>,
+    /// Patterns of paths to leave out
+    pub exclude: Vec<Glob>,
+}
+
+impl SourceFilter {
+    /// Whether the filter keeps every path.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether `path` (relative to the source root) is excluded.
+    pub fn excludes(&self, path: &Path) -> bool {
+        matches_any_ancestor(&self.exclude, path)
+    }
+
+    /// Whether `path` (relative to the source root) is kept.
+    pub fn keeps(&self, path: &Path) -> bool {
+        !self.excludes(path)
+            && (self.include.is_empty() || matches_any_ancestor(&self.include, path))
+    }
+}
+
+/// Whether `path` or one of its parent folders matches one of `globs`.
+fn matches_any_ancestor(globs: &[Glob], path: &Path) -> bool {
+    path.ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| globs.iter().any(|g| g.matches(p)))
+}
+
+fn match_segments(pattern: &[&str], parts: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => parts.is_empty(),
+        Some((&"**", rest)) => (0..=parts.len()).any(|skip| match_segments(rest, &parts[skip..])),
+        Some((segment, rest)) => parts.split_first().is_some_and(|(part, parts)| {
+            match_segment(segment, part) && match_segments(rest, parts)
+        }),
+    }
+}
+
+/// Match one segment with `*` and `?` wildcards.
+fn match_segment(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Iterative wildcard matching with backtracking to the last `*`
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().matches(Path::new(path))
+    }
+
+    #[test]
+    fn test_name_patterns_match_at_any_depth() {
+        assert!(matches("*.ps1", "install.ps1"));
+        assert!(matches("*.ps1", "scripts/deep/setup.ps1"));
+        assert!(!matches("*.ps1", "install.ps1.bak"));
+        assert!(matches("config.json", "app/config.json"));
+        assert!(matches("setup?.exe", "setup2.exe"));
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        assert!(matches("config/*.json", "config/app.json"));
+        assert!(!matches("config/*.json", "other/config/app.json"));
+        assert!(matches("**/bin/*", "a/b/bin/tool"));
+        assert!(matches("**/bin/*", "bin/tool"));
+        assert!(matches("docs/**", "docs/a/b.md"));
+        assert!(matches("app\\*.ini", "app/settings.ini"));
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let glob = |p: &str| Glob::new(p).unwrap();
+        let filter = SourceFilter {
+            include: Vec::new(),
+            exclude: vec![glob("node_modules"), glob("*.pdb"), glob("build/tmp")],
+        };
+        assert!(filter.keeps(Path::new("app.exe")));
+        assert!(!filter.keeps(Path::new("node_modules")));
+        assert!(!filter.keeps(Path::new("web/node_modules/x/index.js")));
+        assert!(!filter.keeps(Path::new("bin/app.pdb")));
+        assert!(!filter.keeps(Path::new("build/tmp/obj.o")));
+        assert!(filter.keeps(Path::new("build/out.exe")));
+
+        let filter = SourceFilter {
+            include: vec![glob("*.dll"), glob("config")],
+            exclude: vec![glob("debug.dll")],
+        };
+        assert!(filter.keeps(Path::new("lib/a.dll")));
+        assert!(filter.keeps(Path::new("config/app/settings.json")));
+        assert!(!filter.keeps(Path::new("readme.txt")));
+        assert!(!filter.keeps(Path::new("lib/debug.dll")));
+        assert!(SourceFilter::default().is_empty());
+        assert!(SourceFilter::default().keeps(Path::new("any/file")));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(Glob::new("").is_err());
+        assert!(Glob::new("a/**b").is_err());
+    }
+}
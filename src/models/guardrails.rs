@@ -0,0 +1,216 @@
+//! Guardrails against packaging obviously wrong source folders.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::SourcePackage;
+use crate::models::size::ByteSize;
+
+/// Refuse filesystem roots and the user's home directory (or any folder
+/// containing it) as a package source, unless `allow` is set.
+///
+/// Paths that cannot be resolved are left for the normal existence checks.
+pub fn check_source_folder(path: &Path, allow: bool) -> PackageResult<()> {
+    if allow {
+        return Ok(());
+    }
+
+    let Ok(resolved) = path.canonicalize() else {
+        return Ok(());
+    };
+
+    if let Some(reason) = dangerous_reason(&resolved, home_dir().as_deref()) {
+        return Err(PackageError::DangerousSourceFolder {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Default number of files above which packaging needs confirmation.
+pub const DEFAULT_MAX_FILES: usize = 100_000;
+
+/// Default total size above which packaging needs confirmation (10 GB).
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Size limits above which a source folder needs explicit confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLimits {
+    /// Maximum number of files
+    pub max_files: usize,
+    /// Maximum total uncompressed size in bytes
+    pub max_bytes: u64,
+}
+
+impl Default for SourceLimits {
+    fn default() -> Self {
+        Self {
+            max_files: DEFAULT_MAX_FILES,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl SourceLimits {
+    /// Check collected content against the limits.
+    pub fn check(&self, path: &Path, file_count: usize, total_size: u64) -> PackageResult<()> {
+        let reason = if file_count > self.max_files {
+            format!(
+                "{} files exceeds the limit of {}",
+                file_count, self.max_files
+            )
+        } else if total_size > self.max_bytes {
+            format!(
+                "{} exceeds the limit of {}",
+                ByteSize(total_size),
+                ByteSize(self.max_bytes)
+            )
+        } else {
+            return Ok(());
+        };
+
+        Err(PackageError::SourceTooLarge {
+            path: path.to_path_buf(),
+            reason,
+        })
+    }
+}
+
+/// Largest package Intune accepts for a Win32 app (30 GB).
+pub const INTUNE_MAX_PACKAGE_SIZE: ByteSize = ByteSize(30 << 30);
+
+/// ZIP headers of a content entry besides its name: local header, central
+/// directory record and ZIP64 extra fields.
+const ZIP_ENTRY_OVERHEAD: u64 = 30 + 46 + 2 * 28;
+
+/// Encryption IV, HMAC and AES padding, plus the outer ZIP with
+/// Detection.xml and the end records.
+const PACKAGE_OVERHEAD: u64 = 16 + 32 + 16 + 8 * 1024;
+
+/// The projected size of a package, taken before any content is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeEstimate {
+    /// Largest size the package can reach: its content stored uncompressed
+    pub projected: ByteSize,
+    /// Size the package is checked against
+    pub limit: ByteSize,
+}
+
+impl SizeEstimate {
+    /// Project the size of a package of `source` against `limit`.
+    ///
+    /// Compression only makes the package smaller, so the projection is an
+    /// upper bound: content that compresses well stays far below it.
+    pub fn for_source(source: &SourcePackage, limit: ByteSize) -> Self {
+        let entries = source
+            .files
+            .iter()
+            .map(|file| (file.relative_path.as_path(), file.size))
+            .chain(source.directories.iter().map(|dir| (dir.as_path(), 0)));
+        let projected = entries.fold(PACKAGE_OVERHEAD, |total, (path, size)| {
+            // Deflate falls back to stored blocks of up to 64 KiB, each
+            // with a 5-byte header
+            let stored = size + size.div_ceil(0xffff) * 5;
+            let name = path.as_os_str().len() as u64 + 1;
+            total.saturating_add(stored + ZIP_ENTRY_OVERHEAD + 2 * name)
+        });
+        Self {
+            projected: ByteSize(projected),
+            limit,
+        }
+    }
+
+    /// Whether the package may end up larger than the limit.
+    pub fn exceeds_limit(&self) -> bool {
+        self.projected > self.limit
+    }
+}
+
+/// Describe why `resolved` is unsafe to package, if it is.
+fn dangerous_reason(resolved: &Path, home: Option<&Path>) -> Option<&'static str> {
+    if resolved.parent().is_none() {
+        return Some("it is a filesystem root");
+    }
+
+    if let Some(home) = home {
+        if home == resolved {
+            return Some("it is the home directory");
+        }
+        if home.starts_with(resolved) {
+            return Some("it contains the home directory");
+        }
+    }
+
+    None
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    PathBuf::from(home).canonicalize().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangerous_reason() {
+        let home = Path::new("/home/alex");
+
+        assert!(dangerous_reason(Path::new("/"), Some(home)).is_some());
+        assert!(dangerous_reason(Path::new("/home/alex"), Some(home)).is_some());
+        assert!(dangerous_reason(Path::new("/home"), Some(home)).is_some());
+        assert!(dangerous_reason(Path::new("/home/alex/app"), Some(home)).is_none());
+        assert!(dangerous_reason(Path::new("/opt/app"), None).is_none());
+    }
+
+    #[test]
+    fn test_source_limits() {
+        let limits = SourceLimits {
+            max_files: 10,
+            max_bytes: 1024,
+        };
+        let path = Path::new("/src");
+
+        assert!(limits.check(path, 10, 1024).is_ok());
+        assert!(matches!(
+            limits.check(path, 11, 0),
+            Err(PackageError::SourceTooLarge { .. })
+        ));
+        assert!(matches!(
+            limits.check(path, 1, 1025),
+            Err(PackageError::SourceTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_size_estimate() {
+        let mut source = SourcePackage::new(PathBuf::from("src"), PathBuf::from("setup.exe"));
+        source.add_directory(PathBuf::from("data"));
+        source.add_file(PathBuf::from("setup.exe"), 1000, true);
+        source.add_file(PathBuf::from("data/big.bin"), 3 << 30, false);
+
+        let estimate = SizeEstimate::for_source(&source, INTUNE_MAX_PACKAGE_SIZE);
+        assert!(estimate.projected.bytes() > source.total_size);
+        assert!(estimate.projected.bytes() < source.total_size + ByteSize::MIB.bytes());
+        assert!(!estimate.exceeds_limit());
+
+        let estimate = SizeEstimate::for_source(&source, ByteSize::GIB);
+        assert!(estimate.exceeds_limit());
+    }
+
+    #[test]
+    fn test_check_source_folder_root() {
+        let root = Path::new("/");
+        if root.exists() {
+            assert!(matches!(
+                check_source_folder(root, false),
+                Err(PackageError::DangerousSourceFolder { .. })
+            ));
+            assert!(check_source_folder(root, true).is_ok());
+        }
+    }
+}
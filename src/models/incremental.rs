@@ -0,0 +1,168 @@
+//! State kept between runs to rebuild unchanged packages cheaply.
+//!
+//! Nightly pipelines often repackage apps whose files have not changed.
+//! With a state file, each build records digests of its source and
+//! settings next to the package it wrote:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "source_sha256": "<files, folders and compression>",
+//!   "settings_sha256": "<everything else that shapes the package>",
+//!   "content_digest": "<the unencrypted content ZIP>",
+//!   "package": "out/setup.intunewin",
+//!   "package_sha256": "<the package file>"
+//! }
+//! ```
+//!
+//! When the next build finds the same source, the content ZIP is decrypted
+//! from the previous package instead of compressed again, and only
+//! encrypted anew. When the settings match too, `skip_unchanged` returns
+//! the previous package untouched.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::json::{self, JsonValue};
+
+/// Version of the state file format.
+pub const STATE_VERSION: u64 = 1;
+
+/// Where incremental builds keep their state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncrementalOptions {
+    /// State file, written after every build
+    pub state_file: PathBuf,
+    /// Return the previous package when neither the source nor the
+    /// settings changed
+    pub skip_unchanged: bool,
+}
+
+impl IncrementalOptions {
+    /// Keep state in `state_file`, re-encrypting unchanged content.
+    pub fn new(state_file: impl Into<PathBuf>) -> Self {
+        Self {
+            state_file: state_file.into(),
+            skip_unchanged: false,
+        }
+    }
+
+    /// Set whether an unchanged build returns the previous package.
+    pub fn with_skip_unchanged(mut self, skip: bool) -> Self {
+        self.skip_unchanged = skip;
+        self
+    }
+}
+
+/// What an incremental build did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncrementalOutcome {
+    /// The source changed, or no usable state was found
+    Built,
+    /// The source was unchanged; its content was re-encrypted
+    Reencrypted,
+    /// Source and settings were unchanged; the previous package was kept
+    Skipped,
+}
+
+impl fmt::Display for IncrementalOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IncrementalOutcome::Built => "built from the source",
+            IncrementalOutcome::Reencrypted => "source unchanged, content re-encrypted",
+            IncrementalOutcome::Skipped => "source and settings unchanged, previous package kept",
+        })
+    }
+}
+
+/// Digests recorded by the last build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildState {
+    /// Digest of the content files, folders and compression settings
+    pub source_sha256: String,
+    /// Digest of the settings written outside the content ZIP
+    pub settings_sha256: String,
+    /// Detection.xml's FileDigest of the unencrypted content ZIP, in hex
+    pub content_digest: String,
+    /// Package the build wrote
+    pub package: PathBuf,
+    /// SHA-256 of that package
+    pub package_sha256: String,
+}
+
+impl BuildState {
+    /// Parse a state file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = json::parse(text.trim_start_matches('\u{feff}'))?;
+        match value.get("version").and_then(JsonValue::as_u64) {
+            Some(STATE_VERSION) => {}
+            Some(version) => return Err(format!("Unsupported state version {}", version)),
+            None => return Err("'version' is missing".to_string()),
+        }
+
+        let string = |name: &str| {
+            value
+                .get(name)
+                .and_then(JsonValue::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| format!("'{}' is missing", name))
+        };
+        Ok(Self {
+            source_sha256: string("source_sha256")?,
+            settings_sha256: string("settings_sha256")?,
+            content_digest: string("content_digest")?,
+            package: PathBuf::from(string("package")?),
+            package_sha256: string("package_sha256")?,
+        })
+    }
+
+    /// Serialize the state as JSON.
+    pub fn to_json(&self) -> String {
+        JsonValue::Object(vec![
+            ("version".to_string(), STATE_VERSION.into()),
+            (
+                "source_sha256".to_string(),
+                self.source_sha256.as_str().into(),
+            ),
+            (
+                "settings_sha256".to_string(),
+                self.settings_sha256.as_str().into(),
+            ),
+            (
+                "content_digest".to_string(),
+                self.content_digest.as_str().into(),
+            ),
+            (
+                "package".to_string(),
+                self.package.to_string_lossy().into_owned().into(),
+            ),
+            (
+                "package_sha256".to_string(),
+                self.package_sha256.as_str().into(),
+            ),
+        ])
+        .to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_state_round_trip() {
+        let state = BuildState {
+            source_sha256: "a".repeat(64),
+            settings_sha256: "b".repeat(64),
+            content_digest: "c".repeat(64),
+            package: PathBuf::from("out/setup.intunewin"),
+            package_sha256: "e".repeat(64),
+        };
+        assert_eq!(BuildState::parse(&state.to_json()).unwrap(), state);
+
+        let newer = state.to_json().replace("\"version\": 1", "\"version\": 2");
+        assert!(BuildState::parse(&newer).unwrap_err().contains("version 2"));
+        assert!(BuildState::parse(r#"{ "version": 1 }"#).is_err());
+        assert!(BuildState::parse("[]").is_err());
+    }
+}
@@ -0,0 +1,984 @@
+//! Data models for Debian (.deb) and RPM (.rpm) package creation.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::guardrails::check_source_folder;
+use crate::models::package::{SkippedEntry, Verbosity};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+
+/// Maintainer scripts dpkg runs, in the order a scripts folder is read.
+pub const MAINTAINER_SCRIPTS: &[&str] = &["preinst", "postinst", "prerm", "postrm"];
+
+/// Architecture of packages that install on any machine.
+pub const ARCH_ALL: &str = "all";
+
+/// Maintainer recorded when none is given.
+pub const DEFAULT_MAINTAINER: &str = "Unknown <unknown@localhost>";
+
+/// RPM scriptlets (`%pre`, `%post`, `%preun`, `%postun`), in the order a
+/// scripts folder is read.
+pub const RPM_SCRIPTLETS: &[&str] = &["pre", "post", "preun", "postun"];
+
+/// RPM architecture of packages that install on any machine.
+pub const ARCH_NOARCH: &str = "noarch";
+
+/// RPM release recorded when none is given.
+pub const DEFAULT_RELEASE: &str = "1";
+
+/// RPM license recorded when none is given.
+pub const DEFAULT_LICENSE: &str = "Unspecified";
+
+/// Request to create a Debian binary package.
+#[derive(Debug, Clone)]
+pub struct DebRequest {
+    /// Source folder containing files to package
+    pub source_folder: PathBuf,
+    /// Package name (e.g., `contoso-agent`)
+    pub name: String,
+    /// Package version (e.g., `1.2.0-1` or `2:1.0`)
+    pub version: String,
+    /// Debian architecture (e.g., `amd64`, `arm64`; default `all`)
+    pub architecture: String,
+    /// Folder the source folder is installed to (default `/`)
+    pub install_location: PathBuf,
+    /// Output folder for the .deb file
+    pub output_folder: PathBuf,
+    /// Custom output filename (without extension)
+    pub output_name: Option<String>,
+    /// `Maintainer` field (default: [`DEFAULT_MAINTAINER`])
+    pub maintainer: Option<String>,
+    /// Description; the first line is the synopsis (default: the name)
+    pub description: Option<String>,
+    /// `Depends` relations (e.g., `libc6 (>= 2.31)`)
+    pub depends: Vec<String>,
+    /// `Section` field (e.g., `admin`)
+    pub section: Option<String>,
+    /// `Priority` field (e.g., `optional`)
+    pub priority: Option<String>,
+    /// `Homepage` field
+    pub homepage: Option<String>,
+    /// Folder holding `preinst`, `postinst`, `prerm` and/or `postrm`
+    pub scripts_folder: Option<PathBuf>,
+    /// Keep setuid/setgid/sticky bits (otherwise they are stripped)
+    pub allow_special_modes: bool,
+    /// Package a filesystem root or home directory
+    pub allow_dangerous_source: bool,
+    /// Verbosity level
+    pub verbosity: Verbosity,
+    /// Which warnings fail the build
+    pub strictness: Strictness,
+}
+
+impl DebRequest {
+    /// Create a new Debian package request with required fields.
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        name: impl Into<String>,
+        version: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            name: name.into(),
+            version: version.into(),
+            architecture: ARCH_ALL.to_string(),
+            install_location: PathBuf::from("/"),
+            output_folder: output_folder.into(),
+            output_name: None,
+            maintainer: None,
+            description: None,
+            depends: Vec::new(),
+            section: None,
+            priority: None,
+            homepage: None,
+            scripts_folder: None,
+            allow_special_modes: false,
+            allow_dangerous_source: false,
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+
+    /// Set the Debian architecture.
+    pub fn with_architecture(mut self, architecture: impl Into<String>) -> Self {
+        self.set_architecture(architecture);
+        self
+    }
+
+    /// Set the folder the source is installed to.
+    pub fn with_install_location(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_install_location(path);
+        self
+    }
+
+    /// Set custom output filename.
+    pub fn with_output_name(mut self, name: impl Into<String>) -> Self {
+        self.set_output_name(name);
+        self
+    }
+
+    /// Set the maintainer.
+    pub fn with_maintainer(mut self, maintainer: impl Into<String>) -> Self {
+        self.set_maintainer(maintainer);
+        self
+    }
+
+    /// Set the description.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Add a `Depends` relation.
+    pub fn with_depends(mut self, relation: impl Into<String>) -> Self {
+        self.add_depends(relation);
+        self
+    }
+
+    /// Set the section.
+    pub fn with_section(mut self, section: impl Into<String>) -> Self {
+        self.set_section(section);
+        self
+    }
+
+    /// Set the priority.
+    pub fn with_priority(mut self, priority: impl Into<String>) -> Self {
+        self.set_priority(priority);
+        self
+    }
+
+    /// Set the homepage.
+    pub fn with_homepage(mut self, homepage: impl Into<String>) -> Self {
+        self.set_homepage(homepage);
+        self
+    }
+
+    /// Set the maintainer scripts folder.
+    pub fn with_scripts_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_scripts_folder(path);
+        self
+    }
+
+    /// Keep or strip setuid/setgid/sticky bits.
+    pub fn with_allow_special_modes(mut self, allow: bool) -> Self {
+        self.set_allow_special_modes(allow);
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory.
+    pub fn with_allow_dangerous_source(mut self, allow: bool) -> Self {
+        self.set_allow_dangerous_source(allow);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.set_verbosity(verbosity);
+        self
+    }
+
+    /// Set which warnings fail the build.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.set_strictness(strictness);
+        self
+    }
+
+    /// Set the Debian architecture in place.
+    pub fn set_architecture(&mut self, architecture: impl Into<String>) -> &mut Self {
+        self.architecture = architecture.into();
+        self
+    }
+
+    /// Set the folder the source is installed to in place.
+    pub fn set_install_location(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.install_location = path.into();
+        self
+    }
+
+    /// Set custom output filename in place.
+    pub fn set_output_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.output_name = Some(name.into());
+        self
+    }
+
+    /// Set the maintainer in place.
+    pub fn set_maintainer(&mut self, maintainer: impl Into<String>) -> &mut Self {
+        self.maintainer = Some(maintainer.into());
+        self
+    }
+
+    /// Set the description in place.
+    pub fn set_description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Add a `Depends` relation in place.
+    pub fn add_depends(&mut self, relation: impl Into<String>) -> &mut Self {
+        self.depends.push(relation.into());
+        self
+    }
+
+    /// Set the section in place.
+    pub fn set_section(&mut self, section: impl Into<String>) -> &mut Self {
+        self.section = Some(section.into());
+        self
+    }
+
+    /// Set the priority in place.
+    pub fn set_priority(&mut self, priority: impl Into<String>) -> &mut Self {
+        self.priority = Some(priority.into());
+        self
+    }
+
+    /// Set the homepage in place.
+    pub fn set_homepage(&mut self, homepage: impl Into<String>) -> &mut Self {
+        self.homepage = Some(homepage.into());
+        self
+    }
+
+    /// Set the maintainer scripts folder in place.
+    pub fn set_scripts_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.scripts_folder = Some(path.into());
+        self
+    }
+
+    /// Keep or strip setuid/setgid/sticky bits in place.
+    pub fn set_allow_special_modes(&mut self, allow: bool) -> &mut Self {
+        self.allow_special_modes = allow;
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory in place.
+    pub fn set_allow_dangerous_source(&mut self, allow: bool) -> &mut Self {
+        self.allow_dangerous_source = allow;
+        self
+    }
+
+    /// Set verbosity level in place.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> &mut Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail the build in place.
+    pub fn set_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Check the control fields against Debian policy and the source folder.
+    pub fn validate(&self) -> PackageResult<()> {
+        let invalid = |reason: String| Err(PackageError::InvalidArgument { reason });
+        if !is_valid_name(&self.name) {
+            return invalid(format!(
+                "Invalid package name '{}': use at least two lowercase letters, digits, \
+                 '+', '-' or '.', starting with a letter or digit",
+                self.name
+            ));
+        }
+        if !is_valid_version(&self.version) {
+            return invalid(format!(
+                "Invalid package version '{}': expected [epoch:]upstream[-revision] \
+                 with the upstream version starting with a digit",
+                self.version
+            ));
+        }
+        if !is_valid_name(&self.architecture) && self.architecture != "all" {
+            return invalid(format!(
+                "Invalid architecture '{}' (e.g., amd64, arm64 or all)",
+                self.architecture
+            ));
+        }
+        if !self.install_location.has_root() {
+            return invalid(format!(
+                "The install location '{}' must be an absolute path",
+                self.install_location.display()
+            ));
+        }
+        for relation in &self.depends {
+            if relation.trim().is_empty() || relation.contains([',', '\n']) {
+                return invalid(format!(
+                    "Invalid dependency '{}': give one relation per --depends",
+                    relation
+                ));
+            }
+        }
+        for (field, value) in [
+            ("Maintainer", &self.maintainer),
+            ("Section", &self.section),
+            ("Priority", &self.priority),
+            ("Homepage", &self.homepage),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|v| v.trim().is_empty() || v.contains('\n'))
+            {
+                return invalid(format!(
+                    "The {} field must be a single non-empty line",
+                    field
+                ));
+            }
+        }
+        if self
+            .description
+            .as_deref()
+            .is_some_and(|d| d.lines().next().map_or(true, |l| l.trim().is_empty()))
+        {
+            return invalid("The description must start with a synopsis line".to_string());
+        }
+        if !self.source_folder.is_dir() {
+            return Err(PackageError::SourceFolderNotFound {
+                path: self.source_folder.clone(),
+            });
+        }
+        check_source_folder(&self.source_folder, self.allow_dangerous_source)
+    }
+
+    /// Get the output file path (`<name>_<version>_<arch>.deb` without the
+    /// epoch, as `dpkg-name` names it).
+    pub fn output_path(&self) -> PathBuf {
+        let base_name = match &self.output_name {
+            Some(name) => name.trim_end_matches(".deb").to_string(),
+            None => {
+                let version = self
+                    .version
+                    .split_once(':')
+                    .map_or(self.version.as_str(), |(_, version)| version);
+                format!("{}_{}_{}", self.name, version, self.architecture)
+            }
+        };
+        self.output_folder.join(format!("{}.deb", base_name))
+    }
+
+    /// The `control` file for a payload of `installed_kib` KiB.
+    pub fn control(&self, installed_kib: u64) -> String {
+        let mut control = String::new();
+        let mut field = |name: &str, value: &str| {
+            control.push_str(&format!("{}: {}\n", name, value));
+        };
+        field("Package", &self.name);
+        field("Version", &self.version);
+        field("Architecture", &self.architecture);
+        field(
+            "Maintainer",
+            self.maintainer.as_deref().unwrap_or(DEFAULT_MAINTAINER),
+        );
+        field("Installed-Size", &installed_kib.to_string());
+        if !self.depends.is_empty() {
+            let depends: Vec<&str> = self.depends.iter().map(|d| d.trim()).collect();
+            field("Depends", &depends.join(", "));
+        }
+        for (name, value) in [
+            ("Section", &self.section),
+            ("Priority", &self.priority),
+            ("Homepage", &self.homepage),
+        ] {
+            if let Some(value) = value {
+                field(name, value);
+            }
+        }
+
+        // Extended description lines are indented; empty ones become " ."
+        let description = self.description.as_deref().unwrap_or(&self.name);
+        let mut lines = description.trim_end().lines();
+        field("Description", lines.next().unwrap_or_default().trim());
+        for line in lines {
+            let line = line.trim_end();
+            if line.is_empty() {
+                control.push_str(" .\n");
+            } else {
+                control.push_str(&format!(" {}\n", line));
+            }
+        }
+        control
+    }
+}
+
+/// Whether `name` is a valid Debian package name.
+fn is_valid_name(name: &str) -> bool {
+    name.len() >= 2
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c))
+}
+
+/// Whether `version` is a valid Debian version
+/// (`[epoch:]upstream_version[-debian_revision]`).
+fn is_valid_version(version: &str) -> bool {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) => (Some(epoch), rest),
+        None => (None, version),
+    };
+    let (upstream, revision) = match rest.rsplit_once('-') {
+        Some((upstream, revision)) => (upstream, Some(revision)),
+        None => (rest, None),
+    };
+    epoch.map_or(true, |e| {
+        !e.is_empty() && e.chars().all(|c| c.is_ascii_digit())
+    }) && upstream.starts_with(|c: char| c.is_ascii_digit())
+        && upstream
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".+~-".contains(c))
+        && revision.map_or(true, |r| {
+            !r.is_empty()
+                && r.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || ".+~".contains(c))
+        })
+}
+
+/// Result of Debian package creation.
+#[derive(Debug, Clone)]
+pub struct DebResult {
+    /// Path to the created .deb file
+    pub output_path: PathBuf,
+    /// Size of the created package
+    pub package_size: ByteSize,
+    /// Number of regular files in the payload
+    pub file_count: usize,
+    /// `Installed-Size` recorded in the control file, in KiB
+    pub installed_kib: u64,
+    /// The generated control file
+    pub control: String,
+    /// Maintainer scripts included
+    pub scripts: Vec<String>,
+    /// Payload entries with setuid/setgid/sticky bits in the source
+    pub special_mode_files: Vec<PathBuf>,
+    /// Special entries in the source that were left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Time taken to create the package
+    pub creation_time: Duration,
+}
+
+/// Request to create an RPM package.
+#[derive(Debug, Clone)]
+pub struct RpmRequest {
+    /// Source folder containing files to package
+    pub source_folder: PathBuf,
+    /// Package name (e.g., `contoso-agent`)
+    pub name: String,
+    /// Package version (e.g., `1.2.0`)
+    pub version: String,
+    /// Package release (default: [`DEFAULT_RELEASE`])
+    pub release: String,
+    /// Package epoch, if any
+    pub epoch: Option<u32>,
+    /// RPM architecture (e.g., `x86_64`, `aarch64`; default `noarch`)
+    pub architecture: String,
+    /// Folder the source folder is installed to (default `/`)
+    pub install_location: PathBuf,
+    /// Output folder for the .rpm file
+    pub output_folder: PathBuf,
+    /// Custom output filename (without extension)
+    pub output_name: Option<String>,
+    /// One-line summary (default: the name)
+    pub summary: Option<String>,
+    /// Description (default: the summary)
+    pub description: Option<String>,
+    /// `License` tag (default: [`DEFAULT_LICENSE`])
+    pub license: Option<String>,
+    /// `URL` tag
+    pub url: Option<String>,
+    /// `Requires` relations (e.g., `glibc >= 2.17`)
+    pub requires: Vec<String>,
+    /// Folder holding `pre`, `post`, `preun` and/or `postun`
+    pub scripts_folder: Option<PathBuf>,
+    /// Keep setuid/setgid/sticky bits (otherwise they are stripped)
+    pub allow_special_modes: bool,
+    /// Package a filesystem root or home directory
+    pub allow_dangerous_source: bool,
+    /// Verbosity level
+    pub verbosity: Verbosity,
+    /// Which warnings fail the build
+    pub strictness: Strictness,
+}
+
+impl RpmRequest {
+    /// Create a new RPM package request with required fields.
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        name: impl Into<String>,
+        version: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            name: name.into(),
+            version: version.into(),
+            release: DEFAULT_RELEASE.to_string(),
+            epoch: None,
+            architecture: ARCH_NOARCH.to_string(),
+            install_location: PathBuf::from("/"),
+            output_folder: output_folder.into(),
+            output_name: None,
+            summary: None,
+            description: None,
+            license: None,
+            url: None,
+            requires: Vec::new(),
+            scripts_folder: None,
+            allow_special_modes: false,
+            allow_dangerous_source: false,
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+
+    /// Set the release.
+    pub fn with_release(mut self, release: impl Into<String>) -> Self {
+        self.set_release(release);
+        self
+    }
+
+    /// Set the epoch.
+    pub fn with_epoch(mut self, epoch: u32) -> Self {
+        self.set_epoch(epoch);
+        self
+    }
+
+    /// Set the RPM architecture.
+    pub fn with_architecture(mut self, architecture: impl Into<String>) -> Self {
+        self.set_architecture(architecture);
+        self
+    }
+
+    /// Set the folder the source is installed to.
+    pub fn with_install_location(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_install_location(path);
+        self
+    }
+
+    /// Set custom output filename.
+    pub fn with_output_name(mut self, name: impl Into<String>) -> Self {
+        self.set_output_name(name);
+        self
+    }
+
+    /// Set the summary.
+    pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+        self.set_summary(summary);
+        self
+    }
+
+    /// Set the description.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Set the license.
+    pub fn with_license(mut self, license: impl Into<String>) -> Self {
+        self.set_license(license);
+        self
+    }
+
+    /// Set the URL.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.set_url(url);
+        self
+    }
+
+    /// Add a `Requires` relation.
+    pub fn with_requires(mut self, relation: impl Into<String>) -> Self {
+        self.add_requires(relation);
+        self
+    }
+
+    /// Set the scriptlets folder.
+    pub fn with_scripts_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_scripts_folder(path);
+        self
+    }
+
+    /// Keep or strip setuid/setgid/sticky bits.
+    pub fn with_allow_special_modes(mut self, allow: bool) -> Self {
+        self.set_allow_special_modes(allow);
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory.
+    pub fn with_allow_dangerous_source(mut self, allow: bool) -> Self {
+        self.set_allow_dangerous_source(allow);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.set_verbosity(verbosity);
+        self
+    }
+
+    /// Set which warnings fail the build.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.set_strictness(strictness);
+        self
+    }
+
+    /// Set the release in place.
+    pub fn set_release(&mut self, release: impl Into<String>) -> &mut Self {
+        self.release = release.into();
+        self
+    }
+
+    /// Set the epoch in place.
+    pub fn set_epoch(&mut self, epoch: u32) -> &mut Self {
+        self.epoch = Some(epoch);
+        self
+    }
+
+    /// Set the RPM architecture in place.
+    pub fn set_architecture(&mut self, architecture: impl Into<String>) -> &mut Self {
+        self.architecture = architecture.into();
+        self
+    }
+
+    /// Set the folder the source is installed to in place.
+    pub fn set_install_location(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.install_location = path.into();
+        self
+    }
+
+    /// Set custom output filename in place.
+    pub fn set_output_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.output_name = Some(name.into());
+        self
+    }
+
+    /// Set the summary in place.
+    pub fn set_summary(&mut self, summary: impl Into<String>) -> &mut Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    /// Set the description in place.
+    pub fn set_description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the license in place.
+    pub fn set_license(&mut self, license: impl Into<String>) -> &mut Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    /// Set the URL in place.
+    pub fn set_url(&mut self, url: impl Into<String>) -> &mut Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Add a `Requires` relation in place.
+    pub fn add_requires(&mut self, relation: impl Into<String>) -> &mut Self {
+        self.requires.push(relation.into());
+        self
+    }
+
+    /// Set the scriptlets folder in place.
+    pub fn set_scripts_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.scripts_folder = Some(path.into());
+        self
+    }
+
+    /// Keep or strip setuid/setgid/sticky bits in place.
+    pub fn set_allow_special_modes(&mut self, allow: bool) -> &mut Self {
+        self.allow_special_modes = allow;
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory in place.
+    pub fn set_allow_dangerous_source(&mut self, allow: bool) -> &mut Self {
+        self.allow_dangerous_source = allow;
+        self
+    }
+
+    /// Set verbosity level in place.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> &mut Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail the build in place.
+    pub fn set_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// The summary, defaulting to the name.
+    pub fn summary(&self) -> &str {
+        self.summary.as_deref().unwrap_or(&self.name)
+    }
+
+    /// `[epoch:]version-release`, as provided by the package.
+    pub fn evr(&self) -> String {
+        match self.epoch {
+            Some(epoch) => format!("{}:{}-{}", epoch, self.version, self.release),
+            None => format!("{}-{}", self.version, self.release),
+        }
+    }
+
+    /// Check the tags against RPM's rules and the source folder.
+    pub fn validate(&self) -> PackageResult<()> {
+        let invalid = |reason: String| Err(PackageError::InvalidArgument { reason });
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || "._+-".contains(c);
+        if self.name.is_empty()
+            || self.name.starts_with('-')
+            || !self.name.chars().all(is_name_char)
+        {
+            return invalid(format!(
+                "Invalid package name '{}': use letters, digits, '.', '_', '+' or '-', \
+                 not starting with '-'",
+                self.name
+            ));
+        }
+        for (field, value) in [("version", &self.version), ("release", &self.release)] {
+            if !is_valid_rpm_version(value) {
+                return invalid(format!(
+                    "Invalid package {} '{}': use letters, digits, '.', '_', '+', '~' or '^' \
+                     (no '-')",
+                    field, value
+                ));
+            }
+        }
+        if self.architecture.is_empty()
+            || !self
+                .architecture
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return invalid(format!(
+                "Invalid architecture '{}' (e.g., x86_64, aarch64 or noarch)",
+                self.architecture
+            ));
+        }
+        if !self.install_location.has_root() {
+            return invalid(format!(
+                "The install location '{}' must be an absolute path",
+                self.install_location.display()
+            ));
+        }
+        for relation in &self.requires {
+            if split_rpm_relation(relation).is_none() {
+                return invalid(format!(
+                    "Invalid requirement '{}': expected 'name' or 'name <op> version' \
+                     with <, <=, =, >= or >, one per --requires",
+                    relation
+                ));
+            }
+        }
+        for (field, value) in [
+            ("Summary", &self.summary),
+            ("License", &self.license),
+            ("URL", &self.url),
+        ] {
+            if value
+                .as_deref()
+                .is_some_and(|v| v.trim().is_empty() || v.contains('\n'))
+            {
+                return invalid(format!("The {} tag must be a single non-empty line", field));
+            }
+        }
+        if !self.source_folder.is_dir() {
+            return Err(PackageError::SourceFolderNotFound {
+                path: self.source_folder.clone(),
+            });
+        }
+        check_source_folder(&self.source_folder, self.allow_dangerous_source)
+    }
+
+    /// Get the output file path (`<name>-<version>-<release>.<arch>.rpm`,
+    /// as `rpmbuild` names it).
+    pub fn output_path(&self) -> PathBuf {
+        let base_name = match &self.output_name {
+            Some(name) => name.trim_end_matches(".rpm").to_string(),
+            None => format!(
+                "{}-{}-{}.{}",
+                self.name, self.version, self.release, self.architecture
+            ),
+        };
+        self.output_folder.join(format!("{}.rpm", base_name))
+    }
+}
+
+/// Whether `version` is a valid RPM version or release.
+fn is_valid_rpm_version(version: &str) -> bool {
+    !version.is_empty()
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+~^".contains(c))
+}
+
+/// Split an RPM relation into its name and, if versioned, its comparison
+/// operator and version (`glibc >= 2.17`).
+pub fn split_rpm_relation(relation: &str) -> Option<(&str, Option<(&str, &str)>)> {
+    let parts: Vec<&str> = relation.split_whitespace().collect();
+    let valid = |part: &str| !part.contains(',');
+    match parts.as_slice() {
+        [name] if valid(name) => Some((name, None)),
+        [name, op, version]
+            if valid(name) && valid(version) && ["<", "<=", "=", ">=", ">"].contains(op) =>
+        {
+            Some((name, Some((op, version))))
+        }
+        _ => None,
+    }
+}
+
+/// Result of RPM package creation.
+#[derive(Debug, Clone)]
+pub struct RpmResult {
+    /// Path to the created .rpm file
+    pub output_path: PathBuf,
+    /// Size of the created package
+    pub package_size: ByteSize,
+    /// Number of regular files in the payload
+    pub file_count: usize,
+    /// Total size of the installed files
+    pub installed_size: ByteSize,
+    /// Scriptlets included
+    pub scripts: Vec<String>,
+    /// Payload entries with setuid/setgid/sticky bits in the source
+    pub special_mode_files: Vec<PathBuf>,
+    /// Special entries in the source that were left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Time taken to create the package
+    pub creation_time: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_names_and_versions() {
+        for name in ["contoso-agent", "lib2", "g++", "a.b"] {
+            assert!(is_valid_name(name), "{}", name);
+        }
+        for name in ["", "a", "Agent", "-agent", "my_agent"] {
+            assert!(!is_valid_name(name), "{}", name);
+        }
+        for version in [
+            "1",
+            "1.2.0",
+            "1.2.0-1",
+            "2:1.0~rc1",
+            "1.0-2-3",
+            "1.0+git1-0ubuntu1",
+        ] {
+            assert!(is_valid_version(version), "{}", version);
+        }
+        for version in ["", "v1", "1.0-", ":1.0", "a:1.0", "1.0_1", "1.0-re_v"] {
+            assert!(!is_valid_version(version), "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_output_path_and_control() {
+        let request = DebRequest::new("/src", "contoso-agent", "2:1.2.0-1", "/out")
+            .with_architecture("amd64")
+            .with_depends("libc6 (>= 2.31)")
+            .with_depends("curl")
+            .with_section("admin")
+            .with_description("Contoso agent\nRuns in the background.\n\nKeeps hosts in sync.");
+        assert_eq!(
+            request.output_path(),
+            PathBuf::from("/out/contoso-agent_1.2.0-1_amd64.deb")
+        );
+        assert_eq!(
+            request.control(12),
+            "Package: contoso-agent\n\
+             Version: 2:1.2.0-1\n\
+             Architecture: amd64\n\
+             Maintainer: Unknown <unknown@localhost>\n\
+             Installed-Size: 12\n\
+             Depends: libc6 (>= 2.31), curl\n\
+             Section: admin\n\
+             Description: Contoso agent\n \
+             Runs in the background.\n \
+             .\n \
+             Keeps hosts in sync.\n"
+        );
+        assert_eq!(
+            request.with_output_name("agent.deb").output_path(),
+            PathBuf::from("/out/agent.deb")
+        );
+    }
+
+    #[test]
+    fn test_validate_fields() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let request = DebRequest::new(temp.path(), "agent", "1.0", "/out");
+        assert!(request.validate().is_ok());
+        assert!(
+            request
+                .clone()
+                .with_architecture("x86_64")
+                .validate()
+                .is_err()
+        );
+        assert!(request.clone().with_depends("a, b").validate().is_err());
+        assert!(request.clone().with_maintainer("a\nb").validate().is_err());
+        assert!(
+            request
+                .clone()
+                .with_description("\nbody")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            request
+                .clone()
+                .with_install_location("opt")
+                .validate()
+                .is_err()
+        );
+        let mut missing = request;
+        missing.source_folder = temp.path().join("missing");
+        assert!(missing.validate().is_err());
+    }
+
+    #[test]
+    fn test_rpm_request() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let request = RpmRequest::new(temp.path(), "contoso-agent", "1.2.0", "/out")
+            .with_architecture("x86_64")
+            .with_requires("glibc >= 2.17")
+            .with_requires("perl(Getopt::Long)");
+        assert!(request.validate().is_ok());
+        assert_eq!(
+            request.output_path(),
+            PathBuf::from("/out/contoso-agent-1.2.0-1.x86_64.rpm")
+        );
+        assert_eq!(request.evr(), "1.2.0-1");
+        assert_eq!(request.clone().with_epoch(2).evr(), "2:1.2.0-1");
+        assert_eq!(request.summary(), "contoso-agent");
+
+        assert!(request.clone().with_release("1-2").validate().is_err());
+        assert!(
+            request
+                .clone()
+                .with_architecture("x86-64")
+                .validate()
+                .is_err()
+        );
+        assert!(request.clone().with_requires("a, b").validate().is_err());
+        assert!(request.clone().with_requires("a >> 1").validate().is_err());
+        assert!(request.clone().with_summary("a\nb").validate().is_err());
+        assert!(
+            RpmRequest::new(temp.path(), "-agent", "1.0", "/out")
+                .validate()
+                .is_err()
+        );
+
+        assert_eq!(
+            split_rpm_relation("glibc >= 2.17"),
+            Some(("glibc", Some((">=", "2.17"))))
+        );
+        assert_eq!(split_rpm_relation("curl"), Some(("curl", None)));
+        assert_eq!(split_rpm_relation("curl >="), None);
+    }
+}
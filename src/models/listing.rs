@@ -0,0 +1,114 @@
+//! The file table of a package's content (`intune list`).
+
+use std::path::Path;
+
+use crate::json::JsonValue;
+use crate::models::relationships::AppRelationships;
+use crate::models::size::ByteSize;
+
+/// An entry of the content ZIP inside a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentEntry {
+    /// Path in the package content, with `/` separators; directories end
+    /// in `/`
+    pub path: String,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+    /// Uncompressed size
+    pub size: ByteSize,
+    /// Size as stored in the content ZIP
+    pub compressed_size: ByteSize,
+    /// CRC-32 of the uncompressed content
+    pub crc32: u32,
+    /// Compression method, e.g. `deflate`
+    pub compression: String,
+}
+
+impl ContentEntry {
+    /// The CRC-32 as eight hex digits.
+    pub fn crc32_hex(&self) -> String {
+        format!("{:08x}", self.crc32)
+    }
+
+    /// The entry as a JSON object.
+    pub fn to_json_value(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("path".to_string(), self.path.clone().into()),
+            ("is_dir".to_string(), self.is_dir.into()),
+            ("size".to_string(), self.size.0.into()),
+            ("compressed_size".to_string(), self.compressed_size.0.into()),
+            ("crc32".to_string(), self.crc32_hex().into()),
+            ("compression".to_string(), self.compression.clone().into()),
+        ])
+    }
+}
+
+/// Serialize the content listing of the package at `package`, and the app
+/// relationships bundled with it, as JSON.
+pub fn listing_json(
+    package: &Path,
+    entries: &[ContentEntry],
+    relationships: Option<&AppRelationships>,
+) -> String {
+    let files = entries.iter().filter(|e| !e.is_dir);
+    let total: u64 = files.clone().map(|e| e.size.0).sum();
+
+    JsonValue::Object(vec![
+        (
+            "package".to_string(),
+            package.to_string_lossy().into_owned().into(),
+        ),
+        ("files".to_string(), (files.count() as u64).into()),
+        ("total_size".to_string(), total.into()),
+        (
+            "entries".to_string(),
+            JsonValue::Array(entries.iter().map(ContentEntry::to_json_value).collect()),
+        ),
+        (
+            "relationships".to_string(),
+            relationships.map_or(JsonValue::Null, AppRelationships::to_json_value),
+        ),
+    ])
+    .to_pretty_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_listing_json() {
+        let entries = vec![
+            ContentEntry {
+                path: "data/".to_string(),
+                is_dir: true,
+                size: ByteSize(0),
+                compressed_size: ByteSize(0),
+                crc32: 0,
+                compression: "stored".to_string(),
+            },
+            ContentEntry {
+                path: "data/app.exe".to_string(),
+                is_dir: false,
+                size: ByteSize(1000),
+                compressed_size: ByteSize(400),
+                crc32: 0xdeadbeef,
+                compression: "deflate".to_string(),
+            },
+        ];
+
+        let value = json::parse(&listing_json(Path::new("app.intunewin"), &entries, None)).unwrap();
+        assert_eq!(value.get("files").and_then(JsonValue::as_u64), Some(1));
+        assert_eq!(
+            value.get("total_size").and_then(JsonValue::as_u64),
+            Some(1000)
+        );
+        let file = &value.get("entries").unwrap().as_array().unwrap()[1];
+        assert_eq!(
+            file.get("crc32").and_then(JsonValue::as_str),
+            Some("deadbeef")
+        );
+        assert_eq!(value.get("relationships"), Some(&JsonValue::Null));
+    }
+}
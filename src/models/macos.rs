@@ -1,66 +1,1518 @@
 //! macOS package-related data models.
 
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
-use crate::models::package::Verbosity;
+use zeroize::Zeroizing;
+
+use crate::models::artifact::ArtifactMetadata;
+use crate::models::blocklist::{BlocklistAction, BlocklistMatch};
+use crate::models::compression::Compression;
+#[cfg(feature = "zstd")]
+use crate::models::compression::CompressionMethod;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::{Glob, SourceFilter};
+use crate::models::guardrails::{SourceLimits, check_source_folder};
+use crate::models::naming::{self, NameTemplate};
+use crate::models::package::{SkippedEntry, SparseFile, Verbosity, required};
+use crate::models::progress::ProgressStats;
+pub use crate::models::retry::RetryPolicy;
+use crate::models::sbom::{Sbom, SbomOptions};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+use crate::models::transform::{TransformRecord, TransformRule};
+use crate::models::validation::ValidationReport;
+use crate::packager::archive::SourceArchive;
+use crate::packager::source::is_remote;
 
 /// Request to create a macOS flat package (.pkg).
 #[derive(Debug, Clone)]
-pub struct MacosPkgRequest {
-    /// Path to the source folder containing files to package
-    pub source_folder: PathBuf,
-    /// Package identifier (e.g., "com.company.app")
-    pub identifier: String,
-    /// Package version (e.g., "1.0.0")
-    pub version: String,
-    /// Installation target path on macOS
-    pub install_location: PathBuf,
-    /// Path to the output folder where .pkg will be created
+pub struct MacosPkgRequest {
+    /// Path to the source folder containing files to package, to an
+    /// archive of them (see [`SourceArchive`]), or the URL of an archive
+    /// (see [`packager::source`](crate::packager::source))
+    pub source_folder: PathBuf,
+    /// Package identifier (e.g., "com.company.app")
+    pub identifier: String,
+    /// Package version (e.g., "1.0.0")
+    pub version: String,
+    /// Installation target path on macOS
+    pub install_location: PathBuf,
+    /// Path to the output folder where .pkg will be created
+    pub output_folder: PathBuf,
+    /// Optional custom output filename (without extension)
+    pub output_name: Option<String>,
+    /// Template the output filename is built from, e.g.
+    /// `{identifier}-{version}-{git_sha}`
+    pub name_template: Option<NameTemplate>,
+    /// Optional folder containing preinstall/postinstall scripts
+    pub scripts_folder: Option<PathBuf>,
+    /// Preinstall script given directly rather than in `scripts_folder`
+    pub preinstall_script: Option<ScriptSource>,
+    /// Postinstall script given directly rather than in `scripts_folder`
+    pub postinstall_script: Option<ScriptSource>,
+    /// Bundled helper scripts run before the preinstall script
+    pub script_templates: Vec<ScriptTemplate>,
+    /// Verbosity level for output
+    pub verbosity: Verbosity,
+    /// Optional known-bad hash list (CSV or JSON) to check content against
+    pub blocklist: Option<PathBuf>,
+    /// What to do when a blocklisted file is found
+    pub blocklist_action: BlocklistAction,
+    /// Keep setuid/setgid/sticky bits instead of stripping them
+    pub allow_special_modes: bool,
+    /// Allow packaging a filesystem root or home directory
+    pub allow_dangerous_source: bool,
+    /// File count and size limits that need confirmation to exceed
+    pub source_limits: SourceLimits,
+    /// Package even when the source exceeds the limits
+    pub force: bool,
+    /// Re-open and validate the output after creation, within this time box
+    pub post_validate: Option<Duration>,
+    /// Which warnings fail the run
+    pub strictness: Strictness,
+    /// Rules rewriting matching files as they are packaged
+    pub transforms: Vec<TransformRule>,
+    /// Identity to sign the package with
+    pub signing: Option<MacosSigning>,
+    /// Package `source_folder` as an application bundle, like
+    /// `pkgbuild --component`
+    pub component: bool,
+    /// Patterns selecting which source files are packaged
+    pub filter: SourceFilter,
+    /// How the gzip payload is compressed
+    pub compression: Compression,
+    /// Software bill of materials to produce for the payload (not written
+    /// for the components of a distribution)
+    pub sbom: Option<SbomOptions>,
+    /// Only install on Macs of this architecture (honoured for the
+    /// components of a distribution)
+    pub host_architecture: Option<HostArchitecture>,
+    /// Build a payload-free package that only runs its scripts, like
+    /// `pkgbuild --nopayload`; the source folder is not read
+    pub no_payload: bool,
+    /// Title, resources and install options of the Distribution
+    pub distribution: DistributionOptions,
+    /// Retries of source reads and output writes that fail transiently
+    pub io_retry: RetryPolicy,
+}
+
+impl MacosPkgRequest {
+    /// Create a new macOS package request with required fields.
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        identifier: impl Into<String>,
+        version: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            identifier: identifier.into(),
+            version: version.into(),
+            install_location: PathBuf::from("/"),
+            output_folder: output_folder.into(),
+            output_name: None,
+            name_template: None,
+            scripts_folder: None,
+            preinstall_script: None,
+            postinstall_script: None,
+            script_templates: Vec::new(),
+            verbosity: Verbosity::default(),
+            blocklist: None,
+            blocklist_action: BlocklistAction::default(),
+            allow_special_modes: false,
+            allow_dangerous_source: false,
+            source_limits: SourceLimits::default(),
+            force: false,
+            post_validate: None,
+            strictness: Strictness::default(),
+            transforms: Vec::new(),
+            signing: None,
+            component: false,
+            filter: SourceFilter::default(),
+            compression: Compression::default(),
+            sbom: None,
+            host_architecture: None,
+            no_payload: false,
+            distribution: DistributionOptions::default(),
+            io_retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Start building a request whose required fields are set incrementally.
+    pub fn builder() -> MacosPkgRequestBuilder {
+        MacosPkgRequestBuilder::default()
+    }
+
+    /// Set custom installation location.
+    pub fn with_install_location(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_install_location(path);
+        self
+    }
+
+    /// Set custom output filename.
+    pub fn with_output_name(mut self, name: impl Into<String>) -> Self {
+        self.set_output_name(name);
+        self
+    }
+
+    /// Build the output filename from `template`.
+    pub fn with_name_template(mut self, template: NameTemplate) -> Self {
+        self.set_name_template(Some(template));
+        self
+    }
+
+    /// Set scripts folder.
+    pub fn with_scripts_folder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_scripts_folder(path);
+        self
+    }
+
+    /// Set the preinstall script.
+    pub fn with_preinstall_script(mut self, script: ScriptSource) -> Self {
+        self.set_preinstall_script(script);
+        self
+    }
+
+    /// Set the postinstall script.
+    pub fn with_postinstall_script(mut self, script: ScriptSource) -> Self {
+        self.set_postinstall_script(script);
+        self
+    }
+
+    /// Add a script template.
+    pub fn with_script_template(mut self, template: ScriptTemplate) -> Self {
+        self.add_script_template(template);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.set_verbosity(verbosity);
+        self
+    }
+
+    /// Set a known-bad hash list and the action to take on a match.
+    pub fn with_blocklist(mut self, path: impl Into<PathBuf>, action: BlocklistAction) -> Self {
+        self.set_blocklist(path, action);
+        self
+    }
+
+    /// Keep setuid/setgid/sticky bits in the payload and BOM.
+    pub fn with_allow_special_modes(mut self, allow: bool) -> Self {
+        self.set_allow_special_modes(allow);
+        self
+    }
+
+    /// Allow packaging a filesystem root or home directory.
+    pub fn with_allow_dangerous_source(mut self, allow: bool) -> Self {
+        self.set_allow_dangerous_source(allow);
+        self
+    }
+
+    /// Set the file count and size limits that need confirmation to exceed.
+    pub fn with_source_limits(mut self, limits: SourceLimits) -> Self {
+        self.set_source_limits(limits);
+        self
+    }
+
+    /// Package even when the source exceeds the limits.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.set_force(force);
+        self
+    }
+
+    /// Validate the output after creation, failing if it takes longer than `timeout`.
+    pub fn with_post_validate(mut self, timeout: Duration) -> Self {
+        self.set_post_validate(Some(timeout));
+        self
+    }
+
+    /// Set which warnings fail the run.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.set_strictness(strictness);
+        self
+    }
+
+    /// Rewrite files matching the rule's glob as they are packaged.
+    pub fn with_transform(mut self, rule: TransformRule) -> Self {
+        self.add_transform(rule);
+        self
+    }
+
+    /// Only package source files matching `glob` (or in folders matching it).
+    pub fn with_include(mut self, glob: Glob) -> Self {
+        self.add_include(glob);
+        self
+    }
+
+    /// Leave out source files matching `glob` (or in folders matching it).
+    pub fn with_exclude(mut self, glob: Glob) -> Self {
+        self.add_exclude(glob);
+        self
+    }
+
+    /// Sign the package with a Developer ID Installer identity.
+    pub fn with_signing(mut self, signing: MacosSigning) -> Self {
+        self.set_signing(signing);
+        self
+    }
+
+    /// Package the source folder as an application bundle.
+    pub fn with_component(mut self, component: bool) -> Self {
+        self.set_component(component);
+        self
+    }
+
+    /// Set how the payload is compressed; stored payloads are gzip
+    /// streams at level 0.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.set_compression(compression);
+        self
+    }
+
+    /// Produce a software bill of materials listing the payload files.
+    pub fn with_sbom(mut self, sbom: SbomOptions) -> Self {
+        self.set_sbom(Some(sbom));
+        self
+    }
+
+    /// Only install the component on Macs of `arch`.
+    pub fn with_host_architecture(mut self, arch: HostArchitecture) -> Self {
+        self.set_host_architecture(Some(arch));
+        self
+    }
+
+    /// Build a payload-free package that only runs its scripts.
+    pub fn with_no_payload(mut self, no_payload: bool) -> Self {
+        self.set_no_payload(no_payload);
+        self
+    }
+
+    /// Set the title, resources and install options of the Distribution.
+    pub fn with_distribution(mut self, options: DistributionOptions) -> Self {
+        self.set_distribution(options);
+        self
+    }
+
+    /// Retry source reads and output writes that fail transiently, e.g.
+    /// on a network share.
+    pub fn with_io_retry(mut self, retry: RetryPolicy) -> Self {
+        self.set_io_retry(retry);
+        self
+    }
+
+    /// Set installation location in place.
+    pub fn set_install_location(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.install_location = path.into();
+        self
+    }
+
+    /// Set custom output filename in place.
+    pub fn set_output_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.output_name = Some(name.into());
+        self
+    }
+
+    /// Set or clear the output filename template in place.
+    pub fn set_name_template(&mut self, template: Option<NameTemplate>) -> &mut Self {
+        self.name_template = template;
+        self
+    }
+
+    /// Set scripts folder in place.
+    pub fn set_scripts_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.scripts_folder = Some(path.into());
+        self
+    }
+
+    /// Set the preinstall script in place.
+    pub fn set_preinstall_script(&mut self, script: ScriptSource) -> &mut Self {
+        self.preinstall_script = Some(script);
+        self
+    }
+
+    /// Set the postinstall script in place.
+    pub fn set_postinstall_script(&mut self, script: ScriptSource) -> &mut Self {
+        self.postinstall_script = Some(script);
+        self
+    }
+
+    /// Add a script template in place; each template runs once.
+    pub fn add_script_template(&mut self, template: ScriptTemplate) -> &mut Self {
+        if !self.script_templates.contains(&template) {
+            self.script_templates.push(template);
+        }
+        self
+    }
+
+    /// Set verbosity level in place.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> &mut Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set a known-bad hash list and match action in place.
+    pub fn set_blocklist(
+        &mut self,
+        path: impl Into<PathBuf>,
+        action: BlocklistAction,
+    ) -> &mut Self {
+        self.blocklist = Some(path.into());
+        self.blocklist_action = action;
+        self
+    }
+
+    /// Keep or strip setuid/setgid/sticky bits in place.
+    pub fn set_allow_special_modes(&mut self, allow: bool) -> &mut Self {
+        self.allow_special_modes = allow;
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory in place.
+    pub fn set_allow_dangerous_source(&mut self, allow: bool) -> &mut Self {
+        self.allow_dangerous_source = allow;
+        self
+    }
+
+    /// Set the source limits in place.
+    pub fn set_source_limits(&mut self, limits: SourceLimits) -> &mut Self {
+        self.source_limits = limits;
+        self
+    }
+
+    /// Set whether to package above the source limits in place.
+    pub fn set_force(&mut self, force: bool) -> &mut Self {
+        self.force = force;
+        self
+    }
+
+    /// Enable (with a time box) or disable post-creation validation in place.
+    pub fn set_post_validate(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.post_validate = timeout;
+        self
+    }
+
+    /// Set which warnings fail the run in place.
+    pub fn set_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Add a transform rule in place; rules run in the order added.
+    pub fn add_transform(&mut self, rule: TransformRule) -> &mut Self {
+        self.transforms.push(rule);
+        self
+    }
+
+    /// Add an include pattern in place.
+    pub fn add_include(&mut self, glob: Glob) -> &mut Self {
+        self.filter.include.push(glob);
+        self
+    }
+
+    /// Add an exclude pattern in place.
+    pub fn add_exclude(&mut self, glob: Glob) -> &mut Self {
+        self.filter.exclude.push(glob);
+        self
+    }
+
+    /// Set the signing identity in place.
+    pub fn set_signing(&mut self, signing: MacosSigning) -> &mut Self {
+        self.signing = Some(signing);
+        self
+    }
+
+    /// Set whether the source folder is an application bundle in place.
+    ///
+    /// The install location is not changed; bundles usually go to
+    /// [`COMPONENT_INSTALL_LOCATION`].
+    pub fn set_component(&mut self, component: bool) -> &mut Self {
+        self.component = component;
+        self
+    }
+
+    /// Set the payload compression in place.
+    pub fn set_compression(&mut self, compression: Compression) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set or clear the SBOM options in place.
+    pub fn set_sbom(&mut self, sbom: Option<SbomOptions>) -> &mut Self {
+        self.sbom = sbom;
+        self
+    }
+
+    /// Set or clear the architecture the component is limited to in place.
+    pub fn set_host_architecture(&mut self, arch: Option<HostArchitecture>) -> &mut Self {
+        self.host_architecture = arch;
+        self
+    }
+
+    /// Set whether the package is payload-free (scripts only) in place.
+    pub fn set_no_payload(&mut self, no_payload: bool) -> &mut Self {
+        self.no_payload = no_payload;
+        self
+    }
+
+    /// Set the Distribution options in place.
+    pub fn set_distribution(&mut self, options: DistributionOptions) -> &mut Self {
+        self.distribution = options;
+        self
+    }
+
+    /// Set the I/O retry policy in place.
+    pub fn set_io_retry(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.io_retry = retry;
+        self
+    }
+
+    /// Validate the package request.
+    pub fn validate(&self) -> PackageResult<()> {
+        self.compression.validate()?;
+        #[cfg(feature = "zstd")]
+        if self.compression.method == CompressionMethod::Zstd {
+            return Err(PackageError::InvalidArgument {
+                reason: "The macOS Installer cannot read Zstandard payloads: use deflate or stored"
+                    .to_string(),
+            });
+        }
+        if let Some(sbom) = &self.sbom {
+            sbom.validate()?;
+        }
+        self.distribution.validate()?;
+        let unnamed_app = self
+            .script_templates
+            .iter()
+            .any(|t| matches!(t, ScriptTemplate::KillApp(None)));
+        if unnamed_app && !self.component {
+            return Err(PackageError::InvalidArgument {
+                reason: "The kill-app template needs an application name (kill-app=NAME) \
+                         unless an app bundle is packaged as a component"
+                    .to_string(),
+            });
+        }
+        if let Some(template) = &self.name_template {
+            if self.output_name.is_some() {
+                return Err(PackageError::InvalidArgument {
+                    reason: "An output name and a name template cannot be combined".to_string(),
+                });
+            }
+            template
+                .check_variables(MACOS_NAME_VARIABLES)
+                .map_err(|reason| PackageError::InvalidArgument { reason })?;
+        }
+        if self.no_payload {
+            return self.validate_payload_free();
+        }
+        check_source_folder(&self.source_folder, self.allow_dangerous_source)
+    }
+
+    /// Checks of a payload-free request, which has scripts but no files.
+    fn validate_payload_free(&self) -> PackageResult<()> {
+        let conflict = if self.component {
+            Some("an app bundle component")
+        } else if self.sbom.is_some() {
+            Some("an SBOM")
+        } else {
+            None
+        };
+        if let Some(conflict) = conflict {
+            return Err(PackageError::InvalidArgument {
+                reason: format!("A payload-free package cannot have {}", conflict),
+            });
+        }
+        let has_scripts = self.scripts_folder.is_some()
+            || self.preinstall_script.is_some()
+            || self.postinstall_script.is_some()
+            || !self.script_templates.is_empty();
+        if !has_scripts {
+            return Err(PackageError::InvalidArgument {
+                reason: "A payload-free package needs a preinstall or postinstall script"
+                    .to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the output file path.
+    pub fn output_path(&self) -> PathBuf {
+        let base_name = match (&self.output_name, &self.name_template) {
+            (Some(name), _) => name.trim_end_matches(".pkg").to_string(),
+            (None, Some(template)) => template.expand(|variable| match variable {
+                naming::IDENTIFIER => Some(self.identifier.clone()),
+                naming::VERSION => Some(self.version.clone()),
+                naming::DATE => Some(naming::today()),
+                naming::GIT_SHA => naming::git_short_sha(&self.source_folder),
+                _ => None,
+            }),
+            (None, None) => format!("{}-{}", self.identifier, self.version),
+        };
+
+        self.output_folder.join(format!("{}.pkg", base_name))
+    }
+
+    /// Check if identifier follows reverse-DNS convention.
+    pub fn is_valid_identifier(&self) -> bool {
+        let parts: Vec<&str> = self.identifier.split('.').collect();
+        parts.len() >= 2 && parts.iter().all(|p| !p.is_empty())
+    }
+}
+
+/// Variables a macOS name template may use.
+const MACOS_NAME_VARIABLES: &[&str] = &[
+    naming::IDENTIFIER,
+    naming::VERSION,
+    naming::DATE,
+    naming::GIT_SHA,
+];
+
+/// Where an install script given on its own comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptSource {
+    /// A script file anywhere on disk
+    File(PathBuf),
+    /// Script content held in memory (e.g., read from stdin)
+    Inline(Vec<u8>),
+}
+
+impl ScriptSource {
+    /// Read the script content.
+    pub fn read(&self) -> PackageResult<Vec<u8>> {
+        match self {
+            ScriptSource::File(path) => {
+                fs::read(path).map_err(|e| PackageError::source_read(path.clone(), e))
+            }
+            ScriptSource::Inline(data) => Ok(data.clone()),
+        }
+    }
+}
+
+/// Helper scripts bundled with iamawrapper that run before installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptTemplate {
+    /// Quit the named application (the packaged app bundle by default)
+    /// so its files can be replaced
+    KillApp(Option<String>),
+    /// Remove the files installed by the previous version of the package
+    /// and forget its receipt
+    RemovePrevious,
+}
+
+impl ScriptTemplate {
+    /// Name used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptTemplate::KillApp(_) => "kill-app",
+            ScriptTemplate::RemovePrevious => "remove-previous",
+        }
+    }
+}
+
+impl fmt::Display for ScriptTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptTemplate::KillApp(Some(app)) => write!(f, "kill-app={}", app),
+            other => f.write_str(other.name()),
+        }
+    }
+}
+
+impl FromStr for ScriptTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once('=') {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (s, None),
+        };
+        match (name.trim(), argument) {
+            ("kill-app", None) => Ok(ScriptTemplate::KillApp(None)),
+            ("kill-app", Some(app)) if !app.is_empty() => {
+                Ok(ScriptTemplate::KillApp(Some(app.to_string())))
+            }
+            ("remove-previous", None) => Ok(ScriptTemplate::RemovePrevious),
+            _ => Err(format!(
+                "Invalid script template '{}' (expected 'kill-app', 'kill-app=NAME' or 'remove-previous')",
+                s
+            )),
+        }
+    }
+}
+
+/// Where to load a package signing identity from.
+///
+/// `identity` is either a PKCS#12 file (key and certificate chain, as
+/// exported from Keychain Access) or a PEM private key, in which case
+/// `certificate` names the PEM certificate chain, leaf first.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MacosSigning {
+    /// PKCS#12 file or PEM private key
+    pub identity: PathBuf,
+    /// PEM certificate chain for a PEM key
+    pub certificate: Option<PathBuf>,
+    /// Password of the PKCS#12 file, wiped from memory when dropped
+    pub password: Option<Zeroizing<String>>,
+}
+
+impl MacosSigning {
+    /// Sign with the identity at `identity`.
+    pub fn new(identity: impl Into<PathBuf>) -> Self {
+        Self {
+            identity: identity.into(),
+            certificate: None,
+            password: None,
+        }
+    }
+
+    /// Use the certificate chain at `path` (for a PEM key).
+    pub fn with_certificate(mut self, path: impl Into<PathBuf>) -> Self {
+        self.certificate = Some(path.into());
+        self
+    }
+
+    /// Unlock the PKCS#12 file with `password`.
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(Zeroizing::new(password.into()));
+        self
+    }
+}
+
+// The password is kept out of debug output
+impl std::fmt::Debug for MacosSigning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MacosSigning")
+            .field("identity", &self.identity)
+            .field("certificate", &self.certificate)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Where an application bundle is installed unless told otherwise.
+pub const COMPONENT_INSTALL_LOCATION: &str = "/Applications";
+
+/// An application bundle, as described by its `Contents/Info.plist`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppBundle {
+    /// Path to the `.app` folder
+    pub path: PathBuf,
+    /// `CFBundleIdentifier`
+    pub identifier: String,
+    /// `CFBundleShortVersionString`, the marketing version
+    pub short_version: Option<String>,
+    /// `CFBundleVersion`, the build number
+    pub bundle_version: Option<String>,
+}
+
+impl AppBundle {
+    /// Folder name of the bundle (e.g., "MyApp.app").
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Version to give the package: the marketing version, or the build
+    /// number when the bundle has none.
+    pub fn version(&self) -> Option<&str> {
+        self.short_version
+            .as_deref()
+            .or(self.bundle_version.as_deref())
+    }
+}
+
+/// Incremental builder for [`MacosPkgRequest`].
+///
+/// Required fields may be set in any order; [`MacosPkgRequestBuilder::build`]
+/// reports missing ones, checks the identifier and runs
+/// [`MacosPkgRequest::validate`] up front.
+#[derive(Debug, Clone, Default)]
+pub struct MacosPkgRequestBuilder {
+    source_folder: Option<PathBuf>,
+    identifier: Option<String>,
+    version: Option<String>,
+    output_folder: Option<PathBuf>,
+    options: Option<MacosPkgRequest>,
+}
+
+impl MacosPkgRequestBuilder {
+    /// Set the source folder.
+    pub fn source_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.source_folder = Some(path.into());
+        self
+    }
+
+    /// Set the package identifier.
+    pub fn identifier(&mut self, identifier: impl Into<String>) -> &mut Self {
+        self.identifier = Some(identifier.into());
+        self
+    }
+
+    /// Set the package version.
+    pub fn version(&mut self, version: impl Into<String>) -> &mut Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Set the output folder.
+    pub fn output_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_folder = Some(path.into());
+        self
+    }
+
+    /// Access the optional settings (install location, scripts, ...).
+    pub fn options(&mut self) -> &mut MacosPkgRequest {
+        self.options.get_or_insert_with(empty_request)
+    }
+
+    /// Build and validate the request.
+    pub fn build(&self) -> PackageResult<MacosPkgRequest> {
+        let mut request = self.options.clone().unwrap_or_else(empty_request);
+        request.source_folder = required(&self.source_folder, "source folder")?;
+        request.identifier = required(&self.identifier, "identifier")?;
+        request.version = required(&self.version, "version")?;
+        request.output_folder = required(&self.output_folder, "output folder")?;
+
+        if !request.is_valid_identifier() {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Identifier '{}' must be in reverse-DNS format (e.g., com.example.app)",
+                    request.identifier
+                ),
+            });
+        }
+        if !request.source_folder.is_dir()
+            && SourceArchive::detect(&request.source_folder).is_none()
+            && !is_remote(&request.source_folder)
+        {
+            return Err(PackageError::SourceFolderNotFound {
+                path: request.source_folder.clone(),
+            });
+        }
+
+        request.validate()?;
+        Ok(request)
+    }
+}
+
+fn empty_request() -> MacosPkgRequest {
+    MacosPkgRequest::new(PathBuf::new(), String::new(), String::new(), PathBuf::new())
+}
+
+/// Result of successful macOS package creation.
+#[derive(Debug, Clone)]
+pub struct MacosPkgResult {
+    /// Full path to created .pkg file
+    pub output_path: PathBuf,
+    /// Size of final .pkg in bytes
+    pub package_size: ByteSize,
+    /// Number of regular files in payload
+    pub file_count: usize,
+    /// Payload breakdown and the sizes reported in PackageInfo
+    pub payload: PayloadSummary,
+    /// Version record embedded in the package
+    pub artifact: ArtifactMetadata,
+    /// Subject of the signing certificate, when the package was signed
+    pub signed_by: Option<String>,
+    /// Application bundle packaged as a component
+    pub bundle: Option<AppBundle>,
+    /// Time to create package
+    pub creation_time: Duration,
+    /// Payload files that matched the blocklist (warn mode only)
+    pub blocklist_matches: Vec<BlocklistMatch>,
+    /// Payload entries with setuid/setgid/sticky bits in the source
+    pub special_mode_files: Vec<PathBuf>,
+    /// Whether those bits were kept (otherwise they were stripped)
+    pub special_modes_preserved: bool,
+    /// Post-creation validation results, when requested
+    pub validation: Option<ValidationReport>,
+    /// Files rewritten by transform rules
+    pub transforms: Vec<TransformRecord>,
+    /// Payload files that are sparse on disk (fully allocated on install)
+    pub sparse_files: Vec<SparseFile>,
+    /// Special entries in the source that were left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Counters collected while packaging (populated at every verbosity)
+    pub stats: ProgressStats,
+    /// Software bill of materials, when one was requested
+    pub sbom: Option<Sbom>,
+    /// Path of the sidecar SBOM, when one was written
+    pub sbom_path: Option<PathBuf>,
+}
+
+/// CPU architecture of the Mac a package installs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HostArchitecture {
+    /// Apple silicon
+    Arm64,
+    /// Intel
+    X86_64,
+}
+
+impl HostArchitecture {
+    /// Name used by the Installer and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            HostArchitecture::Arm64 => "arm64",
+            HostArchitecture::X86_64 => "x86_64",
+        }
+    }
+
+    /// Installer JavaScript that is true on Macs of this architecture.
+    ///
+    /// `hw.optional.arm64` is 1 on Apple silicon even when the Installer
+    /// runs under Rosetta, and missing on Intel Macs.
+    pub fn condition(self) -> &'static str {
+        match self {
+            HostArchitecture::Arm64 => "system.sysctl('hw.optional.arm64') == 1",
+            HostArchitecture::X86_64 => "system.sysctl('hw.optional.arm64') != 1",
+        }
+    }
+}
+
+impl fmt::Display for HostArchitecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HostArchitecture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "arm64" => Ok(HostArchitecture::Arm64),
+            "x86_64" => Ok(HostArchitecture::X86_64),
+            other => Err(format!(
+                "Invalid architecture '{}' (expected 'arm64' or 'x86_64')",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether the Installer's Customize button lets users pick what installs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CustomizeMode {
+    /// Install everything without offering the choice
+    #[default]
+    Never,
+    /// Offer the Customize button
+    Allow,
+    /// Open the customization pane right away
+    Always,
+}
+
+impl CustomizeMode {
+    /// Name used in Distribution and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            CustomizeMode::Never => "never",
+            CustomizeMode::Allow => "allow",
+            CustomizeMode::Always => "always",
+        }
+    }
+}
+
+impl fmt::Display for CustomizeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CustomizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "never" => Ok(CustomizeMode::Never),
+            "allow" => Ok(CustomizeMode::Allow),
+            "always" => Ok(CustomizeMode::Always),
+            other => Err(format!(
+                "Invalid customize mode '{}' (expected 'never', 'allow' or 'always')",
+                other
+            )),
+        }
+    }
+}
+
+/// Where the Installer may install a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstallDomain {
+    /// The startup disk (`enable_localSystem`)
+    LocalSystem,
+    /// The user's home folder, without admin rights
+    /// (`enable_currentUserHome`)
+    CurrentUserHome,
+    /// Any volume the user picks (`enable_anywhere`)
+    Anywhere,
+}
+
+impl InstallDomain {
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            InstallDomain::LocalSystem => "system",
+            InstallDomain::CurrentUserHome => "user",
+            InstallDomain::Anywhere => "anywhere",
+        }
+    }
+
+    /// Attribute of `<domains>` enabling this domain.
+    pub fn attribute(self) -> &'static str {
+        match self {
+            InstallDomain::LocalSystem => "enable_localSystem",
+            InstallDomain::CurrentUserHome => "enable_currentUserHome",
+            InstallDomain::Anywhere => "enable_anywhere",
+        }
+    }
+}
+
+impl fmt::Display for InstallDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for InstallDomain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "system" => Ok(InstallDomain::LocalSystem),
+            "user" => Ok(InstallDomain::CurrentUserHome),
+            "anywhere" => Ok(InstallDomain::Anywhere),
+            other => Err(format!(
+                "Invalid install domain '{}' (expected 'system', 'user' or 'anywhere')",
+                other
+            )),
+        }
+    }
+}
+
+/// Distribution elements that show a file from `Resources`, in the order
+/// they are written.
+pub const RESOURCE_ELEMENTS: &[&str] =
+    &["welcome", "license", "readme", "conclusion", "background"];
+
+/// Installer UI and install target options written to a Distribution.
+///
+/// The defaults match what packages got before these options existed: the
+/// identifier as title, no customization, and the startup disk only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DistributionOptions {
+    /// Title shown by the Installer (default: the package identifier)
+    pub title: Option<String>,
+    /// Welcome text (RTF, TXT or HTML) shown first
+    pub welcome: Option<PathBuf>,
+    /// License the user has to agree to
+    pub license: Option<PathBuf>,
+    /// Read Me shown before installing
+    pub readme: Option<PathBuf>,
+    /// Text shown once the install is done
+    pub conclusion: Option<PathBuf>,
+    /// Image drawn behind the Installer's panes
+    pub background: Option<PathBuf>,
+    /// Folder copied into the package's `Resources`; files named after a
+    /// Distribution element (e.g. `welcome.html`) are shown unless that
+    /// element's file is set
+    pub resources_dir: Option<PathBuf>,
+    /// Oldest macOS version the package installs on, e.g. `12.0`
+    pub min_os_version: Option<String>,
+    /// Whether users can customize the install
+    pub customize: CustomizeMode,
+    /// Domains the package can be installed to; the startup disk when
+    /// empty
+    pub domains: Vec<InstallDomain>,
+}
+
+impl DistributionOptions {
+    /// Set the title shown by the Installer.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the welcome file.
+    pub fn with_welcome(mut self, path: impl Into<PathBuf>) -> Self {
+        self.welcome = Some(path.into());
+        self
+    }
+
+    /// Set the license file.
+    pub fn with_license(mut self, path: impl Into<PathBuf>) -> Self {
+        self.license = Some(path.into());
+        self
+    }
+
+    /// Set the Read Me file.
+    pub fn with_readme(mut self, path: impl Into<PathBuf>) -> Self {
+        self.readme = Some(path.into());
+        self
+    }
+
+    /// Set the conclusion file.
+    pub fn with_conclusion(mut self, path: impl Into<PathBuf>) -> Self {
+        self.conclusion = Some(path.into());
+        self
+    }
+
+    /// Set the background image.
+    pub fn with_background(mut self, path: impl Into<PathBuf>) -> Self {
+        self.background = Some(path.into());
+        self
+    }
+
+    /// Set the folder copied into the package's `Resources`.
+    pub fn with_resources_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.resources_dir = Some(path.into());
+        self
+    }
+
+    /// Set the oldest macOS version the package installs on.
+    pub fn with_min_os_version(mut self, version: impl Into<String>) -> Self {
+        self.min_os_version = Some(version.into());
+        self
+    }
+
+    /// Set whether users can customize the install.
+    pub fn with_customize(mut self, customize: CustomizeMode) -> Self {
+        self.customize = customize;
+        self
+    }
+
+    /// Allow installing to `domain`.
+    pub fn with_domain(mut self, domain: InstallDomain) -> Self {
+        if !self.domains.contains(&domain) {
+            self.domains.push(domain);
+        }
+        self
+    }
+
+    /// Whether the package can be installed to `domain`.
+    pub fn enables(&self, domain: InstallDomain) -> bool {
+        if self.domains.is_empty() {
+            domain == InstallDomain::LocalSystem
+        } else {
+            self.domains.contains(&domain)
+        }
+    }
+
+    /// Resource files with the Distribution element referencing each, in
+    /// [`RESOURCE_ELEMENTS`] order.
+    pub fn resources(&self) -> Vec<(&'static str, &PathBuf)> {
+        [
+            ("welcome", &self.welcome),
+            ("license", &self.license),
+            ("readme", &self.readme),
+            ("conclusion", &self.conclusion),
+            ("background", &self.background),
+        ]
+        .into_iter()
+        .filter_map(|(element, path)| path.as_ref().map(|path| (element, path)))
+        .collect()
+    }
+
+    /// Check the resource files and folder exist, the files have distinct
+    /// names and the minimum macOS version is a version number.
+    pub fn validate(&self) -> PackageResult<()> {
+        if let Some(dir) = &self.resources_dir {
+            if !dir.is_dir() {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!("The resources folder '{}' does not exist", dir.display()),
+                });
+            }
+        }
+        let mut names = BTreeSet::new();
+        for (element, path) in self.resources() {
+            if !path.is_file() {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!("The {} file '{}' does not exist", element, path.display()),
+                });
+            }
+            if !names.insert(path.file_name()) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "The {} file '{}' has the same name as another resource",
+                        element,
+                        path.display()
+                    ),
+                });
+            }
+        }
+        if let Some(version) = &self.min_os_version {
+            let valid = version
+                .split('.')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+            if !valid {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "Invalid minimum macOS version '{}' (expected e.g. '12.0')",
+                        version
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A payload folder built for one architecture (`--arch-payload arm64=DIR`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchPayload {
+    /// Architecture the payload is built for
+    pub arch: HostArchitecture,
+    /// Folder holding the payload
+    pub source_folder: PathBuf,
+}
+
+impl ArchPayload {
+    /// Parse `ARCH=DIR`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (arch, folder) = spec.split_once('=').ok_or_else(|| {
+            format!(
+                "Invalid architecture payload '{}' (expected ARCH=DIR)",
+                spec
+            )
+        })?;
+        if folder.is_empty() {
+            return Err(format!("Architecture payload '{}' names no folder", spec));
+        }
+        Ok(Self {
+            arch: arch.parse()?,
+            source_folder: PathBuf::from(folder),
+        })
+    }
+}
+
+/// Request to combine several component packages into one distribution
+/// `.pkg`, as `productbuild --package` does.
+///
+/// Each component keeps its own source, install location, scripts,
+/// transforms and filters; its output folder, output name and signing are
+/// ignored in favour of the distribution's.
+#[derive(Debug, Clone)]
+pub struct MacosDistributionRequest {
+    /// Product identifier (names the output file by default)
+    pub identifier: String,
+    /// Product version
+    pub version: String,
+    /// Component packages, installed in order
+    pub components: Vec<MacosPkgRequest>,
+    /// Output folder for the .pkg file
+    pub output_folder: PathBuf,
+    /// Custom output filename (without extension)
+    pub output_name: Option<String>,
+    /// Installer title (default: the identifier)
+    pub title: Option<String>,
+    /// Identity to sign the distribution with
+    pub signing: Option<MacosSigning>,
+    /// Re-open the produced package and validate it, with this timeout
+    pub post_validate: Option<Duration>,
+    /// Output verbosity level
+    pub verbosity: Verbosity,
+}
+
+impl MacosDistributionRequest {
+    /// Create a distribution request without components.
+    pub fn new(
+        identifier: impl Into<String>,
+        version: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            identifier: identifier.into(),
+            version: version.into(),
+            components: Vec::new(),
+            output_folder: output_folder.into(),
+            output_name: None,
+            title: None,
+            signing: None,
+            post_validate: None,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Add a component package.
+    pub fn with_component(mut self, component: MacosPkgRequest) -> Self {
+        self.add_component(component);
+        self
+    }
+
+    /// Set custom output filename.
+    pub fn with_output_name(mut self, name: impl Into<String>) -> Self {
+        self.output_name = Some(name.into());
+        self
+    }
+
+    /// Set the installer title.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sign the distribution with the given identity.
+    pub fn with_signing(mut self, signing: MacosSigning) -> Self {
+        self.signing = Some(signing);
+        self
+    }
+
+    /// Validate the produced package, giving up after `timeout`.
+    pub fn with_post_validate(mut self, timeout: Duration) -> Self {
+        self.post_validate = Some(timeout);
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Build one component per architecture from the settings of `base`.
+    ///
+    /// Each component installs `base` built from its payload folder under
+    /// the identifier `<identifier>.<arch>`, and only on Macs of that
+    /// architecture. The product takes the identifier, version, output,
+    /// signing and validation settings of `base`.
+    pub fn for_architectures(base: &MacosPkgRequest, payloads: &[ArchPayload]) -> Self {
+        let mut request = Self::new(
+            base.identifier.clone(),
+            base.version.clone(),
+            base.output_folder.clone(),
+        );
+        request.output_name = base.output_name.clone();
+        request.title = base.distribution.title.clone();
+        request.signing = base.signing.clone();
+        request.post_validate = base.post_validate;
+        request.verbosity = base.verbosity;
+        for payload in payloads {
+            let mut component = base.clone();
+            component.source_folder = payload.source_folder.clone();
+            component.identifier = format!("{}.{}", base.identifier, payload.arch);
+            component.host_architecture = Some(payload.arch);
+            component.signing = None;
+            component.post_validate = None;
+            component.sbom = None;
+            request.add_component(component);
+        }
+        request
+    }
+
+    /// Package the components even when a source exceeds its limits.
+    pub fn with_force(mut self, force: bool) -> Self {
+        for component in &mut self.components {
+            component.set_force(component.force || force);
+        }
+        self
+    }
+
+    /// Add a component package in place.
+    pub fn add_component(&mut self, component: MacosPkgRequest) -> &mut Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Check there is at least one component, that component identifiers
+    /// are unique, and each component's source folder.
+    pub fn validate(&self) -> PackageResult<()> {
+        if self.components.is_empty() {
+            return Err(PackageError::InvalidArgument {
+                reason: "A distribution needs at least one component package".to_string(),
+            });
+        }
+        let mut identifiers = BTreeSet::new();
+        for component in &self.components {
+            if !identifiers.insert(component.identifier.as_str()) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "Component identifier '{}' is used more than once",
+                        component.identifier
+                    ),
+                });
+            }
+            component.validate()?;
+        }
+        Ok(())
+    }
+
+    /// The installer title.
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.identifier)
+    }
+
+    /// Get the output file path.
+    pub fn output_path(&self) -> PathBuf {
+        let base_name = self
+            .output_name
+            .as_ref()
+            .map(|n| n.trim_end_matches(".pkg").to_string())
+            .unwrap_or_else(|| format!("{}-{}", self.identifier, self.version));
+
+        self.output_folder.join(format!("{}.pkg", base_name))
+    }
+}
+
+/// A component of a created distribution.
+#[derive(Debug, Clone)]
+pub struct DistributionComponent {
+    /// Component package identifier
+    pub identifier: String,
+    /// Component package version
+    pub version: String,
+    /// Where the component installs
+    pub install_location: PathBuf,
+    /// Architecture the component is limited to
+    pub host_architecture: Option<HostArchitecture>,
+    /// Number of files in the component's payload
+    pub file_count: usize,
+    /// Files rewritten by transform rules
+    pub transforms: Vec<TransformRecord>,
+    /// Payload files that matched the blocklist (warn mode only)
+    pub blocklist_matches: Vec<BlocklistMatch>,
+}
+
+/// Result of distribution package creation.
+#[derive(Debug, Clone)]
+pub struct MacosDistributionResult {
+    /// Full path to created .pkg file
+    pub output_path: PathBuf,
+    /// Size of final .pkg in bytes
+    pub package_size: ByteSize,
+    /// The components, in install order
+    pub components: Vec<DistributionComponent>,
+    /// Version record embedded in the package (covers every component)
+    pub artifact: ArtifactMetadata,
+    /// Subject of the signing certificate, when the package was signed
+    pub signed_by: Option<String>,
+    /// Post-creation validation results, when requested
+    pub validation: Option<ValidationReport>,
+    /// Time to create package
+    pub creation_time: Duration,
+}
+
+/// Request to extract a `.pkg` into a folder.
+#[derive(Debug, Clone)]
+pub struct MacosExtractRequest {
+    /// Package to extract
+    pub input_file: PathBuf,
+    /// Folder the package is expanded into
+    pub output_folder: PathBuf,
+    /// Output verbosity level
+    pub verbosity: Verbosity,
+}
+
+impl MacosExtractRequest {
+    /// Create an extract request.
+    pub fn new(input_file: impl Into<PathBuf>, output_folder: impl Into<PathBuf>) -> Self {
+        Self {
+            input_file: input_file.into(),
+            output_folder: output_folder.into(),
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.input_file.is_file() {
+            return Err(PackageError::SourceReadError {
+                path: self.input_file.clone(),
+                reason: "Input package not found".to_string(),
+                source: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Result of package extraction.
+#[derive(Debug, Clone)]
+pub struct MacosExtractResult {
+    /// Folder the package was expanded into
     pub output_folder: PathBuf,
-    /// Optional custom output filename (without extension)
-    pub output_name: Option<String>,
-    /// Optional folder containing preinstall/postinstall scripts
-    pub scripts_folder: Option<PathBuf>,
-    /// Verbosity level for output
+    /// Number of files written (payload files, scripts and metadata)
+    pub file_count: usize,
+    /// Number of directories created
+    pub dir_count: usize,
+    /// Total size of the files written
+    pub total_size: ByteSize,
+    /// Folders the Payload and Scripts archives were expanded into,
+    /// relative to the output folder
+    pub archives: Vec<PathBuf>,
+    /// Time to extract the package
+    pub extract_time: Duration,
+}
+
+/// Request to modify the Distribution and resources of an existing `.pkg`.
+#[derive(Debug, Clone)]
+pub struct MacosRepackRequest {
+    /// Package to modify
+    pub input: PathBuf,
+    /// Path of the rewritten package
+    pub output: PathBuf,
+    /// Installer title to set in Distribution
+    pub title: Option<String>,
+    /// License file (RTF, TXT or HTML) added to Resources and referenced from Distribution
+    pub license: Option<PathBuf>,
+    /// Additional files added to (or replaced in) Resources
+    pub resources: Vec<PathBuf>,
+    /// Carry an existing signature over instead of stripping it
+    pub keep_signature: bool,
+    /// Output verbosity level
     pub verbosity: Verbosity,
+    /// Which warnings fail the run
+    pub strictness: Strictness,
 }
 
-impl MacosPkgRequest {
-    /// Create a new macOS package request with required fields.
-    pub fn new(
-        source_folder: PathBuf,
-        identifier: String,
-        version: String,
-        output_folder: PathBuf,
-    ) -> Self {
+impl MacosRepackRequest {
+    /// Create a repack request that rewrites `input` to `output` unchanged.
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
         Self {
-            source_folder,
-            identifier,
-            version,
-            install_location: PathBuf::from("/"),
-            output_folder,
-            output_name: None,
-            scripts_folder: None,
+            input: input.into(),
+            output: output.into(),
+            title: None,
+            license: None,
+            resources: Vec::new(),
+            keep_signature: false,
             verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
         }
     }
 
-    /// Set custom installation location.
-    pub fn with_install_location(mut self, path: PathBuf) -> Self {
-        self.install_location = path;
+    /// Set the installer title.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
         self
     }
 
-    /// Set custom output filename.
-    pub fn with_output_name(mut self, name: String) -> Self {
-        self.output_name = Some(name);
+    /// Set the license file.
+    pub fn with_license(mut self, license: impl Into<PathBuf>) -> Self {
+        self.license = Some(license.into());
         self
     }
 
-    /// Set scripts folder.
-    pub fn with_scripts_folder(mut self, path: PathBuf) -> Self {
-        self.scripts_folder = Some(path);
+    /// Add a file to Resources.
+    pub fn with_resource(mut self, resource: impl Into<PathBuf>) -> Self {
+        self.resources.push(resource.into());
+        self
+    }
+
+    /// Keep an existing signature instead of stripping it.
+    pub fn with_keep_signature(mut self, keep: bool) -> Self {
+        self.keep_signature = keep;
         self
     }
 
@@ -70,34 +1522,334 @@ impl MacosPkgRequest {
         self
     }
 
-    /// Get the output file path.
+    /// Set which warnings fail the run.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.input.is_file() {
+            return Err(PackageError::SourceReadError {
+                path: self.input.clone(),
+                reason: "Input package not found".to_string(),
+                source: None,
+            });
+        }
+
+        for file in self.license.iter().chain(&self.resources) {
+            if !file.is_file() {
+                return Err(PackageError::SourceReadError {
+                    path: file.clone(),
+                    reason: "Resource file not found".to_string(),
+                    source: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of repacking a macOS package.
+#[derive(Debug, Clone)]
+pub struct MacosRepackResult {
+    /// Path of the rewritten package
+    pub output_path: PathBuf,
+    /// Size of the rewritten package
+    pub package_size: ByteSize,
+    /// Archive paths added or replaced under Resources
+    pub resources: Vec<String>,
+    /// Whether the input carried a signature
+    pub was_signed: bool,
+    /// Whether the output carries a signature
+    pub signed: bool,
+    /// Why the output is not validly signed, when the input was
+    pub signature_warning: Option<String>,
+    /// Time to repack
+    pub repack_time: Duration,
+}
+
+/// Request to add or replace the install scripts of an existing `.pkg`.
+#[derive(Debug, Clone)]
+pub struct MacosRewrapRequest {
+    /// Package to modify
+    pub input: PathBuf,
+    /// Folder of scripts merged into the component's Scripts archive
+    pub scripts_folder: PathBuf,
+    /// Path of the rewritten package (default: `<input>-rewrapped.pkg`)
+    pub output: Option<PathBuf>,
+    /// Identifier of the component to change, required when the package
+    /// has more than one
+    pub component: Option<String>,
+    /// Output verbosity level
+    pub verbosity: Verbosity,
+    /// Which warnings fail the run
+    pub strictness: Strictness,
+}
+
+impl MacosRewrapRequest {
+    /// Create a request merging `scripts_folder` into `input`.
+    pub fn new(input: impl Into<PathBuf>, scripts_folder: impl Into<PathBuf>) -> Self {
+        Self {
+            input: input.into(),
+            scripts_folder: scripts_folder.into(),
+            output: None,
+            component: None,
+            verbosity: Verbosity::default(),
+            strictness: Strictness::default(),
+        }
+    }
+
+    /// Set the path of the rewritten package.
+    pub fn with_output(mut self, output: impl Into<PathBuf>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// Choose the component to change by identifier.
+    pub fn with_component(mut self, identifier: impl Into<String>) -> Self {
+        self.component = Some(identifier.into());
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Set which warnings fail the run.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Path of the rewritten package.
     pub fn output_path(&self) -> PathBuf {
-        let base_name = self
-            .output_name
-            .as_ref()
-            .map(|n| n.trim_end_matches(".pkg").to_string())
-            .unwrap_or_else(|| format!("{}-{}", self.identifier, self.version));
+        match &self.output {
+            Some(output) => output.clone(),
+            None => {
+                let stem = self
+                    .input
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "package".to_string());
+                self.input.with_file_name(format!("{}-rewrapped.pkg", stem))
+            }
+        }
+    }
 
-        self.output_folder.join(format!("{}.pkg", base_name))
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.input.is_file() {
+            return Err(PackageError::SourceReadError {
+                path: self.input.clone(),
+                reason: "Input package not found".to_string(),
+                source: None,
+            });
+        }
+        if !self.scripts_folder.is_dir() {
+            return Err(PackageError::ScriptsFolderNotFound {
+                path: self.scripts_folder.clone(),
+            });
+        }
+        if self.output_path() == self.input {
+            return Err(PackageError::InvalidArgument {
+                reason: "The rewrapped package would overwrite the input".to_string(),
+            });
+        }
+        Ok(())
     }
+}
 
-    /// Check if identifier follows reverse-DNS convention.
-    pub fn is_valid_identifier(&self) -> bool {
-        let parts: Vec<&str> = self.identifier.split('.').collect();
-        parts.len() >= 2 && parts.iter().all(|p| !p.is_empty())
+/// Result of rewrapping a macOS package.
+#[derive(Debug, Clone)]
+pub struct MacosRewrapResult {
+    /// Path of the rewritten package
+    pub output_path: PathBuf,
+    /// Size of the rewritten package
+    pub package_size: ByteSize,
+    /// Identifier of the changed component
+    pub component: String,
+    /// Scripts added to the component
+    pub added: Vec<String>,
+    /// Scripts that replaced ones already in the component
+    pub replaced: Vec<String>,
+    /// Whether the input carried a signature
+    pub was_signed: bool,
+    /// Whether the output carries a signature
+    pub signed: bool,
+    /// Why the output is not signed, when the input was
+    pub signature_warning: Option<String>,
+    /// Time to rewrap
+    pub rewrap_time: Duration,
+}
+
+/// How the data of a disk image is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DmgFormat {
+    /// Read-only, zlib-compressed (`hdiutil` UDZO)
+    #[default]
+    Udzo,
+    /// Read-only, uncompressed (`hdiutil` UDRO)
+    Udro,
+}
+
+impl DmgFormat {
+    /// Name used on the command line and by `hdiutil`.
+    pub fn name(self) -> &'static str {
+        match self {
+            DmgFormat::Udzo => "UDZO",
+            DmgFormat::Udro => "UDRO",
+        }
     }
 }
 
-/// Result of successful macOS package creation.
+impl fmt::Display for DmgFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DmgFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "UDZO" => Ok(DmgFormat::Udzo),
+            "UDRO" => Ok(DmgFormat::Udro),
+            other => Err(format!(
+                "Invalid disk image format '{}' (expected 'UDZO' or 'UDRO')",
+                other
+            )),
+        }
+    }
+}
+
+/// Longest volume name a disk image can carry.
+pub const MAX_VOLUME_NAME_LEN: usize = 32;
+
+/// Request to build a disk image (`.dmg`) from a folder.
 #[derive(Debug, Clone)]
-pub struct MacosPkgResult {
-    /// Full path to created .pkg file
+pub struct MacosDmgRequest {
+    /// Folder whose contents become the root of the volume
+    pub source_folder: PathBuf,
+    /// Path of the disk image to write
+    pub output: PathBuf,
+    /// Name the volume mounts as (default: the source folder's name)
+    pub volume_name: Option<String>,
+    /// How the image data is stored
+    pub format: DmgFormat,
+    /// Plain-text license shown before the image is mounted
+    pub license: Option<PathBuf>,
+    /// Output verbosity level
+    pub verbosity: Verbosity,
+}
+
+impl MacosDmgRequest {
+    /// Create a request for a compressed image of `source_folder`.
+    pub fn new(source_folder: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            output: output.into(),
+            volume_name: None,
+            format: DmgFormat::default(),
+            license: None,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Set the volume name.
+    pub fn with_volume_name(mut self, name: impl Into<String>) -> Self {
+        self.volume_name = Some(name.into());
+        self
+    }
+
+    /// Set how the image data is stored.
+    pub fn with_format(mut self, format: DmgFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the license file.
+    pub fn with_license(mut self, license: impl Into<PathBuf>) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// The volume name, defaulting to the source folder's name.
+    pub fn volume_name(&self) -> String {
+        match &self.volume_name {
+            Some(name) => name.clone(),
+            None => self
+                .source_folder
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Untitled".to_string()),
+        }
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.source_folder.is_dir() {
+            return Err(PackageError::SourceFolderNotFound {
+                path: self.source_folder.clone(),
+            });
+        }
+
+        let name = self.volume_name();
+        if name.is_empty()
+            || name.len() > MAX_VOLUME_NAME_LEN
+            || !name.chars().all(|c| c.is_ascii() && !c.is_ascii_control())
+        {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Invalid volume name '{}': use 1 to {} ASCII characters",
+                    name, MAX_VOLUME_NAME_LEN
+                ),
+            });
+        }
+
+        if let Some(license) = &self.license {
+            if !license.is_file() {
+                return Err(PackageError::SourceReadError {
+                    path: license.clone(),
+                    reason: "License file not found".to_string(),
+                    source: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of building a disk image.
+#[derive(Debug, Clone)]
+pub struct MacosDmgResult {
+    /// Path of the disk image
     pub output_path: PathBuf,
-    /// Size of final .pkg in bytes
-    pub package_size: u64,
-    /// Number of files in payload
+    /// Size of the disk image
+    pub image_size: ByteSize,
+    /// Size of the volume once mounted
+    pub volume_size: ByteSize,
+    /// Name the volume mounts as
+    pub volume_name: String,
+    /// How the image data is stored
+    pub format: DmgFormat,
+    /// Number of regular files on the volume
     pub file_count: usize,
-    /// Time to create package
+    /// SHA-256 of the image, as a Homebrew cask's `sha256` expects
+    pub sha256: String,
+    /// Time to build the image
     pub creation_time: Duration,
 }
 
@@ -112,13 +1864,180 @@ pub struct PayloadFile {
     pub mode: u32,
 }
 
+impl PayloadFile {
+    /// Whether the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode & 0o170000 == 0o040000
+    }
+
+    /// Whether the entry is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.mode & 0o170000 == 0o120000
+    }
+
+    /// Whether the entry is a regular file, whose contents are archived.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir() && !self.is_symlink()
+    }
+}
+
+/// App Store Connect API key used to authenticate with the notary service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryCredentials {
+    /// Key ID (the `<id>` in `AuthKey_<id>.p8`)
+    pub key_id: String,
+    /// Issuer ID shown above the team's API keys
+    pub issuer_id: String,
+    /// The `.p8` private key file
+    pub private_key: PathBuf,
+}
+
+impl NotaryCredentials {
+    /// Credentials for the key `key_id` of `issuer_id`, stored in `private_key`.
+    pub fn new(
+        key_id: impl Into<String>,
+        issuer_id: impl Into<String>,
+        private_key: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            key_id: key_id.into(),
+            issuer_id: issuer_id.into(),
+            private_key: private_key.into(),
+        }
+    }
+}
+
+/// Request to notarize a signed `.pkg` and staple the ticket to it.
+#[derive(Debug, Clone)]
+pub struct NotarizeRequest {
+    /// Signed package to notarize; the ticket is stapled to it in place
+    pub input_file: PathBuf,
+    /// API key to authenticate with
+    pub credentials: NotaryCredentials,
+    /// Give up waiting for a verdict after this long
+    pub timeout: Duration,
+    /// Wait between status checks
+    pub poll_interval: Duration,
+    /// How failed requests are retried
+    pub retry: RetryPolicy,
+    /// Staple the ticket once the package is accepted
+    pub staple: bool,
+    /// Output verbosity level
+    pub verbosity: Verbosity,
+}
+
+impl NotarizeRequest {
+    /// Notarize `input_file` with `credentials`, waiting up to an hour.
+    pub fn new(input_file: impl Into<PathBuf>, credentials: NotaryCredentials) -> Self {
+        Self {
+            input_file: input_file.into(),
+            credentials,
+            timeout: Duration::from_secs(60 * 60),
+            poll_interval: Duration::from_secs(30),
+            retry: RetryPolicy::default(),
+            staple: true,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Set how long to wait for a verdict.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the wait between status checks.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set how failed requests are retried.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Staple the ticket to the package once accepted.
+    pub fn with_staple(mut self, staple: bool) -> Self {
+        self.staple = staple;
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.input_file.is_file() {
+            return Err(PackageError::SourceReadError {
+                path: self.input_file.clone(),
+                reason: "Input package not found".to_string(),
+                source: None,
+            });
+        }
+        if self.credentials.key_id.trim().is_empty() || self.credentials.issuer_id.trim().is_empty()
+        {
+            return Err(PackageError::InvalidArgument {
+                reason: "Notarization needs an API key ID and issuer ID".to_string(),
+            });
+        }
+        if self.retry.attempts == 0 {
+            return Err(PackageError::InvalidArgument {
+                reason: "Retry attempts must be at least 1".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Result of notarizing a package.
+#[derive(Debug, Clone)]
+pub struct NotarizeResult {
+    /// The notarized package
+    pub input_file: PathBuf,
+    /// Submission ID assigned by the notary service
+    pub submission_id: String,
+    /// Size of the ticket stapled to the package, if stapled
+    pub stapled_ticket: Option<ByteSize>,
+    /// Time from upload to verdict (and stapling)
+    pub notarize_time: Duration,
+}
+
+/// Allocation block that pkgbuild rounds each file up to for `installKBytes`.
+const INSTALL_BLOCK_SIZE: u64 = 4096;
+
+/// Payload entries by kind, and the sizes written to PackageInfo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PayloadSummary {
+    /// Regular files
+    pub files: usize,
+    /// Directories below the install location
+    pub directories: usize,
+    /// Symbolic links
+    pub symlinks: usize,
+    /// Total size of the regular files
+    pub file_bytes: u64,
+    /// PackageInfo `installKBytes`: files rounded up to 4 KiB blocks
+    pub install_kbytes: u64,
+    /// PackageInfo `numberOfFiles`: every BOM path, including `.`
+    pub number_of_files: usize,
+}
+
 /// The package payload (files to install).
 #[derive(Debug, Clone)]
 pub struct PackagePayload {
-    /// Files included in payload
+    /// Files and directories included in payload
     pub files: Vec<PayloadFile>,
     /// Uncompressed total size
     pub total_size: u64,
+    /// Files that are sparse on disk
+    pub sparse_files: Vec<SparseFile>,
+    /// Special entries left out
+    pub skipped_entries: Vec<SkippedEntry>,
 }
 
 impl PackagePayload {
@@ -127,6 +2046,8 @@ impl PackagePayload {
         Self {
             files: Vec::new(),
             total_size: 0,
+            sparse_files: Vec::new(),
+            skipped_entries: Vec::new(),
         }
     }
 
@@ -140,10 +2061,70 @@ impl PackagePayload {
         self.total_size += size;
     }
 
-    /// Get the number of files.
+    /// Add a directory to the payload.
+    pub fn add_directory(&mut self, relative_path: PathBuf, mode: u32) {
+        self.add_file(relative_path, 0, (mode & !0o170000) | 0o040000);
+    }
+
+    /// Add a symbolic link whose target is `target_len` bytes long.
+    pub fn add_symlink(&mut self, relative_path: PathBuf, target_len: u64, mode: u32) {
+        self.add_file(relative_path, target_len, (mode & !0o170000) | 0o120000);
+    }
+
+    /// Get the number of payload entries (files and directories).
     pub fn file_count(&self) -> usize {
         self.files.len()
     }
+
+    /// Value for PackageInfo's `installKBytes`.
+    ///
+    /// Follows pkgbuild, which estimates the space used once installed:
+    /// each regular file takes whole 4 KiB blocks, while directories and
+    /// symbolic links are not counted.
+    pub fn install_kbytes(&self) -> u64 {
+        self.files
+            .iter()
+            .filter(|f| f.is_file())
+            .map(|f| f.size.div_ceil(INSTALL_BLOCK_SIZE) * (INSTALL_BLOCK_SIZE / 1024))
+            .sum()
+    }
+
+    /// Count the payload by kind of entry, with the PackageInfo values.
+    pub fn summary(&self) -> PayloadSummary {
+        let mut summary = PayloadSummary {
+            install_kbytes: self.install_kbytes(),
+            number_of_files: self.number_of_files(),
+            ..PayloadSummary::default()
+        };
+        for file in &self.files {
+            if file.is_dir() {
+                summary.directories += 1;
+            } else if file.is_symlink() {
+                summary.symlinks += 1;
+            } else {
+                summary.files += 1;
+                summary.file_bytes += file.size;
+            }
+        }
+        summary
+    }
+
+    /// Value for PackageInfo's `numberOfFiles`.
+    ///
+    /// Follows pkgbuild, which counts every path in the BOM as `lsbom`
+    /// lists them: files and directories alike, implicit parent
+    /// directories, and the root `.` itself.
+    pub fn number_of_files(&self) -> usize {
+        let mut paths = BTreeSet::new();
+        for file in &self.files {
+            paths.extend(
+                file.relative_path
+                    .ancestors()
+                    .take_while(|p| !p.as_os_str().is_empty()),
+            );
+        }
+        paths.len() + 1
+    }
 }
 
 impl Default for PackagePayload {
@@ -156,6 +2137,27 @@ impl Default for PackagePayload {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_macos_pkg_request_builder() {
+        let temp = tempfile::TempDir::new().unwrap();
+
+        let mut builder = MacosPkgRequest::builder();
+        builder
+            .source_folder(temp.path())
+            .identifier("invalid")
+            .version("1.0")
+            .output_folder("out");
+        builder.options().set_install_location("/Applications");
+        assert!(matches!(
+            builder.build(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        builder.identifier("com.example.app");
+        let request = builder.build().unwrap();
+        assert_eq!(request.install_location, PathBuf::from("/Applications"));
+    }
+
     #[test]
     fn test_macos_pkg_request_new() {
         let req = MacosPkgRequest::new(
@@ -202,6 +2204,25 @@ mod tests {
         assert_eq!(req.output_path(), PathBuf::from("/output/MyApp.pkg"));
     }
 
+    #[test]
+    fn test_output_path_name_template() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let template = NameTemplate::new("{identifier}_{version}-{git_sha}").unwrap();
+        let req = MacosPkgRequest::new(temp.path(), "com.test.app", "1.0.0", "/output")
+            .with_name_template(template);
+
+        assert_eq!(
+            req.output_path(),
+            PathBuf::from("/output/com.test.app_1.0.0.pkg")
+        );
+        assert!(
+            req.clone()
+                .with_name_template(NameTemplate::new("{setup_stem}").unwrap())
+                .validate()
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_is_valid_identifier() {
         let req = MacosPkgRequest::new(
@@ -230,4 +2251,91 @@ mod tests {
         assert_eq!(payload.file_count(), 2);
         assert_eq!(payload.total_size, 3072);
     }
+
+    #[test]
+    fn test_payload_number_of_files_counts_bom_paths() {
+        let mut payload = PackagePayload::new();
+        // Parent directories not listed explicitly still count, once
+        payload.add_file(PathBuf::from("Applications/Foo.app"), 0, 0o040755);
+        payload.add_file(
+            PathBuf::from("Applications/Foo.app/Contents/Info.plist"),
+            10,
+            0o644,
+        );
+        payload.add_file(
+            PathBuf::from("Applications/Foo.app/Contents/MacOS/foo"),
+            20,
+            0o755,
+        );
+
+        // ., Applications, Foo.app, Contents, Info.plist, MacOS, foo
+        assert_eq!(payload.number_of_files(), 7);
+        assert_eq!(PackagePayload::new().number_of_files(), 1);
+    }
+
+    #[test]
+    fn test_payload_summary_rounds_files_to_blocks() {
+        let mut payload = PackagePayload::new();
+        payload.add_directory(PathBuf::from("App.app"), 0o755);
+        payload.add_file(PathBuf::from("App.app/Info.plist"), 1, 0o644);
+        payload.add_file(PathBuf::from("App.app/app"), 4097, 0o755);
+        payload.add_file(PathBuf::from("App.app/empty"), 0, 0o644);
+        payload.add_symlink(PathBuf::from("App.app/link"), 2048, 0o755);
+
+        let summary = payload.summary();
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.directories, 1);
+        assert_eq!(summary.symlinks, 1);
+        assert_eq!(summary.file_bytes, 4098);
+        // 4 KiB for the plist, 8 KiB for the binary, nothing else
+        assert_eq!(summary.install_kbytes, 12);
+        assert_eq!(summary.number_of_files, 6);
+        assert_eq!(payload.total_size, 6146);
+    }
+
+    #[test]
+    fn test_parse_script_template() {
+        assert_eq!("kill-app".parse(), Ok(ScriptTemplate::KillApp(None)));
+        assert_eq!(
+            "kill-app=Acme".parse(),
+            Ok(ScriptTemplate::KillApp(Some("Acme".to_string())))
+        );
+        assert_eq!(
+            "remove-previous".parse(),
+            Ok(ScriptTemplate::RemovePrevious)
+        );
+        assert!("kill-app=".parse::<ScriptTemplate>().is_err());
+        assert!("remove-previous=x".parse::<ScriptTemplate>().is_err());
+        assert_eq!(
+            ScriptTemplate::KillApp(Some("Acme".to_string())).to_string(),
+            "kill-app=Acme"
+        );
+
+        // An unnamed app needs a bundle to take the name from
+        let temp = tempfile::TempDir::new().unwrap();
+        let request = MacosPkgRequest::new(temp.path(), "com.example.app", "1.0", temp.path())
+            .with_script_template(ScriptTemplate::KillApp(None))
+            .with_script_template(ScriptTemplate::KillApp(None));
+        assert_eq!(request.script_templates.len(), 1);
+        assert!(request.validate().is_err());
+        assert!(request.with_component(true).validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_arch_payload() {
+        assert_eq!(
+            ArchPayload::parse("arm64=build/arm64"),
+            Ok(ArchPayload {
+                arch: HostArchitecture::Arm64,
+                source_folder: PathBuf::from("build/arm64"),
+            })
+        );
+        assert_eq!(
+            ArchPayload::parse("x86_64=a=b").unwrap().source_folder,
+            PathBuf::from("a=b")
+        );
+        assert!(ArchPayload::parse("arm64").is_err());
+        assert!(ArchPayload::parse("arm64=").is_err());
+        assert!(ArchPayload::parse("ppc=build").is_err());
+    }
 }
@@ -1,13 +1,106 @@
 //! Data models for the iamawrapper packager.
 
+pub mod analyze;
+pub mod artifact;
+pub mod batch;
+pub mod blocklist;
+pub mod build;
+pub mod cache;
+pub mod capture;
+pub mod choco;
+pub mod compression;
+pub mod config;
 pub mod detection;
+pub mod diff;
+pub mod dry_run;
+pub mod encryption;
 pub mod error;
+pub mod glob;
+pub mod guardrails;
+pub mod incremental;
+#[cfg(feature = "linux")]
+pub mod linux;
+pub mod listing;
 #[cfg(feature = "macos")]
 pub mod macos;
+pub mod naming;
 pub mod package;
+pub mod progress;
+pub mod project;
+pub mod relationships;
+pub mod retry;
+pub mod sbom;
+pub mod scaffold;
+pub mod selftest;
+pub mod setup;
+pub mod size;
+pub mod strictness;
+pub mod suggest;
+pub mod time;
+pub mod transform;
+pub mod validation;
+pub mod watch;
+pub mod win32app;
 
-pub use detection::{DetectionMetadata, EncryptionInfo};
-pub use error::{PackageError, PackageResult};
+pub use analyze::{AnalyzeRequest, DuplicateGroup, FileEstimate, SourceAnalysis};
+pub use artifact::{ArtifactKind, ArtifactMetadata, ArtifactReport};
+pub use batch::{
+    BatchFormat, BatchItem, BatchItemResult, BatchManifest, BatchOptions, BatchOutcome, BatchReport,
+};
+pub use blocklist::{Blocklist, BlocklistAction, BlocklistMatch};
+pub use build::{BuildConfig, BuildContext, TargetKind};
+pub use cache::{CacheConfig, CacheStats};
+pub use capture::{
+    CaptureDiff, CaptureEntry, CaptureFinishRequest, CaptureResult, CaptureSnapshot,
+};
+pub use choco::{ChocoPackRequest, ChocoPackResult};
+pub use compression::{Compression, CompressionMethod};
+pub use detection::{
+    DetectionExtra, DetectionMetadata, DetectionRule, DetectionRules, DetectionRulesRequest,
+    DigestAlgorithm, EncryptionInfo, MsiInformation, XmlElement,
+};
+pub use diff::{ChangeKind, FieldChange, FileChange, FileSummary, PackageDiff};
+pub use dry_run::DryRunReport;
+pub use encryption::{EncryptionOptions, KeySource, ProvidedKeys};
+pub use error::{ErrorCode, PackageError, PackageResult, ZipStage};
+pub use glob::{Glob, SourceFilter};
+pub use incremental::{BuildState, IncrementalOptions, IncrementalOutcome};
+#[cfg(feature = "linux")]
+pub use linux::{DebRequest, DebResult, RpmRequest, RpmResult};
+pub use listing::ContentEntry;
 #[cfg(feature = "macos")]
-pub use macos::{MacosPkgRequest, MacosPkgResult, PackagePayload, PayloadFile};
-pub use package::{IntuneWinPackage, PackageRequest, SourceFile, SourcePackage, Verbosity};
+pub use macos::{
+    AppBundle, ArchPayload, CustomizeMode, DistributionComponent, DistributionOptions, DmgFormat,
+    HostArchitecture, InstallDomain, MacosDistributionRequest, MacosDistributionResult,
+    MacosDmgRequest, MacosDmgResult, MacosExtractRequest, MacosExtractResult, MacosPkgRequest,
+    MacosPkgRequestBuilder, MacosPkgResult, MacosRepackRequest, MacosRepackResult,
+    MacosRewrapRequest, MacosRewrapResult, MacosSigning, NotarizeRequest, NotarizeResult,
+    NotaryCredentials, PackagePayload, PayloadFile, PayloadSummary, ScriptSource, ScriptTemplate,
+};
+pub use naming::NameTemplate;
+pub use package::{
+    IntuneWinPackage, PackageRequest, PackageRequestBuilder, PathIssue, PathProblem, RepackRequest,
+    RepackResult, Reproducible, SkippedEntry, SourceFile, SourcePackage, SparseFile, SpecialKind,
+    Verbosity,
+};
+pub use progress::{ProgressCounters, ProgressStats};
+pub use project::{
+    Project, ProjectBuild, ProjectImportRequest, ProjectImportResult, ProjectScript,
+};
+pub use relationships::{AppDependency, AppRelationships, SupersededApp};
+pub use retry::RetryPolicy;
+pub use sbom::{ProductSource, Sbom, SbomFile, SbomFormat, SbomOptions, SbomProduct};
+pub use scaffold::{ScaffoldRequest, ScaffoldResult};
+pub use selftest::{SelfTestReport, SelfTestStage, StageOutcome};
+pub use setup::ExeVersionInfo;
+pub use size::ByteSize;
+pub use strictness::{Check, CheckLevel, Strictness};
+pub use transform::{FileTransformer, TransformRecord, TransformRule};
+pub use validation::{
+    CheckOutcome, PkgValidationReport, ValidationReport, VerifyCheck, VerifyResult,
+};
+pub use watch::{WatchJob, WatchRequest, WatchRules};
+pub use win32app::{
+    AppManifestRequest, AppSettings, Architecture, InstallContext, RestartBehavior, ReturnCode,
+    ReturnCodeType, Win32AppManifest,
+};
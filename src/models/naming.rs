@@ -0,0 +1,345 @@
+//! Output file names built from templates.
+//!
+//! A template such as `{setup_stem}-{version}-{date}` names the package
+//! after values known when it is built. A variable without a value (no
+//! version information, not in a git repository) is left out together
+//! with the separator in front of it, so `{setup_stem}-{git_sha}` becomes
+//! `setup` outside a repository. Characters Windows does not allow in file
+//! names are replaced with `_`.
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::models::time::civil_time;
+use crate::packager::{msi, pe};
+
+/// Setup file name without its extension (Intune).
+pub const SETUP_STEM: &str = "setup_stem";
+
+/// Package identifier (macOS).
+pub const IDENTIFIER: &str = "identifier";
+
+/// Product version of the setup file (Intune) or package version (macOS).
+pub const VERSION: &str = "version";
+
+/// Today's date (UTC) as `YYYY-MM-DD`.
+pub const DATE: &str = "date";
+
+/// Short SHA of the git commit checked out in the source folder.
+pub const GIT_SHA: &str = "git_sha";
+
+/// Every variable a template may use.
+pub const NAME_VARIABLES: &[&str] = &[SETUP_STEM, IDENTIFIER, VERSION, DATE, GIT_SHA];
+
+/// Characters Windows does not allow in file names.
+const INVALID_NAME_CHARS: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Separators dropped along with a variable that has no value.
+const SEPARATORS: &[char] = &['-', '_', '.', ' '];
+
+/// Length of an abbreviated commit SHA, as `git rev-parse --short` prints it.
+const SHORT_SHA_LEN: usize = 7;
+
+/// One piece of a parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+/// A parsed output name template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    /// Parse a template with `{variable}` placeholders.
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err(format!("Unmatched '}}' in name template '{}'", template));
+            }
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 1..];
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("Unclosed '{{' in name template '{}'", template))?;
+            let name = after[..end].trim();
+            if !NAME_VARIABLES.contains(&name) {
+                return Err(format!(
+                    "Unknown variable '{{{}}}' in name template '{}' (expected one of: {})",
+                    name,
+                    template,
+                    NAME_VARIABLES.join(", ")
+                ));
+            }
+            parts.push(Part::Variable(name.to_string()));
+            rest = &after[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        let text: String = parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                Part::Variable(_) => None,
+            })
+            .collect();
+        if text.contains(INVALID_NAME_CHARS) || text.chars().any(char::is_control) {
+            return Err(format!(
+                "Name template '{}' contains characters not allowed in file names",
+                template
+            ));
+        }
+        if !parts.iter().any(|part| matches!(part, Part::Variable(_))) && text.trim().is_empty() {
+            return Err("Name template is empty".to_string());
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    /// The template as given.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Variables used by the template, in order.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    /// Fail unless every variable used is one of `allowed`.
+    pub fn check_variables(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.variables().find(|name| !allowed.contains(name)) {
+            Some(name) => Err(format!(
+                "'{{{}}}' cannot be used here (expected one of: {})",
+                name,
+                allowed.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Expand the template, asking `lookup` for the value of each variable.
+    pub fn expand(&self, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Variable(name) => match lookup(name).filter(|v| !v.trim().is_empty()) {
+                    Some(value) => out.extend(value.trim().chars().map(|c| {
+                        if INVALID_NAME_CHARS.contains(&c) || c.is_control() {
+                            '_'
+                        } else {
+                            c
+                        }
+                    })),
+                    None => {
+                        if out.ends_with(SEPARATORS) {
+                            out.pop();
+                        }
+                    }
+                },
+            }
+        }
+        let name = out.trim_matches(SEPARATORS);
+        if name.is_empty() {
+            "package".to_string()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+/// Today's date (UTC) as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let [year, month, day, ..] = civil_time(secs);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Product version of an `.exe` (version resource) or `.msi`
+/// (`ProductVersion` property) setup file.
+pub fn setup_version(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    let version = match extension.as_str() {
+        "exe" => {
+            let mut file = fs::File::open(path).ok()?;
+            let info = pe::read_version_info(&mut file).ok().flatten()?;
+            info.product_version.or(info.file_version)
+        }
+        "msi" => {
+            let mut data = Vec::new();
+            fs::File::open(path).ok()?.read_to_end(&mut data).ok()?;
+            msi::read_msi_properties(&data)
+                .ok()?
+                .remove("ProductVersion")
+        }
+        _ => None,
+    };
+    version.filter(|v| !v.trim().is_empty())
+}
+
+/// Short SHA of the commit checked out in the repository containing
+/// `folder`, read from the `.git` directory without running git.
+pub fn git_short_sha(folder: &Path) -> Option<String> {
+    let folder = fs::canonicalize(folder).ok()?;
+    let repository = folder.ancestors().find_map(git_dir)?;
+    let head = fs::read_to_string(repository.join("HEAD")).ok()?;
+    let head = head.trim();
+    let sha = match head.strip_prefix("ref:") {
+        Some(reference) => resolve_ref(&repository, reference.trim())?,
+        None => head.to_string(),
+    };
+    if sha.len() < SHORT_SHA_LEN || !sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(sha[..SHORT_SHA_LEN].to_ascii_lowercase())
+}
+
+/// The git directory of a repository rooted at `dir`: `.git` itself, or
+/// the directory a `.git` file points to (worktrees and submodules).
+fn git_dir(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let link = fs::read_to_string(&dot_git).ok()?;
+    let target = link.trim().strip_prefix("gitdir:")?.trim();
+    Some(dir.join(target))
+}
+
+/// Look up `reference` as a loose ref or in `packed-refs`, in the git
+/// directory and then in the shared one of a worktree.
+fn resolve_ref(git_dir: &Path, reference: &str) -> Option<String> {
+    let mut dirs = vec![git_dir.to_path_buf()];
+    if let Ok(common) = fs::read_to_string(git_dir.join("commondir")) {
+        dirs.push(git_dir.join(common.trim()));
+    }
+    dirs.iter().find_map(|dir| {
+        if let Ok(sha) = fs::read_to_string(dir.join(reference)) {
+            return Some(sha.trim().to_string());
+        }
+        let packed = fs::read_to_string(dir.join("packed-refs")).ok()?;
+        packed.lines().find_map(|line| {
+            let (sha, name) = line.split_once(' ')?;
+            (name.trim() == reference).then(|| sha.to_string())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn values(name: &str) -> Option<String> {
+        match name {
+            SETUP_STEM => Some("setup".to_string()),
+            VERSION => Some("1.2/3".to_string()),
+            DATE => Some("2026-10-16".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_name_template() {
+        let template = NameTemplate::new("{setup_stem}-{version}-{date}").unwrap();
+        assert_eq!(template.expand(values), "setup-1.2_3-2026-10-16");
+        assert_eq!(
+            template.variables().collect::<Vec<_>>(),
+            vec![SETUP_STEM, VERSION, DATE]
+        );
+
+        // Missing values take the separator before them along
+        let template = NameTemplate::new("{setup_stem}-{git_sha}_x64").unwrap();
+        assert_eq!(template.expand(values), "setup_x64");
+        let template = NameTemplate::new("{git_sha}-{setup_stem}").unwrap();
+        assert_eq!(template.expand(values), "setup");
+        assert_eq!(
+            NameTemplate::new("{git_sha}").unwrap().expand(values),
+            "package"
+        );
+    }
+
+    #[test]
+    fn test_name_template_errors() {
+        assert!(NameTemplate::new("{setup_stem").is_err());
+        assert!(NameTemplate::new("setup}").is_err());
+        assert!(NameTemplate::new("{commit}").is_err());
+        assert!(NameTemplate::new("out/{date}").is_err());
+        assert!(NameTemplate::new("  ").is_err());
+
+        let template: NameTemplate = "{identifier}-{version}".parse().unwrap();
+        assert!(template.check_variables(&[IDENTIFIER, VERSION]).is_ok());
+        assert!(template.check_variables(&[SETUP_STEM, VERSION]).is_err());
+        assert_eq!(template.to_string(), "{identifier}-{version}");
+    }
+
+    #[test]
+    fn test_git_short_sha() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src/app");
+        fs::create_dir_all(&source).unwrap();
+        assert_eq!(git_short_sha(&source), None);
+
+        let git = temp.path().join(".git");
+        fs::create_dir_all(git.join("refs/heads")).unwrap();
+        fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            git.join("packed-refs"),
+            "# pack-refs with: peeled\n0123456789abcdef0123456789abcdef01234567 refs/heads/main\n",
+        )
+        .unwrap();
+        assert_eq!(git_short_sha(&source).as_deref(), Some("0123456"));
+
+        fs::write(
+            git.join("refs/heads/main"),
+            "fedcba9876543210fedcba9876543210fedcba98\n",
+        )
+        .unwrap();
+        assert_eq!(git_short_sha(&source).as_deref(), Some("fedcba9"));
+    }
+
+    #[test]
+    fn test_today() {
+        let date = today();
+        assert_eq!(date.len(), 10);
+        assert_eq!(&date[4..5], "-");
+    }
+}
@@ -1,10 +1,44 @@
 //! Package-related data models.
 
+use std::fmt;
 use std::path::PathBuf;
 use std::time::Duration;
 
-use crate::models::detection::DetectionMetadata;
+use zeroize::Zeroizing;
+
+#[cfg(feature = "async")]
+use tokio_util::sync::CancellationToken;
+
+use crate::json::JsonValue;
+use crate::models::artifact::ArtifactMetadata;
+use crate::models::blocklist::{BlocklistAction, BlocklistMatch};
+use crate::models::cache::{CacheConfig, CacheStats};
+use crate::models::compression::Compression;
+use crate::models::detection::{DEFAULT_TOOL_VERSION, DetectionMetadata};
+use crate::models::diff::FieldChange;
+use crate::models::encryption::{EncryptionOptions, KeySource};
 use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::{Glob, SourceFilter};
+use crate::models::guardrails::{
+    INTUNE_MAX_PACKAGE_SIZE, SizeEstimate, SourceLimits, check_source_folder,
+};
+use crate::models::incremental::{IncrementalOptions, IncrementalOutcome};
+use crate::models::naming::{self, NameTemplate};
+use crate::models::progress::ProgressStats;
+use crate::models::relationships::AppRelationships;
+use crate::models::retry::RetryPolicy;
+use crate::models::sbom::{Sbom, SbomOptions};
+use crate::models::setup::ExeVersionInfo;
+use crate::models::size::ByteSize;
+use crate::models::strictness::Strictness;
+use crate::models::suggest::suggest_files;
+use crate::models::transform::{TransformRecord, TransformRule};
+use crate::models::validation::ValidationReport;
+use crate::packager::archive::SourceArchive;
+use crate::packager::source::is_remote;
+
+/// Output folder value that writes the package to stdout instead of a file.
+pub const STDOUT_OUTPUT: &str = "-";
 
 /// Verbosity level for output.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -46,6 +80,123 @@ pub struct SourceFile {
     pub is_setup_file: bool,
 }
 
+/// A file whose allocated size on disk is well below its length.
+///
+/// Packages cannot carry holes, so the file is installed at its full size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseFile {
+    /// Path relative to source root
+    pub relative_path: PathBuf,
+    /// Apparent size in bytes
+    pub size: u64,
+    /// Bytes actually allocated on disk, where the platform reports it
+    pub allocated: Option<u64>,
+}
+
+/// Kind of source entry that cannot be packaged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKind {
+    /// Named pipe (FIFO)
+    Fifo,
+    /// Unix domain socket
+    Socket,
+    /// Block or character device node
+    Device,
+    /// Symlink pointing at one of its own ancestors
+    SymlinkLoop,
+}
+
+impl fmt::Display for SpecialKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SpecialKind::Fifo => "named pipe",
+            SpecialKind::Socket => "socket",
+            SpecialKind::Device => "device node",
+            SpecialKind::SymlinkLoop => "symlink loop",
+        })
+    }
+}
+
+/// A source entry left out of the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    /// Path relative to source root
+    pub relative_path: PathBuf,
+    /// Why it was left out
+    pub kind: SpecialKind,
+}
+
+/// Why a relative path would fail to install on a Windows client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathProblem {
+    /// Longer than the path left once the content is extracted
+    TooLong { length: usize, limit: usize },
+    /// A component is a reserved device name (`CON`, `NUL`, `COM1`, ...)
+    ReservedName(String),
+    /// A component contains a character Windows does not allow
+    InvalidCharacter(char),
+    /// A component ends in a dot or a space, which Windows strips
+    TrailingDotOrSpace,
+    /// Differs only in case from another path, which Windows treats as
+    /// the same file
+    CaseConflict(PathBuf),
+}
+
+impl PathProblem {
+    /// Short name used in reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PathProblem::TooLong { .. } => "too-long",
+            PathProblem::ReservedName(_) => "reserved-name",
+            PathProblem::InvalidCharacter(_) => "invalid-character",
+            PathProblem::TrailingDotOrSpace => "trailing-dot-or-space",
+            PathProblem::CaseConflict(_) => "case-conflict",
+        }
+    }
+}
+
+impl fmt::Display for PathProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathProblem::TooLong { length, limit } => {
+                write!(f, "{} characters, more than {}", length, limit)
+            }
+            PathProblem::ReservedName(name) => write!(f, "reserved name '{}'", name),
+            PathProblem::InvalidCharacter(c) if c.is_control() => {
+                write!(f, "control character U+{:04X}", *c as u32)
+            }
+            PathProblem::InvalidCharacter(c) => write!(f, "invalid character '{}'", c),
+            PathProblem::TrailingDotOrSpace => f.write_str("name ends in a dot or space"),
+            PathProblem::CaseConflict(other) => {
+                write!(f, "same name as '{}' ignoring case", other.display())
+            }
+        }
+    }
+}
+
+/// A source path that would break installation on a Windows client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathIssue {
+    /// Path relative to source root
+    pub relative_path: PathBuf,
+    /// What is wrong with it
+    pub problem: PathProblem,
+}
+
+impl PathIssue {
+    /// The issue as a JSON report entry.
+    pub fn to_json_value(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            (
+                "path".to_string(),
+                self.relative_path.to_string_lossy().into_owned().into(),
+            ),
+            ("problem".to_string(), self.problem.name().into()),
+            ("detail".to_string(), self.problem.to_string().into()),
+        ])
+    }
+}
+
 /// The collection of files to be packaged.
 #[derive(Debug, Clone)]
 pub struct SourcePackage {
@@ -55,8 +206,16 @@ pub struct SourcePackage {
     pub setup_file: PathBuf,
     /// All files to include (relative paths)
     pub files: Vec<SourceFile>,
+    /// All subdirectories, including empty ones (relative paths)
+    pub directories: Vec<PathBuf>,
     /// Total uncompressed size in bytes
     pub total_size: u64,
+    /// Files that are sparse on disk
+    pub sparse_files: Vec<SparseFile>,
+    /// Special entries left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Paths that would not install on Windows
+    pub path_issues: Vec<PathIssue>,
 }
 
 impl SourcePackage {
@@ -66,7 +225,11 @@ impl SourcePackage {
             root,
             setup_file,
             files: Vec::new(),
+            directories: Vec::new(),
             total_size: 0,
+            sparse_files: Vec::new(),
+            skipped_entries: Vec::new(),
+            path_issues: Vec::new(),
         }
     }
 
@@ -80,16 +243,48 @@ impl SourcePackage {
         self.total_size += size;
     }
 
+    /// Add a directory to the package.
+    pub fn add_directory(&mut self, relative_path: PathBuf) {
+        self.directories.push(relative_path);
+    }
+
     /// Get the number of files in the package.
     pub fn file_count(&self) -> usize {
         self.files.len()
     }
+
+    /// Get the number of directories in the package.
+    pub fn dir_count(&self) -> usize {
+        self.directories.len()
+    }
+}
+
+/// Where a reproducible package takes its encryption keys from.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Reproducible {
+    /// Derive the keys from the SHA-256 of the packaged content
+    ContentDigest,
+    /// Derive the keys from a caller-supplied seed, wiped from memory when
+    /// dropped
+    Seed(Zeroizing<String>),
+}
+
+// The seed is kept out of debug output
+impl fmt::Debug for Reproducible {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reproducible::ContentDigest => f.write_str("ContentDigest"),
+            Reproducible::Seed(_) => f.write_str("Seed(***)"),
+        }
+    }
 }
 
 /// Request to create an IntuneWin package.
 #[derive(Debug, Clone)]
 pub struct PackageRequest {
-    /// Path to the source folder containing files to package
+    /// Path to the source folder containing files to package, to an
+    /// archive of them (see [`SourceArchive`]), or the URL of an archive
+    /// (see [`packager::source`](crate::packager::source))
     pub source_folder: PathBuf,
     /// Name of the setup file within the source folder
     pub setup_file: String,
@@ -97,36 +292,464 @@ pub struct PackageRequest {
     pub output_folder: PathBuf,
     /// Optional custom output filename (without extension)
     pub output_name: Option<String>,
+    /// Template the output filename is built from, e.g.
+    /// `{setup_stem}-{version}-{date}`
+    pub name_template: Option<NameTemplate>,
     /// Verbosity level for output
     pub verbosity: Verbosity,
+    /// Optional known-bad hash list (CSV or JSON) to check content against
+    pub blocklist: Option<PathBuf>,
+    /// What to do when a blocklisted file is found
+    pub blocklist_action: BlocklistAction,
+    /// Free-form key/value tags recorded alongside Detection.xml
+    pub content_tags: Vec<(String, String)>,
+    /// Sidecar JSON describing the app's dependencies and supersedence,
+    /// bundled under `Metadata/Extra/`
+    pub relationships: Option<PathBuf>,
+    /// Allow packaging a filesystem root or home directory
+    pub allow_dangerous_source: bool,
+    /// File count and size limits that need confirmation to exceed
+    pub source_limits: SourceLimits,
+    /// Projected package size above which the `package-size` check fires
+    pub size_limit: ByteSize,
+    /// Package even when the source exceeds the limits
+    pub force: bool,
+    /// Re-open and validate the output after creation, within this time box
+    pub post_validate: Option<Duration>,
+    /// Which warnings fail the run
+    pub strictness: Strictness,
+    /// Rules rewriting matching files as they are packaged
+    pub transforms: Vec<TransformRule>,
+    /// Patterns selecting which source files are packaged
+    pub filter: SourceFilter,
+    /// Build byte-identical packages from identical inputs
+    pub reproducible: Option<Reproducible>,
+    /// Random keys (the default) or keys supplied by the caller
+    pub encryption: EncryptionOptions,
+    /// Write the setup file's version information next to the package
+    pub setup_manifest: bool,
+    /// Software bill of materials to produce for the content
+    pub sbom: Option<SbomOptions>,
+    /// Reuse compressed files from earlier runs
+    pub cache: Option<CacheConfig>,
+    /// Reuse the previous package's content when the source is unchanged
+    pub incremental: Option<IncrementalOptions>,
+    /// How content files are compressed in the inner ZIP
+    pub compression: Compression,
+    /// Application name in Detection.xml (the setup file when unset)
+    pub display_name: Option<String>,
+    /// ToolVersion reported in Detection.xml ([`DEFAULT_TOOL_VERSION`]
+    /// when unset)
+    pub tool_version: Option<String>,
+    /// Write the Windows path check results (JSON) to this file
+    pub path_report: Option<PathBuf>,
+    /// Retries of source reads and output writes that fail transiently
+    pub io_retry: RetryPolicy,
+    /// Stops packaging at the next file once cancelled
+    #[cfg(feature = "async")]
+    pub cancellation: Option<CancellationToken>,
 }
 
 impl PackageRequest {
     /// Create a new package request.
-    pub fn new(source_folder: PathBuf, setup_file: String, output_folder: PathBuf) -> Self {
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        setup_file: impl Into<String>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
         Self {
-            source_folder,
-            setup_file,
-            output_folder,
+            source_folder: source_folder.into(),
+            setup_file: setup_file.into(),
+            output_folder: output_folder.into(),
             output_name: None,
+            name_template: None,
             verbosity: Verbosity::default(),
+            blocklist: None,
+            blocklist_action: BlocklistAction::default(),
+            content_tags: Vec::new(),
+            relationships: None,
+            allow_dangerous_source: false,
+            source_limits: SourceLimits::default(),
+            size_limit: INTUNE_MAX_PACKAGE_SIZE,
+            force: false,
+            post_validate: None,
+            strictness: Strictness::default(),
+            transforms: Vec::new(),
+            filter: SourceFilter::default(),
+            reproducible: None,
+            encryption: EncryptionOptions::default(),
+            setup_manifest: false,
+            sbom: None,
+            cache: None,
+            incremental: None,
+            compression: Compression::default(),
+            display_name: None,
+            tool_version: None,
+            path_report: None,
+            io_retry: RetryPolicy::none(),
+            #[cfg(feature = "async")]
+            cancellation: None,
         }
     }
 
+    /// Start building a request whose required fields are set incrementally.
+    pub fn builder() -> PackageRequestBuilder {
+        PackageRequestBuilder::default()
+    }
+
     /// Set custom output filename.
-    pub fn with_output_name(mut self, name: String) -> Self {
-        self.output_name = Some(name);
+    pub fn with_output_name(mut self, name: impl Into<String>) -> Self {
+        self.set_output_name(name);
         self
     }
 
     /// Set verbosity level.
     pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.set_verbosity(verbosity);
+        self
+    }
+
+    /// Set a known-bad hash list and the action to take on a match.
+    pub fn with_blocklist(mut self, path: impl Into<PathBuf>, action: BlocklistAction) -> Self {
+        self.set_blocklist(path, action);
+        self
+    }
+
+    /// Add a content tag (e.g., build ID, git SHA, ticket number).
+    pub fn with_content_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.add_content_tag(key, value);
+        self
+    }
+
+    /// Bundle a file describing the app's dependencies and supersedence
+    /// (see [`AppRelationships`]).
+    pub fn with_relationships(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_relationships(path);
+        self
+    }
+
+    /// Allow packaging a filesystem root or home directory.
+    pub fn with_allow_dangerous_source(mut self, allow: bool) -> Self {
+        self.set_allow_dangerous_source(allow);
+        self
+    }
+
+    /// Set the file count and size limits that need confirmation to exceed.
+    pub fn with_source_limits(mut self, limits: SourceLimits) -> Self {
+        self.set_source_limits(limits);
+        self
+    }
+
+    /// Set the projected package size to warn (or, under strict mode, fail)
+    /// above ([`INTUNE_MAX_PACKAGE_SIZE`] by default).
+    pub fn with_size_limit(mut self, limit: ByteSize) -> Self {
+        self.set_size_limit(limit);
+        self
+    }
+
+    /// Package even when the source exceeds the limits.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.set_force(force);
+        self
+    }
+
+    /// Validate the output after creation, failing if it takes longer than `timeout`.
+    pub fn with_post_validate(mut self, timeout: Duration) -> Self {
+        self.set_post_validate(Some(timeout));
+        self
+    }
+
+    /// Set which warnings fail the run.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.set_strictness(strictness);
+        self
+    }
+
+    /// Rewrite files matching the rule's glob as they are packaged.
+    pub fn with_transform(mut self, rule: TransformRule) -> Self {
+        self.add_transform(rule);
+        self
+    }
+
+    /// Derive keys and timestamps deterministically so identical inputs
+    /// give a byte-identical package.
+    pub fn with_reproducible(mut self, reproducible: Reproducible) -> Self {
+        self.set_reproducible(Some(reproducible));
+        self
+    }
+
+    /// Encrypt with caller-supplied keys instead of random ones.
+    pub fn with_encryption(mut self, encryption: EncryptionOptions) -> Self {
+        self.set_encryption(encryption);
+        self
+    }
+
+    /// Write the version information of an `.exe` setup file to
+    /// `<package>.setup.json`.
+    pub fn with_setup_manifest(mut self, enabled: bool) -> Self {
+        self.set_setup_manifest(enabled);
+        self
+    }
+
+    /// Produce a software bill of materials listing the packaged files.
+    pub fn with_sbom(mut self, sbom: SbomOptions) -> Self {
+        self.set_sbom(Some(sbom));
+        self
+    }
+
+    /// Keep compressed files in a cache so unchanged files are not
+    /// compressed again on the next run.
+    pub fn with_cache(mut self, cache: CacheConfig) -> Self {
+        self.set_cache(Some(cache));
+        self
+    }
+
+    /// Record digests of each build in a state file and, when the source
+    /// has not changed since, re-encrypt the previous package's content
+    /// instead of compressing it again.
+    pub fn with_incremental(mut self, incremental: IncrementalOptions) -> Self {
+        self.set_incremental(Some(incremental));
+        self
+    }
+
+    /// Set how content files are compressed, trading speed against size.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.set_compression(compression);
+        self
+    }
+
+    /// Build the output filename from `template`.
+    pub fn with_name_template(mut self, template: NameTemplate) -> Self {
+        self.set_name_template(Some(template));
+        self
+    }
+
+    /// Set the application name recorded in Detection.xml.
+    pub fn with_display_name(mut self, name: impl Into<String>) -> Self {
+        self.set_display_name(Some(name.into()));
+        self
+    }
+
+    /// Set the ToolVersion recorded in Detection.xml.
+    pub fn with_tool_version(mut self, version: impl Into<String>) -> Self {
+        self.set_tool_version(Some(version.into()));
+        self
+    }
+
+    /// Write the Windows path check results to a JSON file.
+    pub fn with_path_report(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_path_report(Some(path.into()));
+        self
+    }
+
+    /// Retry source reads and output writes that fail transiently, e.g.
+    /// on a network share.
+    pub fn with_io_retry(mut self, retry: RetryPolicy) -> Self {
+        self.set_io_retry(retry);
+        self
+    }
+
+    /// Stop packaging with [`PackageError::Cancelled`] once `token` is
+    /// cancelled.
+    #[cfg(feature = "async")]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.set_cancellation(Some(token));
+        self
+    }
+
+    /// Only package source files matching `glob` (or in folders matching it).
+    pub fn with_include(mut self, glob: Glob) -> Self {
+        self.add_include(glob);
+        self
+    }
+
+    /// Leave out source files matching `glob` (or in folders matching it).
+    pub fn with_exclude(mut self, glob: Glob) -> Self {
+        self.add_exclude(glob);
+        self
+    }
+
+    /// Set custom output filename in place.
+    pub fn set_output_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.output_name = Some(name.into());
+        self
+    }
+
+    /// Set verbosity level in place.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> &mut Self {
         self.verbosity = verbosity;
         self
     }
 
+    /// Set a known-bad hash list and match action in place.
+    pub fn set_blocklist(
+        &mut self,
+        path: impl Into<PathBuf>,
+        action: BlocklistAction,
+    ) -> &mut Self {
+        self.blocklist = Some(path.into());
+        self.blocklist_action = action;
+        self
+    }
+
+    /// Add a content tag in place.
+    pub fn add_content_tag(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &mut Self {
+        self.content_tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set the relationships file in place.
+    pub fn set_relationships(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.relationships = Some(path.into());
+        self
+    }
+
+    /// Allow or refuse a filesystem root or home directory in place.
+    pub fn set_allow_dangerous_source(&mut self, allow: bool) -> &mut Self {
+        self.allow_dangerous_source = allow;
+        self
+    }
+
+    /// Set the source limits in place.
+    pub fn set_source_limits(&mut self, limits: SourceLimits) -> &mut Self {
+        self.source_limits = limits;
+        self
+    }
+
+    /// Set the projected package size limit in place.
+    pub fn set_size_limit(&mut self, limit: ByteSize) -> &mut Self {
+        self.size_limit = limit;
+        self
+    }
+
+    /// Set whether to package above the source limits in place.
+    pub fn set_force(&mut self, force: bool) -> &mut Self {
+        self.force = force;
+        self
+    }
+
+    /// Enable (with a time box) or disable post-creation validation in place.
+    pub fn set_post_validate(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.post_validate = timeout;
+        self
+    }
+
+    /// Set which warnings fail the run in place.
+    pub fn set_strictness(&mut self, strictness: Strictness) -> &mut Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Add a transform rule in place; rules run in the order added.
+    pub fn add_transform(&mut self, rule: TransformRule) -> &mut Self {
+        self.transforms.push(rule);
+        self
+    }
+
+    /// Set or clear reproducible mode in place.
+    pub fn set_reproducible(&mut self, reproducible: Option<Reproducible>) -> &mut Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Set where the encryption keys come from in place.
+    pub fn set_encryption(&mut self, encryption: EncryptionOptions) -> &mut Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Enable or disable the setup manifest in place.
+    pub fn set_setup_manifest(&mut self, enabled: bool) -> &mut Self {
+        self.setup_manifest = enabled;
+        self
+    }
+
+    /// Set or clear the SBOM options in place.
+    pub fn set_sbom(&mut self, sbom: Option<SbomOptions>) -> &mut Self {
+        self.sbom = sbom;
+        self
+    }
+
+    /// Set or clear the content cache in place.
+    pub fn set_cache(&mut self, cache: Option<CacheConfig>) -> &mut Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Set or clear incremental builds in place.
+    pub fn set_incremental(&mut self, incremental: Option<IncrementalOptions>) -> &mut Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Set the content compression in place.
+    pub fn set_compression(&mut self, compression: Compression) -> &mut Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set or clear the output filename template in place.
+    pub fn set_name_template(&mut self, template: Option<NameTemplate>) -> &mut Self {
+        self.name_template = template;
+        self
+    }
+
+    /// Set or clear the Detection.xml application name in place.
+    pub fn set_display_name(&mut self, name: Option<String>) -> &mut Self {
+        self.display_name = name;
+        self
+    }
+
+    /// Set or clear the Detection.xml ToolVersion in place.
+    pub fn set_tool_version(&mut self, version: Option<String>) -> &mut Self {
+        self.tool_version = version;
+        self
+    }
+
+    /// Set or clear the path check report file in place.
+    pub fn set_path_report(&mut self, path: Option<PathBuf>) -> &mut Self {
+        self.path_report = path;
+        self
+    }
+
+    /// Set the I/O retry policy in place.
+    pub fn set_io_retry(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.io_retry = retry;
+        self
+    }
+
+    /// Set or clear the cancellation token in place.
+    #[cfg(feature = "async")]
+    pub fn set_cancellation(&mut self, token: Option<CancellationToken>) -> &mut Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Add an include pattern in place.
+    pub fn add_include(&mut self, glob: Glob) -> &mut Self {
+        self.filter.include.push(glob);
+        self
+    }
+
+    /// Add an exclude pattern in place.
+    pub fn add_exclude(&mut self, glob: Glob) -> &mut Self {
+        self.filter.exclude.push(glob);
+        self
+    }
+
     /// Validate the package request.
     pub fn validate(&self) -> PackageResult<()> {
+        self.validate_settings()?;
+
+        // A remote source is checked once it is downloaded
+        if is_remote(&self.source_folder) {
+            return Ok(());
+        }
+
         // Check source folder exists
         if !self.source_folder.exists() {
             return Err(PackageError::SourceFolderNotFound {
@@ -134,43 +757,248 @@ impl PackageRequest {
             });
         }
 
+        // An archive's entries, the setup file among them, are checked as
+        // it is read
+        if SourceArchive::detect(&self.source_folder).is_some() {
+            return Ok(());
+        }
+
         if !self.source_folder.is_dir() {
             return Err(PackageError::SourceFolderNotFound {
                 path: self.source_folder.clone(),
             });
         }
 
+        // Refuse roots and home before walking millions of files
+        check_source_folder(&self.source_folder, self.allow_dangerous_source)?;
+
         // Check setup file exists in source folder
         let setup_path = self.source_folder.join(&self.setup_file);
         if !setup_path.exists() {
             return Err(PackageError::SetupFileNotFound {
                 file: self.setup_file.clone(),
                 folder: self.source_folder.clone(),
+                suggestions: suggest_files(&self.source_folder, &self.setup_file),
             });
         }
 
         Ok(())
     }
 
-    /// Get the output file path.
-    pub fn output_path(&self) -> PathBuf {
-        let base_name = self
-            .output_name
+    /// Validate the settings that do not depend on where content comes from.
+    pub fn validate_settings(&self) -> PackageResult<()> {
+        self.compression.validate()?;
+
+        // Check content tag keys are usable and unique
+        for (i, (key, _)) in self.content_tags.iter().enumerate() {
+            if key.trim().is_empty() {
+                return Err(PackageError::InvalidArgument {
+                    reason: "Content tag key cannot be empty".to_string(),
+                });
+            }
+            if self.content_tags[..i].iter().any(|(k, _)| k == key) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!("Duplicate content tag key '{}'", key),
+                });
+            }
+        }
+
+        if let Some(template) = &self.name_template {
+            if self.output_name.is_some() {
+                return Err(PackageError::InvalidArgument {
+                    reason: "An output name and a name template cannot be combined".to_string(),
+                });
+            }
+            template
+                .check_variables(INTUNE_NAME_VARIABLES)
+                .map_err(|reason| PackageError::InvalidArgument { reason })?;
+        }
+
+        if self
+            .display_name
             .as_ref()
-            .map(|n| n.trim_end_matches(".intunewin").to_string())
-            .unwrap_or_else(|| {
-                // Use setup file name without extension
-                PathBuf::from(&self.setup_file)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("package")
-                    .to_string()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err(PackageError::InvalidArgument {
+                reason: "Display name cannot be empty".to_string(),
             });
+        }
+        if let Some(version) = &self.tool_version {
+            if !is_tool_version(version) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "Invalid tool version '{}' (expected up to four numbers, e.g. {})",
+                        version, DEFAULT_TOOL_VERSION
+                    ),
+                });
+            }
+        }
+
+        // The package on stdout cannot be re-opened
+        if self.writes_to_stdout() && self.post_validate.is_some() {
+            return Err(PackageError::InvalidArgument {
+                reason: "Post-validation needs an output file, not stdout".to_string(),
+            });
+        }
+        if self.writes_to_stdout() && self.setup_manifest {
+            return Err(PackageError::InvalidArgument {
+                reason: "A setup manifest needs an output file, not stdout".to_string(),
+            });
+        }
+        if self.writes_to_stdout() && self.incremental.is_some() {
+            return Err(PackageError::InvalidArgument {
+                reason: "Incremental builds need an output file, not stdout".to_string(),
+            });
+        }
+        if let Some(sbom) = &self.sbom {
+            sbom.validate()?;
+            if self.writes_to_stdout() && sbom.sidecar {
+                return Err(PackageError::InvalidArgument {
+                    reason: "A sidecar SBOM needs an output file, not stdout".to_string(),
+                });
+            }
+        }
+
+        // Both decide the keys
+        if self.reproducible.is_some() && self.encryption.keys != KeySource::Random {
+            return Err(PackageError::InvalidArgument {
+                reason: "Reproducible packages derive their keys; provided keys cannot be used"
+                    .to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether the package is written to stdout (output folder `-`).
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output_folder.as_os_str() == STDOUT_OUTPUT
+    }
+
+    /// Get the output file path (`-` when writing to stdout).
+    pub fn output_path(&self) -> PathBuf {
+        if self.writes_to_stdout() {
+            return PathBuf::from(STDOUT_OUTPUT);
+        }
+
+        let setup_stem = || {
+            PathBuf::from(&self.setup_file)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("package")
+                .to_string()
+        };
+        let base_name = match (&self.output_name, &self.name_template) {
+            (Some(name), _) => name.trim_end_matches(".intunewin").to_string(),
+            (None, Some(template)) => template.expand(|variable| match variable {
+                naming::SETUP_STEM => Some(setup_stem()),
+                naming::VERSION => {
+                    naming::setup_version(&self.source_folder.join(&self.setup_file))
+                }
+                naming::DATE => Some(naming::today()),
+                naming::GIT_SHA => naming::git_short_sha(&self.source_folder),
+                _ => None,
+            }),
+            // Use setup file name without extension
+            (None, None) => setup_stem(),
+        };
 
         self.output_folder.join(format!("{}.intunewin", base_name))
     }
 }
 
+/// Variables an Intune name template may use.
+const INTUNE_NAME_VARIABLES: &[&str] = &[
+    naming::SETUP_STEM,
+    naming::VERSION,
+    naming::DATE,
+    naming::GIT_SHA,
+];
+
+/// Whether `version` is a version like `1.8.6.0`: one to four numbers
+/// separated by dots.
+fn is_tool_version(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    parts.len() <= 4
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Incremental builder for [`PackageRequest`].
+///
+/// Required fields may be set in any order; [`PackageRequestBuilder::build`]
+/// reports missing ones and runs [`PackageRequest::validate`] up front.
+#[derive(Debug, Clone, Default)]
+pub struct PackageRequestBuilder {
+    source_folder: Option<PathBuf>,
+    setup_file: Option<String>,
+    output_folder: Option<PathBuf>,
+    options: Option<PackageRequest>,
+}
+
+impl PackageRequestBuilder {
+    /// Set the source folder.
+    pub fn source_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.source_folder = Some(path.into());
+        self
+    }
+
+    /// Set the setup file name within the source folder.
+    pub fn setup_file(&mut self, name: impl Into<String>) -> &mut Self {
+        self.setup_file = Some(name.into());
+        self
+    }
+
+    /// Set the output folder.
+    pub fn output_folder(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_folder = Some(path.into());
+        self
+    }
+
+    /// Access the optional settings (output name, verbosity, tags, ...).
+    pub fn options(&mut self) -> &mut PackageRequest {
+        self.options.get_or_insert_with(empty_request)
+    }
+
+    /// Build and validate the request.
+    pub fn build(&self) -> PackageResult<PackageRequest> {
+        let mut request = self.options.clone().unwrap_or_else(empty_request);
+        request.source_folder = required(&self.source_folder, "source folder")?;
+        request.setup_file = required(&self.setup_file, "setup file")?;
+        request.output_folder = required(&self.output_folder, "output folder")?;
+
+        request.validate()?;
+        Ok(request)
+    }
+
+    /// Build a request for content that does not come from the source
+    /// folder, such as files held in memory.
+    ///
+    /// Only the setup file is required; the source and output folders
+    /// default to empty and only [`PackageRequest::validate_settings`] runs.
+    pub fn build_for_content(&self) -> PackageResult<PackageRequest> {
+        let mut request = self.options.clone().unwrap_or_else(empty_request);
+        request.source_folder = self.source_folder.clone().unwrap_or_default();
+        request.setup_file = required(&self.setup_file, "setup file")?;
+        request.output_folder = self.output_folder.clone().unwrap_or_default();
+
+        request.validate_settings()?;
+        Ok(request)
+    }
+}
+
+fn empty_request() -> PackageRequest {
+    PackageRequest::new(PathBuf::new(), String::new(), PathBuf::new())
+}
+
+/// Unwrap a required builder field or report it as missing.
+pub(crate) fn required<T: Clone>(value: &Option<T>, name: &str) -> PackageResult<T> {
+    value.clone().ok_or_else(|| PackageError::InvalidArgument {
+        reason: format!("Missing required {}", name),
+    })
+}
+
 /// The final output package.
 #[derive(Debug, Clone)]
 pub struct IntuneWinPackage {
@@ -178,10 +1006,48 @@ pub struct IntuneWinPackage {
     pub output_path: PathBuf,
     /// Detection metadata
     pub metadata: DetectionMetadata,
+    /// Version record embedded in the package
+    pub artifact: ArtifactMetadata,
     /// Size of the final .intunewin file
-    pub package_size: u64,
+    pub package_size: ByteSize,
     /// Time taken to create the package
     pub creation_time: Duration,
+    /// Content files that matched the blocklist (warn mode only)
+    pub blocklist_matches: Vec<BlocklistMatch>,
+    /// Content tags recorded in the package
+    pub content_tags: Vec<(String, String)>,
+    /// App relationships bundled in the package
+    pub relationships: Option<AppRelationships>,
+    /// Number of files in the package
+    pub file_count: usize,
+    /// Number of directories in the package
+    pub dir_count: usize,
+    /// Post-creation validation results, when requested
+    pub validation: Option<ValidationReport>,
+    /// Files rewritten by transform rules
+    pub transforms: Vec<TransformRecord>,
+    /// Content files that are sparse on disk (expanded in the package)
+    pub sparse_files: Vec<SparseFile>,
+    /// Special entries in the source that were left out
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Content paths that would not install on Windows (warn mode only)
+    pub path_issues: Vec<PathIssue>,
+    /// Projected package size, taken before compression, and its limit
+    pub size_estimate: SizeEstimate,
+    /// Counters collected while packaging (populated at every verbosity)
+    pub stats: ProgressStats,
+    /// Version information of an `.exe` setup file
+    pub setup_info: Option<ExeVersionInfo>,
+    /// Path of the setup manifest, when one was written
+    pub setup_manifest: Option<PathBuf>,
+    /// Software bill of materials, when one was requested
+    pub sbom: Option<Sbom>,
+    /// Path of the sidecar SBOM, when one was written
+    pub sbom_path: Option<PathBuf>,
+    /// Content cache use, when a cache was configured
+    pub cache: Option<CacheStats>,
+    /// What an incremental build did, when one was requested
+    pub incremental: Option<IncrementalOutcome>,
 }
 
 /// Request to unpack an IntuneWin package.
@@ -193,6 +1059,12 @@ pub struct UnpackRequest {
     pub output_folder: PathBuf,
     /// Verbosity level for output
     pub verbosity: Verbosity,
+    /// Write the content into a password-protected ZIP in the output
+    /// folder instead of extracting loose files
+    pub zip_password: Option<ArchivePassword>,
+    /// Stops extraction at the next file once cancelled
+    #[cfg(feature = "async")]
+    pub cancellation: Option<CancellationToken>,
 }
 
 impl UnpackRequest {
@@ -202,6 +1074,9 @@ impl UnpackRequest {
             input_file,
             output_folder,
             verbosity: Verbosity::default(),
+            zip_password: None,
+            #[cfg(feature = "async")]
+            cancellation: None,
         }
     }
 
@@ -211,6 +1086,34 @@ impl UnpackRequest {
         self
     }
 
+    /// Write the decrypted content into an AES-encrypted ZIP protected by
+    /// `password` (see [`UnpackRequest::archive_path`]), so no plaintext
+    /// file is written.
+    pub fn with_zip_password(mut self, password: impl Into<String>) -> Self {
+        self.zip_password = Some(ArchivePassword(Zeroizing::new(password.into())));
+        self
+    }
+
+    /// Stop extraction with [`PackageError::Cancelled`] once `token` is
+    /// cancelled; files already extracted are left in place.
+    #[cfg(feature = "async")]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Path of the protected archive: the package name with a `.zip`
+    /// extension, in the output folder. `None` without a ZIP password.
+    pub fn archive_path(&self) -> Option<PathBuf> {
+        self.zip_password.as_ref()?;
+        let stem = self
+            .input_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "content".to_string());
+        Some(self.output_folder.join(format!("{}.zip", stem)))
+    }
+
     /// Validate the unpack request.
     pub fn validate(&self) -> PackageResult<()> {
         // Check input file exists
@@ -228,10 +1131,44 @@ impl UnpackRequest {
             });
         }
 
+        if self
+            .zip_password
+            .as_ref()
+            .is_some_and(|password| password.0.is_empty())
+        {
+            return Err(PackageError::InvalidArgument {
+                reason: "The ZIP password must not be empty".to_string(),
+            });
+        }
+
+        if let Some(archive) = self.archive_path() {
+            if archive.exists() {
+                return Err(PackageError::OutputFileExists { path: archive });
+            }
+        }
+
         Ok(())
     }
 }
 
+/// Password of a protected extraction archive, left out of `Debug` output
+/// and wiped from memory when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ArchivePassword(Zeroizing<String>);
+
+impl ArchivePassword {
+    /// The password itself.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ArchivePassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ArchivePassword(***)")
+    }
+}
+
 /// Result of unpacking an IntuneWin package.
 #[derive(Debug, Clone)]
 pub struct UnpackResult {
@@ -239,18 +1176,172 @@ pub struct UnpackResult {
     pub output_folder: PathBuf,
     /// Number of files extracted
     pub file_count: usize,
+    /// Number of directories restored
+    pub dir_count: usize,
     /// Total size of extracted files in bytes
-    pub total_size: u64,
+    pub total_size: ByteSize,
     /// Time taken to unpack
     pub unpack_time: Duration,
     /// Original setup file name
     pub setup_file: String,
+    /// Content tags recorded in the package, if any
+    pub content_tags: Vec<(String, String)>,
+    /// Password-protected ZIP holding the content, when one was written
+    /// instead of loose files
+    pub archive: Option<PathBuf>,
+    /// Counters collected while extracting (populated at every verbosity)
+    pub stats: ProgressStats,
+}
+
+/// Request to change the Detection.xml of an existing `.intunewin`.
+///
+/// The content is kept as is and encrypted again with fresh keys.
+#[derive(Debug, Clone)]
+pub struct RepackRequest {
+    /// Package to modify
+    pub input: PathBuf,
+    /// Path of the rewritten package
+    pub output: PathBuf,
+    /// New setup file; must be at the root of the package content
+    pub setup_file: Option<String>,
+    /// New application name (follows the setup file when it was the same)
+    pub name: Option<String>,
+    /// Verbosity level for output
+    pub verbosity: Verbosity,
+}
+
+impl RepackRequest {
+    /// Create a repack request that rewrites `input` to `output` with new keys.
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            input: input.into(),
+            output: output.into(),
+            setup_file: None,
+            name: None,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Set the setup file.
+    pub fn with_setup_file(mut self, setup_file: impl Into<String>) -> Self {
+        self.setup_file = Some(setup_file.into());
+        self
+    }
+
+    /// Set the application name.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.input.is_file() {
+            return Err(PackageError::InvalidIntunewinFile {
+                path: self.input.clone(),
+                reason: "File does not exist".to_string(),
+            });
+        }
+        if self.output == self.input {
+            return Err(PackageError::InvalidArgument {
+                reason: "The output must be a new file, not the input package".to_string(),
+            });
+        }
+        for (field, value) in [("Setup file", &self.setup_file), ("Name", &self.name)] {
+            if value.as_ref().is_some_and(|v| v.trim().is_empty()) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!("{} cannot be empty", field),
+                });
+            }
+        }
+        if let Some(setup_file) = &self.setup_file {
+            if setup_file.contains(['/', '\\']) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "Setup file '{}' must be at the root of the package",
+                        setup_file
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of repacking an IntuneWin package.
+#[derive(Debug, Clone)]
+pub struct RepackResult {
+    /// Path of the rewritten package
+    pub output_path: PathBuf,
+    /// Detection metadata of the rewritten package
+    pub metadata: DetectionMetadata,
+    /// Detection.xml fields that were changed
+    pub changes: Vec<FieldChange>,
+    /// Size of the rewritten package
+    pub package_size: ByteSize,
+    /// Time to repack
+    pub repack_time: Duration,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_package_request_builder() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp.path().join("setup.exe"), "x").unwrap();
+
+        let mut builder = PackageRequest::builder();
+        builder.source_folder(temp.path()).output_folder("out");
+        assert!(matches!(
+            builder.build(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        builder.setup_file("setup.exe");
+        builder
+            .options()
+            .set_output_name("app")
+            .add_content_tag("build", "7");
+        let request = builder.build().unwrap();
+
+        assert_eq!(request.setup_file, "setup.exe");
+        assert_eq!(request.output_name.as_deref(), Some("app"));
+        assert_eq!(request.content_tags.len(), 1);
+
+        builder.setup_file("missing.exe");
+        assert!(matches!(
+            builder.build(),
+            Err(PackageError::SetupFileNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_package_request_builder_for_content() {
+        let mut builder = PackageRequest::builder();
+        assert!(matches!(
+            builder.build_for_content(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        // No source folder needs to exist
+        builder.setup_file("setup.exe");
+        let request = builder.build_for_content().unwrap();
+        assert_eq!(request.source_folder, PathBuf::new());
+        assert_eq!(request.output_path(), PathBuf::from("setup.intunewin"));
+
+        builder.options().add_content_tag("", "x");
+        assert!(builder.build_for_content().is_err());
+    }
+
     #[test]
     fn test_verbosity_suppress_prompts() {
         assert!(!Verbosity::Normal.suppress_prompts());
@@ -307,13 +1398,117 @@ mod tests {
         assert_eq!(req.output_path(), PathBuf::from("/output/MyApp.intunewin"));
     }
 
+    #[test]
+    fn test_output_path_stdout() {
+        let req = PackageRequest::new("/source", "setup.exe", STDOUT_OUTPUT);
+        assert!(req.writes_to_stdout());
+        assert_eq!(req.output_path(), PathBuf::from("-"));
+
+        let req = req.with_post_validate(Duration::from_secs(1));
+        assert!(matches!(
+            req.validate_settings(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_content_tags() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp.path().join("setup.exe"), "x").unwrap();
+
+        let req = PackageRequest::new(
+            temp.path().to_path_buf(),
+            "setup.exe".to_string(),
+            PathBuf::from("/output"),
+        )
+        .with_content_tag("build".to_string(), "42".to_string());
+        assert!(req.validate().is_ok());
+
+        let dup = req
+            .clone()
+            .with_content_tag("build".to_string(), "43".to_string());
+        assert!(matches!(
+            dup.validate(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        let empty = req.with_content_tag(" ".to_string(), "x".to_string());
+        assert!(matches!(
+            empty.validate(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_detection_overrides() {
+        let req = PackageRequest::new("/source", "setup.exe", "/output");
+        assert!(
+            req.clone()
+                .with_display_name("ACME Tool")
+                .with_tool_version("1.8.4.0")
+                .validate_settings()
+                .is_ok()
+        );
+        assert!(
+            req.clone()
+                .with_tool_version("2")
+                .validate_settings()
+                .is_ok()
+        );
+
+        for version in ["", "1.8.4.0.1", "1..4", "v1.8", "1.8.4-beta"] {
+            assert!(
+                matches!(
+                    req.clone().with_tool_version(version).validate_settings(),
+                    Err(PackageError::InvalidArgument { .. })
+                ),
+                "{:?} should be rejected",
+                version
+            );
+        }
+        assert!(matches!(
+            req.with_display_name(" ").validate_settings(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_output_path_from_name_template() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp.path().join("setup.exe"), "MZ").unwrap();
+        let template = NameTemplate::new("{setup_stem}-{version}-{date}").unwrap();
+        let req = PackageRequest::new(temp.path(), "setup.exe", "/output")
+            .with_name_template(template.clone());
+
+        // Without version information or a repository only the stem and
+        // date remain
+        assert_eq!(
+            req.output_path(),
+            PathBuf::from("/output").join(format!("setup-{}.intunewin", naming::today()))
+        );
+        assert!(req.validate_settings().is_ok());
+        assert!(matches!(
+            req.clone().with_output_name("app").validate_settings(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+
+        let macos_only = NameTemplate::new("{identifier}").unwrap();
+        assert!(matches!(
+            req.with_name_template(macos_only).validate_settings(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
     #[test]
     fn test_source_package_add_file() {
         let mut pkg = SourcePackage::new(PathBuf::from("/source"), PathBuf::from("setup.exe"));
         pkg.add_file(PathBuf::from("setup.exe"), 1024, true);
         pkg.add_file(PathBuf::from("data.dll"), 2048, false);
 
+        pkg.add_directory(PathBuf::from("empty"));
+
         assert_eq!(pkg.file_count(), 2);
+        assert_eq!(pkg.dir_count(), 1);
         assert_eq!(pkg.total_size, 3072);
     }
 }
@@ -0,0 +1,76 @@
+//! Lock-free progress counters.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::models::size::ByteSize;
+
+/// Counters updated while packaging, independent of any progress display.
+///
+/// Updates are relaxed atomic adds, so they are cheap enough to run in
+/// every mode and can be read from another thread while work is running.
+#[derive(Debug, Default)]
+pub struct ProgressCounters {
+    files: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl ProgressCounters {
+    /// Record one processed file.
+    pub fn add_file(&self) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record bytes read from the source.
+    pub fn add_bytes_read(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record bytes written to the output.
+    pub fn add_bytes_written(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Read the current values.
+    pub fn snapshot(&self) -> ProgressStats {
+        ProgressStats {
+            files_processed: self.files.load(Ordering::Relaxed),
+            bytes_read: ByteSize(self.bytes_read.load(Ordering::Relaxed)),
+            bytes_written: ByteSize(self.bytes_written.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Counter values captured at the end of an operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressStats {
+    /// Files processed (packaged or extracted)
+    pub files_processed: u64,
+    /// Bytes read from the input
+    pub bytes_read: ByteSize,
+    /// Bytes written to the output
+    pub bytes_written: ByteSize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_snapshot() {
+        let counters = ProgressCounters::default();
+        counters.add_file();
+        counters.add_file();
+        counters.add_bytes_read(100);
+        counters.add_bytes_written(40);
+
+        assert_eq!(
+            counters.snapshot(),
+            ProgressStats {
+                files_processed: 2,
+                bytes_read: ByteSize(100),
+                bytes_written: ByteSize(40),
+            }
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Project bundles: everything needed to rebuild a package except the
+//! binaries.
+//!
+//! A project file records the build settings, the configuration, the
+//! macOS scripts and a manifest (path, size, SHA-256) of the content. The
+//! content itself is archived separately; on import it is checked against
+//! the manifest before the package is rebuilt.
+
+use std::path::PathBuf;
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+
+use crate::json::{self, JsonValue};
+use crate::models::capture::CaptureEntry;
+use crate::models::config::Config;
+use crate::models::package::{IntuneWinPackage, Verbosity};
+
+/// Value of the `format` key identifying a project file.
+pub const PROJECT_FORMAT: &str = "iamawrapper-project";
+
+/// Project file format version.
+pub const PROJECT_VERSION: u64 = 1;
+
+/// What a project builds, with the settings given at export time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectBuild {
+    /// An `.intunewin` package
+    Intune {
+        /// Setup file name within the content
+        setup_file: String,
+        /// Custom output filename (without extension)
+        output_name: Option<String>,
+        /// Content tags recorded in the package metadata
+        content_tags: Vec<(String, String)>,
+    },
+    /// A macOS flat package
+    Macos {
+        /// Package identifier
+        identifier: String,
+        /// Package version
+        version: String,
+        /// Installation target path on macOS
+        install_location: String,
+        /// Custom output filename (without extension)
+        output_name: Option<String>,
+        /// Keep setuid/setgid/sticky bits
+        allow_special_modes: bool,
+    },
+}
+
+/// A script bundled into the project (macOS preinstall/postinstall).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectScript {
+    /// Path relative to the scripts folder
+    pub path: PathBuf,
+    /// Script contents
+    pub data: Vec<u8>,
+}
+
+/// A versioned, self-contained description of a package build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// Version of the tool that exported the project
+    pub tool_version: String,
+    /// Seconds since the Unix epoch when the project was exported
+    pub created: u64,
+    /// Build target and settings
+    pub build: ProjectBuild,
+    /// Configuration (strict mode and check overrides)
+    pub config: Config,
+    /// Scripts, stored in full
+    pub scripts: Vec<ProjectScript>,
+    /// Manifest of the content, sorted by path
+    pub files: Vec<CaptureEntry>,
+}
+
+impl Project {
+    /// Serialize the project as JSON.
+    pub fn to_json(&self) -> String {
+        let build = match &self.build {
+            ProjectBuild::Intune {
+                setup_file,
+                output_name,
+                content_tags,
+            } => {
+                let tags = content_tags
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().into()))
+                    .collect();
+                let mut fields = vec![
+                    ("target".to_string(), "intune".into()),
+                    ("setup_file".to_string(), setup_file.as_str().into()),
+                    ("content_tags".to_string(), JsonValue::Object(tags)),
+                ];
+                if let Some(name) = output_name {
+                    fields.push(("output_name".to_string(), name.as_str().into()));
+                }
+                JsonValue::Object(fields)
+            }
+            ProjectBuild::Macos {
+                identifier,
+                version,
+                install_location,
+                output_name,
+                allow_special_modes,
+            } => {
+                let mut fields = vec![
+                    ("target".to_string(), "macos".into()),
+                    ("identifier".to_string(), identifier.as_str().into()),
+                    ("version".to_string(), version.as_str().into()),
+                    (
+                        "install_location".to_string(),
+                        install_location.as_str().into(),
+                    ),
+                    (
+                        "allow_special_modes".to_string(),
+                        (*allow_special_modes).into(),
+                    ),
+                ];
+                if let Some(name) = output_name {
+                    fields.push(("output_name".to_string(), name.as_str().into()));
+                }
+                JsonValue::Object(fields)
+            }
+        };
+
+        let scripts = self
+            .scripts
+            .iter()
+            .map(|s| {
+                JsonValue::Object(vec![
+                    (
+                        "path".to_string(),
+                        s.path.to_string_lossy().replace('\\', "/").into(),
+                    ),
+                    ("data".to_string(), BASE64.encode(&s.data).into()),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            ("format".to_string(), PROJECT_FORMAT.into()),
+            ("version".to_string(), PROJECT_VERSION.into()),
+            (
+                "tool_version".to_string(),
+                self.tool_version.as_str().into(),
+            ),
+            ("created".to_string(), self.created.into()),
+            ("build".to_string(), build),
+            ("config".to_string(), JsonValue::from(&self.config)),
+            ("scripts".to_string(), JsonValue::Array(scripts)),
+            (
+                "files".to_string(),
+                JsonValue::Array(self.files.iter().map(JsonValue::from).collect()),
+            ),
+        ])
+        .to_pretty_string()
+    }
+
+    /// Parse a project previously written by [`Project::to_json`].
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value = json::parse(text)?;
+
+        if value.get("format").and_then(JsonValue::as_str) != Some(PROJECT_FORMAT) {
+            return Err("Not an iamawrapper project file".to_string());
+        }
+        let version = value
+            .get("version")
+            .and_then(JsonValue::as_u64)
+            .ok_or("Missing project version")?;
+        if version > PROJECT_VERSION {
+            return Err(format!(
+                "Project version {} is newer than supported version {}",
+                version, PROJECT_VERSION
+            ));
+        }
+
+        let tool_version = value
+            .get("tool_version")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("unknown")
+            .to_string();
+        let created = value
+            .get("created")
+            .and_then(JsonValue::as_u64)
+            .unwrap_or(0);
+        let build = parse_build(value.get("build").ok_or("Missing build settings")?)?;
+        let config = match value.get("config") {
+            Some(config) => Config::from_json_value(config)?,
+            None => Config::default(),
+        };
+
+        let mut scripts = Vec::new();
+        if let Some(list) = value.get("scripts") {
+            for script in list.as_array().ok_or("'scripts' must be an array")? {
+                let path = script
+                    .get("path")
+                    .and_then(JsonValue::as_str)
+                    .ok_or("Script is missing 'path'")?;
+                let data = script
+                    .get("data")
+                    .and_then(JsonValue::as_str)
+                    .ok_or("Script is missing 'data'")?;
+                scripts.push(ProjectScript {
+                    path: PathBuf::from(path),
+                    data: BASE64
+                        .decode(data)
+                        .map_err(|e| format!("Script '{}' is not valid base64: {}", path, e))?,
+                });
+            }
+        }
+
+        let files = value
+            .get("files")
+            .and_then(JsonValue::as_array)
+            .ok_or("Missing content manifest")?
+            .iter()
+            .map(CaptureEntry::from_json_value)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            tool_version,
+            created,
+            build,
+            config,
+            scripts,
+            files,
+        })
+    }
+}
+
+fn parse_build(value: &JsonValue) -> Result<ProjectBuild, String> {
+    let string = |key: &str| -> Result<String, String> {
+        value
+            .get(key)
+            .and_then(JsonValue::as_str)
+            .map(String::from)
+            .ok_or_else(|| format!("Build settings are missing '{}'", key))
+    };
+    let output_name = value
+        .get("output_name")
+        .and_then(JsonValue::as_str)
+        .map(String::from);
+
+    match value.get("target").and_then(JsonValue::as_str) {
+        Some("intune") => {
+            let mut content_tags = Vec::new();
+            if let Some(tags) = value.get("content_tags") {
+                for (key, tag) in tags.as_object().ok_or("'content_tags' must be an object")? {
+                    let tag = tag
+                        .as_str()
+                        .ok_or_else(|| format!("Content tag '{}' must be a string", key))?;
+                    content_tags.push((key.clone(), tag.to_string()));
+                }
+            }
+            Ok(ProjectBuild::Intune {
+                setup_file: string("setup_file")?,
+                output_name,
+                content_tags,
+            })
+        }
+        Some("macos") => Ok(ProjectBuild::Macos {
+            identifier: string("identifier")?,
+            version: string("version")?,
+            install_location: string("install_location")?,
+            output_name,
+            allow_special_modes: value
+                .get("allow_special_modes")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false),
+        }),
+        Some(other) => Err(format!("Unknown build target '{}'", other)),
+        None => Err("Build settings are missing 'target'".to_string()),
+    }
+}
+
+/// Request to rebuild a package from a project file and archived content.
+#[derive(Debug, Clone)]
+pub struct ProjectImportRequest {
+    /// Project file written by `export-project`
+    pub project_file: PathBuf,
+    /// Folder holding the archived content
+    pub content_folder: PathBuf,
+    /// Output folder for the rebuilt package
+    pub output_folder: PathBuf,
+    /// Treat warnings as errors on top of the project's configuration
+    pub strict: bool,
+    /// Verbosity level for output
+    pub verbosity: Verbosity,
+}
+
+impl ProjectImportRequest {
+    /// Create a new import request.
+    pub fn new(
+        project_file: impl Into<PathBuf>,
+        content_folder: impl Into<PathBuf>,
+        output_folder: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            project_file: project_file.into(),
+            content_folder: content_folder.into(),
+            output_folder: output_folder.into(),
+            strict: false,
+            verbosity: Verbosity::default(),
+        }
+    }
+
+    /// Treat warnings as errors regardless of the project's configuration.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+}
+
+/// The package rebuilt from a project.
+#[derive(Debug, Clone)]
+pub enum ProjectImportResult {
+    /// An `.intunewin` package
+    Intune(Box<IntuneWinPackage>),
+    /// A macOS flat package
+    #[cfg(feature = "macos")]
+    Macos(Box<crate::models::macos::MacosPkgResult>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::strictness::{Check, CheckLevel};
+
+    fn sample(build: ProjectBuild) -> Project {
+        Project {
+            tool_version: "0.2.3".to_string(),
+            created: 1_700_000_000,
+            build,
+            config: Config::default(),
+            scripts: vec![ProjectScript {
+                path: PathBuf::from("postinstall"),
+                data: b"#!/bin/sh\nexit 0\n".to_vec(),
+            }],
+            files: vec![CaptureEntry {
+                path: PathBuf::from("bin/app"),
+                size: 3,
+                sha256: "aa".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_project_json_roundtrip() {
+        let mut project = sample(ProjectBuild::Intune {
+            setup_file: "setup.exe".to_string(),
+            output_name: Some("MyApp".to_string()),
+            content_tags: vec![("build".to_string(), "42".to_string())],
+        });
+        project.config.strictness = project
+            .config
+            .strictness
+            .with_override(Check::Signature, CheckLevel::Error);
+        assert_eq!(Project::from_json(&project.to_json()), Ok(project));
+
+        let project = sample(ProjectBuild::Macos {
+            identifier: "com.example.app".to_string(),
+            version: "1.0".to_string(),
+            install_location: "/Applications".to_string(),
+            output_name: None,
+            allow_special_modes: true,
+        });
+        assert_eq!(Project::from_json(&project.to_json()), Ok(project));
+    }
+
+    #[test]
+    fn test_project_rejects_other_files() {
+        assert!(Project::from_json(r#"{"version": 1}"#).is_err());
+        assert!(Project::from_json(r#"{"format": "iamawrapper-project", "version": 99}"#).is_err());
+    }
+}
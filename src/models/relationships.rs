@@ -0,0 +1,316 @@
+//! Dependencies and supersedence of a Win32 app, bundled with its package.
+//!
+//! Intune keeps an app's relationships to other apps outside the
+//! `.intunewin`, so they are usually wired up by hand after the upload. A
+//! sidecar JSON file passed with `--relationships` is stored in the package
+//! under `IntuneWinPackage/Metadata/Extra/`, where upload tooling can read
+//! it:
+//!
+//! ```json
+//! {
+//!   "dependencies": [
+//!     { "app": "Visual C++ 2022 Redistributable", "version": "14.38", "auto_install": true }
+//!   ],
+//!   "supersedes": [
+//!     { "app": "Contoso Agent", "id": "0f1e2d3c-...", "version": "4.2", "uninstall": true }
+//!   ]
+//! }
+//! ```
+//!
+//! `app` names the related app; `id` and `version` are optional. Intune
+//! ignores the file.
+
+use crate::json::{self, JsonValue};
+
+/// Most dependencies Intune accepts for one app.
+pub const MAX_DEPENDENCIES: usize = 100;
+
+/// Most apps Intune lets one app supersede.
+pub const MAX_SUPERSEDED: usize = 10;
+
+/// An app that must be installed before this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppDependency {
+    /// Name of the app, as known to the upload tooling
+    pub app: String,
+    /// Intune app ID, if known
+    pub id: Option<String>,
+    /// Version of the app
+    pub version: Option<String>,
+    /// Install the app if it is missing, rather than only detecting it
+    pub auto_install: bool,
+}
+
+/// An app that this one replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupersededApp {
+    /// Name of the app, as known to the upload tooling
+    pub app: String,
+    /// Intune app ID, if known
+    pub id: Option<String>,
+    /// Version of the app
+    pub version: Option<String>,
+    /// Uninstall the app before installing this one, rather than updating
+    /// it in place
+    pub uninstall: bool,
+}
+
+/// Relationships of an app to other apps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppRelationships {
+    /// Apps installed before this one
+    pub dependencies: Vec<AppDependency>,
+    /// Apps this one replaces
+    pub supersedes: Vec<SupersededApp>,
+}
+
+impl AppRelationships {
+    /// Parse and check a relationships file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value = json::parse(text.trim_start_matches('\u{feff}'))?;
+        Self::from_json_value(&value)
+    }
+
+    /// Read relationships from a JSON object.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let members = value
+            .as_object()
+            .ok_or("Relationships must be a JSON object")?;
+        let mut relationships = Self::default();
+
+        for (key, value) in members {
+            let items = || {
+                value
+                    .as_array()
+                    .ok_or_else(|| format!("'{}' must be an array", key))
+            };
+            match key.as_str() {
+                "dependencies" => {
+                    for item in items()? {
+                        let reference = AppReference::parse(key, item, "auto_install", true)?;
+                        relationships.dependencies.push(AppDependency {
+                            app: reference.app,
+                            id: reference.id,
+                            version: reference.version,
+                            auto_install: reference.flag,
+                        });
+                    }
+                }
+                "supersedes" => {
+                    for item in items()? {
+                        let reference = AppReference::parse(key, item, "uninstall", false)?;
+                        relationships.supersedes.push(SupersededApp {
+                            app: reference.app,
+                            id: reference.id,
+                            version: reference.version,
+                            uninstall: reference.flag,
+                        });
+                    }
+                }
+                _ => return Err(format!("Unknown relationships setting '{}'", key)),
+            }
+        }
+
+        relationships.validate()?;
+        Ok(relationships)
+    }
+
+    /// Check the limits Intune puts on relationships.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.is_empty() {
+            return Err("No dependencies or superseded apps are listed".to_string());
+        }
+        if self.dependencies.len() > MAX_DEPENDENCIES {
+            return Err(format!(
+                "{} dependencies are listed; Intune accepts at most {}",
+                self.dependencies.len(),
+                MAX_DEPENDENCIES
+            ));
+        }
+        if self.supersedes.len() > MAX_SUPERSEDED {
+            return Err(format!(
+                "{} superseded apps are listed; Intune accepts at most {}",
+                self.supersedes.len(),
+                MAX_SUPERSEDED
+            ));
+        }
+
+        let dependencies = self.dependencies.iter().map(|d| (&d.app, &d.id));
+        let superseded = self.supersedes.iter().map(|s| (&s.app, &s.id));
+        for (kind, apps) in [
+            ("dependency", dependencies.collect::<Vec<_>>()),
+            ("superseded app", superseded.collect()),
+        ] {
+            for (i, app) in apps.iter().enumerate() {
+                if apps[..i].contains(app) {
+                    return Err(format!("'{}' is listed twice as a {}", app.0, kind));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether no relationships are listed.
+    pub fn is_empty(&self) -> bool {
+        self.dependencies.is_empty() && self.supersedes.is_empty()
+    }
+
+    /// A one-line summary, e.g. `2 dependencies, 1 superseded app`.
+    pub fn summary(&self) -> String {
+        let plural = |count: usize, one: &str, many: &str| {
+            format!("{} {}", count, if count == 1 { one } else { many })
+        };
+        format!(
+            "{}, {}",
+            plural(self.dependencies.len(), "dependency", "dependencies"),
+            plural(self.supersedes.len(), "superseded app", "superseded apps")
+        )
+    }
+
+    /// The relationships as a JSON object, in the format they are read in.
+    pub fn to_json_value(&self) -> JsonValue {
+        let reference = |app: &str, id: &Option<String>, version: &Option<String>| {
+            let mut members = vec![("app".to_string(), JsonValue::from(app))];
+            if let Some(id) = id {
+                members.push(("id".to_string(), id.as_str().into()));
+            }
+            if let Some(version) = version {
+                members.push(("version".to_string(), version.as_str().into()));
+            }
+            members
+        };
+
+        let dependencies = self
+            .dependencies
+            .iter()
+            .map(|d| {
+                let mut members = reference(&d.app, &d.id, &d.version);
+                members.push(("auto_install".to_string(), d.auto_install.into()));
+                JsonValue::Object(members)
+            })
+            .collect();
+        let supersedes = self
+            .supersedes
+            .iter()
+            .map(|s| {
+                let mut members = reference(&s.app, &s.id, &s.version);
+                members.push(("uninstall".to_string(), s.uninstall.into()));
+                JsonValue::Object(members)
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            ("dependencies".to_string(), JsonValue::Array(dependencies)),
+            ("supersedes".to_string(), JsonValue::Array(supersedes)),
+        ])
+    }
+
+    /// Serialize the relationships as JSON.
+    pub fn to_json(&self) -> String {
+        self.to_json_value().to_pretty_string()
+    }
+}
+
+/// The members a dependency and a superseded app share, plus the flag that
+/// sets them apart.
+struct AppReference {
+    app: String,
+    id: Option<String>,
+    version: Option<String>,
+    flag: bool,
+}
+
+impl AppReference {
+    fn parse(
+        list: &str,
+        value: &JsonValue,
+        flag_name: &str,
+        flag_default: bool,
+    ) -> Result<Self, String> {
+        let members = value
+            .as_object()
+            .ok_or_else(|| format!("Entries of '{}' must be objects", list))?;
+        let mut reference = Self {
+            app: String::new(),
+            id: None,
+            version: None,
+            flag: flag_default,
+        };
+
+        for (key, value) in members {
+            let string = || {
+                value
+                    .as_str()
+                    .map(|s| s.trim().to_string())
+                    .ok_or_else(|| format!("'{}.{}' must be a string", list, key))
+            };
+            match key.as_str() {
+                "app" => reference.app = string()?,
+                "id" => reference.id = Some(string()?).filter(|s| !s.is_empty()),
+                "version" => reference.version = Some(string()?).filter(|s| !s.is_empty()),
+                _ if key == flag_name => {
+                    reference.flag = value
+                        .as_bool()
+                        .ok_or_else(|| format!("'{}.{}' must be true or false", list, key))?;
+                }
+                _ => return Err(format!("Unknown '{}' setting '{}'", list, key)),
+            }
+        }
+
+        if reference.app.is_empty() {
+            return Err(format!("Every entry of '{}' needs an 'app' name", list));
+        }
+        Ok(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relationships_round_trip() {
+        let text = r#"{
+            "dependencies": [
+                { "app": "VC++ 2022", "version": "14.38" },
+                { "app": "Agent", "id": "abc", "auto_install": false }
+            ],
+            "supersedes": [{ "app": "Contoso 1.0", "uninstall": true }]
+        }"#;
+        let relationships = AppRelationships::parse(text).unwrap();
+        assert_eq!(relationships.dependencies.len(), 2);
+        assert!(relationships.dependencies[0].auto_install);
+        assert_eq!(relationships.dependencies[1].id.as_deref(), Some("abc"));
+        assert!(!relationships.dependencies[1].auto_install);
+        assert!(relationships.supersedes[0].uninstall);
+        assert_eq!(relationships.summary(), "2 dependencies, 1 superseded app");
+
+        assert_eq!(
+            AppRelationships::parse(&relationships.to_json()).unwrap(),
+            relationships
+        );
+    }
+
+    #[test]
+    fn test_relationships_rejects_invalid_files() {
+        for bad in [
+            "[]",
+            "{}",
+            r#"{ "dependencies": [] }"#,
+            r#"{ "dependants": [{ "app": "a" }] }"#,
+            r#"{ "dependencies": [{ "id": "abc" }] }"#,
+            r#"{ "dependencies": [{ "app": "a", "uninstall": true }] }"#,
+            r#"{ "supersedes": [{ "app": "a", "uninstall": "yes" }] }"#,
+            r#"{ "supersedes": [{ "app": "a" }, { "app": "a" }] }"#,
+        ] {
+            assert!(AppRelationships::parse(bad).is_err(), "{}", bad);
+        }
+
+        let many: Vec<String> = (0..=MAX_SUPERSEDED)
+            .map(|i| format!(r#"{{ "app": "app {}" }}"#, i))
+            .collect();
+        let text = format!(r#"{{ "supersedes": [{}] }}"#, many.join(","));
+        let err = AppRelationships::parse(&text).unwrap_err();
+        assert!(err.contains("at most 10"), "{}", err);
+    }
+}
@@ -0,0 +1,228 @@
+//! Retrying operations that fail transiently.
+//!
+//! Notarization requests are retried after network errors and throttling.
+//! File reads and writes are retried with `--io-retries`: on SMB and NFS
+//! shares they occasionally fail with errors that go away a moment later,
+//! such as timeouts, stale handles and dropped connections. Errors that
+//! waiting will not fix, such as a missing file or a denied permission,
+//! fail at once.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Wait before the first retry of a file operation.
+pub const IO_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Longest wait between retries of a file operation.
+pub const IO_RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How failed requests and file operations are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first included
+    pub attempts: u32,
+    /// Wait before the first retry; doubled for each further retry
+    pub initial_delay: Duration,
+    /// Longest wait between retries
+    pub max_delay: Duration,
+}
+
+/// Five attempts, waiting from 2 seconds up to a minute: suited to
+/// network requests.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt, failing on the first error.
+    pub fn none() -> Self {
+        Self::io(0)
+    }
+
+    /// Up to `retries` retries of a file operation, waiting from
+    /// [`IO_RETRY_DELAY`] up to [`IO_RETRY_MAX_DELAY`].
+    pub fn io(retries: u32) -> Self {
+        Self {
+            attempts: retries.saturating_add(1),
+            initial_delay: IO_RETRY_DELAY,
+            max_delay: IO_RETRY_MAX_DELAY,
+        }
+    }
+
+    /// Retries after the first attempt.
+    pub fn retries(&self) -> u32 {
+        self.attempts.saturating_sub(1)
+    }
+
+    /// Wait before retry number `retry` (0 for the first).
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Run `operation` on `path`, retrying it while it fails with a
+    /// [transient](is_transient) error and attempts are left.
+    ///
+    /// Each retry is logged as a warning naming `action` and `path`.
+    pub fn run<T>(
+        &self,
+        action: &str,
+        path: &Path,
+        mut operation: impl FnMut() -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut retry = 0;
+        loop {
+            match operation() {
+                Err(e) if retry < self.retries() && is_transient(&e) => {
+                    self.wait_to_retry(action, path, &e, retry);
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Log that `action` on `path` failed with `error` and is retried, then
+    /// wait before retry number `retry` (0 for the first).
+    pub fn wait_to_retry(&self, action: &str, path: &Path, error: &io::Error, retry: u32) {
+        warn!(
+            "Retrying {} of '{}' after a transient error ({}), attempt {}/{}",
+            action,
+            path.display(),
+            error,
+            retry + 2,
+            self.attempts
+        );
+        std::thread::sleep(self.delay(retry));
+    }
+}
+
+/// Whether `error` may go away when the operation is tried again.
+///
+/// Besides timeouts and interrupted calls this covers the errors network
+/// file systems report for dropped connections and stale handles.
+pub fn is_transient(error: &io::Error) -> bool {
+    use io::ErrorKind;
+
+    if matches!(
+        error.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+    ) {
+        return true;
+    }
+    error.raw_os_error().is_some_and(is_transient_os_error)
+}
+
+#[cfg(unix)]
+fn is_transient_os_error(code: i32) -> bool {
+    [
+        libc::EIO,
+        libc::EAGAIN,
+        libc::ESTALE,
+        libc::ETIMEDOUT,
+        libc::EHOSTDOWN,
+        libc::EHOSTUNREACH,
+        libc::ENETDOWN,
+        libc::ENETRESET,
+        libc::ENETUNREACH,
+    ]
+    .contains(&code)
+}
+
+#[cfg(windows)]
+fn is_transient_os_error(code: i32) -> bool {
+    // ERROR_NETWORK_BUSY, ERROR_UNEXP_NET_ERR, ERROR_NETNAME_DELETED,
+    // ERROR_SEM_TIMEOUT, ERROR_NETWORK_UNREACHABLE, ERROR_CONNECTION_ABORTED
+    [54, 59, 64, 121, 1231, 1236].contains(&code)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_transient_os_error(_code: i32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn quick(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            ..RetryPolicy::io(retries)
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(8));
+        assert_eq!(policy.delay(10), Duration::from_secs(60));
+        assert_eq!(policy.delay(40), Duration::from_secs(60));
+
+        assert_eq!(RetryPolicy::none().retries(), 0);
+        assert_eq!(RetryPolicy::io(3).attempts, 4);
+        assert_eq!(RetryPolicy::io(3).delay(1), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_retry_policy_retries_transient_errors_only() {
+        let calls = Cell::new(0);
+        let result = quick(3).run("read", Path::new("share/file"), || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            } else {
+                Ok(calls.get())
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // Out of retries
+        calls.set(0);
+        let result: io::Result<()> = quick(1).run("read", Path::new("f"), || {
+            calls.set(calls.get() + 1);
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 2);
+
+        // Permanent errors fail at once
+        calls.set(0);
+        let result: io::Result<()> = quick(5).run("read", Path::new("f"), || {
+            calls.set(calls.get() + 1);
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&io::Error::from(io::ErrorKind::TimedOut)));
+        assert!(!is_transient(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+        #[cfg(unix)]
+        assert!(is_transient(&io::Error::from_raw_os_error(libc::ESTALE)));
+        #[cfg(unix)]
+        assert!(!is_transient(&io::Error::from_raw_os_error(libc::ENOENT)));
+    }
+}
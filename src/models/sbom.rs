@@ -0,0 +1,713 @@
+//! Software bills of materials (SBOMs) for packaged content.
+//!
+//! An SBOM lists every packaged file with its hashes, plus the products
+//! found among them (Windows executables and installers, macOS application
+//! bundles) with their versions, and flags the files found on the
+//! known-bad hash blocklist. It is rendered as CycloneDX 1.5 or SPDX 2.3
+//! JSON.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::json::JsonValue;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::time::iso8601;
+
+/// SBOM document formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl SbomFormat {
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDx => "cyclonedx",
+            SbomFormat::Spdx => "spdx",
+        }
+    }
+
+    /// Conventional file extension, e.g. `app.cdx.json`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDx => "cdx.json",
+            SbomFormat::Spdx => "spdx.json",
+        }
+    }
+
+    /// Where the sidecar SBOM of the package at `package` is written.
+    pub fn sidecar_path(self, package: &Path) -> PathBuf {
+        package.with_extension(self.extension())
+    }
+}
+
+impl fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cyclonedx" | "cdx" => Ok(SbomFormat::CycloneDx),
+            "spdx" => Ok(SbomFormat::Spdx),
+            other => Err(format!(
+                "Invalid SBOM format '{}' (expected 'cyclonedx' or 'spdx')",
+                other
+            )),
+        }
+    }
+}
+
+/// Which SBOM to produce for a package, and where to put it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SbomOptions {
+    /// Document format
+    pub format: SbomFormat,
+    /// Write the SBOM next to the package
+    pub sidecar: bool,
+    /// Store the SBOM in the package's metadata folder
+    pub embed: bool,
+}
+
+impl SbomOptions {
+    /// An SBOM written next to the package.
+    pub fn sidecar(format: SbomFormat) -> Self {
+        Self {
+            format,
+            sidecar: true,
+            embed: false,
+        }
+    }
+
+    /// An SBOM stored in the package only.
+    pub fn embedded(format: SbomFormat) -> Self {
+        Self {
+            format,
+            sidecar: false,
+            embed: true,
+        }
+    }
+
+    /// Also store the SBOM in the package.
+    pub fn with_embed(mut self, embed: bool) -> Self {
+        self.embed = embed;
+        self
+    }
+
+    /// Check that the SBOM goes somewhere.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.sidecar && !self.embed {
+            return Err(PackageError::InvalidArgument {
+                reason: "An SBOM must be written next to the package, embedded, or both"
+                    .to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A packaged file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomFile {
+    /// Path with `/` separators, relative to the content root (or below
+    /// the install location for macOS packages)
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Lowercase hex SHA-256
+    pub sha256: String,
+    /// Lowercase hex SHA-1
+    pub sha1: String,
+}
+
+/// Where a product's name and version were read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProductSource {
+    /// Version resource of a Windows executable or library
+    VersionResource,
+    /// Property table of a Windows Installer database
+    MsiProperties,
+    /// `Info.plist` of a macOS bundle
+    InfoPlist,
+}
+
+impl ProductSource {
+    /// Short name recorded in the SBOM.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProductSource::VersionResource => "version-resource",
+            ProductSource::MsiProperties => "msi-properties",
+            ProductSource::InfoPlist => "info-plist",
+        }
+    }
+}
+
+/// A product identified among the packaged files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomProduct {
+    /// Product name
+    pub name: String,
+    /// Product version, when recorded
+    pub version: Option<String>,
+    /// Company or manufacturer, when recorded
+    pub supplier: Option<String>,
+    /// The file (or bundle folder) the product was read from
+    pub path: String,
+    /// What the information was read from
+    pub source: ProductSource,
+}
+
+/// A packaged file whose hash is on the known-bad hash blocklist, kept
+/// with `--blocklist-action warn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomBlocklistMatch {
+    /// Path of the file, as in [`SbomFile::path`]
+    pub path: String,
+    /// Lowercase hex SHA-256 of the file
+    pub sha256: String,
+    /// Description from the blocklist entry, if any
+    pub description: Option<String>,
+}
+
+impl SbomBlocklistMatch {
+    fn description(&self) -> String {
+        match &self.description {
+            Some(description) => format!("Matches a known-bad hash: {}", description),
+            None => "Matches a known-bad hash".to_string(),
+        }
+    }
+}
+
+/// A software bill of materials for one package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sbom {
+    /// Name of the packaged product
+    pub name: String,
+    /// Version of the packaged product, when known
+    pub version: Option<String>,
+    /// Supplier of the packaged product, when known
+    pub supplier: Option<String>,
+    /// Creation time (Unix seconds; 0 in reproducible builds)
+    pub created: u64,
+    /// Every packaged file, sorted by path
+    pub files: Vec<SbomFile>,
+    /// Products identified among the files
+    pub products: Vec<SbomProduct>,
+    /// Files on the known-bad hash blocklist, sorted by path
+    pub blocklist_matches: Vec<SbomBlocklistMatch>,
+}
+
+impl Sbom {
+    /// An SBOM for the product `name` without files.
+    pub fn new(name: impl Into<String>, created: u64) -> Self {
+        Self {
+            name: name.into(),
+            version: None,
+            supplier: None,
+            created,
+            files: Vec::new(),
+            products: Vec::new(),
+            blocklist_matches: Vec::new(),
+        }
+    }
+
+    /// Set the product version.
+    pub fn with_version(mut self, version: Option<String>) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the product supplier.
+    pub fn with_supplier(mut self, supplier: Option<String>) -> Self {
+        self.supplier = supplier;
+        self
+    }
+
+    /// The blocklist match of the file at `path`, if any.
+    fn blocklist_match(&self, path: &str) -> Option<&SbomBlocklistMatch> {
+        self.blocklist_matches.iter().find(|m| m.path == path)
+    }
+
+    /// Render the SBOM in `format`.
+    pub fn render(&self, format: SbomFormat) -> String {
+        match format {
+            SbomFormat::CycloneDx => self.to_cyclonedx_json(),
+            SbomFormat::Spdx => self.to_spdx_json(),
+        }
+    }
+
+    /// CycloneDX 1.5 JSON.
+    pub fn to_cyclonedx_json(&self) -> String {
+        let mut root = vec![
+            ("type".to_string(), "application".into()),
+            ("name".to_string(), self.name.clone().into()),
+        ];
+        if let Some(version) = &self.version {
+            root.push(("version".to_string(), version.clone().into()));
+        }
+        if let Some(supplier) = &self.supplier {
+            root.push(("supplier".to_string(), named(supplier)));
+        }
+
+        let mut components = Vec::new();
+        for (i, product) in self.products.iter().enumerate() {
+            let mut component = vec![
+                ("type".to_string(), "application".into()),
+                ("bom-ref".to_string(), format!("product-{}", i + 1).into()),
+                ("name".to_string(), product.name.clone().into()),
+            ];
+            if let Some(version) = &product.version {
+                component.push(("version".to_string(), version.clone().into()));
+            }
+            if let Some(supplier) = &product.supplier {
+                component.push(("supplier".to_string(), named(supplier)));
+            }
+            component.push((
+                "properties".to_string(),
+                JsonValue::Array(vec![
+                    property("iamawrapper:path", &product.path),
+                    property("iamawrapper:source", product.source.name()),
+                ]),
+            ));
+            components.push(JsonValue::Object(component));
+        }
+        for file in &self.files {
+            let mut properties = vec![property("iamawrapper:size", &file.size.to_string())];
+            if self.blocklist_match(&file.path).is_some() {
+                properties.push(property("iamawrapper:blocklisted", "true"));
+            }
+            components.push(JsonValue::Object(vec![
+                ("type".to_string(), "file".into()),
+                ("bom-ref".to_string(), format!("file:{}", file.path).into()),
+                ("name".to_string(), file.path.clone().into()),
+                (
+                    "hashes".to_string(),
+                    JsonValue::Array(vec![
+                        hash("SHA-1", &file.sha1),
+                        hash("SHA-256", &file.sha256),
+                    ]),
+                ),
+                ("properties".to_string(), JsonValue::Array(properties)),
+            ]));
+        }
+
+        // Blocklist matches are reported as vulnerabilities of the files
+        let vulnerabilities = self
+            .blocklist_matches
+            .iter()
+            .enumerate()
+            .map(|(i, matched)| {
+                JsonValue::Object(vec![
+                    ("bom-ref".to_string(), format!("blocklist-{}", i + 1).into()),
+                    (
+                        "id".to_string(),
+                        format!("sha256:{}", matched.sha256).into(),
+                    ),
+                    ("source".to_string(), named("iamawrapper blocklist")),
+                    ("description".to_string(), matched.description().into()),
+                    (
+                        "affects".to_string(),
+                        JsonValue::Array(vec![JsonValue::Object(vec![(
+                            "ref".to_string(),
+                            format!("file:{}", matched.path).into(),
+                        )])]),
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let mut document = vec![
+            ("bomFormat".to_string(), "CycloneDX".into()),
+            ("specVersion".to_string(), "1.5".into()),
+            (
+                "serialNumber".to_string(),
+                format!("urn:uuid:{}", self.uuid()).into(),
+            ),
+            ("version".to_string(), 1u64.into()),
+            (
+                "metadata".to_string(),
+                JsonValue::Object(vec![
+                    ("timestamp".to_string(), iso8601(self.created).into()),
+                    (
+                        "tools".to_string(),
+                        JsonValue::Object(vec![(
+                            "components".to_string(),
+                            JsonValue::Array(vec![JsonValue::Object(vec![
+                                ("type".to_string(), "application".into()),
+                                ("name".to_string(), env!("CARGO_PKG_NAME").into()),
+                                ("version".to_string(), env!("CARGO_PKG_VERSION").into()),
+                            ])]),
+                        )]),
+                    ),
+                    ("component".to_string(), JsonValue::Object(root)),
+                ]),
+            ),
+            ("components".to_string(), JsonValue::Array(components)),
+        ];
+        if !vulnerabilities.is_empty() {
+            document.push((
+                "vulnerabilities".to_string(),
+                JsonValue::Array(vulnerabilities),
+            ));
+        }
+        JsonValue::Object(document).to_pretty_string()
+    }
+
+    /// SPDX 2.3 JSON.
+    pub fn to_spdx_json(&self) -> String {
+        let root_id = "SPDXRef-Package";
+        let mut packages = vec![spdx_package(
+            root_id,
+            &self.name,
+            self.version.as_deref(),
+            self.supplier.as_deref(),
+            None,
+        )];
+        let mut relationships = vec![relationship("SPDXRef-DOCUMENT", "DESCRIBES", root_id)];
+
+        for (i, product) in self.products.iter().enumerate() {
+            let id = format!("SPDXRef-Product-{}", i + 1);
+            packages.push(spdx_package(
+                &id,
+                &product.name,
+                product.version.as_deref(),
+                product.supplier.as_deref(),
+                Some(&product.path),
+            ));
+            relationships.push(relationship(root_id, "CONTAINS", &id));
+        }
+
+        let mut files = Vec::new();
+        for (i, file) in self.files.iter().enumerate() {
+            let id = format!("SPDXRef-File-{}", i + 1);
+            let mut entry = vec![
+                ("SPDXID".to_string(), id.clone().into()),
+                (
+                    "fileName".to_string(),
+                    format!("./{}", file.path.trim_start_matches('/')).into(),
+                ),
+                (
+                    "checksums".to_string(),
+                    JsonValue::Array(vec![
+                        checksum("SHA1", &file.sha1),
+                        checksum("SHA256", &file.sha256),
+                    ]),
+                ),
+                ("licenseConcluded".to_string(), "NOASSERTION".into()),
+                ("copyrightText".to_string(), "NOASSERTION".into()),
+            ];
+            if let Some(matched) = self.blocklist_match(&file.path) {
+                entry.push((
+                    "annotations".to_string(),
+                    JsonValue::Array(vec![JsonValue::Object(vec![
+                        ("annotationType".to_string(), "REVIEW".into()),
+                        ("annotator".to_string(), tool().into()),
+                        ("annotationDate".to_string(), iso8601(self.created).into()),
+                        ("comment".to_string(), matched.description().into()),
+                    ])]),
+                ));
+            }
+            files.push(JsonValue::Object(entry));
+            relationships.push(relationship(root_id, "CONTAINS", &id));
+        }
+
+        JsonValue::Object(vec![
+            ("spdxVersion".to_string(), "SPDX-2.3".into()),
+            ("dataLicense".to_string(), "CC0-1.0".into()),
+            ("SPDXID".to_string(), "SPDXRef-DOCUMENT".into()),
+            ("name".to_string(), self.name.clone().into()),
+            (
+                "documentNamespace".to_string(),
+                format!(
+                    "https://spdx.org/spdxdocs/{}-{}",
+                    env!("CARGO_PKG_NAME"),
+                    self.uuid()
+                )
+                .into(),
+            ),
+            (
+                "creationInfo".to_string(),
+                JsonValue::Object(vec![
+                    ("created".to_string(), iso8601(self.created).into()),
+                    (
+                        "creators".to_string(),
+                        JsonValue::Array(vec![tool().into()]),
+                    ),
+                ]),
+            ),
+            ("packages".to_string(), JsonValue::Array(packages)),
+            ("files".to_string(), JsonValue::Array(files)),
+            ("relationships".to_string(), JsonValue::Array(relationships)),
+        ])
+        .to_pretty_string()
+    }
+
+    /// A UUID derived from the SBOM's contents, so identical content gives
+    /// identical documents (and reproducible packages stay reproducible).
+    fn uuid(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.name.as_bytes());
+        hasher.update(self.created.to_be_bytes());
+        for file in &self.files {
+            hasher.update(format!("{}\t{}\n", file.path, file.sha256).as_bytes());
+        }
+        let mut bytes: [u8; 16] = hasher.finalize()[..16].try_into().unwrap_or([0; 16]);
+        // Version 8 (custom) with the RFC 9562 variant
+        bytes[6] = (bytes[6] & 0x0f) | 0x80;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+/// This tool as an SPDX creator or annotator.
+fn tool() -> String {
+    format!(
+        "Tool: {}-{}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn named(name: &str) -> JsonValue {
+    JsonValue::Object(vec![("name".to_string(), name.into())])
+}
+
+fn property(name: &str, value: &str) -> JsonValue {
+    JsonValue::Object(vec![
+        ("name".to_string(), name.into()),
+        ("value".to_string(), value.into()),
+    ])
+}
+
+fn hash(algorithm: &str, content: &str) -> JsonValue {
+    JsonValue::Object(vec![
+        ("alg".to_string(), algorithm.into()),
+        ("content".to_string(), content.into()),
+    ])
+}
+
+fn checksum(algorithm: &str, value: &str) -> JsonValue {
+    JsonValue::Object(vec![
+        ("algorithm".to_string(), algorithm.into()),
+        ("checksumValue".to_string(), value.into()),
+    ])
+}
+
+fn relationship(element: &str, kind: &str, related: &str) -> JsonValue {
+    JsonValue::Object(vec![
+        ("spdxElementId".to_string(), element.into()),
+        ("relationshipType".to_string(), kind.into()),
+        ("relatedSpdxElement".to_string(), related.into()),
+    ])
+}
+
+fn spdx_package(
+    id: &str,
+    name: &str,
+    version: Option<&str>,
+    supplier: Option<&str>,
+    path: Option<&str>,
+) -> JsonValue {
+    let mut package = vec![
+        ("SPDXID".to_string(), id.into()),
+        ("name".to_string(), name.into()),
+    ];
+    if let Some(version) = version {
+        package.push(("versionInfo".to_string(), version.into()));
+    }
+    package.push((
+        "supplier".to_string(),
+        match supplier {
+            Some(supplier) => format!("Organization: {}", supplier).into(),
+            None => "NOASSERTION".into(),
+        },
+    ));
+    if let Some(path) = path {
+        package.push(("packageFileName".to_string(), path.into()));
+    }
+    package.extend([
+        ("downloadLocation".to_string(), "NOASSERTION".into()),
+        ("filesAnalyzed".to_string(), false.into()),
+        ("licenseConcluded".to_string(), "NOASSERTION".into()),
+        ("licenseDeclared".to_string(), "NOASSERTION".into()),
+        ("copyrightText".to_string(), "NOASSERTION".into()),
+    ]);
+    JsonValue::Object(package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    fn sample() -> Sbom {
+        let mut sbom = Sbom::new("ACME Tool", 1_700_000_000)
+            .with_version(Some("2.0".to_string()))
+            .with_supplier(Some("ACME Corp".to_string()));
+        sbom.files.push(SbomFile {
+            path: "bin/tool.exe".to_string(),
+            size: 5,
+            sha256: "a".repeat(64),
+            sha1: "b".repeat(40),
+        });
+        sbom.products.push(SbomProduct {
+            name: "ACME Tool".to_string(),
+            version: Some("2.0.0.1".to_string()),
+            supplier: None,
+            path: "bin/tool.exe".to_string(),
+            source: ProductSource::VersionResource,
+        });
+        sbom
+    }
+
+    #[test]
+    fn test_cyclonedx_json() {
+        let bom = json::parse(&sample().to_cyclonedx_json()).unwrap();
+        assert_eq!(
+            bom.get("bomFormat").and_then(JsonValue::as_str),
+            Some("CycloneDX")
+        );
+        let metadata = bom.get("metadata").unwrap();
+        assert_eq!(
+            metadata.get("timestamp").and_then(JsonValue::as_str),
+            Some("2023-11-14T22:13:20Z")
+        );
+        let components = bom.get("components").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(
+            components[0].get("version").and_then(JsonValue::as_str),
+            Some("2.0.0.1")
+        );
+        let hashes = components[1]
+            .get("hashes")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        assert_eq!(
+            hashes[1].get("content").and_then(JsonValue::as_str),
+            Some("a".repeat(64).as_str())
+        );
+
+        // Identical content gives an identical document
+        assert_eq!(sample().to_cyclonedx_json(), sample().to_cyclonedx_json());
+        let serial = bom.get("serialNumber").and_then(JsonValue::as_str).unwrap();
+        assert_eq!(serial.len(), "urn:uuid:".len() + 36);
+    }
+
+    #[test]
+    fn test_spdx_json() {
+        let doc = json::parse(&sample().render(SbomFormat::Spdx)).unwrap();
+        assert_eq!(
+            doc.get("spdxVersion").and_then(JsonValue::as_str),
+            Some("SPDX-2.3")
+        );
+        let packages = doc.get("packages").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(
+            packages[0].get("supplier").and_then(JsonValue::as_str),
+            Some("Organization: ACME Corp")
+        );
+        let files = doc.get("files").and_then(JsonValue::as_array).unwrap();
+        assert_eq!(
+            files[0].get("fileName").and_then(JsonValue::as_str),
+            Some("./bin/tool.exe")
+        );
+        let relationships = doc
+            .get("relationships")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        // DESCRIBES, the product and the file
+        assert_eq!(relationships.len(), 3);
+    }
+
+    #[test]
+    fn test_blocklist_matches_are_flagged() {
+        let mut sbom = sample();
+        sbom.blocklist_matches.push(SbomBlocklistMatch {
+            path: "bin/tool.exe".to_string(),
+            sha256: "a".repeat(64),
+            description: Some("Known bad".to_string()),
+        });
+
+        let bom = json::parse(&sbom.to_cyclonedx_json()).unwrap();
+        let vulnerabilities = bom
+            .get("vulnerabilities")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        assert_eq!(vulnerabilities.len(), 1);
+        assert_eq!(
+            vulnerabilities[0]
+                .get("description")
+                .and_then(JsonValue::as_str),
+            Some("Matches a known-bad hash: Known bad")
+        );
+        let affects = vulnerabilities[0]
+            .get("affects")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        assert_eq!(
+            affects[0].get("ref").and_then(JsonValue::as_str),
+            Some("file:bin/tool.exe")
+        );
+        let components = bom.get("components").and_then(JsonValue::as_array).unwrap();
+        let properties = components[1]
+            .get("properties")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        assert!(properties.contains(&property("iamawrapper:blocklisted", "true")));
+        // Nothing to report without matches
+        assert!(
+            json::parse(&sample().to_cyclonedx_json())
+                .unwrap()
+                .get("vulnerabilities")
+                .is_none()
+        );
+
+        let doc = json::parse(&sbom.to_spdx_json()).unwrap();
+        let files = doc.get("files").and_then(JsonValue::as_array).unwrap();
+        let annotations = files[0]
+            .get("annotations")
+            .and_then(JsonValue::as_array)
+            .unwrap();
+        assert_eq!(
+            annotations[0].get("comment").and_then(JsonValue::as_str),
+            Some("Matches a known-bad hash: Known bad")
+        );
+    }
+
+    #[test]
+    fn test_sbom_format_and_options() {
+        assert_eq!("CDX".parse(), Ok(SbomFormat::CycloneDx));
+        assert_eq!("spdx".parse(), Ok(SbomFormat::Spdx));
+        assert!("swid".parse::<SbomFormat>().is_err());
+        assert_eq!(
+            SbomFormat::Spdx.sidecar_path(Path::new("out/app.intunewin")),
+            PathBuf::from("out/app.spdx.json")
+        );
+        assert!(SbomOptions::sidecar(SbomFormat::Spdx).validate().is_ok());
+        assert!(
+            SbomOptions::embedded(SbomFormat::Spdx)
+                .with_embed(false)
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! PowerShell install wrappers for `.exe` installers (`intune scaffold`).
+
+use std::path::{Path, PathBuf};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::suggest::suggest_files;
+
+/// Name of the generated install wrapper.
+pub const INSTALL_SCRIPT: &str = "Install.ps1";
+
+/// Name of the generated uninstall wrapper.
+pub const UNINSTALL_SCRIPT: &str = "Uninstall.ps1";
+
+/// Installer exit codes reported to Intune as success.
+pub const DEFAULT_SUCCESS_CODES: &[i32] = &[0, 1707];
+
+/// Installer exit codes passed on to Intune as a reboot request (soft and
+/// hard).
+pub const REBOOT_CODES: &[i32] = &[3010, 1641];
+
+/// Folder under `%ProgramData%` holding the detection tag files.
+pub const DEFAULT_TAG_FOLDER: &str = r"iamawrapper\Tags";
+
+/// Characters Windows does not allow in file names.
+const INVALID_NAME_CHARS: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Request to generate install and uninstall wrappers into a source folder.
+#[derive(Debug, Clone)]
+pub struct ScaffoldRequest {
+    /// Source folder receiving the scripts (and later packaged)
+    pub source_folder: PathBuf,
+    /// The `.exe` installer, relative to the source folder
+    pub setup_file: String,
+    /// Arguments for a silent install (e.g., `/S`)
+    pub install_args: String,
+    /// Uninstaller: relative to the source folder, or a full path on the
+    /// device such as `%ProgramFiles%\App\uninst.exe` (default: the setup
+    /// file)
+    pub uninstall_file: Option<String>,
+    /// Arguments for a silent uninstall
+    pub uninstall_args: String,
+    /// Application name used for logs and the tag file (default: the setup
+    /// file name without extension)
+    pub app_name: Option<String>,
+    /// Installer exit codes treated as success besides [`DEFAULT_SUCCESS_CODES`]
+    pub success_codes: Vec<i32>,
+    /// Folder under `%ProgramData%` receiving the tag file
+    pub tag_folder: String,
+    /// Replace existing wrappers
+    pub overwrite: bool,
+}
+
+impl ScaffoldRequest {
+    /// Create a request for `setup_file` in `source_folder`.
+    pub fn new(
+        source_folder: impl Into<PathBuf>,
+        setup_file: impl Into<String>,
+        install_args: impl Into<String>,
+    ) -> Self {
+        Self {
+            source_folder: source_folder.into(),
+            setup_file: setup_file.into(),
+            install_args: install_args.into(),
+            uninstall_file: None,
+            uninstall_args: String::new(),
+            app_name: None,
+            success_codes: Vec::new(),
+            tag_folder: DEFAULT_TAG_FOLDER.to_string(),
+            overwrite: false,
+        }
+    }
+
+    /// Set the uninstaller and its arguments.
+    pub fn with_uninstall(
+        mut self,
+        uninstall_file: Option<String>,
+        uninstall_args: impl Into<String>,
+    ) -> Self {
+        self.uninstall_file = uninstall_file;
+        self.uninstall_args = uninstall_args.into();
+        self
+    }
+
+    /// Set the application name.
+    pub fn with_app_name(mut self, name: impl Into<String>) -> Self {
+        self.app_name = Some(name.into());
+        self
+    }
+
+    /// Treat another installer exit code as success.
+    pub fn with_success_code(mut self, code: i32) -> Self {
+        self.success_codes.push(code);
+        self
+    }
+
+    /// Set the folder under `%ProgramData%` receiving the tag file.
+    pub fn with_tag_folder(mut self, folder: impl Into<String>) -> Self {
+        self.tag_folder = folder.into();
+        self
+    }
+
+    /// Replace existing wrappers.
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Application name used for logs and the tag file.
+    pub fn app_name(&self) -> String {
+        self.app_name.clone().unwrap_or_else(|| {
+            Path::new(&self.setup_file)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.setup_file.clone())
+        })
+    }
+
+    /// Installer exit codes treated as success.
+    pub fn all_success_codes(&self) -> Vec<i32> {
+        let mut codes = DEFAULT_SUCCESS_CODES.to_vec();
+        for code in &self.success_codes {
+            if !codes.contains(code) {
+                codes.push(*code);
+            }
+        }
+        codes
+    }
+
+    /// Tag file written on install, as a path for an Intune file detection
+    /// rule.
+    pub fn tag_file(&self) -> String {
+        format!(
+            r"%ProgramData%\{}\{}.tag",
+            self.tag_folder.trim_matches('\\'),
+            self.app_name()
+        )
+    }
+
+    /// Validate the request.
+    pub fn validate(&self) -> PackageResult<()> {
+        if !self.source_folder.is_dir() {
+            return Err(PackageError::SourceFolderNotFound {
+                path: self.source_folder.clone(),
+            });
+        }
+
+        if !self.setup_file.to_ascii_lowercase().ends_with(".exe") {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Wrappers are generated for .exe installers, not '{}'",
+                    self.setup_file
+                ),
+            });
+        }
+        self.check_in_source(&self.setup_file)?;
+        if let Some(uninstall_file) = &self.uninstall_file {
+            if !is_device_path(uninstall_file) {
+                self.check_in_source(uninstall_file)?;
+            }
+        }
+
+        let name = self.app_name();
+        if name.trim().is_empty()
+            || name.contains(INVALID_NAME_CHARS)
+            || name.chars().any(char::is_control)
+        {
+            return Err(PackageError::InvalidArgument {
+                reason: format!("Application name '{}' cannot be used as a file name", name),
+            });
+        }
+        if self.tag_folder.trim_matches('\\').is_empty() || self.tag_folder.contains(':') {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Tag folder '{}' must be a folder under %ProgramData%",
+                    self.tag_folder
+                ),
+            });
+        }
+
+        if !self.overwrite {
+            for script in [INSTALL_SCRIPT, UNINSTALL_SCRIPT] {
+                let path = self.source_folder.join(script);
+                if path.exists() {
+                    return Err(PackageError::OutputFileExists { path });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_in_source(&self, file: &str) -> PackageResult<()> {
+        if self.source_folder.join(file).is_file() {
+            return Ok(());
+        }
+        Err(PackageError::SetupFileNotFound {
+            file: file.to_string(),
+            folder: self.source_folder.clone(),
+            suggestions: suggest_files(&self.source_folder, file),
+        })
+    }
+}
+
+/// Whether `path` names a file on the device rather than in the package:
+/// it has a drive letter, is a UNC path or starts with an environment
+/// variable.
+pub fn is_device_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+        || path.starts_with(r"\\")
+        || path.starts_with('%')
+}
+
+/// Wrappers written by `intune scaffold`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaffoldResult {
+    /// Path of the install wrapper
+    pub install_script: PathBuf,
+    /// Path of the uninstall wrapper
+    pub uninstall_script: PathBuf,
+    /// Application name used for logs and the tag file
+    pub app_name: String,
+    /// Tag file on the device, for a file detection rule
+    pub tag_file: String,
+}
+
+impl ScaffoldResult {
+    /// Install command to enter in Intune.
+    pub fn install_command(&self) -> String {
+        powershell_command(INSTALL_SCRIPT)
+    }
+
+    /// Uninstall command to enter in Intune.
+    pub fn uninstall_command(&self) -> String {
+        powershell_command(UNINSTALL_SCRIPT)
+    }
+}
+
+/// Command running a PowerShell script from the package root.
+pub(crate) fn powershell_command(script: &str) -> String {
+    format!(
+        "powershell.exe -ExecutionPolicy Bypass -NoProfile -File .\\{}",
+        script
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn source() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("setup.exe"), "MZ").unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_scaffold_request_defaults() {
+        let temp = source();
+        let request = ScaffoldRequest::new(temp.path(), "setup.exe", "/S").with_success_code(1707);
+
+        assert!(request.validate().is_ok());
+        assert_eq!(request.app_name(), "setup");
+        assert_eq!(request.all_success_codes(), vec![0, 1707]);
+        assert_eq!(
+            request.tag_file(),
+            r"%ProgramData%\iamawrapper\Tags\setup.tag"
+        );
+    }
+
+    #[test]
+    fn test_scaffold_request_validation() {
+        let temp = source();
+        let request = || ScaffoldRequest::new(temp.path(), "setup.exe", "/S");
+
+        assert!(matches!(
+            ScaffoldRequest::new(temp.path(), "setup.msi", "").validate(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            request()
+                .with_uninstall(Some("uninst.exe".to_string()), "/S")
+                .validate(),
+            Err(PackageError::SetupFileNotFound { .. })
+        ));
+        // Uninstallers already on the device are not looked for
+        assert!(
+            request()
+                .with_uninstall(Some(r"%ProgramFiles%\App\uninst.exe".to_string()), "/S")
+                .validate()
+                .is_ok()
+        );
+        assert!(request().with_app_name("A/B").validate().is_err());
+        assert!(request().with_tag_folder(r"C:\Tags").validate().is_err());
+
+        fs::write(temp.path().join(INSTALL_SCRIPT), "").unwrap();
+        assert!(matches!(
+            request().validate(),
+            Err(PackageError::OutputFileExists { .. })
+        ));
+        assert!(request().with_overwrite(true).validate().is_ok());
+    }
+
+    #[test]
+    fn test_is_device_path() {
+        assert!(is_device_path(r"C:\Program Files\App\uninst.exe"));
+        assert!(is_device_path(r"%ProgramFiles%\App\uninst.exe"));
+        assert!(is_device_path(r"\\server\share\uninst.exe"));
+        assert!(!is_device_path(r"tools\uninst.exe"));
+    }
+}
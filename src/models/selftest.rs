@@ -0,0 +1,47 @@
+//! Results of the `self-test` health check.
+
+use std::time::Duration;
+
+/// How a self-test stage ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageOutcome {
+    /// The stage completed
+    Pass,
+    /// The stage failed with the given error
+    Fail(String),
+    /// The stage was not run because an earlier stage it depends on failed
+    Skipped,
+}
+
+/// One step of the self-test (e.g. `intune create`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestStage {
+    /// Stage name, prefixed with the package format
+    pub name: &'static str,
+    /// How the stage ended
+    pub outcome: StageOutcome,
+    /// Time spent in the stage
+    pub duration: Duration,
+}
+
+/// All stages of a self-test run, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Stage results
+    pub stages: Vec<SelfTestStage>,
+}
+
+impl SelfTestReport {
+    /// Number of stages that failed or were skipped.
+    pub fn failures(&self) -> usize {
+        self.stages
+            .iter()
+            .filter(|s| s.outcome != StageOutcome::Pass)
+            .count()
+    }
+
+    /// Whether every stage passed.
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+}
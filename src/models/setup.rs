@@ -0,0 +1,111 @@
+//! Information read from setup files.
+
+use crate::json::JsonValue;
+
+/// Extension of the setup manifest written next to a package
+/// (`<package>.setup.json`).
+pub const SETUP_MANIFEST_EXTENSION: &str = "setup.json";
+
+/// Strings from the version resource of a Windows executable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExeVersionInfo {
+    /// `ProductName`
+    pub product_name: Option<String>,
+    /// `ProductVersion`
+    pub product_version: Option<String>,
+    /// `FileVersion` (the fixed file version when the string is missing)
+    pub file_version: Option<String>,
+    /// `CompanyName`
+    pub company_name: Option<String>,
+    /// `FileDescription`
+    pub file_description: Option<String>,
+    /// `OriginalFilename`
+    pub original_filename: Option<String>,
+}
+
+impl ExeVersionInfo {
+    /// Whether no string was found.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// One-line summary, e.g. `ACME Tool 1.2.3 (ACME Corp)`.
+    pub fn summary(&self) -> String {
+        let name = self
+            .product_name
+            .as_deref()
+            .or(self.file_description.as_deref())
+            .unwrap_or("Unknown product");
+        let mut summary = name.to_string();
+        if let Some(version) = self.file_version.as_ref().or(self.product_version.as_ref()) {
+            summary.push(' ');
+            summary.push_str(version);
+        }
+        if let Some(company) = &self.company_name {
+            summary.push_str(&format!(" ({})", company));
+        }
+        summary
+    }
+
+    /// The sidecar manifest describing `setup_file`.
+    pub fn to_manifest_json(&self, setup_file: &str) -> String {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => value.clone().into(),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            ("setupFile".to_string(), setup_file.into()),
+            (
+                "versionInfo".to_string(),
+                JsonValue::Object(vec![
+                    ("productName".to_string(), optional(&self.product_name)),
+                    (
+                        "productVersion".to_string(),
+                        optional(&self.product_version),
+                    ),
+                    ("fileVersion".to_string(), optional(&self.file_version)),
+                    ("companyName".to_string(), optional(&self.company_name)),
+                    (
+                        "fileDescription".to_string(),
+                        optional(&self.file_description),
+                    ),
+                    (
+                        "originalFilename".to_string(),
+                        optional(&self.original_filename),
+                    ),
+                ]),
+            ),
+        ])
+        .to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_and_manifest() {
+        let info = ExeVersionInfo {
+            product_name: Some("ACME Tool".to_string()),
+            file_version: Some("1.2.3.4".to_string()),
+            company_name: Some("ACME Corp".to_string()),
+            ..ExeVersionInfo::default()
+        };
+        assert_eq!(info.summary(), "ACME Tool 1.2.3.4 (ACME Corp)");
+        assert!(!info.is_empty());
+
+        let manifest = crate::json::parse(&info.to_manifest_json("setup.exe")).unwrap();
+        assert_eq!(
+            manifest.get("setupFile").and_then(JsonValue::as_str),
+            Some("setup.exe")
+        );
+        let version_info = manifest.get("versionInfo").unwrap();
+        assert_eq!(
+            version_info.get("companyName").and_then(JsonValue::as_str),
+            Some("ACME Corp")
+        );
+        assert_eq!(version_info.get("productVersion"), Some(&JsonValue::Null));
+    }
+}
@@ -0,0 +1,171 @@
+//! Byte sizes with human-readable formatting.
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+use crate::json::JsonValue;
+
+/// Binary units, largest first.
+const UNITS: &[(&str, u64)] = &[
+    ("TiB", 1 << 40),
+    ("GiB", 1 << 30),
+    ("MiB", 1 << 20),
+    ("KiB", 1 << 10),
+];
+
+/// A size in bytes.
+///
+/// `Display` picks the largest binary unit that keeps the value at least 1
+/// (`512 B`, `1.50 KiB`, `10.00 GiB`); use `{:#}` for the exact byte count.
+/// In JSON a size is always the plain byte count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// One kibibyte.
+    pub const KIB: ByteSize = ByteSize(1 << 10);
+    /// One mebibyte.
+    pub const MIB: ByteSize = ByteSize(1 << 20);
+    /// One gibibyte.
+    pub const GIB: ByteSize = ByteSize(1 << 30);
+
+    /// Size from a (possibly fractional) number of gibibytes.
+    pub fn from_gib(gib: f64) -> Self {
+        ByteSize((gib * Self::GIB.0 as f64) as u64)
+    }
+
+    /// Number of bytes.
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+
+    /// Whole kibibytes, rounded down.
+    pub fn kib(self) -> u64 {
+        self.0 / Self::KIB.0
+    }
+
+    /// Read a size from JSON: a byte count, or a string such as `"1.5 GiB"`.
+    pub fn from_json(value: &JsonValue) -> Option<Self> {
+        match value {
+            JsonValue::String(s) => s.parse().ok(),
+            other => other.as_u64().map(ByteSize),
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write!(f, "{} bytes", self.0);
+        }
+
+        match UNITS.iter().find(|(_, factor)| self.0 >= *factor) {
+            Some((unit, factor)) => write!(f, "{:.2} {}", self.0 as f64 / *factor as f64, unit),
+            None => write!(f, "{} B", self.0),
+        }
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    /// Parse `"1024"`, `"512 B"`, `"1.5 GiB"` or `"10GB"` (decimal-looking
+    /// suffixes are treated as binary, matching the rest of the tool).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: f64 = number
+            .parse()
+            .map_err(|_| format!("Invalid size '{}'", s))?;
+
+        let factor = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => Self::KIB.0,
+            "m" | "mb" | "mib" => Self::MIB.0,
+            "g" | "gb" | "gib" => Self::GIB.0,
+            "t" | "tb" | "tib" => 1 << 40,
+            other => return Err(format!("Unknown size unit '{}'", other)),
+        };
+
+        Ok(ByteSize((number * factor as f64) as u64))
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+}
+
+impl From<ByteSize> for u64 {
+    fn from(size: ByteSize) -> Self {
+        size.0
+    }
+}
+
+impl From<ByteSize> for JsonValue {
+    fn from(size: ByteSize) -> Self {
+        size.0.into()
+    }
+}
+
+impl Add for ByteSize {
+    type Output = ByteSize;
+
+    fn add(self, rhs: ByteSize) -> ByteSize {
+        ByteSize(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for ByteSize {
+    fn add_assign(&mut self, rhs: ByteSize) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sum for ByteSize {
+    fn sum<I: Iterator<Item = ByteSize>>(iter: I) -> Self {
+        ByteSize(iter.map(|s| s.0).sum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ByteSize(0).to_string(), "0 B");
+        assert_eq!(ByteSize(1023).to_string(), "1023 B");
+        assert_eq!(ByteSize(1536).to_string(), "1.50 KiB");
+        assert_eq!(ByteSize(5 * 1024 * 1024).to_string(), "5.00 MiB");
+        assert_eq!(ByteSize::from_gib(10.0).to_string(), "10.00 GiB");
+        assert_eq!(format!("{:#}", ByteSize(1536)), "1536 bytes");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1024".parse(), Ok(ByteSize(1024)));
+        assert_eq!("1.5 KiB".parse(), Ok(ByteSize(1536)));
+        assert_eq!("10GB".parse(), Ok(ByteSize::from_gib(10.0)));
+        assert!("12 parsecs".parse::<ByteSize>().is_err());
+        assert!("GiB".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let size = ByteSize(123_456);
+        let json = JsonValue::from(size);
+        assert_eq!(json, JsonValue::Number(123_456.0));
+        assert_eq!(ByteSize::from_json(&json), Some(size));
+        assert_eq!(
+            ByteSize::from_json(&JsonValue::from("2 MiB")),
+            Some(ByteSize(2 * 1024 * 1024))
+        );
+    }
+}
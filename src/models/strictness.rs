@@ -0,0 +1,187 @@
+//! Strict mode: which warnings are promoted to errors.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::error::{PackageError, PackageResult};
+
+/// A check that warns by default and can be made fatal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// Warn-mode blocklist matches (`--blocklist-warn`)
+    Blocklist,
+    /// setuid/setgid/sticky bits stripped from a macOS payload
+    SpecialModes,
+    /// A signature lost or invalidated when repacking a macOS package
+    Signature,
+    /// Named pipes, sockets, devices or symlink loops left out of a package
+    SpecialFiles,
+    /// Content paths too long or named in ways Windows clients reject
+    WindowsPaths,
+    /// A package projected to exceed its size limit
+    PackageSize,
+}
+
+impl Check {
+    /// All checks, in documentation order.
+    pub const ALL: &'static [Check] = &[
+        Check::Blocklist,
+        Check::SpecialModes,
+        Check::Signature,
+        Check::SpecialFiles,
+        Check::WindowsPaths,
+        Check::PackageSize,
+    ];
+
+    /// Name used in config files and error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Blocklist => "blocklist",
+            Check::SpecialModes => "special-modes",
+            Check::Signature => "signature",
+            Check::SpecialFiles => "special-files",
+            Check::WindowsPaths => "windows-paths",
+            Check::PackageSize => "package-size",
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Check::ALL
+            .iter()
+            .copied()
+            .find(|c| c.name() == s)
+            .ok_or_else(|| format!("Unknown check '{}'", s))
+    }
+}
+
+/// How a check reports a problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckLevel {
+    /// Report and continue
+    Warn,
+    /// Fail the run
+    Error,
+}
+
+impl FromStr for CheckLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(CheckLevel::Warn),
+            "error" => Ok(CheckLevel::Error),
+            other => Err(format!(
+                "Invalid check level '{}' (expected 'warn' or 'error')",
+                other
+            )),
+        }
+    }
+}
+
+/// Strictness of a run: a global switch plus per-check overrides.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Strictness {
+    /// Promote every warning to an error (`--strict`)
+    pub strict: bool,
+    /// Per-check levels that take precedence over `strict`
+    pub overrides: Vec<(Check, CheckLevel)>,
+}
+
+impl Strictness {
+    /// Strict mode with no overrides.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Set the level of a single check.
+    pub fn with_override(mut self, check: Check, level: CheckLevel) -> Self {
+        self.overrides.retain(|(c, _)| *c != check);
+        self.overrides.push((check, level));
+        self
+    }
+
+    /// Effective level of `check`.
+    pub fn level(&self, check: Check) -> CheckLevel {
+        self.overrides
+            .iter()
+            .find(|(c, _)| *c == check)
+            .map(|(_, level)| *level)
+            .unwrap_or(if self.strict {
+                CheckLevel::Error
+            } else {
+                CheckLevel::Warn
+            })
+    }
+
+    /// Whether `check` fails the run.
+    pub fn is_error(&self, check: Check) -> bool {
+        self.level(check) == CheckLevel::Error
+    }
+
+    /// Fail with [`PackageError::StrictViolation`] if `check` is an error.
+    ///
+    /// The message is only built when it is needed.
+    pub fn enforce(&self, check: Check, message: impl FnOnce() -> String) -> PackageResult<()> {
+        if self.is_error(check) {
+            return Err(PackageError::StrictViolation {
+                check: check.name(),
+                message: message(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_and_overrides() {
+        let standard = Strictness::default();
+        assert_eq!(standard.level(Check::SpecialModes), CheckLevel::Warn);
+        assert!(
+            standard
+                .enforce(Check::Signature, || unreachable!())
+                .is_ok()
+        );
+
+        let strict = Strictness::strict().with_override(Check::Signature, CheckLevel::Warn);
+        assert_eq!(strict.level(Check::SpecialModes), CheckLevel::Error);
+        assert_eq!(strict.level(Check::Signature), CheckLevel::Warn);
+        assert!(matches!(
+            strict.enforce(Check::Blocklist, || "matched".to_string()),
+            Err(PackageError::StrictViolation {
+                check: "blocklist",
+                ..
+            })
+        ));
+
+        let one = Strictness::default().with_override(Check::Blocklist, CheckLevel::Error);
+        assert!(one.is_error(Check::Blocklist));
+        assert!(!one.is_error(Check::SpecialModes));
+    }
+
+    #[test]
+    fn test_parse_names() {
+        for check in Check::ALL {
+            assert_eq!(check.name().parse::<Check>(), Ok(*check));
+        }
+        assert!("paths".parse::<Check>().is_err());
+        assert_eq!("error".parse::<CheckLevel>(), Ok(CheckLevel::Error));
+        assert!("fatal".parse::<CheckLevel>().is_err());
+    }
+}
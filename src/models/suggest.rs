@@ -0,0 +1,134 @@
+//! "Did you mean" suggestions for mistyped file names.
+
+use std::path::Path;
+
+use walkdir::WalkDir;
+
+/// Maximum number of suggestions returned.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Maximum number of files inspected when scanning a folder.
+const MAX_CANDIDATES: usize = 10_000;
+
+/// Minimum normalized similarity for an edit-distance match.
+const MIN_SIMILARITY: f64 = 0.7;
+
+/// Suggest files in `folder` that the user may have meant by `wanted`.
+pub fn suggest_files(folder: &Path, wanted: &str) -> Vec<String> {
+    let candidates = WalkDir::new(folder)
+        .max_depth(4)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .take(MAX_CANDIDATES)
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(folder)
+                .ok()
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+        });
+
+    rank_suggestions(wanted, candidates)
+}
+
+/// Rank candidate relative paths by how likely they are what `wanted` meant.
+///
+/// Case differences rank first, then a missing extension, then the same
+/// file name in another folder, then similar names by edit distance.
+pub fn rank_suggestions<I>(wanted: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let wanted = wanted.replace('\\', "/");
+    let wanted_lower = wanted.to_lowercase();
+    let wanted_name = file_name(&wanted_lower);
+
+    let mut scored: Vec<(u8, f64, String)> = candidates
+        .into_iter()
+        .filter(|c| *c != wanted)
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let name = file_name(&lower);
+            let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+
+            let (rank, similarity) = if lower == wanted_lower {
+                (0, 1.0)
+            } else if stem == wanted_name && name != stem {
+                (1, 1.0)
+            } else if name == wanted_name {
+                (2, 1.0)
+            } else {
+                let similarity = strsim::normalized_damerau_levenshtein(name, wanted_name);
+                if similarity < MIN_SIMILARITY {
+                    return None;
+                }
+                (3, similarity)
+            };
+
+            Some((rank, similarity, candidate))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(b.1.total_cmp(&a.1))
+            .then_with(|| a.2.cmp(&b.2))
+    });
+
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, candidate)| candidate)
+        .collect()
+}
+
+/// Format suggestions for appending to an error message.
+pub fn format_suggestions(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" (did you mean '{}'?)", only),
+        [rest @ .., last] => {
+            let rest: Vec<String> = rest.iter().map(|s| format!("'{}'", s)).collect();
+            format!(" (did you mean {} or '{}'?)", rest.join(", "), last)
+        }
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let files = candidates(&["Setup.EXE", "setup.msi", "bin/setup", "readme.txt"]);
+
+        assert_eq!(rank_suggestions("setup.exe", files.clone())[0], "Setup.EXE");
+        assert_eq!(
+            rank_suggestions("setup", files.clone()),
+            vec!["Setup.EXE", "setup.msi", "bin/setup"]
+        );
+        assert_eq!(rank_suggestions("setpu.msi", files.clone())[0], "setup.msi");
+        assert!(rank_suggestions("uninstall.ps1", files).is_empty());
+    }
+
+    #[test]
+    fn test_format_suggestions() {
+        assert_eq!(format_suggestions(&[]), "");
+        assert_eq!(
+            format_suggestions(&candidates(&["a.exe"])),
+            " (did you mean 'a.exe'?)"
+        );
+        assert_eq!(
+            format_suggestions(&candidates(&["a.exe", "b.exe", "c.exe"])),
+            " (did you mean 'a.exe', 'b.exe' or 'c.exe'?)"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Calendar dates for Unix timestamps.
+
+/// Year, month, day, hour, minute and second of Unix time `secs` (UTC).
+pub fn civil_time(secs: u64) -> [u64; 6] {
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86_400;
+    [
+        year as u64,
+        month as u64,
+        day as u64,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+    ]
+}
+
+/// ISO 8601 timestamp of Unix time `secs`, e.g. `2023-11-14T22:13:20Z`.
+pub fn iso8601(secs: u64) -> String {
+    let [year, month, day, hour, minute, second] = civil_time(secs);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Unix time of an ISO 8601 UTC timestamp like `2023-11-14T22:13:20Z`;
+/// fractional seconds are ignored.
+pub fn parse_iso8601(text: &str) -> Option<u64> {
+    let text = text.strip_suffix('Z')?;
+    let (date, time) = text.split_once('T')?;
+    let time = time.split('.').next()?;
+    let date: Vec<u64> = date
+        .split('-')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let (&[year, month, day], &[hour, minute, second]) = (&date[..], &time[..]) else {
+        return None;
+    };
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    // Days since the epoch from the civil date (Howard Hinnant's algorithm)
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_time() {
+        assert_eq!(civil_time(0), [1970, 1, 1, 0, 0, 0]);
+        assert_eq!(civil_time(1_700_000_000), [2023, 11, 14, 22, 13, 20]);
+        assert_eq!(iso8601(951_782_400), "2000-02-29T00:00:00Z");
+    }
+
+    #[test]
+    fn test_parse_iso8601() {
+        for secs in [0, 951_782_400, 1_700_000_000, 4_102_444_799] {
+            assert_eq!(parse_iso8601(&iso8601(secs)), Some(secs));
+        }
+        assert_eq!(parse_iso8601("2023-11-14T22:13:20.5Z"), Some(1_700_000_000));
+        assert_eq!(parse_iso8601("2023-11-14 22:13:20"), None);
+        assert_eq!(parse_iso8601("2023-13-01T00:00:00Z"), None);
+    }
+}
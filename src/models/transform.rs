@@ -0,0 +1,206 @@
+//! Package-time file transformations.
+//!
+//! A [`TransformRule`] pairs a glob with a [`FileTransformer`]; matching
+//! files are rewritten as they are packaged and every change is recorded
+//! as a [`TransformRecord`] in the package.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::models::glob::Glob;
+
+/// Rewrites the contents of a file as it is packaged.
+pub trait FileTransformer: fmt::Debug + Send + Sync {
+    /// Short description recorded in the package (e.g. `crlf`).
+    fn name(&self) -> String;
+
+    /// Return the new contents of the file at `path`.
+    fn transform(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>, String>;
+}
+
+/// Line ending style for [`LineEndings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\r\n`
+    Crlf,
+    /// `\n`
+    Lf,
+}
+
+/// Normalize every line ending to one style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEndings(pub LineEnding);
+
+impl FileTransformer for LineEndings {
+    fn name(&self) -> String {
+        match self.0 {
+            LineEnding::Crlf => "crlf".to_string(),
+            LineEnding::Lf => "lf".to_string(),
+        }
+    }
+
+    fn transform(&self, _path: &Path, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(data.len() + data.len() / 32);
+        let mut bytes = data.iter().copied().peekable();
+        while let Some(byte) = bytes.next() {
+            let newline = match byte {
+                b'\r' => {
+                    bytes.next_if_eq(&b'\n');
+                    true
+                }
+                b'\n' => true,
+                _ => false,
+            };
+            if !newline {
+                out.push(byte);
+            } else if self.0 == LineEnding::Crlf {
+                out.extend_from_slice(b"\r\n");
+            } else {
+                out.push(b'\n');
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Replace every occurrence of a placeholder, e.g. stamping a version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceText {
+    /// Text to look for
+    pub from: String,
+    /// Replacement
+    pub to: String,
+}
+
+impl FileTransformer for ReplaceText {
+    fn name(&self) -> String {
+        format!("replace:{}={}", self.from, self.to)
+    }
+
+    fn transform(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        let text =
+            String::from_utf8(data).map_err(|_| format!("{} is not UTF-8 text", path.display()))?;
+        Ok(text.replace(&self.from, &self.to).into_bytes())
+    }
+}
+
+/// A transformer applied to the files matching a glob.
+#[derive(Debug, Clone)]
+pub struct TransformRule {
+    /// Files the rule applies to
+    pub glob: Glob,
+    /// The transformation
+    pub transformer: Arc<dyn FileTransformer>,
+}
+
+impl TransformRule {
+    /// Apply `transformer` to files matching `glob`.
+    pub fn new(glob: Glob, transformer: impl FileTransformer + 'static) -> Self {
+        Self {
+            glob,
+            transformer: Arc::new(transformer),
+        }
+    }
+
+    /// Parse a built-in rule: `GLOB=crlf`, `GLOB=lf` or
+    /// `GLOB=replace:FROM=TO`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (pattern, transform) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("expected GLOB=TRANSFORM, got '{}'", spec))?;
+        let glob = Glob::new(pattern.trim())?;
+
+        match transform {
+            "crlf" => Ok(Self::new(glob, LineEndings(LineEnding::Crlf))),
+            "lf" => Ok(Self::new(glob, LineEndings(LineEnding::Lf))),
+            _ => match transform.strip_prefix("replace:") {
+                Some(replace) => {
+                    let (from, to) = replace
+                        .split_once('=')
+                        .filter(|(from, _)| !from.is_empty())
+                        .ok_or_else(|| format!("expected replace:FROM=TO, got '{}'", transform))?;
+                    Ok(Self::new(
+                        glob,
+                        ReplaceText {
+                            from: from.to_string(),
+                            to: to.to_string(),
+                        },
+                    ))
+                }
+                None => Err(format!(
+                    "unknown transform '{}' (expected crlf, lf or replace:FROM=TO)",
+                    transform
+                )),
+            },
+        }
+    }
+}
+
+/// A file changed by transformation, recorded in the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformRecord {
+    /// Path relative to the content root
+    pub path: PathBuf,
+    /// Names of the transformers applied, in order
+    pub transformers: Vec<String>,
+    /// SHA-256 of the source file
+    pub original_sha256: String,
+    /// SHA-256 of the packaged file
+    pub sha256: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_endings() {
+        let crlf = LineEndings(LineEnding::Crlf);
+        let lf = LineEndings(LineEnding::Lf);
+        let path = Path::new("a.ps1");
+
+        assert_eq!(
+            crlf.transform(path, b"a\nb\r\nc\rd".to_vec()).unwrap(),
+            b"a\r\nb\r\nc\r\nd"
+        );
+        assert_eq!(lf.transform(path, b"a\r\nb\n".to_vec()).unwrap(), b"a\nb\n");
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rule = TransformRule::parse("config.json=replace:@VERSION@=1.2.3").unwrap();
+        assert_eq!(rule.glob.as_str(), "config.json");
+        assert_eq!(rule.transformer.name(), "replace:@VERSION@=1.2.3");
+        assert_eq!(
+            rule.transformer
+                .transform(Path::new("config.json"), br#"{"v":"@VERSION@"}"#.to_vec())
+                .unwrap(),
+            br#"{"v":"1.2.3"}"#
+        );
+
+        assert_eq!(
+            TransformRule::parse("*.ps1=crlf")
+                .unwrap()
+                .transformer
+                .name(),
+            "crlf"
+        );
+        assert!(TransformRule::parse("*.ps1").is_err());
+        assert!(TransformRule::parse("*.ps1=upper").is_err());
+        assert!(TransformRule::parse("*.ps1=replace:=x").is_err());
+    }
+
+    #[test]
+    fn test_replace_rejects_binary() {
+        let replace = ReplaceText {
+            from: "a".to_string(),
+            to: "b".to_string(),
+        };
+        assert!(
+            replace
+                .transform(Path::new("x.bin"), vec![0xff, 0xfe])
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Post-creation validation, `intune verify` and `macos validate` results.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::json::JsonValue;
+
+/// Default time box for post-creation validation.
+pub const DEFAULT_VALIDATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Checks that passed when re-opening a produced package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Package that was validated
+    pub path: PathBuf,
+    /// Names of the checks that passed, in the order they ran
+    pub checks: Vec<&'static str>,
+    /// Number of entries found in the package content
+    pub entry_count: usize,
+    /// Time spent validating
+    pub duration: Duration,
+}
+
+/// How a verification check ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The check passed
+    Pass,
+    /// The check failed for the given reason
+    Fail(String),
+    /// The check was not run because an earlier check it depends on failed
+    Skipped,
+}
+
+/// One check of a package verification (e.g. `hmac`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyCheck {
+    /// Check name
+    pub name: &'static str,
+    /// How the check ended
+    pub outcome: CheckOutcome,
+}
+
+/// Every check run against a package, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyResult {
+    /// Package that was verified
+    pub path: PathBuf,
+    /// Check results
+    pub checks: Vec<VerifyCheck>,
+    /// Setup file named in Detection.xml, when it could be read
+    pub setup_file: Option<String>,
+    /// Number of entries in the decrypted content
+    pub entry_count: usize,
+    /// Time spent verifying
+    pub duration: Duration,
+}
+
+impl VerifyResult {
+    /// Number of checks that failed or were skipped.
+    pub fn failures(&self) -> usize {
+        count_failures(&self.checks)
+    }
+
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+
+    /// Name and reason of the first failed check.
+    pub fn first_failure(&self) -> Option<(&'static str, &str)> {
+        first_failure(&self.checks)
+    }
+
+    /// Serialize the result as JSON.
+    pub fn to_json(&self) -> String {
+        let setup_file = match &self.setup_file {
+            Some(setup_file) => setup_file.clone().into(),
+            None => JsonValue::Null,
+        };
+
+        JsonValue::Object(vec![
+            (
+                "path".to_string(),
+                self.path.to_string_lossy().into_owned().into(),
+            ),
+            ("passed".to_string(), self.passed().into()),
+            ("checks".to_string(), checks_json(&self.checks)),
+            ("setup_file".to_string(), setup_file),
+            ("entry_count".to_string(), (self.entry_count as u64).into()),
+        ])
+        .to_pretty_string()
+    }
+}
+
+/// Every check `macos validate` ran against a `.pkg`, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PkgValidationReport {
+    /// Package that was validated
+    pub path: PathBuf,
+    /// Check results
+    pub checks: Vec<VerifyCheck>,
+    /// Identifier and version of each component, from its PackageInfo
+    pub components: Vec<(String, String)>,
+    /// Number of entries in the XAR archive
+    pub entry_count: usize,
+    /// Number of entries in the payloads of all components
+    pub payload_entries: usize,
+    /// Time spent validating
+    pub duration: Duration,
+}
+
+impl PkgValidationReport {
+    /// Number of checks that failed or were skipped.
+    pub fn failures(&self) -> usize {
+        count_failures(&self.checks)
+    }
+
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.failures() == 0
+    }
+
+    /// Name and reason of the first failed check.
+    pub fn first_failure(&self) -> Option<(&'static str, &str)> {
+        first_failure(&self.checks)
+    }
+
+    /// Serialize the report as JSON.
+    pub fn to_json(&self) -> String {
+        let components = self
+            .components
+            .iter()
+            .map(|(identifier, version)| {
+                JsonValue::Object(vec![
+                    ("identifier".to_string(), identifier.as_str().into()),
+                    ("version".to_string(), version.as_str().into()),
+                ])
+            })
+            .collect();
+
+        JsonValue::Object(vec![
+            (
+                "path".to_string(),
+                self.path.to_string_lossy().into_owned().into(),
+            ),
+            ("passed".to_string(), self.passed().into()),
+            ("checks".to_string(), checks_json(&self.checks)),
+            ("components".to_string(), JsonValue::Array(components)),
+            ("entry_count".to_string(), (self.entry_count as u64).into()),
+            (
+                "payload_entries".to_string(),
+                (self.payload_entries as u64).into(),
+            ),
+        ])
+        .to_pretty_string()
+    }
+}
+
+fn count_failures(checks: &[VerifyCheck]) -> usize {
+    checks
+        .iter()
+        .filter(|c| c.outcome != CheckOutcome::Pass)
+        .count()
+}
+
+fn first_failure(checks: &[VerifyCheck]) -> Option<(&'static str, &str)> {
+    checks.iter().find_map(|c| match &c.outcome {
+        CheckOutcome::Fail(reason) => Some((c.name, reason.as_str())),
+        _ => None,
+    })
+}
+
+fn checks_json(checks: &[VerifyCheck]) -> JsonValue {
+    JsonValue::Array(
+        checks
+            .iter()
+            .map(|check| {
+                let (outcome, reason) = match &check.outcome {
+                    CheckOutcome::Pass => ("pass", JsonValue::Null),
+                    CheckOutcome::Fail(reason) => ("fail", reason.clone().into()),
+                    CheckOutcome::Skipped => ("skipped", JsonValue::Null),
+                };
+                JsonValue::Object(vec![
+                    ("name".to_string(), check.name.into()),
+                    ("outcome".to_string(), outcome.into()),
+                    ("reason".to_string(), reason),
+                ])
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,279 @@
+//! Watching a drop folder and packaging what appears in it (`watch`).
+//!
+//! Each subfolder of the drop folder is one application. Once a subfolder
+//! holds a setup file and has stopped changing, it is packaged into the
+//! output folder and moved to `.processed` (or `.failed`) in the drop
+//! folder. Rules come from the `watch` section of the configuration file:
+//!
+//! ```json
+//! {
+//!   "watch": {
+//!     "setup_files": ["setup.exe", "*.msi"],
+//!     "exclude": ["*.pdb"],
+//!     "content_tags": { "source": "drop-share" },
+//!     "poll_seconds": 5,
+//!     "settle_seconds": 30
+//!   }
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::json::JsonValue;
+use crate::models::batch::BatchOutcome;
+use crate::models::glob::Glob;
+use crate::models::package::Verbosity;
+use crate::models::strictness::Strictness;
+
+/// Setup files looked for when the configuration names none, in order.
+pub const DEFAULT_SETUP_FILES: &[&str] = &["setup.exe", "install.ps1", "*.msi", "*.exe"];
+
+/// Folder of the drop folder receiving packaged applications.
+pub const PROCESSED_FOLDER: &str = ".processed";
+
+/// Folder of the drop folder receiving applications that failed.
+pub const FAILED_FOLDER: &str = ".failed";
+
+/// How drop folder subfolders are recognized and packaged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchRules {
+    /// Patterns for the setup file, matched against the files at the top
+    /// of a subfolder; the first pattern with a match wins
+    pub setup_files: Vec<Glob>,
+    /// Source files left out of every package
+    pub exclude: Vec<Glob>,
+    /// Content tags added to every package
+    pub content_tags: Vec<(String, String)>,
+    /// Time between two scans of the drop folder
+    pub poll: Duration,
+    /// How long a subfolder must stay unchanged before it is packaged
+    pub settle: Duration,
+}
+
+impl Default for WatchRules {
+    fn default() -> Self {
+        Self {
+            setup_files: DEFAULT_SETUP_FILES
+                .iter()
+                .map(|pattern| Glob::new(pattern).expect("valid default pattern"))
+                .collect(),
+            exclude: Vec::new(),
+            content_tags: Vec::new(),
+            poll: Duration::from_secs(5),
+            settle: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WatchRules {
+    /// Read the `watch` section of a configuration file.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let section = value.as_object().ok_or("'watch' must be an object")?;
+        let mut rules = Self::default();
+
+        for (key, value) in section {
+            match key.as_str() {
+                "setup_files" => rules.setup_files = parse_globs(key, value)?,
+                "exclude" => rules.exclude = parse_globs(key, value)?,
+                "content_tags" => {
+                    let tags = value
+                        .as_object()
+                        .ok_or("'watch.content_tags' must be an object of strings")?;
+                    rules.content_tags = tags
+                        .iter()
+                        .map(|(key, value)| {
+                            value
+                                .as_str()
+                                .map(|value| (key.clone(), value.to_string()))
+                                .ok_or_else(|| format!("Content tag '{}' must be a string", key))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "poll_seconds" => {
+                    rules.poll = Duration::from_secs(parse_seconds(key, value)?.max(1));
+                }
+                "settle_seconds" => {
+                    rules.settle = Duration::from_secs(parse_seconds(key, value)?);
+                }
+                _ => return Err(format!("Unknown 'watch' setting '{}'", key)),
+            }
+        }
+
+        if rules.setup_files.is_empty() {
+            return Err("'watch.setup_files' must name at least one pattern".to_string());
+        }
+        Ok(rules)
+    }
+}
+
+impl From<&WatchRules> for JsonValue {
+    fn from(rules: &WatchRules) -> Self {
+        let globs = |globs: &[Glob]| {
+            JsonValue::Array(globs.iter().map(|glob| glob.as_str().into()).collect())
+        };
+        let tags = rules
+            .content_tags
+            .iter()
+            .map(|(key, value)| (key.clone(), value.as_str().into()))
+            .collect();
+
+        JsonValue::Object(vec![
+            ("setup_files".to_string(), globs(&rules.setup_files)),
+            ("exclude".to_string(), globs(&rules.exclude)),
+            ("content_tags".to_string(), JsonValue::Object(tags)),
+            ("poll_seconds".to_string(), rules.poll.as_secs().into()),
+            ("settle_seconds".to_string(), rules.settle.as_secs().into()),
+        ])
+    }
+}
+
+fn parse_globs(key: &str, value: &JsonValue) -> Result<Vec<Glob>, String> {
+    value
+        .as_array()
+        .ok_or_else(|| format!("'watch.{}' must be an array of patterns", key))?
+        .iter()
+        .map(|pattern| {
+            pattern
+                .as_str()
+                .ok_or_else(|| format!("'watch.{}' must be an array of patterns", key))
+                .and_then(Glob::new)
+        })
+        .collect()
+}
+
+fn parse_seconds(key: &str, value: &JsonValue) -> Result<u64, String> {
+    value
+        .as_u64()
+        .ok_or_else(|| format!("'watch.{}' must be a whole number of seconds", key))
+}
+
+/// Request to watch a drop folder.
+#[derive(Debug, Clone)]
+pub struct WatchRequest {
+    /// Folder whose subfolders are packaged
+    pub drop_folder: PathBuf,
+    /// Folder receiving packages and per-job logs
+    pub output_folder: PathBuf,
+    /// How subfolders are recognized and packaged
+    pub rules: WatchRules,
+    /// Strict mode settings applied to every package
+    pub strictness: Strictness,
+    /// Verbosity level of the watcher itself (packages are built silently)
+    pub verbosity: Verbosity,
+    /// Package what is in the drop folder now, without waiting for it to
+    /// settle, and return
+    pub once: bool,
+}
+
+impl WatchRequest {
+    /// Create a request with the default rules.
+    pub fn new(drop_folder: impl Into<PathBuf>, output_folder: impl Into<PathBuf>) -> Self {
+        Self {
+            drop_folder: drop_folder.into(),
+            output_folder: output_folder.into(),
+            rules: WatchRules::default(),
+            strictness: Strictness::default(),
+            verbosity: Verbosity::Normal,
+            once: false,
+        }
+    }
+
+    /// Set the packaging rules.
+    pub fn with_rules(mut self, rules: WatchRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Set strict mode for every package.
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Set the verbosity level.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Process the drop folder once instead of watching it.
+    pub fn with_once(mut self, once: bool) -> Self {
+        self.once = once;
+        self
+    }
+}
+
+/// Result of packaging one drop folder subfolder.
+#[derive(Debug)]
+pub struct WatchJob {
+    /// Name of the subfolder, also used for the package and log
+    pub name: String,
+    /// Setup file picked by the rules
+    pub setup_file: Option<String>,
+    /// Package written, or why none was
+    pub outcome: BatchOutcome,
+    /// Where the subfolder was moved (`None` if it could not be moved)
+    pub archived_to: Option<PathBuf>,
+    /// Log of the job in the output folder
+    pub log_path: PathBuf,
+    /// Time spent on the job
+    pub duration: Duration,
+}
+
+impl WatchJob {
+    /// Whether the package was written.
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, BatchOutcome::Packaged { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_parse_watch_rules() {
+        let value = json::parse(
+            r#"{"setup_files": ["*.msi"], "exclude": ["*.pdb"],
+                "content_tags": {"source": "drop"}, "poll_seconds": 0, "settle_seconds": 30}"#,
+        )
+        .unwrap();
+        let rules = WatchRules::from_json_value(&value).unwrap();
+
+        assert_eq!(rules.setup_files, vec![Glob::new("*.msi").unwrap()]);
+        assert_eq!(rules.exclude.len(), 1);
+        assert_eq!(
+            rules.content_tags,
+            vec![("source".to_string(), "drop".to_string())]
+        );
+        // Polling is never faster than once a second
+        assert_eq!(rules.poll, Duration::from_secs(1));
+        assert_eq!(rules.settle, Duration::from_secs(30));
+        assert_eq!(
+            WatchRules::from_json_value(&JsonValue::from(&rules)),
+            Ok(rules)
+        );
+    }
+
+    #[test]
+    fn test_parse_watch_rules_errors() {
+        for text in [
+            "[]",
+            r#"{"setup_files": []}"#,
+            r#"{"setup_files": "*.msi"}"#,
+            r#"{"exclude": ["a/**b"]}"#,
+            r#"{"content_tags": {"a": 1}}"#,
+            r#"{"settle_seconds": "10"}"#,
+            r#"{"pattern": "*.msi"}"#,
+        ] {
+            let value = json::parse(text).unwrap();
+            assert!(
+                WatchRules::from_json_value(&value).is_err(),
+                "{} should be rejected",
+                text
+            );
+        }
+    }
+}
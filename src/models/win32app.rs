@@ -0,0 +1,851 @@
+//! Win32 app manifests: the Microsoft Graph `win32LobApp` body for an
+//! `.intunewin` (`intune manifest`).
+//!
+//! The setup file, package name and detection rules come from the package;
+//! everything else from the `app` section of the configuration file, with
+//! command-line flags taking precedence:
+//!
+//! ```json
+//! {
+//!   "app": {
+//!     "publisher": "ACME",
+//!     "install_command": "setup.exe /S",
+//!     "uninstall_command": "\"%ProgramFiles%\\Acme\\uninstall.exe\" /S",
+//!     "architectures": ["x64"],
+//!     "return_codes": { "0": "success", "3010": "soft-reboot" }
+//!   }
+//! }
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::json::JsonValue;
+use crate::models::detection::{DetectionRule, DetectionRules};
+
+/// Minimum Windows 10/11 release required unless configured.
+pub const DEFAULT_MINIMUM_WINDOWS_RELEASE: &str = "1607";
+
+/// Minutes Intune lets the installer run unless configured.
+pub const DEFAULT_MAX_RUN_TIME_MINUTES: u32 = 60;
+
+/// Account the install and uninstall commands run as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InstallContext {
+    #[default]
+    System,
+    User,
+}
+
+impl InstallContext {
+    /// Name used on the command line and in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstallContext::System => "system",
+            InstallContext::User => "user",
+        }
+    }
+}
+
+impl FromStr for InstallContext {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(InstallContext::System),
+            "user" => Ok(InstallContext::User),
+            other => Err(format!(
+                "Invalid install context '{}' (expected 'system' or 'user')",
+                other
+            )),
+        }
+    }
+}
+
+/// What the device does about restarts after installation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartBehavior {
+    /// Follow the return code's type
+    #[default]
+    BasedOnReturnCode,
+    /// Let the installer restart the device
+    Allow,
+    /// Never restart
+    Suppress,
+    /// Always restart
+    Force,
+}
+
+impl RestartBehavior {
+    /// Name used on the command line and in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RestartBehavior::BasedOnReturnCode => "return-code",
+            RestartBehavior::Allow => "allow",
+            RestartBehavior::Suppress => "suppress",
+            RestartBehavior::Force => "force",
+        }
+    }
+
+    /// Value of Graph's `deviceRestartBehavior`.
+    fn graph_name(&self) -> &'static str {
+        match self {
+            RestartBehavior::BasedOnReturnCode => "basedOnReturnCode",
+            RestartBehavior::Allow => "allow",
+            RestartBehavior::Suppress => "suppress",
+            RestartBehavior::Force => "force",
+        }
+    }
+}
+
+impl FromStr for RestartBehavior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "return-code" => Ok(RestartBehavior::BasedOnReturnCode),
+            "allow" => Ok(RestartBehavior::Allow),
+            "suppress" => Ok(RestartBehavior::Suppress),
+            "force" => Ok(RestartBehavior::Force),
+            other => Err(format!(
+                "Invalid restart behavior '{}' (expected 'return-code', 'allow', \
+                 'suppress' or 'force')",
+                other
+            )),
+        }
+    }
+}
+
+/// Processor architecture a Win32 app can be installed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    X86,
+    X64,
+    Arm64,
+}
+
+impl Architecture {
+    /// Name used on the command line, in the configuration file and by
+    /// Graph.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Architecture::X86 => "x86",
+            Architecture::X64 => "x64",
+            Architecture::Arm64 => "arm64",
+        }
+    }
+}
+
+impl FromStr for Architecture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "x86" => Ok(Architecture::X86),
+            "x64" | "amd64" => Ok(Architecture::X64),
+            "arm64" => Ok(Architecture::Arm64),
+            other => Err(format!(
+                "Invalid architecture '{}' (expected 'x86', 'x64' or 'arm64')",
+                other
+            )),
+        }
+    }
+}
+
+/// How Intune reports an installer exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnCodeType {
+    Success,
+    SoftReboot,
+    HardReboot,
+    Retry,
+    Failed,
+}
+
+impl ReturnCodeType {
+    /// Name used on the command line and in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReturnCodeType::Success => "success",
+            ReturnCodeType::SoftReboot => "soft-reboot",
+            ReturnCodeType::HardReboot => "hard-reboot",
+            ReturnCodeType::Retry => "retry",
+            ReturnCodeType::Failed => "failed",
+        }
+    }
+
+    /// Value of Graph's `win32LobAppReturnCode.type`.
+    fn graph_name(&self) -> &'static str {
+        match self {
+            ReturnCodeType::Success => "success",
+            ReturnCodeType::SoftReboot => "softReboot",
+            ReturnCodeType::HardReboot => "hardReboot",
+            ReturnCodeType::Retry => "retry",
+            ReturnCodeType::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for ReturnCodeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(ReturnCodeType::Success),
+            "soft-reboot" => Ok(ReturnCodeType::SoftReboot),
+            "hard-reboot" => Ok(ReturnCodeType::HardReboot),
+            "retry" => Ok(ReturnCodeType::Retry),
+            "failed" => Ok(ReturnCodeType::Failed),
+            other => Err(format!(
+                "Invalid return code type '{}' (expected 'success', 'soft-reboot', \
+                 'hard-reboot', 'retry' or 'failed')",
+                other
+            )),
+        }
+    }
+}
+
+/// An installer exit code and how Intune reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReturnCode {
+    pub code: i32,
+    pub kind: ReturnCodeType,
+}
+
+impl ReturnCode {
+    /// The return codes Intune assigns to a new Win32 app.
+    pub fn defaults() -> Vec<Self> {
+        [
+            (0, ReturnCodeType::Success),
+            (1707, ReturnCodeType::Success),
+            (3010, ReturnCodeType::SoftReboot),
+            (1641, ReturnCodeType::HardReboot),
+            (1618, ReturnCodeType::Retry),
+        ]
+        .into_iter()
+        .map(|(code, kind)| Self { code, kind })
+        .collect()
+    }
+
+    /// Parse `CODE=TYPE`, e.g. `3010=soft-reboot`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (code, kind) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid return code '{}': expected CODE=TYPE", spec))?;
+        Ok(Self {
+            code: parse_code(code)?,
+            kind: kind.parse()?,
+        })
+    }
+
+    fn to_json_value(self) -> JsonValue {
+        JsonValue::Object(vec![
+            (
+                "@odata.type".to_string(),
+                "#microsoft.graph.win32LobAppReturnCode".into(),
+            ),
+            (
+                "returnCode".to_string(),
+                JsonValue::Number(self.code.into()),
+            ),
+            ("type".to_string(), self.kind.graph_name().into()),
+        ])
+    }
+}
+
+impl fmt::Display for ReturnCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.code, self.kind.name())
+    }
+}
+
+fn parse_code(code: &str) -> Result<i32, String> {
+    code.trim()
+        .parse()
+        .map_err(|_| format!("Invalid return code '{}': expected an integer", code))
+}
+
+/// Settings of a Win32 app that can't be read from the package. Fields
+/// left unset fall back to what the package provides or Intune's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppSettings {
+    /// Name shown in Intune and the Company Portal (default: the MSI's
+    /// ProductName, or the package name)
+    pub display_name: Option<String>,
+    /// Description (default: the display name)
+    pub description: Option<String>,
+    /// Publisher (default: the MSI's Manufacturer)
+    pub publisher: Option<String>,
+    /// Version shown in Intune (default: the MSI's ProductVersion)
+    pub version: Option<String>,
+    pub developer: Option<String>,
+    pub owner: Option<String>,
+    pub notes: Option<String>,
+    pub information_url: Option<String>,
+    pub privacy_url: Option<String>,
+    /// Install command (default for an MSI: `msiexec /i`)
+    pub install_command: Option<String>,
+    /// Uninstall command (default for an MSI: `msiexec /x` with the
+    /// product code)
+    pub uninstall_command: Option<String>,
+    pub install_context: Option<InstallContext>,
+    pub restart_behavior: Option<RestartBehavior>,
+    pub max_run_time_minutes: Option<u32>,
+    /// Architectures the app installs on (default: x64 and x86)
+    pub architectures: Vec<Architecture>,
+    /// Minimum Windows release, e.g. `1607` or `21H2`
+    pub minimum_windows_release: Option<String>,
+    pub minimum_disk_space_mb: Option<u32>,
+    pub minimum_memory_mb: Option<u32>,
+    /// Exit codes and their meaning (default: [`ReturnCode::defaults`])
+    pub return_codes: Vec<ReturnCode>,
+}
+
+impl AppSettings {
+    /// Replace settings with those set in `overrides`.
+    pub fn merge(&mut self, overrides: AppSettings) -> &mut Self {
+        fn take<T>(field: &mut Option<T>, value: Option<T>) {
+            if value.is_some() {
+                *field = value;
+            }
+        }
+        take(&mut self.display_name, overrides.display_name);
+        take(&mut self.description, overrides.description);
+        take(&mut self.publisher, overrides.publisher);
+        take(&mut self.version, overrides.version);
+        take(&mut self.developer, overrides.developer);
+        take(&mut self.owner, overrides.owner);
+        take(&mut self.notes, overrides.notes);
+        take(&mut self.information_url, overrides.information_url);
+        take(&mut self.privacy_url, overrides.privacy_url);
+        take(&mut self.install_command, overrides.install_command);
+        take(&mut self.uninstall_command, overrides.uninstall_command);
+        take(&mut self.install_context, overrides.install_context);
+        take(&mut self.restart_behavior, overrides.restart_behavior);
+        take(
+            &mut self.max_run_time_minutes,
+            overrides.max_run_time_minutes,
+        );
+        take(
+            &mut self.minimum_windows_release,
+            overrides.minimum_windows_release,
+        );
+        take(
+            &mut self.minimum_disk_space_mb,
+            overrides.minimum_disk_space_mb,
+        );
+        take(&mut self.minimum_memory_mb, overrides.minimum_memory_mb);
+        if !overrides.architectures.is_empty() {
+            self.architectures = overrides.architectures;
+        }
+        if !overrides.return_codes.is_empty() {
+            self.return_codes = overrides.return_codes;
+        }
+        self
+    }
+
+    /// The architectures, x64 and x86 unless set.
+    pub fn architectures(&self) -> Vec<Architecture> {
+        if self.architectures.is_empty() {
+            vec![Architecture::X64, Architecture::X86]
+        } else {
+            self.architectures.clone()
+        }
+    }
+
+    /// The return codes, Intune's defaults unless set.
+    pub fn return_codes(&self) -> Vec<ReturnCode> {
+        if self.return_codes.is_empty() {
+            ReturnCode::defaults()
+        } else {
+            self.return_codes.clone()
+        }
+    }
+
+    /// Read the `app` section of a configuration file.
+    pub fn from_json_value(value: &JsonValue) -> Result<Self, String> {
+        let section = value.as_object().ok_or("'app' must be an object")?;
+        let mut settings = Self::default();
+
+        for (key, value) in section {
+            let text = || {
+                value
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("'app.{}' must be a string", key))
+            };
+            let number = || {
+                value
+                    .as_u64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(|| format!("'app.{}' must be a non-negative integer", key))
+            };
+            match key.as_str() {
+                "display_name" => settings.display_name = Some(text()?),
+                "description" => settings.description = Some(text()?),
+                "publisher" => settings.publisher = Some(text()?),
+                "version" => settings.version = Some(text()?),
+                "developer" => settings.developer = Some(text()?),
+                "owner" => settings.owner = Some(text()?),
+                "notes" => settings.notes = Some(text()?),
+                "information_url" => settings.information_url = Some(text()?),
+                "privacy_url" => settings.privacy_url = Some(text()?),
+                "install_command" => settings.install_command = Some(text()?),
+                "uninstall_command" => settings.uninstall_command = Some(text()?),
+                "install_context" => settings.install_context = Some(text()?.parse()?),
+                "restart_behavior" => settings.restart_behavior = Some(text()?.parse()?),
+                "max_run_time_minutes" => settings.max_run_time_minutes = Some(number()?),
+                "architectures" => {
+                    settings.architectures = value
+                        .as_array()
+                        .ok_or("'app.architectures' must be an array of strings")?
+                        .iter()
+                        .map(|arch| {
+                            arch.as_str()
+                                .ok_or("'app.architectures' must be an array of strings")?
+                                .parse()
+                        })
+                        .collect::<Result<_, String>>()?;
+                }
+                "minimum_windows_release" => settings.minimum_windows_release = Some(text()?),
+                "minimum_disk_space_mb" => settings.minimum_disk_space_mb = Some(number()?),
+                "minimum_memory_mb" => settings.minimum_memory_mb = Some(number()?),
+                "return_codes" => {
+                    settings.return_codes = value
+                        .as_object()
+                        .ok_or("'app.return_codes' must be an object of codes to types")?
+                        .iter()
+                        .map(|(code, kind)| {
+                            let kind = kind.as_str().ok_or_else(|| {
+                                format!("Type of return code {} must be a string", code)
+                            })?;
+                            Ok(ReturnCode {
+                                code: parse_code(code)?,
+                                kind: kind.parse()?,
+                            })
+                        })
+                        .collect::<Result<_, String>>()?;
+                }
+                _ => return Err(format!("Unknown 'app' setting '{}'", key)),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl From<&AppSettings> for JsonValue {
+    fn from(settings: &AppSettings) -> Self {
+        let mut members: Vec<(String, JsonValue)> = Vec::new();
+        let mut text = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                members.push((key.to_string(), value.as_str().into()));
+            }
+        };
+        text("display_name", &settings.display_name);
+        text("description", &settings.description);
+        text("publisher", &settings.publisher);
+        text("version", &settings.version);
+        text("developer", &settings.developer);
+        text("owner", &settings.owner);
+        text("notes", &settings.notes);
+        text("information_url", &settings.information_url);
+        text("privacy_url", &settings.privacy_url);
+        text("install_command", &settings.install_command);
+        text("uninstall_command", &settings.uninstall_command);
+        text(
+            "install_context",
+            &settings.install_context.map(|c| c.name().to_string()),
+        );
+        text(
+            "restart_behavior",
+            &settings.restart_behavior.map(|b| b.name().to_string()),
+        );
+        text("minimum_windows_release", &settings.minimum_windows_release);
+
+        let numbers = [
+            ("max_run_time_minutes", settings.max_run_time_minutes),
+            ("minimum_disk_space_mb", settings.minimum_disk_space_mb),
+            ("minimum_memory_mb", settings.minimum_memory_mb),
+        ];
+        for (key, value) in numbers {
+            if let Some(value) = value {
+                members.push((key.to_string(), u64::from(value).into()));
+            }
+        }
+        if !settings.architectures.is_empty() {
+            members.push((
+                "architectures".to_string(),
+                JsonValue::Array(
+                    settings
+                        .architectures
+                        .iter()
+                        .map(|arch| arch.name().into())
+                        .collect(),
+                ),
+            ));
+        }
+        if !settings.return_codes.is_empty() {
+            members.push((
+                "return_codes".to_string(),
+                JsonValue::Object(
+                    settings
+                        .return_codes
+                        .iter()
+                        .map(|code| (code.code.to_string(), code.kind.name().into()))
+                        .collect(),
+                ),
+            ));
+        }
+        JsonValue::Object(members)
+    }
+}
+
+/// Request to generate the Win32 app manifest of an `.intunewin`.
+#[derive(Debug, Clone)]
+pub struct AppManifestRequest {
+    /// The .intunewin the manifest describes
+    pub input_file: PathBuf,
+    /// App settings, from the configuration file and flags
+    pub settings: AppSettings,
+    /// Detection rules added to the derived ones
+    pub rules: Vec<DetectionRule>,
+    /// Where to write the manifest (default: next to the package)
+    pub output_file: Option<PathBuf>,
+}
+
+impl AppManifestRequest {
+    /// Create a new request for the package at `input_file`.
+    pub fn new(input_file: impl Into<PathBuf>) -> Self {
+        Self {
+            input_file: input_file.into(),
+            settings: AppSettings::default(),
+            rules: Vec::new(),
+            output_file: None,
+        }
+    }
+
+    /// Set the app settings.
+    pub fn with_settings(mut self, settings: AppSettings) -> Self {
+        self.set_settings(settings);
+        self
+    }
+
+    /// Add a detection rule to the derived ones.
+    pub fn with_rule(mut self, rule: DetectionRule) -> Self {
+        self.add_rule(rule);
+        self
+    }
+
+    /// Set where the manifest is written.
+    pub fn with_output_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.set_output_file(path);
+        self
+    }
+
+    /// Set the app settings in place.
+    pub fn set_settings(&mut self, settings: AppSettings) -> &mut Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Add a detection rule to the derived ones in place.
+    pub fn add_rule(&mut self, rule: DetectionRule) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Set where the manifest is written in place.
+    pub fn set_output_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.output_file = Some(path.into());
+        self
+    }
+
+    /// Get the output file path (`<package>.app.json` next to the package
+    /// unless set).
+    pub fn output_path(&self) -> PathBuf {
+        match &self.output_file {
+            Some(path) => path.clone(),
+            None => self.input_file.with_extension("app.json"),
+        }
+    }
+}
+
+/// A Win32 app described for Microsoft Graph, with the package's values
+/// and the settings' fallbacks resolved.
+#[derive(Debug, Clone)]
+pub struct Win32AppManifest {
+    pub display_name: String,
+    pub description: String,
+    pub publisher: String,
+    pub version: Option<String>,
+    /// File name of the `.intunewin`
+    pub file_name: String,
+    pub install_command: String,
+    pub uninstall_command: String,
+    /// The remaining settings (contacts, install experience, requirements)
+    pub settings: AppSettings,
+    /// Setup file, MSI identity and detection rules
+    pub detection: DetectionRules,
+}
+
+impl Win32AppManifest {
+    /// Serialize as the JSON body of a Graph `win32LobApp`, ready for
+    /// `POST deviceAppManagement/mobileApps` or the IntuneWin32App module.
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| match value {
+            Some(value) => value.as_str().into(),
+            None => JsonValue::Null,
+        };
+        let optional_number = |value: Option<u32>| match value {
+            Some(value) => u64::from(value).into(),
+            None => JsonValue::Null,
+        };
+        let settings = &self.settings;
+        let context = settings.install_context.unwrap_or_default();
+        let architectures = settings
+            .architectures()
+            .iter()
+            .map(Architecture::name)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut members: Vec<(&str, JsonValue)> = vec![
+            ("@odata.type", "#microsoft.graph.win32LobApp".into()),
+            ("displayName", self.display_name.as_str().into()),
+            ("description", self.description.as_str().into()),
+            ("publisher", self.publisher.as_str().into()),
+            ("displayVersion", optional(&self.version)),
+            ("developer", optional(&settings.developer)),
+            ("owner", optional(&settings.owner)),
+            ("notes", optional(&settings.notes)),
+            ("informationUrl", optional(&settings.information_url)),
+            ("privacyInformationUrl", optional(&settings.privacy_url)),
+            ("isFeatured", false.into()),
+            ("fileName", self.file_name.as_str().into()),
+            ("setupFilePath", self.detection.setup_file.as_str().into()),
+            ("installCommandLine", self.install_command.as_str().into()),
+            (
+                "uninstallCommandLine",
+                self.uninstall_command.as_str().into(),
+            ),
+            ("applicableArchitectures", architectures.into()),
+            (
+                "minimumSupportedWindowsRelease",
+                settings
+                    .minimum_windows_release
+                    .as_deref()
+                    .unwrap_or(DEFAULT_MINIMUM_WINDOWS_RELEASE)
+                    .into(),
+            ),
+            (
+                "minimumFreeDiskSpaceInMB",
+                optional_number(settings.minimum_disk_space_mb),
+            ),
+            (
+                "minimumMemoryInMB",
+                optional_number(settings.minimum_memory_mb),
+            ),
+            (
+                "installExperience",
+                JsonValue::Object(vec![
+                    (
+                        "@odata.type".to_string(),
+                        "#microsoft.graph.win32LobAppInstallExperience".into(),
+                    ),
+                    ("runAsAccount".to_string(), context.name().into()),
+                    (
+                        "deviceRestartBehavior".to_string(),
+                        settings
+                            .restart_behavior
+                            .unwrap_or_default()
+                            .graph_name()
+                            .into(),
+                    ),
+                    (
+                        "maxRunTimeInMinutes".to_string(),
+                        u64::from(
+                            settings
+                                .max_run_time_minutes
+                                .unwrap_or(DEFAULT_MAX_RUN_TIME_MINUTES),
+                        )
+                        .into(),
+                    ),
+                ]),
+            ),
+            (
+                "returnCodes",
+                JsonValue::Array(
+                    settings
+                        .return_codes()
+                        .into_iter()
+                        .map(ReturnCode::to_json_value)
+                        .collect(),
+                ),
+            ),
+            (
+                "rules",
+                JsonValue::Array(
+                    self.detection
+                        .rules
+                        .iter()
+                        .map(DetectionRule::to_json_value)
+                        .collect(),
+                ),
+            ),
+        ];
+        if let Some(msi) = &self.detection.msi {
+            let package_type = match context {
+                InstallContext::System => "perMachine",
+                InstallContext::User => "perUser",
+            };
+            members.push((
+                "msiInformation",
+                JsonValue::Object(vec![
+                    (
+                        "@odata.type".to_string(),
+                        "#microsoft.graph.win32LobAppMsiInformation".into(),
+                    ),
+                    ("productCode".to_string(), msi.product_code.as_str().into()),
+                    (
+                        "productVersion".to_string(),
+                        msi.product_version.as_str().into(),
+                    ),
+                    ("upgradeCode".to_string(), optional(&msi.upgrade_code)),
+                    ("requiresReboot".to_string(), false.into()),
+                    ("packageType".to_string(), package_type.into()),
+                    ("productName".to_string(), optional(&msi.product_name)),
+                    ("publisher".to_string(), optional(&msi.publisher)),
+                ]),
+            ));
+        }
+
+        JsonValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+        .to_pretty_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_return_code() {
+        assert_eq!(
+            ReturnCode::parse("3010=soft-reboot"),
+            Ok(ReturnCode {
+                code: 3010,
+                kind: ReturnCodeType::SoftReboot,
+            })
+        );
+        assert_eq!(
+            ReturnCode::parse("-1=failed").map(|code| code.to_string()),
+            Ok("-1=failed".to_string())
+        );
+        assert!(ReturnCode::parse("3010").is_err());
+        assert!(ReturnCode::parse("x=success").is_err());
+        assert!(ReturnCode::parse("0=ok").is_err());
+    }
+
+    #[test]
+    fn test_app_settings_json_round_trip() {
+        let json = crate::json::parse(
+            r#"{
+                "publisher": "ACME",
+                "install_context": "user",
+                "restart_behavior": "suppress",
+                "max_run_time_minutes": 30,
+                "architectures": ["x64", "arm64"],
+                "return_codes": {"0": "success", "5": "retry"}
+            }"#,
+        )
+        .unwrap();
+        let settings = AppSettings::from_json_value(&json).unwrap();
+        assert_eq!(settings.publisher.as_deref(), Some("ACME"));
+        assert_eq!(settings.install_context, Some(InstallContext::User));
+        assert_eq!(
+            settings.architectures,
+            vec![Architecture::X64, Architecture::Arm64]
+        );
+        assert_eq!(settings.return_codes.len(), 2);
+        assert_eq!(
+            AppSettings::from_json_value(&JsonValue::from(&settings)),
+            Ok(settings)
+        );
+
+        let invalid =
+            |text: &str| AppSettings::from_json_value(&crate::json::parse(text).unwrap()).is_err();
+        assert!(invalid(r#"{"unknown": 1}"#));
+        assert!(invalid(r#"{"publisher": 1}"#));
+        assert!(invalid(r#"{"architectures": ["sparc"]}"#));
+        assert!(invalid(r#"{"return_codes": {"zero": "success"}}"#));
+    }
+
+    #[test]
+    fn test_app_settings_merge() {
+        let mut settings = AppSettings {
+            publisher: Some("ACME".to_string()),
+            notes: Some("From config".to_string()),
+            architectures: vec![Architecture::X64],
+            ..AppSettings::default()
+        };
+        settings.merge(AppSettings {
+            notes: Some("From flags".to_string()),
+            ..AppSettings::default()
+        });
+        assert_eq!(settings.publisher.as_deref(), Some("ACME"));
+        assert_eq!(settings.notes.as_deref(), Some("From flags"));
+        assert_eq!(settings.architectures(), vec![Architecture::X64]);
+        assert_eq!(settings.return_codes(), ReturnCode::defaults());
+    }
+
+    #[test]
+    fn test_manifest_json() {
+        let manifest = Win32AppManifest {
+            display_name: "Acme".to_string(),
+            description: "Acme".to_string(),
+            publisher: "ACME".to_string(),
+            version: None,
+            file_name: "setup.intunewin".to_string(),
+            install_command: "setup.exe /S".to_string(),
+            uninstall_command: "uninstall.exe /S".to_string(),
+            settings: AppSettings::default(),
+            detection: DetectionRules {
+                setup_file: "setup.exe".to_string(),
+                msi: None,
+                rules: vec![DetectionRule::file("C:\\Acme\\acme.exe").unwrap()],
+            },
+        };
+        let json = crate::json::parse(&manifest.to_json()).unwrap();
+        let text = |key: &str| json.get(key).and_then(JsonValue::as_str);
+        assert_eq!(text("@odata.type"), Some("#microsoft.graph.win32LobApp"));
+        assert_eq!(text("setupFilePath"), Some("setup.exe"));
+        assert_eq!(text("applicableArchitectures"), Some("x64,x86"));
+        assert_eq!(text("minimumSupportedWindowsRelease"), Some("1607"));
+        assert_eq!(
+            json.get("installExperience")
+                .and_then(|e| e.get("runAsAccount"))
+                .and_then(JsonValue::as_str),
+            Some("system")
+        );
+        assert_eq!(
+            json.get("returnCodes").unwrap().as_array().unwrap().len(),
+            5
+        );
+        assert_eq!(json.get("rules").unwrap().as_array().unwrap().len(), 1);
+        assert!(json.get("msiInformation").is_none());
+    }
+
+    #[test]
+    fn test_manifest_output_path() {
+        let request = AppManifestRequest::new("/out/setup.intunewin");
+        assert_eq!(request.output_path(), PathBuf::from("/out/setup.app.json"));
+        let request = request.with_output_file("/tmp/app.json");
+        assert_eq!(request.output_path(), PathBuf::from("/tmp/app.json"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Analyzing a source folder before packaging it.
+//!
+//! The files are collected exactly as `intune create` would collect them.
+//! Files that share their size with another file are hashed to find
+//! duplicates; nothing else is read in full. How well each file compresses
+//! is estimated by deflating its first [`SAMPLE_SIZE`] bytes, which is
+//! exact for smaller files and close for most large ones (installers and
+//! archives are compressed throughout, text and binaries rarely change
+//! character halfway).
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::Compression as DeflateLevel;
+use flate2::write::DeflateEncoder;
+use tracing::info_span;
+
+use crate::models::analyze::{AnalyzeRequest, DuplicateGroup, FileEstimate, SourceAnalysis};
+use crate::models::error::{PackageError, PackageResult};
+
+use super::archive::collect_source_files;
+use super::encrypt::compute_sha256_stream;
+use super::manifest::to_hex;
+
+/// Bytes of each file deflated to estimate its compressibility.
+pub const SAMPLE_SIZE: u64 = 1024 * 1024;
+
+/// Report duplicates, the largest files and the expected compression of
+/// the files a package of `request.source_folder` would hold.
+pub fn analyze(request: &AnalyzeRequest) -> PackageResult<SourceAnalysis> {
+    let _span = info_span!("analyze", source = %request.source_folder.display()).entered();
+    let source =
+        collect_source_files(&request.source_folder, &request.setup_file, &request.filter)?;
+
+    let mut estimates = Vec::with_capacity(source.files.len());
+    let mut by_size: BTreeMap<u64, Vec<&Path>> = BTreeMap::new();
+    for file in &source.files {
+        let full_path = source.root.join(&file.relative_path);
+        estimates.push(FileEstimate {
+            path: display_path(&file.relative_path),
+            size: file.size,
+            estimated_compressed: estimate_compressed(&full_path, file.size)?,
+        });
+        if file.size > 0 {
+            by_size
+                .entry(file.size)
+                .or_default()
+                .push(&file.relative_path);
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for path in paths {
+            let full_path = source.root.join(path);
+            let sha256 = hash_file(&full_path)?;
+            by_hash.entry(sha256).or_default().push(display_path(path));
+        }
+        duplicates.extend(
+            by_hash
+                .into_iter()
+                .filter(|(_, paths)| paths.len() > 1)
+                .map(|(sha256, paths)| DuplicateGroup {
+                    sha256,
+                    size,
+                    paths,
+                }),
+        );
+    }
+    duplicates.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then(a.paths.cmp(&b.paths)));
+
+    let estimated_compressed = estimates.iter().map(|file| file.estimated_compressed).sum();
+    estimates.sort_by(|a, b| b.size.cmp(&a.size).then(a.path.cmp(&b.path)));
+    estimates.truncate(request.top);
+
+    Ok(SourceAnalysis {
+        source_folder: request.source_folder.clone(),
+        file_count: source.files.len(),
+        total_size: source.total_size,
+        estimated_compressed,
+        duplicates,
+        largest: estimates,
+        path_issues: source.path_issues,
+    })
+}
+
+/// Deflate the first [`SAMPLE_SIZE`] bytes of the file and scale the
+/// result to its full `size`.
+fn estimate_compressed(path: &Path, size: u64) -> PackageResult<u64> {
+    if size == 0 {
+        return Ok(0);
+    }
+    let read_error = |e: io::Error| PackageError::source_read(path.to_path_buf(), e);
+
+    let file = File::open(path).map_err(read_error)?;
+    let mut encoder = DeflateEncoder::new(io::sink(), DeflateLevel::fast());
+    let sampled =
+        io::copy(&mut BufReader::new(file).take(SAMPLE_SIZE), &mut encoder).map_err(read_error)?;
+    if sampled == 0 {
+        return Ok(0);
+    }
+    encoder.flush().map_err(read_error)?;
+    let compressed = encoder.total_out().min(sampled);
+    Ok(((compressed as u128 * size as u128).div_ceil(sampled as u128)) as u64)
+}
+
+fn hash_file(path: &Path) -> PackageResult<String> {
+    let read_error = |e: io::Error| PackageError::source_read(path.to_path_buf(), e);
+    let mut reader = BufReader::new(File::open(path).map_err(read_error)?);
+    let sha256 = compute_sha256_stream(&mut reader).map_err(read_error)?;
+    Ok(to_hex(&sha256))
+}
+
+fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_analyze_finds_duplicates_and_largest() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("x86")).unwrap();
+        fs::create_dir_all(root.join("x64")).unwrap();
+        fs::write(root.join("setup.exe"), "setup").unwrap();
+        fs::write(root.join("x86/runtime.dll"), vec![7u8; 4096]).unwrap();
+        fs::write(root.join("x64/runtime.dll"), vec![7u8; 4096]).unwrap();
+        // Same size, different content: not a duplicate
+        fs::write(root.join("x64/other.dll"), vec![8u8; 4096]).unwrap();
+        let random: Vec<u8> = (0..8192u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        fs::write(root.join("data.bin"), &random).unwrap();
+
+        let analysis = analyze(&AnalyzeRequest::new(root, "setup.exe").with_top(2)).unwrap();
+
+        assert_eq!(analysis.file_count, 5);
+        assert_eq!(analysis.total_size, 5 + 3 * 4096 + 8192);
+        assert_eq!(
+            analysis.duplicates,
+            vec![DuplicateGroup {
+                sha256: to_hex(&compute_sha256_stream(&mut &vec![7u8; 4096][..]).unwrap()),
+                size: 4096,
+                paths: vec!["x64/runtime.dll".into(), "x86/runtime.dll".into()],
+            }]
+        );
+        assert_eq!(analysis.duplicate_bytes(), 4096);
+
+        assert_eq!(analysis.largest.len(), 2);
+        assert_eq!(analysis.largest[0].path, "data.bin");
+        assert_eq!(analysis.largest[1].path, "x64/other.dll");
+        // Repeated bytes deflate to almost nothing
+        assert!(analysis.largest[1].ratio_percent() < 5);
+        assert!(analysis.estimated_compressed < analysis.total_size);
+    }
+
+    #[test]
+    fn test_analyze_respects_filter() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("setup.exe"), "setup").unwrap();
+        fs::write(temp.path().join("a.pdb"), "symbols").unwrap();
+        fs::write(temp.path().join("b.pdb"), "symbols").unwrap();
+
+        let request = AnalyzeRequest::new(temp.path(), "setup.exe")
+            .with_exclude(crate::models::glob::Glob::new("*.pdb").unwrap());
+        let analysis = analyze(&request).unwrap();
+
+        assert_eq!(analysis.file_count, 1);
+        assert!(analysis.duplicates.is_empty());
+    }
+}
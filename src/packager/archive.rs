@@ -1,21 +1,51 @@
 //! Archive creation and file collection.
+//!
+//! A source can be a folder or an archive handed over by a build system
+//! (`.zip`, `.tar`, `.tar.gz` or `.tgz`); [`open_source`] gives either as a
+//! [`ContentProvider`].
 
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, Write};
 use std::path::{Path, PathBuf};
 
+use flate2::read::GzDecoder;
+
 use walkdir::WalkDir;
+use zip::ZipWriter;
+use zip::write::FileOptions;
 
-use crate::models::error::{PackageError, PackageResult};
+use crate::models::compression::{Compression, CompressionMethod};
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::glob::SourceFilter;
 use crate::models::package::SourcePackage;
+use crate::models::retry::RetryPolicy;
+use crate::models::suggest::rank_suggestions;
+
+use super::cache::ContentCache;
+use super::content::{ContentProvider, FolderContent, TarContent, ZipContent};
+use super::manifest::{HashingReader, ManifestHasher};
+use super::progress::Progress;
+use super::retry::open_file;
+use super::sanitize::{contained_path, sanitize_entry_name};
+use super::sparse::detect_sparse;
+use super::special::{WalkItem, classify};
+use super::winpath::path_issues;
+use super::workspace::StagingDir;
 
 /// Collect all files from the source folder.
 ///
 /// This includes:
 /// - All files recursively (including subdirectories)
+/// - All subdirectories, so empty ones survive a round trip
 /// - Hidden files (dotfiles on Unix, hidden attribute on Windows)
 /// - Follows symbolic links
+///
+/// Paths left out by `filter` are skipped; excluded folders are not
+/// walked at all. The setup file is kept unless it is excluded.
 pub fn collect_source_files(
     source_folder: &Path,
     setup_file: &str,
+    filter: &SourceFilter,
 ) -> PackageResult<SourcePackage> {
     if !source_folder.exists() {
         return Err(PackageError::SourceFolderNotFound {
@@ -28,39 +58,67 @@ pub fn collect_source_files(
     let mut found_setup = false;
 
     // Walk directory, following symlinks
-    for entry in WalkDir::new(source_folder)
+    let walker = WalkDir::new(source_folder)
         .follow_links(true)
         .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        // Skip directories
+        .filter_entry(|e| {
+            e.depth() == 0
+                || !filter.excludes(e.path().strip_prefix(source_folder).unwrap_or(e.path()))
+        });
+    for result in walker {
+        let entry = match classify(source_folder, result) {
+            WalkItem::Entry(entry) => entry,
+            WalkItem::Skipped(skipped) => {
+                package.skipped_entries.push(skipped);
+                continue;
+            }
+            WalkItem::Error(_) => continue,
+        };
+        let full_path = entry.path();
+
+        // Record directories (except the root itself)
         if entry.file_type().is_dir() {
+            if entry.depth() > 0 {
+                let relative_path = full_path
+                    .strip_prefix(source_folder)
+                    .map_err(|_| PackageError::SourceReadError {
+                        path: full_path.to_path_buf(),
+                        reason: "Failed to compute relative path".to_string(),
+                        source: None,
+                    })?
+                    .to_path_buf();
+                if filter.keeps(&relative_path) {
+                    package.add_directory(relative_path);
+                }
+            }
             continue;
         }
 
-        let full_path = entry.path();
-
         // Get relative path from source folder
         let relative_path = full_path
             .strip_prefix(source_folder)
             .map_err(|_| PackageError::SourceReadError {
                 path: full_path.to_path_buf(),
                 reason: "Failed to compute relative path".to_string(),
+                source: None,
             })?
             .to_path_buf();
 
+        let is_setup = is_setup_file(&relative_path, setup_file);
+        if !is_setup && !filter.keeps(&relative_path) {
+            continue;
+        }
+
         // Get file size
         let metadata = entry
             .metadata()
-            .map_err(|e| PackageError::SourceReadError {
-                path: full_path.to_path_buf(),
-                reason: e.to_string(),
-            })?;
+            .map_err(|e| PackageError::source_read(full_path.to_path_buf(), e))?;
 
         let size = metadata.len();
+        if let Some(sparse) = detect_sparse(&relative_path, &metadata) {
+            package.sparse_files.push(sparse);
+        }
 
-        // Check if this is the setup file
-        let is_setup = is_setup_file(&relative_path, setup_file);
         if is_setup {
             found_setup = true;
         }
@@ -73,6 +131,13 @@ pub fn collect_source_files(
         return Err(PackageError::SetupFileNotFound {
             file: setup_file.to_string(),
             folder: source_folder.to_path_buf(),
+            suggestions: rank_suggestions(
+                setup_file,
+                package
+                    .files
+                    .iter()
+                    .map(|f| f.relative_path.to_string_lossy().replace('\\', "/")),
+            ),
         });
     }
 
@@ -80,6 +145,11 @@ pub fn collect_source_files(
     package
         .files
         .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    package.directories.sort();
+    package
+        .skipped_entries
+        .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    package.path_issues = path_issues(&package);
 
     Ok(package)
 }
@@ -105,11 +175,247 @@ fn is_setup_file(relative_path: &Path, setup_file: &str) -> bool {
     }
 }
 
+/// ZIP entry options with the timestamp pinned to the DOS epoch.
+///
+/// Entry times never carry information the manifest lacks, and pinning
+/// them keeps reproducible packages byte-identical whatever features the
+/// zip crate is built with.
+pub(crate) fn entry_options(method: zip::CompressionMethod) -> FileOptions {
+    FileOptions::default()
+        .compression_method(method)
+        .last_modified_time(zip::DateTime::default())
+}
+
+/// Entry options for content files compressed as `compression` asks.
+pub(crate) fn content_options(compression: &Compression) -> FileOptions {
+    match compression.method {
+        CompressionMethod::Stored => entry_options(zip::CompressionMethod::Stored),
+        CompressionMethod::Deflate => entry_options(zip::CompressionMethod::Deflated)
+            .compression_level(Some(compression.effective_level() as i32)),
+        #[cfg(feature = "zstd")]
+        CompressionMethod::Zstd => entry_options(zip::CompressionMethod::Zstd)
+            .compression_level(Some(compression.effective_level() as i32)),
+    }
+}
+
+/// Add every file of `source`, read from `content`, to `zip` under
+/// `prefix`, returning the manifest hash of the files written.
+///
+/// Files the `cache` accepts are copied from it already compressed.
+pub(crate) fn write_content_files<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    source: &SourcePackage,
+    content: &dyn ContentProvider,
+    prefix: &str,
+    compression: &Compression,
+    progress: &Progress,
+    mut cache: Option<&mut ContentCache>,
+) -> PackageResult<String> {
+    let options = content_options(compression);
+    let mut manifest = ManifestHasher::default();
+
+    for file in &source.files {
+        progress.check_cancelled()?;
+
+        // Use forward slashes for ZIP paths (cross-platform)
+        let zip_path = format!("{}{}", prefix, normalize_path(&file.relative_path));
+
+        progress.set_message_with(|| format!("Adding {}", zip_path));
+
+        if let Some(cache) = cache.as_deref_mut().filter(|c| c.accepts(file.size)) {
+            let (written, sha256) =
+                cache.write_entry(zip, &zip_path, content, &file.relative_path)?;
+            manifest.add(&file.relative_path, written, sha256);
+            progress.file_done(written);
+            continue;
+        }
+
+        // Sparse disk images easily pass the 4 GiB limit of plain ZIP entries
+        zip.start_file(&zip_path, options.large_file(file.size >= u32::MAX as u64))
+            .map_err(|e| PackageError::zip(ZipStage::WriteContent, Some(&zip_path), e))?;
+
+        let mut reader = HashingReader::new(content.open(&file.relative_path)?);
+        std::io::copy(&mut reader, zip)
+            .map_err(|e| PackageError::source_read(content.root().join(&file.relative_path), e))?;
+        let (written, sha256) = reader.finish();
+        manifest.add(&file.relative_path, written, sha256);
+
+        progress.file_done(written);
+    }
+
+    Ok(manifest.finish())
+}
+
 /// Normalize path separators to forward slashes for ZIP compatibility.
 pub fn normalize_path(path: &Path) -> String {
     path.to_string_lossy().replace('\\', "/")
 }
 
+/// Archive formats a source can be read from instead of a folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceArchive {
+    /// A ZIP archive (`.zip`)
+    Zip,
+    /// An uncompressed tar archive (`.tar`)
+    Tar,
+    /// A gzip-compressed tar archive (`.tar.gz` or `.tgz`)
+    TarGz,
+}
+
+impl SourceArchive {
+    /// The format named by the extension of `path`, ignoring case.
+    pub fn from_name(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// The format of `path` if it is an archive file; `None` for folders
+    /// and other files.
+    pub fn detect(path: &Path) -> Option<Self> {
+        if path.is_file() {
+            Self::from_name(path)
+        } else {
+            None
+        }
+    }
+}
+
+/// The content of the source at `path`, a folder or an archive file.
+///
+/// ZIP entries are streamed from the archive as they are packaged; tar
+/// archives can only be read in order, so they are held in memory.
+pub fn open_source(
+    path: &Path,
+    setup_file: &str,
+    retry: RetryPolicy,
+) -> PackageResult<Box<dyn ContentProvider>> {
+    let format = match SourceArchive::detect(path) {
+        Some(format) => format,
+        None => return Ok(Box::new(FolderContent::new(path).with_retry(retry))),
+    };
+    if format == SourceArchive::Zip {
+        return Ok(Box::new(ZipContent::open(path, setup_file)?));
+    }
+
+    let reader = open_file(path, &retry).map(BufReader::new)?;
+    let content = match format {
+        SourceArchive::TarGz => TarContent::read_from(GzDecoder::new(reader), path, setup_file),
+        _ => TarContent::read_from(reader, path, setup_file),
+    }?;
+    Ok(Box::new(content))
+}
+
+/// Extract the archive at `path` into a staging folder, keeping Unix
+/// modes and symbolic links, for flows that need the files on disk.
+///
+/// Entry paths are checked like those of any untrusted archive; links are
+/// created last so nothing is written through them.
+#[cfg_attr(not(feature = "macos"), allow(dead_code))]
+pub(crate) fn extract_source(path: &Path, format: SourceArchive) -> PackageResult<StagingDir> {
+    let size = fs::metadata(path)
+        .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?
+        .len();
+    let staging = StagingDir::create_with_space("archive", size)?;
+    let reader = File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    let invalid = |reason: String| PackageError::SourceReadError {
+        path: path.to_path_buf(),
+        reason,
+        source: None,
+    };
+
+    match format {
+        SourceArchive::Zip => extract_zip(reader, path, &staging.path)?,
+        SourceArchive::Tar => tar::Archive::new(reader)
+            .unpack(&staging.path)
+            .map_err(|e| invalid(format!("Invalid tar archive: {}", e)))?,
+        SourceArchive::TarGz => tar::Archive::new(GzDecoder::new(reader))
+            .unpack(&staging.path)
+            .map_err(|e| invalid(format!("Invalid tar archive: {}", e)))?,
+    }
+    Ok(staging)
+}
+
+fn extract_zip<R: Read + Seek>(reader: R, archive_path: &Path, root: &Path) -> PackageResult<()> {
+    let invalid = |e: zip::result::ZipError| PackageError::SourceReadError {
+        path: archive_path.to_path_buf(),
+        reason: format!("Invalid ZIP archive: {}", e),
+        source: None,
+    };
+    let mut archive = zip::ZipArchive::new(reader).map_err(invalid)?;
+    let mut links = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(invalid)?;
+        let name = entry.name().to_string();
+        if sanitize_entry_name(&name)?.as_os_str().is_empty() {
+            continue;
+        }
+        let mode = entry.unix_mode();
+        if mode.is_some_and(|mode| mode & 0o170000 == 0o120000) {
+            let mut target = Vec::new();
+            entry
+                .read_to_end(&mut target)
+                .map_err(|e| PackageError::source_read(archive_path.to_path_buf(), e))?;
+            links.push((name, target));
+            continue;
+        }
+
+        let path = contained_path(root, &name)?;
+        let write_err = |e| PackageError::output_write(path.clone(), e);
+        if entry.is_dir() {
+            fs::create_dir_all(&path).map_err(write_err)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(write_err)?;
+        }
+        let mut file = File::create(&path).map_err(write_err)?;
+        io::copy(&mut entry, &mut file).map_err(write_err)?;
+
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            // Special bits are never restored on the build machine
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))
+                .map_err(write_err)?;
+        }
+    }
+
+    for (name, target) in links {
+        let path = contained_path(root, &name)?;
+        create_symlink(&path, &target)?;
+    }
+    Ok(())
+}
+
+/// Create a symbolic link; other platforms get a file holding the target.
+fn create_symlink(path: &Path, target: &[u8]) -> PackageResult<()> {
+    let write_err = |e| PackageError::output_write(path.to_path_buf(), e);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(write_err)?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path).map_err(write_err)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, target).map_err(write_err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,7 +436,7 @@ mod tests {
         let mut data = File::create(source.join("data.dll")).unwrap();
         data.write_all(b"dll content").unwrap();
 
-        let package = collect_source_files(source, "setup.exe").unwrap();
+        let package = collect_source_files(source, "setup.exe", &SourceFilter::default()).unwrap();
 
         assert_eq!(package.file_count(), 2);
         assert!(package.files.iter().any(|f| f.is_setup_file));
@@ -148,9 +454,25 @@ mod tests {
         fs::create_dir(source.join("data")).unwrap();
         File::create(source.join("data").join("config.xml")).unwrap();
 
-        let package = collect_source_files(source, "setup.exe").unwrap();
+        let package = collect_source_files(source, "setup.exe", &SourceFilter::default()).unwrap();
 
         assert_eq!(package.file_count(), 2);
+        assert_eq!(package.directories, vec![PathBuf::from("data")]);
+    }
+
+    #[test]
+    fn test_collect_source_files_empty_directories() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path();
+
+        File::create(source.join("setup.exe")).unwrap();
+        fs::create_dir_all(source.join("logs/archive")).unwrap();
+
+        let package = collect_source_files(source, "setup.exe", &SourceFilter::default()).unwrap();
+
+        assert_eq!(package.file_count(), 1);
+        assert_eq!(package.dir_count(), 2);
+        assert!(package.directories.contains(&PathBuf::from("logs/archive")));
     }
 
     #[test]
@@ -164,7 +486,7 @@ mod tests {
         // Create hidden file (dotfile)
         File::create(source.join(".hidden")).unwrap();
 
-        let package = collect_source_files(source, "setup.exe").unwrap();
+        let package = collect_source_files(source, "setup.exe", &SourceFilter::default()).unwrap();
 
         // Should include hidden file
         assert_eq!(package.file_count(), 2);
@@ -176,6 +498,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_collect_source_files_filtered() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path();
+
+        File::create(source.join("setup.exe")).unwrap();
+        File::create(source.join("setup.pdb")).unwrap();
+        fs::create_dir_all(source.join(".git/objects")).unwrap();
+        File::create(source.join(".git/HEAD")).unwrap();
+        fs::create_dir_all(source.join("lib")).unwrap();
+        File::create(source.join("lib/core.dll")).unwrap();
+        File::create(source.join("lib/readme.txt")).unwrap();
+
+        let glob = |p: &str| crate::models::Glob::new(p).unwrap();
+        let filter = SourceFilter {
+            include: Vec::new(),
+            exclude: vec![glob(".git"), glob("*.pdb")],
+        };
+        let package = collect_source_files(source, "setup.exe", &filter).unwrap();
+        let files: Vec<_> = package
+            .files
+            .iter()
+            .map(|f| normalize_path(&f.relative_path))
+            .collect();
+        assert_eq!(files, ["lib/core.dll", "lib/readme.txt", "setup.exe"]);
+        assert_eq!(package.directories, vec![PathBuf::from("lib")]);
+
+        // The setup file survives an include list that does not name it
+        let filter = SourceFilter {
+            include: vec![glob("*.dll")],
+            exclude: Vec::new(),
+        };
+        let package = collect_source_files(source, "setup.exe", &filter).unwrap();
+        let files: Vec<_> = package
+            .files
+            .iter()
+            .map(|f| normalize_path(&f.relative_path))
+            .collect();
+        assert_eq!(files, ["lib/core.dll", "setup.exe"]);
+
+        let filter = SourceFilter {
+            include: Vec::new(),
+            exclude: vec![glob("*.exe")],
+        };
+        assert!(matches!(
+            collect_source_files(source, "setup.exe", &filter),
+            Err(PackageError::SetupFileNotFound { .. })
+        ));
+    }
+
     #[test]
     fn test_collect_source_files_missing_setup() {
         let temp = TempDir::new().unwrap();
@@ -184,7 +556,7 @@ mod tests {
         // Create a file but not the setup file
         File::create(source.join("other.exe")).unwrap();
 
-        let result = collect_source_files(source, "setup.exe");
+        let result = collect_source_files(source, "setup.exe", &SourceFilter::default());
 
         assert!(matches!(
             result,
@@ -192,6 +564,100 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_source_archive_from_name() {
+        for (name, format) in [
+            ("app.zip", Some(SourceArchive::Zip)),
+            ("App.ZIP", Some(SourceArchive::Zip)),
+            ("app.tar", Some(SourceArchive::Tar)),
+            ("app.tar.gz", Some(SourceArchive::TarGz)),
+            ("app.tgz", Some(SourceArchive::TarGz)),
+            ("app.gz", None),
+            ("app", None),
+        ] {
+            assert_eq!(
+                SourceArchive::from_name(Path::new(name)),
+                format,
+                "{}",
+                name
+            );
+        }
+
+        // Folders are never archives, whatever their name
+        let temp_dir = TempDir::new().unwrap();
+        let folder = temp_dir.path().join("payload.zip");
+        fs::create_dir(&folder).unwrap();
+        assert_eq!(SourceArchive::detect(&folder), None);
+    }
+
+    #[test]
+    fn test_open_source_reads_archives_and_folders() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        fs::create_dir_all(source.join("bin")).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("bin/app.dll"), "dll").unwrap();
+
+        let tar_path = temp_dir.path().join("source.tgz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_dir_all("payload", &source).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        for path in [&source, &tar_path] {
+            let content = open_source(path, "setup.exe", RetryPolicy::none()).unwrap();
+            let package = content
+                .collect("setup.exe", &SourceFilter::default())
+                .unwrap();
+            assert_eq!(package.file_count(), 2, "{}", path.display());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_source_keeps_modes_and_links() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.add_directory("App.app/Contents/MacOS", FileOptions::default())
+            .unwrap();
+        zip.start_file(
+            "App.app/Contents/MacOS/App",
+            FileOptions::default().unix_permissions(0o755),
+        )
+        .unwrap();
+        zip.write_all(b"binary").unwrap();
+        zip.add_symlink("App.app/Current", "Contents", FileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let staging = extract_source(&path, SourceArchive::Zip).unwrap();
+        let binary = staging.path.join("App.app/Contents/MacOS/App");
+        assert_eq!(fs::read(&binary).unwrap(), b"binary");
+        assert_eq!(
+            fs::metadata(&binary).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(
+            fs::read_link(staging.path.join("App.app/Current")).unwrap(),
+            Path::new("Contents")
+        );
+
+        // A link cannot redirect a later entry out of the staging folder
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.add_symlink("out", "/tmp", FileOptions::default())
+            .unwrap();
+        zip.start_file("out/escaped", FileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+        assert!(extract_source(&path, SourceArchive::Zip).is_err());
+    }
+
     #[test]
     fn test_normalize_path() {
         assert_eq!(normalize_path(Path::new("foo/bar")), "foo/bar");
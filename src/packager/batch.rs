@@ -0,0 +1,264 @@
+//! Packaging every application listed in a batch manifest.
+//!
+//! Items run on a small pool of threads and each failure is recorded with
+//! its item, so one broken entry does not stop the rest of the batch.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use tracing::info_span;
+
+use crate::models::batch::{
+    BatchFormat, BatchItem, BatchItemResult, BatchManifest, BatchOptions, BatchOutcome, BatchReport,
+};
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::Verbosity;
+use crate::models::size::ByteSize;
+
+use super::package;
+
+/// Load a batch manifest (CSV, JSON or TOML, by extension).
+pub fn load_batch_manifest(path: &Path) -> PackageResult<BatchManifest> {
+    let invalid = |reason: String| PackageError::BatchManifestError {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let format = BatchFormat::from_path(path)
+        .ok_or_else(|| invalid("Expected a .csv, .json or .toml file".to_string()))?;
+    let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(BatchManifest::parse(&text, format)
+        .map_err(invalid)?
+        .with_base(base))
+}
+
+/// Package every item of `manifest` as an `.intunewin`.
+pub fn package_batch(manifest: &BatchManifest, options: &BatchOptions) -> BatchReport {
+    run_items(manifest, options, |item| {
+        let request = item
+            .to_package_request(&manifest.base, options.output_folder.as_deref())
+            .map_err(|reason| PackageError::InvalidArgument { reason })?
+            .with_verbosity(Verbosity::Silent)
+            .with_strictness(options.strictness.clone());
+        Ok(PlannedItem {
+            output_path: request.output_path(),
+            run: Box::new(move || {
+                package(&request).map(|result| (result.output_path, result.package_size))
+            }),
+        })
+    })
+}
+
+/// Package every item of `manifest` as a macOS `.pkg`.
+#[cfg(feature = "macos")]
+pub fn package_macos_batch(manifest: &BatchManifest, options: &BatchOptions) -> BatchReport {
+    run_items(manifest, options, |item| {
+        let request = item
+            .to_macos_request(&manifest.base, options.output_folder.as_deref())
+            .map_err(|reason| PackageError::InvalidArgument { reason })?
+            .with_verbosity(Verbosity::Silent)
+            .with_strictness(options.strictness.clone());
+        Ok(PlannedItem {
+            output_path: request.output_path(),
+            run: Box::new(move || {
+                crate::macos::package(request)
+                    .map(|result| (result.output_path, result.package_size))
+            }),
+        })
+    })
+}
+
+/// An item whose request was built, ready to package.
+struct PlannedItem {
+    output_path: PathBuf,
+    run: Box<dyn FnOnce() -> PackageResult<(PathBuf, ByteSize)> + Send>,
+}
+
+/// Plan every item, then package those that could be planned.
+fn run_items(
+    manifest: &BatchManifest,
+    options: &BatchOptions,
+    plan: impl Fn(&BatchItem) -> PackageResult<PlannedItem>,
+) -> BatchReport {
+    let start_time = Instant::now();
+
+    // Items are packaged silently, so refuse to overwrite where the run
+    // itself would ask first; two items writing one file would race
+    let mut outputs: HashMap<PathBuf, String> = HashMap::new();
+    let mut planned = Vec::new();
+    for (index, item) in manifest.items.iter().enumerate() {
+        let name = item.label(index);
+        let job = plan(item).and_then(|job| {
+            if let Some(other) = outputs.get(&job.output_path) {
+                return Err(PackageError::InvalidArgument {
+                    reason: format!(
+                        "Writes '{}', like item '{}'",
+                        job.output_path.display(),
+                        other
+                    ),
+                });
+            }
+            outputs.insert(job.output_path.clone(), name.clone());
+            if !options.verbosity.suppress_prompts() && job.output_path.exists() {
+                return Err(PackageError::OutputFileExists {
+                    path: job.output_path,
+                });
+            }
+            Ok(job)
+        });
+        planned.push(Mutex::new(Some((name, job))));
+    }
+
+    let items = run_batch(&planned, options.jobs, |slot| {
+        let (name, job) = slot
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .expect("each item runs once");
+        let _span = info_span!("batch_item", name = %name).entered();
+        let item_start = Instant::now();
+        let outcome = match job.and_then(|job| (job.run)()) {
+            Ok((output_path, package_size)) => BatchOutcome::Packaged {
+                output_path,
+                package_size,
+            },
+            Err(error) => BatchOutcome::Failed(error),
+        };
+        BatchItemResult {
+            name,
+            outcome,
+            duration: item_start.elapsed(),
+        }
+    });
+
+    BatchReport {
+        items,
+        elapsed: start_time.elapsed(),
+    }
+}
+
+/// Call `run` for each of `items` on up to `jobs` threads (0 = one per
+/// CPU) and return the results in item order.
+pub fn run_batch<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    run: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let jobs = match jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    }
+    .clamp(1, items.len().max(1));
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let result = run(item);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|result| result.expect("every item has run"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_batch_keeps_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let results = run_batch(&items, 4, |n| {
+            thread::sleep(std::time::Duration::from_millis(20 - n));
+            n * 2
+        });
+        assert_eq!(results, (0..20).map(|n| n * 2).collect::<Vec<_>>());
+        assert!(run_batch(&[] as &[u64], 0, |n| *n).is_empty());
+    }
+
+    #[test]
+    fn test_package_batch_collects_failures() {
+        let temp = TempDir::new().unwrap();
+        for app in ["one", "two"] {
+            let source = temp.path().join(app);
+            fs::create_dir_all(&source).unwrap();
+            fs::write(source.join("setup.exe"), app).unwrap();
+        }
+        let manifest_path = temp.path().join("apps.csv");
+        fs::write(
+            &manifest_path,
+            "name,source,setup,output_name\n\
+             One,one,setup.exe,one\n\
+             Broken,one,missing.exe,broken\n\
+             Two,two,setup.exe,two\n\
+             Clash,two,setup.exe,two\n",
+        )
+        .unwrap();
+
+        let manifest = load_batch_manifest(&manifest_path).unwrap();
+        let options = BatchOptions::default()
+            .with_jobs(2)
+            .with_output_folder(temp.path().join("out"));
+        let report = package_batch(&manifest, &options);
+
+        let names: Vec<_> = report.items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["One", "Broken", "Two", "Clash"]);
+        assert_eq!(report.failed(), 2);
+        assert!(matches!(
+            report.items[1].outcome,
+            BatchOutcome::Failed(PackageError::SetupFileNotFound { .. })
+        ));
+        assert!(matches!(
+            report.items[3].outcome,
+            BatchOutcome::Failed(PackageError::InvalidArgument { .. })
+        ));
+        assert!(temp.path().join("out/one.intunewin").exists());
+        assert!(temp.path().join("out/two.intunewin").exists());
+        assert!(
+            report
+                .to_json()
+                .contains(r#""error_code": "setup_file_not_found""#)
+        );
+
+        // Existing packages are kept unless the run suppresses prompts
+        let report = package_batch(&manifest, &options);
+        assert!(matches!(
+            report.items[0].outcome,
+            BatchOutcome::Failed(PackageError::OutputFileExists { .. })
+        ));
+        let report = package_batch(&manifest, &options.with_verbosity(Verbosity::Quiet));
+        assert!(report.items[0].succeeded());
+    }
+
+    #[test]
+    fn test_load_batch_manifest_errors() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("apps.yaml");
+        fs::write(&path, "apps: []").unwrap();
+        assert!(matches!(
+            load_batch_manifest(&path),
+            Err(PackageError::BatchManifestError { .. })
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Blocklist scanning of collected content.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::models::blocklist::{Blocklist, BlocklistAction, BlocklistMatch};
+use crate::models::error::{PackageError, PackageResult};
+
+use super::content::ContentProvider;
+
+/// Load a blocklist file (CSV or JSON).
+pub fn load_blocklist(path: &Path) -> PackageResult<Blocklist> {
+    let text = fs::read_to_string(path).map_err(|e| PackageError::BlocklistError {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    Blocklist::parse(&text).map_err(|reason| PackageError::BlocklistError {
+        path: path.to_path_buf(),
+        reason,
+    })
+}
+
+/// Compute the lowercase hex SHA-256 of a file.
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    sha256_reader(BufReader::new(File::open(path)?))
+}
+
+/// Compute the lowercase hex SHA-256 of everything `reader` yields.
+pub fn sha256_reader<R: Read>(mut reader: R) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Hash each file of `content` and return those found in the blocklist.
+pub fn scan_files<'a, I>(
+    blocklist: &Blocklist,
+    content: &dyn ContentProvider,
+    relative_paths: I,
+) -> PackageResult<Vec<BlocklistMatch>>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    let mut matches = Vec::new();
+
+    for relative_path in relative_paths {
+        let sha256 = sha256_reader(content.open(relative_path)?)
+            .map_err(|e| PackageError::source_read(content.root().join(relative_path), e))?;
+
+        if let Some(description) = blocklist.lookup(&sha256) {
+            matches.push(BlocklistMatch {
+                path: relative_path.to_path_buf(),
+                sha256,
+                description: description.map(String::from),
+            });
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Load the blocklist at `blocklist_path`, scan the content and apply `action`.
+///
+/// Returns the matches when the action is [`BlocklistAction::Warn`]; fails
+/// with [`PackageError::BlockedContent`] on the first match otherwise.
+pub fn check_blocklist<'a, I>(
+    blocklist_path: &Path,
+    action: BlocklistAction,
+    content: &dyn ContentProvider,
+    relative_paths: I,
+) -> PackageResult<Vec<BlocklistMatch>>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    let blocklist = load_blocklist(blocklist_path)?;
+    let matches = scan_files(&blocklist, content, relative_paths)?;
+
+    if action == BlocklistAction::Fail {
+        if let Some(first) = matches.first() {
+            return Err(PackageError::BlockedContent {
+                path: first.path.clone(),
+                sha256: first.sha256.clone(),
+                count: matches.len(),
+            });
+        }
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::content::FolderContent;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    // SHA-256 of "test"
+    const TEST_HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn test_sha256_file() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("file.txt");
+        fs::write(&path, "test").unwrap();
+
+        assert_eq!(sha256_file(&path).unwrap(), TEST_HASH);
+    }
+
+    #[test]
+    fn test_check_blocklist_fail_and_warn() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("bad.dll"), "test").unwrap();
+        fs::write(source.join("good.dll"), "fine").unwrap();
+
+        let list_path = temp.path().join("hashes.csv");
+        fs::write(&list_path, format!("{},Known bad\n", TEST_HASH)).unwrap();
+
+        let files = [Path::new("bad.dll"), Path::new("good.dll")];
+        let source = FolderContent::new(source);
+
+        let result = check_blocklist(&list_path, BlocklistAction::Fail, &source, files);
+        assert!(matches!(
+            result,
+            Err(PackageError::BlockedContent { count: 1, .. })
+        ));
+
+        let matches = check_blocklist(&list_path, BlocklistAction::Warn, &source, files).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].path, PathBuf::from("bad.dll"));
+        assert_eq!(matches[0].description.as_deref(), Some("Known bad"));
+    }
+
+    #[test]
+    fn test_load_blocklist_missing_file() {
+        let result = load_blocklist(Path::new("/nonexistent/hashes.json"));
+        assert!(matches!(result, Err(PackageError::BlocklistError { .. })));
+    }
+}
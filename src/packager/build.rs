@@ -0,0 +1,301 @@
+//! Building every target of a configuration's `build` section.
+//!
+//! Each format sits behind the [`Target`] trait, so one run can write an
+//! `.intunewin` and a `.pkg` of the same application, and a new format only
+//! needs a target and its request. Targets are planned first, so a
+//! misconfigured one is reported without stopping the others.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use tracing::info_span;
+
+use crate::models::batch::{BatchItem, BatchItemResult, BatchOutcome, BatchReport};
+use crate::models::build::{BuildConfig, BuildContext, TargetKind};
+use crate::models::error::{PackageError, PackageResult};
+#[cfg(feature = "macos")]
+use crate::models::macos::MacosPkgRequest;
+use crate::models::package::{PackageRequest, Verbosity};
+use crate::models::size::ByteSize;
+
+use super::package;
+
+/// A package format a build can write.
+pub trait Target {
+    /// Which format this is.
+    fn kind(&self) -> TargetKind;
+
+    /// Path the package will be written to.
+    fn output_path(&self) -> PathBuf;
+
+    /// Write the package, returning its path and size.
+    fn build(&self) -> PackageResult<(PathBuf, ByteSize)>;
+}
+
+/// An `.intunewin` target.
+#[derive(Debug, Clone)]
+pub struct IntuneWinTarget {
+    /// Request the package is built from
+    pub request: PackageRequest,
+}
+
+impl Target for IntuneWinTarget {
+    fn kind(&self) -> TargetKind {
+        TargetKind::IntuneWin
+    }
+
+    fn output_path(&self) -> PathBuf {
+        self.request.output_path()
+    }
+
+    fn build(&self) -> PackageResult<(PathBuf, ByteSize)> {
+        package(&self.request).map(|result| (result.output_path, result.package_size))
+    }
+}
+
+/// A macOS `.pkg` target.
+#[cfg(feature = "macos")]
+#[derive(Debug, Clone)]
+pub struct MacPkgTarget {
+    /// Request the package is built from
+    pub request: MacosPkgRequest,
+}
+
+#[cfg(feature = "macos")]
+impl Target for MacPkgTarget {
+    fn kind(&self) -> TargetKind {
+        TargetKind::MacPkg
+    }
+
+    fn output_path(&self) -> PathBuf {
+        self.request.output_path()
+    }
+
+    fn build(&self) -> PackageResult<(PathBuf, ByteSize)> {
+        crate::macos::package(self.request.clone())
+            .map(|result| (result.output_path, result.package_size))
+    }
+}
+
+/// Build the target of `kind` from its merged configuration fields.
+pub fn plan_target(
+    kind: TargetKind,
+    item: &BatchItem,
+    context: &BuildContext,
+) -> PackageResult<Box<dyn Target>> {
+    let invalid = |reason: String| PackageError::InvalidArgument {
+        reason: format!("build.{}: {}", kind, reason),
+    };
+    let output = context.output_folder.as_deref();
+
+    match kind {
+        TargetKind::IntuneWin => {
+            let request = item
+                .to_package_request(&context.base, output)
+                .map_err(invalid)?
+                .with_verbosity(Verbosity::Silent)
+                .with_strictness(context.strictness.clone());
+            Ok(Box::new(IntuneWinTarget { request }))
+        }
+        #[cfg(feature = "macos")]
+        TargetKind::MacPkg => {
+            let request = item
+                .to_macos_request(&context.base, output)
+                .map_err(invalid)?
+                .with_verbosity(Verbosity::Silent)
+                .with_strictness(context.strictness.clone());
+            Ok(Box::new(MacPkgTarget { request }))
+        }
+        #[cfg(not(feature = "macos"))]
+        TargetKind::MacPkg => Err(invalid(
+            "macOS packaging is not enabled. Build with --features macos".to_string(),
+        )),
+    }
+}
+
+/// The targets to build, in configuration order.
+fn selected_targets<'a>(
+    config: &'a BuildConfig,
+    context: &BuildContext,
+) -> PackageResult<Vec<(TargetKind, &'a BatchItem)>> {
+    if config.targets.is_empty() {
+        return Err(PackageError::InvalidArgument {
+            reason: format!(
+                "No build targets configured (expected a 'build' table for one of: {})",
+                TargetKind::ALL.map(TargetKind::name).join(", ")
+            ),
+        });
+    }
+    if let Some(kind) = context
+        .targets
+        .iter()
+        .find(|kind| !config.targets.iter().any(|(other, _)| other == *kind))
+    {
+        return Err(PackageError::InvalidArgument {
+            reason: format!("Target '{}' is not configured in 'build'", kind),
+        });
+    }
+
+    Ok(config
+        .targets
+        .iter()
+        .filter(|(kind, _)| context.targets.is_empty() || context.targets.contains(kind))
+        .map(|(kind, item)| (*kind, item))
+        .collect())
+}
+
+/// Build every selected target of `config`, one after the other.
+///
+/// Failures are recorded per target; the error is returned only when there
+/// is nothing to build.
+pub fn build(config: &BuildConfig, context: &BuildContext) -> PackageResult<BatchReport> {
+    let start_time = Instant::now();
+    let selected = selected_targets(config, context)?;
+
+    // Targets are built silently, so refuse to overwrite where the run
+    // itself would ask first
+    let mut outputs: HashMap<PathBuf, TargetKind> = HashMap::new();
+    let mut planned = Vec::new();
+    for (kind, item) in selected {
+        let target =
+            plan_target(kind, &config.target_item(kind, item), context).and_then(|target| {
+                let output_path = target.output_path();
+                if let Some(other) = outputs.get(&output_path) {
+                    return Err(PackageError::InvalidArgument {
+                        reason: format!(
+                            "Writes '{}', like target '{}'",
+                            output_path.display(),
+                            other
+                        ),
+                    });
+                }
+                outputs.insert(output_path.clone(), kind);
+                if !context.verbosity.suppress_prompts() && output_path.exists() {
+                    return Err(PackageError::OutputFileExists { path: output_path });
+                }
+                Ok(target)
+            });
+        planned.push((kind, target));
+    }
+
+    let items = planned
+        .into_iter()
+        .map(|(kind, target)| {
+            let _span = info_span!("build_target", target = %kind).entered();
+            let target_start = Instant::now();
+            let outcome = match target.and_then(|target| target.build()) {
+                Ok((output_path, package_size)) => BatchOutcome::Packaged {
+                    output_path,
+                    package_size,
+                },
+                Err(error) => BatchOutcome::Failed(error),
+            };
+            BatchItemResult {
+                name: kind.name().to_string(),
+                outcome,
+                duration: target_start.elapsed(),
+            }
+        })
+        .collect();
+
+    Ok(BatchReport {
+        items,
+        elapsed: start_time.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const CONFIG: &str = r#"
+[build]
+name = "Contoso Tool"
+version = "1.2.0"
+source = "dist"
+output = "out"
+
+[build.intune]
+source = "windows"
+setup = "setup.exe"
+
+[build.macos]
+source = "mac"
+identifier = "com.contoso.tool"
+"#;
+
+    fn write_sources(root: &std::path::Path) {
+        fs::create_dir_all(root.join("dist/windows")).unwrap();
+        fs::write(root.join("dist/windows/setup.exe"), b"MZ installer").unwrap();
+        fs::create_dir_all(root.join("dist/mac/Applications")).unwrap();
+        fs::write(root.join("dist/mac/Applications/tool"), b"binary").unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "macos")]
+    fn test_build_targets() {
+        let temp = TempDir::new().unwrap();
+        write_sources(temp.path());
+        let config = Config::parse_toml(CONFIG).unwrap().build;
+        let context = BuildContext::new(temp.path()).with_verbosity(Verbosity::Silent);
+
+        let report = build(&config, &context).unwrap();
+        assert!(report.passed());
+        let names: Vec<_> = report.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["intune", "macos"]);
+        assert!(temp.path().join("out/setup.intunewin").exists());
+        assert!(
+            fs::read_dir(temp.path().join("out"))
+                .unwrap()
+                .any(|entry| entry.unwrap().path().extension() == Some("pkg".as_ref()))
+        );
+    }
+
+    #[test]
+    fn test_build_selected_target() {
+        let temp = TempDir::new().unwrap();
+        write_sources(temp.path());
+        let config = Config::parse_toml(CONFIG).unwrap().build;
+        let context = BuildContext::new(temp.path())
+            .with_verbosity(Verbosity::Silent)
+            .with_targets(vec![TargetKind::IntuneWin])
+            .with_output_folder(temp.path().join("unused"));
+
+        let report = build(&config, &context).unwrap();
+        assert_eq!(report.items.len(), 1);
+        assert!(report.passed());
+
+        // The existing package is not overwritten without --quiet
+        let context = BuildContext::new(temp.path()).with_targets(vec![TargetKind::IntuneWin]);
+        let report = build(&config, &context).unwrap();
+        assert!(matches!(
+            report.items[0].outcome,
+            BatchOutcome::Failed(PackageError::OutputFileExists { .. })
+        ));
+    }
+
+    #[test]
+    fn test_build_configuration_errors() {
+        let temp = TempDir::new().unwrap();
+        let context = BuildContext::new(temp.path()).with_verbosity(Verbosity::Silent);
+        assert!(build(&BuildConfig::default(), &context).is_err());
+
+        let config = Config::parse_toml("[build.intune]\nsource = \"src\"")
+            .unwrap()
+            .build;
+        let missing = context.clone().with_targets(vec![TargetKind::MacPkg]);
+        assert!(build(&config, &missing).is_err());
+
+        // A target missing a required field fails on its own
+        let report = build(&config, &context).unwrap();
+        assert!(!report.passed());
+        assert!(matches!(
+            &report.items[0].outcome,
+            BatchOutcome::Failed(error) if error.to_string().contains("setup")
+        ));
+    }
+}
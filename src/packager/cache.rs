@@ -0,0 +1,305 @@
+//! Content-addressed cache of compressed files.
+//!
+//! Each blob is a single-entry ZIP at `<dir>/<aa>/<sha256>.zip`, named after
+//! the SHA-256 of the uncompressed file and compressed exactly as the inner
+//! ZIP compresses content. Unchanged files are raw-copied from their blob
+//! into the next package instead of being compressed again. Blobs are
+//! renamed into place once complete, so a run that is interrupted leaves
+//! only whole blobs behind and the next run resumes from them.
+//!
+//! Blobs compressed with other than the default settings carry the method
+//! and level in their name (`<sha256>-deflate9.zip`, `<sha256>-stored.zip`),
+//! so runs with different settings never share them.
+//!
+//! Raw-copied entries carry permissions `0644` without the regular file
+//! type bits; readers, Intune included, treat them as regular files.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use zip::ZipWriter;
+use zip::read::ZipArchive;
+
+use crate::models::cache::{CacheConfig, CacheStats};
+use crate::models::compression::{Compression, CompressionMethod};
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::size::ByteSize;
+
+use super::archive::content_options;
+use super::content::ContentProvider;
+use super::manifest::HashingReader;
+
+/// Name of the single entry in every blob.
+const BLOB_ENTRY: &str = "blob";
+
+/// An open content cache and its hit counts.
+#[derive(Debug)]
+pub struct ContentCache {
+    config: CacheConfig,
+    compression: Compression,
+    stats: CacheStats,
+}
+
+impl ContentCache {
+    /// Open the cache in `config` for files compressed as `compression`
+    /// asks, creating its folder when missing.
+    pub fn open(config: &CacheConfig, compression: Compression) -> PackageResult<Self> {
+        fs::create_dir_all(&config.dir).map_err(|e| cache_error(&config.dir, e))?;
+        Ok(Self {
+            config: config.clone(),
+            compression,
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Whether a file of `size` bytes goes through the cache.
+    pub fn accepts(&self, size: u64) -> bool {
+        size >= self.config.min_file_size.bytes()
+    }
+
+    /// How the cache has been used so far.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Add `relative_path` from `content` to `zip` as `zip_path`, copying the
+    /// compressed data from the cache and storing it there first if needed.
+    ///
+    /// Returns the size and SHA-256 of the file.
+    pub fn write_entry<W: Write + Seek>(
+        &mut self,
+        zip: &mut ZipWriter<W>,
+        zip_path: &str,
+        content: &dyn ContentProvider,
+        relative_path: &Path,
+    ) -> PackageResult<(u64, String)> {
+        let source_path = content.root().join(relative_path);
+        let read_error = |e: io::Error| PackageError::source_read(source_path.clone(), e);
+
+        // Hashing is much cheaper than compressing, so look the file up first
+        let mut reader = HashingReader::new(content.open(relative_path)?);
+        io::copy(&mut reader, &mut io::sink()).map_err(read_error)?;
+        let (size, sha256) = reader.finish();
+
+        let blob_path = self.blob_path(&sha256);
+        let mut blob = match open_blob(&blob_path, size) {
+            Some(blob) => {
+                self.stats.hits += 1;
+                self.stats.reused += ByteSize(size);
+                blob
+            }
+            None => {
+                self.store(&blob_path, content, relative_path, size, &sha256)?;
+                self.stats.misses += 1;
+                open_blob(&blob_path, size).ok_or_else(|| PackageError::CacheError {
+                    path: blob_path.clone(),
+                    reason: "Stored blob cannot be read back".to_string(),
+                })?
+            }
+        };
+
+        let entry = blob
+            .by_index(0)
+            .map_err(|e| PackageError::zip(ZipStage::WriteContent, Some(zip_path), e))?;
+        zip.raw_copy_file_rename(entry, zip_path)
+            .map_err(|e| PackageError::zip(ZipStage::WriteContent, Some(zip_path), e))?;
+        Ok((size, sha256))
+    }
+
+    /// Compress the file into a new blob at `blob_path`.
+    fn store(
+        &self,
+        blob_path: &Path,
+        content: &dyn ContentProvider,
+        relative_path: &Path,
+        size: u64,
+        sha256: &str,
+    ) -> PackageResult<()> {
+        let parent = blob_path.parent().unwrap_or(&self.config.dir);
+        fs::create_dir_all(parent).map_err(|e| cache_error(parent, e))?;
+
+        // Concurrent runs each write their own partial file
+        let partial = blob_path.with_extension(format!("{}.partial", std::process::id()));
+        let result = write_blob(
+            &partial,
+            &self.compression,
+            content,
+            relative_path,
+            size,
+            sha256,
+        )
+        .and_then(|_| fs::rename(&partial, blob_path).map_err(|e| cache_error(blob_path, e)));
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        let name = match self.compression.method {
+            _ if self.compression.is_default() => format!("{}.zip", sha256),
+            CompressionMethod::Stored => format!("{}-stored.zip", sha256),
+            CompressionMethod::Deflate => format!(
+                "{}-deflate{}.zip",
+                sha256,
+                self.compression.effective_level()
+            ),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => {
+                format!("{}-zstd{}.zip", sha256, self.compression.effective_level())
+            }
+        };
+        self.config.dir.join(&sha256[..2]).join(name)
+    }
+}
+
+/// Write a blob holding the file, checking it still has the hashed content.
+fn write_blob(
+    path: &Path,
+    compression: &Compression,
+    content: &dyn ContentProvider,
+    relative_path: &Path,
+    size: u64,
+    sha256: &str,
+) -> PackageResult<()> {
+    let file = File::create(path).map_err(|e| cache_error(path, e))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let options = content_options(compression).large_file(size >= u32::MAX as u64);
+    zip.start_file(BLOB_ENTRY, options)
+        .map_err(|e| cache_error(path, e))?;
+
+    let mut reader = HashingReader::new(content.open(relative_path)?);
+    io::copy(&mut reader, &mut zip)
+        .map_err(|e| PackageError::source_read(content.root().join(relative_path), e))?;
+    if reader.finish() != (size, sha256.to_string()) {
+        return Err(PackageError::SourceReadError {
+            path: content.root().join(relative_path),
+            reason: "File changed while it was being packaged".to_string(),
+            source: None,
+        });
+    }
+
+    zip.finish()
+        .and_then(|mut writer| writer.flush().map_err(Into::into))
+        .map_err(|e| cache_error(path, e))
+}
+
+/// Open a blob, or `None` when it is missing or does not hold `size` bytes.
+fn open_blob(path: &Path, size: u64) -> Option<ZipArchive<BufReader<File>>> {
+    let file = File::open(path).ok()?;
+    let mut archive = ZipArchive::new(BufReader::new(file)).ok()?;
+    let matches = archive.len() == 1 && archive.by_index(0).ok()?.size() == size;
+    matches.then_some(archive)
+}
+
+fn cache_error(path: &Path, error: impl std::fmt::Display) -> PackageError {
+    PackageError::CacheError {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::content::FolderContent;
+    use std::io::{Cursor, Read};
+    use tempfile::TempDir;
+
+    fn zip_with(cache: &mut ContentCache, content: &FolderContent) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+        cache
+            .write_entry(&mut zip, "dir/data.bin", content, Path::new("data.bin"))
+            .unwrap();
+        zip.finish().unwrap();
+        drop(zip);
+        buffer
+    }
+
+    #[test]
+    fn test_write_entry_reuses_blob() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(source.join("data.bin"), &data).unwrap();
+        let content = FolderContent::new(&source);
+
+        let config = CacheConfig::new(temp.path().join("cache"));
+        let mut cache = ContentCache::open(&config, Compression::default()).unwrap();
+        let first = zip_with(&mut cache, &content);
+        assert_eq!(cache.stats().misses, 1);
+
+        let mut cache = ContentCache::open(&config, Compression::default()).unwrap();
+        let second = zip_with(&mut cache, &content);
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().reused, ByteSize(data.len() as u64));
+        assert_eq!(first, second);
+
+        let mut archive = ZipArchive::new(Cursor::new(second)).unwrap();
+        let mut entry = archive.by_name("dir/data.bin").unwrap();
+        let mut read = Vec::new();
+        entry.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn test_compression_settings_keep_separate_blobs() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("data.bin"), "cached content").unwrap();
+        let content = FolderContent::new(temp.path());
+        let config = CacheConfig::new(temp.path().join("cache"));
+
+        let mut deflated = ContentCache::open(&config, Compression::default()).unwrap();
+        zip_with(&mut deflated, &content);
+        let mut stored = ContentCache::open(&config, Compression::stored()).unwrap();
+        let data = zip_with(&mut stored, &content);
+        assert_eq!(stored.stats().misses, 1);
+
+        let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+        let entry = archive.by_name("dir/data.bin").unwrap();
+        assert_eq!(entry.compression(), zip::CompressionMethod::Stored);
+
+        let sha256 = "ab".repeat(32);
+        let smallest = ContentCache::open(&config, Compression::default().with_level(9)).unwrap();
+        assert!(
+            deflated
+                .blob_path(&sha256)
+                .ends_with(format!("{}.zip", sha256))
+        );
+        assert!(
+            stored
+                .blob_path(&sha256)
+                .ends_with(format!("{}-stored.zip", sha256))
+        );
+        assert!(
+            smallest
+                .blob_path(&sha256)
+                .ends_with(format!("{}-deflate9.zip", sha256))
+        );
+    }
+
+    #[test]
+    fn test_damaged_blob_is_replaced() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("data.bin"), "cached content").unwrap();
+        let content = FolderContent::new(temp.path());
+        let mut cache = ContentCache::open(
+            &CacheConfig::new(temp.path().join("cache")),
+            Compression::default(),
+        )
+        .unwrap();
+        zip_with(&mut cache, &content);
+
+        let mut reader = HashingReader::new(&b"cached content"[..]);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        let blob = cache.blob_path(&reader.finish().1);
+        fs::write(&blob, "truncated").unwrap();
+
+        zip_with(&mut cache, &content);
+        assert_eq!(cache.stats().misses, 2);
+        assert!(open_blob(&blob, 14).is_some());
+    }
+}
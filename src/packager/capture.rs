@@ -0,0 +1,281 @@
+//! Before/after capture of a directory tree for repackaging.
+//!
+//! `capture start` hashes every file under a root and saves the snapshot;
+//! `capture finish` hashes the tree again, stages added and changed files
+//! into a temporary folder and packages that folder.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walkdir::WalkDir;
+
+use crate::models::capture::{CaptureEntry, CaptureFinishRequest, CaptureResult, CaptureSnapshot};
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::guardrails::check_source_folder;
+use crate::models::package::PackageRequest;
+use crate::models::suggest::rank_suggestions;
+
+use super::blocklist::sha256_file;
+use super::workspace::{StagingDir, stage_file};
+
+/// Default snapshot file name, written to the current directory.
+pub const DEFAULT_STATE_FILE: &str = ".iamawrapper-capture.json";
+
+/// Hash every file under `root`, skipping `exclude` if it lies inside.
+pub fn take_snapshot(root: &Path, exclude: Option<&Path>) -> PackageResult<CaptureSnapshot> {
+    let root = root
+        .canonicalize()
+        .map_err(|_| PackageError::SourceFolderNotFound {
+            path: root.to_path_buf(),
+        })?;
+    if !root.is_dir() {
+        return Err(PackageError::SourceFolderNotFound { path: root });
+    }
+
+    let exclude = exclude.and_then(|p| p.canonicalize().ok());
+    let mut entries = Vec::new();
+
+    for entry in WalkDir::new(&root).follow_links(false) {
+        let entry = entry.map_err(|e| PackageError::source_read(root.clone(), e))?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if exclude.as_deref() == Some(entry.path()) {
+            continue;
+        }
+
+        let full_path = entry.path();
+        let size = entry
+            .metadata()
+            .map_err(|e| PackageError::source_read(full_path.to_path_buf(), e))?
+            .len();
+        let sha256 = sha256_file(full_path)
+            .map_err(|e| PackageError::source_read(full_path.to_path_buf(), e))?;
+
+        entries.push(CaptureEntry {
+            path: full_path
+                .strip_prefix(&root)
+                .unwrap_or(full_path)
+                .to_path_buf(),
+            size,
+            sha256,
+        });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    Ok(CaptureSnapshot {
+        root,
+        created,
+        entries,
+    })
+}
+
+/// Snapshot `root` and write the snapshot to `state_file`.
+///
+/// Filesystem roots and the home directory are refused unless
+/// `allow_dangerous_source` is set.
+pub fn capture_start(
+    root: &Path,
+    state_file: &Path,
+    allow_dangerous_source: bool,
+) -> PackageResult<CaptureSnapshot> {
+    check_source_folder(root, allow_dangerous_source)?;
+    let snapshot = take_snapshot(root, Some(state_file))?;
+
+    fs::write(state_file, snapshot.to_json())
+        .map_err(|e| PackageError::output_write(state_file.to_path_buf(), e))?;
+
+    Ok(snapshot)
+}
+
+/// Read a snapshot written by [`capture_start`].
+pub fn load_snapshot(state_file: &Path) -> PackageResult<CaptureSnapshot> {
+    let text = fs::read_to_string(state_file).map_err(|e| PackageError::CaptureError {
+        reason: format!(
+            "Cannot read capture state '{}': {} (run 'capture start' first)",
+            state_file.display(),
+            e
+        ),
+    })?;
+
+    CaptureSnapshot::from_json(&text).map_err(|reason| PackageError::CaptureError {
+        reason: format!(
+            "Invalid capture state '{}': {}",
+            state_file.display(),
+            reason
+        ),
+    })
+}
+
+/// Diff the captured tree against its snapshot and package the changes.
+pub fn capture_finish(request: &CaptureFinishRequest) -> PackageResult<CaptureResult> {
+    let before = load_snapshot(&request.state_file)?;
+    let after = take_snapshot(&before.root, Some(&request.state_file))?;
+    let diff = before.diff(&after);
+
+    if diff.is_empty() {
+        return Err(PackageError::CaptureError {
+            reason: format!(
+                "No files were added or changed under {} since capture start",
+                before.root.display()
+            ),
+        });
+    }
+
+    let staging = StagingDir::create("capture")?;
+    for relative_path in diff.staged() {
+        copy_file(
+            &before.root.join(&relative_path),
+            &staging.path.join(&relative_path),
+        )?;
+    }
+
+    let setup_file = stage_setup_file(&request.setup_file, &staging.path, &diff.staged())?;
+
+    let mut package_request = PackageRequest::new(
+        staging.path.clone(),
+        setup_file,
+        request.output_folder.clone(),
+    )
+    .with_verbosity(request.verbosity)
+    .with_strictness(request.strictness.clone());
+    if let Some(name) = &request.output_name {
+        package_request = package_request.with_output_name(name.clone());
+    }
+
+    let package = super::package(&package_request)?;
+
+    Ok(CaptureResult { diff, package })
+}
+
+/// Resolve the setup file for the staged package.
+///
+/// An existing file on disk is copied into the staging root; otherwise the
+/// name must refer to one of the captured files.
+fn stage_setup_file(setup: &str, staging: &Path, staged: &[PathBuf]) -> PackageResult<String> {
+    let setup_path = Path::new(setup);
+
+    if setup_path.is_file() {
+        let file_name = setup_path
+            .file_name()
+            .ok_or_else(|| PackageError::InvalidArgument {
+                reason: format!("Invalid setup file path: {}", setup),
+            })?;
+        let target = staging.join(file_name);
+        if target.exists() {
+            return Err(PackageError::CaptureError {
+                reason: format!(
+                    "Setup file '{}' collides with a captured file of the same name",
+                    file_name.to_string_lossy()
+                ),
+            });
+        }
+        copy_file(setup_path, &target)?;
+        return Ok(file_name.to_string_lossy().into_owned());
+    }
+
+    if staged.iter().any(|p| p == setup_path) {
+        return Ok(setup.to_string());
+    }
+
+    Err(PackageError::SetupFileNotFound {
+        file: setup.to_string(),
+        folder: staging.to_path_buf(),
+        suggestions: rank_suggestions(
+            setup,
+            staged
+                .iter()
+                .map(|p| p.to_string_lossy().replace('\\', "/")),
+        ),
+    })
+}
+
+fn copy_file(from: &Path, to: &Path) -> PackageResult<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+    }
+
+    stage_file(from, to).map_err(|e| PackageError::source_read(from.to_path_buf(), e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::Verbosity;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_take_snapshot_excludes_state_file() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("bin")).unwrap();
+        fs::write(temp.path().join("bin/app"), "v1").unwrap();
+        let state = temp.path().join("state.json");
+        fs::write(&state, "{}").unwrap();
+
+        let snapshot = take_snapshot(temp.path(), Some(&state)).unwrap();
+
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].path, PathBuf::from("bin/app"));
+        assert_eq!(snapshot.entries[0].size, 2);
+    }
+
+    #[test]
+    fn test_capture_finish_packages_changes() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(root.join("app/unchanged.dll"), "same").unwrap();
+        fs::write(root.join("app/config.ini"), "old").unwrap();
+
+        let state = temp.path().join("state.json");
+        capture_start(&root, &state, false).unwrap();
+
+        fs::write(root.join("app/config.ini"), "new").unwrap();
+        fs::write(root.join("app/added.dll"), "added").unwrap();
+
+        let setup = temp.path().join("install.cmd");
+        fs::write(&setup, "xcopy app").unwrap();
+
+        let request = CaptureFinishRequest::new(
+            state,
+            setup.to_string_lossy().into_owned(),
+            temp.path().join("out"),
+        )
+        .with_verbosity(Verbosity::Silent);
+        let result = capture_finish(&request).unwrap();
+
+        assert_eq!(result.diff.added, vec![PathBuf::from("app/added.dll")]);
+        assert_eq!(result.diff.changed, vec![PathBuf::from("app/config.ini")]);
+        assert_eq!(result.package.file_count, 3);
+        assert_eq!(result.package.metadata.setup_file, "install.cmd");
+        assert!(temp.path().join("out/install.intunewin").exists());
+    }
+
+    #[test]
+    fn test_capture_finish_no_changes() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("file"), "same").unwrap();
+
+        let state = temp.path().join("state.json");
+        capture_start(&root, &state, false).unwrap();
+
+        let request = CaptureFinishRequest::new(state, "file".to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = capture_finish(&request);
+
+        assert!(matches!(result, Err(PackageError::CaptureError { .. })));
+    }
+}
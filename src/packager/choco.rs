@@ -0,0 +1,523 @@
+//! Chocolatey package (.nupkg) creation.
+//!
+//! A `.nupkg` is a ZIP holding the `.nuspec` manifest, the Open Packaging
+//! Conventions parts NuGet expects (`[Content_Types].xml`, `_rels/.rels`
+//! and a core properties part) and the content. The content goes under
+//! `tools/` next to a generated `chocolateyInstall.ps1` that runs the
+//! setup file, which is what `choco pack` would produce from a
+//! hand-written package.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, Write};
+use std::time::Instant;
+
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use zip::ZipWriter;
+
+use crate::models::choco::{ChocoPackRequest, ChocoPackResult, TOOLS_FOLDER};
+use crate::models::compression::Compression;
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::package::SourcePackage;
+use crate::models::size::ByteSize;
+
+use super::archive::{entry_options, normalize_path, write_content_files};
+use super::content::{ContentProvider, FolderContent};
+use super::encrypt::compute_sha256;
+use super::progress::Progress;
+
+/// Name of the install script Chocolatey runs.
+pub const INSTALL_SCRIPT: &str = "chocolateyInstall.ps1";
+
+const NUSPEC_NAMESPACE: &str = "http://schemas.microsoft.com/packaging/2015/06/nuspec.xsd";
+const MANIFEST_RELATIONSHIP: &str = "http://schemas.microsoft.com/packaging/2010/07/manifest";
+const CORE_PROPERTIES_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties";
+const OCTET_STREAM: &str = "application/octet";
+
+/// Exit codes Chocolatey treats as success: done, and done but a reboot
+/// is needed.
+const VALID_EXIT_CODES: &str = "@(0, 3010, 1641)";
+
+/// Create a Chocolatey package from the given request.
+pub fn pack(request: &ChocoPackRequest) -> PackageResult<ChocoPackResult> {
+    let start_time = Instant::now();
+
+    request.validate()?;
+
+    let content = FolderContent::new(&request.source_folder);
+    let source = content.collect(&request.setup_file, &request.filter)?;
+    let install_script = generate_install_script(request)?;
+
+    if !request.output_folder.exists() {
+        fs::create_dir_all(&request.output_folder)
+            .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
+    }
+
+    let output_path = request.output_path();
+    if output_path.exists() && !request.verbosity.suppress_prompts() {
+        return Err(PackageError::OutputFileExists { path: output_path });
+    }
+
+    let file = File::create(&output_path)
+        .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+    let progress = Progress::bar(source.file_count() as u64, request.verbosity);
+    write_nupkg(
+        BufWriter::new(file),
+        request,
+        &source,
+        &content,
+        &install_script,
+        &progress,
+    )?;
+    progress.finish("Done!");
+
+    Ok(ChocoPackResult {
+        package_size: ByteSize(fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0)),
+        output_path,
+        file_count: source.file_count(),
+        install_script,
+        creation_time: start_time.elapsed(),
+    })
+}
+
+/// Write the package: nuspec first, then the content, then the parts
+/// describing it.
+fn write_nupkg<W: Write + Seek>(
+    writer: W,
+    request: &ChocoPackRequest,
+    source: &SourcePackage,
+    content: &dyn ContentProvider,
+    install_script: &str,
+    progress: &Progress,
+) -> PackageResult<()> {
+    // Core properties parts are named by a GUID in NuGet's packages; any
+    // unique name works, so derive one that is stable across runs
+    let digest = compute_sha256(format!("{}.{}", request.id, request.version).as_bytes());
+    let core_properties = format!(
+        "package/services/metadata/core-properties/{}.psmdcp",
+        hex_string(&digest[..16])
+    );
+    let nuspec = format!("{}.nuspec", request.id);
+
+    let mut zip = ZipWriter::new(writer);
+
+    add_entry(&mut zip, &nuspec, generate_nuspec(request)?.as_bytes())?;
+    write_content_files(
+        &mut zip,
+        source,
+        content,
+        &format!("{}/", TOOLS_FOLDER),
+        &Compression::default(),
+        progress,
+        None,
+    )?;
+    add_entry(
+        &mut zip,
+        &format!("{}/{}", TOOLS_FOLDER, INSTALL_SCRIPT),
+        install_script.as_bytes(),
+    )?;
+
+    // Keep Chocolatey from creating command shims for packaged executables
+    let executables: Vec<_> = source
+        .files
+        .iter()
+        .map(|f| normalize_path(&f.relative_path))
+        .filter(|p| p.to_ascii_lowercase().ends_with(".exe"))
+        .collect();
+    for path in &executables {
+        add_entry(&mut zip, &format!("{}/{}.ignore", TOOLS_FOLDER, path), b"")?;
+    }
+
+    add_entry(
+        &mut zip,
+        "_rels/.rels",
+        generate_relationships(&nuspec, &core_properties)?.as_bytes(),
+    )?;
+    add_entry(
+        &mut zip,
+        &core_properties,
+        generate_core_properties(request)?.as_bytes(),
+    )?;
+    add_entry(
+        &mut zip,
+        "[Content_Types].xml",
+        generate_content_types(source)?.as_bytes(),
+    )?;
+
+    zip.finish()
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, None, e))?;
+    Ok(())
+}
+
+fn add_entry<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    data: &[u8],
+) -> PackageResult<()> {
+    zip.start_file(name, entry_options(zip::CompressionMethod::Deflated))
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(name), e))?;
+    zip.write_all(data)
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(name), e))
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Quote `value` as a PowerShell single-quoted string.
+pub(crate) fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Generate the `chocolateyInstall.ps1` that runs the setup file.
+///
+/// `.exe`, `.msi` and `.msu` installers go through
+/// `Install-ChocolateyInstallPackage`; PowerShell scripts are run directly.
+pub fn generate_install_script(request: &ChocoPackRequest) -> PackageResult<String> {
+    let setup = request.setup_file.replace('/', "\\");
+    let extension = setup
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let mut script = String::from(
+        "$ErrorActionPreference = 'Stop'\r\n\
+         $toolsDir = Split-Path -Parent $MyInvocation.MyCommand.Definition\r\n\r\n",
+    );
+
+    match extension.as_str() {
+        "exe" | "msi" | "msu" => {
+            let default_args = if extension == "msi" {
+                "/qn /norestart"
+            } else {
+                ""
+            };
+            let silent_args = request.silent_args.as_deref().unwrap_or(default_args);
+            script.push_str(&format!(
+                "$packageArgs = @{{\r\n  \
+                   packageName    = $env:ChocolateyPackageName\r\n  \
+                   fileType       = {}\r\n  \
+                   file           = Join-Path $toolsDir {}\r\n  \
+                   silentArgs     = {}\r\n  \
+                   validExitCodes = {}\r\n\
+                 }}\r\n\r\n\
+                 Install-ChocolateyInstallPackage @packageArgs\r\n",
+                ps_quote(&extension),
+                ps_quote(&setup),
+                ps_quote(silent_args),
+                VALID_EXIT_CODES
+            ));
+        }
+        "ps1" => {
+            let arguments = request
+                .silent_args
+                .as_deref()
+                .map(|args| format!(" {}", args))
+                .unwrap_or_default();
+            script.push_str(&format!(
+                "& (Join-Path $toolsDir {}){}\r\n",
+                ps_quote(&setup),
+                arguments
+            ));
+        }
+        _ => {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "Cannot generate a Chocolatey install script for '{}': \
+                     expected an .exe, .msi, .msu or .ps1 setup file",
+                    request.setup_file
+                ),
+            });
+        }
+    }
+
+    Ok(script)
+}
+
+/// Generate the `.nuspec` manifest.
+pub fn generate_nuspec(request: &ChocoPackRequest) -> PackageResult<String> {
+    let mut xml = XmlDocument::new()?;
+    let mut package = BytesStart::new("package");
+    package.push_attribute(("xmlns", NUSPEC_NAMESPACE));
+    xml.start(package)?;
+    xml.start(BytesStart::new("metadata"))?;
+    xml.element("id", &request.id)?;
+    xml.element("version", &request.version)?;
+    xml.element("title", request.title())?;
+    xml.element("authors", request.authors.as_deref().unwrap_or("Unknown"))?;
+    xml.element(
+        "description",
+        request.description.as_deref().unwrap_or(request.title()),
+    )?;
+    xml.end("metadata")?;
+    xml.end("package")?;
+    xml.finish()
+}
+
+/// Generate the package relationships pointing at the manifest and the
+/// core properties.
+fn generate_relationships(nuspec: &str, core_properties: &str) -> PackageResult<String> {
+    let mut xml = XmlDocument::new()?;
+    let mut root = BytesStart::new("Relationships");
+    root.push_attribute((
+        "xmlns",
+        "http://schemas.openxmlformats.org/package/2006/relationships",
+    ));
+    xml.start(root)?;
+    for (id, kind, target) in [
+        ("R1", MANIFEST_RELATIONSHIP, nuspec),
+        ("R2", CORE_PROPERTIES_RELATIONSHIP, core_properties),
+    ] {
+        let mut relationship = BytesStart::new("Relationship");
+        let target = format!("/{}", target);
+        relationship.push_attribute(("Type", kind));
+        relationship.push_attribute(("Target", target.as_str()));
+        relationship.push_attribute(("Id", id));
+        xml.empty(relationship)?;
+    }
+    xml.end("Relationships")?;
+    xml.finish()
+}
+
+/// Generate the core properties part, which repeats the main metadata.
+fn generate_core_properties(request: &ChocoPackRequest) -> PackageResult<String> {
+    let mut xml = XmlDocument::new()?;
+    let mut root = BytesStart::new("coreProperties");
+    root.push_attribute(("xmlns:dc", "http://purl.org/dc/elements/1.1/"));
+    root.push_attribute(("xmlns:dcterms", "http://purl.org/dc/terms/"));
+    root.push_attribute(("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"));
+    root.push_attribute((
+        "xmlns",
+        "http://schemas.openxmlformats.org/package/2006/metadata/core-properties",
+    ));
+    xml.start(root)?;
+    xml.element(
+        "dc:creator",
+        request.authors.as_deref().unwrap_or("Unknown"),
+    )?;
+    xml.element(
+        "dc:description",
+        request.description.as_deref().unwrap_or(request.title()),
+    )?;
+    xml.element("dc:identifier", &request.id)?;
+    xml.element("version", &request.version)?;
+    xml.element("dc:title", request.title())?;
+    xml.element(
+        "lastModifiedBy",
+        &format!("iamawrapper {}", env!("CARGO_PKG_VERSION")),
+    )?;
+    xml.end("coreProperties")?;
+    xml.finish()
+}
+
+/// Generate `[Content_Types].xml`, giving every part a content type.
+///
+/// Parts are typed by extension; files without one get an override each.
+fn generate_content_types(source: &SourcePackage) -> PackageResult<String> {
+    let mut extensions: BTreeSet<String> = ["ps1", "ignore"].map(String::from).into();
+    let mut overrides = Vec::new();
+    for file in &source.files {
+        let path = normalize_path(&file.relative_path);
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => {
+                extensions.insert(ext.to_ascii_lowercase());
+            }
+            _ => overrides.push(format!("/{}/{}", TOOLS_FOLDER, path)),
+        }
+    }
+
+    let mut xml = XmlDocument::new()?;
+    let mut root = BytesStart::new("Types");
+    root.push_attribute((
+        "xmlns",
+        "http://schemas.openxmlformats.org/package/2006/content-types",
+    ));
+    xml.start(root)?;
+
+    let defaults = [
+        (
+            "rels",
+            "application/vnd.openxmlformats-package.relationships+xml",
+        ),
+        ("nuspec", OCTET_STREAM),
+        (
+            "psmdcp",
+            "application/vnd.openxmlformats-package.core-properties+xml",
+        ),
+    ];
+    let content_extensions = extensions
+        .iter()
+        .filter(|ext| !defaults.iter().any(|(d, _)| d == ext))
+        .map(|ext| (ext.as_str(), OCTET_STREAM));
+    for (extension, content_type) in defaults.into_iter().chain(content_extensions) {
+        let mut default = BytesStart::new("Default");
+        default.push_attribute(("Extension", extension));
+        default.push_attribute(("ContentType", content_type));
+        xml.empty(default)?;
+    }
+    for part in &overrides {
+        let mut entry = BytesStart::new("Override");
+        entry.push_attribute(("PartName", part.as_str()));
+        entry.push_attribute(("ContentType", OCTET_STREAM));
+        xml.empty(entry)?;
+    }
+
+    xml.end("Types")?;
+    xml.finish()
+}
+
+/// An indented UTF-8 XML document with a declaration.
+struct XmlDocument {
+    writer: Writer<Vec<u8>>,
+}
+
+impl XmlDocument {
+    fn new() -> PackageResult<Self> {
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+        writer
+            .write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))
+            .map_err(xml_error)?;
+        Ok(Self { writer })
+    }
+
+    fn start(&mut self, element: BytesStart) -> PackageResult<()> {
+        self.writer
+            .write_event(Event::Start(element))
+            .map_err(xml_error)
+    }
+
+    fn empty(&mut self, element: BytesStart) -> PackageResult<()> {
+        self.writer
+            .write_event(Event::Empty(element))
+            .map_err(xml_error)
+    }
+
+    fn end(&mut self, name: &str) -> PackageResult<()> {
+        self.writer
+            .write_event(Event::End(BytesEnd::new(name)))
+            .map_err(xml_error)
+    }
+
+    fn element(&mut self, name: &str, value: &str) -> PackageResult<()> {
+        self.start(BytesStart::new(name))?;
+        self.writer
+            .write_event(Event::Text(BytesText::new(value)))
+            .map_err(xml_error)?;
+        self.end(name)
+    }
+
+    fn finish(self) -> PackageResult<String> {
+        String::from_utf8(self.writer.into_inner()).map_err(PackageError::xml)
+    }
+}
+
+fn xml_error(e: quick_xml::Error) -> PackageError {
+    PackageError::xml(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::Verbosity;
+    use std::io::Read;
+    use tempfile::TempDir;
+    use zip::ZipArchive;
+
+    fn request(source: &std::path::Path, setup: &str) -> ChocoPackRequest {
+        ChocoPackRequest::new(source, setup, "myapp", "1.2.0", source.join("out"))
+    }
+
+    #[test]
+    fn test_install_script_for_msi() {
+        let script = generate_install_script(&request("/src".as_ref(), "setup.msi")).unwrap();
+        assert!(script.contains("fileType       = 'msi'"));
+        assert!(script.contains("silentArgs     = '/qn /norestart'"));
+        assert!(script.contains("Install-ChocolateyInstallPackage @packageArgs"));
+    }
+
+    #[test]
+    fn test_install_script_quotes_arguments() {
+        let request = request("/src".as_ref(), "bin/setup.exe").with_silent_args("/S /D='C:\\x'");
+        let script = generate_install_script(&request).unwrap();
+        assert!(script.contains("file           = Join-Path $toolsDir 'bin\\setup.exe'"));
+        assert!(script.contains("silentArgs     = '/S /D=''C:\\x'''"));
+
+        let script =
+            generate_install_script(&self::request("/src".as_ref(), "install.ps1")).unwrap();
+        assert!(script.contains("& (Join-Path $toolsDir 'install.ps1')\r\n"));
+
+        assert!(matches!(
+            generate_install_script(&self::request("/src".as_ref(), "setup.sh")),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nuspec_escapes_metadata() {
+        let request = request("/src".as_ref(), "setup.exe")
+            .with_title("Tom & Jerry")
+            .with_authors("ACME");
+        let nuspec = generate_nuspec(&request).unwrap();
+        assert!(nuspec.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>"));
+        assert!(nuspec.contains("<id>myapp</id>"));
+        assert!(nuspec.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(nuspec.contains("<description>Tom &amp; Jerry</description>"));
+        assert!(nuspec.contains("<authors>ACME</authors>"));
+    }
+
+    #[test]
+    fn test_pack_layout() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("lib/data.dll"), "dll").unwrap();
+        fs::write(source.join("LICENSE"), "license").unwrap();
+
+        let request = request(&source, "setup.exe").with_verbosity(Verbosity::Silent);
+        let result = pack(&request).unwrap();
+        assert_eq!(result.output_path, source.join("out/myapp.1.2.0.nupkg"));
+        assert_eq!(result.file_count, 3);
+
+        let mut archive = ZipArchive::new(File::open(&result.output_path).unwrap()).unwrap();
+        let names: BTreeSet<_> = archive.file_names().map(String::from).collect();
+        for name in [
+            "myapp.nuspec",
+            "tools/setup.exe",
+            "tools/setup.exe.ignore",
+            "tools/lib/data.dll",
+            "tools/LICENSE",
+            "tools/chocolateyInstall.ps1",
+            "_rels/.rels",
+            "[Content_Types].xml",
+        ] {
+            assert!(names.contains(name), "{} missing from {:?}", name, names);
+        }
+        assert!(names.iter().any(|n| n.ends_with(".psmdcp")));
+
+        let mut types = String::new();
+        archive
+            .by_name("[Content_Types].xml")
+            .unwrap()
+            .read_to_string(&mut types)
+            .unwrap();
+        assert!(types.contains(r#"<Default Extension="dll" ContentType="application/octet"/>"#));
+        assert!(types.contains(r#"<Override PartName="/tools/LICENSE""#));
+    }
+
+    #[test]
+    fn test_pack_rejects_invalid_version() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("setup.exe"), "setup").unwrap();
+
+        let mut request = request(temp.path(), "setup.exe");
+        request.version = "v1".to_string();
+        assert!(matches!(
+            pack(&request),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,820 @@
+//! Content providers: where the files to package come from.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::DeflateDecoder;
+use zip::ZipArchive;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::SourceFilter;
+use crate::models::package::SourcePackage;
+use crate::models::retry::RetryPolicy;
+use crate::models::suggest::rank_suggestions;
+
+use super::archive::collect_source_files;
+use super::retry::open_file;
+use super::sanitize::sanitize_entry_name;
+use super::winpath::path_issues;
+
+/// Label used as the content root for tar streams read from stdin.
+pub const STDIN_TAR_LABEL: &str = "<stdin>";
+
+/// A source of files to package.
+pub trait ContentProvider {
+    /// Where the content comes from, for messages.
+    fn root(&self) -> &Path;
+
+    /// List the content kept by `filter`, checking that `setup_file` is at
+    /// its root.
+    fn collect(&self, setup_file: &str, filter: &SourceFilter) -> PackageResult<SourcePackage>;
+
+    /// Open a file by its path relative to the content root.
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>>;
+}
+
+/// Content read from a folder on disk.
+#[derive(Debug, Clone)]
+pub struct FolderContent {
+    root: PathBuf,
+    retry: RetryPolicy,
+}
+
+impl FolderContent {
+    /// Use the files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Retry reads that fail transiently, e.g. from a network share.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl ContentProvider for FolderContent {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn collect(&self, setup_file: &str, filter: &SourceFilter) -> PackageResult<SourcePackage> {
+        collect_source_files(&self.root, setup_file, filter)
+    }
+
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>> {
+        let full_path = self.root.join(relative_path);
+        if self.retry.retries() > 0 {
+            return Ok(Box::new(BufReader::new(open_file(
+                &full_path,
+                &self.retry,
+            )?)));
+        }
+        let file =
+            File::open(&full_path).map_err(|e| PackageError::source_read(full_path.clone(), e))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Files held in memory, e.g. generated by the calling program.
+///
+/// Paths are relative to the content root; parent directories are added
+/// as files are.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryContent {
+    label: PathBuf,
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    directories: BTreeSet<PathBuf>,
+}
+
+impl MemoryContent {
+    /// Empty content, labelled `label` in messages.
+    pub fn new(label: impl Into<PathBuf>) -> Self {
+        Self {
+            label: label.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Content holding `files`, labelled `label` in messages.
+    pub fn from_files<P: Into<PathBuf>>(
+        label: impl Into<PathBuf>,
+        files: impl IntoIterator<Item = (P, Vec<u8>)>,
+    ) -> PackageResult<Self> {
+        let mut content = Self::new(label);
+        for (path, data) in files {
+            content.add_file(path, data)?;
+        }
+        Ok(content)
+    }
+
+    /// Add a file, replacing any earlier one at the same path.
+    ///
+    /// Absolute paths and paths escaping the root are rejected.
+    pub fn add_file(&mut self, path: impl Into<PathBuf>, data: Vec<u8>) -> PackageResult<()> {
+        let path = path.into();
+        let relative_path = self.relative(&path)?;
+        self.add_parents(&relative_path);
+        self.files.insert(relative_path, data);
+        Ok(())
+    }
+
+    /// Add a file (builder form of [`MemoryContent::add_file`]).
+    pub fn with_file(mut self, path: impl Into<PathBuf>, data: Vec<u8>) -> PackageResult<Self> {
+        self.add_file(path, data)?;
+        Ok(self)
+    }
+
+    /// Add an empty directory.
+    pub fn add_directory(&mut self, path: impl Into<PathBuf>) -> PackageResult<()> {
+        let path = path.into();
+        let relative_path = self.relative(&path)?;
+        self.add_parents(&relative_path);
+        self.directories.insert(relative_path);
+        Ok(())
+    }
+
+    /// Number of files held.
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Normalize `path`, which must name something below the root.
+    fn relative(&self, path: &Path) -> PackageResult<PathBuf> {
+        match normalize_entry_path(path) {
+            Some(relative_path) if !relative_path.as_os_str().is_empty() => Ok(relative_path),
+            _ => Err(PackageError::SourceReadError {
+                path: self.label.join(path),
+                reason: "Path must be relative and stay below the content root".to_string(),
+                source: None,
+            }),
+        }
+    }
+
+    fn add_parents(&mut self, relative_path: &Path) {
+        add_parents(&mut self.directories, relative_path);
+    }
+}
+
+impl ContentProvider for MemoryContent {
+    fn root(&self) -> &Path {
+        &self.label
+    }
+
+    fn collect(&self, setup_file: &str, filter: &SourceFilter) -> PackageResult<SourcePackage> {
+        collect_entries(
+            &self.label,
+            self.files
+                .iter()
+                .map(|(path, data)| (path, data.len() as u64)),
+            &self.directories,
+            setup_file,
+            filter,
+        )
+    }
+
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>> {
+        let data = self
+            .files
+            .get(relative_path)
+            .ok_or_else(|| PackageError::SourceReadError {
+                path: self.label.join(relative_path),
+                reason: "Not in the content".to_string(),
+                source: None,
+            })?;
+        Ok(Box::new(Cursor::new(data.as_slice())))
+    }
+}
+
+/// Content read from a tar stream and held in memory.
+#[derive(Debug, Clone, Default)]
+pub struct TarContent {
+    content: MemoryContent,
+}
+
+impl TarContent {
+    /// Read a tar stream, labelled `label` in messages.
+    ///
+    /// When `setup_file` is not at the root of the stream but every entry
+    /// sits under one top-level directory that contains it (as produced by
+    /// `tar -c ./payload`), that directory becomes the content root.
+    ///
+    /// Regular files and directories are supported; links, devices and
+    /// paths escaping the root are rejected.
+    pub fn read_from<R: Read>(
+        reader: R,
+        label: impl Into<PathBuf>,
+        setup_file: &str,
+    ) -> PackageResult<Self> {
+        let label = label.into();
+        let invalid = |reason: String| PackageError::SourceReadError {
+            path: label.clone(),
+            reason,
+            source: None,
+        };
+
+        let mut content = MemoryContent::new(label.clone());
+
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive
+            .entries()
+            .map_err(|e| invalid(format!("Invalid tar stream: {}", e)))?;
+
+        for entry in entries {
+            let mut entry = entry.map_err(|e| invalid(format!("Invalid tar stream: {}", e)))?;
+            let raw_path = entry
+                .path()
+                .map_err(|e| invalid(format!("Invalid tar entry path: {}", e)))?
+                .into_owned();
+            let relative_path = normalize_entry_path(&raw_path).ok_or_else(|| {
+                invalid(format!(
+                    "Tar entry '{}' escapes the content root",
+                    raw_path.display()
+                ))
+            })?;
+
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                if relative_path.as_os_str().is_empty() {
+                    continue;
+                }
+                content.add_parents(&relative_path);
+                content.directories.insert(relative_path);
+            } else if entry_type.is_file() || entry_type.is_contiguous() {
+                if relative_path.as_os_str().is_empty() {
+                    return Err(invalid("Tar file entry has an empty path".to_string()));
+                }
+                let mut data = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut data).map_err(|e| {
+                    invalid(format!("Failed to read '{}': {}", raw_path.display(), e))
+                })?;
+                content.add_parents(&relative_path);
+                content.files.insert(relative_path, data);
+            } else {
+                return Err(invalid(format!(
+                    "Unsupported tar entry '{}' (only regular files and directories)",
+                    raw_path.display()
+                )));
+            }
+        }
+
+        let mut content = TarContent { content };
+        content.strip_single_root(setup_file);
+        Ok(content)
+    }
+
+    fn strip_single_root(&mut self, setup_file: &str) {
+        let content = &mut self.content;
+        strip_single_root(&mut content.files, &mut content.directories, setup_file);
+    }
+}
+
+impl ContentProvider for TarContent {
+    fn root(&self) -> &Path {
+        self.content.root()
+    }
+
+    fn collect(&self, setup_file: &str, filter: &SourceFilter) -> PackageResult<SourcePackage> {
+        self.content.collect(setup_file, filter)
+    }
+
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>> {
+        self.content.open(relative_path)
+    }
+}
+
+/// Content read from a ZIP archive on disk.
+///
+/// Only the central directory is read up front; files are streamed from
+/// the archive as they are opened, so nothing is extracted. Stored and
+/// deflated entries are supported, and each one's CRC-32 is checked as it
+/// is read to the end.
+#[derive(Debug, Clone)]
+pub struct ZipContent {
+    path: PathBuf,
+    files: BTreeMap<PathBuf, ZipEntry>,
+    directories: BTreeSet<PathBuf>,
+}
+
+/// Where a file's data lies in the archive.
+#[derive(Debug, Clone, Copy)]
+struct ZipEntry {
+    data_start: u64,
+    compressed_size: u64,
+    size: u64,
+    deflated: bool,
+    crc32: u32,
+}
+
+impl ZipContent {
+    /// Read the entry list of the ZIP archive at `path`.
+    ///
+    /// The single top-level directory is stripped as for
+    /// [`TarContent::read_from`]. Links, encrypted entries and other
+    /// compression methods are rejected.
+    pub fn open(path: impl Into<PathBuf>, setup_file: &str) -> PackageResult<Self> {
+        let path = path.into();
+        let invalid = |reason: String| PackageError::SourceReadError {
+            path: path.clone(),
+            reason,
+            source: None,
+        };
+
+        let file = File::open(&path).map_err(|e| PackageError::source_read(path.clone(), e))?;
+        let mut archive = ZipArchive::new(BufReader::new(file))
+            .map_err(|e| invalid(format!("Invalid ZIP archive: {}", e)))?;
+
+        let mut files = BTreeMap::new();
+        let mut directories = BTreeSet::new();
+        for i in 0..archive.len() {
+            // Fails for encrypted entries and unsupported compression
+            let entry = archive
+                .by_index(i)
+                .map_err(|e| invalid(format!("Unsupported ZIP entry #{}: {}", i, e)))?;
+            let relative_path = sanitize_entry_name(entry.name())?;
+
+            if entry
+                .unix_mode()
+                .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+            {
+                return Err(invalid(format!(
+                    "Unsupported ZIP entry '{}' (only regular files and directories)",
+                    entry.name()
+                )));
+            }
+            if relative_path.as_os_str().is_empty() {
+                continue;
+            }
+            add_parents(&mut directories, &relative_path);
+            if entry.is_dir() {
+                directories.insert(relative_path);
+            } else {
+                let location = ZipEntry {
+                    data_start: entry.data_start(),
+                    compressed_size: entry.compressed_size(),
+                    size: entry.size(),
+                    deflated: entry.compression() == zip::CompressionMethod::Deflated,
+                    crc32: entry.crc32(),
+                };
+                files.insert(relative_path, location);
+            }
+        }
+
+        strip_single_root(&mut files, &mut directories, setup_file);
+        Ok(Self {
+            path,
+            files,
+            directories,
+        })
+    }
+}
+
+impl ContentProvider for ZipContent {
+    fn root(&self) -> &Path {
+        &self.path
+    }
+
+    fn collect(&self, setup_file: &str, filter: &SourceFilter) -> PackageResult<SourcePackage> {
+        collect_entries(
+            &self.path,
+            self.files.iter().map(|(path, entry)| (path, entry.size)),
+            &self.directories,
+            setup_file,
+            filter,
+        )
+    }
+
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>> {
+        let entry = self
+            .files
+            .get(relative_path)
+            .ok_or_else(|| PackageError::SourceReadError {
+                path: self.path.join(relative_path),
+                reason: "Not in the content".to_string(),
+                source: None,
+            })?;
+
+        let read_err = |e| PackageError::source_read(self.path.clone(), e);
+        let mut file = File::open(&self.path).map_err(read_err)?;
+        file.seek(SeekFrom::Start(entry.data_start))
+            .map_err(read_err)?;
+        let raw = BufReader::new(file).take(entry.compressed_size);
+        let data: Box<dyn Read> = if entry.deflated {
+            Box::new(DeflateDecoder::new(raw))
+        } else {
+            Box::new(raw)
+        };
+        Ok(Box::new(CheckedReader {
+            inner: data,
+            hasher: crc32fast::Hasher::new(),
+            remaining: entry.size,
+            entry: *entry,
+        }))
+    }
+}
+
+/// File type bits of a Unix mode, and the value marking a symbolic link.
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Checks the size and CRC-32 of a ZIP entry once it is read to the end.
+struct CheckedReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    remaining: u64,
+    entry: ZipEntry,
+}
+
+impl<R: Read> Read for CheckedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            let corrupt = |reason: &str| Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+            if self.remaining != 0 {
+                return corrupt("ZIP entry is shorter than its recorded size");
+            }
+            if self.hasher.clone().finalize() != self.entry.crc32 {
+                return corrupt("ZIP entry does not match its CRC-32");
+            }
+            return Ok(0);
+        }
+        if n as u64 > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ZIP entry is longer than its recorded size",
+            ));
+        }
+        self.remaining -= n as u64;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// List `files` (with their sizes) and `directories` kept by `filter`,
+/// checking that `setup_file` is among them.
+fn collect_entries<'a>(
+    label: &Path,
+    files: impl Iterator<Item = (&'a PathBuf, u64)> + Clone,
+    directories: &BTreeSet<PathBuf>,
+    setup_file: &str,
+    filter: &SourceFilter,
+) -> PackageResult<SourcePackage> {
+    let mut package = SourcePackage::new(label.to_path_buf(), PathBuf::from(setup_file));
+
+    for dir in directories.iter().filter(|d| filter.keeps(d)) {
+        package.add_directory(dir.clone());
+    }
+    for (path, size) in files.clone() {
+        // The setup file is kept unless it is excluded
+        let is_setup = path == Path::new(setup_file);
+        let kept = if is_setup {
+            !filter.excludes(path)
+        } else {
+            filter.keeps(path)
+        };
+        if !kept {
+            continue;
+        }
+        package.add_file(path.clone(), size, is_setup);
+    }
+
+    if !package.files.iter().any(|f| f.is_setup_file) {
+        return Err(PackageError::SetupFileNotFound {
+            file: setup_file.to_string(),
+            folder: label.to_path_buf(),
+            suggestions: rank_suggestions(
+                setup_file,
+                files.map(|(p, _)| p.to_string_lossy().replace('\\', "/")),
+            ),
+        });
+    }
+    package.path_issues = path_issues(&package);
+
+    Ok(package)
+}
+
+/// Record the folders above `relative_path`.
+fn add_parents(directories: &mut BTreeSet<PathBuf>, relative_path: &Path) {
+    for parent in relative_path.ancestors().skip(1) {
+        if parent.as_os_str().is_empty() {
+            break;
+        }
+        directories.insert(parent.to_path_buf());
+    }
+}
+
+/// When `setup_file` is not at the root but every entry sits under one
+/// top-level directory that contains it, make that directory the root.
+fn strip_single_root<T>(
+    files: &mut BTreeMap<PathBuf, T>,
+    directories: &mut BTreeSet<PathBuf>,
+    setup_file: &str,
+) {
+    if files.contains_key(Path::new(setup_file)) {
+        return;
+    }
+
+    let mut tops = files
+        .keys()
+        .chain(directories.iter())
+        .filter_map(|p| p.components().next());
+    let Some(top) = tops.next() else {
+        return;
+    };
+    if tops.any(|c| c != top) {
+        return;
+    }
+
+    let top = PathBuf::from(top.as_os_str());
+    if !files.contains_key(&top.join(setup_file)) {
+        return;
+    }
+
+    let strip = |p: &PathBuf| p.strip_prefix(&top).ok().map(Path::to_path_buf);
+    *files = std::mem::take(files)
+        .into_iter()
+        .filter_map(|(p, data)| strip(&p).map(|p| (p, data)))
+        .collect();
+    *directories = std::mem::take(directories)
+        .iter()
+        .filter_map(strip)
+        .filter(|p| !p.as_os_str().is_empty())
+        .collect();
+}
+
+/// Drop `.` components; `None` if the path is absolute or climbs out.
+pub(crate) fn normalize_entry_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_of(entries: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            match data {
+                Some(data) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(data.len() as u64);
+                    header.set_mode(0o644);
+                    builder.append_data(&mut header, path, *data).unwrap();
+                }
+                None => {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_size(0);
+                    header.set_mode(0o755);
+                    builder.append_data(&mut header, path, &[][..]).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_tar_content_collect_and_open() {
+        let tar = tar_of(&[
+            ("./", None),
+            ("./setup.exe", Some(b"setup")),
+            ("./lib/data.dll", Some(b"dll")),
+            ("./logs/", None),
+        ]);
+        let content = TarContent::read_from(tar.as_slice(), "<test>", "setup.exe").unwrap();
+        let package = content
+            .collect("setup.exe", &SourceFilter::default())
+            .unwrap();
+
+        assert_eq!(package.file_count(), 2);
+        assert_eq!(package.total_size, 8);
+        assert_eq!(
+            package.directories,
+            vec![PathBuf::from("lib"), PathBuf::from("logs")]
+        );
+
+        let mut data = String::new();
+        content
+            .open(Path::new("lib/data.dll"))
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "dll");
+    }
+
+    #[test]
+    fn test_tar_content_strips_single_root() {
+        let tar = tar_of(&[
+            ("./payload/", None),
+            ("./payload/setup.exe", Some(b"setup")),
+            ("./payload/bin/app.exe", Some(b"app")),
+        ]);
+        let content = TarContent::read_from(tar.as_slice(), "<test>", "setup.exe").unwrap();
+        let package = content
+            .collect("setup.exe", &SourceFilter::default())
+            .unwrap();
+
+        assert!(package.files.iter().any(|f| f.is_setup_file));
+        assert_eq!(package.directories, vec![PathBuf::from("bin")]);
+    }
+
+    #[test]
+    fn test_tar_content_filtered() {
+        let tar = tar_of(&[
+            ("setup.exe", Some(b"setup")),
+            ("node_modules/", None),
+            ("node_modules/x/index.js", Some(b"js")),
+            ("app.pdb", Some(b"pdb")),
+        ]);
+        let content = TarContent::read_from(tar.as_slice(), "<test>", "setup.exe").unwrap();
+        let filter = SourceFilter {
+            include: Vec::new(),
+            exclude: vec![
+                crate::models::Glob::new("node_modules").unwrap(),
+                crate::models::Glob::new("*.pdb").unwrap(),
+            ],
+        };
+        let package = content.collect("setup.exe", &filter).unwrap();
+
+        assert_eq!(package.file_count(), 1);
+        assert!(package.directories.is_empty());
+    }
+
+    #[test]
+    fn test_tar_content_missing_setup() {
+        let tar = tar_of(&[("setup.msi", Some(b"msi"))]);
+        let content = TarContent::read_from(tar.as_slice(), "<test>", "setup.exe").unwrap();
+
+        assert!(matches!(
+            content.collect("setup.exe", &SourceFilter::default()),
+            Err(PackageError::SetupFileNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tar_content_rejects_links() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "link", "/etc/passwd")
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        assert!(matches!(
+            TarContent::read_from(tar.as_slice(), "<test>", "setup.exe"),
+            Err(PackageError::SourceReadError { .. })
+        ));
+    }
+
+    fn zip_file_of(dir: &Path, entries: &[(&str, &[u8])]) -> PathBuf {
+        let path = dir.join("content.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (i, (name, data)) in entries.iter().enumerate() {
+            // Mix both methods the content reads
+            let method = if i % 2 == 0 {
+                zip::CompressionMethod::Deflated
+            } else {
+                zip::CompressionMethod::Stored
+            };
+            let options = zip::write::FileOptions::default().compression_method(method);
+            zip.start_file(*name, options).unwrap();
+            std::io::Write::write_all(&mut zip, data).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_zip_content_streams_entries() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = zip_file_of(
+            temp.path(),
+            &[
+                ("app/setup.exe", b"setup"),
+                ("app/lib/data.dll", b"dll"),
+                ("app/lib/big.bin", &[7u8; 100_000]),
+            ],
+        );
+        let content = ZipContent::open(&path, "setup.exe").unwrap();
+        let package = content
+            .collect("setup.exe", &SourceFilter::default())
+            .unwrap();
+
+        assert_eq!(package.file_count(), 3);
+        assert_eq!(package.total_size, 100_008);
+        assert_eq!(package.directories, vec![PathBuf::from("lib")]);
+
+        let mut data = Vec::new();
+        for (name, expected) in [
+            ("lib/data.dll", &b"dll"[..]),
+            ("lib/big.bin", &[7; 100_000]),
+        ] {
+            data.clear();
+            content
+                .open(Path::new(name))
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            assert_eq!(data, expected);
+        }
+    }
+
+    #[test]
+    fn test_zip_content_detects_corruption() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = zip_file_of(temp.path(), &[("setup.exe", b"setup"), ("a.txt", b"aaaa")]);
+        let content = ZipContent::open(&path, "setup.exe").unwrap();
+
+        // Flip a byte of the stored entry's data
+        let mut bytes = std::fs::read(&path).unwrap();
+        let at = bytes.windows(4).position(|w| w == b"aaaa").unwrap();
+        bytes[at] = b'b';
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut data = Vec::new();
+        let err = content
+            .open(Path::new("a.txt"))
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_zip_content_rejects_unsafe_entries() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = zip_file_of(temp.path(), &[("../setup.exe", b"setup")]);
+        assert!(matches!(
+            ZipContent::open(&path, "setup.exe"),
+            Err(PackageError::UnsafePath { .. })
+        ));
+
+        let path = temp.path().join("link.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.add_symlink("link", "/etc/passwd", zip::write::FileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+        assert!(matches!(
+            ZipContent::open(&path, "setup.exe"),
+            Err(PackageError::SourceReadError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_memory_content() {
+        let content = MemoryContent::from_files(
+            "<memory>",
+            vec![
+                ("setup.exe", b"setup".to_vec()),
+                ("./config/app.json", b"{}".to_vec()),
+            ],
+        )
+        .unwrap();
+        let package = content
+            .collect("setup.exe", &SourceFilter::default())
+            .unwrap();
+
+        assert_eq!(package.file_count(), 2);
+        assert_eq!(package.directories, vec![PathBuf::from("config")]);
+        let mut data = String::new();
+        content
+            .open(Path::new("config/app.json"))
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "{}");
+
+        let mut content = MemoryContent::new("<memory>");
+        for path in ["../setup.exe", "/etc/passwd", "."] {
+            assert!(matches!(
+                content.add_file(path, Vec::new()),
+                Err(PackageError::SourceReadError { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_normalize_entry_path() {
+        assert_eq!(
+            normalize_entry_path(Path::new("./a/./b")),
+            Some(PathBuf::from("a/b"))
+        );
+        assert_eq!(normalize_entry_path(Path::new("a/../../b")), None);
+        assert_eq!(normalize_entry_path(Path::new("/etc/passwd")), None);
+    }
+}
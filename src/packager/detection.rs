@@ -0,0 +1,170 @@
+//! Generating Intune detection rules for a packaged setup file.
+//!
+//! The setup file is read back out of the `.intunewin`, so the rules always
+//! match what was packaged. An MSI yields a product code rule; file and
+//! registry rules for other installers are supplied by the caller.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use zip::read::ZipArchive;
+
+use crate::models::detection::{
+    DetectionRule, DetectionRules, DetectionRulesRequest, MsiInformation,
+};
+use crate::models::error::{PackageError, PackageResult};
+
+use super::encrypt::decrypt_content;
+use super::msi::read_msi_properties;
+use super::{extract_detection_metadata, extract_encrypted_content};
+
+/// Generate the detection rules for the package in `request`.
+pub fn detection_rules(request: &DetectionRulesRequest) -> PackageResult<DetectionRules> {
+    let path = &request.input_file;
+    let (setup_file, setup) = read_setup_file(path)?;
+
+    let msi = if setup_file.to_ascii_lowercase().ends_with(".msi") {
+        let properties =
+            read_msi_properties(&setup).map_err(|reason| PackageError::InvalidIntunewinFile {
+                path: path.clone(),
+                reason: format!(
+                    "Setup file '{}' is not a readable MSI: {}",
+                    setup_file, reason
+                ),
+            })?;
+        MsiInformation::from_properties(&properties)
+    } else {
+        None
+    };
+
+    let mut rules = Vec::new();
+    if let Some(msi) = &msi {
+        rules.push(DetectionRule::MsiProductCode {
+            product_code: msi.product_code.clone(),
+            product_version: Some(msi.product_version.clone()),
+        });
+    }
+    rules.extend(request.rules.iter().cloned());
+    if rules.is_empty() {
+        return Err(PackageError::InvalidArgument {
+            reason: format!(
+                "No detection rule can be derived from '{}': add a file or registry rule",
+                setup_file
+            ),
+        });
+    }
+
+    Ok(DetectionRules {
+        setup_file,
+        msi,
+        rules,
+    })
+}
+
+/// Decrypt the package at `path` and return its setup file's name and
+/// contents.
+fn read_setup_file(path: &Path) -> PackageResult<(String, Vec<u8>)> {
+    let file = File::open(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    let invalid = |reason: String| PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .map_err(|e| invalid(format!("Invalid ZIP archive: {}", e)))?;
+    let metadata = extract_detection_metadata(&mut archive, path)?;
+    let encrypted = extract_encrypted_content(&mut archive, path)?;
+    let decrypted = decrypt_content(&encrypted, &metadata.encryption_info)?;
+
+    let mut inner = ZipArchive::new(Cursor::new(decrypted))
+        .map_err(|e| invalid(format!("Decrypted content is not a valid ZIP: {}", e)))?;
+    let mut entry = inner
+        .by_name(&metadata.setup_file.replace('\\', "/"))
+        .map_err(|_| {
+            invalid(format!(
+                "Setup file '{}' is missing from the content",
+                metadata.setup_file
+            ))
+        })?;
+    let mut data = Vec::new();
+    entry
+        .read_to_end(&mut data)
+        .map_err(|e| invalid(format!("Setup file '{}': {}", metadata.setup_file, e)))?;
+    Ok((metadata.setup_file, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::msi::fixture::build_msi;
+    use crate::packager::package;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn build_package(temp: &TempDir, setup_file: &str, setup: &[u8]) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join(setup_file), setup).unwrap();
+
+        let request = PackageRequest::new(source, setup_file.to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_detection_rules_from_msi() {
+        let temp = TempDir::new().unwrap();
+        let msi = build_msi(&[
+            ("ProductCode", "{11111111-2222-3333-4444-555555555555}"),
+            ("ProductVersion", "4.5.6"),
+            ("Manufacturer", "ACME"),
+        ]);
+        let path = build_package(&temp, "setup.msi", &msi);
+
+        let request = DetectionRulesRequest::new(&path)
+            .with_rule(DetectionRule::file("C:\\Program Files\\Acme\\acme.exe").unwrap());
+        let rules = detection_rules(&request).unwrap();
+        assert_eq!(rules.setup_file, "setup.msi");
+        let msi = rules.msi.as_ref().unwrap();
+        assert_eq!(msi.product_version, "4.5.6");
+        assert_eq!(msi.publisher.as_deref(), Some("ACME"));
+        assert_eq!(
+            rules.rules[0],
+            DetectionRule::MsiProductCode {
+                product_code: "{11111111-2222-3333-4444-555555555555}".to_string(),
+                product_version: Some("4.5.6".to_string()),
+            }
+        );
+        assert_eq!(rules.rules.len(), 2);
+    }
+
+    #[test]
+    fn test_detection_rules_need_a_rule_for_exe() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp, "setup.exe", b"MZ");
+
+        assert!(matches!(
+            detection_rules(&DetectionRulesRequest::new(&path)),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+        let request = DetectionRulesRequest::new(&path)
+            .with_rule(DetectionRule::registry("HKLM\\SOFTWARE\\Acme").unwrap());
+        let rules = detection_rules(&request).unwrap();
+        assert!(rules.msi.is_none());
+        assert_eq!(rules.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_detection_rules_unreadable_msi() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp, "setup.msi", b"not an msi");
+
+        assert!(matches!(
+            detection_rules(&DetectionRulesRequest::new(&path)),
+            Err(PackageError::InvalidIntunewinFile { .. })
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+//! Comparing two `.intunewin` packages without extracting them.
+//!
+//! Both packages are decrypted in memory. Files are compared by SHA-256 of
+//! their content, so repacking the same files compares equal even though
+//! the encrypted content differs. Detection.xml is compared field by field,
+//! leaving out the encryption parameters, which are new for every package.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use std::path::Path;
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use zip::read::ZipArchive;
+
+use crate::models::diff::{ChangeKind, FieldChange, FileChange, FileSummary, PackageDiff};
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::size::ByteSize;
+
+use super::encrypt::{compute_sha256_stream, decrypt_content};
+use super::manifest::to_hex;
+use super::metadata::parse_detection_xml;
+use super::{DETECTION_ENTRY, extract_encrypted_content};
+
+/// Detection.xml element whose children differ in every package.
+const ENCRYPTION_INFO: &str = "EncryptionInfo";
+
+/// What a package holds, as far as the comparison is concerned.
+struct PackageSummary {
+    /// Detection.xml fields in document order
+    fields: Vec<(String, String)>,
+    /// Files by path
+    files: BTreeMap<String, FileSummary>,
+}
+
+/// Compare the package at `new` against the one at `old`.
+pub fn diff(old: &Path, new: &Path) -> PackageResult<PackageDiff> {
+    let before = summarize(old)?;
+    let after = summarize(new)?;
+
+    let mut files = Vec::new();
+    let mut unchanged = 0;
+    for (path, summary) in &before.files {
+        match after.files.get(path) {
+            None => files.push(FileChange {
+                path: path.clone(),
+                kind: ChangeKind::Removed,
+                old: Some(summary.clone()),
+                new: None,
+            }),
+            Some(other) if other != summary => files.push(FileChange {
+                path: path.clone(),
+                kind: ChangeKind::Changed,
+                old: Some(summary.clone()),
+                new: Some(other.clone()),
+            }),
+            Some(_) => unchanged += 1,
+        }
+    }
+    for (path, summary) in &after.files {
+        if !before.files.contains_key(path) {
+            files.push(FileChange {
+                path: path.clone(),
+                kind: ChangeKind::Added,
+                old: None,
+                new: Some(summary.clone()),
+            });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(PackageDiff {
+        old: old.to_path_buf(),
+        new: new.to_path_buf(),
+        detection: diff_fields(&before.fields, &after.fields),
+        files,
+        unchanged,
+    })
+}
+
+/// Fields whose values differ, in the order they appear in either package.
+fn diff_fields(old: &[(String, String)], new: &[(String, String)]) -> Vec<FieldChange> {
+    let lookup = |fields: &[(String, String)], name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    let mut changes = Vec::new();
+    for (field, value) in old {
+        let other = lookup(new, field);
+        if other.as_ref() != Some(value) {
+            changes.push(FieldChange {
+                field: field.clone(),
+                old: Some(value.clone()),
+                new: other,
+            });
+        }
+    }
+    for (field, value) in new {
+        if lookup(old, field).is_none() {
+            changes.push(FieldChange {
+                field: field.clone(),
+                old: None,
+                new: Some(value.clone()),
+            });
+        }
+    }
+    changes
+}
+
+fn invalid(path: &Path, reason: String) -> PackageError {
+    PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason,
+    }
+}
+
+/// Read the Detection.xml fields and content file hashes of a package.
+fn summarize(path: &Path) -> PackageResult<PackageSummary> {
+    let file = File::open(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .map_err(|e| invalid(path, format!("Invalid ZIP archive: {}", e)))?;
+
+    let mut xml = String::new();
+    archive
+        .by_name(DETECTION_ENTRY)
+        .map_err(|e| invalid(path, format!("Missing Detection.xml: {}", e)))?
+        .read_to_string(&mut xml)
+        .map_err(|e| invalid(path, format!("Failed to read Detection.xml: {}", e)))?;
+    let metadata = parse_detection_xml(&xml)?;
+    let fields = detection_fields(&xml)?;
+
+    let encrypted = extract_encrypted_content(&mut archive, path)?;
+    let decrypted = decrypt_content(&encrypted, &metadata.encryption_info)?;
+    drop(encrypted);
+
+    let mut inner =
+        ZipArchive::new(Cursor::new(decrypted)).map_err(|e| PackageError::DecryptionError {
+            reason: format!("Decrypted content is not a valid ZIP: {}", e),
+        })?;
+    let mut files = BTreeMap::new();
+    for i in 0..inner.len() {
+        let mut entry = inner
+            .by_index(i)
+            .map_err(|e| PackageError::zip(ZipStage::ReadContent, Some(&format!("#{}", i)), e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let sha256 = compute_sha256_stream(&mut entry)
+            .map_err(|e| invalid(path, format!("Failed to read '{}': {}", name, e)))?;
+        files.insert(
+            name,
+            FileSummary {
+                size: ByteSize(entry.size()),
+                sha256: to_hex(&sha256),
+            },
+        );
+    }
+
+    Ok(PackageSummary { fields, files })
+}
+
+/// Flatten Detection.xml into `(path, value)` pairs below the root element.
+fn detection_fields(xml: &str) -> PackageResult<Vec<(String, String)>> {
+    fn xml_error(e: impl std::error::Error + Send + Sync + 'static) -> PackageError {
+        PackageError::xml_with(format!("XML parse error: {}", e), e)
+    }
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut add = |name: String, value: String| {
+        // Repeated elements are told apart by their position
+        let mut field = name.clone();
+        let mut n = 1;
+        while fields.iter().any(|(f, _)| *f == field) {
+            n += 1;
+            field = format!("{}[{}]", name, n);
+        }
+        fields.push((field, value));
+    };
+
+    let mut stack: Vec<String> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        let (element, empty) = match reader.read_event_into(&mut buf).map_err(xml_error)? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::Text(text) => {
+                if stack.len() > 1 && !stack.iter().any(|e| e == ENCRYPTION_INFO) {
+                    let value = text.unescape().map_err(xml_error)?;
+                    add(stack[1..].join("/"), value.into_owned());
+                }
+                buf.clear();
+                continue;
+            }
+            Event::End(_) => {
+                stack.pop();
+                buf.clear();
+                continue;
+            }
+            Event::Eof => break,
+            _ => {
+                buf.clear();
+                continue;
+            }
+        };
+
+        // The root element is left out of field names
+        let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+        let path = match stack.get(1..) {
+            Some(parents) if !parents.is_empty() => format!("{}/{}", parents.join("/"), name),
+            Some(_) => name.clone(),
+            None => String::new(),
+        };
+        if !stack.iter().any(|e| e == ENCRYPTION_INFO) && name != ENCRYPTION_INFO {
+            for attribute in element.attributes() {
+                let attribute = attribute.map_err(xml_error)?;
+                let value = attribute.unescape_value().map_err(xml_error)?;
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+                add(format!("{}@{}", path, key), value.into_owned());
+            }
+        }
+        if !empty {
+            stack.push(name);
+        }
+        buf.clear();
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn build(temp: &TempDir, name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let source = temp.path().join(name);
+        for (path, content) in files {
+            let path = source.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let request = PackageRequest::new(&source, "setup.exe".to_string(), temp.path().join(name))
+            .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let temp = TempDir::new().unwrap();
+        let old = build(
+            &temp,
+            "v1",
+            &[
+                ("setup.exe", "setup"),
+                ("lib/a.dll", "a"),
+                ("old.txt", "gone"),
+            ],
+        );
+        let new = build(
+            &temp,
+            "v2",
+            &[
+                ("setup.exe", "setup"),
+                ("lib/a.dll", "a, patched"),
+                ("lib/b.dll", "b"),
+            ],
+        );
+
+        let diff = diff(&old, &new).unwrap();
+        let changes: Vec<_> = diff
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("lib/a.dll", ChangeKind::Changed),
+                ("lib/b.dll", ChangeKind::Added),
+                ("old.txt", ChangeKind::Removed),
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+        let changed = &diff.files[0];
+        assert_eq!(changed.old.as_ref().unwrap().size, ByteSize(1));
+        assert_eq!(changed.new.as_ref().unwrap().size, ByteSize(10));
+        assert_eq!(
+            diff.files[1].new.as_ref().unwrap().sha256,
+            "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d"
+        );
+
+        // The content size changed; encryption parameters are not reported
+        let fields: Vec<_> = diff.detection.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, vec!["UnencryptedContentSize"]);
+    }
+
+    #[test]
+    fn test_diff_identical_content() {
+        let temp = TempDir::new().unwrap();
+        let files = [("setup.exe", "setup"), ("data.txt", "data")];
+        let old = build(&temp, "a", &files);
+        let new = build(&temp, "b", &files);
+
+        let diff = diff(&old, &new).unwrap();
+        assert!(diff.is_empty(), "{:?}", diff);
+        assert_eq!(diff.unchanged, 2);
+    }
+
+    #[test]
+    fn test_detection_fields() {
+        let xml = r#"<ApplicationInfo ToolVersion="1.8.3">
+            <Name>setup.msi</Name>
+            <EncryptionInfo><EncryptionKey>abc</EncryptionKey></EncryptionInfo>
+            <MsiInfo><MsiProductCode>{X}</MsiProductCode><MsiProductVersion>1.0</MsiProductVersion></MsiInfo>
+        </ApplicationInfo>"#;
+        let fields = detection_fields(xml).unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                ("@ToolVersion".to_string(), "1.8.3".to_string()),
+                ("Name".to_string(), "setup.msi".to_string()),
+                ("MsiInfo/MsiProductCode".to_string(), "{X}".to_string()),
+                ("MsiInfo/MsiProductVersion".to_string(), "1.0".to_string()),
+            ]
+        );
+
+        let other = vec![("Name".to_string(), "setup2.msi".to_string())];
+        let changes = diff_fields(&fields, &other);
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[1].new.as_deref(), Some("setup2.msi"));
+        assert_eq!(changes[0].new, None);
+    }
+}
@@ -2,18 +2,27 @@
 //!
 //! Implements AES-256-CBC encryption with HMAC-SHA256 authentication.
 
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use aes::cipher::generic_array::GenericArray;
 use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand::RngCore;
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use zeroize::Zeroizing;
 
-use crate::models::detection::EncryptionInfo;
+use crate::models::detection::{DigestAlgorithm, EncryptionInfo};
+use crate::models::encryption::ProvidedKeys;
 use crate::models::error::{PackageError, PackageResult};
 
 type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
 type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 type HmacSha256 = Hmac<Sha256>;
 
+/// Plaintext encrypted per read when streaming (a multiple of the block size).
+const STREAM_CHUNK: usize = 1024 * 1024;
+
 /// Encrypt content using AES-256-CBC with HMAC-SHA256 authentication.
 ///
 /// Returns the encrypted content (HMAC || IV || ciphertext) and encryption info.
@@ -23,6 +32,135 @@ pub fn encrypt_content(plaintext: &[u8]) -> PackageResult<(Vec<u8>, EncryptionIn
     // Generate random keys and IV
     generate_keys(&mut info)?;
 
+    encrypt_with_keys(plaintext, info)
+}
+
+/// Encrypt content with keys and IV derived from `seed`.
+///
+/// The same plaintext and seed always give the same output. Anyone who
+/// knows the seed can derive the keys, which Detection.xml carries in the
+/// clear anyway.
+pub fn encrypt_content_seeded(
+    plaintext: &[u8],
+    seed: &[u8],
+) -> PackageResult<(Vec<u8>, EncryptionInfo)> {
+    let mut info = EncryptionInfo::new();
+    derive_keys(&mut info, seed)?;
+    encrypt_with_keys(plaintext, info)
+}
+
+/// Keys and IV for a new package: random, or derived from `seed` as in
+/// [`encrypt_content_seeded`].
+pub fn new_encryption_info(seed: Option<&[u8]>) -> PackageResult<EncryptionInfo> {
+    let mut info = EncryptionInfo::new();
+    match seed {
+        Some(seed) => derive_keys(&mut info, seed)?,
+        None => generate_keys(&mut info)?,
+    }
+    Ok(info)
+}
+
+/// Keys and IV for a new package as supplied by the caller; the IV is
+/// random unless given.
+pub fn provided_encryption_info(keys: &ProvidedKeys) -> EncryptionInfo {
+    let mut info = EncryptionInfo::new();
+    info.encryption_key = keys.key;
+    info.mac_key = keys.mac_key;
+    match keys.iv {
+        Some(iv) => info.iv = iv,
+        None => rand::thread_rng().fill_bytes(&mut info.iv),
+    }
+    info
+}
+
+/// Encrypt everything read from `plaintext` into `output` with the keys
+/// and IV in `info`, without holding the content in memory.
+///
+/// The output has the layout of [`encrypt_content`]. The HMAC is only known
+/// at the end, so its slot is written last by seeking back. Fills in
+/// `info.mac` and `info.file_digest`, hashed with
+/// `info.file_digest_algorithm`, and returns the bytes written.
+pub fn encrypt_stream<R, W>(
+    plaintext: &mut R,
+    output: &mut W,
+    info: &mut EncryptionInfo,
+) -> PackageResult<u64>
+where
+    R: Read + ?Sized,
+    W: Write + Seek + ?Sized,
+{
+    let io_err = |e: io::Error| PackageError::EncryptionError {
+        reason: format!("Streaming encryption failed: {}", e),
+    };
+    let mut mac =
+        HmacSha256::new_from_slice(&info.mac_key).map_err(|e| PackageError::EncryptionError {
+            reason: format!("HMAC initialization failed: {}", e),
+        })?;
+    let mut digest = ContentDigest::new(info.file_digest_algorithm);
+    let mut encryptor = Aes256CbcEnc::new((&info.encryption_key).into(), (&info.iv).into());
+
+    let start = output.stream_position().map_err(io_err)?;
+    output.write_all(&[0u8; 32]).map_err(io_err)?;
+    output.write_all(&info.iv).map_err(io_err)?;
+    mac.update(&info.iv);
+    let mut written = 48u64;
+
+    // Room for the padding block after a partial last chunk
+    let mut buffer = vec![0u8; STREAM_CHUNK + 16];
+    loop {
+        let filled = read_full(plaintext, &mut buffer[..STREAM_CHUNK]).map_err(io_err)?;
+        digest.update(&buffer[..filled]);
+
+        if filled < STREAM_CHUNK {
+            let ciphertext = encryptor
+                .encrypt_padded_mut::<Pkcs7>(&mut buffer, filled)
+                .map_err(|e| PackageError::EncryptionError {
+                    reason: format!("AES encryption failed: {}", e),
+                })?;
+            mac.update(ciphertext);
+            output.write_all(ciphertext).map_err(io_err)?;
+            written += ciphertext.len() as u64;
+            break;
+        }
+
+        for block in buffer[..STREAM_CHUNK].chunks_exact_mut(16) {
+            encryptor.encrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        mac.update(&buffer[..STREAM_CHUNK]);
+        output.write_all(&buffer[..STREAM_CHUNK]).map_err(io_err)?;
+        written += STREAM_CHUNK as u64;
+    }
+
+    info.mac.copy_from_slice(&mac.finalize().into_bytes());
+    info.file_digest = digest.finalize();
+
+    output.seek(SeekFrom::Start(start)).map_err(io_err)?;
+    output.write_all(&info.mac).map_err(io_err)?;
+    output
+        .seek(SeekFrom::Start(start + written))
+        .map_err(io_err)?;
+    Ok(written)
+}
+
+/// Fill `buffer` from `reader`, stopping early only at the end of input.
+fn read_full<R: Read + ?Sized>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypt `plaintext` with the keys and IV already in `info`.
+fn encrypt_with_keys(
+    plaintext: &[u8],
+    mut info: EncryptionInfo,
+) -> PackageResult<(Vec<u8>, EncryptionInfo)> {
     // Encrypt the content
     let ciphertext = aes_encrypt(plaintext, &info.encryption_key, &info.iv)?;
 
@@ -39,8 +177,8 @@ pub fn encrypt_content(plaintext: &[u8]) -> PackageResult<(Vec<u8>, EncryptionIn
     output.extend_from_slice(&info.iv);
     output.extend_from_slice(&ciphertext);
 
-    // Compute file digest (SHA256 of the unencrypted content, per Microsoft spec)
-    info.file_digest = compute_sha256(plaintext);
+    // Compute file digest (of the unencrypted content, per Microsoft spec)
+    info.file_digest = compute_digest(info.file_digest_algorithm, plaintext);
 
     Ok((output, info))
 }
@@ -83,6 +221,114 @@ pub fn decrypt_content(
     aes_decrypt(ciphertext, &encryption_info.encryption_key, &iv_array)
 }
 
+/// Check the HMAC of encrypted content (layout of [`encrypt_content`])
+/// read from `encrypted`, one chunk at a time.
+///
+/// Returns the number of bytes read.
+pub fn verify_stream<R: Read + ?Sized>(
+    encrypted: &mut R,
+    encryption_info: &EncryptionInfo,
+) -> PackageResult<u64> {
+    let io_err = |e: io::Error| PackageError::DecryptionError {
+        reason: format!("Failed to read encrypted data: {}", e),
+    };
+    let mut mac = HmacSha256::new_from_slice(&encryption_info.mac_key).map_err(|e| {
+        PackageError::DecryptionError {
+            reason: format!("HMAC initialization failed: {}", e),
+        }
+    })?;
+
+    let mut stored_hmac = [0u8; 32];
+    let mut buffer = vec![0u8; STREAM_CHUNK];
+    let mut total = read_full(encrypted, &mut stored_hmac).map_err(io_err)? as u64;
+    loop {
+        let filled = read_full(encrypted, &mut buffer).map_err(io_err)?;
+        mac.update(&buffer[..filled]);
+        total += filled as u64;
+        if filled < STREAM_CHUNK {
+            break;
+        }
+    }
+
+    // Same minimum as decrypt_content: HMAC, IV and one block
+    if total < 64 || (total - 48) % 16 != 0 {
+        return Err(PackageError::DecryptionError {
+            reason: "Invalid encrypted data length".to_string(),
+        });
+    }
+    if !constant_time_compare(&mac.finalize().into_bytes(), &stored_hmac) {
+        return Err(PackageError::HmacVerificationFailed);
+    }
+    Ok(total)
+}
+
+/// Decrypt content read from `encrypted` into `output`, one chunk at a time.
+///
+/// The HMAC is skipped, not checked: run [`verify_stream`] over the same
+/// content first. The last block is held back until the end of input so its
+/// padding can be removed. Returns the number of plaintext bytes written.
+pub fn decrypt_stream<R, W>(
+    encrypted: &mut R,
+    output: &mut W,
+    encryption_info: &EncryptionInfo,
+) -> PackageResult<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let read_err = |e: io::Error| PackageError::DecryptionError {
+        reason: format!("Failed to read encrypted data: {}", e),
+    };
+    let write_err = |e: io::Error| PackageError::DecryptionError {
+        reason: format!("Failed to write decrypted data: {}", e),
+    };
+    let too_short = || PackageError::DecryptionError {
+        reason: "Encrypted data too short".to_string(),
+    };
+
+    let mut header = [0u8; 48];
+    if read_full(encrypted, &mut header).map_err(read_err)? < header.len() {
+        return Err(too_short());
+    }
+    let mut iv = [0u8; 16];
+    iv.copy_from_slice(&header[32..]);
+    let mut decryptor = Aes256CbcDec::new((&encryption_info.encryption_key).into(), (&iv).into());
+
+    // The block held back from the previous chunk goes in front of the next
+    let mut buffer = vec![0u8; STREAM_CHUNK + 16];
+    let mut carried = 0;
+    let mut written = 0u64;
+    let filled = loop {
+        let filled = carried + read_full(encrypted, &mut buffer[carried..]).map_err(read_err)?;
+        if filled < buffer.len() {
+            break filled;
+        }
+
+        let body = filled - 16;
+        for block in buffer[..body].chunks_exact_mut(16) {
+            decryptor.decrypt_block_mut(GenericArray::from_mut_slice(block));
+        }
+        output.write_all(&buffer[..body]).map_err(write_err)?;
+        written += body as u64;
+        buffer.copy_within(body..filled, 0);
+        carried = 16;
+    };
+
+    if filled == 0 {
+        return Err(too_short());
+    }
+    if filled % 16 != 0 {
+        return Err(PackageError::DecryptionError {
+            reason: "Invalid ciphertext length (must be multiple of 16)".to_string(),
+        });
+    }
+    let plaintext = decryptor
+        .decrypt_padded_mut::<Pkcs7>(&mut buffer[..filled])
+        .map_err(|_| PackageError::InvalidPadding)?;
+    output.write_all(plaintext).map_err(write_err)?;
+    Ok(written + plaintext.len() as u64)
+}
+
 /// Constant-time comparison to prevent timing attacks.
 fn constant_time_compare(a: &[u8], b: &[u8]) -> bool {
     if a.len() != b.len() {
@@ -125,6 +371,34 @@ fn generate_keys(info: &mut EncryptionInfo) -> PackageResult<()> {
     Ok(())
 }
 
+/// Derive encryption key, MAC key, and IV from `seed` with HKDF-SHA256,
+/// one labelled expansion per value.
+fn derive_keys(info: &mut EncryptionInfo, seed: &[u8]) -> PackageResult<()> {
+    // The pseudorandom key is wiped once the values are expanded
+    let (prk, _) = Hkdf::<Sha256>::extract(None, seed);
+    let prk = Zeroizing::new(<[u8; 32]>::from(prk));
+    let hkdf =
+        Hkdf::<Sha256>::from_prk(prk.as_ref()).map_err(|e| PackageError::EncryptionError {
+            reason: format!("Key derivation failed: {}", e),
+        })?;
+
+    for (label, output) in [
+        (
+            &b"iamawrapper encryption key"[..],
+            &mut info.encryption_key[..],
+        ),
+        (b"iamawrapper mac key", &mut info.mac_key[..]),
+        (b"iamawrapper iv", &mut info.iv[..]),
+    ] {
+        hkdf.expand(label, output)
+            .map_err(|e| PackageError::EncryptionError {
+                reason: format!("Key derivation failed: {}", e),
+            })?;
+    }
+
+    Ok(())
+}
+
 /// Encrypt data with AES-256-CBC using PKCS7 padding.
 fn aes_encrypt(plaintext: &[u8], key: &[u8; 32], iv: &[u8; 16]) -> PackageResult<Vec<u8>> {
     // Calculate padded size (PKCS7 padding to 16-byte boundary)
@@ -162,7 +436,7 @@ fn compute_hmac(key: &[u8; 32], data: &[u8]) -> PackageResult<[u8; 32]> {
 }
 
 /// Compute SHA256 hash.
-fn compute_sha256(data: &[u8]) -> [u8; 32] {
+pub(crate) fn compute_sha256(data: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(data);
 
@@ -173,8 +447,57 @@ fn compute_sha256(data: &[u8]) -> [u8; 32] {
     output
 }
 
-/// Verify HMAC for decryption (used for testing).
-#[allow(dead_code)]
+/// A content digest being computed with one of the [`DigestAlgorithm`]s.
+pub(crate) enum ContentDigest {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl ContentDigest {
+    pub(crate) fn new(algorithm: DigestAlgorithm) -> Self {
+        match algorithm {
+            DigestAlgorithm::Sha256 => ContentDigest::Sha256(Sha256::new()),
+            DigestAlgorithm::Sha384 => ContentDigest::Sha384(Sha384::new()),
+            DigestAlgorithm::Sha512 => ContentDigest::Sha512(Sha512::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            ContentDigest::Sha256(hasher) => hasher.update(data),
+            ContentDigest::Sha384(hasher) => hasher.update(data),
+            ContentDigest::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        match self {
+            ContentDigest::Sha256(hasher) => hasher.finalize().to_vec(),
+            ContentDigest::Sha384(hasher) => hasher.finalize().to_vec(),
+            ContentDigest::Sha512(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// Compute the `algorithm` digest of `data`.
+pub(crate) fn compute_digest(algorithm: DigestAlgorithm, data: &[u8]) -> Vec<u8> {
+    let mut digest = ContentDigest::new(algorithm);
+    digest.update(data);
+    digest.finalize()
+}
+
+/// Compute the SHA256 hash of everything read from `reader`.
+pub(crate) fn compute_sha256_stream<R: Read + ?Sized>(reader: &mut R) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize());
+    Ok(output)
+}
+
+/// Check that `expected` is the HMAC-SHA256 of `data` under `key`.
 pub fn verify_hmac(key: &[u8; 32], data: &[u8], expected: &[u8; 32]) -> bool {
     match compute_hmac(key, data) {
         Ok(computed) => computed == *expected,
@@ -211,6 +534,29 @@ mod tests {
         assert_ne!(info1.iv, info2.iv);
     }
 
+    #[test]
+    fn test_encrypt_content_seeded_is_stable() {
+        let plaintext = b"Test data";
+
+        let (encrypted1, info1) = encrypt_content_seeded(plaintext, b"seed").unwrap();
+        let (encrypted2, info2) = encrypt_content_seeded(plaintext, b"seed").unwrap();
+        assert_eq!(encrypted1, encrypted2);
+        assert_eq!(info1.encryption_key, info2.encryption_key);
+        assert_eq!(info1.mac_key, info2.mac_key);
+        assert_ne!(info1.encryption_key, info1.mac_key);
+
+        // HKDF-SHA256 of the seed, with the value's label as info
+        let mut expected = [0u8; 16];
+        Hkdf::<Sha256>::new(None, b"seed")
+            .expand(b"iamawrapper iv", &mut expected)
+            .unwrap();
+        assert_eq!(info1.iv, expected);
+
+        let (encrypted3, _) = encrypt_content_seeded(plaintext, b"other").unwrap();
+        assert_ne!(encrypted1, encrypted3);
+        assert_eq!(decrypt_content(&encrypted1, &info1).unwrap(), plaintext);
+    }
+
     #[test]
     fn test_encrypt_content_hmac_verification() {
         let plaintext = b"Test data for HMAC";
@@ -240,6 +586,24 @@ mod tests {
         assert_eq!(info.file_digest, expected_digest);
     }
 
+    #[test]
+    fn test_encrypt_stream_digest_algorithms() {
+        let plaintext = b"Test data";
+        for algorithm in DigestAlgorithm::ALL {
+            let mut info = new_encryption_info(None).unwrap();
+            info.file_digest_algorithm = algorithm;
+            let mut output = io::Cursor::new(Vec::new());
+            encrypt_stream(&mut &plaintext[..], &mut output, &mut info).unwrap();
+
+            assert_eq!(info.file_digest.len(), algorithm.output_len());
+            assert_eq!(info.file_digest, compute_digest(algorithm, plaintext));
+        }
+        assert_eq!(
+            compute_digest(DigestAlgorithm::Sha384, b"abc")[..4],
+            [0xcb, 0x00, 0x75, 0x3f]
+        );
+    }
+
     #[test]
     fn test_aes_encrypt_padding() {
         let key = [0u8; 32];
@@ -268,6 +632,37 @@ mod tests {
         assert_eq!(ciphertext.len(), 32);
     }
 
+    #[test]
+    fn test_encrypt_stream_matches_in_memory() {
+        // Spans several chunks and ends on a partial one
+        let plaintext: Vec<u8> = (0..STREAM_CHUNK * 2 + 1000)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let (expected, expected_info) = encrypt_content_seeded(&plaintext, b"seed").unwrap();
+
+        let mut info = new_encryption_info(Some(b"seed")).unwrap();
+        let mut output = io::Cursor::new(Vec::new());
+        let written = encrypt_stream(&mut plaintext.as_slice(), &mut output, &mut info).unwrap();
+
+        assert_eq!(written, expected.len() as u64);
+        assert_eq!(output.into_inner(), expected);
+        assert_eq!(info.mac, expected_info.mac);
+        assert_eq!(info.file_digest, expected_info.file_digest);
+    }
+
+    #[test]
+    fn test_encrypt_stream_chunk_aligned() {
+        // A whole number of chunks still gets a full padding block
+        let plaintext = vec![7u8; STREAM_CHUNK];
+        let mut info = new_encryption_info(None).unwrap();
+        let mut output = io::Cursor::new(Vec::new());
+        encrypt_stream(&mut plaintext.as_slice(), &mut output, &mut info).unwrap();
+
+        let encrypted = output.into_inner();
+        assert_eq!(encrypted.len(), 48 + STREAM_CHUNK + 16);
+        assert_eq!(decrypt_content(&encrypted, &info).unwrap(), plaintext);
+    }
+
     #[test]
     fn test_compute_sha256() {
         let data = b"test";
@@ -289,7 +684,7 @@ mod tests {
         let (_, info) = encrypt_content(plaintext).unwrap();
 
         assert_eq!(info.profile_identifier, "ProfileVersion1");
-        assert_eq!(info.file_digest_algorithm, "SHA256");
+        assert_eq!(info.file_digest_algorithm, DigestAlgorithm::Sha256);
     }
 
     #[test]
@@ -364,6 +759,67 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_decrypt_stream_matches_in_memory() {
+        // Partial last chunk, a whole number of chunks, and one block
+        for size in [STREAM_CHUNK * 2 + 1000, STREAM_CHUNK, STREAM_CHUNK - 16, 5] {
+            let plaintext: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let (encrypted, info) = encrypt_content(&plaintext).unwrap();
+
+            let read = verify_stream(&mut encrypted.as_slice(), &info).unwrap();
+            assert_eq!(read, encrypted.len() as u64);
+
+            let mut output = Vec::new();
+            let written = decrypt_stream(&mut encrypted.as_slice(), &mut output, &info).unwrap();
+            assert_eq!(written, size as u64);
+            assert_eq!(output, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_verify_stream_rejects_tampering() {
+        let plaintext = vec![3u8; STREAM_CHUNK + 100];
+        let (mut encrypted, info) = encrypt_content(&plaintext).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0x01;
+        assert!(matches!(
+            verify_stream(&mut encrypted.as_slice(), &info),
+            Err(PackageError::HmacVerificationFailed)
+        ));
+
+        // The same checks on length as decrypt_content
+        assert!(matches!(
+            verify_stream(&mut [0u8; 63].as_slice(), &info),
+            Err(PackageError::DecryptionError { .. })
+        ));
+        assert!(matches!(
+            verify_stream(&mut [0u8; 70].as_slice(), &info),
+            Err(PackageError::DecryptionError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_stream_errors() {
+        let (encrypted, info) = encrypt_content_seeded(b"Test data", b"seed").unwrap();
+        let mut output = Vec::new();
+        assert!(matches!(
+            decrypt_stream(&mut &encrypted[..48], &mut output, &info),
+            Err(PackageError::DecryptionError { .. })
+        ));
+        assert!(matches!(
+            decrypt_stream(&mut &encrypted[..60], &mut output, &info),
+            Err(PackageError::DecryptionError { .. })
+        ));
+
+        // Wrong key: the padding of the last block does not check out
+        let mut wrong = info.clone();
+        wrong.encryption_key[0] ^= 0xFF;
+        assert!(matches!(
+            decrypt_stream(&mut encrypted.as_slice(), &mut output, &wrong),
+            Err(PackageError::InvalidPadding)
+        ));
+    }
+
     #[test]
     fn test_aes_decrypt_roundtrip() {
         let key = [1u8; 32];
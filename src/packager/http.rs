@@ -0,0 +1,301 @@
+//! Minimal HTTPS client for Apple's notarization services and remote
+//! sources.
+//!
+//! Requests go through [`HttpTransport`], so the notarization workflow and
+//! downloads can run over any HTTP stack. [`CurlTransport`] runs the `curl` that ships
+//! with macOS and Windows 10 and later. It passes each request as a curl
+//! config through standard input rather than on the command line or in a
+//! file, which keeps API tokens out of the process list and off the disk.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use zeroize::Zeroizing;
+
+use crate::packager::workspace::StagingDir;
+
+/// Body of an [`HttpRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpBody {
+    /// No body
+    Empty,
+    /// Bytes held in memory (JSON documents)
+    Bytes(Vec<u8>),
+    /// A file streamed from disk (package uploads)
+    File(PathBuf),
+}
+
+/// An HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    /// `GET`, `POST` or `PUT`
+    pub method: &'static str,
+    /// Absolute `https://` URL
+    pub url: String,
+    /// Header names and values
+    pub headers: Vec<(String, String)>,
+    /// Request body
+    pub body: HttpBody,
+    /// File the response body is written to instead of being returned
+    pub download: Option<PathBuf>,
+}
+
+impl HttpRequest {
+    /// A request without headers or body.
+    pub fn new(method: &'static str, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: HttpBody::Empty,
+            download: None,
+        }
+    }
+
+    /// Add a header.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body.
+    pub fn with_body(mut self, body: HttpBody) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Write the response body to `path`, for downloads too large to hold
+    /// in memory.
+    pub fn with_download(mut self, path: impl Into<PathBuf>) -> Self {
+        self.download = Some(path.into());
+        self
+    }
+}
+
+/// Status and body of an HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Status code
+    pub status: u16,
+    /// Response body; empty when it was written to the request's
+    /// `download` file
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Whether the status is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Whether retrying the request may succeed (rate limits and server
+    /// errors).
+    pub fn is_transient(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+
+    /// The body as text.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Sends HTTP requests.
+pub trait HttpTransport {
+    /// Send `request`; errors are failures to get any response at all.
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, String>;
+}
+
+/// Sends requests with the `curl` command.
+#[derive(Debug, Clone)]
+pub struct CurlTransport {
+    program: PathBuf,
+}
+
+impl Default for CurlTransport {
+    fn default() -> Self {
+        Self {
+            program: PathBuf::from("curl"),
+        }
+    }
+}
+
+impl CurlTransport {
+    /// Use the curl binary at `program`.
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+/// Redirects followed before a request fails.
+const MAX_REDIRECTS: u32 = 10;
+
+/// Whether `request` has headers holding credentials: `Authorization`,
+/// cookies and signed `x-amz-*` headers such as `x-amz-security-token`.
+fn carries_credentials(request: &HttpRequest) -> bool {
+    request.headers.iter().any(|(name, _)| {
+        let name = name.to_ascii_lowercase();
+        matches!(
+            name.as_str(),
+            "authorization" | "proxy-authorization" | "cookie"
+        ) || name.starts_with("x-amz-")
+    })
+}
+
+/// Quote a value for a curl config file.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl HttpTransport for CurlTransport {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, String> {
+        let staging = StagingDir::create("http").map_err(|e| e.to_string())?;
+        let response_path = request
+            .download
+            .clone()
+            .unwrap_or_else(|| staging.path.join("response"));
+
+        let mut config = Zeroizing::new(vec![
+            format!("url = {}", quote(&request.url)),
+            format!("request = {}", quote(request.method)),
+            format!("output = {}", quote(&response_path.to_string_lossy())),
+            "write-out = \"%{http_code}\"".to_string(),
+            "silent".to_string(),
+            "show-error".to_string(),
+            "connect-timeout = 30".to_string(),
+        ]);
+        // Artifact servers and pre-signed URLs answer with redirects. curl
+        // passes custom headers other than Authorization and Cookie on to
+        // whatever host a redirect names, so requests holding credentials
+        // never follow one
+        if !carries_credentials(request) {
+            config.push("location".to_string());
+            config.push(format!("max-redirs = {}", MAX_REDIRECTS));
+            if request.url.starts_with("https://") {
+                // A redirect must not downgrade the request to plain http
+                config.push("proto-redir = \"=https\"".to_string());
+            }
+        }
+        for (name, value) in &request.headers {
+            config.push(format!(
+                "header = {}",
+                quote(&format!("{}: {}", name, value))
+            ));
+        }
+        match &request.body {
+            HttpBody::Empty => {}
+            HttpBody::Bytes(data) => {
+                let body_path = staging.path.join("body");
+                fs::write(&body_path, data).map_err(|e| e.to_string())?;
+                config.push(format!(
+                    "data-binary = {}",
+                    quote(&format!("@{}", body_path.to_string_lossy()))
+                ));
+            }
+            HttpBody::File(path) => {
+                config.push(format!("upload-file = {}", quote(&path.to_string_lossy())));
+            }
+        }
+        let config = Zeroizing::new(config.join("\n") + "\n");
+
+        let mut child = Command::new(&self.program)
+            .args(["--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run {}: {}", self.program.display(), e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(config.as_bytes())
+                .map_err(|e| format!("cannot pass the request to curl: {}", e))?;
+        }
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+
+        let status = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| "curl did not report a status code".to_string())?;
+        let body = match request.download {
+            Some(_) => Vec::new(),
+            None => fs::read(&response_path).unwrap_or_default(),
+        };
+        Ok(HttpResponse { status, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+    }
+
+    #[test]
+    fn test_response_classes() {
+        let response = |status| HttpResponse {
+            status,
+            body: Vec::new(),
+        };
+        assert!(response(201).is_success());
+        assert!(!response(404).is_success());
+        assert!(response(429).is_transient());
+        assert!(response(503).is_transient());
+        assert!(!response(401).is_transient());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_config_passed_on_stdin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let captured = temp.path().join("config");
+        let program = temp.path().join("curl");
+        fs::write(
+            &program,
+            format!(
+                "#!/bin/sh\n[ \"$*\" = \"--config -\" ] || exit 2\ncat > '{}'\nprintf 200\n",
+                captured.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let transport = CurlTransport::new(&program);
+        let request = HttpRequest::new("GET", "https://example.com/");
+        assert_eq!(transport.send(&request).unwrap().status, 200);
+        let config = fs::read_to_string(&captured).unwrap();
+        assert!(config.contains("url = \"https://example.com/\"\n"));
+        assert!(config.contains("\nlocation\nmax-redirs = 10\n"));
+        assert!(config.contains("proto-redir = \"=https\"\n"));
+
+        // Credentials are never passed on to the host a redirect names
+        for header in ["Authorization", "x-amz-security-token"] {
+            let request = request.clone().with_header(header, "secret");
+            assert_eq!(transport.send(&request).unwrap().status, 200);
+            let config = fs::read_to_string(&captured).unwrap();
+            assert!(config.contains(&format!("header = \"{}: secret\"\n", header)));
+            assert!(!config.contains("location"), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_missing_curl() {
+        let transport = CurlTransport::new("/nonexistent/curl");
+        let error = transport
+            .send(&HttpRequest::new("GET", "https://example.com/"))
+            .unwrap_err();
+        assert!(error.contains("cannot run"));
+    }
+}
@@ -0,0 +1,352 @@
+//! Incremental builds: digests of a build's inputs and the state file that
+//! records them between runs.
+//!
+//! The source digest covers what goes into the content ZIP (file paths and
+//! contents, folders and compression), so an equal digest means the
+//! previous package's content can be decrypted and used as is. Hashing
+//! reads every file once more than a full build, which is far cheaper than
+//! compressing it.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+use zip::read::ZipArchive;
+
+use crate::models::artifact::ArtifactMetadata;
+use crate::models::detection::DetectionMetadata;
+use crate::models::encryption::KeySource;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::incremental::{BuildState, IncrementalOptions, IncrementalOutcome};
+use crate::models::package::{PackageRequest, SourcePackage};
+use crate::models::relationships::AppRelationships;
+use crate::models::transform::TransformRecord;
+
+use super::blocklist::sha256_file;
+use super::manifest::{ManifestHasher, to_hex};
+use super::output::write_atomically;
+use super::{
+    Preflight, decrypt_encrypted_content, extract_artifact_metadata, extract_detection_metadata,
+};
+
+/// An incremental build in progress: the digests of this build and the
+/// previous package, when it can be reused.
+pub(super) struct Incremental {
+    state_file: PathBuf,
+    skip_unchanged: bool,
+    /// Manifest hash of the content, as the artifact record holds it
+    pub(super) manifest_sha256: String,
+    source_sha256: String,
+    settings_sha256: String,
+    /// The previous build, when its source matches and its package is intact
+    previous: Option<(BuildState, DetectionMetadata)>,
+}
+
+impl Incremental {
+    /// Hash this build's inputs and compare them with the state file.
+    pub(super) fn prepare(
+        options: &IncrementalOptions,
+        request: &PackageRequest,
+        preflight: &Preflight<'_>,
+    ) -> PackageResult<Self> {
+        let content = &preflight.content;
+        let source = content.source();
+        let mut manifest = ManifestHasher::default();
+        manifest.add_content(
+            content,
+            source.files.iter().map(|f| f.relative_path.clone()),
+            Path::new(""),
+        )?;
+        let manifest_sha256 = manifest.finish();
+
+        let mut incremental = Self {
+            state_file: options.state_file.clone(),
+            skip_unchanged: options.skip_unchanged,
+            source_sha256: source_digest(&manifest_sha256, source, request),
+            settings_sha256: settings_digest(
+                request,
+                preflight.relationships.as_ref(),
+                content.records(),
+            ),
+            manifest_sha256,
+            previous: None,
+        };
+        incremental.previous = load_state(&options.state_file)
+            .filter(|state| state.source_sha256 == incremental.source_sha256)
+            .and_then(|state| {
+                let metadata = intact_package(&state)?;
+                Some((state, metadata))
+            });
+        Ok(incremental)
+    }
+
+    /// What this build does.
+    pub(super) fn outcome(&self) -> IncrementalOutcome {
+        match &self.previous {
+            Some((state, _))
+                if self.skip_unchanged && state.settings_sha256 == self.settings_sha256 =>
+            {
+                IncrementalOutcome::Skipped
+            }
+            Some(_) => IncrementalOutcome::Reencrypted,
+            None => IncrementalOutcome::Built,
+        }
+    }
+
+    /// The previous package and its detection metadata, when reusable.
+    pub(super) fn previous(&self) -> PackageResult<(&Path, &DetectionMetadata)> {
+        self.previous
+            .as_ref()
+            .map(|(state, metadata)| (state.package.as_path(), metadata))
+            .ok_or_else(|| PackageError::InvalidArgument {
+                reason: "No previous package to reuse".to_string(),
+            })
+    }
+
+    /// The artifact record of the previous package.
+    pub(super) fn previous_artifact(&self) -> PackageResult<Option<ArtifactMetadata>> {
+        let (path, _) = self.previous()?;
+        extract_artifact_metadata(&mut open_package(path)?, path)
+    }
+
+    /// Check and decrypt the previous package's content ZIP into `output`.
+    pub(super) fn decrypt_previous<W: Write + ?Sized>(&self, output: &mut W) -> PackageResult<()> {
+        let (path, metadata) = self.previous()?;
+        let mut archive = open_package(path)?;
+        decrypt_encrypted_content(&mut archive, path, &metadata.encryption_info, output)?;
+        Ok(())
+    }
+
+    /// Record the package this build wrote.
+    pub(super) fn save(
+        &self,
+        package: &Path,
+        metadata: &DetectionMetadata,
+        request: &PackageRequest,
+    ) -> PackageResult<()> {
+        let state = BuildState {
+            source_sha256: self.source_sha256.clone(),
+            settings_sha256: self.settings_sha256.clone(),
+            content_digest: to_hex(&metadata.encryption_info.file_digest),
+            package: package.to_path_buf(),
+            package_sha256: sha256_file(package)
+                .map_err(|e| PackageError::source_read(package.to_path_buf(), e))?,
+        };
+        if let Some(parent) = self
+            .state_file
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_write(self.state_file.clone(), e))?;
+        }
+        write_atomically(&self.state_file, &request.io_retry, |writer| {
+            writer
+                .write_all(state.to_json().as_bytes())
+                .map_err(|e| PackageError::output_write(self.state_file.clone(), e))
+        })
+    }
+}
+
+/// Digest of everything that shapes the content ZIP.
+fn source_digest(
+    manifest_sha256: &str,
+    source: &SourcePackage,
+    request: &PackageRequest,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("manifest\t{}\n", manifest_sha256));
+    for dir in &source.directories {
+        hasher.update(format!(
+            "dir\t{}\n",
+            dir.to_string_lossy().replace('\\', "/")
+        ));
+    }
+    hasher.update(format!("compression\t{}\n", request.compression));
+    to_hex(&hasher.finalize())
+}
+
+/// Digest of everything written outside the content ZIP, and of the tool
+/// version, so an upgrade rebuilds the package.
+fn settings_digest(
+    request: &PackageRequest,
+    relationships: Option<&AppRelationships>,
+    transforms: &[TransformRecord],
+) -> String {
+    let keys = match &request.encryption.keys {
+        KeySource::Random => "random".to_string(),
+        KeySource::Provided(keys) => {
+            let mut hasher = Sha256::new();
+            hasher.update(keys.key);
+            hasher.update(keys.mac_key);
+            hasher.update(keys.iv.unwrap_or_default());
+            format!("provided {}", to_hex(&hasher.finalize()))
+        }
+    };
+
+    let mut hasher = Sha256::new();
+    for (name, value) in [
+        ("tool", env!("CARGO_PKG_VERSION").to_string()),
+        (
+            "output",
+            request.output_path().to_string_lossy().into_owned(),
+        ),
+        ("setup_file", request.setup_file.clone()),
+        ("display_name", format!("{:?}", request.display_name)),
+        ("tool_version", format!("{:?}", request.tool_version)),
+        ("content_tags", format!("{:?}", request.content_tags)),
+        (
+            "relationships",
+            relationships
+                .map(AppRelationships::to_json)
+                .unwrap_or_default(),
+        ),
+        ("transforms", format!("{:?}", transforms)),
+        ("reproducible", format!("{:?}", request.reproducible)),
+        ("keys", keys),
+        ("digest", request.encryption.digest_algorithm.to_string()),
+        ("setup_manifest", request.setup_manifest.to_string()),
+        ("sbom", format!("{:?}", request.sbom)),
+    ] {
+        hasher.update(format!("{}\t{}\n", name, value));
+    }
+    to_hex(&hasher.finalize())
+}
+
+/// Read the state file; a missing or unreadable one means a full build.
+fn load_state(path: &Path) -> Option<BuildState> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!(path = %path.display(), "Ignoring unreadable state file: {}", e);
+            return None;
+        }
+    };
+    BuildState::parse(&text)
+        .map_err(|e| warn!(path = %path.display(), "Ignoring invalid state file: {}", e))
+        .ok()
+}
+
+/// The detection metadata of the state's package, if the package is the
+/// one the state recorded and holds the recorded content.
+fn intact_package(state: &BuildState) -> Option<DetectionMetadata> {
+    let unchanged = sha256_file(&state.package)
+        .is_ok_and(|sha256| sha256.eq_ignore_ascii_case(&state.package_sha256));
+    if !unchanged {
+        debug!(path = %state.package.display(), "Previous package is missing or changed");
+        return None;
+    }
+
+    let metadata = open_package(&state.package)
+        .and_then(|mut archive| extract_detection_metadata(&mut archive, &state.package))
+        .ok()?;
+    let content_digest = to_hex(&metadata.encryption_info.file_digest);
+    content_digest
+        .eq_ignore_ascii_case(&state.content_digest)
+        .then_some(metadata)
+}
+
+fn open_package(path: &Path) -> PackageResult<ZipArchive<BufReader<File>>> {
+    let file = File::open(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    ZipArchive::new(BufReader::new(file)).map_err(|e| PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason: format!("Invalid ZIP archive: {}", e),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{UnpackRequest, Verbosity};
+    use crate::packager::{package, unpack};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_incremental_reuses_unchanged_content() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), b"MZ installer").unwrap();
+        fs::write(source.join("data.txt"), "data ".repeat(1000)).unwrap();
+
+        let state_file = temp.path().join("state").join("app.json");
+        let request = |skip: bool| {
+            PackageRequest::new(&source, "setup.exe", temp.path().join("out"))
+                .with_verbosity(Verbosity::Silent)
+                .with_incremental(IncrementalOptions::new(&state_file).with_skip_unchanged(skip))
+        };
+
+        let first = package(&request(false)).unwrap();
+        assert_eq!(first.incremental, Some(IncrementalOutcome::Built));
+        let state = BuildState::parse(&fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert_eq!(state.package, first.output_path);
+
+        // Same content under new keys
+        let second = package(&request(false)).unwrap();
+        assert_eq!(second.incremental, Some(IncrementalOutcome::Reencrypted));
+        assert_eq!(
+            second.metadata.encryption_info.file_digest,
+            first.metadata.encryption_info.file_digest
+        );
+        assert_ne!(
+            second.metadata.encryption_info.encryption_key,
+            first.metadata.encryption_info.encryption_key
+        );
+        assert_eq!(
+            second.artifact.manifest_sha256,
+            first.artifact.manifest_sha256
+        );
+        let extracted = temp.path().join("extracted");
+        unpack(&UnpackRequest::new(
+            second.output_path.clone(),
+            extracted.clone(),
+        ))
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(extracted.join("data.txt")).unwrap(),
+            "data ".repeat(1000)
+        );
+
+        let before = fs::read(&second.output_path).unwrap();
+        let third = package(&request(true)).unwrap();
+        assert_eq!(third.incremental, Some(IncrementalOutcome::Skipped));
+        assert_eq!(third.output_path, second.output_path);
+        assert_eq!(fs::read(&third.output_path).unwrap(), before);
+
+        // A changed setting re-encrypts, a changed file rebuilds
+        let renamed = package(&request(true).with_display_name("App")).unwrap();
+        assert_eq!(renamed.incremental, Some(IncrementalOutcome::Reencrypted));
+        fs::write(source.join("data.txt"), "changed").unwrap();
+        let changed = package(&request(true)).unwrap();
+        assert_eq!(changed.incremental, Some(IncrementalOutcome::Built));
+    }
+
+    #[test]
+    fn test_incremental_rebuilds_when_package_changed() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), b"MZ installer").unwrap();
+
+        let state_file = temp.path().join("state.json");
+        let request = PackageRequest::new(&source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent)
+            .with_incremental(IncrementalOptions::new(&state_file).with_skip_unchanged(true));
+        let first = package(&request).unwrap();
+
+        // The previous package is no longer the one recorded
+        let mut bytes = fs::read(&first.output_path).unwrap();
+        bytes.push(0);
+        fs::write(&first.output_path, bytes).unwrap();
+        let second = package(&request).unwrap();
+        assert_eq!(second.incremental, Some(IncrementalOutcome::Built));
+
+        // An unreadable state file is ignored
+        fs::write(&state_file, "not json").unwrap();
+        let third = package(&request).unwrap();
+        assert_eq!(third.incremental, Some(IncrementalOutcome::Built));
+    }
+}
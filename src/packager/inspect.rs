@@ -0,0 +1,151 @@
+//! Reading the artifact record back from a package file.
+//!
+//! The package type is sniffed from the file's magic bytes rather than its
+//! extension, so renamed or extension-less artifacts are still recognized.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use zip::read::ZipArchive;
+
+use crate::models::artifact::{ArtifactKind, ArtifactReport};
+use crate::models::detection::DetectionMetadata;
+use crate::models::error::{PackageError, PackageResult};
+
+use super::{extract_artifact_metadata, extract_detection_metadata};
+
+/// Read the type and artifact record of the package at `path`.
+pub fn inspect(path: &Path) -> PackageResult<ArtifactReport> {
+    let read_error = |e: std::io::Error| PackageError::source_read(path.to_path_buf(), e);
+    let mut file = File::open(path).map_err(read_error)?;
+    let mut magic = Vec::with_capacity(4);
+    file.by_ref()
+        .take(4)
+        .read_to_end(&mut magic)
+        .map_err(read_error)?;
+
+    if magic.starts_with(b"PK") {
+        file.rewind().map_err(read_error)?;
+        let mut archive = ZipArchive::new(BufReader::new(file)).map_err(|e| {
+            PackageError::InvalidIntunewinFile {
+                path: path.to_path_buf(),
+                reason: format!("Invalid ZIP archive: {}", e),
+            }
+        })?;
+        return Ok(ArtifactReport {
+            path: path.to_path_buf(),
+            kind: ArtifactKind::Intune,
+            metadata: extract_artifact_metadata(&mut archive, path)?,
+        });
+    }
+
+    if magic == b"xar!" {
+        return inspect_pkg(path, &fs::read(path).map_err(read_error)?);
+    }
+
+    Err(PackageError::InvalidArgument {
+        reason: format!(
+            "'{}' is neither an .intunewin nor a .pkg file",
+            path.display()
+        ),
+    })
+}
+
+/// Read the Detection.xml metadata of the `.intunewin` at `path`.
+///
+/// Only the outer ZIP's metadata entry is read; the encrypted content is
+/// neither decrypted nor extracted.
+pub fn inspect_detection(path: &Path) -> PackageResult<DetectionMetadata> {
+    let file = File::open(path).map_err(|e| PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason: format!("Failed to open file: {}", e),
+    })?;
+    let mut archive =
+        ZipArchive::new(BufReader::new(file)).map_err(|e| PackageError::InvalidIntunewinFile {
+            path: path.to_path_buf(),
+            reason: format!("Invalid ZIP archive: {}", e),
+        })?;
+    extract_detection_metadata(&mut archive, path)
+}
+
+#[cfg(feature = "macos")]
+fn inspect_pkg(path: &Path, data: &[u8]) -> PackageResult<ArtifactReport> {
+    let archive = crate::macos::xar::XarArchive::read(data)?;
+    Ok(ArtifactReport {
+        path: path.to_path_buf(),
+        kind: ArtifactKind::MacosPkg,
+        metadata: archive.artifact().cloned(),
+    })
+}
+
+#[cfg(not(feature = "macos"))]
+fn inspect_pkg(_path: &Path, _data: &[u8]) -> PackageResult<ArtifactReport> {
+    Err(PackageError::InvalidArgument {
+        reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inspect_intunewin() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+
+        let request = PackageRequest::new(&source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+
+        let report = inspect(&result.output_path).unwrap();
+        assert_eq!(report.kind, ArtifactKind::Intune);
+        assert_eq!(report.metadata, Some(result.artifact));
+    }
+
+    #[test]
+    fn test_inspect_detection() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("install.ps1"), "Write-Host hi").unwrap();
+
+        let request = PackageRequest::new(&source, "install.ps1", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+
+        let metadata = inspect_detection(&result.output_path).unwrap();
+        assert_eq!(metadata.setup_file, "install.ps1");
+        assert_eq!(
+            metadata.unencrypted_content_size,
+            result.metadata.unencrypted_content_size
+        );
+        assert_eq!(
+            metadata.encryption_info.encryption_key,
+            result.metadata.encryption_info.encryption_key
+        );
+
+        let not_zip = temp.path().join("notes.txt");
+        fs::write(&not_zip, "hello").unwrap();
+        assert!(matches!(
+            inspect_detection(&not_zip),
+            Err(PackageError::InvalidIntunewinFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_inspect_rejects_unknown_files() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("notes.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let err = inspect(&path).unwrap_err();
+        assert!(matches!(err, PackageError::InvalidArgument { .. }));
+    }
+}
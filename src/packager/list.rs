@@ -0,0 +1,113 @@
+//! Listing the content of an `.intunewin` without extracting it.
+//!
+//! The encrypted content is checked against its HMAC and decrypted in
+//! memory, and only the file table of the inner ZIP is read. Nothing is
+//! decompressed or written to disk, so listing is quick even for large
+//! packages, and works for entries this build could not extract.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+
+use zip::read::ZipArchive;
+
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::listing::ContentEntry;
+use crate::models::size::ByteSize;
+
+use super::{decrypt_encrypted_content, extract_detection_metadata};
+
+/// List the entries of the content of the package at `path`, in archive
+/// order.
+pub fn list_contents(path: &Path) -> PackageResult<Vec<ContentEntry>> {
+    let file = File::open(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    let mut archive =
+        ZipArchive::new(BufReader::new(file)).map_err(|e| PackageError::InvalidIntunewinFile {
+            path: path.to_path_buf(),
+            reason: format!("Invalid ZIP archive: {}", e),
+        })?;
+    let metadata = extract_detection_metadata(&mut archive, path)?;
+
+    let mut decrypted = Vec::new();
+    decrypt_encrypted_content(
+        &mut archive,
+        path,
+        &metadata.encryption_info,
+        &mut decrypted,
+    )?;
+    let mut inner =
+        ZipArchive::new(Cursor::new(decrypted)).map_err(|e| PackageError::DecryptionError {
+            reason: format!("Decrypted content is not a valid ZIP: {}", e),
+        })?;
+
+    (0..inner.len())
+        .map(|i| {
+            let entry = inner.by_index_raw(i).map_err(|e| {
+                PackageError::zip(ZipStage::ReadContent, Some(&format!("#{}", i)), e)
+            })?;
+            Ok(ContentEntry {
+                path: entry.name().to_string(),
+                is_dir: entry.is_dir(),
+                size: ByteSize(entry.size()),
+                compressed_size: ByteSize(entry.compressed_size()),
+                crc32: entry.crc32(),
+                compression: method_name(entry.compression()),
+            })
+        })
+        .collect()
+}
+
+/// Name of a ZIP compression method as `--compression` spells it.
+fn method_name(method: zip::CompressionMethod) -> String {
+    match method {
+        zip::CompressionMethod::Stored => "stored".to_string(),
+        zip::CompressionMethod::Deflated => "deflate".to_string(),
+        zip::CompressionMethod::ZSTD => "zstd".to_string(),
+        other => other.to_string().to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_list_contents() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir_all(source.join("data/empty")).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("data/config.xml"), "<config/>".repeat(100)).unwrap();
+
+        let request = PackageRequest::new(&source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+
+        let entries = list_contents(&result.output_path).unwrap();
+        let config = entries
+            .iter()
+            .find(|e| e.path == "data/config.xml")
+            .unwrap();
+        assert_eq!(config.size, ByteSize(900));
+        assert!(config.compressed_size < config.size);
+        assert_eq!(config.compression, "deflate");
+        assert_eq!(config.crc32_hex().len(), 8);
+        assert!(entries.iter().any(|e| e.path == "data/empty/" && e.is_dir));
+        assert_eq!(entries.iter().filter(|e| !e.is_dir).count(), 2);
+    }
+
+    #[test]
+    fn test_list_contents_rejects_non_package() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("notes.txt");
+        fs::write(&path, "not a package").unwrap();
+        assert!(matches!(
+            list_contents(&path),
+            Err(PackageError::InvalidIntunewinFile { .. })
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! Content manifest hashing for the artifact record.
+//!
+//! The manifest lists every packaged file as `path<TAB>size<TAB>sha256`,
+//! one per line, sorted by path with `/` separators. Its SHA-256 identifies
+//! the content independently of the package format, so an `.intunewin` and
+//! a `.pkg` built from the same files carry the same hash.
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::models::error::{PackageError, PackageResult};
+
+use super::content::ContentProvider;
+
+/// Collects file hashes and produces the manifest hash.
+#[derive(Debug, Default)]
+pub struct ManifestHasher {
+    entries: Vec<(String, u64, String)>,
+}
+
+impl ManifestHasher {
+    /// Record a file by its path relative to the content root.
+    pub fn add(&mut self, relative_path: &Path, size: u64, sha256: String) {
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        self.entries.push((path, size, sha256));
+    }
+
+    /// Hash and record the files at `paths` in `content` under `prefix`.
+    pub fn add_content(
+        &mut self,
+        content: &dyn ContentProvider,
+        paths: impl IntoIterator<Item = PathBuf>,
+        prefix: &Path,
+    ) -> PackageResult<()> {
+        for path in paths {
+            let mut reader = HashingReader::new(content.open(&path)?);
+            io::copy(&mut reader, &mut io::sink())
+                .map_err(|e| PackageError::source_read(content.root().join(&path), e))?;
+            let (size, sha256) = reader.finish();
+            self.add(&prefix.join(&path), size, sha256);
+        }
+        Ok(())
+    }
+
+    /// Lowercase hex SHA-256 of the manifest.
+    pub fn finish(mut self) -> String {
+        self.entries.sort();
+        let mut hasher = Sha256::new();
+        for (path, size, sha256) in &self.entries {
+            hasher.update(format!("{}\t{}\t{}\n", path, size, sha256).as_bytes());
+        }
+        to_hex(&hasher.finalize())
+    }
+}
+
+/// Reader that hashes everything read through it.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Hash the bytes read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    /// Bytes read so far and their lowercase hex SHA-256.
+    pub fn finish(self) -> (u64, String) {
+        (self.size, to_hex(&self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Manifest hash of the files at `paths` in `content`.
+pub fn manifest_sha256(
+    content: &dyn ContentProvider,
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> PackageResult<String> {
+    let mut manifest = ManifestHasher::default();
+    manifest.add_content(content, paths, Path::new(""))?;
+    Ok(manifest.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::content::FolderContent;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_manifest_hash_is_order_independent() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir(temp.path().join("sub")).unwrap();
+        fs::write(temp.path().join("a.txt"), "a").unwrap();
+        fs::write(temp.path().join("sub/b.txt"), "b").unwrap();
+        let content = FolderContent::new(temp.path());
+
+        let forward = manifest_sha256(
+            &content,
+            [PathBuf::from("a.txt"), PathBuf::from("sub/b.txt")],
+        )
+        .unwrap();
+        let backward = manifest_sha256(
+            &content,
+            [PathBuf::from("sub/b.txt"), PathBuf::from("a.txt")],
+        )
+        .unwrap();
+        assert_eq!(forward, backward);
+        assert_eq!(forward.len(), 64);
+
+        fs::write(temp.path().join("a.txt"), "changed").unwrap();
+        let changed = manifest_sha256(
+            &content,
+            [PathBuf::from("a.txt"), PathBuf::from("sub/b.txt")],
+        )
+        .unwrap();
+        assert_ne!(forward, changed);
+    }
+
+    #[test]
+    fn test_hashing_reader_counts_bytes() {
+        let mut reader = HashingReader::new(&b"hello"[..]);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        let (size, sha256) = reader.finish();
+        assert_eq!(size, 5);
+        assert_eq!(
+            sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
@@ -1,14 +1,19 @@
 //! Detection.xml generation and parsing.
 
+use std::path::PathBuf;
+
 use quick_xml::Reader;
 use quick_xml::Writer;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use tracing::debug;
+use zeroize::Zeroizing;
 
-use crate::models::detection::{DetectionMetadata, EncryptionInfo};
+use crate::models::artifact::{ARTIFACT_ELEMENT, ARTIFACT_NAMESPACE, ArtifactMetadata};
+use crate::models::detection::{
+    DEFAULT_TOOL_VERSION, DetectionExtra, DetectionMetadata, EncryptionInfo, XmlElement,
+};
 use crate::models::error::{PackageError, PackageResult};
-
-/// Tool version to include in Detection.xml (matches Microsoft's format).
-const TOOL_VERSION: &str = "1.8.6.0";
+use crate::models::transform::TransformRecord;
 
 /// Generate Detection.xml content matching the Microsoft format.
 ///
@@ -23,12 +28,13 @@ pub fn generate_detection_xml(metadata: &DetectionMetadata) -> PackageResult<Str
     let mut root = BytesStart::new("ApplicationInfo");
     root.push_attribute(("xmlns:xsd", "http://www.w3.org/2001/XMLSchema"));
     root.push_attribute(("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"));
-    root.push_attribute(("ToolVersion", TOOL_VERSION));
+    root.push_attribute(("ToolVersion", metadata.tool_version.as_str()));
+    for (key, value) in &metadata.extra.attributes {
+        root.push_attribute((key.as_str(), value.as_str()));
+    }
     writer
         .write_event(Event::Start(root))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
 
     // Name element
     write_element(&mut writer, "Name", &metadata.name)?;
@@ -49,14 +55,15 @@ pub fn generate_detection_xml(metadata: &DetectionMetadata) -> PackageResult<Str
     // EncryptionInfo element
     writer
         .write_event(Event::Start(BytesStart::new("EncryptionInfo")))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
 
     let info = &metadata.encryption_info;
 
-    write_element(&mut writer, "EncryptionKey", &info.encryption_key_base64())?;
-    write_element(&mut writer, "MacKey", &info.mac_key_base64())?;
+    // The encoded keys are wiped as soon as they are written
+    let encryption_key = Zeroizing::new(info.encryption_key_base64());
+    let mac_key = Zeroizing::new(info.mac_key_base64());
+    write_element(&mut writer, "EncryptionKey", &encryption_key)?;
+    write_element(&mut writer, "MacKey", &mac_key)?;
     write_element(&mut writer, "InitializationVector", &info.iv_base64())?;
     write_element(&mut writer, "Mac", &info.mac_base64())?;
     write_element(&mut writer, "ProfileIdentifier", &info.profile_identifier)?;
@@ -64,26 +71,28 @@ pub fn generate_detection_xml(metadata: &DetectionMetadata) -> PackageResult<Str
     write_element(
         &mut writer,
         "FileDigestAlgorithm",
-        &info.file_digest_algorithm,
+        info.file_digest_algorithm.name(),
     )?;
+    for element in &metadata.extra.encryption_elements {
+        write_kept_element(&mut writer, element)?;
+    }
 
     writer
         .write_event(Event::End(BytesEnd::new("EncryptionInfo")))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
+
+    // Elements of a third-party Detection.xml, such as MsiInfo
+    for element in &metadata.extra.elements {
+        write_kept_element(&mut writer, element)?;
+    }
 
     // Close ApplicationInfo
     writer
         .write_event(Event::End(BytesEnd::new("ApplicationInfo")))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
 
     let output = writer.into_inner();
-    let xml = String::from_utf8(output).map_err(|e| PackageError::XmlError {
-        reason: e.to_string(),
-    })?;
+    let xml = Zeroizing::new(String::from_utf8(output).map_err(PackageError::xml)?);
 
     // Convert LF to CRLF for Windows compatibility (Microsoft tool uses CRLF)
     Ok(xml.replace('\n', "\r\n"))
@@ -96,129 +105,572 @@ fn write_element<W: std::io::Write>(
 ) -> PackageResult<()> {
     writer
         .write_event(Event::Start(BytesStart::new(name)))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
     writer
         .write_event(Event::Text(BytesText::new(value)))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
     writer
         .write_event(Event::End(BytesEnd::new(name)))
-        .map_err(|e| PackageError::XmlError {
-            reason: e.to_string(),
-        })?;
+        .map_err(PackageError::xml)?;
     Ok(())
 }
 
-/// Parse Detection.xml content into DetectionMetadata.
-pub fn parse_detection_xml(xml: &str) -> PackageResult<DetectionMetadata> {
+/// Write an element kept from a parsed Detection.xml, with its children.
+fn write_kept_element<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    element: &XmlElement,
+) -> PackageResult<()> {
+    let mut start = BytesStart::new(element.name.as_str());
+    for (key, value) in &element.attributes {
+        start.push_attribute((key.as_str(), value.as_str()));
+    }
+    if element.text.is_empty() && element.children.is_empty() {
+        writer
+            .write_event(Event::Empty(start))
+            .map_err(PackageError::xml)?;
+        return Ok(());
+    }
+
+    writer
+        .write_event(Event::Start(start))
+        .map_err(PackageError::xml)?;
+    if !element.text.is_empty() {
+        writer
+            .write_event(Event::Text(BytesText::new(&element.text)))
+            .map_err(PackageError::xml)?;
+    }
+    for child in &element.children {
+        write_kept_element(writer, child)?;
+    }
+    writer
+        .write_event(Event::End(BytesEnd::new(element.name.as_str())))
+        .map_err(PackageError::xml)?;
+    Ok(())
+}
+
+/// Generate the content tags XML stored next to Detection.xml.
+///
+/// Uses the same formatting as Detection.xml (no declaration, CRLF).
+pub fn generate_content_tags_xml(tags: &[(String, String)]) -> PackageResult<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    writer
+        .write_event(Event::Start(BytesStart::new("ContentTags")))
+        .map_err(PackageError::xml)?;
+
+    for (key, value) in tags {
+        let mut tag = BytesStart::new("Tag");
+        tag.push_attribute(("Key", key.as_str()));
+        writer
+            .write_event(Event::Start(tag))
+            .map_err(PackageError::xml)?;
+        writer
+            .write_event(Event::Text(BytesText::new(value)))
+            .map_err(PackageError::xml)?;
+        writer
+            .write_event(Event::End(BytesEnd::new("Tag")))
+            .map_err(PackageError::xml)?;
+    }
+
+    writer
+        .write_event(Event::End(BytesEnd::new("ContentTags")))
+        .map_err(PackageError::xml)?;
+
+    let xml = String::from_utf8(writer.into_inner()).map_err(PackageError::xml)?;
+
+    Ok(xml.replace('\n', "\r\n"))
+}
+
+/// Generate the transforms file recording files changed at package time.
+pub fn generate_transforms_xml(records: &[TransformRecord]) -> PackageResult<String> {
+    let xml_error = |e: quick_xml::Error| PackageError::xml(e);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    writer
+        .write_event(Event::Start(BytesStart::new("Transforms")))
+        .map_err(xml_error)?;
+
+    for record in records {
+        let path = record.path.to_string_lossy().replace('\\', "/");
+        let mut file = BytesStart::new("File");
+        file.push_attribute(("Path", path.as_str()));
+        file.push_attribute(("OriginalSha256", record.original_sha256.as_str()));
+        file.push_attribute(("Sha256", record.sha256.as_str()));
+        writer.write_event(Event::Start(file)).map_err(xml_error)?;
+
+        for transformer in &record.transformers {
+            writer
+                .write_event(Event::Start(BytesStart::new("Transformer")))
+                .map_err(xml_error)?;
+            writer
+                .write_event(Event::Text(BytesText::new(transformer)))
+                .map_err(xml_error)?;
+            writer
+                .write_event(Event::End(BytesEnd::new("Transformer")))
+                .map_err(xml_error)?;
+        }
+
+        writer
+            .write_event(Event::End(BytesEnd::new("File")))
+            .map_err(xml_error)?;
+    }
+
+    writer
+        .write_event(Event::End(BytesEnd::new("Transforms")))
+        .map_err(xml_error)?;
+
+    let xml = String::from_utf8(writer.into_inner()).map_err(PackageError::xml)?;
+
+    Ok(xml.replace('\n', "\r\n"))
+}
+
+/// Parse a transforms file written by [`generate_transforms_xml`].
+pub fn parse_transforms_xml(xml: &str) -> PackageResult<Vec<TransformRecord>> {
     let mut reader = Reader::from_str(xml);
     reader.trim_text(true);
 
-    let mut name = String::new();
-    let mut unencrypted_content_size: u64 = 0;
-    let mut file_name = String::new();
-    let mut setup_file = String::new();
-    let mut encryption_info = EncryptionInfo::new();
+    let mut records: Vec<TransformRecord> = Vec::new();
+    let mut in_transformer = false;
+    let mut buf = Vec::new();
 
-    let mut current_element = String::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if e.name().as_ref() == b"File" => {
+                let attribute = |name: &str| -> PackageResult<String> {
+                    let value = e.try_get_attribute(name).ok().flatten().ok_or_else(|| {
+                        PackageError::XmlError {
+                            reason: format!("File element is missing {} attribute", name),
+                            source: None,
+                        }
+                    })?;
+                    value
+                        .unescape_value()
+                        .map(|v| v.to_string())
+                        .map_err(|err| {
+                            PackageError::xml_with(
+                                format!("Failed to unescape {}: {}", name, err),
+                                err,
+                            )
+                        })
+                };
+                records.push(TransformRecord {
+                    path: PathBuf::from(attribute("Path")?),
+                    transformers: Vec::new(),
+                    original_sha256: attribute("OriginalSha256")?,
+                    sha256: attribute("Sha256")?,
+                });
+            }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"Transformer" => in_transformer = true,
+            Ok(Event::Text(e)) if in_transformer => {
+                let text = e.unescape().map_err(|err| {
+                    PackageError::xml_with(format!("Failed to unescape text: {}", err), err)
+                })?;
+                if let Some(record) = records.last_mut() {
+                    record.transformers.push(text.to_string());
+                }
+            }
+            Ok(Event::End(_)) => in_transformer = false,
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(PackageError::xml_with(format!("XML parse error: {}", e), e));
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(records)
+}
+
+/// Generate the artifact record written next to Detection.xml.
+pub fn generate_artifact_xml(artifact: &ArtifactMetadata) -> PackageResult<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    let mut root = BytesStart::new(ARTIFACT_ELEMENT);
+    root.push_attribute(("xmlns", ARTIFACT_NAMESPACE));
+    writer
+        .write_event(Event::Start(root))
+        .map_err(PackageError::xml)?;
+
+    for (name, value) in artifact.fields() {
+        write_element(&mut writer, name, &value)?;
+    }
+
+    writer
+        .write_event(Event::End(BytesEnd::new(ARTIFACT_ELEMENT)))
+        .map_err(PackageError::xml)?;
+
+    let xml = String::from_utf8(writer.into_inner()).map_err(PackageError::xml)?;
+
+    Ok(xml.replace('\n', "\r\n"))
+}
+
+/// Parse an artifact record written by [`generate_artifact_xml`].
+pub fn parse_artifact_xml(xml: &str) -> PackageResult<ArtifactMetadata> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut current: Option<String> = None;
     let mut buf = Vec::new();
 
     loop {
         match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(e)) => {
-                current_element = String::from_utf8_lossy(e.name().as_ref()).to_string();
+            Ok(Event::Start(e)) if e.name().as_ref() != ARTIFACT_ELEMENT.as_bytes() => {
+                current = Some(String::from_utf8_lossy(e.name().as_ref()).to_string());
             }
             Ok(Event::Text(e)) => {
-                let text = e.unescape().map_err(|err| PackageError::XmlError {
-                    reason: format!("Failed to unescape text: {}", err),
-                })?;
+                if let Some(name) = current.take() {
+                    let text = e.unescape().map_err(|err| {
+                        PackageError::xml_with(format!("Failed to unescape text: {}", err), err)
+                    })?;
+                    fields.push((name, text.to_string()));
+                }
+            }
+            Ok(Event::End(_)) => current = None,
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(PackageError::xml_with(format!("XML parse error: {}", e), e));
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    ArtifactMetadata::from_fields(fields.iter().map(|(n, v)| (n.as_str(), v.as_str()))).map_err(
+        |reason| PackageError::XmlError {
+            reason,
+            source: None,
+        },
+    )
+}
 
-                match current_element.as_str() {
-                    "Name" => name = text.to_string(),
-                    "UnencryptedContentSize" => {
-                        unencrypted_content_size =
-                            text.parse().map_err(|e| PackageError::XmlError {
-                                reason: format!("Invalid UnencryptedContentSize: {}", e),
-                            })?;
-                    }
-                    "FileName" => file_name = text.to_string(),
-                    "SetupFile" => setup_file = text.to_string(),
-                    "EncryptionKey" => {
-                        encryption_info
-                            .set_encryption_key_from_base64(&text)
-                            .map_err(|e| PackageError::XmlError { reason: e })?;
-                    }
-                    "MacKey" => {
-                        encryption_info
-                            .set_mac_key_from_base64(&text)
-                            .map_err(|e| PackageError::XmlError { reason: e })?;
-                    }
-                    "InitializationVector" => {
-                        encryption_info
-                            .set_iv_from_base64(&text)
-                            .map_err(|e| PackageError::XmlError { reason: e })?;
-                    }
-                    "Mac" => {
-                        encryption_info
-                            .set_mac_from_base64(&text)
-                            .map_err(|e| PackageError::XmlError { reason: e })?;
-                    }
-                    "ProfileIdentifier" => {
-                        encryption_info.profile_identifier = text.to_string();
-                    }
-                    "FileDigest" => {
-                        encryption_info
-                            .set_file_digest_from_base64(&text)
-                            .map_err(|e| PackageError::XmlError { reason: e })?;
-                    }
-                    "FileDigestAlgorithm" => {
-                        encryption_info.file_digest_algorithm = text.to_string();
-                    }
-                    _ => {}
+/// Parse content tags XML into key/value pairs.
+pub fn parse_content_tags_xml(xml: &str) -> PackageResult<Vec<(String, String)>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut tags = Vec::new();
+    let mut current_key: Option<String> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if e.name().as_ref() == b"Tag" => {
+                let key = e
+                    .try_get_attribute("Key")
+                    .map_err(|err| {
+                        PackageError::xml_with(format!("Invalid Tag attribute: {}", err), err)
+                    })?
+                    .ok_or_else(|| PackageError::XmlError {
+                        reason: "Tag element is missing Key attribute".to_string(),
+                        source: None,
+                    })?
+                    .unescape_value()
+                    .map_err(|err| {
+                        PackageError::xml_with(format!("Failed to unescape Key: {}", err), err)
+                    })?
+                    .to_string();
+                tags.push((key.clone(), String::new()));
+                current_key = Some(key);
+            }
+            Ok(Event::Text(e)) if current_key.is_some() => {
+                let text = e.unescape().map_err(|err| {
+                    PackageError::xml_with(format!("Failed to unescape text: {}", err), err)
+                })?;
+                if let Some((_, value)) = tags.last_mut() {
+                    *value = text.to_string();
                 }
             }
-            Ok(Event::End(_)) => {
-                current_element.clear();
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"Tag" => {
+                if let Some(attr) = e.try_get_attribute("Key").ok().flatten() {
+                    let key = attr.unescape_value().map_err(|err| {
+                        PackageError::xml_with(format!("Failed to unescape Key: {}", err), err)
+                    })?;
+                    tags.push((key.to_string(), String::new()));
+                }
             }
+            Ok(Event::End(_)) => current_key = None,
             Ok(Event::Eof) => break,
             Err(e) => {
-                return Err(PackageError::XmlError {
-                    reason: format!("XML parse error: {}", e),
-                });
+                return Err(PackageError::xml_with(format!("XML parse error: {}", e), e));
+            }
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(tags)
+}
+
+/// Children of `ApplicationInfo` the parser interprets.
+const APPLICATION_INFO_FIELDS: &[&str] = &[
+    "Name",
+    "UnencryptedContentSize",
+    "FileName",
+    "SetupFile",
+    "EncryptionInfo",
+];
+
+/// Children of `EncryptionInfo` the parser interprets.
+const ENCRYPTION_INFO_FIELDS: &[&str] = &[
+    "EncryptionKey",
+    "MacKey",
+    "InitializationVector",
+    "Mac",
+    "ProfileIdentifier",
+    "FileDigest",
+    "FileDigestAlgorithm",
+];
+
+/// Namespace declarations [`generate_detection_xml`] always writes.
+const ROOT_NAMESPACES: &[&str] = &["xmlns:xsd", "xmlns:xsi"];
+
+/// Parse Detection.xml content into DetectionMetadata.
+///
+/// Fields are matched by their position in the document, so elements of the
+/// same name nested elsewhere (as in the `MsiInfo` block of Microsoft's
+/// tool) do not overwrite them. Anything not interpreted is kept in
+/// [`DetectionMetadata::extra`].
+pub fn parse_detection_xml(xml: &str) -> PackageResult<DetectionMetadata> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut parser = DetectionParser::default();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => parser.start(&e)?,
+            Ok(Event::Empty(e)) => {
+                parser.start(&e)?;
+                parser.end();
+            }
+            Ok(Event::Text(e)) => {
+                let text = e.unescape().map_err(|err| {
+                    PackageError::xml_with(format!("Failed to unescape text: {}", err), err)
+                })?;
+                parser.text(&text)?;
+            }
+            Ok(Event::CData(e)) => parser.text(&String::from_utf8_lossy(&e))?,
+            Ok(Event::End(_)) => parser.end(),
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                return Err(PackageError::xml_with(format!("XML parse error: {}", e), e));
             }
             _ => {}
         }
         buf.clear();
     }
 
-    // Validate required fields
-    if name.is_empty() {
-        return Err(PackageError::XmlError {
-            reason: "Missing Name element".to_string(),
-        });
+    parser.finish()
+}
+
+/// State of [`parse_detection_xml`] while it walks the document.
+struct DetectionParser {
+    tool_version: String,
+    name: String,
+    unencrypted_content_size: u64,
+    file_name: String,
+    setup_file: String,
+    encryption_info: EncryptionInfo,
+    extra: DetectionExtra,
+    /// Local names of the open interpreted elements, outermost first
+    path: Vec<String>,
+    /// Open elements being kept as written, innermost last; everything
+    /// inside a kept element is kept with it
+    kept: Vec<XmlElement>,
+}
+
+impl Default for DetectionParser {
+    fn default() -> Self {
+        Self {
+            // Packages from tools that leave it out report the default
+            tool_version: DEFAULT_TOOL_VERSION.to_string(),
+            name: String::new(),
+            unencrypted_content_size: 0,
+            file_name: String::new(),
+            setup_file: String::new(),
+            encryption_info: EncryptionInfo::new(),
+            extra: DetectionExtra::default(),
+            path: Vec::new(),
+            kept: Vec::new(),
+        }
+    }
+}
+
+impl DetectionParser {
+    fn start(&mut self, e: &BytesStart<'_>) -> PackageResult<()> {
+        let local_name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        let known = match self.path.as_slice() {
+            _ if !self.kept.is_empty() => false,
+            [] => local_name == "ApplicationInfo",
+            [_] => APPLICATION_INFO_FIELDS.contains(&local_name.as_str()),
+            [_, parent] if parent == "EncryptionInfo" => {
+                ENCRYPTION_INFO_FIELDS.contains(&local_name.as_str())
+            }
+            _ => false,
+        };
+
+        if !known {
+            self.kept.push(XmlElement {
+                name: String::from_utf8_lossy(e.name().as_ref()).into_owned(),
+                attributes: read_attributes(e)?,
+                ..XmlElement::default()
+            });
+            return Ok(());
+        }
+
+        if self.path.is_empty() {
+            for (key, value) in read_attributes(e)? {
+                if key == "ToolVersion" {
+                    self.tool_version = value;
+                } else if !ROOT_NAMESPACES.contains(&key.as_str()) {
+                    self.extra.attributes.push((key, value));
+                }
+            }
+        }
+        self.path.push(local_name);
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> PackageResult<()> {
+        if let Some(element) = self.kept.last_mut() {
+            element.text.push_str(text);
+            return Ok(());
+        }
+
+        let invalid = |reason: String| PackageError::XmlError {
+            reason,
+            source: None,
+        };
+        // The interpreted names are unique, so the innermost one says which
+        // field this is
+        let Some(field) = self.path.last() else {
+            return Ok(());
+        };
+        match field.as_str() {
+            "Name" => self.name = text.to_string(),
+            "UnencryptedContentSize" => {
+                self.unencrypted_content_size = text.parse().map_err(|e| {
+                    PackageError::xml_with(format!("Invalid UnencryptedContentSize: {}", e), e)
+                })?;
+            }
+            "FileName" => self.file_name = text.to_string(),
+            "SetupFile" => self.setup_file = text.to_string(),
+            "EncryptionKey" => {
+                self.encryption_info
+                    .set_encryption_key_from_base64(text)
+                    .map_err(invalid)?;
+            }
+            "MacKey" => {
+                self.encryption_info
+                    .set_mac_key_from_base64(text)
+                    .map_err(invalid)?;
+            }
+            "InitializationVector" => {
+                self.encryption_info
+                    .set_iv_from_base64(text)
+                    .map_err(invalid)?;
+            }
+            "Mac" => {
+                self.encryption_info
+                    .set_mac_from_base64(text)
+                    .map_err(invalid)?;
+            }
+            "ProfileIdentifier" => {
+                self.encryption_info.profile_identifier = text.to_string();
+            }
+            "FileDigest" => {
+                self.encryption_info
+                    .set_file_digest_from_base64(text)
+                    .map_err(invalid)?;
+            }
+            "FileDigestAlgorithm" => {
+                self.encryption_info.file_digest_algorithm = text.parse().map_err(invalid)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) {
+        let Some(element) = self.kept.pop() else {
+            self.path.pop();
+            return;
+        };
+        if let Some(parent) = self.kept.last_mut() {
+            parent.children.push(element);
+            return;
+        }
+        match self.path.as_slice() {
+            [_] => self.extra.elements.push(element),
+            [_, parent] if parent == "EncryptionInfo" => {
+                self.extra.encryption_elements.push(element)
+            }
+            _ => debug!(
+                "Ignoring <{}> inside <{}> in Detection.xml",
+                element.name,
+                self.path.join("/")
+            ),
+        }
     }
-    if setup_file.is_empty() {
-        return Err(PackageError::XmlError {
-            reason: "Missing SetupFile element".to_string(),
-        });
+
+    fn finish(self) -> PackageResult<DetectionMetadata> {
+        // Validate required fields
+        if self.name.is_empty() {
+            return Err(PackageError::XmlError {
+                reason: "Missing Name element".to_string(),
+                source: None,
+            });
+        }
+        if self.setup_file.is_empty() {
+            return Err(PackageError::XmlError {
+                reason: "Missing SetupFile element".to_string(),
+                source: None,
+            });
+        }
+        self.encryption_info
+            .check_file_digest()
+            .map_err(|reason| PackageError::XmlError {
+                reason,
+                source: None,
+            })?;
+
+        Ok(DetectionMetadata {
+            tool_version: self.tool_version,
+            name: self.name,
+            unencrypted_content_size: self.unencrypted_content_size,
+            file_name: self.file_name,
+            setup_file: self.setup_file,
+            encryption_info: self.encryption_info,
+            extra: self.extra,
+        })
     }
+}
 
-    Ok(DetectionMetadata {
-        name,
-        unencrypted_content_size,
-        file_name,
-        setup_file,
-        encryption_info,
-    })
+/// The attributes of `e` in document order, unescaped.
+fn read_attributes(e: &BytesStart<'_>) -> PackageResult<Vec<(String, String)>> {
+    e.attributes()
+        .map(|attr| {
+            let attr = attr.map_err(|err| {
+                PackageError::xml_with(format!("Invalid attribute: {}", err), err)
+            })?;
+            let value = attr.unescape_value().map_err(|err| {
+                PackageError::xml_with(format!("Failed to unescape attribute: {}", err), err)
+            })?;
+            Ok((
+                String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                value.into_owned(),
+            ))
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::detection::EncryptionInfo;
+    use crate::models::detection::{DigestAlgorithm, EncryptionInfo, XmlElement};
 
     #[test]
     fn test_generate_detection_xml_structure() {
@@ -334,7 +786,7 @@ mod tests {
         original.encryption_info.mac_key = [2u8; 32];
         original.encryption_info.iv = [3u8; 16];
         original.encryption_info.mac = [4u8; 32];
-        original.encryption_info.file_digest = [5u8; 32];
+        original.encryption_info.file_digest = vec![5u8; 32];
 
         // Generate XML
         let xml = generate_detection_xml(&original).unwrap();
@@ -374,6 +826,200 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_third_party_detection_xml() {
+        let mut keys = DetectionMetadata::new("setup.msi".to_string(), 0);
+        keys.encryption_info.encryption_key = [1u8; 32];
+        keys.encryption_info.file_digest = vec![5u8; 32];
+        let info = &keys.encryption_info;
+
+        // As Microsoft's tool writes it for an MSI, with fields reordered,
+        // an unknown attribute and a Name nested in MsiInfo
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ApplicationInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" ToolVersion="1.8.4.0" Vendor="Contoso">
+  <SetupFile>setup.msi</SetupFile>
+  <Name>Contoso App</Name>
+  <UnencryptedContentSize>4096</UnencryptedContentSize>
+  <FileName>IntunePackage.intunewin</FileName>
+  <EncryptionInfo>
+    <EncryptionKey>{}</EncryptionKey>
+    <MacKey>{}</MacKey>
+    <InitializationVector>{}</InitializationVector>
+    <Mac>{}</Mac>
+    <ProfileIdentifier>ProfileVersion1</ProfileIdentifier>
+    <FileDigest>{}</FileDigest>
+    <FileDigestAlgorithm>SHA256</FileDigestAlgorithm>
+    <KeyVersion>2</KeyVersion>
+  </EncryptionInfo>
+  <MsiInfo>
+    <MsiProductCode>{{11111111-2222-3333-4444-555555555555}}</MsiProductCode>
+    <MsiProductVersion>1.2.3</MsiProductVersion>
+    <MsiUpgradeCode>{{66666666-7777-8888-9999-000000000000}}</MsiUpgradeCode>
+    <MsiExecutionContext>System</MsiExecutionContext>
+    <MsiRequiresReboot>false</MsiRequiresReboot>
+    <MsiPublisher>Contoso &amp; Co</MsiPublisher>
+    <Name>Not the app name</Name>
+    <MsiNotes/>
+  </MsiInfo>
+</ApplicationInfo>"#,
+            info.encryption_key_base64(),
+            info.mac_key_base64(),
+            info.iv_base64(),
+            info.mac_base64(),
+            info.file_digest_base64(),
+        );
+
+        let parsed = parse_detection_xml(&xml).unwrap();
+        assert_eq!(parsed.name, "Contoso App");
+        assert_eq!(parsed.setup_file, "setup.msi");
+        assert_eq!(parsed.tool_version, "1.8.4.0");
+        assert_eq!(parsed.unencrypted_content_size, 4096);
+        assert_eq!(parsed.encryption_info.encryption_key, [1u8; 32]);
+        assert_eq!(
+            parsed.extra.attributes,
+            vec![("Vendor".to_string(), "Contoso".to_string())]
+        );
+        assert_eq!(
+            parsed.extra.encryption_elements,
+            vec![XmlElement::new("KeyVersion").with_text("2")]
+        );
+        let msi_info = &parsed.extra.elements[0];
+        assert_eq!(msi_info.children.len(), 8);
+        assert_eq!(
+            msi_info.child("MsiNotes"),
+            Some(&XmlElement::new("MsiNotes"))
+        );
+
+        let msi = parsed.msi_info().unwrap();
+        assert_eq!(msi.product_code, "{11111111-2222-3333-4444-555555555555}");
+        assert_eq!(msi.product_version, "1.2.3");
+        assert_eq!(msi.publisher.as_deref(), Some("Contoso & Co"));
+
+        // Writing it back keeps what was not interpreted
+        let rewritten = generate_detection_xml(&parsed).unwrap();
+        assert!(rewritten.contains("ToolVersion=\"1.8.4.0\" Vendor=\"Contoso\""));
+        assert!(rewritten.contains("<MsiPublisher>Contoso &amp; Co</MsiPublisher>"));
+        assert!(rewritten.contains("<MsiNotes/>"));
+        assert!(
+            rewritten.find("<KeyVersion>").unwrap() < rewritten.find("</EncryptionInfo>").unwrap()
+        );
+        let reparsed = parse_detection_xml(&rewritten).unwrap();
+        assert_eq!(reparsed.extra, parsed.extra);
+        assert_eq!(reparsed.name, parsed.name);
+    }
+
+    #[test]
+    fn test_parse_detection_xml_ignores_misplaced_fields() {
+        let mut metadata = DetectionMetadata::new("setup.exe".to_string(), 2048);
+        metadata.encryption_info.file_digest = vec![5u8; 32];
+        let xml = generate_detection_xml(&metadata).unwrap().replace(
+            "<FileName>",
+            "<Extra><SetupFile>other.exe</SetupFile></Extra><FileName>",
+        );
+
+        let parsed = parse_detection_xml(&xml).unwrap();
+        assert_eq!(parsed.setup_file, "setup.exe");
+        assert_eq!(
+            parsed.extra.elements,
+            vec![
+                XmlElement::new("Extra")
+                    .with_child(XmlElement::new("SetupFile").with_text("other.exe"))
+            ]
+        );
+        assert!(metadata.extra.is_empty());
+    }
+
+    #[test]
+    fn test_parse_detection_xml_digest_algorithms() {
+        let mut metadata = DetectionMetadata::new("setup.exe".to_string(), 2048);
+        metadata.encryption_info.file_digest_algorithm = DigestAlgorithm::Sha384;
+        metadata.encryption_info.file_digest = vec![5u8; 48];
+        let xml = generate_detection_xml(&metadata).unwrap();
+        assert!(xml.contains("<FileDigestAlgorithm>SHA384</FileDigestAlgorithm>"));
+
+        let parsed = parse_detection_xml(&xml).unwrap();
+        assert_eq!(
+            parsed.encryption_info.file_digest_algorithm,
+            DigestAlgorithm::Sha384
+        );
+        assert_eq!(parsed.encryption_info.file_digest, vec![5u8; 48]);
+
+        // The digest must fit the algorithm, and the algorithm be known
+        let short = xml.replace(">SHA384<", ">SHA512<");
+        assert!(parse_detection_xml(&short).is_err());
+        let unknown = xml.replace(">SHA384<", ">MD5<");
+        assert!(parse_detection_xml(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_detection_xml_overrides_roundtrip() {
+        let mut original = DetectionMetadata::new("setup.exe".to_string(), 1);
+        original.name = "ACME Tool & Friends".to_string();
+        original.tool_version = "1.8.4.0".to_string();
+
+        let xml = generate_detection_xml(&original).unwrap();
+        assert!(xml.contains("ToolVersion=\"1.8.4.0\""));
+        assert!(xml.contains("<Name>ACME Tool &amp; Friends</Name>"));
+
+        let parsed = parse_detection_xml(&xml).unwrap();
+        assert_eq!(parsed.tool_version, "1.8.4.0");
+        assert_eq!(parsed.name, "ACME Tool & Friends");
+        assert_eq!(parsed.setup_file, "setup.exe");
+
+        // Packages without the attribute report the default
+        let without = xml.replace(" ToolVersion=\"1.8.4.0\"", "");
+        assert_eq!(
+            parse_detection_xml(&without).unwrap().tool_version,
+            DEFAULT_TOOL_VERSION
+        );
+    }
+
+    #[test]
+    fn test_content_tags_xml_roundtrip() {
+        let tags = vec![
+            ("build".to_string(), "1234".to_string()),
+            ("git".to_string(), "a1b2c3d".to_string()),
+            ("ticket".to_string(), "CHG<42> & more".to_string()),
+            ("empty".to_string(), String::new()),
+        ];
+
+        let xml = generate_content_tags_xml(&tags).unwrap();
+        assert!(xml.contains("<Tag Key=\"build\">1234</Tag>"));
+        assert!(xml.contains("\r\n"));
+
+        let parsed = parse_content_tags_xml(&xml).unwrap();
+        assert_eq!(parsed, tags);
+    }
+
+    #[test]
+    fn test_transforms_xml_roundtrip() {
+        let records = vec![TransformRecord {
+            path: PathBuf::from("scripts/install.ps1"),
+            transformers: vec!["crlf".to_string(), "replace:<v>=1 & 2".to_string()],
+            original_sha256: "aa".to_string(),
+            sha256: "bb".to_string(),
+        }];
+
+        let xml = generate_transforms_xml(&records).unwrap();
+        assert!(xml.contains("Path=\"scripts/install.ps1\""));
+        assert_eq!(parse_transforms_xml(&xml).unwrap(), records);
+    }
+
+    #[test]
+    fn test_artifact_xml_roundtrip() {
+        let artifact = ArtifactMetadata::new("0f".repeat(32));
+
+        let xml = generate_artifact_xml(&artifact).unwrap();
+        assert!(xml.starts_with("<ArtifactInfo xmlns=\"urn:iamawrapper:artifact\">\r\n"));
+        assert!(xml.contains("<SchemaVersion>1</SchemaVersion>"));
+        assert_eq!(parse_artifact_xml(&xml).unwrap(), artifact);
+
+        let err = parse_artifact_xml("<ArtifactInfo><ToolVersion>1</ToolVersion></ArtifactInfo>")
+            .unwrap_err();
+        assert!(err.to_string().contains("SchemaVersion"));
+    }
+
     #[test]
     fn test_parse_detection_xml_missing_name() {
         let xml = r#"<?xml version="1.0" encoding="utf-8"?>
@@ -1,213 +1,931 @@
 //! Package creation and extraction module.
 
+pub mod analyze;
 pub mod archive;
+pub mod batch;
+pub mod blocklist;
+pub mod build;
+pub mod cache;
+pub mod capture;
+pub mod choco;
+pub mod content;
+pub mod detection;
+pub mod diff;
 pub mod encrypt;
+pub mod http;
+mod incremental;
+pub mod inspect;
+pub mod list;
+pub mod manifest;
 pub mod metadata;
+pub mod msi;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod output;
+pub mod pe;
+pub mod progress;
+pub mod project;
+pub mod protected_zip;
+pub mod relationships;
+pub mod repack;
+pub mod retry;
+pub mod sanitize;
+pub mod sbom;
+pub mod scaffold;
+pub mod selftest;
+#[cfg(any(feature = "macos", feature = "s3"))]
+pub(crate) mod sigv4;
+pub mod source;
+pub mod sparse;
+pub mod special;
+pub mod transform;
+pub mod upload;
+pub mod validate;
+pub mod verify;
+pub mod watch;
+pub mod win32app;
+pub mod winpath;
+pub mod workspace;
 
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter, Read as IoRead, Write};
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Cursor, Read as IoRead, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
-use indicatif::{ProgressBar, ProgressStyle};
+use tracing::{debug, info, info_span};
 use zip::ZipWriter;
 use zip::read::ZipArchive;
-use zip::write::FileOptions;
 
-use crate::models::detection::DetectionMetadata;
-use crate::models::error::{PackageError, PackageResult};
+use crate::models::artifact::ArtifactMetadata;
+use crate::models::blocklist::BlocklistMatch;
+use crate::models::compression::Compression;
+use crate::models::detection::{DetectionMetadata, EncryptionInfo};
+use crate::models::dry_run::DryRunReport;
+use crate::models::encryption::KeySource;
+use crate::models::error::{PackageError, PackageResult, ZipStage};
+use crate::models::guardrails::SizeEstimate;
+use crate::models::incremental::IncrementalOutcome;
 use crate::models::package::{
-    IntuneWinPackage, PackageRequest, SourcePackage, UnpackRequest, UnpackResult, Verbosity,
+    IntuneWinPackage, PackageRequest, Reproducible, SourcePackage, UnpackRequest, UnpackResult,
 };
-
-use self::archive::collect_source_files;
-use self::encrypt::{decrypt_content, encrypt_content};
-use self::metadata::{generate_detection_xml, parse_detection_xml};
+use crate::models::progress::ProgressStats;
+use crate::models::relationships::AppRelationships;
+use crate::models::sbom::Sbom;
+use crate::models::setup::{ExeVersionInfo, SETUP_MANIFEST_EXTENSION};
+use crate::models::size::ByteSize;
+use crate::models::strictness::Check;
+
+use self::archive::{entry_options, open_source, write_content_files};
+use self::blocklist::check_blocklist;
+use self::cache::ContentCache;
+use self::content::ContentProvider;
+use self::encrypt::{
+    compute_sha256_stream, decrypt_stream, encrypt_stream, new_encryption_info,
+    provided_encryption_info, verify_stream,
+};
+use self::incremental::Incremental;
+use self::metadata::{
+    generate_artifact_xml, generate_content_tags_xml, generate_detection_xml,
+    generate_transforms_xml, parse_artifact_xml, parse_content_tags_xml, parse_detection_xml,
+};
+use self::output::write_atomically;
+use self::progress::Progress;
+use self::relationships::load_relationships;
+use self::sanitize::{contained_path, sanitize_entry_name};
+use self::source::fetch_source;
+use self::special::check_skipped;
+use self::transform::TransformedContent;
+use self::validate::{validate_intunewin, validate_with_timeout};
+use self::winpath::{check_path_issues, path_report_json};
+use self::workspace::StagingDir;
+
+pub use self::analyze::analyze;
+pub use self::inspect::inspect_detection;
+pub use self::list::list_contents;
+#[cfg(feature = "async")]
+pub use self::nonblocking::{package_async, unpack_async};
+pub use self::relationships::inspect_relationships;
+pub use self::scaffold::scaffold;
+pub use self::verify::verify;
+pub use self::watch::watch;
+pub use self::win32app::app_manifest;
+
+/// Path of the encrypted content inside the outer ZIP.
+pub const CONTENT_ENTRY: &str = "IntuneWinPackage/Contents/IntunePackage.intunewin";
+
+/// Path of Detection.xml inside the outer ZIP.
+pub const DETECTION_ENTRY: &str = "IntuneWinPackage/Metadata/Detection.xml";
+
+/// Path of the optional content tags file inside the outer ZIP.
+pub const CONTENT_TAGS_ENTRY: &str = "IntuneWinPackage/Metadata/ContentTags.xml";
+
+/// Path of the optional record of transformed files inside the outer ZIP.
+pub const TRANSFORMS_ENTRY: &str = "IntuneWinPackage/Metadata/Transforms.xml";
+
+/// Path of the artifact record inside the outer ZIP.
+pub const ARTIFACT_ENTRY: &str = "IntuneWinPackage/Metadata/ArtifactInfo.xml";
+
+/// Path of the optional embedded SBOM inside the outer ZIP.
+pub const SBOM_ENTRY: &str = "IntuneWinPackage/Metadata/SBOM.json";
+
+/// Path of the optional app relationships file inside the outer ZIP.
+pub const RELATIONSHIPS_ENTRY: &str = "IntuneWinPackage/Metadata/Extra/Relationships.json";
+
+/// ZIP compression method number of Zstandard.
+const ZSTD_METHOD: u16 = 93;
 
 /// Create an IntuneWin package from the given request.
 pub fn package(request: &PackageRequest) -> PackageResult<IntuneWinPackage> {
-    let start_time = Instant::now();
-
     // Validate request
     request.validate()?;
 
-    // Collect source files
-    let source_package = collect_source_files(&request.source_folder, &request.setup_file)?;
+    let source = fetch_source(&request.source_folder)?;
+    let content = open_source(source.path(), &request.setup_file, request.io_retry)?;
+    package_content(request, &*content)
+}
 
-    if source_package.files.is_empty() {
-        return Err(PackageError::SourceFolderEmpty {
-            path: request.source_folder.clone(),
+/// Create an IntuneWin package from `content` instead of the request's
+/// source folder.
+pub fn package_content(
+    request: &PackageRequest,
+    content: &dyn ContentProvider,
+) -> PackageResult<IntuneWinPackage> {
+    build_package(request, content, Output::Request)
+}
+
+/// Create an IntuneWin package from `content` and write it to `writer`.
+///
+/// Nothing is read from or written to disk beyond what the request's
+/// settings name (blocklist, cache, transform files): the request's output
+/// folder is ignored and intermediate data is kept in memory, so this
+/// suits content that fits in memory, such as a
+/// [`MemoryContent`](content::MemoryContent). The returned `output_path`
+/// is the name the package would have in the output folder.
+pub fn package_to_writer<W: Write + Seek>(
+    request: &PackageRequest,
+    content: &dyn ContentProvider,
+    writer: &mut W,
+) -> PackageResult<IntuneWinPackage> {
+    build_package(request, content, Output::Writer(writer))
+}
+
+/// A writer ZIP archives can be written to.
+trait WriteSeek: Write + Seek {}
+
+impl<T: Write + Seek> WriteSeek for T {}
+
+/// A reader ZIP archives can be read from.
+trait ReadSeek: IoRead + Seek {}
+
+impl<T: IoRead + Seek> ReadSeek for T {}
+
+/// Collect and check the request's source folder as [`package`] does,
+/// without compressing or writing anything.
+pub fn dry_run(request: &PackageRequest) -> PackageResult<DryRunReport> {
+    request.validate()?;
+
+    let source = fetch_source(&request.source_folder)?;
+    let content = open_source(source.path(), &request.setup_file, request.io_retry)?;
+    dry_run_content(request, &*content)
+}
+
+/// Collect and check `content` as [`package_content`] does, without
+/// compressing or writing anything.
+///
+/// The report lists what the package would hold and where it would go;
+/// checks that would fail the run fail the dry run the same way. No path
+/// report is written.
+pub fn dry_run_content(
+    request: &PackageRequest,
+    content: &dyn ContentProvider,
+) -> PackageResult<DryRunReport> {
+    let _span = info_span!("dry_run", setup_file = %request.setup_file).entered();
+
+    request.validate_settings()?;
+
+    let Preflight {
+        content,
+        size_estimate,
+        blocklist_matches,
+        setup_info,
+        relationships,
+    } = preflight(request, content, false)?;
+    let source = content.source();
+
+    let mut metadata = vec![("setup_file".to_string(), request.setup_file.clone())];
+    if let Some(name) = &request.display_name {
+        metadata.push(("name".to_string(), name.clone()));
+    }
+    if let Some(relationships) = &relationships {
+        metadata.push(("relationships".to_string(), relationships.summary()));
+    }
+    if let Some(info) = setup_info {
+        let version = info.file_version.or(info.product_version);
+        metadata.extend(
+            [
+                ("product", info.product_name),
+                ("version", version),
+                ("company", info.company_name),
+            ]
+            .into_iter()
+            .filter_map(|(label, value)| Some((label.to_string(), value?))),
+        );
+    }
+
+    let output_path = request.output_path();
+    Ok(DryRunReport {
+        output_exists: !request.writes_to_stdout() && output_path.exists(),
+        output_path,
+        file_count: source.file_count(),
+        dir_count: source.dir_count(),
+        total_size: ByteSize(source.total_size),
+        size_estimate: Some(size_estimate),
+        estimated_time: DryRunReport::estimate_time(ByteSize(source.total_size)),
+        metadata,
+        blocklist_matches,
+        sparse_files: source.sparse_files.clone(),
+        skipped_entries: source.skipped_entries.clone(),
+        path_issues: source.path_issues.clone(),
+    })
+}
+
+/// Where a finished package goes.
+enum Output<'a> {
+    /// The request's output path, or stdout when it is `-`
+    Request,
+    /// A caller's writer; intermediate data stays in memory
+    Writer(&'a mut dyn WriteSeek),
+}
+
+fn build_package(
+    request: &PackageRequest,
+    content: &dyn ContentProvider,
+    output: Output<'_>,
+) -> PackageResult<IntuneWinPackage> {
+    let start_time = Instant::now();
+    let _span = info_span!("package", setup_file = %request.setup_file).entered();
+
+    request.validate_settings()?;
+
+    // Files written next to the package need one on disk
+    let in_memory = matches!(output, Output::Writer(_));
+    if in_memory && request.post_validate.is_some() {
+        return Err(PackageError::InvalidArgument {
+            reason: "Post-validation needs an output file, not a writer".to_string(),
+        });
+    }
+    if in_memory && request.setup_manifest {
+        return Err(PackageError::InvalidArgument {
+            reason: "A setup manifest needs an output file, not a writer".to_string(),
+        });
+    }
+    if in_memory && request.sbom.is_some_and(|sbom| sbom.sidecar) {
+        return Err(PackageError::InvalidArgument {
+            reason: "A sidecar SBOM needs an output file, not a writer".to_string(),
         });
     }
+    if in_memory && request.incremental.is_some() {
+        return Err(PackageError::InvalidArgument {
+            reason: "Incremental builds need an output file, not a writer".to_string(),
+        });
+    }
+
+    let preflight = preflight(request, content, true)?;
+
+    // An unchanged source lets the previous package's content be reused
+    let incremental = request
+        .incremental
+        .as_ref()
+        .map(|options| Incremental::prepare(options, request, &preflight))
+        .transpose()?;
+    let outcome = incremental.as_ref().map(Incremental::outcome);
+    if let Some(outcome) = outcome {
+        info!(%outcome, "Checked incremental state");
+    }
+    if let (Some(incremental), Some(IncrementalOutcome::Skipped)) = (&incremental, outcome) {
+        return previous_package(request, incremental, start_time, preflight);
+    }
+
+    let Preflight {
+        content: transformed,
+        size_estimate,
+        blocklist_matches,
+        setup_info,
+        relationships,
+    } = preflight;
+    let content: &dyn ContentProvider = &transformed;
+    let source_package = transformed.source();
 
     // Create output folder if needed
-    if !request.output_folder.exists() {
-        fs::create_dir_all(&request.output_folder).map_err(|e| {
-            PackageError::OutputFolderCreationFailed {
-                path: request.output_folder.clone(),
-                reason: e.to_string(),
-            }
-        })?;
+    let to_stdout = request.writes_to_stdout();
+    let to_file = !in_memory && !to_stdout;
+    if to_file && !request.output_folder.exists() {
+        fs::create_dir_all(&request.output_folder)
+            .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
     }
 
     // Check if output file exists
     let output_path = request.output_path();
-    if output_path.exists() && !request.verbosity.suppress_prompts() {
+    let replaces_previous = incremental
+        .as_ref()
+        .and_then(|incremental| incremental.previous().ok())
+        .is_some_and(|(path, _)| path == output_path);
+    if to_file
+        && output_path.exists()
+        && !replaces_previous
+        && !request.verbosity.suppress_prompts()
+    {
         return Err(PackageError::OutputFileExists { path: output_path });
     }
 
     // Create progress bar
-    let progress = create_progress_bar(&source_package, request.verbosity);
+    let progress = Progress::bar(source_package.file_count() as u64, request.verbosity);
+    #[cfg(feature = "async")]
+    let progress = progress.with_cancellation(request.cancellation.clone());
+
+    // The inner ZIP and its encrypted form are spooled to the run workspace,
+    // so packages far larger than memory (and past 4 GiB) can be built
+    let workspace = if in_memory {
+        None
+    } else {
+        // Room for the content ZIP and its encrypted copy, stored at worst
+        Some(StagingDir::create_with_space(
+            "package",
+            source_package.total_size.saturating_mul(2),
+        )?)
+    };
 
     // Create inner ZIP (content to be encrypted)
-    let inner_zip = create_inner_zip(&source_package, &progress)?;
-    let unencrypted_size = inner_zip.len() as u64;
+    let mut inner = SpoolFile::new(workspace.as_ref(), "content.zip");
+    let mut cache = None;
+    let manifest_sha256 = match (&incremental, outcome) {
+        (Some(incremental), Some(IncrementalOutcome::Reencrypted)) => {
+            progress.set_message("Reusing content...");
+            info_span!("reuse")
+                .in_scope(|| inner.write(|writer| incremental.decrypt_previous(writer)))?;
+            incremental.manifest_sha256.clone()
+        }
+        _ => {
+            cache = request
+                .cache
+                .as_ref()
+                .map(|config| ContentCache::open(config, request.compression))
+                .transpose()?;
+            info_span!("zip").in_scope(|| {
+                inner.write(|writer| {
+                    create_inner_zip(
+                        source_package,
+                        content,
+                        &request.compression,
+                        &progress,
+                        cache.as_mut(),
+                        writer,
+                    )
+                })
+            })?
+        }
+    };
+    let unencrypted_size = inner.size()?;
+    debug!(bytes = unencrypted_size, "Wrote content archive");
 
+    progress.check_cancelled()?;
     progress.set_message("Encrypting...");
 
-    // Encrypt the inner ZIP
-    let (encrypted_content, encryption_info) = encrypt_content(&inner_zip)?;
-
+    // Encrypt the inner ZIP; reproducible packages derive their keys and
+    // callers may supply them
+    let encrypt_span = info_span!("encrypt").entered();
+    let mut encryption_info = match (&request.encryption.keys, &request.reproducible) {
+        (KeySource::Provided(keys), _) => provided_encryption_info(keys),
+        (KeySource::Random, Some(Reproducible::Seed(seed))) => {
+            new_encryption_info(Some(seed.as_bytes()))?
+        }
+        (KeySource::Random, Some(Reproducible::ContentDigest)) => {
+            let digest = compute_sha256_stream(&mut inner.open()?).map_err(|e| inner.error(e))?;
+            new_encryption_info(Some(&digest))?
+        }
+        (KeySource::Random, None) => new_encryption_info(None)?,
+    };
+    encryption_info.file_digest_algorithm = request.encryption.digest_algorithm;
+    let mut encrypted = SpoolFile::new(workspace.as_ref(), "content.intunewin");
+    let encrypted_size = encrypted
+        .write(|writer| encrypt_stream(&mut inner.open()?, writer, &mut encryption_info))?;
+    inner.remove()?;
+    debug!(bytes = encrypted_size, "Encrypted content archive");
+    drop(encrypt_span);
+
+    progress.check_cancelled()?;
     progress.set_message("Writing package...");
 
     // Create detection metadata
     let mut metadata = DetectionMetadata::new(request.setup_file.clone(), unencrypted_size);
+    if let Some(name) = &request.display_name {
+        metadata.name = name.clone();
+    }
+    if let Some(version) = &request.tool_version {
+        metadata.tool_version = version.clone();
+    }
     metadata.encryption_info = encryption_info;
 
     // Generate Detection.xml
     let detection_xml = generate_detection_xml(&metadata)?;
 
-    // Create outer ZIP (final .intunewin file)
-    create_outer_zip(&output_path, &detection_xml, &encrypted_content)?;
+    // The artifact record is always written; content tags and transforms
+    // files only when non-empty
+    let mut artifact = ArtifactMetadata::new(manifest_sha256);
+    if request.reproducible.is_some() {
+        // The build time would make every package unique
+        artifact.created = 0;
+    }
+    let mut extra_metadata = vec![(ARTIFACT_ENTRY, generate_artifact_xml(&artifact)?)];
+    if !request.content_tags.is_empty() {
+        extra_metadata.push((
+            CONTENT_TAGS_ENTRY,
+            generate_content_tags_xml(&request.content_tags)?,
+        ));
+    }
+    if !transformed.records().is_empty() {
+        extra_metadata.push((
+            TRANSFORMS_ENTRY,
+            generate_transforms_xml(transformed.records())?,
+        ));
+    }
+    if let Some(relationships) = &relationships {
+        extra_metadata.push((RELATIONSHIPS_ENTRY, relationships.to_json()));
+    }
+    let sbom = match &request.sbom {
+        Some(options) => {
+            let sbom = build_sbom(
+                content,
+                source_package,
+                &request.setup_file,
+                setup_info.as_ref(),
+                &blocklist_matches,
+                artifact.created,
+            )?;
+            if options.embed {
+                extra_metadata.push((SBOM_ENTRY, sbom.render(options.format)));
+            }
+            Some(sbom)
+        }
+        None => None,
+    };
 
-    progress.finish_with_message("Done!");
+    // Create outer ZIP (final .intunewin file)
+    let write_span = info_span!("write").entered();
+    let encrypted_content = EncryptedContent {
+        reader: &mut encrypted.open()?,
+        size: encrypted_size,
+    };
+    let package_size = if let Output::Writer(writer) = output {
+        let write_error = |e: io::Error| PackageError::output_write(output_path.clone(), e);
+        let start = writer.stream_position().map_err(write_error)?;
+        write_outer_zip(
+            &mut *writer,
+            &detection_xml,
+            &extra_metadata,
+            encrypted_content,
+        )?;
+        ByteSize(writer.stream_position().map_err(write_error)? - start)
+    } else if to_stdout {
+        // ZIP writing seeks, so spool the package before streaming it out
+        let mut package = SpoolFile::new(workspace.as_ref(), "package.intunewin");
+        package.write(|writer| {
+            write_outer_zip(writer, &detection_xml, &extra_metadata, encrypted_content)
+        })?;
 
-    let package_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        let mut stdout = io::stdout().lock();
+        let size = io::copy(&mut package.open()?, &mut stdout)
+            .and_then(|size| stdout.flush().map(|_| size))
+            .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+        ByteSize(size)
+    } else {
+        write_atomically(&output_path, &request.io_retry, |writer| {
+            write_outer_zip(writer, &detection_xml, &extra_metadata, encrypted_content)
+        })?;
+        if let Some(incremental) = &incremental {
+            incremental.save(&output_path, &metadata, request)?;
+        }
+        ByteSize(fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0))
+    };
+    drop(write_span);
+    drop(workspace);
+    progress.add_bytes_written(package_size.bytes());
+    let stats = progress.finish("Done!");
+    info!(
+        path = %output_path.display(),
+        bytes = package_size.bytes(),
+        "Wrote package"
+    );
+
+    let setup_manifest = match &setup_info {
+        Some(info) if request.setup_manifest => {
+            let path = output_path.with_extension(SETUP_MANIFEST_EXTENSION);
+            fs::write(&path, info.to_manifest_json(&request.setup_file))
+                .map_err(|e| PackageError::output_write(path.clone(), e))?;
+            Some(path)
+        }
+        _ => None,
+    };
+    let sbom_path = match (&sbom, &request.sbom) {
+        (Some(sbom), Some(options)) if options.sidecar => Some(self::sbom::write_sidecar(
+            sbom,
+            options.format,
+            &output_path,
+        )?),
+        _ => None,
+    };
+
+    // Prove the artifact can be read back
+    let validation = match request.post_validate {
+        Some(timeout) => Some(
+            info_span!("validate")
+                .in_scope(|| validate_with_timeout(&output_path, timeout, validate_intunewin))?,
+        ),
+        None => None,
+    };
 
     Ok(IntuneWinPackage {
         output_path,
         metadata,
+        artifact,
         package_size,
         creation_time: start_time.elapsed(),
+        blocklist_matches,
+        content_tags: request.content_tags.clone(),
+        relationships,
+        file_count: source_package.file_count(),
+        dir_count: source_package.dir_count(),
+        validation,
+        transforms: transformed.records().to_vec(),
+        sparse_files: source_package.sparse_files.clone(),
+        skipped_entries: source_package.skipped_entries.clone(),
+        path_issues: source_package.path_issues.clone(),
+        size_estimate,
+        stats,
+        setup_info,
+        setup_manifest,
+        sbom,
+        sbom_path,
+        cache: cache.map(|c| c.stats()),
+        incremental: outcome,
     })
 }
 
-fn create_progress_bar(source: &SourcePackage, verbosity: Verbosity) -> ProgressBar {
-    if verbosity.suppress_output() {
-        return ProgressBar::hidden();
-    }
+/// The result of an incremental build that kept the previous package,
+/// whose source and settings are unchanged.
+///
+/// Nothing is written; files a full build writes next to the package are
+/// left as the previous build wrote them.
+fn previous_package(
+    request: &PackageRequest,
+    incremental: &Incremental,
+    start_time: Instant,
+    preflight: Preflight<'_>,
+) -> PackageResult<IntuneWinPackage> {
+    let (path, metadata) = incremental.previous()?;
+    let artifact = incremental
+        .previous_artifact()?
+        .unwrap_or_else(|| ArtifactMetadata::new(incremental.manifest_sha256.clone()));
+    let source = preflight.content.source();
+    let setup_manifest = Some(path.with_extension(SETUP_MANIFEST_EXTENSION))
+        .filter(|path| request.setup_manifest && path.is_file());
+    info!(path = %path.display(), "Kept previous package");
 
-    let pb = ProgressBar::new(source.file_count() as u64);
-
-    if verbosity.show_progress() {
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-        );
-    }
+    Ok(IntuneWinPackage {
+        output_path: path.to_path_buf(),
+        metadata: metadata.clone(),
+        artifact,
+        package_size: ByteSize(fs::metadata(path).map(|m| m.len()).unwrap_or(0)),
+        creation_time: start_time.elapsed(),
+        blocklist_matches: preflight.blocklist_matches,
+        content_tags: request.content_tags.clone(),
+        relationships: preflight.relationships,
+        file_count: source.file_count(),
+        dir_count: source.dir_count(),
+        validation: None,
+        transforms: preflight.content.records().to_vec(),
+        sparse_files: source.sparse_files.clone(),
+        skipped_entries: source.skipped_entries.clone(),
+        path_issues: source.path_issues.clone(),
+        size_estimate: preflight.size_estimate,
+        stats: ProgressStats::default(),
+        setup_info: preflight.setup_info,
+        setup_manifest,
+        sbom: None,
+        sbom_path: None,
+        cache: None,
+        incremental: Some(IncrementalOutcome::Skipped),
+    })
+}
 
-    pb
+/// Content collected and checked against a request, before anything is
+/// compressed.
+struct Preflight<'a> {
+    content: TransformedContent<'a>,
+    size_estimate: SizeEstimate,
+    blocklist_matches: Vec<BlocklistMatch>,
+    setup_info: Option<ExeVersionInfo>,
+    relationships: Option<AppRelationships>,
 }
 
-fn create_inner_zip(source: &SourcePackage, progress: &ProgressBar) -> PackageResult<Vec<u8>> {
-    let mut buffer = Vec::new();
-    {
-        let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
-        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+/// Collect `content` and run the checks that need no output: strict-mode
+/// checks, source limits, the projected size and the blocklist, and load
+/// the relationships file. The path report is written only with
+/// `write_reports`.
+fn preflight<'a>(
+    request: &PackageRequest,
+    content: &'a dyn ContentProvider,
+    write_reports: bool,
+) -> PackageResult<Preflight<'a>> {
+    // Collect source files, rewriting those matched by transform rules
+    let transformed = TransformedContent::apply(
+        content,
+        &request.transforms,
+        content.collect(&request.setup_file, &request.filter)?,
+    )?;
+    let source_package = transformed.source();
 
-        for file in &source.files {
-            let full_path = source.root.join(&file.relative_path);
+    if source_package.files.is_empty() {
+        return Err(PackageError::SourceFolderEmpty {
+            path: transformed.root().to_path_buf(),
+        });
+    }
+    check_skipped(&request.strictness, &source_package.skipped_entries)?;
+    if let (true, Some(report)) = (write_reports, &request.path_report) {
+        fs::write(report, path_report_json(&source_package.path_issues))
+            .map_err(|e| PackageError::output_write(report.clone(), e))?;
+    }
+    check_path_issues(&request.strictness, &source_package.path_issues)?;
+    info!(
+        source = %transformed.root().display(),
+        files = source_package.file_count(),
+        bytes = source_package.total_size,
+        "Collected source files"
+    );
+
+    // Guard against accidental monster packages
+    if !request.force {
+        request.source_limits.check(
+            transformed.root(),
+            source_package.file_count(),
+            source_package.total_size,
+        )?;
+    }
 
-            // Use forward slashes for ZIP paths (cross-platform)
-            let zip_path = file.relative_path.to_string_lossy().replace('\\', "/");
+    // Intune rejects oversized packages only after the upload, so check the
+    // projected size before spending time on compression
+    let size_estimate = SizeEstimate::for_source(source_package, request.size_limit);
+    if size_estimate.exceeds_limit() {
+        request.strictness.enforce(Check::PackageSize, || {
+            format!(
+                "The package may reach {}, above the limit of {}",
+                size_estimate.projected, size_estimate.limit
+            )
+        })?;
+    }
 
-            progress.set_message(format!("Adding {}", zip_path));
+    // Check content against the known-bad hash list
+    let blocklist_matches = match &request.blocklist {
+        Some(blocklist_path) => check_blocklist(
+            blocklist_path,
+            request.blocklist_action.under(&request.strictness),
+            &transformed,
+            source_package
+                .files
+                .iter()
+                .map(|f| f.relative_path.as_path()),
+        )?,
+        None => Vec::new(),
+    };
+
+    // Version information is only a summary, so unreadable resources are
+    // not an error
+    let setup_info = read_setup_info(&transformed, &request.setup_file);
+
+    let relationships = request
+        .relationships
+        .as_deref()
+        .map(load_relationships)
+        .transpose()?;
+
+    Ok(Preflight {
+        content: transformed,
+        size_estimate,
+        blocklist_matches,
+        setup_info,
+        relationships,
+    })
+}
 
-            zip.start_file(&zip_path, options)
-                .map_err(|e| PackageError::ZipError {
-                    reason: e.to_string(),
-                })?;
+/// Read the version information of an `.exe` setup file.
+fn read_setup_info(content: &dyn ContentProvider, setup_file: &str) -> Option<ExeVersionInfo> {
+    if !setup_file.to_ascii_lowercase().ends_with(".exe") {
+        return None;
+    }
+    let mut reader = content.open(Path::new(setup_file)).ok()?;
+    pe::read_version_info(&mut reader)
+        .ok()
+        .flatten()
+        .filter(|info| !info.is_empty())
+}
 
-            let content = fs::read(&full_path).map_err(|e| PackageError::SourceReadError {
-                path: full_path.clone(),
-                reason: e.to_string(),
-            })?;
+/// List the packaged files, the products among them and the blocklist
+/// matches kept with a warning, named after the setup file's product when
+/// it has version information.
+fn build_sbom(
+    content: &dyn ContentProvider,
+    source: &SourcePackage,
+    setup_file: &str,
+    setup_info: Option<&ExeVersionInfo>,
+    blocklist_matches: &[BlocklistMatch],
+    created: u64,
+) -> PackageResult<Sbom> {
+    let name = setup_info
+        .and_then(|info| info.product_name.clone())
+        .unwrap_or_else(|| {
+            Path::new(setup_file)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| setup_file.to_string())
+        });
+    let mut sbom = Sbom::new(name, created)
+        .with_version(setup_info.and_then(|info| info.product_version.clone()))
+        .with_supplier(setup_info.and_then(|info| info.company_name.clone()));
+    self::sbom::scan_content(
+        &mut sbom,
+        content,
+        source.files.iter().map(|f| f.relative_path.clone()),
+        Path::new(""),
+    )?;
+    self::sbom::add_blocklist_matches(&mut sbom, blocklist_matches, Path::new(""));
+    Ok(sbom)
+}
 
-            zip.write_all(&content)
-                .map_err(|e| PackageError::ZipError {
-                    reason: e.to_string(),
-                })?;
+/// Write the inner ZIP to `writer` and return the content's manifest hash.
+fn create_inner_zip<W: Write + Seek>(
+    source: &SourcePackage,
+    content: &dyn ContentProvider,
+    compression: &Compression,
+    progress: &Progress,
+    cache: Option<&mut ContentCache>,
+    writer: W,
+) -> PackageResult<String> {
+    let mut zip = ZipWriter::new(writer);
+    let options = entry_options(zip::CompressionMethod::Deflated);
+
+    // Directory entries first so empty directories are restored on extract
+    for dir in &source.directories {
+        let zip_path = format!("{}/", dir.to_string_lossy().replace('\\', "/"));
+        zip.add_directory(&zip_path, options)
+            .map_err(|e| PackageError::zip(ZipStage::WriteContent, Some(&zip_path), e))?;
+    }
 
-            progress.inc(1);
-        }
+    let manifest_sha256 =
+        write_content_files(&mut zip, source, content, "", compression, progress, cache)?;
 
-        zip.finish().map_err(|e| PackageError::ZipError {
-            reason: e.to_string(),
-        })?;
-    }
+    zip.finish()
+        .and_then(|mut writer| writer.flush().map_err(Into::into))
+        .map_err(|e| PackageError::zip(ZipStage::WriteContent, None, e))?;
+    Ok(manifest_sha256)
+}
 
-    Ok(buffer)
+/// The encrypted content, read while writing the outer ZIP.
+struct EncryptedContent<'a> {
+    reader: &'a mut dyn IoRead,
+    size: u64,
 }
 
-fn create_outer_zip(
-    output_path: &Path,
+/// Write the outer ZIP; `extra_metadata` holds optional files (entry, XML)
+/// written after Detection.xml.
+fn write_outer_zip<W: Write + Seek>(
+    writer: W,
     detection_xml: &str,
-    encrypted_content: &[u8],
+    extra_metadata: &[(&str, String)],
+    encrypted_content: EncryptedContent<'_>,
 ) -> PackageResult<()> {
-    let file = File::create(output_path).map_err(|e| PackageError::OutputWriteError {
-        path: output_path.to_path_buf(),
-        reason: e.to_string(),
-    })?;
+    let mut zip = ZipWriter::new(writer);
+    let options = entry_options(zip::CompressionMethod::Stored);
 
-    let mut zip = ZipWriter::new(BufWriter::new(file));
-    let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
-
-    // Add encrypted content first (matches Microsoft file order)
-    zip.start_file("IntuneWinPackage/Contents/IntunePackage.intunewin", options)
-        .map_err(|e| PackageError::ZipError {
-            reason: e.to_string(),
-        })?;
-    zip.write_all(encrypted_content)
-        .map_err(|e| PackageError::ZipError {
-            reason: e.to_string(),
-        })?;
+    // Add encrypted content first (matches Microsoft file order); past
+    // 4 GiB its sizes and the offsets after it need ZIP64 records
+    let content_options = options.large_file(encrypted_content.size >= u32::MAX as u64);
+    zip.start_file(CONTENT_ENTRY, content_options)
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(CONTENT_ENTRY), e))?;
+    io::copy(encrypted_content.reader, &mut zip)
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(CONTENT_ENTRY), e))?;
 
     // Add Detection.xml second
-    zip.start_file("IntuneWinPackage/Metadata/Detection.xml", options)
-        .map_err(|e| PackageError::ZipError {
-            reason: e.to_string(),
-        })?;
+    zip.start_file(DETECTION_ENTRY, options)
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(DETECTION_ENTRY), e))?;
     zip.write_all(detection_xml.as_bytes())
-        .map_err(|e| PackageError::ZipError {
-            reason: e.to_string(),
-        })?;
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(DETECTION_ENTRY), e))?;
+
+    // Add our own metadata last so Intune-relevant entries keep their order
+    for (entry, xml) in extra_metadata {
+        zip.start_file(*entry, options)
+            .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(entry), e))?;
+        zip.write_all(xml.as_bytes())
+            .map_err(|e| PackageError::zip(ZipStage::WritePackage, Some(entry), e))?;
+    }
 
-    zip.finish().map_err(|e| PackageError::ZipError {
-        reason: e.to_string(),
-    })?;
+    zip.finish()
+        .and_then(|mut writer| writer.flush().map_err(Into::into))
+        .map_err(|e| PackageError::zip(ZipStage::WritePackage, None, e))?;
 
     Ok(())
 }
 
+/// Intermediate data written by one packaging stage and read by the next.
+enum SpoolFile {
+    /// A file in the run workspace
+    Disk(PathBuf),
+    /// A buffer, when packaging must not touch the filesystem
+    Memory(Vec<u8>),
+}
+
+impl SpoolFile {
+    /// A file named `name` in `workspace`, or a buffer without one.
+    fn new(workspace: Option<&StagingDir>, name: &str) -> Self {
+        match workspace {
+            Some(workspace) => SpoolFile::Disk(workspace.path.join(name)),
+            None => SpoolFile::Memory(Vec::new()),
+        }
+    }
+
+    /// Replace the data with what `write` produces.
+    fn write<T>(
+        &mut self,
+        write: impl FnOnce(&mut dyn WriteSeek) -> PackageResult<T>,
+    ) -> PackageResult<T> {
+        match self {
+            SpoolFile::Disk(path) => {
+                let mut file = File::create(&*path)
+                    .map(BufWriter::new)
+                    .map_err(|e| spool_error(path, e))?;
+                let result = write(&mut file)?;
+                file.flush().map_err(|e| spool_error(path, e))?;
+                Ok(result)
+            }
+            SpoolFile::Memory(buffer) => {
+                let mut cursor = Cursor::new(std::mem::take(buffer));
+                let result = write(&mut cursor);
+                *buffer = cursor.into_inner();
+                result
+            }
+        }
+    }
+
+    /// Read the data from the start.
+    fn open(&self) -> PackageResult<Box<dyn ReadSeek + '_>> {
+        match self {
+            SpoolFile::Disk(path) => File::open(path)
+                .map(|file| Box::new(BufReader::new(file)) as Box<dyn ReadSeek>)
+                .map_err(|e| spool_error(path, e)),
+            SpoolFile::Memory(buffer) => Ok(Box::new(Cursor::new(buffer.as_slice()))),
+        }
+    }
+
+    fn size(&self) -> PackageResult<u64> {
+        match self {
+            SpoolFile::Disk(path) => fs::metadata(path)
+                .map(|m| m.len())
+                .map_err(|e| spool_error(path, e)),
+            SpoolFile::Memory(buffer) => Ok(buffer.len() as u64),
+        }
+    }
+
+    /// Free the data once no later stage needs it.
+    fn remove(self) -> PackageResult<()> {
+        match self {
+            SpoolFile::Disk(path) => fs::remove_file(&path).map_err(|e| spool_error(&path, e)),
+            SpoolFile::Memory(_) => Ok(()),
+        }
+    }
+
+    fn error(&self, error: io::Error) -> PackageError {
+        match self {
+            SpoolFile::Disk(path) => spool_error(path, error),
+            SpoolFile::Memory(_) => spool_error(Path::new("<memory>"), error),
+        }
+    }
+}
+
+fn spool_error(path: &Path, error: io::Error) -> PackageError {
+    PackageError::output_write(path.to_path_buf(), error)
+}
+
 /// Unpack an IntuneWin package to extract the original files.
 pub fn unpack(request: &UnpackRequest) -> PackageResult<UnpackResult> {
     let start_time = Instant::now();
+    let _span = info_span!("unpack", input = %request.input_file.display()).entered();
 
     // Validate request
     request.validate()?;
 
     // Create output folder if needed
     if !request.output_folder.exists() {
-        fs::create_dir_all(&request.output_folder).map_err(|e| {
-            PackageError::OutputFolderCreationFailed {
-                path: request.output_folder.clone(),
-                reason: e.to_string(),
-            }
-        })?;
+        fs::create_dir_all(&request.output_folder)
+            .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
     }
 
     // Open the outer ZIP
@@ -225,86 +943,177 @@ pub fn unpack(request: &UnpackRequest) -> PackageResult<UnpackResult> {
     // Extract and parse Detection.xml
     let metadata = extract_detection_metadata(&mut archive, &request.input_file)?;
 
-    // Extract encrypted content
-    let encrypted_content = extract_encrypted_content(&mut archive, &request.input_file)?;
+    // Content tags are optional (absent in packages from other tools)
+    let content_tags = extract_content_tags(&mut archive, &request.input_file)?;
 
     // Create progress bar
-    let progress = create_unpack_progress_bar(request.verbosity);
+    let progress = Progress::spinner(request.verbosity);
+    #[cfg(feature = "async")]
+    let progress = progress.with_cancellation(request.cancellation.clone());
+    progress.check_cancelled()?;
     progress.set_message("Decrypting...");
 
-    // Decrypt the inner ZIP
-    let decrypted_content = decrypt_content(&encrypted_content, &metadata.encryption_info)?;
-
-    progress.set_message("Extracting files...");
-
-    // Extract inner ZIP to output folder
-    let (file_count, total_size) =
-        extract_inner_zip(&decrypted_content, &request.output_folder, &progress)?;
+    // Check the HMAC and decrypt the inner ZIP into the run workspace, so
+    // neither the encrypted nor the decrypted content is held in memory.
+    // A protected archive must not leave plaintext on disk, so its content
+    // is decrypted into memory instead.
+    let protected = match (request.archive_path(), &request.zip_password) {
+        (Some(archive), Some(password)) => Some((archive, password)),
+        _ => None,
+    };
+    let workspace = match protected {
+        Some(_) => None,
+        None => Some(StagingDir::create_with_space(
+            "unpack",
+            metadata.unencrypted_content_size,
+        )?),
+    };
+    let mut inner = SpoolFile::new(workspace.as_ref(), "content.zip");
+    let encrypted_size = info_span!("decrypt").in_scope(|| {
+        inner.write(|writer| {
+            decrypt_encrypted_content(
+                &mut archive,
+                &request.input_file,
+                &metadata.encryption_info,
+                writer,
+            )
+        })
+    })?;
+    progress.add_bytes_read(encrypted_size);
+    progress.check_cancelled()?;
+
+    // Extract inner ZIP to output folder, or re-encrypt it into an archive
+    let (file_count, dir_count, total_size) = match &protected {
+        Some((archive, password)) => {
+            progress.set_message("Writing protected archive...");
+            info_span!("extract", archive = %archive.display()).in_scope(|| {
+                write_protected_zip(inner.open()?, archive, password.expose(), &progress)
+            })?
+        }
+        None => {
+            progress.set_message("Extracting files...");
+            info_span!("extract")
+                .in_scope(|| extract_inner_zip(inner.open()?, &request.output_folder, &progress))?
+        }
+    };
+    let archive = protected.map(|(archive, _)| archive);
 
-    progress.finish_with_message("Done!");
+    let stats = progress.finish("Done!");
+    info!(
+        path = %archive.as_deref().unwrap_or(&request.output_folder).display(),
+        files = file_count,
+        bytes = total_size,
+        "Unpacked package"
+    );
 
     Ok(UnpackResult {
         output_folder: request.output_folder.clone(),
         file_count,
-        total_size,
+        dir_count,
+        total_size: ByteSize(total_size),
         unpack_time: start_time.elapsed(),
         setup_file: metadata.setup_file,
+        content_tags,
+        archive,
+        stats,
     })
 }
 
-fn create_unpack_progress_bar(verbosity: Verbosity) -> ProgressBar {
-    if verbosity.suppress_output() {
-        return ProgressBar::hidden();
-    }
-
-    let pb = ProgressBar::new_spinner();
+pub(crate) fn extract_detection_metadata<R: IoRead + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    input_path: &Path,
+) -> PackageResult<DetectionMetadata> {
+    let mut detection_file =
+        archive
+            .by_name(DETECTION_ENTRY)
+            .map_err(|e| PackageError::InvalidIntunewinFile {
+                path: input_path.to_path_buf(),
+                reason: format!("Missing Detection.xml: {}", e),
+            })?;
 
-    if verbosity.show_progress() {
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} [{elapsed_precise}] {msg}")
-                .unwrap(),
-        );
-    }
+    let mut xml_content = String::new();
+    detection_file
+        .read_to_string(&mut xml_content)
+        .map_err(|e| PackageError::InvalidIntunewinFile {
+            path: input_path.to_path_buf(),
+            reason: format!("Failed to read Detection.xml: {}", e),
+        })?;
 
-    pb
+    parse_detection_xml(&xml_content)
 }
 
-fn extract_detection_metadata<R: IoRead + std::io::Seek>(
+/// Read the content tags file, returning no tags when it is absent.
+pub fn extract_content_tags<R: IoRead + std::io::Seek>(
     archive: &mut ZipArchive<R>,
     input_path: &Path,
-) -> PackageResult<DetectionMetadata> {
-    let mut detection_file = archive
-        .by_name("IntuneWinPackage/Metadata/Detection.xml")
+) -> PackageResult<Vec<(String, String)>> {
+    let mut tags_file = match archive.by_name(CONTENT_TAGS_ENTRY) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(PackageError::InvalidIntunewinFile {
+                path: input_path.to_path_buf(),
+                reason: format!("Failed to open content tags: {}", e),
+            });
+        }
+    };
+
+    let mut xml_content = String::new();
+    tags_file
+        .read_to_string(&mut xml_content)
         .map_err(|e| PackageError::InvalidIntunewinFile {
             path: input_path.to_path_buf(),
-            reason: format!("Missing Detection.xml: {}", e),
+            reason: format!("Failed to read content tags: {}", e),
         })?;
 
+    parse_content_tags_xml(&xml_content)
+}
+
+/// Read the artifact record, returning `None` when it is absent.
+pub fn extract_artifact_metadata<R: IoRead + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    input_path: &Path,
+) -> PackageResult<Option<ArtifactMetadata>> {
+    let mut artifact_file = match archive.by_name(ARTIFACT_ENTRY) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => {
+            return Err(PackageError::InvalidIntunewinFile {
+                path: input_path.to_path_buf(),
+                reason: format!("Failed to open artifact record: {}", e),
+            });
+        }
+    };
+
     let mut xml_content = String::new();
-    detection_file
+    artifact_file
         .read_to_string(&mut xml_content)
         .map_err(|e| PackageError::InvalidIntunewinFile {
             path: input_path.to_path_buf(),
-            reason: format!("Failed to read Detection.xml: {}", e),
+            reason: format!("Failed to read artifact record: {}", e),
         })?;
 
-    parse_detection_xml(&xml_content)
+    parse_artifact_xml(&xml_content).map(Some)
 }
 
-fn extract_encrypted_content<R: IoRead + std::io::Seek>(
-    archive: &mut ZipArchive<R>,
+fn open_encrypted_content<'a, R: IoRead + Seek>(
+    archive: &'a mut ZipArchive<R>,
     input_path: &Path,
-) -> PackageResult<Vec<u8>> {
-    let mut content_file = archive
-        .by_name("IntuneWinPackage/Contents/IntunePackage.intunewin")
+) -> PackageResult<zip::read::ZipFile<'a>> {
+    archive
+        .by_name(CONTENT_ENTRY)
         .map_err(|e| PackageError::InvalidIntunewinFile {
             path: input_path.to_path_buf(),
             reason: format!("Missing encrypted content: {}", e),
-        })?;
+        })
+}
 
+pub(crate) fn extract_encrypted_content<R: IoRead + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    input_path: &Path,
+) -> PackageResult<Vec<u8>> {
     let mut encrypted_content = Vec::new();
-    content_file
+    open_encrypted_content(archive, input_path)?
         .read_to_end(&mut encrypted_content)
         .map_err(|e| PackageError::InvalidIntunewinFile {
             path: input_path.to_path_buf(),
@@ -314,63 +1123,613 @@ fn extract_encrypted_content<R: IoRead + std::io::Seek>(
     Ok(encrypted_content)
 }
 
-fn extract_inner_zip(
-    decrypted_content: &[u8],
+/// Check the HMAC of the encrypted content and decrypt it into `output`.
+///
+/// The ZIP entry is read twice, once per step, so only a fixed-size buffer
+/// is held in memory. Returns the size of the encrypted content.
+fn decrypt_encrypted_content<R: IoRead + Seek, W: Write + ?Sized>(
+    archive: &mut ZipArchive<R>,
+    input_path: &Path,
+    encryption_info: &EncryptionInfo,
+    output: &mut W,
+) -> PackageResult<u64> {
+    let size = verify_stream(
+        &mut open_encrypted_content(archive, input_path)?,
+        encryption_info,
+    )?;
+    decrypt_stream(
+        &mut open_encrypted_content(archive, input_path)?,
+        output,
+        encryption_info,
+    )?;
+    Ok(size)
+}
+
+fn extract_inner_zip<R: IoRead + Seek>(
+    decrypted_content: R,
     output_folder: &Path,
-    progress: &ProgressBar,
-) -> PackageResult<(usize, u64)> {
-    let cursor = std::io::Cursor::new(decrypted_content);
-    let mut archive = ZipArchive::new(cursor).map_err(|e| PackageError::DecryptionError {
-        reason: format!("Decrypted content is not a valid ZIP: {}", e),
-    })?;
+    progress: &Progress,
+) -> PackageResult<(usize, usize, u64)> {
+    let mut archive =
+        ZipArchive::new(decrypted_content).map_err(|e| PackageError::DecryptionError {
+            reason: format!("Decrypted content is not a valid ZIP: {}", e),
+        })?;
 
     let mut file_count = 0;
+    let mut dir_count = 0;
     let mut total_size = 0u64;
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| PackageError::ZipError {
-            reason: format!("Failed to read file from archive: {}", e),
-        })?;
+        progress.check_cancelled()?;
+        check_entry_method(&mut archive, i)?;
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| PackageError::zip(ZipStage::ReadContent, Some(&format!("#{}", i)), e))?;
 
         let file_name = file.name().to_string();
+        let output_path = contained_path(output_folder, &file_name)?;
 
-        // Skip directories
+        // Restore directories (including empty ones)
         if file_name.ends_with('/') {
+            let dir_path = output_path;
+            fs::create_dir_all(&dir_path)
+                .map_err(|e| PackageError::output_folder(dir_path.clone(), e))?;
+            dir_count += 1;
             continue;
         }
 
-        progress.set_message(format!("Extracting {}", file_name));
-
-        let output_path = output_folder.join(&file_name);
+        progress.set_message_with(|| format!("Extracting {}", file_name));
 
         // Create parent directories if needed
         if let Some(parent) = output_path.parent() {
             if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| {
-                    PackageError::OutputFolderCreationFailed {
-                        path: parent.to_path_buf(),
-                        reason: e.to_string(),
-                    }
-                })?;
+                fs::create_dir_all(parent)
+                    .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
             }
         }
 
         // Extract file
-        let mut outfile =
-            File::create(&output_path).map_err(|e| PackageError::OutputWriteError {
-                path: output_path.clone(),
-                reason: e.to_string(),
-            })?;
+        let mut outfile = File::create(&output_path)
+            .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
 
-        let bytes_written =
-            std::io::copy(&mut file, &mut outfile).map_err(|e| PackageError::OutputWriteError {
-                path: output_path.clone(),
-                reason: e.to_string(),
-            })?;
+        let bytes_written = std::io::copy(&mut file, &mut outfile)
+            .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
 
+        progress.file_done(0);
+        progress.add_bytes_written(bytes_written);
         file_count += 1;
         total_size += bytes_written;
     }
 
-    Ok((file_count, total_size))
+    Ok((file_count, dir_count, total_size))
+}
+
+/// Fail with a clear message when entry `index` of the inner ZIP uses a
+/// compression method this build cannot read, such as Zstandard (method
+/// 93) without the `zstd` feature.
+fn check_entry_method<R: IoRead + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+) -> PackageResult<()> {
+    let entry = archive
+        .by_index_raw(index)
+        .map_err(|e| PackageError::zip(ZipStage::ReadContent, Some(&format!("#{}", index)), e))?;
+
+    #[allow(deprecated)]
+    let zip::CompressionMethod::Unsupported(method) = entry.compression() else {
+        return Ok(());
+    };
+    let reason = match method {
+        ZSTD_METHOD => {
+            "compressed with Zstandard, which needs a build with the 'zstd' feature".to_string()
+        }
+        other => format!("unsupported compression method {}", other),
+    };
+    Err(PackageError::ZipError {
+        stage: ZipStage::ReadContent,
+        entry: Some(entry.name().to_string()),
+        reason,
+        source: None,
+    })
+}
+
+/// Largest entry a password-protected archive can hold without ZIP64.
+const MAX_PROTECTED_ENTRY_SIZE: u64 = u32::MAX as u64;
+
+/// Copy the entries of the decrypted inner ZIP into an AES-encrypted ZIP
+/// at `archive_path`. Entry names are checked like [`extract_inner_zip`]
+/// would; a partial archive is removed on failure.
+fn write_protected_zip<R: IoRead + Seek>(
+    decrypted_content: R,
+    archive_path: &Path,
+    password: &str,
+    progress: &Progress,
+) -> PackageResult<(usize, usize, u64)> {
+    let mut archive =
+        ZipArchive::new(decrypted_content).map_err(|e| PackageError::DecryptionError {
+            reason: format!("Decrypted content is not a valid ZIP: {}", e),
+        })?;
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(archive_path)
+        .map_err(|e| PackageError::output_write(archive_path.to_path_buf(), e))?;
+    let writer = protected_zip::AesZipWriter::new(BufWriter::new(file), password);
+
+    let result = write_protected_entries(&mut archive, writer, archive_path, progress);
+    if result.is_err() {
+        let _ = fs::remove_file(archive_path);
+    }
+    result
+}
+
+fn write_protected_entries<R: IoRead + Seek>(
+    archive: &mut ZipArchive<R>,
+    mut writer: protected_zip::AesZipWriter<BufWriter<File>>,
+    archive_path: &Path,
+    progress: &Progress,
+) -> PackageResult<(usize, usize, u64)> {
+    let mut file_count = 0;
+    let mut dir_count = 0;
+    let mut total_size = 0u64;
+
+    for i in 0..archive.len() {
+        progress.check_cancelled()?;
+        check_entry_method(archive, i)?;
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| PackageError::zip(ZipStage::ReadContent, Some(&format!("#{}", i)), e))?;
+        let name = entry.name().to_string();
+        let relative = sanitize_entry_name(&name)?;
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        let archive_name = relative.to_string_lossy().replace('\\', "/");
+
+        if name.ends_with('/') {
+            writer
+                .add_directory(
+                    &format!("{}/", archive_name),
+                    entry.last_modified(),
+                    entry.unix_mode(),
+                )
+                .map_err(|e| PackageError::zip(ZipStage::WriteArchive, Some(&name), e))?;
+            dir_count += 1;
+            continue;
+        }
+
+        // The sizes come from the package, so they only bound the read and
+        // are never used to preallocate
+        if entry.size() > MAX_PROTECTED_ENTRY_SIZE {
+            return Err(PackageError::ZipError {
+                stage: ZipStage::WriteArchive,
+                entry: Some(name),
+                reason: "exceeds 4 GiB, too large for a password-protected archive".to_string(),
+                source: None,
+            });
+        }
+        progress.set_message_with(|| format!("Archiving {}", name));
+        let mut data = Vec::new();
+        (&mut entry)
+            .take(MAX_PROTECTED_ENTRY_SIZE + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| PackageError::zip(ZipStage::ReadContent, Some(&name), e))?;
+        writer
+            .add_file(
+                &archive_name,
+                &data,
+                entry.last_modified(),
+                entry.unix_mode(),
+            )
+            .map_err(|e| PackageError::zip(ZipStage::WriteArchive, Some(&name), e))?;
+
+        progress.file_done(0);
+        progress.add_bytes_written(data.len() as u64);
+        file_count += 1;
+        total_size += data.len() as u64;
+    }
+
+    writer
+        .finish()
+        .map_err(|e| PackageError::zip(ZipStage::WriteArchive, None, e))?
+        .into_inner()
+        .map_err(|e| PackageError::output_write(archive_path.to_path_buf(), e.into_error()))?;
+
+    Ok((file_count, dir_count, total_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::strictness::Strictness;
+    use crate::packager::encrypt::decrypt_content;
+
+    /// Seekable writer that only keeps the start and end of its output.
+    #[derive(Default)]
+    struct LargeOutput {
+        position: u64,
+        head: Vec<u8>,
+        tail: Vec<u8>,
+    }
+
+    const HEAD_SIZE: u64 = 64 * 1024;
+    const TAIL_START: u64 = 1 << 32;
+
+    fn keep(buffer: &mut Vec<u8>, offset: usize, data: &[u8]) {
+        if buffer.len() < offset + data.len() {
+            buffer.resize(offset + data.len(), 0);
+        }
+        buffer[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    impl Write for LargeOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let end = self.position + buf.len() as u64;
+            if self.position < HEAD_SIZE {
+                let len = (end.min(HEAD_SIZE) - self.position) as usize;
+                keep(&mut self.head, self.position as usize, &buf[..len]);
+            }
+            if end > TAIL_START {
+                let skip = TAIL_START.saturating_sub(self.position) as usize;
+                let offset = (self.position + skip as u64 - TAIL_START) as usize;
+                keep(&mut self.tail, offset, &buf[skip..]);
+            }
+            self.position = end;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for LargeOutput {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            let len = TAIL_START + self.tail.len() as u64;
+            self.position = match pos {
+                io::SeekFrom::Start(offset) => offset,
+                io::SeekFrom::Current(delta) => self.position.saturating_add_signed(delta),
+                io::SeekFrom::End(delta) => len.saturating_add_signed(delta),
+            };
+            Ok(self.position)
+        }
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    #[ignore = "streams 4 GiB; run with --ignored"]
+    fn test_outer_zip_over_4gib_uses_zip64() {
+        let size = u32::MAX as u64 + 1;
+        let mut output = LargeOutput::default();
+        write_outer_zip(
+            &mut output,
+            "<ApplicationInfo/>",
+            &[],
+            EncryptedContent {
+                reader: &mut io::repeat(0x5a).take(size),
+                size,
+            },
+        )
+        .unwrap();
+
+        // Local header of the content carries a ZIP64 extra field
+        assert_eq!(&output.head[..4], b"PK\x03\x04");
+        assert_eq!(&output.head[18..26], &[0xff; 8]);
+        // Central directory sits past 4 GiB and ends with ZIP64 records
+        assert!(contains(&output.tail, b"PK\x01\x02"));
+        assert!(contains(&output.tail, b"PK\x06\x06"));
+        assert!(contains(&output.tail, b"PK\x06\x07"));
+        assert!(contains(&output.tail, DETECTION_ENTRY.as_bytes()));
+    }
+
+    #[test]
+    fn test_outer_zip_small_content_has_no_zip64() {
+        let mut buffer = io::Cursor::new(Vec::new());
+        let mut data = &b"encrypted"[..];
+        write_outer_zip(
+            &mut buffer,
+            "<ApplicationInfo/>",
+            &[],
+            EncryptedContent {
+                reader: &mut data,
+                size: 9,
+            },
+        )
+        .unwrap();
+        let bytes = buffer.into_inner();
+        assert!(!contains(&bytes, b"PK\x06\x06"));
+
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        let mut entry = archive.by_name(CONTENT_ENTRY).unwrap();
+        let mut read = Vec::new();
+        entry.read_to_end(&mut read).unwrap();
+        assert_eq!(read, b"encrypted");
+    }
+
+    #[test]
+    fn test_package_to_writer() {
+        let content = content::MemoryContent::from_files(
+            "<memory>",
+            vec![
+                ("setup.exe", b"setup".to_vec()),
+                ("config/app.json", b"{}".to_vec()),
+            ],
+        )
+        .unwrap();
+        let mut builder = PackageRequest::builder();
+        builder.setup_file("setup.exe");
+        builder
+            .options()
+            .set_verbosity(crate::models::Verbosity::Silent);
+        let request = builder.build_for_content().unwrap();
+
+        let mut output = io::Cursor::new(Vec::new());
+        let result = package_to_writer(&request, &content, &mut output).unwrap();
+        let bytes = output.into_inner();
+        assert_eq!(result.package_size.bytes(), bytes.len() as u64);
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.output_path, PathBuf::from("setup.intunewin"));
+
+        let path = Path::new("<memory>");
+        let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+        let metadata = extract_detection_metadata(&mut archive, path).unwrap();
+        let encrypted = extract_encrypted_content(&mut archive, path).unwrap();
+        let inner = decrypt_content(&encrypted, &metadata.encryption_info).unwrap();
+        let mut inner = ZipArchive::new(io::Cursor::new(inner)).unwrap();
+        let mut data = String::new();
+        inner
+            .by_name("config/app.json")
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "{}");
+
+        // Files written next to the package cannot be
+        builder.options().set_setup_manifest(true);
+        let request = builder.build_for_content().unwrap();
+        assert!(matches!(
+            package_to_writer(&request, &content, &mut io::Cursor::new(Vec::new())),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_package_with_provided_keys() {
+        use crate::models::encryption::{EncryptionOptions, ProvidedKeys};
+
+        let content =
+            content::MemoryContent::from_files("<memory>", vec![("setup.exe", b"setup".to_vec())])
+                .unwrap();
+        let keys = ProvidedKeys {
+            key: [7; 32],
+            mac_key: [9; 32],
+            iv: Some([3; 16]),
+        };
+        let mut builder = PackageRequest::builder();
+        builder.setup_file("setup.exe");
+        builder
+            .options()
+            .set_verbosity(crate::models::Verbosity::Silent)
+            .set_encryption(EncryptionOptions::provided(keys.clone()));
+        let request = builder.build_for_content().unwrap();
+
+        let mut output = io::Cursor::new(Vec::new());
+        let result = package_to_writer(&request, &content, &mut output).unwrap();
+        let info = &result.metadata.encryption_info;
+        assert_eq!(
+            (info.encryption_key, info.mac_key, info.iv),
+            (keys.key, keys.mac_key, [3; 16])
+        );
+
+        // The package decrypts with the keys recorded in Detection.xml
+        let path = Path::new("<memory>");
+        let mut archive = ZipArchive::new(io::Cursor::new(output.into_inner())).unwrap();
+        let metadata = extract_detection_metadata(&mut archive, path).unwrap();
+        let encrypted = extract_encrypted_content(&mut archive, path).unwrap();
+        assert!(decrypt_content(&encrypted, &metadata.encryption_info).is_ok());
+
+        // Reproducible packages derive their own keys
+        builder
+            .options()
+            .set_reproducible(Some(Reproducible::ContentDigest));
+        assert!(matches!(
+            builder.build_for_content(),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_package_with_sha384_digest() {
+        use crate::models::detection::DigestAlgorithm;
+        use crate::models::encryption::EncryptionOptions;
+        use crate::packager::encrypt::compute_digest;
+
+        let content =
+            content::MemoryContent::from_files("<memory>", vec![("setup.exe", b"setup".to_vec())])
+                .unwrap();
+        let request = PackageRequest::new("", "setup.exe", "")
+            .with_verbosity(crate::models::Verbosity::Silent)
+            .with_encryption(
+                EncryptionOptions::default().with_digest_algorithm(DigestAlgorithm::Sha384),
+            );
+        let mut output = io::Cursor::new(Vec::new());
+        package_to_writer(&request, &content, &mut output).unwrap();
+
+        // Detection.xml names the algorithm and holds a digest of its length
+        let path = Path::new("<memory>");
+        let mut archive = ZipArchive::new(io::Cursor::new(output.into_inner())).unwrap();
+        let info = extract_detection_metadata(&mut archive, path)
+            .unwrap()
+            .encryption_info;
+        assert_eq!(info.file_digest_algorithm, DigestAlgorithm::Sha384);
+        let encrypted = extract_encrypted_content(&mut archive, path).unwrap();
+        let decrypted = decrypt_content(&encrypted, &info).unwrap();
+        assert_eq!(
+            info.file_digest,
+            compute_digest(DigestAlgorithm::Sha384, &decrypted)
+        );
+    }
+
+    /// An inner ZIP holding `entries` as stored files.
+    fn crafted_zip(entries: &[&str]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+        for name in entries {
+            zip.start_file(*name, entry_options(zip::CompressionMethod::Stored))
+                .unwrap();
+            zip.write_all(b"evil").unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_extract_inner_zip_rejects_unsafe_paths() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let output = temp.path().join("out");
+        fs::create_dir(&output).unwrap();
+        let progress = Progress::spinner(crate::models::Verbosity::Silent);
+
+        for name in [
+            "../evil.txt",
+            "data/../../evil.txt",
+            "..\\evil.txt",
+            "/tmp/evil.txt",
+            "C:/evil.txt",
+        ] {
+            let zip = crafted_zip(&["ok.txt", name]);
+            assert!(
+                matches!(
+                    extract_inner_zip(Cursor::new(&zip), &output, &progress),
+                    Err(PackageError::UnsafePath { .. })
+                ),
+                "{:?} should be rejected",
+                name
+            );
+        }
+        assert!(!temp.path().join("evil.txt").exists());
+
+        let zip = crafted_zip(&["./data/ok.txt"]);
+        assert_eq!(
+            extract_inner_zip(Cursor::new(&zip), &output, &progress)
+                .unwrap()
+                .0,
+            1
+        );
+        assert_eq!(fs::read(output.join("data/ok.txt")).unwrap(), b"evil");
+    }
+
+    #[test]
+    fn test_protected_zip_ignores_claimed_entry_size() {
+        // Claim a ~4 GiB entry in the central directory
+        let mut zip = crafted_zip(&["data.bin"]);
+        let central = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        zip[central + 24..central + 28].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let archive = temp.path().join("protected.zip");
+        let progress = Progress::spinner(crate::models::Verbosity::Silent);
+        let counts = write_protected_zip(Cursor::new(&zip), &archive, "secret", &progress).unwrap();
+        assert_eq!(counts, (1, 0, 4));
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_extract_inner_zip_names_missing_zstd_support() {
+        // Mark the only entry as Zstandard in its local and central headers
+        let mut zip = crafted_zip(&["data.bin"]);
+        zip[8..10].copy_from_slice(&ZSTD_METHOD.to_le_bytes());
+        let central = zip.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        zip[central + 10..central + 12].copy_from_slice(&ZSTD_METHOD.to_le_bytes());
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let progress = Progress::spinner(crate::models::Verbosity::Silent);
+        match extract_inner_zip(Cursor::new(&zip), temp.path(), &progress) {
+            Err(PackageError::ZipError { entry, reason, .. }) => {
+                assert_eq!(entry.as_deref(), Some("data.bin"));
+                assert!(reason.contains("'zstd' feature"), "{}", reason);
+            }
+            other => panic!("expected a ZIP error, got {:?}", other.map(|_| ())),
+        }
+
+        let archive = temp.path().join("protected.zip");
+        match write_protected_zip(Cursor::new(&zip), &archive, "secret", &progress) {
+            Err(PackageError::ZipError { reason, .. }) => {
+                assert!(reason.contains("'zstd' feature"), "{}", reason);
+            }
+            other => panic!("expected a ZIP error, got {:?}", other),
+        }
+        assert!(!archive.exists());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_extract_inner_zip_reads_zstd_entries() {
+        use crate::models::compression::CompressionMethod;
+
+        let data = b"zstd content ".repeat(1000);
+        let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let compression = Compression::new(CompressionMethod::Zstd);
+        zip.start_file("data.bin", archive::content_options(&compression))
+            .unwrap();
+        zip.write_all(&data).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        assert!(zip.len() < data.len() / 10);
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let progress = Progress::spinner(crate::models::Verbosity::Silent);
+        extract_inner_zip(Cursor::new(&zip), temp.path(), &progress).unwrap();
+        assert_eq!(fs::read(temp.path().join("data.bin")).unwrap(), data);
+    }
+
+    #[test]
+    fn test_dry_run_content() {
+        let content = content::MemoryContent::from_files(
+            "<memory>",
+            vec![
+                ("setup.exe", b"setup".to_vec()),
+                ("config/app.json", b"{}".to_vec()),
+            ],
+        )
+        .unwrap();
+        let mut builder = PackageRequest::builder();
+        builder.setup_file("setup.exe");
+        builder.options().set_display_name(Some("App".to_string()));
+        let request = builder.build_for_content().unwrap();
+
+        let report = dry_run_content(&request, &content).unwrap();
+        assert_eq!(report.file_count, 2);
+        assert_eq!(report.dir_count, 1);
+        assert_eq!(report.total_size, ByteSize(7));
+        assert!(!report.size_estimate.unwrap().exceeds_limit());
+        assert_eq!(
+            report.metadata,
+            [
+                ("setup_file".to_string(), "setup.exe".to_string()),
+                ("name".to_string(), "App".to_string()),
+            ]
+        );
+
+        // A projection above the limit warns, or fails under strict mode
+        builder.options().set_size_limit(ByteSize(16));
+        let request = builder.build_for_content().unwrap();
+        assert!(
+            dry_run_content(&request, &content)
+                .unwrap()
+                .size_estimate
+                .unwrap()
+                .exceeds_limit()
+        );
+        builder.options().set_strictness(Strictness::strict());
+        let request = builder.build_for_content().unwrap();
+        assert!(matches!(
+            dry_run_content(&request, &content),
+            Err(PackageError::StrictViolation {
+                check: "package-size",
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,587 @@
+//! Reading the Property table of Windows Installer (.msi) files.
+//!
+//! An MSI is a Compound File Binary (CFB) document whose database tables
+//! are stored as streams in the root storage. Only what is needed to look
+//! up properties such as `ProductCode` is implemented: the sector chains,
+//! the string pool and the two-column `Property` table.
+
+use std::collections::BTreeMap;
+
+/// Signature at the start of every compound file.
+const CFB_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// Size of the compound file header.
+const HEADER_SIZE: usize = 512;
+
+/// Size of a directory entry.
+const DIR_ENTRY_SIZE: usize = 128;
+
+/// Marks the end of a sector chain.
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+
+/// Number of FAT sector locations stored in the header.
+const HEADER_DIFAT_ENTRIES: usize = 109;
+
+/// Directory entry type of a stream.
+const STREAM_ENTRY: u8 = 2;
+
+/// Directory entry type of the root storage.
+const ROOT_ENTRY: u8 = 5;
+
+/// Read the `Property` table of the MSI in `data` into a map.
+pub fn read_msi_properties(data: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    let cfb = CompoundFile::parse(data)?;
+    let pool = cfb
+        .stream("!_StringPool")?
+        .ok_or("String pool is missing")?;
+    let string_data = cfb
+        .stream("!_StringData")?
+        .ok_or("String data is missing")?;
+    let strings = StringPool::parse(&pool, &string_data)?;
+    let table = cfb
+        .stream("!Property")?
+        .ok_or("Property table is missing")?;
+
+    // Tables are stored column by column: every Property, then every Value
+    let row_size = strings.ref_size * 2;
+    if table.len() % row_size != 0 {
+        return Err("Property table has a partial row".to_string());
+    }
+    let rows = table.len() / row_size;
+    let column = |column: usize, row: usize| {
+        let start = (column * rows + row) * strings.ref_size;
+        strings.get(read_ref(&table[start..start + strings.ref_size]))
+    };
+
+    let mut properties = BTreeMap::new();
+    for row in 0..rows {
+        properties.insert(column(0, row)?.to_string(), column(1, row)?.to_string());
+    }
+    Ok(properties)
+}
+
+/// A parsed compound file with its allocation tables loaded.
+struct CompoundFile<'a> {
+    data: &'a [u8],
+    sector_size: usize,
+    mini_sector_size: usize,
+    mini_stream_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    entries: Vec<DirEntry>,
+}
+
+/// A directory entry with its decoded name.
+struct DirEntry {
+    name: String,
+    kind: u8,
+    start: u32,
+    size: u64,
+}
+
+impl<'a> CompoundFile<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE || data[..8] != CFB_SIGNATURE {
+            return Err("Not a Windows Installer file".to_string());
+        }
+        let sector_shift = u16_at(data, 0x1E);
+        let mini_sector_shift = u16_at(data, 0x20);
+        if !(9..=12).contains(&sector_shift) || mini_sector_shift >= sector_shift {
+            return Err("Invalid sector size".to_string());
+        }
+
+        let mut cfb = Self {
+            data,
+            sector_size: 1 << sector_shift,
+            mini_sector_size: 1 << mini_sector_shift,
+            mini_stream_cutoff: u64::from(u32_at(data, 0x38)),
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            entries: Vec::new(),
+        };
+
+        // The first FAT sectors are listed in the header, the rest in a
+        // chain of DIFAT sectors whose last slot points to the next one
+        let fat_sectors = u32_at(data, 0x2C) as usize;
+        let mut fat_locations: Vec<u32> = (0..HEADER_DIFAT_ENTRIES)
+            .map(|i| u32_at(data, 0x4C + i * 4))
+            .collect();
+        let mut difat = u32_at(data, 0x44);
+        let per_difat = cfb.sector_size / 4 - 1;
+        for _ in 0..u32_at(data, 0x48) {
+            let sector = cfb.sector(difat)?;
+            fat_locations.extend((0..per_difat).map(|i| u32_at(sector, i * 4)));
+            difat = u32_at(sector, per_difat * 4);
+        }
+        if fat_locations.len() < fat_sectors {
+            return Err("FAT is truncated".to_string());
+        }
+        for &location in &fat_locations[..fat_sectors] {
+            let sector = cfb.sector(location)?;
+            cfb.fat
+                .extend((0..cfb.sector_size / 4).map(|i| u32_at(sector, i * 4)));
+        }
+
+        let directory = cfb.read_chain(u32_at(data, 0x30), None)?;
+        cfb.entries = directory
+            .chunks_exact(DIR_ENTRY_SIZE)
+            .map(DirEntry::parse)
+            .collect();
+        if cfb.sector_size == 512 {
+            // Version 3 files may leave garbage in the high half of the size
+            for entry in &mut cfb.entries {
+                entry.size &= 0xFFFF_FFFF;
+            }
+        }
+        let root = cfb
+            .entries
+            .first()
+            .filter(|entry| entry.kind == ROOT_ENTRY)
+            .ok_or("Root storage is missing")?;
+        let (root_start, root_size) = (root.start, root.size);
+
+        if u32_at(data, 0x40) > 0 {
+            let mini_fat = cfb.read_chain(u32_at(data, 0x3C), None)?;
+            cfb.mini_fat = mini_fat
+                .chunks_exact(4)
+                .map(|bytes| u32_at(bytes, 0))
+                .collect();
+            cfb.mini_stream = cfb.read_chain(root_start, Some(root_size))?;
+        }
+        Ok(cfb)
+    }
+
+    /// The contents of the stream with the decoded name `name`, if any.
+    fn stream(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let Some(entry) = self
+            .entries
+            .iter()
+            .find(|entry| entry.kind == STREAM_ENTRY && entry.name == name)
+        else {
+            return Ok(None);
+        };
+        if entry.size >= self.mini_stream_cutoff {
+            return self.read_chain(entry.start, Some(entry.size)).map(Some);
+        }
+
+        let mut data = Vec::new();
+        for sector in follow_chain(&self.mini_fat, entry.start)? {
+            let start = sector as usize * self.mini_sector_size;
+            let bytes = self
+                .mini_stream
+                .get(start..start + self.mini_sector_size)
+                .ok_or("Mini stream is truncated")?;
+            data.extend_from_slice(bytes);
+        }
+        truncate(data, entry.size).map(Some)
+    }
+
+    /// Read a chain of regular sectors, cut to `size` if given.
+    fn read_chain(&self, start: u32, size: Option<u64>) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        for sector in follow_chain(&self.fat, start)? {
+            data.extend_from_slice(self.sector(sector)?);
+        }
+        match size {
+            Some(size) => truncate(data, size),
+            None => Ok(data),
+        }
+    }
+
+    fn sector(&self, sector: u32) -> Result<&'a [u8], String> {
+        let start = (sector as usize + 1) * self.sector_size;
+        self.data
+            .get(start..start + self.sector_size)
+            .ok_or_else(|| format!("Sector {} is past the end of the file", sector))
+    }
+}
+
+impl DirEntry {
+    fn parse(bytes: &[u8]) -> Self {
+        let name_len = (u16_at(bytes, 64) as usize).min(64) / 2;
+        let name: Vec<u16> = (0..name_len.saturating_sub(1))
+            .map(|i| u16_at(bytes, i * 2))
+            .collect();
+        Self {
+            name: decode_stream_name(&name),
+            kind: bytes[66],
+            start: u32_at(bytes, 116),
+            size: u64::from_le_bytes(bytes[120..128].try_into().unwrap()),
+        }
+    }
+}
+
+/// Follow the chain starting at `start` through `table`.
+fn follow_chain(table: &[u32], start: u32) -> Result<Vec<u32>, String> {
+    let mut chain = Vec::new();
+    let mut sector = start;
+    while sector != END_OF_CHAIN {
+        // A chain longer than the table must contain a loop
+        if chain.len() > table.len() {
+            return Err("Sector chain contains a loop".to_string());
+        }
+        chain.push(sector);
+        sector = *table
+            .get(sector as usize)
+            .ok_or_else(|| format!("Sector {} is outside the allocation table", sector))?;
+    }
+    Ok(chain)
+}
+
+fn truncate(mut data: Vec<u8>, size: u64) -> Result<Vec<u8>, String> {
+    let size = usize::try_from(size).map_err(|_| "Stream is too large".to_string())?;
+    if data.len() < size {
+        return Err("Stream is shorter than its recorded size".to_string());
+    }
+    data.truncate(size);
+    Ok(data)
+}
+
+/// Decode an MSI stream name.
+///
+/// MSI packs two characters from a 64-character alphabet into one code unit
+/// between U+3800 and U+47FF, a single one into U+4800 to U+483F, and marks
+/// table streams with U+4840 (decoded as `!`).
+fn decode_stream_name(name: &[u16]) -> String {
+    fn alphabet(value: u16) -> char {
+        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._"[value as usize & 0x3F]
+            as char
+    }
+
+    let mut decoded = String::new();
+    for &unit in name {
+        match unit {
+            0x3800..=0x47FF => {
+                decoded.push(alphabet(unit - 0x3800));
+                decoded.push(alphabet((unit - 0x3800) >> 6));
+            }
+            0x4800..=0x483F => decoded.push(alphabet(unit - 0x4800)),
+            0x4840 => decoded.push('!'),
+            _ => decoded.extend(char::decode_utf16([unit]).map(|c| c.unwrap_or('\u{FFFD}'))),
+        }
+    }
+    decoded
+}
+
+/// The database's shared strings, indexed by string id.
+struct StringPool {
+    strings: Vec<String>,
+    /// Width of a string reference in table streams (2 or 3 bytes)
+    ref_size: usize,
+}
+
+impl StringPool {
+    /// Parse `!_StringPool` (lengths) and `!_StringData` (concatenated text).
+    fn parse(pool: &[u8], data: &[u8]) -> Result<Self, String> {
+        if pool.len() < 4 {
+            return Err("String pool is truncated".to_string());
+        }
+        let ref_size = if u16_at(pool, 2) & 0x8000 != 0 { 3 } else { 2 };
+        let words: Vec<u16> = pool[4..]
+            .chunks_exact(2)
+            .map(|bytes| u16_at(bytes, 0))
+            .collect();
+
+        // Id 0 is the null string; each entry is (length, reference count)
+        let mut strings = vec![String::new()];
+        let mut offset = 0;
+        let mut i = 0;
+        while i + 1 < words.len() {
+            let (len, refs) = (words[i], words[i + 1]);
+            let len = if len == 0 && refs != 0 && i + 3 < words.len() {
+                // Strings over 64K store the high word of the length in the
+                // reference count, followed by an entry with the low word
+                i += 2;
+                (usize::from(refs) << 16) | usize::from(words[i])
+            } else {
+                usize::from(len)
+            };
+            i += 2;
+            let text = data
+                .get(offset..offset + len)
+                .ok_or("String data is truncated")?;
+            strings.push(String::from_utf8_lossy(text).into_owned());
+            offset += len;
+        }
+        Ok(Self { strings, ref_size })
+    }
+
+    fn get(&self, id: u32) -> Result<&str, String> {
+        self.strings
+            .get(id as usize)
+            .map(String::as_str)
+            .ok_or_else(|| format!("String id {} is not in the string pool", id))
+    }
+}
+
+/// Read a little-endian string reference of 2 or 3 bytes.
+fn read_ref(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |id, &byte| (id << 8) | u32::from(byte))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Build minimal MSI files for tests.
+#[cfg(test)]
+pub(crate) mod fixture {
+    /// An MSI with a string pool and a `Property` table holding `properties`.
+    ///
+    /// Streams under 4K go to the mini stream, as in real files; values
+    /// adding up to more than that put the string data in regular sectors.
+    pub(crate) fn build_msi(properties: &[(&str, &str)]) -> Vec<u8> {
+        // String pool: id 1.. in order of first use
+        let mut strings: Vec<&str> = Vec::new();
+        let mut ids = Vec::new();
+        for (name, value) in properties {
+            for s in [*name, *value] {
+                let id = match strings.iter().position(|x| *x == s) {
+                    Some(pos) => pos + 1,
+                    None => {
+                        strings.push(s);
+                        strings.len()
+                    }
+                };
+                ids.push(id as u16);
+            }
+        }
+        let mut pool = vec![0xE9, 0xFD, 0, 0]; // codepage 65001
+        let mut data = Vec::new();
+        for s in &strings {
+            pool.extend_from_slice(&(s.len() as u16).to_le_bytes());
+            pool.extend_from_slice(&1u16.to_le_bytes());
+            data.extend_from_slice(s.as_bytes());
+        }
+        let mut table = Vec::new();
+        for column in 0..2 {
+            for row in ids.chunks(2) {
+                table.extend_from_slice(&row[column].to_le_bytes());
+            }
+        }
+
+        build_cfb(&[
+            (encode_table_name("_StringPool"), pool),
+            (encode_table_name("_StringData"), data),
+            (encode_table_name("Property"), table),
+        ])
+    }
+
+    /// Encode a table name the way MSI stores it.
+    pub(crate) fn encode_table_name(name: &str) -> Vec<u16> {
+        let index = |c: u8| {
+            b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._"
+                .iter()
+                .position(|x| *x == c)
+                .unwrap() as u16
+        };
+        let mut encoded = vec![0x4840];
+        for pair in name.as_bytes().chunks(2) {
+            encoded.push(match pair {
+                [a, b] => 0x3800 + index(*a) + (index(*b) << 6),
+                [a] => 0x4800 + index(*a),
+                _ => unreachable!(),
+            });
+        }
+        encoded
+    }
+
+    /// A version 3 compound file (512-byte sectors) with `streams` in the
+    /// root storage.
+    fn build_cfb(streams: &[(Vec<u16>, Vec<u8>)]) -> Vec<u8> {
+        const SECTOR: usize = 512;
+        const MINI: usize = 64;
+        const FREE: u32 = 0xFFFF_FFFF;
+        const END: u32 = 0xFFFF_FFFE;
+
+        // Lay out regular sectors: FAT, directory, mini FAT, mini stream,
+        // then large streams
+        let mut mini_stream = Vec::new();
+        let mut mini_fat: Vec<u32> = Vec::new();
+        let mut placements = Vec::new(); // (start, size, is_mini)
+        let mut large = Vec::new();
+        for (_, data) in streams {
+            if data.len() < 4096 {
+                let start = mini_fat.len();
+                let count = data.len().div_ceil(MINI).max(1);
+                for i in 0..count {
+                    mini_fat.push(if i + 1 == count {
+                        END
+                    } else {
+                        (start + i + 1) as u32
+                    });
+                }
+                let mut padded = data.clone();
+                padded.resize(count * MINI, 0);
+                mini_stream.extend_from_slice(&padded);
+                placements.push((start as u32, data.len(), true));
+            } else {
+                placements.push((0, data.len(), false));
+                large.push(placements.len() - 1);
+            }
+        }
+
+        let mut sectors: Vec<Vec<u8>> = Vec::new();
+        let mut fat: Vec<u32> = Vec::new();
+        fn alloc(bytes: &[u8], sectors: &mut Vec<Vec<u8>>, fat: &mut Vec<u32>) -> u32 {
+            let start = sectors.len();
+            let count = bytes.len().div_ceil(SECTOR).max(1);
+            for i in 0..count {
+                let mut sector = bytes
+                    [(i * SECTOR).min(bytes.len())..((i + 1) * SECTOR).min(bytes.len())]
+                    .to_vec();
+                sector.resize(SECTOR, 0);
+                sectors.push(sector);
+                fat.push(if i + 1 == count {
+                    END
+                } else {
+                    (start + i + 1) as u32
+                });
+            }
+            start as u32
+        }
+
+        // Sector 0 is the FAT itself
+        sectors.push(vec![0; SECTOR]);
+        fat.push(0xFFFF_FFFD);
+
+        let entry_count = streams.len() + 1;
+        let dir_start = alloc(
+            &vec![0; entry_count.div_ceil(4) * 4 * 128],
+            &mut sectors,
+            &mut fat,
+        );
+        let mini_fat_bytes: Vec<u8> = mini_fat
+            .iter()
+            .chain(std::iter::repeat(&FREE))
+            .take(mini_fat.len().div_ceil(128).max(1) * 128)
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let mini_fat_start = alloc(&mini_fat_bytes, &mut sectors, &mut fat);
+        let mini_stream_start = alloc(&mini_stream, &mut sectors, &mut fat);
+        for index in large {
+            placements[index].0 = alloc(&streams[index].1, &mut sectors, &mut fat);
+        }
+        assert!(fat.len() <= 128, "fixture needs a single FAT sector");
+
+        let mut fat_sector: Vec<u8> = fat
+            .iter()
+            .chain(std::iter::repeat(&FREE))
+            .take(128)
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        sectors[0] = std::mem::take(&mut fat_sector);
+
+        // Directory: root first, streams as a right-leaning chain of siblings
+        let mut directory = Vec::new();
+        let mut entry =
+            |name: &[u16], kind: u8, child: u32, right: u32, start: u32, size: usize| {
+                let mut bytes = vec![0u8; 128];
+                for (i, unit) in name.iter().enumerate() {
+                    bytes[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+                }
+                bytes[64..66].copy_from_slice(&(((name.len() + 1) * 2) as u16).to_le_bytes());
+                bytes[66] = kind;
+                bytes[68..72].copy_from_slice(&FREE.to_le_bytes());
+                bytes[72..76].copy_from_slice(&right.to_le_bytes());
+                bytes[76..80].copy_from_slice(&child.to_le_bytes());
+                bytes[116..120].copy_from_slice(&start.to_le_bytes());
+                bytes[120..128].copy_from_slice(&(size as u64).to_le_bytes());
+                directory.extend_from_slice(&bytes);
+            };
+        let root_name: Vec<u16> = "Root Entry".encode_utf16().collect();
+        entry(&root_name, 5, 1, FREE, mini_stream_start, mini_stream.len());
+        for (i, ((name, _), (start, size, _))) in streams.iter().zip(&placements).enumerate() {
+            let right = if i + 1 < streams.len() {
+                (i + 2) as u32
+            } else {
+                FREE
+            };
+            entry(name, 2, FREE, right, *start, *size);
+        }
+        for (i, chunk) in directory.chunks(SECTOR).enumerate() {
+            sectors[dir_start as usize + i][..chunk.len()].copy_from_slice(chunk);
+        }
+
+        let mut header = vec![0u8; 512];
+        header[..8].copy_from_slice(&super::CFB_SIGNATURE);
+        header[0x18..0x1A].copy_from_slice(&0x3Eu16.to_le_bytes());
+        header[0x1A..0x1C].copy_from_slice(&3u16.to_le_bytes());
+        header[0x1C..0x1E].copy_from_slice(&0xFFFEu16.to_le_bytes());
+        header[0x1E..0x20].copy_from_slice(&9u16.to_le_bytes());
+        header[0x20..0x22].copy_from_slice(&6u16.to_le_bytes());
+        header[0x2C..0x30].copy_from_slice(&1u32.to_le_bytes());
+        header[0x30..0x34].copy_from_slice(&dir_start.to_le_bytes());
+        header[0x38..0x3C].copy_from_slice(&4096u32.to_le_bytes());
+        header[0x3C..0x40].copy_from_slice(&mini_fat_start.to_le_bytes());
+        header[0x40..0x44].copy_from_slice(&1u32.to_le_bytes());
+        header[0x44..0x48].copy_from_slice(&END.to_le_bytes());
+        for i in 0..super::HEADER_DIFAT_ENTRIES {
+            let location = if i == 0 { 0 } else { FREE };
+            header[0x4C + i * 4..0x50 + i * 4].copy_from_slice(&location.to_le_bytes());
+        }
+
+        let mut file = header;
+        for sector in sectors {
+            file.extend_from_slice(&sector);
+        }
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::{build_msi, encode_table_name};
+    use super::*;
+
+    #[test]
+    fn test_read_msi_properties() {
+        let msi = build_msi(&[
+            ("ProductCode", "{12345678-1234-1234-1234-123456789ABC}"),
+            ("ProductVersion", "1.2.3"),
+            ("ProductName", "My App"),
+            ("Manufacturer", "ACME"),
+        ]);
+        let properties = read_msi_properties(&msi).unwrap();
+        assert_eq!(
+            properties["ProductCode"],
+            "{12345678-1234-1234-1234-123456789ABC}"
+        );
+        assert_eq!(properties["ProductVersion"], "1.2.3");
+        assert_eq!(properties["Manufacturer"], "ACME");
+        assert_eq!(properties.len(), 4);
+    }
+
+    #[test]
+    fn test_read_msi_properties_large_stream() {
+        // A value this long pushes the string data out of the mini stream
+        let license = "x".repeat(5000);
+        let msi = build_msi(&[("ProductCode", "{A}"), ("License", &license)]);
+        let properties = read_msi_properties(&msi).unwrap();
+        assert_eq!(properties["License"], license);
+        assert_eq!(properties["ProductCode"], "{A}");
+    }
+
+    #[test]
+    fn test_decode_stream_name() {
+        let name = encode_table_name("_Validation");
+        assert_eq!(decode_stream_name(&name), "!_Validation");
+        let plain: Vec<u16> = "SummaryInformation".encode_utf16().collect();
+        assert_eq!(decode_stream_name(&plain), "SummaryInformation");
+    }
+
+    #[test]
+    fn test_not_an_msi() {
+        assert!(read_msi_properties(b"MZ not an msi").is_err());
+        assert!(read_msi_properties(&[0u8; 1024]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Async packaging for tokio runtimes (`async` feature).
+//!
+//! Request checks use `tokio::fs`; collecting, compressing and encrypting
+//! run on the blocking thread pool, so a service building packages on
+//! demand never stalls its runtime. Cancelling the request's
+//! `CancellationToken` stops the work at the next file with
+//! [`PackageError::Cancelled`].
+
+use tokio::fs;
+use tokio::task;
+use tokio_util::sync::CancellationToken;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::{IntuneWinPackage, PackageRequest, UnpackRequest, UnpackResult};
+
+use super::archive::SourceArchive;
+use super::source::is_remote;
+use super::{package, unpack};
+
+/// Create an IntuneWin package without blocking the runtime.
+///
+/// No package is written when the request is cancelled.
+pub async fn package_async(request: PackageRequest) -> PackageResult<IntuneWinPackage> {
+    let is_source = is_remote(&request.source_folder)
+        || fs::metadata(&request.source_folder)
+            .await
+            .is_ok_and(|metadata| {
+                metadata.is_dir()
+                    || (metadata.is_file()
+                        && SourceArchive::from_name(&request.source_folder).is_some())
+            });
+    if !is_source {
+        return Err(PackageError::SourceFolderNotFound {
+            path: request.source_folder,
+        });
+    }
+
+    let cancellation = request.cancellation.clone();
+    run_blocking(cancellation.as_ref(), move || package(&request)).await
+}
+
+/// Extract an IntuneWin package without blocking the runtime.
+///
+/// Files extracted before a cancellation are left in place.
+pub async fn unpack_async(request: UnpackRequest) -> PackageResult<UnpackResult> {
+    let is_file = fs::metadata(&request.input_file)
+        .await
+        .is_ok_and(|metadata| metadata.is_file());
+    if !is_file {
+        return Err(PackageError::InvalidIntunewinFile {
+            path: request.input_file,
+            reason: "File does not exist".to_string(),
+        });
+    }
+
+    let cancellation = request.cancellation.clone();
+    run_blocking(cancellation.as_ref(), move || unpack(&request)).await
+}
+
+/// Run `work` on the blocking pool unless already cancelled.
+async fn run_blocking<T: Send + 'static>(
+    cancellation: Option<&CancellationToken>,
+    work: impl FnOnce() -> PackageResult<T> + Send + 'static,
+) -> PackageResult<T> {
+    if cancellation.is_some_and(CancellationToken::is_cancelled) {
+        return Err(PackageError::Cancelled);
+    }
+
+    match task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        // The runtime is shutting down
+        Err(_) => Err(PackageError::Cancelled),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::Verbosity;
+    use std::fs;
+    use std::future::Future;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn request(temp: &TempDir) -> PackageRequest {
+        let source = temp.path().join("src");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("data.txt"), "data").unwrap();
+        PackageRequest::new(source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent)
+    }
+
+    #[test]
+    fn test_package_and_unpack_async() {
+        let temp = TempDir::new().unwrap();
+        let packaged = block_on(package_async(request(&temp))).unwrap();
+        assert!(packaged.output_path.exists());
+
+        let extracted = temp.path().join("extracted");
+        let unpack_request = UnpackRequest::new(packaged.output_path, extracted.clone())
+            .with_verbosity(Verbosity::Silent);
+        let unpacked = block_on(unpack_async(unpack_request)).unwrap();
+        assert_eq!(unpacked.file_count, 2);
+        assert_eq!(fs::read(extracted.join("data.txt")).unwrap(), b"data");
+    }
+
+    #[test]
+    fn test_package_async_cancelled() {
+        let temp = TempDir::new().unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let request = request(&temp).with_cancellation(token);
+        assert!(matches!(
+            block_on(package_async(request.clone())),
+            Err(PackageError::Cancelled)
+        ));
+        // The blocking work checks the token too, before writing anything
+        assert!(matches!(package(&request), Err(PackageError::Cancelled)));
+        assert!(!request.output_path().exists());
+    }
+
+    #[test]
+    fn test_async_request_checks() {
+        let temp = TempDir::new().unwrap();
+        let missing = PackageRequest::new(temp.path().join("missing"), "setup.exe", temp.path());
+        assert!(matches!(
+            block_on(package_async(missing)),
+            Err(PackageError::SourceFolderNotFound { .. })
+        ));
+
+        let unpack_request =
+            UnpackRequest::new(temp.path().join("x.intunewin"), Path::new("out").into());
+        assert!(matches!(
+            block_on(unpack_async(unpack_request)),
+            Err(PackageError::InvalidIntunewinFile { .. })
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+//! Writing finished packages to their destination.
+//!
+//! Sync jobs and deployment pipelines pick up any `.intunewin` or `.pkg`
+//! that appears in an output folder. A package is therefore written to
+//! `<name>.partial` next to its destination and renamed into place only
+//! once it is complete and synced to disk, so a failed or interrupted run,
+//! or a power loss, never leaves a truncated package under the final name.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::retry::RetryPolicy;
+
+use super::retry::RetryWriter;
+
+/// Extension appended to a package's file name while it is written.
+pub const PARTIAL_EXTENSION: &str = "partial";
+
+/// Path `path` is written to before it is renamed into place.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".");
+    name.push(PARTIAL_EXTENSION);
+    path.with_file_name(name)
+}
+
+/// Write `path` through its partial file: `write` fills the file, which is
+/// flushed, synced to disk and renamed to `path` on success and removed on
+/// any error.
+///
+/// An existing file at `path` is replaced only once the new one is
+/// complete. Writes that fail transiently are retried as `retry` allows.
+pub fn write_atomically<T>(
+    path: &Path,
+    retry: &RetryPolicy,
+    write: impl FnOnce(&mut BufWriter<RetryWriter>) -> PackageResult<T>,
+) -> PackageResult<T> {
+    let partial = partial_path(path);
+    let result = RetryWriter::create(&partial, retry)
+        .map_err(|e| PackageError::output_write(path, e))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let value = write(&mut writer)?;
+            writer
+                .flush()
+                .map_err(|e| PackageError::output_write(path, e))?;
+            // Without this the rename may reach the disk before the data
+            writer
+                .get_mut()
+                .sync_all()
+                .map_err(|e| PackageError::output_write(path, e))?;
+            Ok(value)
+        })
+        .and_then(|value| {
+            retry
+                .run("rename", &partial, || fs::rename(&partial, path))
+                .map_err(|e| PackageError::output_write(path, e))?;
+            Ok(value)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_partial_path() {
+        assert_eq!(
+            partial_path(Path::new("out/setup.intunewin")),
+            PathBuf::from("out/setup.intunewin.partial")
+        );
+    }
+
+    #[test]
+    fn test_write_atomically_renames_on_success() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("app.pkg");
+        fs::write(&path, b"old").unwrap();
+
+        let size = write_atomically(&path, &RetryPolicy::none(), |writer| {
+            writer.write_all(b"new package").unwrap();
+            Ok(11)
+        })
+        .unwrap();
+        assert_eq!(size, 11);
+        assert_eq!(fs::read(&path).unwrap(), b"new package");
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[test]
+    fn test_write_atomically_cleans_up_on_error() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("setup.intunewin");
+        fs::write(&path, b"previous build").unwrap();
+
+        let result: PackageResult<()> = write_atomically(&path, &RetryPolicy::io(2), |writer| {
+            writer.write_all(b"trunc").unwrap();
+            Err(PackageError::InvalidArgument {
+                reason: "interrupted".to_string(),
+            })
+        });
+        assert!(result.is_err());
+        assert!(!partial_path(&path).exists());
+        assert_eq!(fs::read(&path).unwrap(), b"previous build");
+
+        // Nothing is left behind when there was no earlier build either
+        let fresh = temp.path().join("fresh.intunewin");
+        let _ = write_atomically::<()>(&fresh, &RetryPolicy::none(), |_| {
+            Err(PackageError::InvalidArgument {
+                reason: "interrupted".to_string(),
+            })
+        });
+        assert!(!fresh.exists());
+        assert!(!partial_path(&fresh).exists());
+    }
+}
@@ -0,0 +1,555 @@
+//! Reading the version resource of Windows executables (.exe).
+//!
+//! The version strings (`ProductName`, `FileVersion`, `CompanyName`, ...)
+//! live in the `RT_VERSION` resource. Only the PE headers and the section
+//! holding the resource directory are read, so large self-extracting
+//! installers are streamed past rather than loaded into memory.
+
+use std::io::{self, Read};
+
+use crate::models::setup::ExeVersionInfo;
+
+/// Size of the DOS header.
+const DOS_HEADER_SIZE: usize = 64;
+
+/// Size of the `PE\0\0` signature and COFF file header.
+const COFF_HEADER_SIZE: usize = 24;
+
+/// Size of a section table entry.
+const SECTION_HEADER_SIZE: usize = 40;
+
+/// Index of the resource table in the optional header's data directories.
+const RESOURCE_DIRECTORY: usize = 2;
+
+/// Resource type of version information.
+const RT_VERSION: u32 = 16;
+
+/// Signature of `VS_FIXEDFILEINFO`.
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+
+/// Largest resource section that is read.
+const MAX_RESOURCE_SECTION: u64 = 64 * 1024 * 1024;
+
+/// Read the version information of the executable in `reader`.
+///
+/// Returns `None` for executables without a version resource.
+pub fn read_version_info(reader: &mut dyn Read) -> Result<Option<ExeVersionInfo>, String> {
+    let mut headers = Vec::new();
+    read_more(reader, &mut headers, DOS_HEADER_SIZE)?;
+    if &headers[0..2] != b"MZ" {
+        return Err("Not a Windows executable (no MZ header)".to_string());
+    }
+
+    let pe_offset = u32_at(&headers, 0x3C)? as usize;
+    if pe_offset > 0x10000 {
+        return Err("PE header offset out of range".to_string());
+    }
+    read_more(reader, &mut headers, pe_offset + COFF_HEADER_SIZE)?;
+    if &headers[pe_offset..pe_offset + 4] != b"PE\0\0" {
+        return Err("Missing PE signature".to_string());
+    }
+
+    let section_count = u16_at(&headers, pe_offset + 6)? as usize;
+    let optional_size = u16_at(&headers, pe_offset + 20)? as usize;
+    let optional_start = pe_offset + COFF_HEADER_SIZE;
+    let sections_start = optional_start + optional_size;
+    read_more(
+        reader,
+        &mut headers,
+        sections_start + section_count * SECTION_HEADER_SIZE,
+    )?;
+
+    // Data directories follow the PE32 or PE32+ specific fields
+    let (count_offset, directories_offset) = match u16_at(&headers, optional_start)? {
+        0x10b => (92, 96),
+        0x20b => (108, 112),
+        magic => return Err(format!("Unknown optional header magic {:#x}", magic)),
+    };
+    let directory_count = u32_at(&headers, optional_start + count_offset)? as usize;
+    let directory = optional_start + directories_offset + RESOURCE_DIRECTORY * 8;
+    if directory_count <= RESOURCE_DIRECTORY || directory + 8 > sections_start {
+        return Ok(None);
+    }
+    let resource_rva = u32_at(&headers, directory)?;
+    if resource_rva == 0 {
+        return Ok(None);
+    }
+
+    let section = (0..section_count)
+        .map(|i| Section::parse(&headers, sections_start + i * SECTION_HEADER_SIZE))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .find(|s| s.contains(resource_rva))
+        .ok_or("Resource directory is outside every section")?;
+
+    // Skip to the section and read it whole
+    let position = headers.len() as u64;
+    let offset = u64::from(section.raw_offset);
+    if offset < position {
+        return Err("Resource section overlaps the headers".to_string());
+    }
+    if u64::from(section.raw_size) > MAX_RESOURCE_SECTION {
+        return Err("Resource section is too large".to_string());
+    }
+    io::copy(&mut reader.take(offset - position), &mut io::sink()).map_err(|e| e.to_string())?;
+    let mut data = Vec::new();
+    reader
+        .take(u64::from(section.raw_size))
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+
+    let resources = Resources {
+        data: &data,
+        base: section.virtual_address,
+        root: (resource_rva - section.virtual_address) as usize,
+    };
+    match resources.version_resource()? {
+        Some(version) => parse_version_info(version).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// The parts of a section header needed to map an RVA to file data.
+#[derive(Debug)]
+struct Section {
+    virtual_size: u32,
+    virtual_address: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+impl Section {
+    fn parse(headers: &[u8], offset: usize) -> Result<Self, String> {
+        Ok(Self {
+            virtual_size: u32_at(headers, offset + 8)?,
+            virtual_address: u32_at(headers, offset + 12)?,
+            raw_size: u32_at(headers, offset + 16)?,
+            raw_offset: u32_at(headers, offset + 20)?,
+        })
+    }
+
+    fn contains(&self, rva: u32) -> bool {
+        let size = self.virtual_size.max(self.raw_size);
+        rva >= self.virtual_address && rva - self.virtual_address < size
+    }
+}
+
+/// A resource section, with the resource directory at `root`.
+struct Resources<'a> {
+    data: &'a [u8],
+    base: u32,
+    root: usize,
+}
+
+impl Resources<'_> {
+    /// The data of the first `RT_VERSION` resource, if any.
+    fn version_resource(&self) -> Result<Option<&[u8]>, String> {
+        let Some(names) = self.find_entry(self.root, Some(RT_VERSION))? else {
+            return Ok(None);
+        };
+        let Some(languages) = self.find_entry(self.subdirectory(names)?, None)? else {
+            return Ok(None);
+        };
+        let Some(entry) = self.find_entry(self.subdirectory(languages)?, None)? else {
+            return Ok(None);
+        };
+        if entry & 0x8000_0000 != 0 {
+            return Err("Version resource nests too deep".to_string());
+        }
+
+        // IMAGE_RESOURCE_DATA_ENTRY: data RVA and size
+        let data_entry = self.root + entry as usize;
+        let rva = u32_at(self.data, data_entry)?;
+        let size = u32_at(self.data, data_entry + 4)? as usize;
+        let start =
+            rva.checked_sub(self.base)
+                .ok_or("Version resource is outside the resource section")? as usize;
+        self.data
+            .get(start..start + size)
+            .map(Some)
+            .ok_or_else(|| "Version resource is truncated".to_string())
+    }
+
+    /// The offset field of the entry with `id` (or the first entry) in the
+    /// directory at `directory`.
+    fn find_entry(&self, directory: usize, id: Option<u32>) -> Result<Option<u32>, String> {
+        let named = u16_at(self.data, directory + 12)? as usize;
+        let ids = u16_at(self.data, directory + 14)? as usize;
+        for i in 0..named + ids {
+            let entry = directory + 16 + i * 8;
+            let name = u32_at(self.data, entry)?;
+            if id.is_none() || id == Some(name) {
+                return u32_at(self.data, entry + 4).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Offset of the subdirectory an entry points at.
+    fn subdirectory(&self, entry: u32) -> Result<usize, String> {
+        if entry & 0x8000_0000 == 0 {
+            return Err("Expected a resource subdirectory".to_string());
+        }
+        Ok(self.root + (entry & 0x7FFF_FFFF) as usize)
+    }
+}
+
+/// A `VS_VERSIONINFO`-style block: a key, a value and child blocks.
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    is_text: bool,
+    children: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    /// Parse the block at the start of `data`, returning it and its length
+    /// including padding.
+    fn parse(data: &'a [u8]) -> Result<(Self, usize), String> {
+        let length = u16_at(data, 0)? as usize;
+        let value_length = u16_at(data, 2)? as usize;
+        let is_text = u16_at(data, 4)? == 1;
+        let block = data.get(..length).ok_or("Version block is truncated")?;
+
+        let (key, key_end) = utf16_string(block, 6)?;
+        let value_start = align4(key_end);
+        // Text values are measured in UTF-16 code units
+        let value_size = if is_text {
+            value_length * 2
+        } else {
+            value_length
+        };
+        let value_end = (value_start + value_size).min(length);
+        let value = block.get(value_start..value_end).unwrap_or_default();
+        let children = block.get(align4(value_end)..).unwrap_or_default();
+
+        Ok((
+            Self {
+                key,
+                value,
+                is_text,
+                children,
+            },
+            align4(length).max(4),
+        ))
+    }
+
+    /// The child blocks.
+    fn children(&self) -> Result<Vec<Block<'a>>, String> {
+        let mut children = Vec::new();
+        let mut rest = self.children;
+        while rest.len() >= 6 {
+            let (child, size) = Block::parse(rest)?;
+            children.push(child);
+            rest = rest.get(size..).unwrap_or_default();
+        }
+        Ok(children)
+    }
+}
+
+/// Parse a `VS_VERSIONINFO` resource.
+fn parse_version_info(data: &[u8]) -> Result<ExeVersionInfo, String> {
+    let (root, _) = Block::parse(data)?;
+    if root.key != "VS_VERSION_INFO" {
+        return Err(format!("Unexpected version resource key '{}'", root.key));
+    }
+
+    let mut info = ExeVersionInfo::default();
+    for child in root.children()? {
+        if child.key != "StringFileInfo" {
+            continue;
+        }
+        // The first string table (usually the only language) wins
+        let Some(table) = child.children()?.into_iter().next() else {
+            continue;
+        };
+        for string in table.children()? {
+            let value = if string.is_text {
+                utf16_string(string.value, 0)?.0
+            } else {
+                String::new()
+            };
+            let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+            let field = match string.key.as_str() {
+                "ProductName" => &mut info.product_name,
+                "ProductVersion" => &mut info.product_version,
+                "FileVersion" => &mut info.file_version,
+                "CompanyName" => &mut info.company_name,
+                "FileDescription" => &mut info.file_description,
+                "OriginalFilename" => &mut info.original_filename,
+                _ => continue,
+            };
+            *field = value;
+        }
+    }
+
+    // VS_FIXEDFILEINFO: signature, struct version, file version MS/LS
+    if info.file_version.is_none()
+        && root.value.len() >= 16
+        && u32_at(root.value, 0)? == FIXED_FILE_INFO_SIGNATURE
+    {
+        let high = u32_at(root.value, 8)?;
+        let low = u32_at(root.value, 12)?;
+        info.file_version = Some(format!(
+            "{}.{}.{}.{}",
+            high >> 16,
+            high & 0xFFFF,
+            low >> 16,
+            low & 0xFFFF
+        ));
+    }
+
+    Ok(info)
+}
+
+/// Read a NUL-terminated UTF-16LE string at `offset`, returning it and the
+/// offset just past its terminator.
+fn utf16_string(data: &[u8], offset: usize) -> Result<(String, usize), String> {
+    let mut units = Vec::new();
+    let mut pos = offset;
+    while let Some(bytes) = data.get(pos..pos + 2) {
+        pos += 2;
+        let unit = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if unit == 0 {
+            return Ok((String::from_utf16_lossy(&units), pos));
+        }
+        units.push(unit);
+    }
+    // Values may fill their block without a terminator
+    Ok((String::from_utf16_lossy(&units), pos))
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+/// Read from `reader` until `buffer` holds `len` bytes.
+fn read_more(reader: &mut dyn Read, buffer: &mut Vec<u8>, len: usize) -> Result<(), String> {
+    if buffer.len() >= len {
+        return Ok(());
+    }
+    let start = buffer.len();
+    buffer.resize(len, 0);
+    reader
+        .read_exact(&mut buffer[start..])
+        .map_err(|_| "File is too short for a Windows executable".to_string())
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("Unexpected end of data at offset {}", offset))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| format!("Unexpected end of data at offset {}", offset))
+}
+
+/// Building minimal executables with a version resource for tests.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    /// Section alignment of the fixture's single `.rsrc` section.
+    const RSRC_RVA: u32 = 0x1000;
+
+    /// File offset of the `.rsrc` section.
+    const RSRC_OFFSET: u32 = 0x400;
+
+    fn utf16z(text: &str) -> Vec<u8> {
+        let mut bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    fn pad4(data: &mut Vec<u8>) {
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    /// A version block with `value` and `children`.
+    fn block(
+        key: &str,
+        value: &[u8],
+        value_length: u16,
+        is_text: bool,
+        children: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut data = vec![0, 0];
+        data.extend_from_slice(&value_length.to_le_bytes());
+        data.extend_from_slice(&u16::from(is_text).to_le_bytes());
+        data.extend_from_slice(&utf16z(key));
+        pad4(&mut data);
+        data.extend_from_slice(value);
+        for child in children {
+            pad4(&mut data);
+            data.extend_from_slice(child);
+        }
+        let length = data.len() as u16;
+        data[0..2].copy_from_slice(&length.to_le_bytes());
+        data
+    }
+
+    /// The `VS_VERSIONINFO` resource for `strings` and `file_version`.
+    fn version_info(strings: &[(&str, &str)], file_version: [u16; 4]) -> Vec<u8> {
+        let mut fixed = Vec::new();
+        fixed.extend_from_slice(&FIXED_FILE_INFO_SIGNATURE.to_le_bytes());
+        fixed.extend_from_slice(&0x0001_0000u32.to_le_bytes());
+        let [a, b, c, d] = file_version.map(u32::from);
+        fixed.extend_from_slice(&((a << 16) | b).to_le_bytes());
+        fixed.extend_from_slice(&((c << 16) | d).to_le_bytes());
+        fixed.resize(52, 0);
+
+        let strings: Vec<Vec<u8>> = strings
+            .iter()
+            .map(|(key, value)| {
+                let value_units = value.encode_utf16().count() as u16 + 1;
+                block(key, &utf16z(value), value_units, true, &[])
+            })
+            .collect();
+        let table = block("040904b0", &[], 0, true, &strings);
+        let string_file_info = block("StringFileInfo", &[], 0, true, &[table]);
+        block("VS_VERSION_INFO", &fixed, 52, false, &[string_file_info])
+    }
+
+    /// A resource directory with one entry pointing at `target`.
+    fn directory(id: u32, target: u32) -> Vec<u8> {
+        let mut data = vec![0; 12];
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&id.to_le_bytes());
+        data.extend_from_slice(&target.to_le_bytes());
+        data
+    }
+
+    /// A PE32 executable whose only section holds a version resource
+    /// (or none, when `strings` is `None`).
+    pub(crate) fn build_exe(strings: Option<&[(&str, &str)]>, file_version: [u16; 4]) -> Vec<u8> {
+        // .rsrc: three directory levels, a data entry, then the data
+        let mut rsrc = Vec::new();
+        if let Some(strings) = strings {
+            let version = version_info(strings, file_version);
+            rsrc.extend(directory(RT_VERSION, 0x8000_0000 | 24));
+            rsrc.extend(directory(1, 0x8000_0000 | 48));
+            rsrc.extend(directory(0x409, 72));
+            rsrc.extend_from_slice(&(RSRC_RVA + 88).to_le_bytes());
+            rsrc.extend_from_slice(&(version.len() as u32).to_le_bytes());
+            rsrc.resize(88, 0);
+            rsrc.extend(version);
+        } else {
+            rsrc.extend(directory(3, 0x8000_0000 | 24));
+            rsrc.extend(vec![0; 16]);
+        }
+
+        let mut exe = vec![0u8; 0x80];
+        exe[0..2].copy_from_slice(b"MZ");
+        exe[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+
+        // COFF header: i386, one section, PE32 optional header
+        exe.extend_from_slice(b"PE\0\0");
+        exe.extend_from_slice(&0x14cu16.to_le_bytes());
+        exe.extend_from_slice(&1u16.to_le_bytes());
+        exe.extend_from_slice(&[0; 12]);
+        exe.extend_from_slice(&224u16.to_le_bytes());
+        exe.extend_from_slice(&0x0102u16.to_le_bytes());
+
+        let mut optional = vec![0u8; 224];
+        optional[0..2].copy_from_slice(&0x10bu16.to_le_bytes());
+        optional[92..96].copy_from_slice(&16u32.to_le_bytes());
+        let resource = 96 + RESOURCE_DIRECTORY * 8;
+        optional[resource..resource + 4].copy_from_slice(&RSRC_RVA.to_le_bytes());
+        optional[resource + 4..resource + 8].copy_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        exe.extend(optional);
+
+        let mut section = b".rsrc\0\0\0".to_vec();
+        section.extend_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        section.extend_from_slice(&RSRC_RVA.to_le_bytes());
+        section.extend_from_slice(&(rsrc.len() as u32).to_le_bytes());
+        section.extend_from_slice(&RSRC_OFFSET.to_le_bytes());
+        section.resize(SECTION_HEADER_SIZE, 0);
+        exe.extend(section);
+
+        exe.resize(RSRC_OFFSET as usize, 0);
+        exe.extend(rsrc);
+        exe
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::build_exe;
+    use super::*;
+
+    #[test]
+    fn test_read_version_strings() {
+        let exe = build_exe(
+            Some(&[
+                ("CompanyName", "ACME Corp"),
+                ("ProductName", "ACME Tool"),
+                ("FileVersion", "1.2.3.4"),
+                ("ProductVersion", "1.2"),
+                ("Comments", "ignored"),
+            ]),
+            [1, 2, 3, 4],
+        );
+        let info = read_version_info(&mut exe.as_slice()).unwrap().unwrap();
+        assert_eq!(info.company_name.as_deref(), Some("ACME Corp"));
+        assert_eq!(info.product_name.as_deref(), Some("ACME Tool"));
+        assert_eq!(info.file_version.as_deref(), Some("1.2.3.4"));
+        assert_eq!(info.product_version.as_deref(), Some("1.2"));
+        assert_eq!(info.original_filename, None);
+    }
+
+    #[test]
+    fn test_fixed_file_version_fallback() {
+        let exe = build_exe(Some(&[("ProductName", "Tool")]), [10, 0, 19041, 1]);
+        let info = read_version_info(&mut exe.as_slice()).unwrap().unwrap();
+        assert_eq!(info.file_version.as_deref(), Some("10.0.19041.1"));
+    }
+
+    #[test]
+    fn test_no_version_resource() {
+        let exe = build_exe(None, [0; 4]);
+        assert_eq!(read_version_info(&mut exe.as_slice()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_not_an_executable() {
+        assert!(read_version_info(&mut &b"#!/bin/sh\necho hi\n"[..]).is_err());
+        let mut truncated = build_exe(None, [0; 4]);
+        truncated.truncate(0x90);
+        assert!(read_version_info(&mut truncated.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_package_writes_setup_manifest() {
+        use crate::models::package::{PackageRequest, Verbosity};
+        use std::fs;
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        let exe = build_exe(Some(&[("ProductName", "ACME Tool")]), [2, 0, 0, 0]);
+        fs::write(source.join("Setup.EXE"), exe).unwrap();
+
+        let request = PackageRequest::new(&source, "Setup.EXE", temp.path())
+            .with_setup_manifest(true)
+            .with_verbosity(Verbosity::Silent);
+        let result = crate::packager::package(&request).unwrap();
+
+        assert_eq!(result.setup_info.unwrap().summary(), "ACME Tool 2.0.0.0");
+        let manifest = result.setup_manifest.unwrap();
+        assert_eq!(manifest, temp.path().join("Setup.setup.json"));
+        assert!(
+            fs::read_to_string(manifest)
+                .unwrap()
+                .contains("\"ACME Tool\"")
+        );
+
+        let to_stdout = PackageRequest::new(&source, "Setup.EXE", "-").with_setup_manifest(true);
+        assert!(to_stdout.validate_settings().is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Progress reporting that costs nothing when hidden.
+
+use std::sync::Arc;
+
+#[cfg(feature = "cli")]
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "async")]
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "cli")]
+use crate::cli::console::can_draw_progress;
+
+#[cfg(feature = "async")]
+use crate::models::error::PackageError;
+use crate::models::error::PackageResult;
+use crate::models::package::Verbosity;
+use crate::models::progress::{ProgressCounters, ProgressStats};
+
+/// Without the `cli` feature nothing is ever drawn.
+#[cfg(not(feature = "cli"))]
+#[derive(Debug)]
+enum ProgressBar {}
+
+#[cfg(not(feature = "cli"))]
+impl ProgressBar {
+    fn set_message(&self, _: impl Into<String>) {
+        match *self {}
+    }
+
+    fn inc(&self, _: u64) {
+        match *self {}
+    }
+
+    fn finish_with_message(&self, _: &'static str) {
+        match *self {}
+    }
+}
+
+/// Progress display plus always-on counters.
+///
+/// In silent mode, and when stderr is not a terminal, no bar exists, so
+/// message closures are never called and no formatting happens; the
+/// counters are updated regardless. Work loops also ask it whether the
+/// request was cancelled, since it is already passed down to every file.
+#[derive(Debug, Default)]
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    counters: Arc<ProgressCounters>,
+    #[cfg(feature = "async")]
+    cancellation: Option<CancellationToken>,
+}
+
+impl Progress {
+    /// Progress with counters only.
+    pub fn hidden() -> Self {
+        Self::default()
+    }
+
+    /// Progress bar over `len` files.
+    #[cfg(feature = "cli")]
+    pub fn bar(len: u64, verbosity: Verbosity) -> Self {
+        if verbosity.suppress_output() || !can_draw_progress() {
+            return Self::hidden();
+        }
+
+        let pb = ProgressBar::new(len);
+        if verbosity.show_progress() {
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template(
+                        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
+                    )
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+        }
+
+        Self {
+            bar: Some(pb),
+            ..Self::default()
+        }
+    }
+
+    /// Progress bar over `len` files (counters only without `cli`).
+    #[cfg(not(feature = "cli"))]
+    pub fn bar(_len: u64, _verbosity: Verbosity) -> Self {
+        Self::hidden()
+    }
+
+    /// Spinner for work of unknown length.
+    #[cfg(feature = "cli")]
+    pub fn spinner(verbosity: Verbosity) -> Self {
+        if verbosity.suppress_output() || !can_draw_progress() {
+            return Self::hidden();
+        }
+
+        let pb = ProgressBar::new_spinner();
+        if verbosity.show_progress() {
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} [{elapsed_precise}] {msg}")
+                    .unwrap(),
+            );
+        }
+
+        Self {
+            bar: Some(pb),
+            ..Self::default()
+        }
+    }
+
+    /// Spinner for work of unknown length (counters only without `cli`).
+    #[cfg(not(feature = "cli"))]
+    pub fn spinner(_verbosity: Verbosity) -> Self {
+        Self::hidden()
+    }
+
+    /// Report cancellation of `token` from [`Progress::check_cancelled`].
+    #[cfg(feature = "async")]
+    pub fn with_cancellation(mut self, token: Option<CancellationToken>) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Fail with [`crate::PackageError::Cancelled`] if the work was cancelled.
+    pub fn check_cancelled(&self) -> PackageResult<()> {
+        #[cfg(feature = "async")]
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(PackageError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Shared handle to the counters, e.g. for polling from another thread.
+    pub fn counters(&self) -> Arc<ProgressCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    /// Set a fixed status message.
+    pub fn set_message(&self, message: &'static str) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(message);
+        }
+    }
+
+    /// Set a status message built only when it will be displayed.
+    pub fn set_message_with(&self, message: impl FnOnce() -> String) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(message());
+        }
+    }
+
+    /// Record a processed file and the bytes read for it.
+    pub fn file_done(&self, bytes_read: u64) {
+        self.counters.add_file();
+        self.counters.add_bytes_read(bytes_read);
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+
+    /// Record bytes read without completing a file.
+    pub fn add_bytes_read(&self, bytes: u64) {
+        self.counters.add_bytes_read(bytes);
+    }
+
+    /// Record bytes written to the output.
+    pub fn add_bytes_written(&self, bytes: u64) {
+        self.counters.add_bytes_written(bytes);
+    }
+
+    /// Finish the display and return the final counter values.
+    pub fn finish(&self, message: &'static str) -> ProgressStats {
+        if let Some(bar) = &self.bar {
+            bar.finish_with_message(message);
+        }
+        self.counters.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, UnpackRequest};
+    use crate::packager::{package, unpack};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hidden_progress_counts_without_formatting() {
+        let progress = Progress::bar(10, Verbosity::Silent);
+        progress.set_message_with(|| panic!("message built while hidden"));
+        progress.file_done(5);
+        progress.add_bytes_written(3);
+
+        let stats = progress.finish("Done!");
+        assert_eq!(stats.files_processed, 1);
+        assert_eq!(stats.bytes_read.bytes(), 5);
+        assert_eq!(stats.bytes_written.bytes(), 3);
+    }
+
+    #[test]
+    fn test_silent_results_carry_stats() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), vec![1u8; 300]).unwrap();
+        fs::write(source.join("data.bin"), vec![2u8; 200]).unwrap();
+
+        let request = PackageRequest::new(source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let packaged = package(&request).unwrap();
+        assert_eq!(packaged.stats.files_processed, 2);
+        assert_eq!(packaged.stats.bytes_read.bytes(), 500);
+        assert_eq!(packaged.stats.bytes_written, packaged.package_size);
+
+        let unpack_request = UnpackRequest::new(packaged.output_path, temp.path().join("x"))
+            .with_verbosity(Verbosity::Silent);
+        let unpacked = unpack(&unpack_request).unwrap();
+        assert_eq!(unpacked.stats.files_processed, 2);
+        assert_eq!(unpacked.stats.bytes_written.bytes(), 500);
+    }
+}
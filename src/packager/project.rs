@@ -0,0 +1,336 @@
+//! Export and import of project bundles.
+//!
+//! `export-project` snapshots the content into a manifest and writes it
+//! with the build settings, configuration and scripts to a project file.
+//! `import-project` checks archived content against that manifest and
+//! rebuilds the package with the recorded settings.
+
+use std::fs;
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walkdir::WalkDir;
+
+use crate::models::capture::CaptureSnapshot;
+use crate::models::config::Config;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::PackageRequest;
+use crate::models::project::{
+    Project, ProjectBuild, ProjectImportRequest, ProjectImportResult, ProjectScript,
+};
+
+use super::capture::take_snapshot;
+
+/// Snapshot `content_folder` and write a project file to `output`.
+///
+/// Only the manifest of the content is stored; scripts from
+/// `scripts_folder` are stored in full.
+pub fn export_project(
+    content_folder: &Path,
+    build: ProjectBuild,
+    config: Config,
+    scripts_folder: Option<&Path>,
+    output: &Path,
+) -> PackageResult<Project> {
+    let snapshot = take_snapshot(content_folder, Some(output))?;
+    let scripts = match scripts_folder {
+        Some(folder) => collect_scripts(folder)?,
+        None => Vec::new(),
+    };
+
+    let project = Project {
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        build,
+        config,
+        scripts,
+        files: snapshot.entries,
+    };
+
+    fs::write(output, project.to_json())
+        .map_err(|e| PackageError::output_write(output.to_path_buf(), e))?;
+
+    Ok(project)
+}
+
+/// Read a project file written by [`export_project`].
+pub fn load_project(path: &Path) -> PackageResult<Project> {
+    let invalid = |reason: String| PackageError::ProjectError {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    Project::from_json(&text).map_err(invalid)
+}
+
+/// Check `content_folder` (minus `exclude`) against the project's manifest.
+///
+/// Fails listing the first differences when files were added, changed or
+/// removed since export.
+pub fn verify_content(
+    project: &Project,
+    content_folder: &Path,
+    exclude: Option<&Path>,
+) -> PackageResult<()> {
+    let current = take_snapshot(content_folder, exclude)?;
+    let recorded = CaptureSnapshot {
+        root: current.root.clone(),
+        created: project.created,
+        entries: project.files.clone(),
+    };
+    let diff = recorded.diff(&current);
+
+    if diff.is_empty() && diff.removed.is_empty() {
+        return Ok(());
+    }
+
+    let mut problems: Vec<String> = Vec::new();
+    problems.extend(diff.added.iter().map(|p| format!("added {}", p.display())));
+    problems.extend(
+        diff.changed
+            .iter()
+            .map(|p| format!("changed {}", p.display())),
+    );
+    problems.extend(
+        diff.removed
+            .iter()
+            .map(|p| format!("missing {}", p.display())),
+    );
+    let shown = problems.len().min(5);
+
+    Err(PackageError::ProjectError {
+        path: content_folder.to_path_buf(),
+        reason: format!(
+            "Content does not match the project manifest ({} difference(s): {}{})",
+            problems.len(),
+            problems[..shown].join(", "),
+            if problems.len() > shown { ", ..." } else { "" }
+        ),
+    })
+}
+
+/// Verify the archived content and rebuild the package described by the
+/// project file.
+pub fn import_project(request: &ProjectImportRequest) -> PackageResult<ProjectImportResult> {
+    let project = load_project(&request.project_file)?;
+    verify_content(
+        &project,
+        &request.content_folder,
+        Some(&request.project_file),
+    )?;
+
+    let mut strictness = project.config.strictness.clone();
+    strictness.strict |= request.strict;
+
+    match project.build {
+        ProjectBuild::Intune {
+            setup_file,
+            output_name,
+            content_tags,
+        } => {
+            let mut package_request = PackageRequest::new(
+                request.content_folder.clone(),
+                setup_file,
+                request.output_folder.clone(),
+            )
+            .with_verbosity(request.verbosity)
+            .with_strictness(strictness);
+            if let Some(name) = output_name {
+                package_request.set_output_name(name);
+            }
+            for (key, value) in content_tags {
+                package_request.add_content_tag(key, value);
+            }
+
+            super::package(&package_request).map(|p| ProjectImportResult::Intune(Box::new(p)))
+        }
+        #[cfg(feature = "macos")]
+        ProjectBuild::Macos {
+            identifier,
+            version,
+            install_location,
+            output_name,
+            allow_special_modes,
+        } => {
+            use crate::models::macos::MacosPkgRequest;
+
+            // Scripts must exist on disk for the macOS packager
+            let scripts = if project.scripts.is_empty() {
+                None
+            } else {
+                let staging = super::workspace::StagingDir::create("project")?;
+                restore_scripts(&project.scripts, &staging.path)?;
+                Some(staging)
+            };
+
+            let mut pkg_request = MacosPkgRequest::new(
+                request.content_folder.clone(),
+                identifier,
+                version,
+                request.output_folder.clone(),
+            )
+            .with_install_location(install_location)
+            .with_allow_special_modes(allow_special_modes)
+            .with_verbosity(request.verbosity)
+            .with_strictness(strictness);
+            if let Some(name) = output_name {
+                pkg_request.set_output_name(name);
+            }
+            if let Some(scripts) = &scripts {
+                pkg_request.set_scripts_folder(scripts.path.clone());
+            }
+
+            crate::macos::package(pkg_request).map(|p| ProjectImportResult::Macos(Box::new(p)))
+        }
+        #[cfg(not(feature = "macos"))]
+        ProjectBuild::Macos { .. } => Err(PackageError::InvalidArgument {
+            reason: "macOS packaging is not enabled. Build with --features macos".to_string(),
+        }),
+    }
+}
+
+/// Read every regular file under the scripts folder.
+fn collect_scripts(folder: &Path) -> PackageResult<Vec<ProjectScript>> {
+    if !folder.is_dir() {
+        return Err(PackageError::ScriptsFolderNotFound {
+            path: folder.to_path_buf(),
+        });
+    }
+
+    let mut scripts = Vec::new();
+    for entry in WalkDir::new(folder).sort_by_file_name() {
+        let entry = entry.map_err(|e| PackageError::source_read(folder.to_path_buf(), e))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let data = fs::read(entry.path())
+            .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))?;
+        scripts.push(ProjectScript {
+            path: entry
+                .path()
+                .strip_prefix(folder)
+                .unwrap_or(entry.path())
+                .to_path_buf(),
+            data,
+        });
+    }
+
+    Ok(scripts)
+}
+
+/// Write bundled scripts under `folder`, marked executable.
+#[cfg_attr(not(feature = "macos"), allow(dead_code))]
+fn restore_scripts(scripts: &[ProjectScript], folder: &Path) -> PackageResult<()> {
+    for script in scripts {
+        if !script
+            .path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(PackageError::ProjectError {
+                path: script.path.clone(),
+                reason: "Script path escapes the scripts folder".to_string(),
+            });
+        }
+
+        let target = folder.join(&script.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+        }
+        fs::write(&target, &script.data)
+            .map_err(|e| PackageError::output_write(target.clone(), e))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&target, fs::Permissions::from_mode(0o755));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::Verbosity;
+    use tempfile::TempDir;
+
+    fn intune_build() -> ProjectBuild {
+        ProjectBuild::Intune {
+            setup_file: "setup.exe".to_string(),
+            output_name: Some("App".to_string()),
+            content_tags: vec![("build".to_string(), "7".to_string())],
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "macos"), allow(irrefutable_let_patterns))]
+    fn test_export_and_import_intune() {
+        let temp = TempDir::new().unwrap();
+        let content = temp.path().join("content");
+        fs::create_dir_all(content.join("lib")).unwrap();
+        fs::write(content.join("setup.exe"), "setup").unwrap();
+        fs::write(content.join("lib/data.dll"), "dll").unwrap();
+        let project_file = temp.path().join("app.project.json");
+
+        let project = export_project(
+            &content,
+            intune_build(),
+            Config::default(),
+            None,
+            &project_file,
+        )
+        .unwrap();
+        assert_eq!(project.files.len(), 2);
+
+        let request = ProjectImportRequest::new(&project_file, &content, temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let ProjectImportResult::Intune(package) = import_project(&request).unwrap() else {
+            panic!("expected an Intune package");
+        };
+        assert!(package.output_path.ends_with("App.intunewin"));
+        assert_eq!(
+            package.content_tags,
+            vec![("build".to_string(), "7".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_verify_content_reports_differences() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("setup.exe"), "setup").unwrap();
+        let project_file = temp.path().join("p.json");
+        let project = export_project(
+            temp.path(),
+            intune_build(),
+            Config::default(),
+            None,
+            &project_file,
+        )
+        .unwrap();
+
+        fs::write(temp.path().join("setup.exe"), "patched").unwrap();
+        let err = verify_content(&project, temp.path(), Some(&project_file)).unwrap_err();
+        assert!(err.to_string().contains("changed setup.exe"));
+    }
+
+    #[test]
+    fn test_restore_scripts_rejects_escaping_paths() {
+        let temp = TempDir::new().unwrap();
+        let scripts = [ProjectScript {
+            path: "../evil".into(),
+            data: Vec::new(),
+        }];
+        assert!(matches!(
+            restore_scripts(&scripts, temp.path()),
+            Err(PackageError::ProjectError { .. })
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+//! Password-protected ZIP archives (WinZip AES, AE-2).
+//!
+//! `intune extract --extract-to-zip` writes the decrypted content into one
+//! of these instead of loose files, so the plaintext never lands on the
+//! disk of a shared build agent. The `zip` crate only reads AES entries,
+//! so entries are written here: each file is deflated, then encrypted with
+//! AES-256 in CTR mode under a key derived from the password with
+//! PBKDF2-HMAC-SHA1 (1000 rounds, 16-byte random salt) and authenticated
+//! with a truncated HMAC-SHA1. As AE-2 prescribes, the CRC is left at zero
+//! so it gives nothing away about the plaintext. Directory entries carry
+//! no data and are stored unencrypted. The password and the keys derived
+//! for each entry are zeroized once they are no longer needed. 7-Zip,
+//! WinZip, libarchive and macOS Archive Utility read these archives.
+
+use std::io::{self, Write};
+
+use aes::Aes256;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use flate2::Compression as DeflateLevel;
+use flate2::write::DeflateEncoder;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use zeroize::Zeroizing;
+use zip::DateTime;
+
+/// Compression method announcing an AES-encrypted entry.
+const METHOD_AES: u16 = 99;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Header ID of the WinZip AES extra field.
+const AES_EXTRA_ID: u16 = 0x9901;
+/// AE-2: no CRC, the HMAC alone authenticates the data.
+const AES_VENDOR_VERSION: u16 = 2;
+/// AES-256.
+const AES_STRENGTH: u8 = 3;
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const VERIFIER_LEN: usize = 2;
+const MAC_LEN: usize = 10;
+const PBKDF2_ROUNDS: u32 = 1000;
+
+/// General purpose flags: encrypted, UTF-8 names.
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_UTF8: u16 = 0x0800;
+
+/// Version needed to extract AES entries (5.1), and plain ones (2.0).
+const VERSION_AES: u16 = 51;
+const VERSION_DEFAULT: u16 = 20;
+/// Version made by: Unix, so external attributes carry the file mode.
+const VERSION_MADE_BY: u16 = (3 << 8) | VERSION_AES;
+
+const MSDOS_DIRECTORY: u32 = 0x10;
+
+/// Writes a ZIP archive whose files are encrypted with `password`.
+pub struct AesZipWriter<W: Write> {
+    inner: W,
+    password: Zeroizing<Vec<u8>>,
+    offset: u64,
+    central_directory: Vec<u8>,
+    entries: u64,
+}
+
+impl<W: Write> AesZipWriter<W> {
+    /// Start an archive on `inner`.
+    pub fn new(inner: W, password: &str) -> Self {
+        Self {
+            inner,
+            password: Zeroizing::new(password.as_bytes().to_vec()),
+            offset: 0,
+            central_directory: Vec::new(),
+            entries: 0,
+        }
+    }
+
+    /// Add a directory entry; `name` must end with `/`.
+    pub fn add_directory(
+        &mut self,
+        name: &str,
+        modified: DateTime,
+        unix_mode: Option<u32>,
+    ) -> io::Result<()> {
+        let header = EntryHeader {
+            name,
+            version: VERSION_DEFAULT,
+            flags: FLAG_UTF8,
+            method: METHOD_STORED,
+            modified,
+            compressed_size: 0,
+            size: 0,
+            extra: Vec::new(),
+            external_attributes: (unix_mode.unwrap_or(0o40755) << 16) | MSDOS_DIRECTORY,
+        };
+        self.write_entry(&header, &[])
+    }
+
+    /// Add a file entry holding `data`, compressed and encrypted.
+    pub fn add_file(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        modified: DateTime,
+        unix_mode: Option<u32>,
+    ) -> io::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), DeflateLevel::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        let (method, mut payload) = if deflated.len() < data.len() {
+            (METHOD_DEFLATED, deflated)
+        } else {
+            (METHOD_STORED, data.to_vec())
+        };
+
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let keys = derive_keys(&self.password, &salt);
+        let (encryption_key, rest) = keys.split_at(KEY_LEN);
+        let (mac_key, verifier) = rest.split_at(KEY_LEN);
+
+        // WinZip counts blocks little-endian, starting at 1
+        let mut counter = [0u8; 16];
+        counter[0] = 1;
+        ctr::Ctr128LE::<Aes256>::new(encryption_key.into(), &counter.into())
+            .apply_keystream(&mut payload);
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(mac_key).expect("HMAC accepts keys of any length");
+        mac.update(&payload);
+        let mac = mac.finalize().into_bytes();
+
+        let mut body = Vec::with_capacity(SALT_LEN + VERIFIER_LEN + payload.len() + MAC_LEN);
+        body.extend_from_slice(&salt);
+        body.extend_from_slice(verifier);
+        body.extend_from_slice(&payload);
+        body.extend_from_slice(&mac[..MAC_LEN]);
+
+        let mut extra = Vec::with_capacity(11);
+        extra.extend_from_slice(&AES_EXTRA_ID.to_le_bytes());
+        extra.extend_from_slice(&7u16.to_le_bytes());
+        extra.extend_from_slice(&AES_VENDOR_VERSION.to_le_bytes());
+        extra.extend_from_slice(b"AE");
+        extra.push(AES_STRENGTH);
+        extra.extend_from_slice(&method.to_le_bytes());
+
+        let header = EntryHeader {
+            name,
+            version: VERSION_AES,
+            flags: FLAG_ENCRYPTED | FLAG_UTF8,
+            method: METHOD_AES,
+            modified,
+            compressed_size: zip32(body.len() as u64, name)?,
+            size: zip32(data.len() as u64, name)?,
+            extra,
+            external_attributes: unix_mode.unwrap_or(0o100644) << 16,
+        };
+        self.write_entry(&header, &body)
+    }
+
+    /// Write the central directory and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let entries = u16::try_from(self.entries).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "more than 65535 entries do not fit in a password-protected archive",
+            )
+        })?;
+        let directory_offset = zip32(self.offset, "central directory")?;
+        let directory_size = zip32(self.central_directory.len() as u64, "central directory")?;
+
+        self.inner.write_all(&self.central_directory)?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&entries.to_le_bytes());
+        end.extend_from_slice(&entries.to_le_bytes());
+        end.extend_from_slice(&directory_size.to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.inner.write_all(&end)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_entry(&mut self, header: &EntryHeader<'_>, body: &[u8]) -> io::Result<()> {
+        let offset = zip32(self.offset, header.name)?;
+        let name = header.name.as_bytes();
+
+        let mut local = Vec::with_capacity(30 + name.len() + header.extra.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.write_common(&mut local);
+        local.extend_from_slice(name);
+        local.extend_from_slice(&header.extra);
+        self.inner.write_all(&local)?;
+        self.inner.write_all(body)?;
+        self.offset += (local.len() + body.len()) as u64;
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&VERSION_MADE_BY.to_le_bytes());
+        header.write_common(central);
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&header.external_attributes.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name);
+        central.extend_from_slice(&header.extra);
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+/// Fields shared by the local and central headers of an entry.
+struct EntryHeader<'a> {
+    name: &'a str,
+    version: u16,
+    flags: u16,
+    method: u16,
+    modified: DateTime,
+    compressed_size: u32,
+    size: u32,
+    extra: Vec<u8>,
+    external_attributes: u32,
+}
+
+impl EntryHeader<'_> {
+    /// From "version needed" to "extra field length".
+    fn write_common(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.method.to_le_bytes());
+        out.extend_from_slice(&self.modified.timepart().to_le_bytes());
+        out.extend_from_slice(&self.modified.datepart().to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes()); // CRC-32, zero under AE-2
+        out.extend_from_slice(&self.compressed_size.to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.extra.len() as u16).to_le_bytes());
+    }
+}
+
+/// Encryption key, MAC key and password verifier for one entry, zeroized
+/// when the entry is written.
+fn derive_keys(password: &[u8], salt: &[u8]) -> Zeroizing<[u8; 2 * KEY_LEN + VERIFIER_LEN]> {
+    let mut keys = Zeroizing::new([0u8; 2 * KEY_LEN + VERIFIER_LEN]);
+    pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, PBKDF2_ROUNDS, keys.as_mut());
+    keys
+}
+
+/// Sizes and offsets past 4 GiB would need ZIP64, which AES readers
+/// handle inconsistently.
+fn zip32(value: u64, what: &str) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} exceeds 4 GiB, too large for a password-protected archive",
+                what
+            ),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_round_trip() {
+        let text = b"Hello from a protected archive\n".repeat(100);
+        let mut writer = AesZipWriter::new(Cursor::new(Vec::new()), "s3cret");
+        writer
+            .add_directory("docs/", DateTime::default(), None)
+            .unwrap();
+        writer
+            .add_file(
+                "docs/readme.txt",
+                &text,
+                DateTime::default(),
+                Some(0o100644),
+            )
+            .unwrap();
+        writer
+            .add_file("tiny.bin", b"\x00\x01", DateTime::default(), Some(0o100755))
+            .unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert!(archive.by_index(0).unwrap().is_dir());
+
+        // Encrypted entries cannot be read without the password
+        assert!(archive.by_name("docs/readme.txt").is_err());
+        assert!(
+            archive
+                .by_name_decrypt("docs/readme.txt", b"wrong")
+                .unwrap()
+                .is_err()
+        );
+
+        let mut content = Vec::new();
+        archive
+            .by_name_decrypt("docs/readme.txt", b"s3cret")
+            .unwrap()
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, text);
+
+        let mut tiny = archive
+            .by_name_decrypt("tiny.bin", b"s3cret")
+            .unwrap()
+            .unwrap();
+        assert_eq!(tiny.unix_mode(), Some(0o100755));
+        let mut content = Vec::new();
+        tiny.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"\x00\x01");
+    }
+}
@@ -0,0 +1,140 @@
+//! App relationships files, bundled into packages and read back from them.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use zip::read::ZipArchive;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::relationships::AppRelationships;
+
+use super::RELATIONSHIPS_ENTRY;
+
+/// Largest bundled relationships file read back from a package.
+const MAX_RELATIONSHIPS_SIZE: u64 = 1024 * 1024;
+
+/// Load and check a relationships file.
+pub fn load_relationships(path: &Path) -> PackageResult<AppRelationships> {
+    let text = fs::read_to_string(path).map_err(|e| PackageError::RelationshipsError {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    AppRelationships::parse(&text).map_err(|reason| PackageError::RelationshipsError {
+        path: path.to_path_buf(),
+        reason,
+    })
+}
+
+/// Read the bundled relationships, returning `None` when there are none.
+pub fn extract_relationships<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    input_path: &Path,
+) -> PackageResult<Option<AppRelationships>> {
+    let invalid = |reason: String| PackageError::InvalidIntunewinFile {
+        path: input_path.to_path_buf(),
+        reason,
+    };
+    let file = match archive.by_name(RELATIONSHIPS_ENTRY) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(invalid(format!("Failed to open relationships: {}", e))),
+    };
+
+    if file.size() > MAX_RELATIONSHIPS_SIZE {
+        return Err(invalid(format!(
+            "Relationships are {} bytes, more than the {} byte limit",
+            file.size(),
+            MAX_RELATIONSHIPS_SIZE
+        )));
+    }
+    let mut text = String::new();
+    file.take(MAX_RELATIONSHIPS_SIZE)
+        .read_to_string(&mut text)
+        .map_err(|e| invalid(format!("Failed to read relationships: {}", e)))?;
+    AppRelationships::parse(&text)
+        .map(Some)
+        .map_err(|reason| invalid(format!("Invalid relationships: {}", reason)))
+}
+
+/// Read the relationships bundled in the `.intunewin` at `path`, without
+/// decrypting its content.
+pub fn inspect_relationships(path: &Path) -> PackageResult<Option<AppRelationships>> {
+    let file = File::open(path).map_err(|e| PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason: format!("Failed to open file: {}", e),
+    })?;
+    let mut archive =
+        ZipArchive::new(BufReader::new(file)).map_err(|e| PackageError::InvalidIntunewinFile {
+            path: path.to_path_buf(),
+            reason: format!("Invalid ZIP archive: {}", e),
+        })?;
+    extract_relationships(&mut archive, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use std::io::{Cursor, Write};
+    use tempfile::TempDir;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn archive_with(relationships: &[u8]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(RELATIONSHIPS_ENTRY, FileOptions::default())
+            .unwrap();
+        zip.write_all(relationships).unwrap();
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_relationships_are_bundled() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        let sidecar = temp.path().join("relationships.json");
+        fs::write(
+            &sidecar,
+            r#"{ "dependencies": [{ "app": "VC++ 2022" }], "supersedes": [{ "app": "App 1.0" }] }"#,
+        )
+        .unwrap();
+
+        let request = PackageRequest::new(&source, "setup.exe", temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let plain = package(&request).unwrap();
+        assert_eq!(inspect_relationships(&plain.output_path).unwrap(), None);
+
+        let request = request
+            .with_output_name("related")
+            .with_relationships(&sidecar);
+        let result = package(&request).unwrap();
+        let bundled = inspect_relationships(&result.output_path).unwrap().unwrap();
+        assert_eq!(bundled, load_relationships(&sidecar).unwrap());
+        assert_eq!(result.relationships, Some(bundled));
+
+        fs::write(&sidecar, r#"{ "dependencies": [{ "version": "1" }] }"#).unwrap();
+        assert!(matches!(
+            package(&request.with_output_name("invalid")),
+            Err(PackageError::RelationshipsError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_crafted_relationships_fail_cleanly() {
+        let path = Path::new("crafted.intunewin");
+        let deep = "[".repeat(1_000_000);
+        assert!(matches!(
+            extract_relationships(&mut archive_with(deep.as_bytes()), path),
+            Err(PackageError::InvalidIntunewinFile { .. })
+        ));
+
+        let large = vec![b' '; MAX_RELATIONSHIPS_SIZE as usize + 1];
+        let err = extract_relationships(&mut archive_with(&large), path).unwrap_err();
+        assert!(err.to_string().contains("byte limit"));
+    }
+}
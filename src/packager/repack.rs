@@ -0,0 +1,298 @@
+//! Changing the Detection.xml of an existing `.intunewin`.
+//!
+//! The content is decrypted in memory, checked against the new settings and
+//! encrypted again with fresh keys. Metadata entries other than
+//! Detection.xml (artifact record, content tags, transforms) are copied as
+//! they are.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Cursor, Read};
+use std::time::Instant;
+
+use zip::read::ZipArchive;
+
+use crate::models::diff::FieldChange;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::{RepackRequest, RepackResult};
+use crate::models::size::ByteSize;
+use crate::models::suggest::rank_suggestions;
+
+use super::encrypt::{decrypt_content, encrypt_stream, new_encryption_info};
+use super::metadata::generate_detection_xml;
+use super::{
+    CONTENT_ENTRY, DETECTION_ENTRY, EncryptedContent, extract_detection_metadata,
+    extract_encrypted_content, write_outer_zip,
+};
+
+/// Rewrite the package at `request.input` with the requested Detection.xml
+/// changes and new encryption keys.
+pub fn repack(request: &RepackRequest) -> PackageResult<RepackResult> {
+    let start_time = Instant::now();
+    request.validate()?;
+
+    if request.output.exists() && !request.verbosity.suppress_prompts() {
+        return Err(PackageError::OutputFileExists {
+            path: request.output.clone(),
+        });
+    }
+
+    let invalid = |reason: String| PackageError::InvalidIntunewinFile {
+        path: request.input.clone(),
+        reason,
+    };
+    let file = File::open(&request.input).map_err(|e| invalid(e.to_string()))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .map_err(|e| invalid(format!("Invalid ZIP archive: {}", e)))?;
+
+    let old = extract_detection_metadata(&mut archive, &request.input)?;
+    let encrypted = extract_encrypted_content(&mut archive, &request.input)?;
+    let content = decrypt_content(&encrypted, &old.encryption_info)?;
+    drop(encrypted);
+
+    // Our other metadata entries are carried over in their original order
+    let mut extra_metadata = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| invalid(format!("Failed to read entry #{}: {}", i, e)))?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || name == CONTENT_ENTRY || name == DETECTION_ENTRY {
+            continue;
+        }
+        let mut xml = String::new();
+        entry
+            .read_to_string(&mut xml)
+            .map_err(|e| invalid(format!("Failed to read '{}': {}", name, e)))?;
+        extra_metadata.push((name, xml));
+    }
+
+    let mut metadata = old.clone();
+    if let Some(setup_file) = &request.setup_file {
+        check_setup_file(&content, setup_file, request)?;
+        // The name tracks the setup file unless it was set apart from it
+        if metadata.name == metadata.setup_file {
+            metadata.name = setup_file.clone();
+        }
+        metadata.setup_file = setup_file.clone();
+    }
+    if let Some(name) = &request.name {
+        metadata.name = name.clone();
+    }
+
+    let mut changes = Vec::new();
+    for (field, before, after) in [
+        ("Name", &old.name, &metadata.name),
+        ("SetupFile", &old.setup_file, &metadata.setup_file),
+    ] {
+        if before != after {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                old: Some(before.clone()),
+                new: Some(after.clone()),
+            });
+        }
+    }
+
+    // Never reuse the keys of the original package; keep its digest algorithm
+    let digest_algorithm = metadata.encryption_info.file_digest_algorithm;
+    metadata.encryption_info = new_encryption_info(None)?;
+    metadata.encryption_info.file_digest_algorithm = digest_algorithm;
+    let mut encrypted = Cursor::new(Vec::new());
+    let encrypted_size = encrypt_stream(
+        &mut content.as_slice(),
+        &mut encrypted,
+        &mut metadata.encryption_info,
+    )?;
+    drop(content);
+    let encrypted = encrypted.into_inner();
+
+    let detection_xml = generate_detection_xml(&metadata)?;
+    let extra_metadata: Vec<(&str, String)> = extra_metadata
+        .iter()
+        .map(|(name, xml)| (name.as_str(), xml.clone()))
+        .collect();
+
+    let write_error = |e: std::io::Error| PackageError::output_write(request.output.clone(), e);
+    if let Some(parent) = request.output.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+    }
+    let file = File::create(&request.output).map_err(write_error)?;
+    write_outer_zip(
+        BufWriter::new(file),
+        &detection_xml,
+        &extra_metadata,
+        EncryptedContent {
+            reader: &mut encrypted.as_slice(),
+            size: encrypted_size,
+        },
+    )?;
+    let package_size = fs::metadata(&request.output)
+        .map(|m| m.len())
+        .map_err(write_error)?;
+
+    Ok(RepackResult {
+        output_path: request.output.clone(),
+        metadata,
+        changes,
+        package_size: ByteSize(package_size),
+        repack_time: start_time.elapsed(),
+    })
+}
+
+/// Check that `setup_file` is at the root of the decrypted content.
+fn check_setup_file(
+    content: &[u8],
+    setup_file: &str,
+    request: &RepackRequest,
+) -> PackageResult<()> {
+    let inner =
+        ZipArchive::new(Cursor::new(content)).map_err(|e| PackageError::DecryptionError {
+            reason: format!("Decrypted content is not a valid ZIP: {}", e),
+        })?;
+    let names: Vec<&str> = inner.file_names().collect();
+    if names.contains(&setup_file) {
+        return Ok(());
+    }
+
+    Err(PackageError::SetupFileNotFound {
+        file: setup_file.to_string(),
+        folder: request.input.clone(),
+        suggestions: rank_suggestions(
+            setup_file,
+            names
+                .iter()
+                .filter(|name| !name.ends_with('/'))
+                .map(|name| name.to_string()),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, UnpackRequest, Verbosity};
+    use crate::packager::{package, unpack};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn build(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("install.cmd"), "setup.exe /S").unwrap();
+        let request =
+            PackageRequest::new(&source, "setup.exe".to_string(), temp.path().join("out"))
+                .with_content_tag("build", "7")
+                .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_repack_setup_file() {
+        let temp = TempDir::new().unwrap();
+        let input = build(&temp);
+        let output = temp.path().join("repacked/app.intunewin");
+
+        let result = repack(
+            &RepackRequest::new(&input, &output)
+                .with_setup_file("install.cmd")
+                .with_verbosity(Verbosity::Silent),
+        )
+        .unwrap();
+        assert_eq!(result.metadata.setup_file, "install.cmd");
+        assert_eq!(result.metadata.name, "install.cmd");
+        assert_eq!(result.changes.len(), 2);
+        assert_eq!(result.changes[1].old.as_deref(), Some("setup.exe"));
+
+        // Fresh keys, same content, other metadata kept
+        let mut archive = ZipArchive::new(File::open(&input).unwrap()).unwrap();
+        let old = extract_detection_metadata(&mut archive, &input).unwrap();
+        assert_ne!(
+            old.encryption_info.encryption_key,
+            result.metadata.encryption_info.encryption_key
+        );
+        let unpacked = unpack(
+            &UnpackRequest::new(output.clone(), temp.path().join("unpacked"))
+                .with_verbosity(Verbosity::Silent),
+        )
+        .unwrap();
+        assert_eq!(unpacked.setup_file, "install.cmd");
+        assert_eq!(unpacked.content_tags.len(), 1);
+        assert_eq!(
+            fs::read_to_string(temp.path().join("unpacked/setup.exe")).unwrap(),
+            "setup"
+        );
+    }
+
+    #[test]
+    fn test_repack_name_and_errors() {
+        let temp = TempDir::new().unwrap();
+        let input = build(&temp);
+        let output = temp.path().join("renamed.intunewin");
+
+        let result = repack(&RepackRequest::new(&input, &output).with_name("My App")).unwrap();
+        assert_eq!(result.metadata.name, "My App");
+        assert_eq!(result.metadata.setup_file, "setup.exe");
+        assert_eq!(result.changes.len(), 1);
+
+        // The output exists now
+        assert!(matches!(
+            repack(&RepackRequest::new(&input, &output)),
+            Err(PackageError::OutputFileExists { .. })
+        ));
+        assert!(matches!(
+            repack(&RepackRequest::new(&input, &input)),
+            Err(PackageError::InvalidArgument { .. })
+        ));
+        match repack(
+            &RepackRequest::new(&input, temp.path().join("x.intunewin"))
+                .with_setup_file("Setup.exe"),
+        ) {
+            Err(PackageError::SetupFileNotFound { suggestions, .. }) => {
+                assert_eq!(suggestions, vec!["setup.exe".to_string()])
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_repack_keeps_third_party_elements() {
+        let temp = TempDir::new().unwrap();
+        let built = build(&temp);
+
+        // Add an MsiInfo block the way Microsoft's tool writes one
+        let input = temp.path().join("third-party.intunewin");
+        let mut archive = ZipArchive::new(File::open(&built).unwrap()).unwrap();
+        let mut writer = zip::ZipWriter::new(File::create(&input).unwrap());
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            if entry.name() != DETECTION_ENTRY {
+                writer.raw_copy_file(entry).unwrap();
+                continue;
+            }
+            let mut xml = String::new();
+            entry.read_to_string(&mut xml).unwrap();
+            let xml = xml.replace(
+                "</ApplicationInfo>",
+                "<MsiInfo><MsiProductCode>{1}</MsiProductCode>\
+                 <MsiProductVersion>2.0</MsiProductVersion></MsiInfo></ApplicationInfo>",
+            );
+            writer
+                .start_file(DETECTION_ENTRY, zip::write::FileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut writer, xml.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output = temp.path().join("repacked.intunewin");
+        repack(&RepackRequest::new(&input, &output).with_name("My App")).unwrap();
+        let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        let metadata = extract_detection_metadata(&mut archive, &output).unwrap();
+        assert_eq!(metadata.name, "My App");
+        let msi = metadata.msi_info().unwrap();
+        assert_eq!(msi.product_code, "{1}");
+        assert_eq!(msi.product_version, "2.0");
+    }
+}
@@ -0,0 +1,152 @@
+//! Source reads and output writes that survive transient I/O errors.
+//!
+//! A read that fails partway through a file re-opens it and continues at
+//! the same offset, so a dropped connection to a share costs a retry rather
+//! than the whole run. See [`RetryPolicy`] for which errors are retried.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::retry::{RetryPolicy, is_transient};
+
+/// Open `path` for reading, retrying transient errors.
+pub fn open_file(path: &Path, policy: &RetryPolicy) -> PackageResult<RetryReader> {
+    let file = policy
+        .run("open", path, || File::open(path))
+        .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    Ok(RetryReader {
+        path: path.to_path_buf(),
+        file,
+        offset: 0,
+        policy: *policy,
+    })
+}
+
+/// Read all of `path`, retrying transient errors.
+pub fn read_file(path: &Path, policy: &RetryPolicy) -> PackageResult<Vec<u8>> {
+    if policy.retries() == 0 {
+        return fs::read(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e));
+    }
+    let mut data = Vec::new();
+    open_file(path, policy)?
+        .read_to_end(&mut data)
+        .map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+    Ok(data)
+}
+
+/// A file being read that is re-opened at the same offset after a
+/// transient error.
+#[derive(Debug)]
+pub struct RetryReader {
+    path: PathBuf,
+    file: File,
+    offset: u64,
+    policy: RetryPolicy,
+}
+
+impl Read for RetryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retry = 0;
+        loop {
+            match self.file.read(buf) {
+                Ok(n) => {
+                    self.offset += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if retry < self.policy.retries() && is_transient(&e) => {
+                    self.policy.wait_to_retry("read", &self.path, &e, retry);
+                    retry += 1;
+                    // The handle may be stale; a fresh one is positioned
+                    // where the last successful read ended
+                    if let Ok(mut file) = File::open(&self.path) {
+                        if file.seek(SeekFrom::Start(self.offset)).is_ok() {
+                            self.file = file;
+                        }
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// An output file whose writes, flushes and seeks are retried after
+/// transient errors.
+#[derive(Debug)]
+pub struct RetryWriter {
+    path: PathBuf,
+    file: File,
+    policy: RetryPolicy,
+}
+
+impl RetryWriter {
+    /// Create (or truncate) `path`, retrying transient errors.
+    pub fn create(path: &Path, policy: &RetryPolicy) -> io::Result<Self> {
+        let file = policy.run("create", path, || File::create(path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            policy: *policy,
+        })
+    }
+
+    /// Flush the file's contents to the storage device.
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        let Self { path, file, policy } = self;
+        policy.run("write", path, || file.sync_all())
+    }
+}
+
+impl Write for RetryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Self { path, file, policy } = self;
+        policy.run("write", path, || file.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let Self { path, file, policy } = self;
+        policy.run("write", path, || file.flush())
+    }
+}
+
+impl Seek for RetryWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let Self { path, file, policy } = self;
+        policy.run("write", path, || file.seek(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_file_with_retries() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("data.bin");
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        fs::write(&path, &data).unwrap();
+
+        assert_eq!(read_file(&path, &RetryPolicy::io(2)).unwrap(), data);
+        assert_eq!(read_file(&path, &RetryPolicy::none()).unwrap(), data);
+        assert!(matches!(
+            read_file(&temp.path().join("missing"), &RetryPolicy::io(3)),
+            Err(PackageError::SourceReadError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_retry_writer() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("out.bin");
+        let mut writer = RetryWriter::create(&path, &RetryPolicy::io(2)).unwrap();
+        writer.write_all(b"hello world").unwrap();
+        writer.seek(SeekFrom::Start(0)).unwrap();
+        writer.write_all(b"J").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"Jello world");
+    }
+}
@@ -0,0 +1,168 @@
+//! Safe extraction paths for archive entries.
+//!
+//! Entry names in a package are untrusted. Before anything is written, a
+//! name is normalized with both `/` and `\` as separators; absolute paths,
+//! drive letters, NUL bytes and `..` components are rejected. The target's
+//! deepest existing ancestor must then resolve inside the output folder, so
+//! a symbolic link already on disk cannot redirect a write elsewhere.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::error::{PackageError, PackageResult};
+
+/// Normalize the archive entry `name` to a relative path.
+///
+/// `.` components are dropped, so a name such as `./` gives an empty path
+/// standing for the output folder itself.
+pub fn sanitize_entry_name(name: &str) -> PackageResult<PathBuf> {
+    let unsafe_path = |reason: &str| PackageError::UnsafePath {
+        path: name.to_string(),
+        reason: reason.to_string(),
+    };
+
+    if name.contains('\0') {
+        return Err(unsafe_path("contains a NUL byte"));
+    }
+    if name.starts_with('/') || name.starts_with('\\') {
+        return Err(unsafe_path("absolute path"));
+    }
+
+    let mut path = PathBuf::new();
+    for (i, segment) in name.split(['/', '\\']).enumerate() {
+        match segment {
+            "" | "." => {}
+            ".." => return Err(unsafe_path("climbs out of the output folder")),
+            _ if i == 0 && is_drive_prefix(segment) => {
+                return Err(unsafe_path("drive-qualified path"));
+            }
+            _ => path.push(segment),
+        }
+    }
+    Ok(path)
+}
+
+/// The path under `root` where the entry `name` is extracted.
+///
+/// `root` must exist. Fails if the entry name is unsafe, if the target is
+/// an existing symbolic link, or if an existing parent folder resolves
+/// outside `root`.
+pub fn contained_path(root: &Path, name: &str) -> PackageResult<PathBuf> {
+    let relative = sanitize_entry_name(name)?;
+    let target = root.join(&relative);
+    if relative.as_os_str().is_empty() {
+        return Ok(target);
+    }
+
+    let unsafe_path = |reason: &str| PackageError::UnsafePath {
+        path: name.to_string(),
+        reason: reason.to_string(),
+    };
+
+    if fs::symlink_metadata(&target).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(unsafe_path("would be written through a symbolic link"));
+    }
+
+    let canonical_root =
+        fs::canonicalize(root).map_err(|e| PackageError::output_write(root.to_path_buf(), e))?;
+    // Broken links count as existing: folders would be created through them
+    let existing = target
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| fs::symlink_metadata(ancestor).is_ok())
+        .unwrap_or(root);
+    let resolved = fs::canonicalize(existing)
+        .map_err(|e| unsafe_path(&format!("cannot resolve '{}': {}", existing.display(), e)))?;
+    if !resolved.starts_with(&canonical_root) {
+        return Err(unsafe_path("resolves outside the output folder"));
+    }
+
+    Ok(target)
+}
+
+/// Whether `segment` is a Windows drive such as `C:`.
+fn is_drive_prefix(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sanitize_entry_name() {
+        assert_eq!(
+            sanitize_entry_name("./data//config.xml").unwrap(),
+            PathBuf::from("data/config.xml")
+        );
+        assert_eq!(
+            sanitize_entry_name("data\\app.dll").unwrap(),
+            PathBuf::from("data/app.dll")
+        );
+        assert_eq!(sanitize_entry_name("./").unwrap(), PathBuf::new());
+        // Dots inside names are fine
+        assert_eq!(
+            sanitize_entry_name("a..b/..c").unwrap(),
+            PathBuf::from("a..b/..c")
+        );
+
+        for name in [
+            "../evil.txt",
+            "data/../../evil.txt",
+            "..\\..\\evil.txt",
+            "/etc/passwd",
+            "\\\\server\\share\\evil.txt",
+            "C:\\Windows\\evil.dll",
+            "c:evil.txt",
+            "evil\0.txt",
+        ] {
+            assert!(
+                matches!(
+                    sanitize_entry_name(name),
+                    Err(PackageError::UnsafePath { .. })
+                ),
+                "{:?} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_contained_path() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("out");
+        fs::create_dir(&root).unwrap();
+
+        assert_eq!(
+            contained_path(&root, "a/b/c.txt").unwrap(),
+            root.join("a/b/c.txt")
+        );
+        assert_eq!(contained_path(&root, "./").unwrap(), root);
+        assert!(contained_path(&root, "../c.txt").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_contained_path_rejects_symlinks_out() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("out");
+        let outside = temp.path().join("outside");
+        fs::create_dir(&root).unwrap();
+        fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("file"), root.join("file")).unwrap();
+
+        let err = contained_path(&root, "link/sub/evil.txt").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("resolves outside the output folder")
+        );
+        let err = contained_path(&root, "file").unwrap_err();
+        assert!(err.to_string().contains("symbolic link"));
+
+        std::os::unix::fs::symlink(outside.join("missing"), root.join("broken")).unwrap();
+        assert!(contained_path(&root, "broken/sub/evil.txt").is_err());
+    }
+}
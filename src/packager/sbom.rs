@@ -0,0 +1,266 @@
+//! Software bills of materials for packaged content.
+//!
+//! Every file is hashed with SHA-1 (which SPDX requires) and SHA-256.
+//! Products are identified from the version resources of Windows
+//! executables, the Property table of Windows Installer databases and,
+//! with macOS support, the `Info.plist` of application bundles. Files
+//! whose metadata cannot be read are still listed, without a product.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::models::blocklist::BlocklistMatch;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::sbom::{
+    ProductSource, Sbom, SbomBlocklistMatch, SbomFile, SbomFormat, SbomProduct,
+};
+
+use super::content::ContentProvider;
+use super::manifest::to_hex;
+use super::{msi, pe};
+
+/// Windows Installer databases larger than this are not read for
+/// product information.
+const MAX_MSI_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Extensions of files carrying a Windows version resource.
+const PE_EXTENSIONS: &[&str] = &["exe", "dll", "sys"];
+
+/// Hash the files at `paths` in `content` and add them to `sbom` under
+/// `prefix`, along with the products identified among them.
+pub fn scan_content(
+    sbom: &mut Sbom,
+    content: &dyn ContentProvider,
+    paths: impl IntoIterator<Item = PathBuf>,
+    prefix: &Path,
+) -> PackageResult<()> {
+    for path in paths {
+        let read_error = |e: io::Error| PackageError::source_read(content.root().join(&path), e);
+        let sbom_path = to_sbom_path(&prefix.join(&path));
+
+        let (size, sha256, sha1) = hash_reader(content.open(&path)?).map_err(read_error)?;
+        sbom.files.push(SbomFile {
+            path: sbom_path.clone(),
+            size,
+            sha256,
+            sha1,
+        });
+
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let product = if PE_EXTENSIONS.contains(&extension.as_str()) {
+            read_pe_product(content, &path, &sbom_path)
+        } else if extension == "msi" && size <= MAX_MSI_SIZE {
+            read_msi_product(content, &path, &sbom_path)
+        } else {
+            read_bundle_product(content, &path, prefix)
+        };
+        sbom.products.extend(product);
+    }
+
+    sbom.files.sort_by(|a, b| a.path.cmp(&b.path));
+    sbom.products.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(())
+}
+
+/// Flag the files of `matches`, found by the blocklist check of the
+/// content, in `sbom` under `prefix`.
+pub fn add_blocklist_matches(sbom: &mut Sbom, matches: &[BlocklistMatch], prefix: &Path) {
+    sbom.blocklist_matches
+        .extend(matches.iter().map(|matched| SbomBlocklistMatch {
+            path: to_sbom_path(&prefix.join(&matched.path)),
+            sha256: matched.sha256.clone(),
+            description: matched.description.clone(),
+        }));
+    sbom.blocklist_matches.sort_by(|a, b| a.path.cmp(&b.path));
+}
+
+/// Write `sbom` in `format` next to the package at `package`.
+pub fn write_sidecar(sbom: &Sbom, format: SbomFormat, package: &Path) -> PackageResult<PathBuf> {
+    let path = format.sidecar_path(package);
+    fs::write(&path, sbom.render(format))
+        .map_err(|e| PackageError::output_write(path.clone(), e))?;
+    Ok(path)
+}
+
+/// Size, SHA-256 and SHA-1 of everything in `reader`.
+fn hash_reader(mut reader: impl Read) -> io::Result<(u64, String, String)> {
+    let mut sha256 = Sha256::new();
+    let mut sha1 = Sha1::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        sha256.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((size, to_hex(&sha256.finalize()), to_hex(&sha1.finalize())))
+}
+
+fn to_sbom_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn read_pe_product(
+    content: &dyn ContentProvider,
+    path: &Path,
+    sbom_path: &str,
+) -> Option<SbomProduct> {
+    let mut reader = content.open(path).ok()?;
+    let info = pe::read_version_info(&mut reader).ok().flatten()?;
+    let name = info
+        .product_name
+        .or(info.file_description)
+        .filter(|name| !name.trim().is_empty())?;
+    Some(SbomProduct {
+        name,
+        version: info.product_version.or(info.file_version),
+        supplier: info.company_name,
+        path: sbom_path.to_string(),
+        source: ProductSource::VersionResource,
+    })
+}
+
+fn read_msi_product(
+    content: &dyn ContentProvider,
+    path: &Path,
+    sbom_path: &str,
+) -> Option<SbomProduct> {
+    let mut data = Vec::new();
+    content.open(path).ok()?.read_to_end(&mut data).ok()?;
+    let mut properties = msi::read_msi_properties(&data).ok()?;
+    let name = properties
+        .remove("ProductName")
+        .filter(|name| !name.trim().is_empty())?;
+    Some(SbomProduct {
+        name,
+        version: properties.remove("ProductVersion"),
+        supplier: properties.remove("Manufacturer"),
+        path: sbom_path.to_string(),
+        source: ProductSource::MsiProperties,
+    })
+}
+
+/// The product described by `path` when it is the `Info.plist` of an
+/// application bundle.
+#[cfg(feature = "macos")]
+fn read_bundle_product(
+    content: &dyn ContentProvider,
+    path: &Path,
+    prefix: &Path,
+) -> Option<SbomProduct> {
+    use crate::macos::bundle::{INFO_PLIST, parse_info_plist};
+
+    let plist_path = to_sbom_path(path);
+    let bundle = plist_path.strip_suffix(&format!("/{}", INFO_PLIST))?;
+    if !bundle.to_ascii_lowercase().ends_with(".app") {
+        return None;
+    }
+
+    let mut data = Vec::new();
+    content.open(path).ok()?.read_to_end(&mut data).ok()?;
+    let mut values = parse_info_plist(&data).ok()?;
+    let name = values
+        .remove("CFBundleName")
+        .or_else(|| values.remove("CFBundleIdentifier"))
+        .filter(|name| !name.trim().is_empty())?;
+    Some(SbomProduct {
+        name,
+        version: values
+            .remove("CFBundleShortVersionString")
+            .or_else(|| values.remove("CFBundleVersion")),
+        supplier: None,
+        path: to_sbom_path(&prefix.join(bundle)),
+        source: ProductSource::InfoPlist,
+    })
+}
+
+#[cfg(not(feature = "macos"))]
+fn read_bundle_product(
+    _content: &dyn ContentProvider,
+    _path: &Path,
+    _prefix: &Path,
+) -> Option<SbomProduct> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::content::MemoryContent;
+
+    #[test]
+    fn test_scan_content_hashes_files() {
+        let content = MemoryContent::from_files(
+            "memory",
+            [
+                ("setup.exe", b"not a PE file".to_vec()),
+                ("data/test.txt", b"test".to_vec()),
+            ],
+        )
+        .unwrap();
+
+        let mut sbom = Sbom::new("Test", 0);
+        scan_content(
+            &mut sbom,
+            &content,
+            [PathBuf::from("setup.exe"), PathBuf::from("data/test.txt")],
+            Path::new(""),
+        )
+        .unwrap();
+
+        assert_eq!(sbom.files.len(), 2);
+        let text = &sbom.files[0];
+        assert_eq!(text.path, "data/test.txt");
+        assert_eq!(text.size, 4);
+        assert_eq!(
+            text.sha256,
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+        assert_eq!(text.sha1, "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
+        // Unreadable version resources are not an error
+        assert!(sbom.products.is_empty());
+    }
+
+    #[cfg(feature = "macos")]
+    #[test]
+    fn test_scan_content_reads_bundle_versions() {
+        let plist = br#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0"><dict>
+<key>CFBundleName</key><string>Demo</string>
+<key>CFBundleShortVersionString</key><string>1.4</string>
+</dict></plist>"#;
+        let content =
+            MemoryContent::from_files("memory", [("Demo.app/Contents/Info.plist", plist.to_vec())])
+                .unwrap();
+
+        let mut sbom = Sbom::new("Demo", 0);
+        scan_content(
+            &mut sbom,
+            &content,
+            [PathBuf::from("Demo.app/Contents/Info.plist")],
+            Path::new("Applications"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            sbom.files[0].path,
+            "Applications/Demo.app/Contents/Info.plist"
+        );
+        let product = &sbom.products[0];
+        assert_eq!(product.name, "Demo");
+        assert_eq!(product.version.as_deref(), Some("1.4"));
+        assert_eq!(product.path, "Applications/Demo.app");
+        assert_eq!(product.source, ProductSource::InfoPlist);
+    }
+}
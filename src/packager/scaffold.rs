@@ -0,0 +1,261 @@
+//! Generating `Install.ps1`/`Uninstall.ps1` wrappers for `.exe` installers.
+//!
+//! Both scripts are rendered from one template: they relaunch in 64-bit
+//! PowerShell when Intune starts them in the 32-bit host, run the
+//! installer with the configured arguments, log to the Intune Management
+//! Extension log folder (collected by "Collect diagnostics"), translate
+//! exit codes and write or remove a tag file under `%ProgramData%` that a
+//! file detection rule can look for. The scripts are written with a UTF-8
+//! BOM and CRLF line endings, which Windows PowerShell 5.1 needs to read
+//! non-ASCII text correctly.
+
+use std::fs;
+
+use tracing::info_span;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::scaffold::{
+    INSTALL_SCRIPT, REBOOT_CODES, ScaffoldRequest, ScaffoldResult, UNINSTALL_SCRIPT,
+};
+
+use super::choco::ps_quote;
+
+const WRAPPER_TEMPLATE: &str = r#"# {{ACTION}} wrapper for {{APP_NAME}}, generated by iamawrapper.
+$ErrorActionPreference = 'Stop'
+
+# Intune runs scripts in 32-bit PowerShell on 64-bit Windows
+$sysNative = "$env:WINDIR\SysNative\WindowsPowerShell\v1.0\powershell.exe"
+if ($env:PROCESSOR_ARCHITEW6432 -eq 'AMD64' -and (Test-Path $sysNative)) {
+    & $sysNative -ExecutionPolicy Bypass -NoProfile -File $PSCommandPath
+    exit $LASTEXITCODE
+}
+
+$AppName = {{APP_NAME_QUOTED}}
+$Action = {{ACTION_QUOTED}}
+$FilePath = {{FILE}}
+$Arguments = {{ARGUMENTS}}
+$SuccessCodes = @({{SUCCESS_CODES}})
+$RebootCodes = @({{REBOOT_CODES}})
+$TagFile = Join-Path (Join-Path $env:ProgramData {{TAG_FOLDER}}) "$($AppName).tag"
+$WriteTag = {{WRITE_TAG}}
+
+$LogFolder = Join-Path $env:ProgramData 'Microsoft\IntuneManagementExtension\Logs'
+$LogFile = Join-Path $LogFolder "$($AppName)-$($Action).log"
+
+function Write-Log([string]$Message) {
+    $line = '{0:yyyy-MM-dd HH:mm:ss} {1}' -f (Get-Date), $Message
+    Write-Output $line
+    try { Add-Content -Path $LogFile -Value $line -Encoding UTF8 } catch { }
+}
+
+$FilePath = [Environment]::ExpandEnvironmentVariables($FilePath)
+if (-not [IO.Path]::IsPathRooted($FilePath)) {
+    $FilePath = Join-Path $PSScriptRoot $FilePath
+}
+
+try {
+    New-Item -ItemType Directory -Path $LogFolder -Force | Out-Null
+    Write-Log "$Action of $AppName started: $FilePath $Arguments"
+    $startArgs = @{ FilePath = $FilePath; Wait = $true; PassThru = $true; WindowStyle = 'Hidden' }
+    if ($Arguments) { $startArgs.ArgumentList = $Arguments }
+    $exitCode = (Start-Process @startArgs).ExitCode
+    Write-Log "Installer exited with code $exitCode"
+} catch {
+    Write-Log "Failed to run the installer: $_"
+    exit 1
+}
+
+if ($SuccessCodes -contains $exitCode -or $RebootCodes -contains $exitCode) {
+    if ($WriteTag) {
+        New-Item -ItemType Directory -Path (Split-Path $TagFile) -Force | Out-Null
+        Set-Content -Path $TagFile -Value (Get-Date -Format o)
+    } else {
+        Remove-Item -Path $TagFile -Force -ErrorAction SilentlyContinue
+    }
+}
+
+if ($RebootCodes -contains $exitCode) {
+    Write-Log "$Action succeeded, a reboot is required"
+    exit $exitCode
+}
+if ($SuccessCodes -contains $exitCode) {
+    Write-Log "$Action succeeded"
+    exit 0
+}
+Write-Log "$Action failed with exit code $exitCode"
+exit $exitCode
+"#;
+
+/// Write `Install.ps1` and `Uninstall.ps1` into the source folder.
+pub fn scaffold(request: &ScaffoldRequest) -> PackageResult<ScaffoldResult> {
+    let _span = info_span!("scaffold", source = %request.source_folder.display()).entered();
+    request.validate()?;
+
+    let install_script = request.source_folder.join(INSTALL_SCRIPT);
+    let uninstall_script = request.source_folder.join(UNINSTALL_SCRIPT);
+    for (path, script) in [
+        (&install_script, generate_install_script(request)),
+        (&uninstall_script, generate_uninstall_script(request)),
+    ] {
+        let mut bytes = "\u{feff}".as_bytes().to_vec();
+        bytes.extend_from_slice(script.replace('\n', "\r\n").as_bytes());
+        fs::write(path, bytes).map_err(|e| PackageError::output_write(path.clone(), e))?;
+    }
+
+    Ok(ScaffoldResult {
+        install_script,
+        uninstall_script,
+        app_name: request.app_name(),
+        tag_file: request.tag_file(),
+    })
+}
+
+/// Generate the install wrapper (with `\n` line endings).
+pub fn generate_install_script(request: &ScaffoldRequest) -> String {
+    generate_wrapper(
+        request,
+        "Install",
+        &request.setup_file,
+        &request.install_args,
+    )
+}
+
+/// Generate the uninstall wrapper (with `\n` line endings).
+pub fn generate_uninstall_script(request: &ScaffoldRequest) -> String {
+    let file = request
+        .uninstall_file
+        .as_deref()
+        .unwrap_or(&request.setup_file);
+    generate_wrapper(request, "Uninstall", file, &request.uninstall_args)
+}
+
+fn generate_wrapper(request: &ScaffoldRequest, action: &str, file: &str, args: &str) -> String {
+    let app_name = request.app_name();
+    let codes = |codes: &[i32]| {
+        codes
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    render(
+        WRAPPER_TEMPLATE,
+        &[
+            ("ACTION", action.to_string()),
+            ("ACTION_QUOTED", ps_quote(action)),
+            ("APP_NAME", app_name.clone()),
+            ("APP_NAME_QUOTED", ps_quote(&app_name)),
+            ("FILE", ps_quote(&file.replace('/', "\\"))),
+            ("ARGUMENTS", ps_quote(args)),
+            ("SUCCESS_CODES", codes(&request.all_success_codes())),
+            ("REBOOT_CODES", codes(REBOOT_CODES)),
+            (
+                "TAG_FOLDER",
+                ps_quote(request.tag_folder.trim_matches('\\')),
+            ),
+            (
+                "WRITE_TAG",
+                if action == "Install" {
+                    "$true"
+                } else {
+                    "$false"
+                }
+                .to_string(),
+            ),
+        ],
+    )
+}
+
+/// Replace each `{{KEY}}` in `template` with its value. Placeholders
+/// without a value are left as they are, and values are not scanned for
+/// placeholders themselves.
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after.find("}}").and_then(|end| {
+            let key = &after[..end];
+            values
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render() {
+        let values = [("A", "x".to_string()), ("B", "{{A}}".to_string())];
+        assert_eq!(render("{{A}}-{{B}}-{{C}}", &values), "x-{{A}}-{{C}}");
+        assert_eq!(render("no placeholders {{", &values), "no placeholders {{");
+    }
+
+    #[test]
+    fn test_generate_scripts() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("bin")).unwrap();
+        fs::write(temp.path().join("bin/setup.exe"), "MZ").unwrap();
+        let request = ScaffoldRequest::new(temp.path(), "bin/setup.exe", "/S /D=C:\\It's Here")
+            .with_uninstall(Some(r"%ProgramFiles%\App\uninst.exe".to_string()), "/S")
+            .with_app_name("Contoso App")
+            .with_success_code(42);
+
+        let install = generate_install_script(&request);
+        assert!(!install.contains("{{"));
+        assert!(install.contains(r"$FilePath = 'bin\setup.exe'"));
+        assert!(install.contains(r"$Arguments = '/S /D=C:\It''s Here'"));
+        assert!(install.contains("$SuccessCodes = @(0, 1707, 42)"));
+        assert!(install.contains("$RebootCodes = @(3010, 1641)"));
+        assert!(install.contains("$AppName = 'Contoso App'"));
+        assert!(install.contains(r"Join-Path $env:ProgramData 'iamawrapper\Tags'"));
+        assert!(install.contains("$WriteTag = $true"));
+
+        let uninstall = generate_uninstall_script(&request);
+        assert!(!uninstall.contains("{{"));
+        assert!(uninstall.contains(r"$FilePath = '%ProgramFiles%\App\uninst.exe'"));
+        assert!(uninstall.contains("$Action = 'Uninstall'"));
+        assert!(uninstall.contains("$WriteTag = $false"));
+    }
+
+    #[test]
+    fn test_scaffold_writes_scripts() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("setup.exe"), "MZ").unwrap();
+        let request = ScaffoldRequest::new(temp.path(), "setup.exe", "/quiet");
+
+        let result = scaffold(&request).unwrap();
+        assert_eq!(result.tag_file, r"%ProgramData%\iamawrapper\Tags\setup.tag");
+
+        let bytes = fs::read(&result.install_script).unwrap();
+        assert!(bytes.starts_with(b"\xef\xbb\xbf# Install wrapper for setup"));
+        let text = String::from_utf8(bytes).unwrap();
+        assert_eq!(text.matches('\n').count(), text.matches("\r\n").count());
+        assert!(result.uninstall_script.is_file());
+
+        // Existing wrappers are kept unless overwriting is requested
+        assert!(matches!(
+            scaffold(&request),
+            Err(PackageError::OutputFileExists { .. })
+        ));
+        assert!(scaffold(&request.with_overwrite(true)).is_ok());
+    }
+}
@@ -0,0 +1,326 @@
+//! Self-test: build, validate and read back small packages in a temp folder.
+//!
+//! The sample content is generated: text, an empty file, nested folders, a
+//! non-ASCII name and pseudo-random data spanning several compression
+//! blocks. Each enabled format runs as a chain of stages, one per
+//! subsystem it exercises (container, encryption, payload archive, bill of
+//! materials), and every round trip is compared byte for byte. Once a
+//! stage fails the rest of its chain is reported as skipped.
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::{PackageRequest, UnpackRequest, Verbosity};
+use crate::models::selftest::{SelfTestReport, SelfTestStage, StageOutcome};
+
+use super::validate::validate_intunewin;
+use super::verify::verify;
+use super::workspace::StagingDir;
+
+/// Setup file written into the sample content.
+const SETUP_FILE: &str = "setup.exe";
+
+/// Size of the generated binary sample file.
+const RANDOM_FILE_SIZE: usize = 256 * 1024;
+
+/// Files of the sample content, relative to its root.
+fn sample_files() -> Vec<(&'static str, Vec<u8>)> {
+    // xorshift32: the same incompressible bytes on every platform
+    let mut state = 0x1234_5678u32;
+    let random = (0..RANDOM_FILE_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    vec![
+        (SETUP_FILE, b"MZ iamawrapper self-test".to_vec()),
+        ("lib/data.txt", b"self-test payload\n".repeat(64)),
+        ("lib/empty.dat", Vec::new()),
+        (
+            "lib/nested/deeper/config.json",
+            br#"{"self-test": true}"#.to_vec(),
+        ),
+        (
+            "docs/r\u{e9}sum\u{e9}.txt",
+            "non-ASCII name\n".as_bytes().to_vec(),
+        ),
+        ("bin/random.bin", random),
+    ]
+}
+
+/// Runs the stages of one format, skipping the rest after a failure.
+struct Chain<'a> {
+    report: &'a mut SelfTestReport,
+    failed: bool,
+}
+
+impl<'a> Chain<'a> {
+    fn new(report: &'a mut SelfTestReport) -> Self {
+        Self {
+            report,
+            failed: false,
+        }
+    }
+
+    fn stage(&mut self, name: &'static str, run: impl FnOnce() -> PackageResult<()>) {
+        let start = Instant::now();
+        let outcome = if self.failed {
+            StageOutcome::Skipped
+        } else {
+            match run() {
+                Ok(()) => StageOutcome::Pass,
+                Err(e) => {
+                    self.failed = true;
+                    StageOutcome::Fail(e.to_string())
+                }
+            }
+        };
+
+        self.report.stages.push(SelfTestStage {
+            name,
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+}
+
+/// Package, validate and unpack sample content for every enabled format.
+///
+/// Fails only when the temp workspace cannot be set up; stage failures are
+/// recorded in the report.
+pub fn self_test() -> PackageResult<SelfTestReport> {
+    let workspace = StagingDir::create("self-test")?;
+    let content = workspace.path.join("content");
+    write_sample(&content)?;
+
+    let mut report = SelfTestReport::default();
+    test_intune(&content, &workspace.path.join("intune"), &mut report);
+    #[cfg(feature = "macos")]
+    test_macos(&content, &workspace.path.join("macos"), &mut report);
+
+    Ok(report)
+}
+
+fn write_sample(root: &Path) -> PackageResult<()> {
+    for (path, data) in sample_files() {
+        let target = root.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PackageError::output_folder(parent.to_path_buf(), e))?;
+        }
+        fs::write(&target, data).map_err(|e| PackageError::output_write(target.clone(), e))?;
+    }
+    Ok(())
+}
+
+/// Check that `root` holds exactly the sample files.
+fn compare_sample(root: &Path) -> PackageResult<()> {
+    let files = sample_files();
+    for (path, expected) in &files {
+        let target = root.join(path);
+        let data = fs::read(&target).map_err(|e| PackageError::source_read(target.clone(), e))?;
+        if data != *expected {
+            return Err(PackageError::ValidationFailed {
+                path: target,
+                reason: "Unpacked file differs from the original".to_string(),
+            });
+        }
+    }
+
+    let unpacked = WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .count();
+    if unpacked != files.len() {
+        return Err(PackageError::ValidationFailed {
+            path: root.to_path_buf(),
+            reason: format!("Unpacked {} files instead of {}", unpacked, files.len()),
+        });
+    }
+    Ok(())
+}
+
+/// Check that `files` (path, contents) are exactly the sample files.
+#[cfg(feature = "macos")]
+fn compare_sample_files(package: &Path, files: &[(String, Vec<u8>)]) -> PackageResult<()> {
+    let mismatch = |reason: String| PackageError::ValidationFailed {
+        path: package.to_path_buf(),
+        reason,
+    };
+    let expected = sample_files();
+    for (path, data) in &expected {
+        match files.iter().find(|(name, _)| name == path) {
+            Some((_, found)) if found == data => {}
+            Some(_) => return Err(mismatch(format!("'{}' differs from the original", path))),
+            None => return Err(mismatch(format!("'{}' is missing", path))),
+        }
+    }
+    if files.len() != expected.len() {
+        return Err(mismatch(format!(
+            "Found {} files instead of {}",
+            files.len(),
+            expected.len()
+        )));
+    }
+    Ok(())
+}
+
+fn test_intune(content: &Path, work: &Path, report: &mut SelfTestReport) {
+    let output_folder = work.join("out");
+    let mut package_path = None;
+    let mut chain = Chain::new(report);
+
+    chain.stage("intune create", || {
+        let request = PackageRequest::new(content.to_path_buf(), SETUP_FILE, output_folder)
+            .with_verbosity(Verbosity::Silent);
+        package_path = Some(super::package(&request)?.output_path);
+        Ok(())
+    });
+    let package_path = package_path.unwrap_or_default();
+
+    chain.stage("intune validate", || {
+        validate_intunewin(&package_path).map(|_| ())
+    });
+
+    chain.stage("intune verify", || {
+        let result = verify(&package_path)?;
+        match result.first_failure() {
+            None => Ok(()),
+            Some((check, reason)) => Err(PackageError::ValidationFailed {
+                path: package_path.clone(),
+                reason: format!("{} check failed: {}", check, reason),
+            }),
+        }
+    });
+
+    chain.stage("intune unpack", || {
+        let unpacked = work.join("unpacked");
+        let request = UnpackRequest::new(package_path.clone(), unpacked.clone())
+            .with_verbosity(Verbosity::Silent);
+        super::unpack(&request)?;
+        compare_sample(&unpacked)
+    });
+}
+
+#[cfg(feature = "macos")]
+fn test_macos(content: &Path, work: &Path, report: &mut SelfTestReport) {
+    use crate::macos::bom::{cksum, read_bom};
+    use crate::macos::cpio::read_payload;
+    use crate::macos::validate::validate_pkg;
+    use crate::macos::xar::XarArchive;
+    use crate::models::macos::MacosPkgRequest;
+
+    const IDENTIFIER: &str = "com.iamawrapper.selftest";
+
+    // The component's files sit in a folder of their own
+    fn entry<'a>(xar: &'a XarArchive, name: &str) -> Option<&'a [u8]> {
+        xar.entries()
+            .iter()
+            .find(|e| e.name == name)
+            .map(|e| e.data.as_slice())
+    }
+
+    let mut package_path = None;
+    let mut archive = None;
+    let mut chain = Chain::new(report);
+
+    chain.stage("pkg create", || {
+        let request =
+            MacosPkgRequest::new(content.to_path_buf(), IDENTIFIER, "1.0", work.join("out"))
+                .with_verbosity(Verbosity::Silent);
+        package_path = Some(crate::macos::package(request)?.output_path);
+        Ok(())
+    });
+    let package_path = package_path.unwrap_or_default();
+    let invalid = |reason: &str| PackageError::ValidationFailed {
+        path: package_path.clone(),
+        reason: reason.to_string(),
+    };
+
+    // XAR container: header, table of contents, checksums, PackageInfo
+    chain.stage("pkg xar", || {
+        validate_pkg(&package_path)?;
+        let data = fs::read(&package_path)
+            .map_err(|e| PackageError::source_read(package_path.clone(), e))?;
+        let xar = XarArchive::read(&data)?;
+        match entry(&xar, "PackageInfo").map(String::from_utf8_lossy) {
+            Some(info) if info.contains(IDENTIFIER) => {}
+            _ => {
+                return Err(invalid(
+                    "PackageInfo is missing or has the wrong identifier",
+                ));
+            }
+        }
+        archive = Some(xar);
+        Ok(())
+    });
+
+    // CPIO payload: every sample file reads back byte for byte
+    let mut payload_files = Vec::new();
+    chain.stage("pkg cpio", || {
+        let xar = archive.as_ref().ok_or_else(|| invalid("No archive"))?;
+        let payload = entry(xar, "Payload").ok_or_else(|| invalid("Payload is missing"))?;
+        payload_files = read_payload(payload)?
+            .into_iter()
+            .filter(|record| !record.is_dir() && !record.is_symlink())
+            .map(|record| {
+                let path = record.path.trim_start_matches("./").to_string();
+                (path, record.data)
+            })
+            .collect();
+        compare_sample_files(&package_path, &payload_files)
+    });
+
+    // BOM: lists every payload file with its size and checksum
+    chain.stage("pkg bom", || {
+        let xar = archive.as_ref().ok_or_else(|| invalid("No archive"))?;
+        let bom = read_bom(entry(xar, "Bom").ok_or_else(|| invalid("Bom is missing"))?)?;
+        for (path, data) in &payload_files {
+            let listed = bom
+                .iter()
+                .find(|record| record.path == *path)
+                .is_some_and(|record| {
+                    record.size == data.len() as u64 && record.checksum == cksum(data)
+                });
+            if !listed {
+                return Err(PackageError::BomError {
+                    reason: format!("'{}' is missing or has the wrong size or checksum", path),
+                });
+            }
+        }
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test_passes() {
+        let report = self_test().unwrap();
+        assert!(report.passed(), "{:?}", report);
+        assert!(report.stages.iter().any(|s| s.name == "intune unpack"));
+    }
+
+    #[test]
+    fn test_chain_skips_after_failure() {
+        let mut report = SelfTestReport::default();
+        let mut chain = Chain::new(&mut report);
+        chain.stage("first", || Err(PackageError::Cancelled));
+        chain.stage("second", || Ok(()));
+
+        assert!(matches!(report.stages[0].outcome, StageOutcome::Fail(_)));
+        assert_eq!(report.stages[1].outcome, StageOutcome::Skipped);
+        assert_eq!(report.failures(), 2);
+    }
+}
@@ -0,0 +1,661 @@
+//! Where a source comes from: a local folder or archive, or an archive
+//! downloaded from a URL.
+//!
+//! `-c` takes any of:
+//!
+//! - a folder, or a `.zip`, `.tar`, `.tar.gz` or `.tgz` archive on disk
+//! - `https://artifacts.example.com/myapp.zip#sha256=<hex>`, or an
+//!   `http://` URL, which must carry the `#sha256=` fragment
+//! - `s3://bucket/builds/myapp.zip#sha256=<hex>` (`s3` feature), signed
+//!   with `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and the optional
+//!   `AWS_SESSION_TOKEN` for `AWS_REGION` (default `us-east-1`);
+//!   `AWS_ENDPOINT_URL` points it at an S3-compatible service instead
+//! - `azblob://account/container/myapp.zip#sha256=<hex>` (`azure` feature),
+//!   authorized by the SAS token in `AZURE_STORAGE_SAS_TOKEN`, if set
+//!
+//! A remote archive is downloaded into the run workspace and removed with
+//! it. The `#sha256=` fragment is optional for the other schemes; when
+//! given, the download is refused unless its SHA-256 matches. Credentials are read from the
+//! environment, never from the location, and query strings (such as
+//! pre-signed URL signatures) are left out of messages.
+
+#[cfg(any(feature = "s3", feature = "azure"))]
+use std::env;
+use std::path::{Path, PathBuf};
+
+use tracing::info;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::size::ByteSize;
+
+use super::archive::SourceArchive;
+use super::blocklist::sha256_file;
+use super::http::{CurlTransport, HttpRequest, HttpTransport};
+use super::workspace::StagingDir;
+
+/// Environment variable holding the SAS token for `azblob://` sources.
+pub const AZURE_SAS_TOKEN_ENV: &str = "AZURE_STORAGE_SAS_TOKEN";
+
+/// URL schemes of remote sources.
+const REMOTE_SCHEMES: &[&str] = &["http://", "https://", "s3://", "azblob://"];
+
+/// A source that can be made available on disk for packaging.
+pub trait SourceProvider {
+    /// Where the source comes from, for messages.
+    fn describe(&self) -> String;
+
+    /// Make the source available on disk, downloading it if needed.
+    fn fetch(&self) -> PackageResult<FetchedSource>;
+}
+
+/// A source on disk: a folder or an archive.
+///
+/// A downloaded source is removed when this is dropped.
+pub struct FetchedSource {
+    path: PathBuf,
+    _download: Option<StagingDir>,
+}
+
+impl FetchedSource {
+    /// Folder or archive to package.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl std::fmt::Debug for FetchedSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FetchedSource")
+            .field("path", &self.path)
+            .field("downloaded", &self._download.is_some())
+            .finish()
+    }
+}
+
+/// Whether `location` names a remote source rather than a path.
+pub fn is_remote(location: &Path) -> bool {
+    location
+        .to_str()
+        .is_some_and(|text| REMOTE_SCHEMES.iter().any(|scheme| text.starts_with(scheme)))
+}
+
+/// `location` for messages, without the query string of a URL.
+pub fn describe_location(location: &Path) -> String {
+    match location.to_str() {
+        Some(text) if is_remote(location) => redact_query(text),
+        _ => location.display().to_string(),
+    }
+}
+
+/// The provider for `location`: a path, or a URL with a supported scheme.
+pub fn source_provider(location: &Path) -> PackageResult<Box<dyn SourceProvider>> {
+    let text = match location.to_str() {
+        Some(text) if is_remote(location) => text,
+        _ => return Ok(Box::new(LocalSource::new(location))),
+    };
+
+    if text.starts_with("s3://") {
+        #[cfg(feature = "s3")]
+        return Ok(Box::new(S3Source::new(text)?));
+        #[cfg(not(feature = "s3"))]
+        return Err(PackageError::InvalidArgument {
+            reason: "S3 sources need a build with the `s3` feature".to_string(),
+        });
+    }
+    if text.starts_with("azblob://") {
+        #[cfg(feature = "azure")]
+        return Ok(Box::new(AzureBlobSource::new(text)?));
+        #[cfg(not(feature = "azure"))]
+        return Err(PackageError::InvalidArgument {
+            reason: "Azure Blob Storage sources need a build with the `azure` feature".to_string(),
+        });
+    }
+    Ok(Box::new(HttpSource::new(text)?))
+}
+
+/// Fetch the source at `location`.
+pub fn fetch_source(location: &Path) -> PackageResult<FetchedSource> {
+    source_provider(location)?.fetch()
+}
+
+/// A folder or archive on disk, used where it is.
+#[derive(Debug, Clone)]
+pub struct LocalSource {
+    path: PathBuf,
+}
+
+impl LocalSource {
+    /// Use the folder or archive at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SourceProvider for LocalSource {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn fetch(&self) -> PackageResult<FetchedSource> {
+        Ok(FetchedSource {
+            path: self.path.clone(),
+            _download: None,
+        })
+    }
+}
+
+/// An archive downloaded over HTTP(S).
+pub struct HttpSource {
+    url: String,
+    sha256: Option<String>,
+    transport: Box<dyn HttpTransport>,
+}
+
+impl HttpSource {
+    /// Download `url`, checking the SHA-256 in its `#sha256=` fragment.
+    ///
+    /// Plain `http://` downloads are neither private nor authenticated, so
+    /// they are only accepted with a checksum.
+    pub fn new(url: &str) -> PackageResult<Self> {
+        let (url, sha256) = split_checksum(url)?;
+        if url.starts_with("http://") && sha256.is_none() {
+            return Err(PackageError::InvalidArgument {
+                reason: format!(
+                    "'{}' is not encrypted; use https:// or pin the archive with '#sha256=<hex>'",
+                    redact_query(&url)
+                ),
+            });
+        }
+        Ok(Self {
+            url,
+            sha256,
+            transport: Box::new(CurlTransport::default()),
+        })
+    }
+
+    /// Send requests with `transport` instead of `curl`.
+    pub fn with_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+}
+
+impl SourceProvider for HttpSource {
+    fn describe(&self) -> String {
+        redact_query(&self.url)
+    }
+
+    fn fetch(&self) -> PackageResult<FetchedSource> {
+        download(
+            &*self.transport,
+            HttpRequest::new("GET", &self.url),
+            &self.describe(),
+            self.sha256.as_deref(),
+        )
+    }
+}
+
+/// An object downloaded from Amazon S3 or an S3-compatible service.
+#[cfg(feature = "s3")]
+pub struct S3Source {
+    bucket: String,
+    key: String,
+    sha256: Option<String>,
+    transport: Box<dyn HttpTransport>,
+}
+
+#[cfg(feature = "s3")]
+impl S3Source {
+    /// Download `s3://bucket/key`, checking the SHA-256 in its `#sha256=`
+    /// fragment.
+    pub fn new(location: &str) -> PackageResult<Self> {
+        let (location, sha256) = split_checksum(location)?;
+        let path = location.strip_prefix("s3://").unwrap_or(&location);
+        let (bucket, key) = path
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| PackageError::InvalidArgument {
+                reason: format!("'{}' must look like s3://bucket/key", location),
+            })?;
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            sha256,
+            transport: Box::new(CurlTransport::default()),
+        })
+    }
+
+    /// Send requests with `transport` instead of `curl`.
+    pub fn with_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// The signed request for the object at Unix time `now`, with
+    /// credentials and settings looked up by `var`.
+    fn request(
+        &self,
+        now: u64,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> PackageResult<HttpRequest> {
+        use super::sigv4::{AwsCredentials, SigV4, amz_date, sigv4_authorization, uri_encode};
+
+        let missing = |name: &str| PackageError::SourceDownloadError {
+            url: self.describe(),
+            reason: format!("{} is not set", name),
+        };
+        let credentials = AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID").ok_or_else(|| missing("AWS_ACCESS_KEY_ID"))?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY")
+                .ok_or_else(|| missing("AWS_SECRET_ACCESS_KEY"))?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        };
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| "us-east-1".to_string());
+
+        // Path-style for other endpoints, virtual-hosted style for AWS
+        let (base, host, uri) = match var("AWS_ENDPOINT_URL") {
+            Some(endpoint) => {
+                let endpoint = endpoint.trim_end_matches('/').to_string();
+                let host = endpoint
+                    .split_once("://")
+                    .map_or(endpoint.as_str(), |(_, rest)| rest)
+                    .to_string();
+                let uri = format!("/{}/{}", uri_encode(&self.bucket), uri_encode(&self.key));
+                (endpoint, host, uri)
+            }
+            None => {
+                let host = format!("{}.s3.{}.amazonaws.com", self.bucket, region);
+                (
+                    format!("https://{}", host),
+                    host,
+                    format!("/{}", uri_encode(&self.key)),
+                )
+            }
+        };
+
+        // An empty payload, as for any GET
+        let payload_sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let amz_date = amz_date(now);
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_sha256.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = sigv4_authorization(
+            &SigV4 {
+                method: "GET",
+                uri: &uri,
+                headers: &headers,
+                payload_sha256,
+                region: &region,
+                service: "s3",
+                amz_date: &amz_date,
+            },
+            &credentials,
+        );
+
+        let mut request = HttpRequest::new("GET", format!("{}{}", base, uri))
+            .with_header("Authorization", authorization);
+        // curl derives Host from the URL
+        for (name, value) in headers.into_iter().skip(1) {
+            request = request.with_header(name, value);
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(feature = "s3")]
+impl SourceProvider for S3Source {
+    fn describe(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.key)
+    }
+
+    fn fetch(&self) -> PackageResult<FetchedSource> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        download(
+            &*self.transport,
+            self.request(now, var)?,
+            &self.describe(),
+            self.sha256.as_deref(),
+        )
+    }
+}
+
+/// A blob downloaded from Azure Blob Storage.
+#[cfg(feature = "azure")]
+pub struct AzureBlobSource {
+    account: String,
+    path: String,
+    sha256: Option<String>,
+    transport: Box<dyn HttpTransport>,
+}
+
+#[cfg(feature = "azure")]
+impl AzureBlobSource {
+    /// Download `azblob://account/container/blob`, checking the SHA-256 in
+    /// its `#sha256=` fragment.
+    pub fn new(location: &str) -> PackageResult<Self> {
+        let (location, sha256) = split_checksum(location)?;
+        let rest = location.strip_prefix("azblob://").unwrap_or(&location);
+        let (account, path) = rest
+            .split_once('/')
+            .filter(|(account, path)| {
+                !account.is_empty()
+                    && path
+                        .split_once('/')
+                        .is_some_and(|(c, b)| !c.is_empty() && !b.is_empty())
+            })
+            .ok_or_else(|| PackageError::InvalidArgument {
+                reason: format!(
+                    "'{}' must look like azblob://account/container/blob",
+                    location
+                ),
+            })?;
+        Ok(Self {
+            account: account.to_string(),
+            path: path.to_string(),
+            sha256,
+            transport: Box::new(CurlTransport::default()),
+        })
+    }
+
+    /// Send requests with `transport` instead of `curl`.
+    pub fn with_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    fn request(&self) -> HttpRequest {
+        let mut url = format!(
+            "https://{}.blob.core.windows.net/{}",
+            self.account, self.path
+        );
+        if let Some(token) = env::var(AZURE_SAS_TOKEN_ENV)
+            .ok()
+            .filter(|token| !token.is_empty())
+        {
+            url.push('?');
+            url.push_str(token.trim_start_matches('?'));
+        }
+        HttpRequest::new("GET", url).with_header("x-ms-version", "2021-08-06")
+    }
+}
+
+#[cfg(feature = "azure")]
+impl SourceProvider for AzureBlobSource {
+    fn describe(&self) -> String {
+        format!("azblob://{}/{}", self.account, self.path)
+    }
+
+    fn fetch(&self) -> PackageResult<FetchedSource> {
+        download(
+            &*self.transport,
+            self.request(),
+            &self.describe(),
+            self.sha256.as_deref(),
+        )
+    }
+}
+
+/// Split the `#sha256=<hex>` fragment off `location`.
+fn split_checksum(location: &str) -> PackageResult<(String, Option<String>)> {
+    let Some((location, fragment)) = location.split_once('#') else {
+        return Ok((location.to_string(), None));
+    };
+    let invalid = || PackageError::InvalidArgument {
+        reason: format!(
+            "'#{}' must be '#sha256=' followed by 64 hex digits",
+            fragment
+        ),
+    };
+    let hex = fragment.strip_prefix("sha256=").ok_or_else(invalid)?;
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    Ok((location.to_string(), Some(hex.to_ascii_lowercase())))
+}
+
+/// `url` without its query string, which may hold credentials.
+fn redact_query(url: &str) -> String {
+    match url.split_once('?') {
+        Some((base, _)) => format!("{}?...", base),
+        None => url.to_string(),
+    }
+}
+
+/// Download the archive `request` asks for into the run workspace and
+/// check its SHA-256.
+fn download(
+    transport: &dyn HttpTransport,
+    request: HttpRequest,
+    description: &str,
+    sha256: Option<&str>,
+) -> PackageResult<FetchedSource> {
+    let failed = |reason: String| PackageError::SourceDownloadError {
+        url: description.to_string(),
+        reason,
+    };
+
+    // The archive is read by the name it has in the URL
+    let name = request
+        .url
+        .split(['?', '#'])
+        .next()
+        .and_then(|url| url.rsplit('/').next())
+        .filter(|name| SourceArchive::from_name(Path::new(name)).is_some())
+        .ok_or_else(|| {
+            failed("A remote source must be a .zip, .tar, .tar.gz or .tgz archive".to_string())
+        })?
+        .to_string();
+
+    let staging = StagingDir::create("download")?;
+    let path = staging.path.join(&name);
+    info!(source = %description, "Downloading source");
+    let response = transport
+        .send(&request.with_download(&path))
+        .map_err(failed)?;
+    if !response.is_success() {
+        return Err(failed(format!("HTTP status {}", response.status)));
+    }
+
+    let actual = sha256_file(&path).map_err(|e| PackageError::source_read(path.clone(), e))?;
+    if let Some(expected) = sha256 {
+        if actual != expected {
+            return Err(failed(format!(
+                "SHA-256 is {}, expected {}",
+                actual, expected
+            )));
+        }
+    }
+    let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+    info!(
+        source = %description,
+        size = %ByteSize(size),
+        sha256 = %actual,
+        "Downloaded source"
+    );
+
+    Ok(FetchedSource {
+        path,
+        _download: Some(staging),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::http::HttpResponse;
+    use std::fs;
+
+    /// Serves `body` with `status` and records the requested URL.
+    struct FileServer {
+        status: u16,
+        body: Vec<u8>,
+        requests: std::rc::Rc<std::cell::RefCell<Vec<HttpRequest>>>,
+    }
+
+    impl HttpTransport for FileServer {
+        fn send(&self, request: &HttpRequest) -> Result<HttpResponse, String> {
+            self.requests.borrow_mut().push(request.clone());
+            if let Some(path) = &request.download {
+                fs::write(path, &self.body).map_err(|e| e.to_string())?;
+            }
+            Ok(HttpResponse {
+                status: self.status,
+                body: Vec::new(),
+            })
+        }
+    }
+
+    fn server(status: u16, body: &[u8]) -> FileServer {
+        FileServer {
+            status,
+            body: body.to_vec(),
+            requests: Default::default(),
+        }
+    }
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_source_provider_kinds() {
+        assert!(is_remote(Path::new("https://example.com/app.zip")));
+        assert!(is_remote(Path::new("s3://bucket/app.zip")));
+        assert!(!is_remote(Path::new("./https/app.zip")));
+
+        let local = source_provider(Path::new("payload")).unwrap();
+        assert_eq!(local.describe(), "payload");
+        assert_eq!(local.fetch().unwrap().path(), Path::new("payload"));
+
+        let remote = source_provider(Path::new("https://example.com/app.zip?sig=secret")).unwrap();
+        assert_eq!(remote.describe(), "https://example.com/app.zip?...");
+    }
+
+    #[test]
+    fn test_http_source_downloads_and_checks_sha256() {
+        let url = format!("https://example.com/builds/app.zip#sha256={}", ABC_SHA256);
+        let source = HttpSource::new(&url)
+            .unwrap()
+            .with_transport(server(200, b"abc"));
+        let fetched = source.fetch().unwrap();
+        assert_eq!(fetched.path().file_name().unwrap(), "app.zip");
+        assert_eq!(fs::read(fetched.path()).unwrap(), b"abc");
+
+        // The download goes with the fetched source
+        let path = fetched.path().to_path_buf();
+        drop(fetched);
+        assert!(!path.exists());
+
+        let source = HttpSource::new(&url)
+            .unwrap()
+            .with_transport(server(200, b"abd"));
+        assert!(matches!(
+            source.fetch(),
+            Err(PackageError::SourceDownloadError { reason, .. }) if reason.contains("expected")
+        ));
+    }
+
+    #[test]
+    fn test_plain_http_source_needs_checksum() {
+        let error = source_provider(Path::new("http://example.com/app.zip?sig=secret"))
+            .err()
+            .unwrap();
+        assert!(matches!(error, PackageError::InvalidArgument { .. }));
+        assert!(!error.to_string().contains("secret"));
+
+        let pinned = format!("http://example.com/app.zip#sha256={}", ABC_SHA256);
+        let source = HttpSource::new(&pinned)
+            .unwrap()
+            .with_transport(server(200, b"abc"));
+        assert!(source.fetch().is_ok());
+    }
+
+    #[test]
+    fn test_http_source_rejects_bad_locations_and_replies() {
+        for bad in [
+            "https://example.com/app.zip#md5=abc",
+            "https://example.com/app.zip#sha256=abc",
+        ] {
+            assert!(matches!(
+                HttpSource::new(bad),
+                Err(PackageError::InvalidArgument { .. })
+            ));
+        }
+
+        let source = HttpSource::new("https://example.com/app.msi")
+            .unwrap()
+            .with_transport(server(200, b"msi"));
+        assert!(matches!(
+            source.fetch(),
+            Err(PackageError::SourceDownloadError { .. })
+        ));
+
+        let source = HttpSource::new("https://example.com/app.zip?token=secret")
+            .unwrap()
+            .with_transport(server(404, b""));
+        let err = source.fetch().unwrap_err().to_string();
+        assert!(err.contains("HTTP status 404"), "{}", err);
+        assert!(!err.contains("secret"), "{}", err);
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_s3_source_signs_request() {
+        let source = S3Source::new("s3://builds/app/1.0/app.zip").unwrap();
+        assert_eq!(source.describe(), "s3://builds/app/1.0/app.zip");
+        assert!(S3Source::new("s3://bucket-only").is_err());
+
+        let settings = |endpoint: Option<&'static str>| {
+            move |name: &str| match name {
+                "AWS_ACCESS_KEY_ID" => Some("AKIDEXAMPLE".to_string()),
+                "AWS_SECRET_ACCESS_KEY" => Some("secret".to_string()),
+                "AWS_REGION" => Some("eu-west-1".to_string()),
+                "AWS_ENDPOINT_URL" => endpoint.map(String::from),
+                _ => None,
+            }
+        };
+        let request = source.request(1_440_938_160, settings(None)).unwrap();
+        assert_eq!(
+            request.url,
+            "https://builds.s3.eu-west-1.amazonaws.com/app/1.0/app.zip"
+        );
+        let authorization = &request.headers[0].1;
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/eu-west-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ));
+
+        let request = source
+            .request(1_440_938_160, settings(Some("http://localhost:9000/")))
+            .unwrap();
+        assert_eq!(request.url, "http://localhost:9000/builds/app/1.0/app.zip");
+
+        assert!(matches!(
+            source.request(0, |_| None),
+            Err(PackageError::SourceDownloadError { .. })
+        ));
+    }
+
+    #[cfg(feature = "azure")]
+    #[test]
+    fn test_azure_blob_source_location() {
+        let source = AzureBlobSource::new("azblob://acct/builds/app.zip").unwrap();
+        assert_eq!(source.describe(), "azblob://acct/builds/app.zip");
+        assert!(
+            source
+                .request()
+                .url
+                .starts_with("https://acct.blob.core.windows.net/builds/app.zip")
+        );
+        assert!(AzureBlobSource::new("azblob://acct/container").is_err());
+    }
+}
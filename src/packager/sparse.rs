@@ -0,0 +1,118 @@
+//! Sparse file detection.
+//!
+//! Neither package format can carry holes: Intune extracts and the macOS
+//! Installer writes every file at its full length. The holes themselves
+//! deflate/gzip to almost nothing, so the package stays small, but a 2 GB
+//! sparse VHD occupies 2 GB on every device. Sparse files are detected while
+//! collecting content and reported so that growth is no surprise.
+
+use std::fs::Metadata;
+use std::path::Path;
+
+use crate::models::package::SparseFile;
+
+/// Files smaller than this are never reported as sparse.
+///
+/// Filesystems with inline data or transparent compression report small
+/// files with fewer allocated blocks than their length.
+pub const SPARSE_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Describe the file at `relative_path` if it is sparse on disk.
+///
+/// On Unix a file counts as sparse when less than half of its length is
+/// allocated; on Windows when it carries the sparse attribute.
+pub fn detect_sparse(relative_path: &Path, metadata: &Metadata) -> Option<SparseFile> {
+    if !metadata.is_file() || metadata.len() < SPARSE_MIN_SIZE {
+        return None;
+    }
+
+    let allocated = allocated_size(metadata);
+    let sparse = match allocated {
+        Some(allocated) => allocated < metadata.len() / 2,
+        None => has_sparse_attribute(metadata),
+    };
+
+    sparse.then(|| SparseFile {
+        relative_path: relative_path.to_path_buf(),
+        size: metadata.len(),
+        allocated,
+    })
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is always in 512-byte units
+    Some(metadata.blocks() * 512)
+}
+
+#[cfg(not(unix))]
+fn allocated_size(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(windows)]
+fn has_sparse_attribute(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+    metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0
+}
+
+#[cfg(not(windows))]
+fn has_sparse_attribute(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_small_and_empty_files_are_not_sparse() {
+        let temp = TempDir::new().unwrap();
+        let empty = temp.path().join("empty.txt");
+        fs::write(&empty, "").unwrap();
+        let small = temp.path().join("small.img");
+        fs::File::create(&small).unwrap().set_len(4096).unwrap();
+
+        for path in [&empty, &small] {
+            let metadata = fs::metadata(path).unwrap();
+            assert_eq!(detect_sparse(Path::new("f"), &metadata), None);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_detects_unallocated_file() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("disk.vhd");
+        fs::File::create(&path)
+            .unwrap()
+            .set_len(64 * 1024 * 1024)
+            .unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        // Only meaningful where the filesystem supports holes
+        if metadata.blocks() * 512 < metadata.len() / 2 {
+            let sparse = detect_sparse(Path::new("disk.vhd"), &metadata).unwrap();
+            assert_eq!(sparse.size, 64 * 1024 * 1024);
+            assert!(sparse.allocated.unwrap() < sparse.size);
+        }
+    }
+
+    #[test]
+    fn test_dense_file_is_not_sparse() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("dense.bin");
+        fs::write(&path, vec![1u8; SPARSE_MIN_SIZE as usize]).unwrap();
+
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(detect_sparse(Path::new("dense.bin"), &metadata), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Source entries that cannot be packaged.
+//!
+//! Named pipes, sockets and device nodes have no contents to archive, and
+//! opening a pipe blocks until something writes to it. Symlinks pointing at
+//! one of their own ancestors would be walked forever. Every walk over a
+//! source folder leaves them out; the collectors report them as
+//! [`SkippedEntry`]s, which `--strict` turns into an error.
+//!
+//! The Unix mode helpers shared by the macOS and Linux payload writers
+//! live here as well.
+
+#[cfg(any(feature = "macos", feature = "linux"))]
+use std::fs;
+use std::fs::FileType;
+use std::path::Path;
+#[cfg(any(feature = "macos", feature = "linux"))]
+use std::path::PathBuf;
+
+use walkdir::DirEntry;
+
+#[cfg(any(feature = "macos", feature = "linux"))]
+use crate::models::error::PackageError;
+use crate::models::error::PackageResult;
+use crate::models::package::{SkippedEntry, SpecialKind};
+use crate::models::strictness::{Check, Strictness};
+
+/// Whether macOS and Linux payload walks keep symbolic links as links.
+///
+/// Bundles rely on them (`Versions/Current` in frameworks), so they are
+/// archived with their targets instead of being followed. Outside Unix
+/// links cannot be read back faithfully and are still followed.
+pub(crate) const PRESERVE_SYMLINKS: bool = cfg!(unix);
+
+/// setuid, setgid and sticky permission bits.
+pub const SPECIAL_MODE_BITS: u32 = 0o7000;
+
+/// Apply the special-mode policy to a Unix mode.
+///
+/// setuid/setgid/sticky bits are stripped unless explicitly allowed; file
+/// type bits are left untouched so this works for CPIO, tar and BOM modes
+/// alike.
+pub fn effective_mode(mode: u32, allow_special_modes: bool) -> u32 {
+    if allow_special_modes {
+        mode
+    } else {
+        mode & !SPECIAL_MODE_BITS
+    }
+}
+
+/// Unix mode of a source entry, type bits included; outside Unix,
+/// `0o755` for directories and `0o644` for anything else.
+#[cfg(any(feature = "macos", feature = "linux"))]
+pub(crate) fn unix_mode(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode()
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.is_dir() { 0o755 } else { 0o644 }
+    }
+}
+
+/// Read the target of the symbolic link at `path` as raw bytes.
+#[cfg(any(feature = "macos", feature = "linux"))]
+pub(crate) fn read_link_target(path: &Path) -> PackageResult<Vec<u8>> {
+    let target =
+        fs::read_link(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(target.into_os_string().into_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(target.to_string_lossy().replace('\\', "/").into_bytes())
+    }
+}
+
+/// One step of a source folder walk.
+pub(crate) enum WalkItem {
+    /// A regular file or directory
+    Entry(DirEntry),
+    /// An entry that must be left out
+    Skipped(SkippedEntry),
+    /// Any other walk error
+    Error(walkdir::Error),
+}
+
+/// Sort a walk result under `root` into a packageable entry, a skipped
+/// special entry, or an error.
+pub(crate) fn classify(root: &Path, result: walkdir::Result<DirEntry>) -> WalkItem {
+    let skipped = |path: &Path, kind| {
+        WalkItem::Skipped(SkippedEntry {
+            relative_path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            kind,
+        })
+    };
+
+    match result {
+        Ok(entry) => match special_kind(&entry.file_type()) {
+            Some(kind) => skipped(entry.path(), kind),
+            None => WalkItem::Entry(entry),
+        },
+        Err(error) => match (error.loop_ancestor(), error.path()) {
+            (Some(_), Some(path)) => skipped(path, SpecialKind::SymlinkLoop),
+            _ => WalkItem::Error(error),
+        },
+    }
+}
+
+/// Fail if left-out special entries are an error under `strictness`.
+pub(crate) fn check_skipped(
+    strictness: &Strictness,
+    skipped: &[SkippedEntry],
+) -> PackageResult<()> {
+    let Some(first) = skipped.first() else {
+        return Ok(());
+    };
+    strictness.enforce(Check::SpecialFiles, || {
+        format!(
+            "{} special entr(ies) cannot be packaged, e.g. '{}' ({})",
+            skipped.len(),
+            first.relative_path.display(),
+            first.kind
+        )
+    })
+}
+
+/// Fail if `files` with setuid/setgid/sticky bits would have them stripped
+/// and that is an error under `strictness`.
+#[cfg(any(feature = "macos", feature = "linux"))]
+pub(crate) fn check_special_modes(
+    strictness: &Strictness,
+    allow_special_modes: bool,
+    files: &[PathBuf],
+) -> PackageResult<()> {
+    if allow_special_modes || files.is_empty() {
+        return Ok(());
+    }
+    strictness.enforce(Check::SpecialModes, || {
+        format!(
+            "{} entr(ies) have setuid/setgid/sticky bits that would be stripped \
+             (use --allow-special-modes to keep them)",
+            files.len()
+        )
+    })
+}
+
+/// The special kind of `file_type`, or `None` for files, directories and
+/// symlinks.
+#[cfg(unix)]
+pub fn special_kind(file_type: &FileType) -> Option<SpecialKind> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        Some(SpecialKind::Fifo)
+    } else if file_type.is_socket() {
+        Some(SpecialKind::Socket)
+    } else if file_type.is_block_device() || file_type.is_char_device() {
+        Some(SpecialKind::Device)
+    } else {
+        None
+    }
+}
+
+/// The special kind of `file_type`; always `None` outside Unix.
+#[cfg(not(unix))]
+pub fn special_kind(_file_type: &FileType) -> Option<SpecialKind> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use tempfile::TempDir;
+    use walkdir::WalkDir;
+
+    /// Create a named pipe with `mkfifo(3)`.
+    fn mkfifo(path: &Path) {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        // SAFETY: path is a valid NUL-terminated string
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+    }
+
+    #[test]
+    fn test_classify_special_entries() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir(root.join("dir")).unwrap();
+        fs::write(root.join("dir/file.txt"), "data").unwrap();
+        mkfifo(&root.join("pipe"));
+        let _listener = UnixListener::bind(root.join("app.sock")).unwrap();
+        std::os::unix::fs::symlink("..", root.join("dir/up")).unwrap();
+
+        let mut entries = Vec::new();
+        let mut skipped = Vec::new();
+        for result in WalkDir::new(root).follow_links(true).sort_by_file_name() {
+            match classify(root, result) {
+                WalkItem::Entry(entry) => entries.push(entry.path().to_path_buf()),
+                WalkItem::Skipped(entry) => skipped.push((entry.relative_path, entry.kind)),
+                WalkItem::Error(e) => panic!("unexpected walk error: {}", e),
+            }
+        }
+
+        assert!(entries.contains(&root.join("dir/file.txt")));
+        assert_eq!(
+            skipped,
+            vec![
+                ("app.sock".into(), SpecialKind::Socket),
+                ("dir/up".into(), SpecialKind::SymlinkLoop),
+                ("pipe".into(), SpecialKind::Fifo),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_special_kind_of_device() {
+        let metadata = fs::metadata("/dev/null").unwrap();
+        assert_eq!(
+            special_kind(&metadata.file_type()),
+            Some(SpecialKind::Device)
+        );
+        let metadata = fs::metadata("/").unwrap();
+        assert_eq!(special_kind(&metadata.file_type()), None);
+    }
+}
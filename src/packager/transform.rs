@@ -0,0 +1,266 @@
+//! Applying [`TransformRule`]s to content as it is packaged.
+//!
+//! Intune packages read through [`TransformedContent`]; macOS packages are
+//! built from a transformed staging copy of the source folder, since the
+//! payload and BOM are written from disk.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::SourceFilter;
+use crate::models::package::SourcePackage;
+use crate::models::transform::{TransformRecord, TransformRule};
+
+use super::blocklist::sha256_reader;
+use super::content::ContentProvider;
+use super::special::{PRESERVE_SYMLINKS, WalkItem, classify};
+use super::workspace::{StagingDir, stage_tree};
+
+/// Run every rule matching `path` over `data`, in order.
+///
+/// Returns `None` when no rule matches or the contents are unchanged.
+fn transform_file(
+    rules: &[TransformRule],
+    path: &Path,
+    read: impl FnOnce() -> PackageResult<Vec<u8>>,
+) -> PackageResult<Option<(Vec<u8>, TransformRecord)>> {
+    let matching: Vec<&TransformRule> = rules.iter().filter(|r| r.glob.matches(path)).collect();
+    if matching.is_empty() {
+        return Ok(None);
+    }
+
+    let original = read()?;
+    let mut data = original.clone();
+    for rule in &matching {
+        data = rule.transformer.transform(path, data).map_err(|reason| {
+            PackageError::TransformError {
+                path: path.to_path_buf(),
+                transformer: rule.transformer.name(),
+                reason,
+            }
+        })?;
+    }
+    if data == original {
+        return Ok(None);
+    }
+
+    let record = TransformRecord {
+        path: path.to_path_buf(),
+        transformers: matching.iter().map(|r| r.transformer.name()).collect(),
+        original_sha256: sha256_reader(original.as_slice())?,
+        sha256: sha256_reader(data.as_slice())?,
+    };
+    Ok(Some((data, record)))
+}
+
+/// Content with matching files replaced by their transformed contents.
+pub struct TransformedContent<'a> {
+    inner: &'a dyn ContentProvider,
+    source: SourcePackage,
+    transformed: BTreeMap<PathBuf, Vec<u8>>,
+    records: Vec<TransformRecord>,
+}
+
+impl<'a> TransformedContent<'a> {
+    /// Transform the files of `source` (collected from `inner`) that match
+    /// `rules`, updating their sizes.
+    pub fn apply(
+        inner: &'a dyn ContentProvider,
+        rules: &[TransformRule],
+        mut source: SourcePackage,
+    ) -> PackageResult<Self> {
+        let mut transformed = BTreeMap::new();
+        let mut records = Vec::new();
+
+        if !rules.is_empty() {
+            for file in &mut source.files {
+                let read = || {
+                    let mut data = Vec::new();
+                    inner.open(&file.relative_path)?.read_to_end(&mut data)?;
+                    Ok(data)
+                };
+                if let Some((data, record)) = transform_file(rules, &file.relative_path, read)? {
+                    source.total_size = source.total_size - file.size + data.len() as u64;
+                    file.size = data.len() as u64;
+                    transformed.insert(file.relative_path.clone(), data);
+                    records.push(record);
+                }
+            }
+        }
+
+        Ok(Self {
+            inner,
+            source,
+            transformed,
+            records,
+        })
+    }
+
+    /// The collected files, with transformed sizes.
+    pub fn source(&self) -> &SourcePackage {
+        &self.source
+    }
+
+    /// The files that were changed.
+    pub fn records(&self) -> &[TransformRecord] {
+        &self.records
+    }
+}
+
+impl ContentProvider for TransformedContent<'_> {
+    fn root(&self) -> &Path {
+        self.inner.root()
+    }
+
+    fn collect(&self, _setup_file: &str, _filter: &SourceFilter) -> PackageResult<SourcePackage> {
+        Ok(self.source.clone())
+    }
+
+    fn open(&self, relative_path: &Path) -> PackageResult<Box<dyn Read + '_>> {
+        match self.transformed.get(relative_path) {
+            Some(data) => Ok(Box::new(Cursor::new(data.as_slice()))),
+            None => self.inner.open(relative_path),
+        }
+    }
+}
+
+/// Mirror `source_folder` into a staging folder with matching files
+/// transformed.
+///
+/// Unchanged files are staged as clones or hard links. Returns `None`
+/// when no file is changed, so the source folder can be used directly.
+#[cfg_attr(not(feature = "macos"), allow(dead_code))]
+pub(crate) fn stage_transformed(
+    source_folder: &Path,
+    rules: &[TransformRule],
+) -> PackageResult<Option<(StagingDir, Vec<TransformRecord>)>> {
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    // Transform first so nothing is staged when no file changes
+    let mut changed = BTreeMap::new();
+    let mut records = Vec::new();
+    for result in WalkDir::new(source_folder)
+        .follow_links(!PRESERVE_SYMLINKS)
+        .sort_by_file_name()
+    {
+        let entry = match classify(source_folder, result) {
+            WalkItem::Entry(entry) => entry,
+            WalkItem::Skipped(_) => continue,
+            WalkItem::Error(e) => {
+                return Err(PackageError::source_read(source_folder.to_path_buf(), e));
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry
+            .path()
+            .strip_prefix(source_folder)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+        let read = || {
+            fs::read(entry.path())
+                .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))
+        };
+        if let Some((data, record)) = transform_file(rules, &relative, read)? {
+            changed.insert(relative, data);
+            records.push(record);
+        }
+    }
+    if changed.is_empty() {
+        return Ok(None);
+    }
+
+    let staging = StagingDir::create("transform")?;
+    stage_tree(
+        source_folder,
+        &staging.path,
+        &changed,
+        &SourceFilter::default(),
+    )?;
+
+    Ok(Some((staging, records)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packager::content::FolderContent;
+    use tempfile::TempDir;
+
+    fn rules() -> Vec<TransformRule> {
+        vec![
+            TransformRule::parse("*.ps1=crlf").unwrap(),
+            TransformRule::parse("config.json=replace:@VERSION@=2.0").unwrap(),
+        ]
+    }
+
+    fn create_source(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("scripts")).unwrap();
+        fs::write(source.join("setup.exe"), "MZ").unwrap();
+        fs::write(source.join("scripts/install.ps1"), "a\nb\n").unwrap();
+        fs::write(source.join("config.json"), r#"{"v":"@VERSION@"}"#).unwrap();
+        fs::write(source.join("notes.ps1"), "already\r\n").unwrap();
+        source
+    }
+
+    #[test]
+    fn test_transformed_content_replaces_matching_files() {
+        let temp = TempDir::new().unwrap();
+        let folder = FolderContent::new(create_source(&temp));
+        let source = folder
+            .collect("setup.exe", &SourceFilter::default())
+            .unwrap();
+        let total = source.total_size;
+
+        let content = TransformedContent::apply(&folder, &rules(), source).unwrap();
+
+        let paths: Vec<_> = content.records().iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("config.json"),
+                PathBuf::from("scripts/install.ps1")
+            ]
+        );
+        assert_eq!(content.source().total_size, total + 2 - 6);
+
+        let mut data = String::new();
+        content
+            .open(Path::new("scripts/install.ps1"))
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        assert_eq!(data, "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_stage_transformed_mirrors_tree() {
+        let temp = TempDir::new().unwrap();
+        let source = create_source(&temp);
+
+        let (staging, records) = stage_transformed(&source, &rules()).unwrap().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            fs::read_to_string(staging.path.join("config.json")).unwrap(),
+            r#"{"v":"2.0"}"#
+        );
+        assert_eq!(fs::read(staging.path.join("setup.exe")).unwrap(), b"MZ");
+        // Untouched source
+        assert_eq!(
+            fs::read_to_string(source.join("scripts/install.ps1")).unwrap(),
+            "a\nb\n"
+        );
+
+        let unmatched = [TransformRule::parse("*.txt=lf").unwrap()];
+        assert!(stage_transformed(&source, &unmatched).unwrap().is_none());
+    }
+}
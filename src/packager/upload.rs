@@ -0,0 +1,210 @@
+//! Upload-ready chunking of the encrypted content.
+//!
+//! The Graph upload flow commits the encrypted `IntunePackage.intunewin`
+//! to Azure Storage as a list of blocks with base64 block IDs. The outer
+//! ZIP stores that entry uncompressed, so blocks are read straight from the
+//! `.intunewin` file at the entry's data offset without loading it.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Take};
+use std::path::{Path, PathBuf};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use zip::CompressionMethod;
+use zip::read::ZipArchive;
+
+use crate::models::error::{PackageError, PackageResult};
+
+use super::CONTENT_ENTRY;
+
+/// Default block size used by the Intune upload flow (6 MB).
+pub const DEFAULT_BLOCK_SIZE: u64 = 6 * 1024 * 1024;
+
+/// Azure Storage limit for a single block (4000 MiB).
+pub const MAX_BLOCK_SIZE: u64 = 4000 * 1024 * 1024;
+
+/// Iterator over the upload blocks of a `.intunewin` file.
+#[derive(Debug, Clone)]
+pub struct UploadBlocks {
+    path: PathBuf,
+    data_start: u64,
+    content_size: u64,
+    block_size: u64,
+    index: u32,
+}
+
+impl UploadBlocks {
+    /// Size of the encrypted content in bytes.
+    pub fn content_size(&self) -> u64 {
+        self.content_size
+    }
+
+    /// Total number of blocks.
+    pub fn block_count(&self) -> u32 {
+        self.content_size.div_ceil(self.block_size) as u32
+    }
+}
+
+impl Iterator for UploadBlocks {
+    type Item = io::Result<UploadBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = u64::from(self.index) * self.block_size;
+        if offset >= self.content_size {
+            return None;
+        }
+
+        let len = self.block_size.min(self.content_size - offset);
+        let index = self.index;
+        self.index += 1;
+
+        let reader = File::open(&self.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(self.data_start + offset))?;
+            Ok(file.take(len))
+        });
+
+        Some(reader.map(|reader| UploadBlock {
+            id: block_id(index),
+            index,
+            offset,
+            len,
+            reader,
+        }))
+    }
+}
+
+/// One block of encrypted content, readable on its own file handle.
+#[derive(Debug)]
+pub struct UploadBlock {
+    /// Base64 block ID to pass to Put Block / Put Block List
+    pub id: String,
+    /// Zero-based block index
+    pub index: u32,
+    /// Offset of the block within the encrypted content
+    pub offset: u64,
+    /// Block length in bytes
+    pub len: u64,
+    reader: Take<File>,
+}
+
+impl Read for UploadBlock {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+/// Base64 block ID for a block index.
+///
+/// Azure requires all IDs in a blob to have the same length, so the index
+/// is zero-padded before encoding.
+pub fn block_id(index: u32) -> String {
+    BASE64.encode(format!("block-{:010}", index))
+}
+
+/// Split the encrypted content of a `.intunewin` file into upload blocks.
+pub fn upload_blocks(path: &Path, block_size: u64) -> PackageResult<UploadBlocks> {
+    if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+        return Err(PackageError::InvalidArgument {
+            reason: format!("Block size must be between 1 and {} bytes", MAX_BLOCK_SIZE),
+        });
+    }
+
+    let invalid = |reason: String| PackageError::InvalidIntunewinFile {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let file = File::open(path).map_err(|e| invalid(format!("Failed to open file: {}", e)))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| invalid(format!("Invalid ZIP archive: {}", e)))?;
+    let entry = archive
+        .by_name(CONTENT_ENTRY)
+        .map_err(|e| invalid(format!("Encrypted content not found: {}", e)))?;
+
+    if entry.compression() != CompressionMethod::Stored {
+        return Err(invalid(
+            "Encrypted content is compressed and cannot be uploaded in place".to_string(),
+        ));
+    }
+
+    Ok(UploadBlocks {
+        path: path.to_path_buf(),
+        data_start: entry.data_start(),
+        content_size: entry.size(),
+        block_size,
+        index: 0,
+    })
+}
+
+/// Build the Put Block List request body for the given block IDs.
+pub fn block_list_xml<'a, I>(block_ids: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>");
+    for id in block_ids {
+        xml.push_str("<Latest>");
+        xml.push_str(id);
+        xml.push_str("</Latest>");
+    }
+    xml.push_str("</BlockList>");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn read_content(path: &Path) -> Vec<u8> {
+        let mut archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        let mut entry = archive.by_name(CONTENT_ENTRY).unwrap();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_block_id_fixed_length() {
+        assert_eq!(block_id(0).len(), block_id(u32::MAX).len());
+        assert_ne!(block_id(1), block_id(2));
+    }
+
+    #[test]
+    fn test_upload_blocks_reassemble_content() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), vec![7u8; 10_000]).unwrap();
+
+        let request = PackageRequest::new(source, "setup.exe".to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        let result = package(&request).unwrap();
+
+        let blocks = upload_blocks(&result.output_path, 1000).unwrap();
+        let expected_count = blocks.block_count();
+        let mut reassembled = Vec::new();
+        let mut ids = Vec::new();
+        for block in blocks {
+            let mut block = block.unwrap();
+            assert_eq!(block.offset, reassembled.len() as u64);
+            block.read_to_end(&mut reassembled).unwrap();
+            ids.push(block.id);
+        }
+
+        assert_eq!(ids.len() as u32, expected_count);
+        assert_eq!(reassembled, read_content(&result.output_path));
+
+        let xml = block_list_xml(ids.iter().map(String::as_str));
+        assert!(xml.contains(&format!("<Latest>{}</Latest>", ids[0])));
+    }
+
+    #[test]
+    fn test_upload_blocks_invalid_block_size() {
+        let result = upload_blocks(Path::new("missing.intunewin"), 0);
+        assert!(matches!(result, Err(PackageError::InvalidArgument { .. })));
+    }
+}
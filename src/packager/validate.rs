@@ -0,0 +1,122 @@
+//! Post-creation validation of produced packages.
+//!
+//! Re-opens a freshly written `.intunewin` and runs the [`verify`] checks
+//! on it: structure, HMAC, file digest, content size and setup file.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::validation::ValidationReport;
+
+use super::verify::verify;
+
+/// Validate an `.intunewin` file, failing on the first failed check.
+pub fn validate_intunewin(path: &Path) -> PackageResult<ValidationReport> {
+    let fail = |reason: String| PackageError::ValidationFailed {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let result = verify(path).map_err(|e| fail(format!("Cannot open package: {}", e)))?;
+    if let Some((_, reason)) = result.first_failure() {
+        return Err(fail(reason.to_string()));
+    }
+
+    Ok(ValidationReport {
+        path: path.to_path_buf(),
+        checks: result.checks.iter().map(|c| c.name).collect(),
+        entry_count: result.entry_count,
+        duration: result.duration,
+    })
+}
+
+/// Run `validate` on `path` in a worker thread, failing after `timeout`.
+pub fn validate_with_timeout(
+    path: &Path,
+    timeout: Duration,
+    validate: fn(&Path) -> PackageResult<ValidationReport>,
+) -> PackageResult<ValidationReport> {
+    let (sender, receiver) = mpsc::channel();
+    let owned: PathBuf = path.to_path_buf();
+
+    thread::spawn(move || {
+        let _ = sender.send(validate(&owned));
+    });
+
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(PackageError::ValidationFailed {
+            path: path.to_path_buf(),
+            reason: format!("Timed out after {}s", timeout.as_secs_f64()),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::package;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn build_package(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+
+        let request = PackageRequest::new(source, "setup.exe".to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_validate_intunewin() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp);
+
+        let report = validate_intunewin(&path).unwrap();
+        assert_eq!(
+            report.checks,
+            vec![
+                "structure",
+                "hmac",
+                "decrypt",
+                "digest",
+                "size",
+                "content zip",
+                "setup file"
+            ]
+        );
+        assert_eq!(report.entry_count, 1);
+    }
+
+    #[test]
+    fn test_validate_intunewin_corrupted() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp);
+        let mut bytes = fs::read(&path).unwrap();
+        // Flip a byte inside the stored encrypted content
+        let offset = bytes.len() / 2;
+        bytes[offset] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(
+            validate_intunewin(&path),
+            Err(PackageError::ValidationFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_with_timeout() {
+        fn slow(_: &Path) -> PackageResult<ValidationReport> {
+            thread::sleep(Duration::from_millis(500));
+            Err(PackageError::Cancelled)
+        }
+
+        let result = validate_with_timeout(Path::new("x"), Duration::from_millis(10), slow);
+        assert!(matches!(result, Err(PackageError::ValidationFailed { .. })));
+    }
+}
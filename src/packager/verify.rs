@@ -0,0 +1,311 @@
+//! Verifying an `.intunewin` without extracting it.
+//!
+//! Every check runs in memory and is reported on its own, so a CI pipeline
+//! can see exactly what is wrong with a package. A check whose input is
+//! unavailable (e.g. the content cannot be decrypted) is reported as
+//! skipped; independent checks still run.
+
+use std::fs::File;
+use std::io::{self, BufReader, Cursor};
+use std::path::Path;
+use std::time::Instant;
+
+use zip::read::ZipArchive;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::validation::{CheckOutcome, VerifyCheck, VerifyResult};
+
+use super::encrypt::{compute_digest, decrypt_content, verify_hmac};
+use super::{extract_detection_metadata, extract_encrypted_content};
+
+/// Names of the checks, in the order they run.
+pub const VERIFY_CHECKS: [&str; 7] = [
+    "structure",
+    "hmac",
+    "decrypt",
+    "digest",
+    "size",
+    "content zip",
+    "setup file",
+];
+
+/// Check results and what the checks found out about the package.
+#[derive(Default)]
+struct Checks {
+    checks: Vec<VerifyCheck>,
+    setup_file: Option<String>,
+    entry_count: usize,
+}
+
+impl Checks {
+    fn run<T>(
+        &mut self,
+        name: &'static str,
+        check: impl FnOnce() -> Result<T, String>,
+    ) -> Option<T> {
+        match check() {
+            Ok(value) => {
+                self.push(name, CheckOutcome::Pass);
+                Some(value)
+            }
+            Err(reason) => {
+                self.push(name, CheckOutcome::Fail(reason));
+                None
+            }
+        }
+    }
+
+    fn push(&mut self, name: &'static str, outcome: CheckOutcome) {
+        self.checks.push(VerifyCheck { name, outcome });
+    }
+
+    /// Report every check that did not run as skipped.
+    fn finish(mut self, path: &Path, start: Instant) -> VerifyResult {
+        for name in VERIFY_CHECKS {
+            if !self.checks.iter().any(|c| c.name == name) {
+                self.push(name, CheckOutcome::Skipped);
+            }
+        }
+        self.checks
+            .sort_by_key(|c| VERIFY_CHECKS.iter().position(|name| *name == c.name));
+
+        VerifyResult {
+            path: path.to_path_buf(),
+            checks: self.checks,
+            setup_file: self.setup_file,
+            entry_count: self.entry_count,
+            duration: start.elapsed(),
+        }
+    }
+}
+
+/// Verify the `.intunewin` at `path`.
+///
+/// Fails only when the file cannot be opened; check failures are recorded
+/// in the result.
+pub fn verify(path: &Path) -> PackageResult<VerifyResult> {
+    let start = Instant::now();
+    let file = File::open(path).map_err(|e| PackageError::source_read(path.to_path_buf(), e))?;
+
+    let mut checks = Checks::default();
+    run_checks(path, file, &mut checks);
+    Ok(checks.finish(path, start))
+}
+
+/// Run the checks in order, stopping where the remaining ones lack input.
+fn run_checks(path: &Path, file: File, checks: &mut Checks) -> Option<()> {
+    let (metadata, encrypted) = checks.run("structure", || {
+        let mut archive = ZipArchive::new(BufReader::new(file))
+            .map_err(|e| format!("Invalid ZIP archive: {}", e))?;
+        let metadata = extract_detection_metadata(&mut archive, path)
+            .map_err(|e| format!("Detection.xml: {}", e))?;
+        let encrypted = extract_encrypted_content(&mut archive, path)
+            .map_err(|e| format!("Encrypted content: {}", e))?;
+        Ok((metadata, encrypted))
+    })?;
+    let info = &metadata.encryption_info;
+    checks.setup_file = Some(metadata.setup_file.clone());
+
+    checks.run("hmac", || {
+        if encrypted.len() < 48 || encrypted[..32] != info.mac {
+            return Err("Stored HMAC does not match Detection.xml".to_string());
+        }
+        if !verify_hmac(&info.mac_key, &encrypted[32..], &info.mac) {
+            return Err("HMAC does not match the encrypted content".to_string());
+        }
+        Ok(())
+    })?;
+
+    let decrypted = checks.run("decrypt", || {
+        decrypt_content(&encrypted, info).map_err(|e| e.to_string())
+    })?;
+
+    checks.run("digest", || {
+        if compute_digest(info.file_digest_algorithm, &decrypted) != info.file_digest {
+            return Err("File digest does not match decrypted content".to_string());
+        }
+        Ok(())
+    });
+
+    checks.run("size", || {
+        if decrypted.len() as u64 != metadata.unencrypted_content_size {
+            return Err(format!(
+                "Decrypted size {} does not match UnencryptedContentSize {}",
+                decrypted.len(),
+                metadata.unencrypted_content_size
+            ));
+        }
+        Ok(())
+    });
+
+    let mut inner = checks.run("content zip", || {
+        let mut inner = ZipArchive::new(Cursor::new(decrypted))
+            .map_err(|e| format!("Decrypted content is not a valid ZIP: {}", e))?;
+        for i in 0..inner.len() {
+            let mut entry = inner
+                .by_index(i)
+                .map_err(|e| format!("Entry #{}: {}", i, e))?;
+            if entry.enclosed_name().is_none() {
+                return Err(format!("Entry '{}' has an unsafe path", entry.name()));
+            }
+            // Reading to the end checks the entry's CRC
+            io::copy(&mut entry, &mut io::sink())
+                .map_err(|e| format!("Entry '{}': {}", entry.name(), e))?;
+        }
+        Ok(inner)
+    })?;
+    checks.entry_count = inner.len();
+
+    checks.run("setup file", || {
+        let setup_entry = metadata.setup_file.replace('\\', "/");
+        match inner.by_name(&setup_entry) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "Setup file '{}' is missing from the content",
+                metadata.setup_file
+            )),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::packager::{DETECTION_ENTRY, package};
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn build_package(temp: &TempDir) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::write(source.join("setup.exe"), "setup").unwrap();
+        fs::write(source.join("lib/data.txt"), "data").unwrap();
+
+        let request = PackageRequest::new(source, "setup.exe".to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    /// Rewrite the package at `path` with `edit` applied to Detection.xml.
+    fn edit_detection(path: &Path, edit: impl Fn(String) -> String) {
+        let mut archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            entries.push((entry.name().to_string(), data));
+        }
+
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in entries {
+            let data = if name == DETECTION_ENTRY {
+                edit(String::from_utf8(data).unwrap()).into_bytes()
+            } else {
+                data
+            };
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn outcome(result: &VerifyResult, name: &str) -> CheckOutcome {
+        result
+            .checks
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap()
+            .outcome
+            .clone()
+    }
+
+    #[test]
+    fn test_verify_valid_package() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp);
+
+        let result = verify(&path).unwrap();
+        assert!(result.passed(), "{:?}", result.checks);
+        let names: Vec<_> = result.checks.iter().map(|c| c.name).collect();
+        assert_eq!(names, VERIFY_CHECKS);
+        assert_eq!(result.setup_file.as_deref(), Some("setup.exe"));
+        assert_eq!(result.entry_count, 3);
+    }
+
+    #[test]
+    fn test_verify_reports_each_failure() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp);
+        edit_detection(&path, |xml| {
+            let start = xml.find("<FileDigest>").unwrap() + "<FileDigest>".len();
+            let mut xml = xml;
+            xml.replace_range(start..start + 4, "AAAA");
+            xml.replace(
+                "<SetupFile>setup.exe</SetupFile>",
+                "<SetupFile>other.exe</SetupFile>",
+            )
+        });
+
+        let result = verify(&path).unwrap();
+        assert!(!result.passed());
+        assert_eq!(outcome(&result, "hmac"), CheckOutcome::Pass);
+        assert!(matches!(outcome(&result, "digest"), CheckOutcome::Fail(_)));
+        assert_eq!(outcome(&result, "size"), CheckOutcome::Pass);
+        assert_eq!(outcome(&result, "content zip"), CheckOutcome::Pass);
+        assert!(matches!(
+            outcome(&result, "setup file"),
+            CheckOutcome::Fail(_)
+        ));
+        assert_eq!(result.first_failure().unwrap().0, "digest");
+        assert_eq!(result.failures(), 2);
+    }
+
+    #[test]
+    fn test_verify_skips_checks_after_hmac_failure() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp);
+
+        // A MAC key that does not match the stored HMAC
+        edit_detection(&path, |xml| {
+            let start = xml.find("<MacKey>").unwrap() + "<MacKey>".len();
+            let mut xml = xml;
+            xml.replace_range(start..start + 4, "AAAA");
+            xml
+        });
+
+        let result = verify(&path).unwrap();
+        assert_eq!(outcome(&result, "structure"), CheckOutcome::Pass);
+        assert!(matches!(outcome(&result, "hmac"), CheckOutcome::Fail(_)));
+        for name in &VERIFY_CHECKS[2..] {
+            assert_eq!(outcome(&result, name), CheckOutcome::Skipped);
+        }
+        assert_eq!(result.entry_count, 0);
+        assert!(result.to_json().contains("\"outcome\": \"skipped\""));
+    }
+
+    #[test]
+    fn test_verify_not_a_package() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("bogus.intunewin");
+        fs::write(&path, "not a zip").unwrap();
+
+        let result = verify(&path).unwrap();
+        assert!(matches!(
+            outcome(&result, "structure"),
+            CheckOutcome::Fail(_)
+        ));
+        assert_eq!(result.failures(), VERIFY_CHECKS.len());
+        assert!(result.setup_file.is_none());
+
+        assert!(matches!(
+            verify(&temp.path().join("missing.intunewin")),
+            Err(PackageError::SourceReadError { .. })
+        ));
+    }
+}
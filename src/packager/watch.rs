@@ -0,0 +1,437 @@
+//! Packaging applications dropped into a watched folder.
+//!
+//! The drop folder is scanned every few seconds rather than through file
+//! system notifications, which network shares often do not deliver. A
+//! subfolder is only packaged once its fingerprint (file count, total size
+//! and latest modification time) stayed the same for the settle time, so
+//! copies still in progress are left alone.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::{info, info_span};
+
+use crate::models::batch::BatchOutcome;
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::package::{PackageRequest, Verbosity};
+use crate::models::size::ByteSize;
+use crate::models::time::iso8601;
+use crate::models::watch::{FAILED_FOLDER, PROCESSED_FOLDER, WatchJob, WatchRequest, WatchRules};
+
+use super::package;
+use super::workspace::StagingDir;
+
+/// What a subfolder looked like at one scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    files: u64,
+    bytes: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Finds drop folder subfolders that stopped changing.
+#[derive(Debug)]
+pub struct DropFolderWatcher {
+    root: PathBuf,
+    settle: Duration,
+    /// Subfolders seen changing, with their fingerprint and when it was taken
+    pending: BTreeMap<PathBuf, (Fingerprint, Instant)>,
+    /// Subfolders already returned, with their fingerprint at the time
+    handled: BTreeMap<PathBuf, Fingerprint>,
+}
+
+impl DropFolderWatcher {
+    /// Watch the subfolders of `root`.
+    pub fn new(root: impl Into<PathBuf>, settle: Duration) -> Self {
+        Self {
+            root: root.into(),
+            settle,
+            pending: BTreeMap::new(),
+            handled: BTreeMap::new(),
+        }
+    }
+
+    /// Scan the drop folder at `now` and return the subfolders that have
+    /// been unchanged for the settle time, each once until it changes again.
+    ///
+    /// Folders whose name starts with `.` are skipped.
+    pub fn poll(&mut self, now: Instant) -> PackageResult<Vec<PathBuf>> {
+        let entries = fs::read_dir(&self.root)
+            .map_err(|e| PackageError::source_read(self.root.clone(), e))?;
+
+        let mut seen = Vec::new();
+        let mut ready = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            // Unreadable while files are being copied; try again next scan
+            let Ok(fingerprint) = fingerprint(&path) else {
+                continue;
+            };
+            seen.push(path.clone());
+
+            if self.handled.get(&path) == Some(&fingerprint) {
+                continue;
+            }
+            self.handled.remove(&path);
+
+            let since = match self.pending.get(&path) {
+                Some((previous, since)) if *previous == fingerprint => *since,
+                _ => {
+                    self.pending.insert(path.clone(), (fingerprint, now));
+                    now
+                }
+            };
+            if now.saturating_duration_since(since) >= self.settle {
+                self.pending.remove(&path);
+                self.handled.insert(path.clone(), fingerprint);
+                ready.push(path);
+            }
+        }
+
+        self.pending.retain(|path, _| seen.contains(path));
+        self.handled.retain(|path, _| seen.contains(path));
+        ready.sort();
+        Ok(ready)
+    }
+}
+
+/// File count, total size and latest modification time under `folder`.
+fn fingerprint(folder: &Path) -> std::io::Result<Fingerprint> {
+    let mut result = Fingerprint {
+        files: 0,
+        bytes: 0,
+        modified: None,
+    };
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                folders.push(entry.path());
+            } else {
+                result.files += 1;
+                result.bytes += metadata.len();
+            }
+            let modified = metadata.modified().ok();
+            result.modified = result.modified.max(modified);
+        }
+    }
+    Ok(result)
+}
+
+/// The setup file of `folder`: the first of its top-level files matching
+/// the earliest pattern of `rules.setup_files`.
+pub fn find_setup_file(folder: &Path, rules: &WatchRules) -> Option<String> {
+    let mut names: Vec<String> = fs::read_dir(folder)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+
+    rules.setup_files.iter().find_map(|glob| {
+        names
+            .iter()
+            .find(|name| glob.matches(Path::new(name)))
+            .cloned()
+    })
+}
+
+/// Watch `request.drop_folder` and package each application dropped in it,
+/// calling `on_job` after each one, until `stop` returns true.
+///
+/// With `request.once`, the drop folder is processed a single time without
+/// waiting for subfolders to settle.
+pub fn watch(
+    request: &WatchRequest,
+    mut on_job: impl FnMut(&WatchJob),
+    stop: &dyn Fn() -> bool,
+) -> PackageResult<()> {
+    check_folders(request)?;
+
+    let settle = if request.once {
+        Duration::ZERO
+    } else {
+        request.rules.settle
+    };
+    let mut watcher = DropFolderWatcher::new(&request.drop_folder, settle);
+    loop {
+        for folder in watcher.poll(Instant::now())? {
+            if find_setup_file(&folder, &request.rules).is_some() {
+                on_job(&run_job(request, &folder));
+            }
+        }
+        if request.once || stop() {
+            return Ok(());
+        }
+        thread::sleep(request.rules.poll);
+    }
+}
+
+/// Make sure the drop folder exists and the output folder is outside it.
+fn check_folders(request: &WatchRequest) -> PackageResult<()> {
+    if !request.drop_folder.is_dir() {
+        return Err(PackageError::SourceFolderNotFound {
+            path: request.drop_folder.clone(),
+        });
+    }
+    fs::create_dir_all(&request.output_folder)
+        .map_err(|e| PackageError::output_folder(request.output_folder.clone(), e))?;
+
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if canonical(&request.output_folder).starts_with(canonical(&request.drop_folder)) {
+        return Err(PackageError::InvalidArgument {
+            reason: "The output folder must not be inside the drop folder".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Package the subfolder `folder`, move the package to the output folder
+/// and the subfolder out of the way, and log each step.
+pub fn run_job(request: &WatchRequest, folder: &Path) -> WatchJob {
+    let start_time = Instant::now();
+    let name = folder
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let _span = info_span!("watch_job", name = %name).entered();
+    let log_path = request.output_folder.join(format!("{}.log", name));
+    let mut log = JobLog::default();
+    log.line(format!("Packaging '{}'", folder.display()));
+
+    let setup_file = find_setup_file(folder, &request.rules);
+    let outcome = match &setup_file {
+        Some(setup_file) => {
+            log.line(format!("Setup file: {}", setup_file));
+            match build(request, folder, &name, setup_file, &mut log) {
+                Ok((output_path, package_size)) => {
+                    log.line(format!(
+                        "Packaged '{}' ({})",
+                        output_path.display(),
+                        package_size
+                    ));
+                    BatchOutcome::Packaged {
+                        output_path,
+                        package_size,
+                    }
+                }
+                Err(error) => {
+                    log.line(format!("Failed: {}", error));
+                    BatchOutcome::Failed(error)
+                }
+            }
+        }
+        None => {
+            let error = PackageError::InvalidArgument {
+                reason: "No file matches the setup file patterns".to_string(),
+            };
+            log.line(format!("Failed: {}", error));
+            BatchOutcome::Failed(error)
+        }
+    };
+
+    let archive = if matches!(outcome, BatchOutcome::Packaged { .. }) {
+        PROCESSED_FOLDER
+    } else {
+        FAILED_FOLDER
+    };
+    let archived_to = match archive_source(&request.drop_folder.join(archive), folder, &name) {
+        Ok(path) => {
+            log.line(format!("Moved source to '{}'", path.display()));
+            Some(path)
+        }
+        Err(e) => {
+            log.line(format!("Could not move source: {}", e));
+            None
+        }
+    };
+
+    let duration = start_time.elapsed();
+    log.line(format!("Finished in {:.2}s", duration.as_secs_f64()));
+    // A log that cannot be written must not hide the package result
+    let _ = log.append_to(&log_path);
+
+    WatchJob {
+        name,
+        setup_file,
+        outcome,
+        archived_to,
+        log_path,
+        duration,
+    }
+}
+
+/// Package `folder` in a staging folder, then move it to the output folder.
+fn build(
+    request: &WatchRequest,
+    folder: &Path,
+    name: &str,
+    setup_file: &str,
+    log: &mut JobLog,
+) -> PackageResult<(PathBuf, ByteSize)> {
+    let staging = StagingDir::create("watch")?;
+    let mut package_request = PackageRequest::new(folder, setup_file, &staging.path)
+        .with_output_name(name)
+        .with_verbosity(Verbosity::Silent)
+        .with_strictness(request.strictness.clone());
+    for (key, value) in &request.rules.content_tags {
+        package_request = package_request.with_content_tag(key.clone(), value.clone());
+    }
+    for glob in &request.rules.exclude {
+        package_request = package_request.with_exclude(glob.clone());
+    }
+
+    let result = package(&package_request)?;
+    let file_name = result.output_path.file_name().unwrap_or_default();
+    let output_path = request.output_folder.join(file_name);
+    if output_path.exists() {
+        log.line(format!("Replacing '{}'", output_path.display()));
+    }
+    move_path(&result.output_path, &output_path)
+        .map_err(|e| PackageError::output_write(output_path.clone(), e))?;
+    Ok((output_path, result.package_size))
+}
+
+/// Move the subfolder `folder` into `archive`, under a free name.
+fn archive_source(archive: &Path, folder: &Path, name: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(archive)?;
+    let mut target = archive.join(name);
+    let mut suffix = 1;
+    while fs::symlink_metadata(&target).is_ok() {
+        suffix += 1;
+        target = archive.join(format!("{}-{}", name, suffix));
+    }
+    fs::rename(folder, &target)?;
+    Ok(target)
+}
+
+/// Move a file, copying it when `from` and `to` are on different volumes.
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// Timestamped lines describing one job.
+#[derive(Default)]
+struct JobLog {
+    lines: Vec<String>,
+}
+
+impl JobLog {
+    fn line(&mut self, message: String) {
+        info!("{}", message);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.lines.push(format!("{} {}", iso8601(now), message));
+    }
+
+    /// Append the lines to `path`, keeping the logs of earlier drops.
+    fn append_to(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for line in &self.lines {
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::glob::Glob;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_watcher_waits_for_folder_to_settle() {
+        let temp = TempDir::new().unwrap();
+        let app = temp.path().join("app");
+        fs::create_dir(&app).unwrap();
+        fs::write(app.join("setup.exe"), b"part").unwrap();
+        fs::create_dir(temp.path().join(".processed")).unwrap();
+
+        let settle = Duration::from_secs(10);
+        let mut watcher = DropFolderWatcher::new(temp.path(), settle);
+        let start = Instant::now();
+        assert!(watcher.poll(start).unwrap().is_empty());
+
+        // Still being copied: the settle time starts over
+        fs::write(app.join("setup.exe"), b"partial copy").unwrap();
+        assert!(watcher.poll(start + settle).unwrap().is_empty());
+
+        assert_eq!(watcher.poll(start + settle * 2).unwrap(), vec![app.clone()]);
+        // Returned once until it changes again
+        assert!(watcher.poll(start + settle * 3).unwrap().is_empty());
+
+        fs::write(app.join("extra.txt"), b"x").unwrap();
+        assert!(watcher.poll(start + settle * 4).unwrap().is_empty());
+        assert_eq!(watcher.poll(start + settle * 5).unwrap(), vec![app]);
+    }
+
+    #[test]
+    fn test_find_setup_file() {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("helper.exe"), b"").unwrap();
+        fs::write(temp.path().join("app.msi"), b"").unwrap();
+        fs::create_dir(temp.path().join("setup.exe")).unwrap();
+
+        let rules = WatchRules::default();
+        // A folder named like a setup file does not count
+        assert_eq!(
+            find_setup_file(temp.path(), &rules).as_deref(),
+            Some("app.msi")
+        );
+
+        fs::write(temp.path().join("install.ps1"), b"").unwrap();
+        assert_eq!(
+            find_setup_file(temp.path(), &rules).as_deref(),
+            Some("install.ps1")
+        );
+
+        let rules = WatchRules {
+            setup_files: vec![Glob::new("*.bat").unwrap()],
+            ..WatchRules::default()
+        };
+        assert_eq!(find_setup_file(temp.path(), &rules), None);
+    }
+
+    #[test]
+    fn test_watch_once_packages_and_archives() {
+        let temp = TempDir::new().unwrap();
+        let drop = temp.path().join("drop");
+        let output = temp.path().join("output");
+        fs::create_dir_all(drop.join("good")).unwrap();
+        fs::write(drop.join("good/setup.exe"), b"fake installer").unwrap();
+        fs::create_dir_all(drop.join("empty")).unwrap();
+        fs::write(drop.join("empty/readme.txt"), b"no installer").unwrap();
+
+        let request = WatchRequest::new(&drop, &output).with_once(true);
+        let mut jobs = Vec::new();
+        watch(&request, |job| jobs.push(job.name.clone()), &|| false).unwrap();
+
+        // Folders without a setup file are left for later
+        assert_eq!(jobs, vec!["good".to_string()]);
+        assert!(output.join("good.intunewin").exists());
+        assert!(output.join("good.log").exists());
+        assert!(drop.join(".processed/good/setup.exe").exists());
+        assert!(drop.join("empty").exists());
+
+        let inside = WatchRequest::new(&drop, drop.join("out")).with_once(true);
+        assert!(watch(&inside, |_| {}, &|| false).is_err());
+    }
+}
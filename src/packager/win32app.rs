@@ -0,0 +1,194 @@
+//! Generating the Win32 app manifest of an `.intunewin`.
+//!
+//! The Detection.xml metadata names the package and its setup file, and the
+//! detection rules are derived as for `intune rules`. An MSI also supplies
+//! the display name, publisher, version and install commands; for other
+//! setup files the commands come from the settings.
+
+use std::path::Path;
+
+use crate::models::detection::{DetectionRulesRequest, MsiInformation};
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::scaffold::{INSTALL_SCRIPT, UNINSTALL_SCRIPT, powershell_command};
+use crate::models::win32app::{AppManifestRequest, Win32AppManifest};
+
+use super::detection::detection_rules;
+use super::inspect::inspect_detection;
+
+/// Generate the Win32 app manifest for the package in `request`.
+pub fn app_manifest(request: &AppManifestRequest) -> PackageResult<Win32AppManifest> {
+    let path = &request.input_file;
+    let metadata = inspect_detection(path)?;
+
+    let mut rules_request = DetectionRulesRequest::new(path);
+    for rule in &request.rules {
+        rules_request.add_rule(rule.clone());
+    }
+    let detection = detection_rules(&rules_request)?;
+
+    let settings = &request.settings;
+    let msi = detection.msi.as_ref();
+    let missing = |what: &str, flag: &str| PackageError::InvalidArgument {
+        reason: format!(
+            "No {} can be derived from '{}': use {}",
+            what, detection.setup_file, flag
+        ),
+    };
+
+    let display_name = settings
+        .display_name
+        .clone()
+        .or_else(|| msi.and_then(|msi| msi.product_name.clone()))
+        .unwrap_or_else(|| metadata.name.clone());
+    let publisher = settings
+        .publisher
+        .clone()
+        .or_else(|| msi.and_then(|msi| msi.publisher.clone()))
+        .ok_or_else(|| missing("publisher", "--publisher"))?;
+    let (default_install, default_uninstall) = default_commands(&detection.setup_file, msi);
+    let install_command = settings
+        .install_command
+        .clone()
+        .or(default_install)
+        .ok_or_else(|| missing("install command", "--install-command"))?;
+    let uninstall_command = settings
+        .uninstall_command
+        .clone()
+        .or(default_uninstall)
+        .ok_or_else(|| missing("uninstall command", "--uninstall-command"))?;
+
+    Ok(Win32AppManifest {
+        description: settings
+            .description
+            .clone()
+            .unwrap_or_else(|| display_name.clone()),
+        display_name,
+        publisher,
+        version: settings
+            .version
+            .clone()
+            .or_else(|| msi.map(|msi| msi.product_version.clone())),
+        file_name: file_name(path),
+        install_command,
+        uninstall_command,
+        settings: settings.clone(),
+        detection,
+    })
+}
+
+/// Install and uninstall commands implied by the setup file: `msiexec` for
+/// an MSI, and the wrappers of `intune scaffold` for its `Install.ps1`.
+fn default_commands(
+    setup_file: &str,
+    msi: Option<&MsiInformation>,
+) -> (Option<String>, Option<String>) {
+    if let Some(msi) = msi {
+        return (
+            Some(format!("msiexec /i \"{}\" /qn", setup_file)),
+            Some(format!("msiexec /x \"{}\" /qn", msi.product_code)),
+        );
+    }
+    if setup_file.eq_ignore_ascii_case(INSTALL_SCRIPT) {
+        return (
+            Some(powershell_command(INSTALL_SCRIPT)),
+            Some(powershell_command(UNINSTALL_SCRIPT)),
+        );
+    }
+    if setup_file.to_ascii_lowercase().ends_with(".ps1") {
+        return (Some(powershell_command(setup_file)), None);
+    }
+    (None, None)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{self, JsonValue};
+    use crate::models::detection::DetectionRule;
+    use crate::models::package::{PackageRequest, Verbosity};
+    use crate::models::win32app::AppSettings;
+    use crate::packager::msi::fixture::build_msi;
+    use crate::packager::package;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn build_package(temp: &TempDir, setup_file: &str, setup: &[u8]) -> PathBuf {
+        let source = temp.path().join("src");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join(setup_file), setup).unwrap();
+
+        let request = PackageRequest::new(source, setup_file.to_string(), temp.path().join("out"))
+            .with_verbosity(Verbosity::Silent);
+        package(&request).unwrap().output_path
+    }
+
+    #[test]
+    fn test_app_manifest_from_msi() {
+        let temp = TempDir::new().unwrap();
+        let msi = build_msi(&[
+            ("ProductCode", "{11111111-2222-3333-4444-555555555555}"),
+            ("ProductVersion", "4.5.6"),
+            ("ProductName", "Acme Tool"),
+            ("Manufacturer", "ACME"),
+        ]);
+        let path = build_package(&temp, "setup.msi", &msi);
+
+        let manifest = app_manifest(&AppManifestRequest::new(&path)).unwrap();
+        assert_eq!(manifest.display_name, "Acme Tool");
+        assert_eq!(manifest.publisher, "ACME");
+        assert_eq!(manifest.version.as_deref(), Some("4.5.6"));
+        assert_eq!(manifest.file_name, "setup.intunewin");
+        assert_eq!(manifest.install_command, "msiexec /i \"setup.msi\" /qn");
+        assert_eq!(
+            manifest.uninstall_command,
+            "msiexec /x \"{11111111-2222-3333-4444-555555555555}\" /qn"
+        );
+
+        let json = json::parse(&manifest.to_json()).unwrap();
+        let msi = json.get("msiInformation").unwrap();
+        assert_eq!(
+            msi.get("packageType").and_then(JsonValue::as_str),
+            Some("perMachine")
+        );
+    }
+
+    #[test]
+    fn test_app_manifest_needs_commands_for_exe() {
+        let temp = TempDir::new().unwrap();
+        let path = build_package(&temp, "setup.exe", b"MZ");
+        let request = AppManifestRequest::new(&path)
+            .with_rule(DetectionRule::file("C:\\Acme\\acme.exe").unwrap())
+            .with_settings(AppSettings {
+                publisher: Some("ACME".to_string()),
+                install_command: Some("setup.exe /S".to_string()),
+                ..AppSettings::default()
+            });
+
+        assert!(matches!(
+            app_manifest(&request),
+            Err(PackageError::InvalidArgument { reason }) if reason.contains("--uninstall-command")
+        ));
+
+        let mut settings = request.settings.clone();
+        settings.uninstall_command = Some("uninstall.exe /S".to_string());
+        let manifest = app_manifest(&request.with_settings(settings)).unwrap();
+        assert_eq!(manifest.display_name, "setup.exe");
+        assert_eq!(manifest.description, "setup.exe");
+        assert!(manifest.version.is_none());
+    }
+
+    #[test]
+    fn test_default_commands_for_scaffold_wrappers() {
+        let (install, uninstall) = default_commands("Install.ps1", None);
+        assert_eq!(install, Some(powershell_command(INSTALL_SCRIPT)));
+        assert_eq!(uninstall, Some(powershell_command(UNINSTALL_SCRIPT)));
+        assert_eq!(default_commands("setup.exe", None), (None, None));
+    }
+}
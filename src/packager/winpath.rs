@@ -0,0 +1,245 @@
+//! Preflight checks of content paths against Windows naming rules.
+//!
+//! The Intune Management Extension extracts a package's content under
+//! `C:\Windows\IMECache\<app id>_<revision>\` before running the install
+//! command, so a relative path only gets what that prefix leaves of
+//! `MAX_PATH`. Reserved device names, characters such as `:` or `?`,
+//! trailing dots and spaces, and paths that differ only in case all
+//! package fine elsewhere but fail or collide on the device. The collectors
+//! report such paths as [`PathIssue`]s, which `--strict` turns into an
+//! error.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::json::JsonValue;
+use crate::models::error::PackageResult;
+use crate::models::package::{PathIssue, PathProblem, SourcePackage};
+use crate::models::strictness::{Check, Strictness};
+
+/// Longest relative path that still fits in `MAX_PATH` (259 characters
+/// and a terminator) under the 59-character IMECache extraction folder.
+pub const MAX_RELATIVE_PATH: usize = 200;
+
+/// Device names Windows reserves in every folder, with any extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows does not allow in file names, besides control
+/// characters.
+const INVALID_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Find the paths of `package` that would not install on Windows, in path
+/// order.
+pub fn path_issues(package: &SourcePackage) -> Vec<PathIssue> {
+    let mut paths: Vec<&Path> = package
+        .directories
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(package.files.iter().map(|f| f.relative_path.as_path()))
+        .collect();
+    paths.sort();
+
+    // Name problems are reported once, on the component's own path
+    let mut issues: BTreeMap<(PathBuf, &'static str), PathProblem> = BTreeMap::new();
+    let mut folded: BTreeMap<String, &Path> = BTreeMap::new();
+    for path in paths {
+        let mut prefix = PathBuf::new();
+        for component in path.iter() {
+            prefix.push(component);
+            if let Some(problem) = name_problem(&component.to_string_lossy()) {
+                issues.insert((prefix.clone(), problem.name()), problem);
+            }
+        }
+
+        let length = windows_length(path);
+        if length > MAX_RELATIVE_PATH {
+            let problem = PathProblem::TooLong {
+                length,
+                limit: MAX_RELATIVE_PATH,
+            };
+            issues.insert((path.to_path_buf(), problem.name()), problem);
+        }
+
+        let key = path.to_string_lossy().to_lowercase();
+        match folded.get(&key) {
+            Some(other) if *other != path => {
+                let problem = PathProblem::CaseConflict(other.to_path_buf());
+                issues.insert((path.to_path_buf(), problem.name()), problem);
+            }
+            _ => {
+                folded.insert(key, path);
+            }
+        }
+    }
+
+    issues
+        .into_iter()
+        .map(|((relative_path, _), problem)| PathIssue {
+            relative_path,
+            problem,
+        })
+        .collect()
+}
+
+/// The first problem Windows has with the file name `name`, if any.
+fn name_problem(name: &str) -> Option<PathProblem> {
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_ascii_control() || INVALID_CHARACTERS.contains(c))
+    {
+        return Some(PathProblem::InvalidCharacter(c));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some(PathProblem::TrailingDotOrSpace);
+    }
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .find(|reserved| stem.eq_ignore_ascii_case(reserved))
+        .map(|reserved| PathProblem::ReservedName(reserved.to_string()))
+}
+
+/// Length of `path` as Windows counts it: UTF-16 units with `\` between
+/// components.
+fn windows_length(path: &Path) -> usize {
+    let components: Vec<_> = path.iter().collect();
+    let separators = components.len().saturating_sub(1);
+    components
+        .iter()
+        .map(|c| c.to_string_lossy().encode_utf16().count())
+        .sum::<usize>()
+        + separators
+}
+
+/// Fail if path issues are an error under `strictness`.
+pub(crate) fn check_path_issues(
+    strictness: &Strictness,
+    issues: &[PathIssue],
+) -> PackageResult<()> {
+    let Some(first) = issues.first() else {
+        return Ok(());
+    };
+    strictness.enforce(Check::WindowsPaths, || {
+        format!(
+            "{} path(s) would not install on Windows, e.g. '{}' ({})",
+            issues.len(),
+            first.relative_path.display(),
+            first.problem
+        )
+    })
+}
+
+/// Serialize path issues as a JSON report.
+pub fn path_report_json(issues: &[PathIssue]) -> String {
+    let issues = issues.iter().map(PathIssue::to_json_value).collect();
+    JsonValue::Object(vec![
+        (
+            "max_relative_path".to_string(),
+            (MAX_RELATIVE_PATH as u64).into(),
+        ),
+        ("issues".to_string(), JsonValue::Array(issues)),
+    ])
+    .to_pretty_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::error::PackageError;
+
+    fn package(files: &[&str], directories: &[&str]) -> SourcePackage {
+        let mut package = SourcePackage::new(PathBuf::from("src"), PathBuf::from("setup.exe"));
+        for dir in directories {
+            package.add_directory(PathBuf::from(dir));
+        }
+        for file in files {
+            package.add_file(PathBuf::from(file), 1, false);
+        }
+        package
+    }
+
+    #[test]
+    fn test_name_problems() {
+        assert_eq!(
+            name_problem("con.txt"),
+            Some(PathProblem::ReservedName("CON".to_string()))
+        );
+        assert_eq!(
+            name_problem("LPT1"),
+            Some(PathProblem::ReservedName("LPT1".to_string()))
+        );
+        assert_eq!(
+            name_problem("a:b"),
+            Some(PathProblem::InvalidCharacter(':'))
+        );
+        assert_eq!(
+            name_problem("tab\there"),
+            Some(PathProblem::InvalidCharacter('\t'))
+        );
+        assert_eq!(
+            name_problem("readme."),
+            Some(PathProblem::TrailingDotOrSpace)
+        );
+        assert_eq!(name_problem("console.exe"), None);
+        assert_eq!(name_problem("COM10"), None);
+    }
+
+    #[test]
+    fn test_path_issues() {
+        let long = format!("data/{}", "x".repeat(MAX_RELATIVE_PATH));
+        let package = package(
+            &[
+                "setup.exe",
+                "aux/readme.txt",
+                "Docs/a.txt",
+                "docs/A.txt",
+                &long,
+            ],
+            &["aux", "data"],
+        );
+
+        let issues = path_issues(&package);
+        let found: Vec<_> = issues
+            .iter()
+            .map(|i| {
+                (
+                    i.relative_path.to_string_lossy().into_owned(),
+                    i.problem.name(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("aux".to_string(), "reserved-name"),
+                (long.clone(), "too-long"),
+                ("docs/A.txt".to_string(), "case-conflict"),
+            ]
+        );
+        assert!(path_issues(&self::package(&["setup.exe", "bin/app.exe"], &["bin"])).is_empty());
+    }
+
+    #[test]
+    fn test_check_path_issues() {
+        let issues = path_issues(&package(&["nul"], &[]));
+        assert!(check_path_issues(&Strictness::default(), &issues).is_ok());
+        assert!(matches!(
+            check_path_issues(&Strictness::strict(), &issues),
+            Err(PackageError::StrictViolation {
+                check: "windows-paths",
+                ..
+            })
+        ));
+
+        let report = crate::json::parse(&path_report_json(&issues)).unwrap();
+        let first = &report.get("issues").unwrap().as_array().unwrap()[0];
+        assert_eq!(
+            first.get("problem").and_then(JsonValue::as_str),
+            Some("reserved-name")
+        );
+    }
+}
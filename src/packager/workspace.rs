@@ -0,0 +1,631 @@
+//! Per-run temp workspace.
+//!
+//! Every intermediate lives under `$TMP/iamawrapper-<uid>/<run-id>/`, where
+//! `$TMP` is the folder set with [`set_temp_dir`] (`--temp-dir`) or the
+//! system temp directory (`TMPDIR`, `TMP`/`TEMP` on Windows). Each user
+//! has a root of their own (see [`workspace_root`]), and the folders are
+//! private to them. Staging folders are created exclusively, so concurrent
+//! runs and threads never share one, and removed when dropped, also while
+//! unwinding from a panic; the run folder goes with the last of them.
+//! Folders left behind by crashed runs are purged with
+//! `iamawrapper clean-workspaces`. Files are staged as clones or hard links
+//! where possible, so large payloads are not copied byte by byte.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use walkdir::WalkDir;
+
+use crate::models::error::{PackageError, PackageResult};
+use crate::models::glob::SourceFilter;
+use crate::models::size::ByteSize;
+
+use super::special::{PRESERVE_SYMLINKS, WalkItem, classify};
+
+/// Name of the folder under the temp directory holding all run
+/// workspaces; on Unix the effective user ID is appended.
+pub const WORKSPACE_DIR: &str = "iamawrapper";
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Identifier of this process's run: `<unix-seconds>-<pid>-<nonce>`.
+///
+/// The leading timestamp lets `clean-workspaces` age folders without
+/// trusting filesystem times.
+pub fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        format!("{}-{}-{:08x}", now_secs(), std::process::id(), nanos)
+    })
+}
+
+/// Temp folder set with [`set_temp_dir`].
+static TEMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Free space kept on the workspace volume beyond what a run needs.
+const SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Put run workspaces under `dir` instead of the system temp directory.
+///
+/// Must be called before the first staging folder is created, and only
+/// once; `dir` is created if needed.
+pub fn set_temp_dir(dir: impl Into<PathBuf>) -> PackageResult<()> {
+    let dir = dir.into();
+    fs::create_dir_all(&dir).map_err(|e| PackageError::output_folder(dir.clone(), e))?;
+    let dir = fs::canonicalize(&dir).unwrap_or(dir);
+    if TEMP_DIR.get().is_some_and(|set| *set != dir) {
+        return Err(PackageError::InvalidArgument {
+            reason: "The temp directory can only be set once per process".to_string(),
+        });
+    }
+    let _ = TEMP_DIR.set(dir);
+    Ok(())
+}
+
+/// Temp folder run workspaces are created in.
+pub fn temp_dir() -> PathBuf {
+    TEMP_DIR.get().cloned().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Folder holding the current user's run workspaces:
+/// `iamawrapper-<euid>` on Unix, so users sharing a temp directory never
+/// share a root, and `iamawrapper` elsewhere.
+pub fn workspace_root() -> PathBuf {
+    #[cfg(unix)]
+    let name = format!("{}-{}", WORKSPACE_DIR, unsafe { libc::geteuid() });
+    #[cfg(not(unix))]
+    let name = WORKSPACE_DIR.to_string();
+    temp_dir().join(name)
+}
+
+/// This run's workspace folder (created on first use).
+pub fn run_workspace() -> PathBuf {
+    workspace_root().join(run_id())
+}
+
+/// Creation time in Unix seconds encoded in a run ID, if it is one.
+fn run_started(run_id: &str) -> Option<u64> {
+    let mut parts = run_id.splitn(3, '-');
+    let secs = parts.next()?.parse().ok()?;
+    parts.next()?.parse::<u32>().ok()?;
+    let nonce = parts.next()?;
+    (nonce.len() == 8 && nonce.chars().all(|c| c.is_ascii_hexdigit())).then_some(secs)
+}
+
+/// Temporary staging folder in the run workspace, removed when dropped.
+pub(crate) struct StagingDir {
+    pub(crate) path: PathBuf,
+}
+
+impl StagingDir {
+    /// Create an empty folder named after `purpose` in the run workspace.
+    pub(crate) fn create(purpose: &str) -> PackageResult<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let workspace = run_workspace();
+
+        loop {
+            let path = workspace.join(format!(
+                "{}-{}",
+                purpose,
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            // Another staging folder being dropped may remove the empty run
+            // folder while this one is created
+            let mut result = create_exclusive(&workspace, &path);
+            for _ in 0..3 {
+                match &result {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        result = create_exclusive(&workspace, &path);
+                    }
+                    _ => break,
+                }
+            }
+            match result {
+                Ok(()) => return Ok(Self { path }),
+                // Left behind by an earlier run with the same ID; never reuse it
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(PackageError::output_folder(path, e)),
+            }
+        }
+    }
+
+    /// Like [`create`](Self::create), after checking the workspace volume
+    /// has `needed` bytes free (see [`ensure_free_space`]).
+    pub(crate) fn create_with_space(purpose: &str, needed: u64) -> PackageResult<Self> {
+        ensure_free_space(needed)?;
+        Self::create(purpose)
+    }
+}
+
+/// Bytes staging the tree at `source` writes to the workspace volume: none
+/// when both are on the same volume, where files are cloned or hard
+/// linked, and the size of its files otherwise.
+#[cfg_attr(not(feature = "macos"), allow(dead_code))]
+pub(crate) fn staged_copy_size(source: &Path) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = temp_dir();
+        let device = |path: &Path| {
+            path.ancestors()
+                .find_map(|p| fs::metadata(p).ok())
+                .map(|m| m.dev())
+        };
+        if device(source).is_some_and(|dev| device(&temp) == Some(dev)) {
+            return 0;
+        }
+    }
+    WalkDir::new(source)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Create `workspace` if needed and `path` in it, failing if `path` exists.
+///
+/// The user's root, the run folder and `path` are private to the current
+/// user (mode 0700 on Unix), and a root or run folder that already exists
+/// must belong to the user and not be writable by anyone else, so other
+/// local users can neither read intermediates nor plant folders to be used
+/// in their place.
+fn create_exclusive(workspace: &Path, path: &Path) -> std::io::Result<()> {
+    if let Some(root) = workspace.parent() {
+        fs::create_dir_all(root.parent().unwrap_or(root))?;
+        create_private(root)?;
+    }
+    create_private(workspace)?;
+    private_dir_builder().create(path)
+}
+
+/// Create `dir` private to the current user, or check the existing one is.
+fn create_private(dir: &Path) -> std::io::Result<()> {
+    match private_dir_builder().create(dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => check_private(dir),
+        result => result,
+    }
+}
+
+fn private_dir_builder() -> fs::DirBuilder {
+    #[allow(unused_mut)]
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+}
+
+/// Fail unless `dir` is a folder (not a link to one) owned by the current
+/// user that group and others cannot write to.
+#[cfg(unix)]
+fn check_private(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::geteuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o022 != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "'{}' is not a folder owned by the current user and writable only by them; \
+                 remove it or use --temp-dir",
+                dir.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Check the volume of the run workspace has `needed` bytes free, plus a
+/// safety margin, so a run fails up front instead of halfway through
+/// writing its intermediates.
+///
+/// Passes when free space cannot be determined on this platform.
+pub fn ensure_free_space(needed: u64) -> PackageResult<()> {
+    let path = temp_dir();
+    let Some(available) = available_space(&path) else {
+        return Ok(());
+    };
+    let needed = needed.saturating_add(SPACE_MARGIN);
+    if available < needed {
+        return Err(PackageError::InsufficientSpace {
+            path,
+            needed: ByteSize(needed),
+            available: ByteSize(available),
+        });
+    }
+    Ok(())
+}
+
+/// Bytes available to this process on the volume holding `path`, or its
+/// nearest existing ancestor.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `path` is a valid NUL-terminated string and `stats` a
+    // writable statvfs that is only read after a successful call
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } == -1 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+/// Bytes available to this process on the volume holding `path`; unknown
+/// on this platform.
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+        // Only succeeds once no other staging folder of the run is left
+        let _ = fs::remove_dir(run_workspace());
+    }
+}
+
+/// How a file was placed into a staging folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageMethod {
+    /// Copy-on-write clone (`clonefile` on macOS, `FICLONE` on Linux)
+    Reflink,
+    /// Hard link to the source file
+    Hardlink,
+    /// Full byte copy
+    Copy,
+}
+
+/// Place `from` at `to` as cheaply as the filesystem allows.
+///
+/// Tries a copy-on-write clone, then a hard link, and falls back to a byte
+/// copy when source and staging are on different volumes or the
+/// filesystem supports neither. Staged files must not be modified, since a
+/// hard link shares its data with the source.
+pub fn stage_file(from: &Path, to: &Path) -> std::io::Result<StageMethod> {
+    if reflink(from, to).is_ok() {
+        return Ok(StageMethod::Reflink);
+    }
+    if fs::hard_link(from, to).is_ok() {
+        return Ok(StageMethod::Hardlink);
+    }
+    fs::copy(from, to)?;
+    Ok(StageMethod::Copy)
+}
+
+/// Stage the tree at `source` under `target`, leaving out special entries.
+///
+/// Symbolic links are recreated as links where [`PRESERVE_SYMLINKS`] holds;
+/// elsewhere the files they point at are staged. Files listed in `replaced` (by path relative to
+/// `source`) are written with the given contents instead. Paths left out
+/// by `filter` are not staged.
+#[cfg_attr(not(feature = "macos"), allow(dead_code))]
+pub(crate) fn stage_tree(
+    source: &Path,
+    target: &Path,
+    replaced: &BTreeMap<PathBuf, Vec<u8>>,
+    filter: &SourceFilter,
+) -> PackageResult<()> {
+    let walker = WalkDir::new(source)
+        .follow_links(!PRESERVE_SYMLINKS)
+        .into_iter()
+        .filter_entry(|e| {
+            e.depth() == 0 || !filter.excludes(e.path().strip_prefix(source).unwrap_or(e.path()))
+        });
+    for result in walker {
+        let entry = match classify(source, result) {
+            WalkItem::Entry(entry) => entry,
+            WalkItem::Skipped(_) => continue,
+            WalkItem::Error(e) => return Err(PackageError::source_read(source.to_path_buf(), e)),
+        };
+        let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+        if entry.depth() > 0 && !filter.keeps(relative) {
+            continue;
+        }
+        let staged = target.join(relative);
+        let metadata = entry
+            .metadata()
+            .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))?;
+        let write_error = |e: std::io::Error| PackageError::output_write(staged.clone(), e);
+
+        // Folders not matched by an include pattern were not staged
+        if !metadata.is_dir() && !filter.include.is_empty() {
+            if let Some(parent) = staged.parent() {
+                fs::create_dir_all(parent).map_err(write_error)?;
+            }
+        }
+
+        if metadata.file_type().is_symlink() {
+            // Links carry no permissions of their own
+            let target = fs::read_link(entry.path())
+                .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(target, &staged).map_err(write_error)?;
+            #[cfg(not(unix))]
+            let _ = target;
+            continue;
+        } else if metadata.is_dir() {
+            fs::create_dir_all(&staged).map_err(write_error)?;
+        } else if let Some(data) = replaced.get(relative) {
+            fs::write(&staged, data).map_err(write_error)?;
+        } else if metadata.is_file() {
+            let from = fs::canonicalize(entry.path())
+                .map_err(|e| PackageError::source_read(entry.path().to_path_buf(), e))?;
+            stage_file(&from, &staged).map_err(write_error)?;
+            continue;
+        } else {
+            continue;
+        }
+        fs::set_permissions(&staged, metadata.permissions()).map_err(write_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let source = fs::File::open(from)?;
+    let target = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)?;
+
+    // SAFETY: both descriptors are open for the duration of the call
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } == -1 {
+        let error = std::io::Error::last_os_error();
+        drop(target);
+        let _ = fs::remove_file(to);
+        return Err(error);
+    }
+
+    target.set_permissions(source.metadata()?.permissions())
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(from: &Path, to: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+
+    // SAFETY: both paths are valid NUL-terminated strings
+    if unsafe { libc::clonefile(from.as_ptr(), to.as_ptr(), 0) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_from: &Path, _to: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Remove run workspaces under `root` started at least `older_than` ago.
+///
+/// The current run's workspace and folders that are not run workspaces are
+/// never touched. With `dry_run` nothing is removed. Returns the folders
+/// that were (or would be) removed.
+pub fn clean_workspaces(
+    root: &Path,
+    older_than: Duration,
+    dry_run: bool,
+) -> PackageResult<Vec<PathBuf>> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(PackageError::source_read(root.to_path_buf(), e));
+        }
+    };
+
+    let cutoff = now_secs().saturating_sub(older_than.as_secs());
+    let mut removed = Vec::new();
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let stale = run_started(&name).is_some_and(|started| started <= cutoff);
+        if !stale || name == run_id() || !entry.path().is_dir() {
+            continue;
+        }
+
+        if !dry_run {
+            fs::remove_dir_all(entry.path())
+                .map_err(|e| PackageError::output_write(entry.path(), e))?;
+        }
+        removed.push(entry.path());
+    }
+
+    removed.sort();
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_id_format() {
+        let started = run_started(run_id()).unwrap();
+        assert!(started <= now_secs());
+        assert_eq!(run_started("1700000000-42-0badf00d"), Some(1_700_000_000));
+        assert_eq!(run_started("notes"), None);
+        assert_eq!(run_started("2024-01-report"), None);
+    }
+
+    #[test]
+    fn test_staging_dir_lives_in_run_workspace() {
+        let staging = StagingDir::create("test").unwrap();
+        assert!(staging.path.starts_with(run_workspace()));
+        assert!(staging.path.is_dir());
+
+        let path = staging.path.clone();
+        drop(staging);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_folders_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let staging = StagingDir::create("private").unwrap();
+        for dir in [&staging.path, &run_workspace()] {
+            let mode = fs::metadata(dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700, "{}", dir.display());
+        }
+        // Each user has a root of their own, which is never open
+        let uid = unsafe { libc::geteuid() };
+        assert!(workspace_root().ends_with(format!("iamawrapper-{}", uid)));
+        let mode = fs::metadata(workspace_root()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o022, 0);
+
+        // A folder others can write to, or a link, is not used
+        let temp = TempDir::new().unwrap();
+        let open = temp.path().join("open");
+        fs::create_dir(&open).unwrap();
+        fs::set_permissions(&open, fs::Permissions::from_mode(0o777)).unwrap();
+        let err = create_private(&open).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        std::os::unix::fs::symlink(temp.path(), temp.path().join("link")).unwrap();
+        assert!(create_private(&temp.path().join("link")).is_err());
+        let owned = temp.path().join("owned");
+        fs::create_dir(&owned).unwrap();
+        fs::set_permissions(&owned, fs::Permissions::from_mode(0o755)).unwrap();
+        create_private(&owned).unwrap();
+    }
+
+    #[test]
+    fn test_staging_dirs_are_unique_across_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(|| StagingDir::create("thread").unwrap()))
+            .collect();
+        let staged: Vec<StagingDir> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let unique: std::collections::BTreeSet<_> = staged.iter().map(|s| &s.path).collect();
+        assert_eq!(unique.len(), staged.len());
+    }
+
+    #[test]
+    fn test_staging_dir_removed_on_panic() {
+        let path = std::sync::Mutex::new(PathBuf::new());
+        let result = std::panic::catch_unwind(|| {
+            let staging = StagingDir::create("panic").unwrap();
+            fs::write(staging.path.join("partial.zip"), "half").unwrap();
+            *path.lock().unwrap() = staging.path.clone();
+            panic!("packaging failed");
+        });
+        assert!(result.is_err());
+        let path = path.into_inner().unwrap();
+        assert!(path.starts_with(run_workspace()));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_free_space_preflight() {
+        assert!(ensure_free_space(0).is_ok());
+        assert!(
+            matches!(
+                StagingDir::create_with_space("huge", u64::MAX / 2),
+                Err(PackageError::InsufficientSpace { .. })
+            ) || available_space(&temp_dir()).is_none()
+        );
+        if cfg!(unix) {
+            let temp = TempDir::new().unwrap();
+            assert!(available_space(&temp.path().join("not/yet/created")).is_some());
+        }
+    }
+
+    #[test]
+    fn test_stage_file_shares_or_copies_data() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source.bin");
+        fs::write(&source, "payload").unwrap();
+        let staged = temp.path().join("staged.bin");
+
+        let method = stage_file(&source, &staged).unwrap();
+        assert_eq!(fs::read(&staged).unwrap(), b"payload");
+
+        // Same volume: never a full copy on filesystems with hard links
+        if cfg!(unix) {
+            assert_ne!(method, StageMethod::Copy);
+        }
+
+        assert!(stage_file(&temp.path().join("missing"), &staged).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stage_tree_keeps_symlinks() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("source");
+        fs::create_dir_all(source.join("Versions/A")).unwrap();
+        fs::write(source.join("Versions/A/lib"), "binary").unwrap();
+        std::os::unix::fs::symlink("A", source.join("Versions/Current")).unwrap();
+
+        let target = temp.path().join("staged");
+        stage_tree(&source, &target, &BTreeMap::new(), &SourceFilter::default()).unwrap();
+
+        let link = target.join("Versions/Current");
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("A"));
+        assert_eq!(fs::read(link.join("lib")).unwrap(), b"binary");
+    }
+
+    #[test]
+    fn test_clean_workspaces_removes_stale_runs_only() {
+        let root = TempDir::new().unwrap();
+        let stale = root.path().join("1000-42-00000000");
+        let fresh = root.path().join(format!("{}-42-00000000", now_secs()));
+        let current = root.path().join(run_id());
+        let other = root.path().join("keep-me");
+        for dir in [&stale, &fresh, &current, &other] {
+            fs::create_dir_all(dir.join("capture-0")).unwrap();
+        }
+
+        let listed = clean_workspaces(root.path(), Duration::ZERO, true).unwrap();
+        assert_eq!(listed, vec![stale.clone(), fresh.clone()]);
+        assert!(stale.exists());
+
+        let removed = clean_workspaces(root.path(), Duration::from_secs(60), false).unwrap();
+        assert_eq!(removed, vec![stale.clone()]);
+        assert!(!stale.exists());
+        assert!(fresh.exists() && current.exists() && other.exists());
+    }
+}
@@ -0,0 +1,625 @@
+//! Detached signatures over finished packages, in minisign's format.
+//!
+//! A package signed with `--sign-key` gets a `<package>.minisig` next to
+//! it, so the pipeline that uploads it can check that it is the file the
+//! packaging pipeline built. Keys and signatures are those of
+//! [minisign](https://jedisct1.github.io/minisign/): `minisign -G` creates
+//! a key pair, and `minisign -Vm <package> -p <key>.pub` checks a signature
+//! just as `iamawrapper verify-signature` does.
+//!
+//! Signatures are Ed25519 over the BLAKE2b-512 hash of the file (minisign's
+//! default), so files of any size are hashed as they are read. Legacy
+//! signatures over the whole file (`minisign -l`) are not supported.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Digest};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::models::error::{PackageError, PackageResult};
+
+/// Extension of signature files, appended to the name of the signed file.
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// Algorithm of Ed25519 keys, and of legacy signatures over the whole file.
+const ALG_ED25519: [u8; 2] = *b"Ed";
+/// Algorithm of signatures over the BLAKE2b-512 hash of the file.
+const ALG_HASHED: [u8; 2] = *b"ED";
+/// Key derivation of secret keys encrypted with scrypt.
+const KDF_SCRYPT: [u8; 2] = *b"Sc";
+/// Key derivation of unencrypted secret keys (`minisign -W`).
+const KDF_NONE: [u8; 2] = [0, 0];
+/// Checksum of secret keys (BLAKE2b-256).
+const CHECKSUM_BLAKE2B: [u8; 2] = *b"B2";
+
+/// Scrypt limits minisign encrypts new secret keys with.
+const OPSLIMIT: u64 = 1 << 25;
+const MEMLIMIT: u64 = 1 << 30;
+
+/// Most memory an encrypted secret key may make scrypt use; minisign's own
+/// keys need 1 GiB.
+const MAX_SCRYPT_MEMORY: u64 = 2 << 30;
+
+/// Key ID, secret key and checksum: the part of a secret key that is
+/// encrypted.
+const KEYNUM_LEN: usize = 8 + 64 + 32;
+/// Algorithms, salt, scrypt limits and the encrypted part.
+const SECRET_KEY_LEN: usize = 2 + 2 + 2 + 32 + 8 + 8 + KEYNUM_LEN;
+/// Algorithm, key ID and key.
+const PUBLIC_KEY_LEN: usize = 2 + 8 + 32;
+/// Algorithm, key ID and signature.
+const SIGNATURE_LEN: usize = 2 + 8 + 64;
+
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+
+/// A key ID as minisign prints it, e.g. `E7620F1842B4E81F`.
+fn key_id_hex(key_id: &[u8; 8]) -> String {
+    format!("{:016X}", u64::from_le_bytes(*key_id))
+}
+
+/// The Base64 payload of a key: the first line that is not a comment. A
+/// bare payload, as `minisign -P` takes it, is read too.
+fn payload(text: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    let line = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_PREFIX))
+        .ok_or("The key is empty")?;
+    BASE64
+        .decode(line)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("The key is not valid Base64: {}", e))
+}
+
+/// Checksum of a secret key, over its algorithm, key ID and key.
+fn checksum(key_id: &[u8], secret: &[u8]) -> [u8; 32] {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update(ALG_ED25519);
+    hasher.update(key_id);
+    hasher.update(secret);
+    hasher.finalize().into()
+}
+
+/// libsodium's scrypt log2(N), r and p for an ops and memory limit
+/// (`pickparams`), which minisign stores in encrypted keys.
+fn scrypt_params(opslimit: u64, memlimit: u64) -> (u8, u32, u32) {
+    let opslimit = opslimit.max(32768);
+    let r = 8u32;
+    let log_n = |max_n: u64| {
+        (1..63)
+            .find(|&log_n| 1u64 << log_n > max_n / 2)
+            .unwrap_or(63)
+    };
+    if opslimit < memlimit / 32 {
+        (log_n(opslimit / (u64::from(r) * 4)), r, 1)
+    } else {
+        let log_n = log_n(memlimit / (u64::from(r) * 128));
+        let max_rp = ((opslimit / 4) >> log_n).min(0x3fff_ffff);
+        (log_n, r, max_rp as u32 / r)
+    }
+}
+
+/// The stream a secret key is encrypted with, derived from `password`.
+fn key_stream(
+    password: &str,
+    salt: &[u8],
+    opslimit: u64,
+    memlimit: u64,
+) -> Result<Zeroizing<[u8; KEYNUM_LEN]>, String> {
+    let (log_n, r, p) = scrypt_params(opslimit, memlimit);
+    if (128 * u64::from(r))
+        .checked_shl(u32::from(log_n))
+        .unwrap_or(u64::MAX)
+        > MAX_SCRYPT_MEMORY
+    {
+        return Err("The key is encrypted with more memory than allowed".to_string());
+    }
+    // The stream's length comes from the buffer; `len` only serves
+    // password hashes
+    let params = scrypt::Params::new(log_n, r, p, scrypt::Params::RECOMMENDED_LEN)
+        .map_err(|e| format!("The key has invalid scrypt parameters: {}", e))?;
+
+    let mut stream = Zeroizing::new([0u8; KEYNUM_LEN]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, stream.as_mut())
+        .map_err(|e| format!("Could not derive the key from its password: {}", e))?;
+    Ok(stream)
+}
+
+fn xor(data: &mut [u8], stream: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(stream) {
+        *byte ^= key;
+    }
+}
+
+/// Hash the file at `path` the way minisign signs it.
+fn hash_file(path: &Path) -> io::Result<[u8; 64]> {
+    let mut hasher = Blake2b512::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// A minisign secret key, wiped from memory when dropped.
+#[derive(Clone)]
+pub struct SecretKey {
+    key_id: [u8; 8],
+    key: SigningKey,
+}
+
+impl SecretKey {
+    /// A new random key pair.
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut seed = Zeroizing::new([0u8; 32]);
+        rng.fill_bytes(seed.as_mut());
+        let mut key_id = [0u8; 8];
+        rng.fill_bytes(&mut key_id);
+        Self {
+            key_id,
+            key: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    /// Whether the key in `text` needs a password.
+    pub fn is_encrypted(text: &str) -> bool {
+        payload(text).is_ok_and(|data| data.len() == SECRET_KEY_LEN && data[2..4] == KDF_SCRYPT)
+    }
+
+    /// Read a secret key as `minisign -G` writes it, decrypting it with
+    /// `password` unless it was created unencrypted (`-W`).
+    pub fn parse(text: &str, password: Option<&str>) -> Result<Self, String> {
+        let data = payload(text)?;
+        if data.len() != SECRET_KEY_LEN {
+            return Err("Not a minisign secret key".to_string());
+        }
+        if data[..2] != ALG_ED25519 || data[4..6] != CHECKSUM_BLAKE2B {
+            return Err("The key uses an unsupported algorithm".to_string());
+        }
+
+        let mut keynum = Zeroizing::new([0u8; KEYNUM_LEN]);
+        keynum.copy_from_slice(&data[SECRET_KEY_LEN - KEYNUM_LEN..]);
+        let encrypted = data[2..4] == KDF_SCRYPT;
+        if encrypted {
+            let password = password.ok_or("The key is encrypted and no password was given")?;
+            let limit = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+            let stream = key_stream(password, &data[6..38], limit(38), limit(46))?;
+            xor(keynum.as_mut(), stream.as_ref());
+        } else if data[2..4] != KDF_NONE {
+            return Err("The key is encrypted with an unsupported algorithm".to_string());
+        }
+
+        let (key_id, rest) = keynum.split_at(8);
+        let (secret, stored) = rest.split_at(64);
+        if checksum(key_id, secret) != stored {
+            return Err(if encrypted {
+                "Wrong password, or the key is damaged".to_string()
+            } else {
+                "The key is damaged".to_string()
+            });
+        }
+
+        let mut seed = Zeroizing::new([0u8; 32]);
+        seed.copy_from_slice(&secret[..32]);
+        let key = SigningKey::from_bytes(&seed);
+        if key.verifying_key().as_bytes()[..] != secret[32..] {
+            return Err("The key is damaged".to_string());
+        }
+        Ok(Self {
+            key_id: key_id.try_into().unwrap(),
+            key,
+        })
+    }
+
+    /// Read the secret key at `path`.
+    pub fn load(path: &Path, password: Option<&str>) -> PackageResult<Self> {
+        let error = |reason: String| PackageError::SigningError {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let text = fs::read_to_string(path)
+            .map(Zeroizing::new)
+            .map_err(|e| error(e.to_string()))?;
+        Self::parse(&text, password).map_err(error)
+    }
+
+    /// The key in minisign's format, encrypted with `password` if one is
+    /// given.
+    pub fn to_text(&self, password: Option<&str>) -> Result<Zeroizing<String>, String> {
+        self.encode(password, OPSLIMIT, MEMLIMIT)
+    }
+
+    fn encode(
+        &self,
+        password: Option<&str>,
+        opslimit: u64,
+        memlimit: u64,
+    ) -> Result<Zeroizing<String>, String> {
+        let mut keynum = Zeroizing::new([0u8; KEYNUM_LEN]);
+        keynum[..8].copy_from_slice(&self.key_id);
+        keynum[8..40].copy_from_slice(self.key.as_bytes());
+        keynum[40..72].copy_from_slice(self.key.verifying_key().as_bytes());
+        let checksum = checksum(&self.key_id, &keynum[8..72]);
+        keynum[72..].copy_from_slice(&checksum);
+
+        let mut salt = [0u8; 32];
+        let (kdf, opslimit, memlimit) = match password {
+            Some(password) => {
+                rand::thread_rng().fill_bytes(&mut salt);
+                let stream = key_stream(password, &salt, opslimit, memlimit)?;
+                xor(keynum.as_mut(), stream.as_ref());
+                (KDF_SCRYPT, opslimit, memlimit)
+            }
+            None => (KDF_NONE, 0, 0),
+        };
+
+        let mut data = Zeroizing::new(Vec::with_capacity(SECRET_KEY_LEN));
+        data.extend_from_slice(&ALG_ED25519);
+        data.extend_from_slice(&kdf);
+        data.extend_from_slice(&CHECKSUM_BLAKE2B);
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&opslimit.to_le_bytes());
+        data.extend_from_slice(&memlimit.to_le_bytes());
+        data.extend_from_slice(keynum.as_ref());
+
+        let encoded = Zeroizing::new(BASE64.encode(data.as_slice()));
+        Ok(Zeroizing::new(format!(
+            "{}minisign {}secret key\n{}\n",
+            UNTRUSTED_PREFIX,
+            if password.is_some() { "encrypted " } else { "" },
+            encoded.as_str()
+        )))
+    }
+
+    /// The public half of the key.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            key_id: self.key_id,
+            key: self.key.verifying_key(),
+        }
+    }
+
+    /// The key ID, as minisign prints it.
+    pub fn key_id(&self) -> String {
+        key_id_hex(&self.key_id)
+    }
+
+    /// Sign the file at `path`. `trusted_comment` is covered by the
+    /// signature too; [`default_trusted_comment`] gives minisign's.
+    pub fn sign_file(&self, path: &Path, trusted_comment: &str) -> PackageResult<Signature> {
+        if trusted_comment.contains(['\r', '\n']) {
+            return Err(PackageError::InvalidArgument {
+                reason: "A trusted comment must be a single line".to_string(),
+            });
+        }
+        let hash = hash_file(path).map_err(|e| PackageError::source_read(path, e))?;
+        let signature = self.key.sign(&hash).to_bytes();
+        let global_signature = self
+            .key
+            .sign(&[&signature[..], trusted_comment.as_bytes()].concat())
+            .to_bytes();
+
+        Ok(Signature {
+            key_id: self.key_id,
+            signature,
+            trusted_comment: trusted_comment.to_string(),
+            global_signature,
+        })
+    }
+}
+
+// The key itself is kept out of debug output
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey({})", self.key_id())
+    }
+}
+
+/// A minisign public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    key_id: [u8; 8],
+    key: VerifyingKey,
+}
+
+impl PublicKey {
+    /// Read a public key as `minisign -G` writes it, or its Base64 line
+    /// alone.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let data = payload(text)?;
+        if data.len() != PUBLIC_KEY_LEN {
+            return Err("Not a minisign public key".to_string());
+        }
+        if data[..2] != ALG_ED25519 {
+            return Err("The key uses an unsupported algorithm".to_string());
+        }
+        let key = VerifyingKey::from_bytes(data[10..].try_into().unwrap())
+            .map_err(|_| "The key is not a valid Ed25519 key".to_string())?;
+        Ok(Self {
+            key_id: data[2..10].try_into().unwrap(),
+            key,
+        })
+    }
+
+    /// Read the public key at `path`.
+    pub fn load(path: &Path) -> PackageResult<Self> {
+        let error = |reason: String| PackageError::SigningError {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Self::parse(&text).map_err(error)
+    }
+
+    /// The key in minisign's format.
+    pub fn to_text(&self) -> String {
+        let mut data = Vec::with_capacity(PUBLIC_KEY_LEN);
+        data.extend_from_slice(&ALG_ED25519);
+        data.extend_from_slice(&self.key_id);
+        data.extend_from_slice(self.key.as_bytes());
+        format!(
+            "{}minisign public key {}\n{}\n",
+            UNTRUSTED_PREFIX,
+            self.key_id(),
+            BASE64.encode(data)
+        )
+    }
+
+    /// The key ID, as minisign prints it.
+    pub fn key_id(&self) -> String {
+        key_id_hex(&self.key_id)
+    }
+
+    /// Check that `signature` was made over the file at `path` with this
+    /// key, and that its trusted comment is unchanged.
+    pub fn verify_file(&self, path: &Path, signature: &Signature) -> PackageResult<()> {
+        let invalid = |reason: String| PackageError::SignatureInvalid {
+            path: path.to_path_buf(),
+            reason,
+        };
+        if signature.key_id != self.key_id {
+            return Err(invalid(format!(
+                "It was made with key {}, not {}",
+                key_id_hex(&signature.key_id),
+                self.key_id()
+            )));
+        }
+
+        let hash = hash_file(path).map_err(|e| PackageError::source_read(path, e))?;
+        let ed25519 = ed25519_dalek::Signature::from_bytes(&signature.signature);
+        self.key
+            .verify_strict(&hash, &ed25519)
+            .map_err(|_| invalid("The file does not match the signature".to_string()))?;
+
+        let global = ed25519_dalek::Signature::from_bytes(&signature.global_signature);
+        let signed = [
+            &signature.signature[..],
+            signature.trusted_comment.as_bytes(),
+        ]
+        .concat();
+        self.key
+            .verify_strict(&signed, &global)
+            .map_err(|_| invalid("The trusted comment has been altered".to_string()))
+    }
+}
+
+/// A detached signature over a file, as a `.minisig` file holds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    key_id: [u8; 8],
+    signature: [u8; 64],
+    trusted_comment: String,
+    global_signature: [u8; 64],
+}
+
+impl Signature {
+    /// Read a signature as minisign writes it.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .map(str::trim_end);
+        let mut line = || lines.next().ok_or("The signature is incomplete");
+        if !line()?.starts_with(UNTRUSTED_PREFIX) {
+            return Err("Not a minisign signature".to_string());
+        }
+        let data = BASE64
+            .decode(line()?)
+            .map_err(|e| format!("The signature is not valid Base64: {}", e))?;
+        let trusted_comment = line()?
+            .strip_prefix(TRUSTED_PREFIX)
+            .ok_or("The signature has no trusted comment")?
+            .to_string();
+        let global = BASE64
+            .decode(line()?)
+            .map_err(|e| format!("The comment signature is not valid Base64: {}", e))?;
+
+        if data.len() != SIGNATURE_LEN || global.len() != 64 {
+            return Err("Not a minisign signature".to_string());
+        }
+        if data[..2] == ALG_ED25519 {
+            return Err(
+                "Legacy signatures over the whole file (minisign -l) are not supported".to_string(),
+            );
+        }
+        if data[..2] != ALG_HASHED {
+            return Err("The signature uses an unsupported algorithm".to_string());
+        }
+        Ok(Self {
+            key_id: data[2..10].try_into().unwrap(),
+            signature: data[10..].try_into().unwrap(),
+            trusted_comment,
+            global_signature: global.try_into().unwrap(),
+        })
+    }
+
+    /// Read the signature at `path`.
+    pub fn load(path: &Path) -> PackageResult<Self> {
+        let error = |reason: String| PackageError::SignatureInvalid {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        Self::parse(&text).map_err(error)
+    }
+
+    /// Write the signature to `path`.
+    pub fn save(&self, path: &Path) -> PackageResult<()> {
+        fs::write(path, self.to_text())
+            .map_err(|e| PackageError::output_write(path.to_path_buf(), e))
+    }
+
+    /// The signature in minisign's format.
+    pub fn to_text(&self) -> String {
+        let mut data = Vec::with_capacity(SIGNATURE_LEN);
+        data.extend_from_slice(&ALG_HASHED);
+        data.extend_from_slice(&self.key_id);
+        data.extend_from_slice(&self.signature);
+        format!(
+            "{}signature from iamawrapper secret key\n{}\n{}{}\n{}\n",
+            UNTRUSTED_PREFIX,
+            BASE64.encode(data),
+            TRUSTED_PREFIX,
+            self.trusted_comment,
+            BASE64.encode(self.global_signature)
+        )
+    }
+
+    /// ID of the key the signature was made with.
+    pub fn key_id(&self) -> String {
+        key_id_hex(&self.key_id)
+    }
+
+    /// The comment covered by the signature, e.g. the time and file name
+    /// of [`default_trusted_comment`].
+    pub fn trusted_comment(&self) -> &str {
+        &self.trusted_comment
+    }
+}
+
+/// Where the signature of the file at `path` is kept: next to it, with
+/// `.minisig` appended to its name.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// The trusted comment minisign writes: when the file was signed and its
+/// name.
+pub fn default_trusted_comment(path: &Path) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    format!("timestamp:{}\tfile:{}\thashed", timestamp, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sign_and_verify_file() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("app.intunewin");
+        fs::write(&path, b"package content").unwrap();
+
+        let key = SecretKey::parse(&SecretKey::generate().to_text(None).unwrap(), None).unwrap();
+        let public = PublicKey::parse(&key.public_key().to_text()).unwrap();
+        assert_eq!(public.key_id(), key.key_id());
+
+        let comment = default_trusted_comment(&path);
+        assert!(
+            comment.ends_with("\tfile:app.intunewin\thashed"),
+            "{}",
+            comment
+        );
+        let signature = key.sign_file(&path, &comment).unwrap();
+        let signature = Signature::parse(&signature.to_text()).unwrap();
+        assert_eq!(signature.trusted_comment(), comment);
+        public.verify_file(&path, &signature).unwrap();
+
+        // A changed comment, another key or a changed file all fail
+        let mut altered = signature.clone();
+        altered.trusted_comment.push_str(" (edited)");
+        assert!(public.verify_file(&path, &altered).is_err());
+
+        let other = SecretKey::generate().public_key();
+        let err = other.verify_file(&path, &signature).unwrap_err();
+        assert!(err.to_string().contains(&key.key_id()), "{}", err);
+
+        fs::write(&path, b"package content!").unwrap();
+        let err = public.verify_file(&path, &signature).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+
+        assert_eq!(
+            signature_path(&path),
+            temp.path().join("app.intunewin.minisig")
+        );
+    }
+
+    #[test]
+    fn test_encrypted_secret_key() {
+        // Small scrypt limits; minisign's own take a second and 1 GiB
+        let key = SecretKey::generate();
+        let text = key.encode(Some("hunter2"), 32768, 1 << 24).unwrap();
+        assert!(SecretKey::is_encrypted(&text));
+        assert!(!SecretKey::is_encrypted(&key.to_text(None).unwrap()));
+
+        let parsed = SecretKey::parse(&text, Some("hunter2")).unwrap();
+        assert_eq!(parsed.public_key(), key.public_key());
+        assert!(
+            SecretKey::parse(&text, Some("wrong"))
+                .unwrap_err()
+                .contains("Wrong password")
+        );
+        assert!(SecretKey::parse(&text, None).is_err());
+        assert!(format!("{:?}", parsed).starts_with("SecretKey("));
+    }
+
+    #[test]
+    fn test_verify_minisign_signature() {
+        // Signature of "test" made by minisign itself
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test");
+        fs::write(&path, b"test").unwrap();
+
+        let public =
+            PublicKey::parse("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3").unwrap();
+        let signature = Signature::parse(concat!(
+            "untrusted comment: signature from minisign secret key\n",
+            "RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/",
+            "z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=\n",
+            "trusted comment: timestamp:1633700835\tfile:test\tprehashed\n",
+            "wLMDjy9FLAuxZ3q4NlEvkgtyhrr0gtTu6KC4KBJdITbbOeAi1zBIYo0v4iTgt8jJpIidRJnp94ABQkJAgAooBQ==\n",
+        ))
+        .unwrap();
+        assert_eq!(signature.key_id(), public.key_id());
+        public.verify_file(&path, &signature).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_malformed_keys_and_signatures() {
+        assert!(PublicKey::parse("").is_err());
+        assert!(PublicKey::parse("not base64!").is_err());
+        assert!(SecretKey::parse(&SecretKey::generate().public_key().to_text(), None).is_err());
+        assert!(Signature::parse("untrusted comment: x\nAAAA\n").is_err());
+
+        let public = SecretKey::generate().public_key().to_text();
+        assert!(Signature::parse(&public).is_err());
+    }
+}
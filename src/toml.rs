@@ -0,0 +1,244 @@
+//! Reader for the subset of TOML used by configuration files and batch
+//! manifests.
+//!
+//! Documents are tables (`[build]`, `[build.intune]`) of `key = value`
+//! lines. Values are strings (basic or literal), numbers, booleans, or
+//! arrays of those on one line. A document is returned as the
+//! [`JsonValue`] it corresponds to, so TOML and JSON files share the code
+//! that reads them.
+
+use crate::json::JsonValue;
+
+/// Parse a TOML document into a JSON object.
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut root = Vec::new();
+    let mut table: Vec<String> = Vec::new();
+
+    for (index, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let error = |reason: String| format!("Line {}: {}", index + 1, reason);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header
+                .strip_suffix(']')
+                .filter(|name| !name.starts_with('['))
+                .ok_or_else(|| error(format!("Unsupported table header {}", line)))?;
+            table = name
+                .split('.')
+                .map(|part| parse_key(part.trim()))
+                .collect::<Option<_>>()
+                .ok_or_else(|| error(format!("Invalid table name '{}'", name)))?;
+            table_mut(&mut root, &table).map_err(error)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("Expected 'key = value'".to_string()))?;
+        let key = parse_key(key.trim()).ok_or_else(|| error("Invalid key".to_string()))?;
+        let value = parse_value(value.trim()).ok_or_else(|| {
+            error(format!(
+                "Value of '{}' must be a string, number, boolean or array",
+                key
+            ))
+        })?;
+        let members = table_mut(&mut root, &table).map_err(error)?;
+        if members.iter().any(|(name, _)| *name == key) {
+            return Err(error(format!("Duplicate key '{}'", key)));
+        }
+        members.push((key, value));
+    }
+
+    Ok(JsonValue::Object(root))
+}
+
+/// The members of the table at `path`, created if missing.
+fn table_mut<'a>(
+    mut table: &'a mut Vec<(String, JsonValue)>,
+    path: &[String],
+) -> Result<&'a mut Vec<(String, JsonValue)>, String> {
+    for name in path {
+        let index = match table.iter().position(|(key, _)| key == name) {
+            Some(index) => index,
+            None => {
+                table.push((name.clone(), JsonValue::Object(Vec::new())));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            JsonValue::Object(members) => members,
+            _ => return Err(format!("'{}' is a value, not a table", name)),
+        };
+    }
+    Ok(table)
+}
+
+/// Drop a `#` comment that is not inside a string.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// A bare (`A-Za-z0-9_-`) or double-quoted key.
+pub(crate) fn parse_key(key: &str) -> Option<String> {
+    if let Some(quoted) = key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        return Some(quoted.to_string());
+    }
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    bare.then(|| key.to_string())
+}
+
+/// A literal (`'...'`) or basic (`"..."`) string, unescaped.
+pub(crate) fn parse_string(value: &str) -> Option<String> {
+    if let Some(literal) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Some(literal.to_string());
+    }
+    let basic = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))?;
+    let mut unescaped = String::new();
+    let mut chars = basic.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            c @ ('"' | '\\') => c,
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+fn parse_value(value: &str) -> Option<JsonValue> {
+    if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return split_array(items)?
+            .into_iter()
+            .map(parse_scalar)
+            .collect::<Option<_>>()
+            .map(JsonValue::Array);
+    }
+    parse_scalar(value)
+}
+
+fn parse_scalar(value: &str) -> Option<JsonValue> {
+    match value {
+        "true" => Some(JsonValue::Bool(true)),
+        "false" => Some(JsonValue::Bool(false)),
+        _ if value.starts_with(['"', '\'']) => parse_string(value).map(JsonValue::String),
+        _ => value.replace('_', "").parse().ok().map(JsonValue::Number),
+    }
+}
+
+/// Split the inside of a one-line array at the commas outside strings;
+/// `None` for nested arrays.
+fn split_array(items: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in items.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[' | ']') => return None,
+            (None, ',') => {
+                parts.push(items[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    // A trailing comma is allowed
+    let last = items[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tables() {
+        let value = parse(
+            r#"
+# Shared settings
+strict = true
+[build]
+name = "Contoso Tool" # trailing comment
+retries = 3
+
+[build.intune]
+setup = 'setup.exe'
+tags = ["a", "b#c",]
+
+[ "build" . macos ]
+identifier = "com.contoso.tool"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(value.get("strict").and_then(JsonValue::as_bool), Some(true));
+        let build = value.get("build").unwrap();
+        assert_eq!(
+            build.get("name").and_then(JsonValue::as_str),
+            Some("Contoso Tool")
+        );
+        assert_eq!(build.get("retries").and_then(JsonValue::as_u64), Some(3));
+        let intune = build.get("intune").unwrap();
+        assert_eq!(
+            intune.get("tags"),
+            Some(&JsonValue::Array(vec!["a".into(), "b#c".into()]))
+        );
+        assert_eq!(
+            build
+                .get("macos")
+                .and_then(|macos| macos.get("identifier"))
+                .and_then(JsonValue::as_str),
+            Some("com.contoso.tool")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("key").unwrap_err().contains("Line 1"));
+        assert!(
+            parse("a = 1\na = 2")
+                .unwrap_err()
+                .contains("Duplicate key 'a'")
+        );
+        assert!(parse("[[apps]]").is_err());
+        assert!(parse("a = 1\n[a]").unwrap_err().contains("not a table"));
+        assert!(parse("a = [[1]]").is_err());
+        assert!(parse("a = bare").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Integration tests for Chocolatey package creation.
+
+#![cfg(feature = "cli")]
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use predicates::prelude::*;
+use std::fs::{self, File};
+use std::io::Read;
+use tempfile::TempDir;
+use zip::ZipArchive;
+
+#[test]
+fn test_choco_pack() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("data")).unwrap();
+    fs::write(source_dir.join("setup.msi"), "msi content").unwrap();
+    fs::write(source_dir.join("data/config.xml"), "<config/>").unwrap();
+    fs::write(source_dir.join("data/debug.pdb"), "symbols").unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "choco",
+        "pack",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.msi",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--id",
+        "acme.tool",
+        "--version",
+        "2.1.0",
+        "--authors",
+        "ACME",
+        "--exclude",
+        "*.pdb",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Package created successfully"))
+        .stdout(predicate::str::contains("2 files included"));
+
+    let package = output_dir.join("acme.tool.2.1.0.nupkg");
+    let mut archive = ZipArchive::new(File::open(&package).unwrap()).unwrap();
+    let read = |archive: &mut ZipArchive<File>, name: &str| {
+        let mut data = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut data)
+            .unwrap();
+        data
+    };
+
+    let nuspec = read(&mut archive, "acme.tool.nuspec");
+    assert!(nuspec.contains("<version>2.1.0</version>"));
+    assert!(nuspec.contains("<authors>ACME</authors>"));
+
+    let script = read(&mut archive, "tools/chocolateyInstall.ps1");
+    assert!(script.contains("Join-Path $toolsDir 'setup.msi'"));
+    assert!(script.contains("'/qn /norestart'"));
+
+    assert_eq!(read(&mut archive, "tools/data/config.xml"), "<config/>");
+    assert!(archive.by_name("tools/data/debug.pdb").is_err());
+}
+
+#[test]
+fn test_choco_pack_invalid_id() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("setup.exe"), "setup").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "choco",
+        "pack",
+        "-c",
+        temp_dir.path().to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+        "--id",
+        "my app",
+        "--version",
+        "1.0",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid package id"));
+}
@@ -2,6 +2,8 @@
 //!
 //! Tests that the CLI properly routes to macos subcommands.
 
+#![cfg(feature = "cli")]
+
 use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 
@@ -83,3 +85,194 @@ fn test_no_args_shows_help_or_interactive() {
     // Just verify it doesn't crash - it can succeed or show help
     let _ = result;
 }
+
+#[test]
+fn test_self_test_passes() {
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.arg("self-test");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PASS  intune create"))
+        .stdout(predicate::str::contains("PASS  intune unpack"))
+        .stdout(predicate::str::contains("FAIL").not());
+}
+
+#[test]
+fn test_selftest_alias_checks_pkg_structure() {
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.arg("selftest");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PASS  intune verify"))
+        .stdout(predicate::str::contains("PASS  pkg xar"))
+        .stdout(predicate::str::contains("PASS  pkg cpio"))
+        .stdout(predicate::str::contains("PASS  pkg bom"));
+}
+
+/// Without a console (piped stdio, as under a service) there is no prompt
+#[test]
+fn test_no_console_refuses_interactive_mode() {
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.write_stdin("");
+
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("needs a terminal"));
+}
+
+#[test]
+fn test_no_tty_env_disables_progress() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::write(source_dir.join("setup.exe"), "setup").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.env("IAMAWRAPPER_NO_TTY", "1").args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("setup.intunewin"))
+        .stderr(predicate::str::is_empty());
+}
+
+/// Name of the current user's workspace root under a temp directory.
+fn workspace_dir() -> std::ffi::OsString {
+    let root = iamawrapper::packager::workspace::workspace_root();
+    root.file_name().unwrap().to_owned()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clean_workspaces_removes_stale_runs() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let stale = tmp.path().join(workspace_dir()).join("1000-42-00000000");
+    std::fs::create_dir_all(stale.join("capture-0")).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.env("TMPDIR", tmp.path()).arg("clean-workspaces");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 1 workspace(s)"));
+    assert!(!stale.exists());
+}
+
+#[test]
+fn test_temp_dir_holds_run_workspace() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let source = temp_dir.path().join("source");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("setup.exe"), "setup").unwrap();
+    let scratch = temp_dir.path().join("scratch");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "--temp-dir",
+        scratch.to_str().unwrap(),
+        "-q",
+        "intune",
+        "create",
+    ])
+    .args(["-c", source.to_str().unwrap(), "-s", "setup.exe"])
+    .args(["-o", temp_dir.path().join("out").to_str().unwrap()]);
+    cmd.assert().success();
+
+    // The run folder is gone once its last staging folder is dropped
+    let root = scratch.join(workspace_dir());
+    assert!(root.is_dir());
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+    assert!(temp_dir.path().join("out/setup.intunewin").exists());
+}
+
+#[test]
+fn test_io_retries_option() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let source = temp_dir.path().join("source");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("setup.exe"), "setup").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["-q", "intune", "create", "--io-retries", "3"])
+        .args(["-c", source.to_str().unwrap(), "-s", "setup.exe"])
+        .args(["-o", temp_dir.path().join("out").to_str().unwrap()]);
+    cmd.assert().success().stderr(predicate::str::is_empty());
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "create", "--io-retries", "many"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_size_limit_option() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let source = temp_dir.path().join("source");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("setup.exe"), vec![0u8; 64 * 1024]).unwrap();
+    let output = temp_dir.path().join("out");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["-q", "intune", "create", "--size-limit", "32KiB"])
+        .args(["-c", source.to_str().unwrap(), "-s", "setup.exe"])
+        .args(["-o", output.to_str().unwrap()]);
+    cmd.assert().success().stderr(predicate::str::contains(
+        "above the size limit of 32.00 KiB",
+    ));
+
+    // Strict mode fails before anything is written
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "--strict",
+        "-q",
+        "intune",
+        "create",
+        "--size-limit",
+        "32KiB",
+    ])
+    .args(["-c", source.to_str().unwrap(), "-s", "setup.exe"])
+    .args(["-o", temp_dir.path().join("strict").to_str().unwrap()]);
+    cmd.assert()
+        .code(9)
+        .stderr(predicate::str::contains("package-size"));
+    assert!(!temp_dir.path().join("strict").exists());
+}
+
+#[test]
+fn test_completions_cover_subcommands() {
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["completions", "bash"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("--size-limit"))
+        .stdout(predicate::str::contains("notarize"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["completions", "tcsh"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_manpages_written_per_command() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path().join("man1");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["-q", "manpages", dir.to_str().unwrap()]);
+    cmd.assert().success().stdout(predicate::str::is_empty());
+
+    assert!(dir.join("iamawrapper.1").is_file());
+    let page = std::fs::read_to_string(dir.join("iamawrapper-intune-create.1")).unwrap();
+    assert!(page.contains("size\\-limit"));
+    assert!(dir.join("iamawrapper-macos-pkg.1").is_file());
+}
@@ -0,0 +1,2386 @@
+//! Integration tests for Intune package creation and extraction.
+
+#![cfg(feature = "cli")]
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use predicates::prelude::*;
+use std::fs;
+use std::io::Read;
+use tempfile::TempDir;
+
+/// Create a source folder with a setup file and one data file.
+fn create_source(temp_dir: &TempDir) -> std::path::PathBuf {
+    let source_dir = temp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("data")).unwrap();
+    fs::write(source_dir.join("setup.exe"), "setup content").unwrap();
+    fs::write(source_dir.join("data/config.xml"), "<config/>").unwrap();
+    source_dir
+}
+
+#[test]
+fn test_intune_create_and_extract_roundtrip() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let package = output_dir.join("setup.intunewin");
+    assert!(package.exists(), "Package file should be created");
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        package.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("setup.exe")).unwrap(),
+        "setup content"
+    );
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("data/config.xml")).unwrap(),
+        "<config/>"
+    );
+}
+
+#[test]
+fn test_intune_extract_to_zip() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let package = output_dir.join("setup.intunewin");
+    let extract_dir = temp_dir.path().join("extracted");
+    let extract = || {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "extract",
+            "-i",
+            package.to_str().unwrap(),
+            "-o",
+            extract_dir.to_str().unwrap(),
+            "--extract-to-zip",
+            "-q",
+        ]);
+        cmd
+    };
+
+    // The password only comes from the environment
+    extract()
+        .env_remove("IAMAWRAPPER_ZIP_PASSWORD")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("IAMAWRAPPER_ZIP_PASSWORD"));
+
+    let archive_path = extract_dir.join("setup.zip");
+    extract()
+        .env("IAMAWRAPPER_ZIP_PASSWORD", "correct horse")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("setup.zip"));
+
+    // Nothing but the archive is written
+    let written: Vec<_> = fs::read_dir(&extract_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(written, ["setup.zip"]);
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&archive_path).unwrap()).unwrap();
+    let mut config = String::new();
+    archive
+        .by_name_decrypt("data/config.xml", b"correct horse")
+        .unwrap()
+        .unwrap()
+        .read_to_string(&mut config)
+        .unwrap();
+    assert_eq!(config, "<config/>");
+    assert!(
+        archive
+            .by_name_decrypt("setup.exe", b"wrong")
+            .unwrap()
+            .is_err()
+    );
+
+    // An existing archive is not overwritten
+    extract()
+        .env("IAMAWRAPPER_ZIP_PASSWORD", "correct horse")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[test]
+fn test_intune_create_content_tags() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+        "--content-tag",
+        "build=1234",
+        "--content-tag",
+        "git=a1b2c3d",
+    ]);
+    cmd.assert().success();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("extracted").to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("build = 1234"))
+        .stdout(predicate::str::contains("git = a1b2c3d"));
+}
+
+#[test]
+fn test_intune_create_embeds_artifact_record() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "inspect",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "--json",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"kind\": \"intunewin\""))
+        .stdout(predicate::str::contains("\"schema_version\": 1"))
+        .stdout(predicate::str::contains("\"manifest_sha256\": \""));
+
+    // Files that are not packages are rejected
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "inspect",
+        "-i",
+        source_dir.join("setup.exe").to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("neither an .intunewin nor a .pkg"));
+}
+
+#[test]
+fn test_intune_create_with_sha384_digest() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--digest-algorithm",
+        "sha-384",
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "verify", "-i", package.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PASS  digest"));
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        package.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("setup.exe")).unwrap(),
+        "setup content"
+    );
+}
+
+#[test]
+fn test_intune_verify() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "verify", "-i", package.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("PASS  hmac"))
+        .stdout(predicate::str::contains("PASS  setup file"));
+
+    // A broken package fails with every check listed
+    let broken = temp_dir.path().join("broken.intunewin");
+    fs::write(&broken, "not a zip").unwrap();
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "verify", "-i", broken.to_str().unwrap(), "--json"]);
+    cmd.assert()
+        .failure()
+        .code(1)
+        .stdout(predicate::str::contains("\"passed\": false"))
+        .stdout(predicate::str::contains("\"outcome\": \"skipped\""))
+        .stderr(predicate::str::contains("7 check(s) did not pass"));
+}
+
+#[test]
+fn test_intune_rules() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    // An exe gives nothing to derive rules from
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "rules", "-i", package.to_str().unwrap()]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("add a file or registry rule"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "rules",
+        "-i",
+        package.to_str().unwrap(),
+        "--file",
+        "C:\\Program Files\\Acme\\acme.exe",
+        "--registry",
+        "HKLM\\SOFTWARE\\Acme:Version=1.0",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2 detection rule(s) written"));
+
+    let rules = fs::read_to_string(output_dir.join("setup.detection.json")).unwrap();
+    assert!(rules.contains("\"fileOrFolderName\": \"acme.exe\""));
+    assert!(rules.contains("HKEY_LOCAL_MACHINE\\\\SOFTWARE\\\\Acme"));
+    assert!(rules.contains("\"msiInformation\": null"));
+}
+
+#[test]
+fn test_intune_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    // The publisher and commands come from the config, overridden by flags
+    let config = temp_dir.path().join("config.json");
+    fs::write(
+        &config,
+        r#"{"app": {"publisher": "ACME", "install_command": "setup.exe /S", "uninstall_command": "uninstall.exe /S"}}"#,
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "--config",
+        config.to_str().unwrap(),
+        "intune",
+        "manifest",
+        "-i",
+        package.to_str().unwrap(),
+        "--display-name",
+        "Acme Tool",
+        "--install-command",
+        "setup.exe /quiet",
+        "--architecture",
+        "x64",
+        "--return-code",
+        "0=success",
+        "--file",
+        "C:\\Program Files\\Acme\\acme.exe",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("App: Acme Tool (ACME)"));
+
+    let manifest = fs::read_to_string(output_dir.join("setup.app.json")).unwrap();
+    assert!(manifest.contains("\"@odata.type\": \"#microsoft.graph.win32LobApp\""));
+    assert!(manifest.contains("\"installCommandLine\": \"setup.exe /quiet\""));
+    assert!(manifest.contains("\"uninstallCommandLine\": \"uninstall.exe /S\""));
+    assert!(manifest.contains("\"applicableArchitectures\": \"x64\""));
+    assert!(manifest.contains("\"fileOrFolderName\": \"acme.exe\""));
+
+    // Without a config, an exe needs its commands on the command line
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "manifest",
+        "-i",
+        package.to_str().unwrap(),
+        "--publisher",
+        "ACME",
+        "--file",
+        "C:\\Acme\\acme.exe",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--install-command"));
+}
+
+#[test]
+fn test_intune_create_transforms_matching_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::write(source_dir.join("install.ps1"), "line1\nline2\n").unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--transform",
+        "*.ps1=crlf",
+        "--transform",
+        "config.xml=replace:config=settings",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Transformed files:"))
+        .stdout(predicate::str::contains("install.ps1 (crlf)"));
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("install.ps1")).unwrap(),
+        "line1\r\nline2\r\n"
+    );
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("data/config.xml")).unwrap(),
+        "<settings/>"
+    );
+    // The source folder is left untouched
+    assert_eq!(
+        fs::read_to_string(source_dir.join("install.ps1")).unwrap(),
+        "line1\nline2\n"
+    );
+}
+
+#[test]
+fn test_intune_create_rejects_unknown_transform() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().join("output").to_str().unwrap(),
+        "--transform",
+        "*.ps1=upper",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown transform 'upper'"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_intune_create_reports_sparse_and_keeps_empty_files() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::write(source_dir.join("empty.txt"), "").unwrap();
+    let disk = source_dir.join("disk.vhd");
+    fs::File::create(&disk)
+        .unwrap()
+        .set_len(32 * 1024 * 1024)
+        .unwrap();
+    let metadata = fs::metadata(&disk).unwrap();
+    let holes_supported = metadata.blocks() * 512 < metadata.len() / 2;
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    let assert = cmd.assert().success();
+    if holes_supported {
+        assert
+            .stderr(predicate::str::contains("1 sparse file(s)"))
+            .stderr(predicate::str::contains("disk.vhd"));
+    }
+
+    // Holes compress away, so the package stays small
+    let package = output_dir.join("setup.intunewin");
+    assert!(fs::metadata(&package).unwrap().len() < 1024 * 1024);
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        package.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::metadata(extract_dir.join("empty.txt")).unwrap().len(),
+        0
+    );
+    assert_eq!(
+        fs::metadata(extract_dir.join("disk.vhd")).unwrap().len(),
+        32 * 1024 * 1024
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_intune_create_skips_fifos_and_symlink_loops() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let status = std::process::Command::new("mkfifo")
+        .arg(source_dir.join("data/pipe"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::os::unix::fs::symlink("..", source_dir.join("data/loop")).unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let create = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ]);
+        cmd.args(extra);
+        cmd
+    };
+
+    create(&["--strict"])
+        .assert()
+        .failure()
+        .code(9)
+        .stderr(predicate::str::contains("2 special entr(ies)"));
+
+    // Finishes instead of blocking on the pipe
+    create(&[])
+        .timeout(std::time::Duration::from_secs(60))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("skipped 2 entr(ies)"))
+        .stderr(predicate::str::contains("data/loop (symlink loop)"))
+        .stderr(predicate::str::contains("data/pipe (named pipe)"));
+}
+
+#[test]
+fn test_intune_roundtrip_restores_empty_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::create_dir_all(source_dir.join("logs/archive")).unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("2 files, 3 directories extracted"));
+
+    assert!(extract_dir.join("logs/archive").is_dir());
+}
+
+#[test]
+fn test_capture_start_and_finish() {
+    let temp_dir = TempDir::new().unwrap();
+    let watched = temp_dir.path().join("vendor");
+    fs::create_dir_all(&watched).unwrap();
+    fs::write(watched.join("existing.dll"), "v1").unwrap();
+    let state = temp_dir.path().join("capture.json");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "capture",
+        "start",
+        "-p",
+        watched.to_str().unwrap(),
+        "--state",
+        state.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Captured 1 files"));
+
+    fs::write(watched.join("existing.dll"), "v2").unwrap();
+    fs::write(watched.join("setup.exe"), "installer").unwrap();
+
+    let output_dir = temp_dir.path().join("output");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "capture",
+        "finish",
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--state",
+        state.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("1 added, 1 changed, 0 removed"));
+
+    assert!(output_dir.join("setup.intunewin").exists());
+}
+
+#[test]
+fn test_intune_create_refuses_filesystem_root() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        "/",
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("--i-know-what-im-doing"));
+}
+
+#[test]
+fn test_intune_create_file_limit_requires_force() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+        "--max-files",
+        "1",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unusually large"));
+    assert!(!output_dir.join("setup.intunewin").exists());
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+        "--max-files",
+        "1",
+        "--force",
+    ]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_intune_create_post_validate() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--post-validate",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Post-validation passed"));
+}
+
+#[test]
+fn test_intune_create_setup_not_found_suggests() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup",
+        "-o",
+        temp_dir.path().join("output").to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("did you mean 'setup.exe'?"));
+}
+
+#[test]
+fn test_intune_create_strict_promotes_warnings() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let hash =
+        iamawrapper::packager::blocklist::sha256_file(&source_dir.join("setup.exe")).unwrap();
+    let blocklist = temp_dir.path().join("hashes.csv");
+    fs::write(&blocklist, format!("{},Known bad\n", hash)).unwrap();
+
+    let create = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--blocklist",
+            blocklist.to_str().unwrap(),
+            "--blocklist-warn",
+            "-q",
+        ]);
+        cmd.args(extra);
+        cmd
+    };
+
+    create(&["--strict"]).assert().failure().code(8);
+
+    // A per-check override in the config file wins over --strict
+    let config = temp_dir.path().join("config.json");
+    fs::write(&config, r#"{"checks": {"blocklist": "warn"}}"#).unwrap();
+    create(&["--strict", "--config", config.to_str().unwrap()])
+        .assert()
+        .success();
+
+    fs::write(&config, r#"{"checks": {"blocklist": "fatal"}}"#).unwrap();
+    create(&["--config", config.to_str().unwrap()])
+        .assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid config file"));
+}
+
+#[test]
+fn test_intune_create_from_stdin_tar() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_dir_all("./payload", &source_dir).unwrap();
+    let tar_stream = builder.into_inner().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "--content-stdin-tar",
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ])
+    .write_stdin(tar_stream);
+    cmd.assert().success();
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("setup.exe")).unwrap(),
+        "setup content"
+    );
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("data/config.xml")).unwrap(),
+        "<config/>"
+    );
+}
+
+#[test]
+fn test_intune_create_from_zip_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    // As build systems hand it over: everything under one top-level folder
+    let archive_path = temp_dir.path().join("app.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive_path).unwrap());
+    let options = zip::write::FileOptions::default();
+    zip.add_directory("app/", options).unwrap();
+    zip.start_file("app/setup.exe", options).unwrap();
+    std::io::Write::write_all(&mut zip, b"setup content").unwrap();
+    zip.start_file("app/data/config.xml", options).unwrap();
+    std::io::Write::write_all(&mut zip, b"<config/>").unwrap();
+    zip.finish().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        archive_path.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("setup.exe")).unwrap(),
+        "setup content"
+    );
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("data/config.xml")).unwrap(),
+        "<config/>"
+    );
+}
+
+#[test]
+fn test_intune_create_incremental() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+    let state_file = temp_dir.path().join("state.json");
+    let create = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--state-file",
+            state_file.to_str().unwrap(),
+        ]);
+        cmd.args(extra);
+        cmd.assert().success()
+    };
+
+    create(&["-q"]);
+    assert!(state_file.exists());
+
+    // The previous package may be replaced without prompting
+    create(&[]).stdout(predicate::str::contains(
+        "Incremental: source unchanged, content re-encrypted",
+    ));
+    let package = fs::read(output_dir.join("setup.intunewin")).unwrap();
+    create(&["--skip-unchanged"]).stdout(predicate::str::contains(
+        "Incremental: source and settings unchanged, previous package kept",
+    ));
+    assert_eq!(
+        fs::read(output_dir.join("setup.intunewin")).unwrap(),
+        package
+    );
+}
+
+#[test]
+fn test_intune_create_rejects_bad_remote_sources() {
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    // All fail before anything is downloaded
+    let mut locations = vec![
+        (
+            "https://artifacts.example.com/app.zip#sha256=1234",
+            "64 hex digits",
+        ),
+        ("http://artifacts.example.com/app.zip", "not encrypted"),
+    ];
+    if cfg!(not(feature = "s3")) {
+        locations.push(("s3://builds/app.zip", "`s3` feature"));
+    }
+    for (location, message) in locations {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            location,
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ]);
+        cmd.assert()
+            .failure()
+            .code(2)
+            .stderr(predicate::str::contains(message));
+    }
+    assert!(!output_dir.join("setup.intunewin").exists());
+}
+
+#[test]
+fn test_intune_create_with_exclude() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::create_dir_all(source_dir.join(".git/objects")).unwrap();
+    fs::write(source_dir.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+    fs::write(source_dir.join("data/setup.pdb"), "symbols").unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--exclude",
+        ".git",
+        "--exclude",
+        "*.pdb",
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert!(extract_dir.join("setup.exe").exists());
+    assert!(extract_dir.join("data/config.xml").exists());
+    assert!(!extract_dir.join(".git").exists());
+    assert!(!extract_dir.join("data/setup.pdb").exists());
+}
+
+#[test]
+fn test_intune_create_invalid_exclude() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().join("output").to_str().unwrap(),
+        "--exclude",
+        "",
+    ]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_intune_create_reproducible() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let build = |output: &str, extra: &[&str]| {
+        let output_dir = temp_dir.path().join(output);
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--reproducible",
+            "-q",
+        ])
+        .args(extra);
+        cmd.assert().success();
+        fs::read(output_dir.join("setup.intunewin")).unwrap()
+    };
+
+    let first = build("first", &[]);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert_eq!(first, build("second", &[]));
+
+    let seeded = build("seeded", &["--reproducible-seed", "ci-42"]);
+    assert_ne!(first, seeded);
+    assert_eq!(
+        seeded,
+        build("seeded-again", &["--reproducible-seed", "ci-42"])
+    );
+
+    // Reproducible packages are ordinary packages
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "verify",
+        "-i",
+        temp_dir
+            .path()
+            .join("seeded/setup.intunewin")
+            .to_str()
+            .unwrap(),
+    ]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_intune_create_with_provided_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let create = |output: &str| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            temp_dir.path().join(output).to_str().unwrap(),
+            "-q",
+        ]);
+        cmd
+    };
+    let inspect = |output: &str| {
+        let package = temp_dir.path().join(output).join("setup.intunewin");
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "inspect",
+            package.to_str().unwrap(),
+            "--json",
+            "--show-secrets",
+        ]);
+        String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap()
+    };
+
+    let keys = temp_dir.path().join("keys.json");
+    fs::write(
+        &keys,
+        r#"{
+            "encryption_key": "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=",
+            "mac_key": "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI="
+        }"#,
+    )
+    .unwrap();
+    create("file")
+        .args(["--keys-file", keys.to_str().unwrap()])
+        .assert()
+        .success();
+    let metadata = inspect("file");
+    assert!(metadata.contains("AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE="));
+
+    // The inspect output of one package supplies the keys of the next
+    create("env")
+        .arg("--keys-from-env")
+        .env("IAMAWRAPPER_ENCRYPTION_KEYS", &metadata)
+        .assert()
+        .success();
+    assert_eq!(inspect("env"), metadata);
+
+    create("missing")
+        .arg("--keys-from-env")
+        .env_remove("IAMAWRAPPER_ENCRYPTION_KEYS")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("IAMAWRAPPER_ENCRYPTION_KEYS"));
+
+    fs::write(&keys, r#"{ "encryption_key": "AQID", "mac_key": "AQID" }"#).unwrap();
+    create("invalid")
+        .args(["--keys-file", keys.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("must be 32 bytes"));
+
+    create("conflict")
+        .args(["--keys-file", keys.to_str().unwrap(), "--reproducible"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_intune_create_with_cache() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let large: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+    fs::write(source_dir.join("data/large.bin"), &large).unwrap();
+    let cache_dir = temp_dir.path().join("cache");
+
+    let build = |output: &str| {
+        let output_dir = temp_dir.path().join(output);
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--cache-dir",
+            cache_dir.to_str().unwrap(),
+            "--reproducible",
+        ]);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        (
+            String::from_utf8(output).unwrap(),
+            output_dir.join("setup.intunewin"),
+        )
+    };
+
+    let (first_output, first) = build("first");
+    assert!(first_output.contains("Cache: 0 file(s) reused (0 B), 1 added"));
+    let (second_output, second) = build("second");
+    assert!(second_output.contains("Cache: 1 file(s) reused (292.97 KiB), 0 added"));
+    assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        second.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+    assert_eq!(fs::read(extract_dir.join("data/large.bin")).unwrap(), large);
+}
+
+#[test]
+fn test_intune_create_compression() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::write(source_dir.join("data/text.log"), "line\n".repeat(50_000)).unwrap();
+
+    let build = |output: &str, compression: &[&str]| {
+        let output_dir = temp_dir.path().join(output);
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ])
+        .args(compression);
+        cmd.assert().success();
+        output_dir.join("setup.intunewin")
+    };
+
+    let stored = build("stored", &["--compression", "stored"]);
+    let smallest = build("smallest", &["--level", "9"]);
+    assert!(fs::metadata(&stored).unwrap().len() > 200_000);
+    assert!(fs::metadata(&smallest).unwrap().len() < 10_000);
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        stored.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("data/text.log")).unwrap(),
+        "line\n".repeat(50_000)
+    );
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        temp_dir.path().join("invalid").to_str().unwrap(),
+        "--compression",
+        "stored",
+        "--level",
+        "3",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("needs deflate compression"));
+}
+
+#[test]
+fn test_intune_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let build = |output: &str| {
+        let output_dir = temp_dir.path().join(output);
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ]);
+        cmd.assert().success();
+        output_dir.join("setup.intunewin")
+    };
+
+    let v1 = build("v1");
+    let v1_again = build("v1-again");
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "diff",
+            v1.to_str().unwrap(),
+            v1_again.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Packages are identical (2 files"));
+
+    fs::write(
+        source_dir.join("data/config.xml"),
+        "<config debug=\"true\"/>",
+    )
+    .unwrap();
+    fs::write(source_dir.join("data/new.dll"), "dll").unwrap();
+    let v2 = build("v2");
+    cargo_bin_cmd!("iamawrapper")
+        .args(["intune", "diff", v1.to_str().unwrap(), v2.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("~ data/config.xml (9 B -> 22 B"))
+        .stdout(predicate::str::contains("+ data/new.dll (3 B"))
+        .stdout(predicate::str::contains("~ UnencryptedContentSize:"));
+
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "diff",
+            v1.to_str().unwrap(),
+            v2.to_str().unwrap(),
+            "--json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"change\": \"added\""))
+        .stdout(predicate::str::contains("\"identical\": false"));
+}
+
+#[test]
+fn test_intune_create_to_stdout() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        "-",
+    ]);
+    let output = cmd.assert().success().get_output().clone();
+
+    // Human output goes to stderr; stdout is the package alone
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Package created successfully"));
+    assert!(output.stdout.starts_with(b"PK"));
+
+    let package = temp_dir.path().join("piped.intunewin");
+    fs::write(&package, &output.stdout).unwrap();
+
+    let extract_dir = temp_dir.path().join("extracted");
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "extract",
+        "-i",
+        package.to_str().unwrap(),
+        "-o",
+        extract_dir.to_str().unwrap(),
+    ]);
+    cmd.assert().success();
+
+    assert_eq!(
+        fs::read_to_string(extract_dir.join("setup.exe")).unwrap(),
+        "setup content"
+    );
+}
+
+#[test]
+fn test_export_and_import_project() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let project = temp_dir.path().join("app.project.json");
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "--strict",
+        "export-project",
+        "-o",
+        project.to_str().unwrap(),
+        "intune",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "--content-tag",
+        "build=42",
+    ]);
+    cmd.assert().success();
+
+    let text = fs::read_to_string(&project).unwrap();
+    assert!(text.contains("\"setup_file\": \"setup.exe\""));
+    assert!(text.contains("\"strict\": true"));
+    assert!(!text.contains("setup content"));
+
+    let import = || {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "import-project",
+            "-i",
+            project.to_str().unwrap(),
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ]);
+        cmd
+    };
+    import()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("setup.intunewin"));
+
+    // Changed content no longer reproduces the recorded build
+    fs::write(source_dir.join("data/config.xml"), "<changed/>").unwrap();
+    import()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("changed data/config.xml"));
+}
+
+#[test]
+fn test_intune_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    create_source(&temp_dir);
+    let manifest = temp_dir.path().join("apps.toml");
+    fs::write(
+        &manifest,
+        r#"
+[[apps]]
+name = "Good"
+source = "source"
+setup = "setup.exe"
+
+[[apps]]
+name = "Missing setup"
+source = "source"
+setup = "absent.exe"
+output_name = "absent"
+"#,
+    )
+    .unwrap();
+
+    let output_dir = temp_dir.path().join("out");
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "batch",
+            manifest.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-j",
+            "2",
+        ])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Good"))
+        .stdout(predicate::str::contains("FAILED"))
+        .stdout(predicate::str::contains("1 of 2 application(s) packaged"))
+        .stderr(predicate::str::contains("1 of 2 item(s) were not packaged"));
+    assert!(output_dir.join("setup.intunewin").exists());
+
+    // Remove the broken entry; the rerun overwrites in quiet mode
+    let text = fs::read_to_string(&manifest).unwrap();
+    fs::write(
+        &manifest,
+        &text[..text.find("[[apps]]\nname = \"Missing").unwrap()],
+    )
+    .unwrap();
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "batch",
+            manifest.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("setup.intunewin"));
+}
+
+#[test]
+fn test_intune_repack() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::write(source_dir.join("install.ps1"), "Start-Process setup.exe").unwrap();
+    let output_dir = temp_dir.path().join("output");
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+        ])
+        .assert()
+        .success();
+
+    let input = output_dir.join("setup.intunewin");
+    let repacked = temp_dir.path().join("install.intunewin");
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "repack",
+            "-i",
+            input.to_str().unwrap(),
+            "-o",
+            repacked.to_str().unwrap(),
+            "--set-setup-file",
+            "install.ps1",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "~ SetupFile: setup.exe -> install.ps1",
+        ));
+
+    // The repacked package extracts with the new setup file
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "extract",
+            "-i",
+            repacked.to_str().unwrap(),
+            "-o",
+            temp_dir.path().join("extracted").to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Setup file: install.ps1"));
+
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "repack",
+            "-i",
+            input.to_str().unwrap(),
+            "-o",
+            temp_dir.path().join("bad.intunewin").to_str().unwrap(),
+            "--set-setup-file",
+            "missing.exe",
+        ])
+        .assert()
+        .failure()
+        .code(4);
+}
+
+#[test]
+fn test_intune_create_with_sbom() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--sbom",
+        "spdx",
+        "--embed-sbom",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("SBOM: "))
+        .stdout(predicate::str::contains("(2 file(s), 0 product(s))"));
+
+    let sidecar = fs::read_to_string(output_dir.join("setup.spdx.json")).unwrap();
+    assert!(sidecar.contains("\"spdxVersion\": \"SPDX-2.3\""));
+    assert!(sidecar.contains("\"fileName\": \"./data/config.xml\""));
+
+    let package = fs::File::open(output_dir.join("setup.intunewin")).unwrap();
+    let mut archive = zip::ZipArchive::new(package).unwrap();
+    let mut embedded = String::new();
+    std::io::Read::read_to_string(
+        &mut archive
+            .by_name("IntuneWinPackage/Metadata/SBOM.json")
+            .unwrap(),
+        &mut embedded,
+    )
+    .unwrap();
+    assert_eq!(embedded, sidecar);
+
+    // A sidecar cannot be written next to stdout
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        "-",
+        "--sbom",
+        "cyclonedx",
+    ]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "A sidecar SBOM needs an output file",
+    ));
+}
+
+#[test]
+fn test_intune_create_sbom_flags_blocklist_matches() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let hash =
+        iamawrapper::packager::blocklist::sha256_file(&source_dir.join("setup.exe")).unwrap();
+    let blocklist = temp_dir.path().join("hashes.csv");
+    fs::write(&blocklist, format!("{},Known bad\n", hash)).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--blocklist",
+        blocklist.to_str().unwrap(),
+        "--blocklist-warn",
+        "--sbom",
+        "cyclonedx",
+        "-q",
+    ]);
+    cmd.assert().success();
+
+    let sidecar = fs::read_to_string(output_dir.join("setup.cdx.json")).unwrap();
+    assert!(sidecar.contains("\"vulnerabilities\""));
+    assert!(sidecar.contains(&format!("\"id\": \"sha256:{}\"", hash)));
+    assert!(sidecar.contains("\"description\": \"Matches a known-bad hash: Known bad\""));
+    assert!(sidecar.contains("\"ref\": \"file:setup.exe\""));
+}
+
+#[test]
+fn test_intune_inspect() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "inspect", package.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Setup file: setup.exe"))
+        .stdout(predicate::str::contains(
+            "Encryption key (256 bits): <redacted>",
+        ))
+        .stdout(predicate::str::contains("File digest (SHA256): "));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "inspect",
+        package.to_str().unwrap(),
+        "--json",
+        "--show-secrets",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"unencrypted_content_size\": "))
+        .stdout(predicate::str::contains("<redacted>").not());
+
+    // Nothing is extracted
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
+}
+
+#[test]
+fn test_intune_list() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+    ]);
+    cmd.assert().success();
+    let package = output_dir.join("setup.intunewin");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "list", package.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("data/config.xml"))
+        .stdout(predicate::str::contains("setup.exe"))
+        .stdout(predicate::str::contains("2 file(s)"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "list", package.to_str().unwrap(), "--json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"path\": \"data/config.xml\""))
+        .stdout(predicate::str::contains("\"size\": 9,"))
+        .stdout(predicate::str::contains("\"crc32\": "));
+
+    // Nothing is extracted
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
+}
+
+#[test]
+fn test_intune_relationships() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+    let sidecar = temp_dir.path().join("relationships.json");
+    fs::write(
+        &sidecar,
+        r#"{
+            "dependencies": [{ "app": "VC++ 2022", "version": "14.38" }],
+            "supersedes": [{ "app": "App", "version": "1.0", "uninstall": true }]
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--relationships",
+        sidecar.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Dependencies:"));
+    let package = output_dir.join("setup.intunewin");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "inspect", package.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "VC++ 2022 14.38 (installed if missing)",
+        ))
+        .stdout(predicate::str::contains("App 1.0 (uninstalled first)"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "list", package.to_str().unwrap(), "--json"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"relationships\": {"))
+        .stdout(predicate::str::contains("\"uninstall\": true"));
+
+    // A malformed file fails before anything is packaged
+    fs::write(&sidecar, r#"{ "supersedes": "App" }"#).unwrap();
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-n",
+        "invalid",
+        "--relationships",
+        sidecar.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid relationships file"));
+    assert!(!output_dir.join("invalid.intunewin").exists());
+}
+
+#[test]
+fn test_watch_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let drop_dir = temp_dir.path().join("drop");
+    let output_dir = temp_dir.path().join("share");
+    fs::create_dir_all(drop_dir.join("App1")).unwrap();
+    fs::write(drop_dir.join("App1/install.cmd"), b"@echo off").unwrap();
+    fs::write(drop_dir.join("App1/debug.pdb"), b"symbols").unwrap();
+
+    let config = temp_dir.path().join("config.json");
+    fs::write(
+        &config,
+        r#"{"watch": {"setup_files": ["*.cmd"], "exclude": ["*.pdb"]}}"#,
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "watch",
+            "-d",
+            drop_dir.to_str().unwrap(),
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--once",
+            "--config",
+            config.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("App1  ok"))
+        .stdout(predicate::str::contains("1 of 1 application(s) packaged"));
+
+    assert!(output_dir.join("App1.intunewin").exists());
+    let log = fs::read_to_string(output_dir.join("App1.log")).unwrap();
+    assert!(log.contains("Setup file: install.cmd"));
+    assert!(drop_dir.join(".processed/App1/install.cmd").exists());
+
+    // Output inside the drop folder would be picked up as an application
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "watch",
+            "-d",
+            drop_dir.to_str().unwrap(),
+            "-o",
+            drop_dir.join("out").to_str().unwrap(),
+            "--once",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "must not be inside the drop folder",
+        ));
+}
+
+#[test]
+fn test_log_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+    let log_file = temp_dir.path().join("run.log");
+
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+            "--log-file",
+            log_file.to_str().unwrap(),
+            "--log-format",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    let log = fs::read_to_string(&log_file).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert!(lines.iter().all(|line| line.starts_with("{\"timestamp\":")));
+    assert!(log.contains("\"level\":\"INFO\""));
+    assert!(log.contains("\"message\":\"Wrote package\""));
+    // Each phase reports its time when it ends
+    for phase in ["zip", "encrypt", "write"] {
+        assert!(
+            lines.iter().any(|line| line.contains("\"time.busy\"")
+                && line.contains(&format!("\"span\":{{\"name\":\"{}\"}}", phase))),
+            "no timing for {}",
+            phase
+        );
+    }
+
+    // Errors are logged too, in silent mode only to the file
+    cargo_bin_cmd!("iamawrapper")
+        .args([
+            "intune",
+            "create",
+            "-c",
+            temp_dir.path().join("missing").to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "--silent",
+            "--log-file",
+            log_file.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_empty());
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(
+        log.lines()
+            .any(|line| line.contains("ERROR") && line.contains("Source folder not found"))
+    );
+}
+
+#[test]
+fn test_intune_analyze_reports_duplicates() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::create_dir_all(source_dir.join("x64")).unwrap();
+    fs::write(source_dir.join("data/runtime.dll"), vec![1u8; 2048]).unwrap();
+    fs::write(source_dir.join("x64/runtime.dll"), vec![1u8; 2048]).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "analyze",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Files: 4"))
+        .stdout(predicate::str::contains(
+            "Duplicate files (2.00 KiB in extra copies)",
+        ))
+        .stdout(predicate::str::contains("    x64/runtime.dll"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "analyze",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "--exclude",
+        "x64",
+        "--json",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"duplicate_bytes\": 0"));
+}
+
+#[test]
+fn test_intune_create_detection_overrides() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-q",
+        "--display-name",
+        "ACME Tool",
+        "--tool-version",
+        "1.8.4.0",
+    ]);
+    cmd.assert().success();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "inspect",
+        output_dir.join("setup.intunewin").to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Tool version: 1.8.4.0"))
+        .stdout(predicate::str::contains("Name: ACME Tool"))
+        .stdout(predicate::str::contains("Setup file: setup.exe"));
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "-n",
+        "other",
+        "--tool-version",
+        "latest",
+    ]);
+    cmd.assert()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid tool version 'latest'"));
+}
+
+#[test]
+fn test_intune_scaffold_writes_wrappers() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let scaffold = || {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "scaffold",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "--install-args",
+            "/S",
+            "--uninstall-file",
+            r"%ProgramFiles%\Contoso\uninst.exe",
+            "--uninstall-args",
+            "/S",
+            "--name",
+            "Contoso",
+            "--success-code",
+            "-1",
+        ]);
+        cmd
+    };
+
+    scaffold()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Install command:   powershell.exe -ExecutionPolicy Bypass -NoProfile -File .\\Install.ps1",
+        ))
+        .stdout(predicate::str::contains(
+            r"%ProgramData%\iamawrapper\Tags\Contoso.tag",
+        ));
+
+    let install = fs::read_to_string(source_dir.join("Install.ps1")).unwrap();
+    assert!(install.contains("$SuccessCodes = @(0, 1707, -1)"));
+    let uninstall = fs::read_to_string(source_dir.join("Uninstall.ps1")).unwrap();
+    assert!(uninstall.contains(r"$FilePath = '%ProgramFiles%\Contoso\uninst.exe'"));
+
+    // Existing wrappers are only replaced with --force
+    scaffold()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+    scaffold().arg("--force").assert().success();
+}
+
+#[test]
+fn test_intune_create_name_template() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    // A checkout whose HEAD is detached at a known commit
+    let git_dir = temp_dir.path().join(".git");
+    fs::create_dir_all(&git_dir).unwrap();
+    fs::write(
+        git_dir.join("HEAD"),
+        "0123456789abcdef0123456789abcdef01234567\n",
+    )
+    .unwrap();
+
+    let create = |template: &str| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+            "--name-template",
+            template,
+        ]);
+        cmd
+    };
+
+    // setup.exe has no version information, so {version} is left out
+    create("{setup_stem}-{version}-{git_sha}")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("setup-0123456.intunewin"));
+    assert!(output_dir.join("setup-0123456.intunewin").exists());
+
+    create("{setup_stem}-{build}")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Unknown variable '{build}'"));
+}
+
+#[test]
+fn test_intune_create_checks_windows_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    fs::write(source_dir.join("data/con.txt"), "device").unwrap();
+    fs::write(source_dir.join("data/a:b.txt"), "colon").unwrap();
+    let output_dir = temp_dir.path().join("output");
+    let report = temp_dir.path().join("paths.json");
+
+    let create = |extra: &[&str]| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "intune",
+            "create",
+            "-c",
+            source_dir.to_str().unwrap(),
+            "-s",
+            "setup.exe",
+            "-o",
+            output_dir.to_str().unwrap(),
+            "-q",
+            "--force",
+        ]);
+        cmd.args(extra);
+        cmd
+    };
+
+    create(&["--strict"])
+        .assert()
+        .failure()
+        .code(9)
+        .stderr(predicate::str::contains("windows-paths"));
+
+    create(&["--path-report", report.to_str().unwrap()])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "2 path(s) would not install on Windows",
+        ))
+        .stderr(predicate::str::contains("data/a:b.txt"))
+        .stderr(predicate::str::contains("data/con.txt"));
+
+    let text = fs::read_to_string(&report).unwrap();
+    assert!(text.contains("\"problem\": \"invalid-character\""));
+    assert!(text.contains("\"problem\": \"reserved-name\""));
+}
+
+#[test]
+fn test_intune_create_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("data")).unwrap();
+    fs::write(source_dir.join("setup.exe"), "setup").unwrap();
+    fs::write(source_dir.join("data/app.dat"), vec![7u8; 4096]).unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "create", "--dry-run"])
+        .args(["-c", source_dir.to_str().unwrap(), "-s", "setup.exe"])
+        .args(["-o", output_dir.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Dry run: nothing was written"))
+        .stdout(predicate::str::contains("2 files, 1 directories"))
+        .stdout(predicate::str::contains("setup_file: setup.exe"));
+    assert!(!output_dir.exists());
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args(["intune", "create", "--dry-run", "--json"])
+        .args(["-c", source_dir.to_str().unwrap(), "-s", "setup.exe"])
+        .args(["-o", output_dir.to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"file_count\": 2"))
+        .stdout(predicate::str::contains("\"size_limit\""));
+
+    // Checks that fail a run fail the dry run too
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "--strict",
+        "intune",
+        "create",
+        "--dry-run",
+        "--size-limit",
+        "1KiB",
+    ])
+    .args(["-c", source_dir.to_str().unwrap(), "-s", "setup.exe"])
+    .args(["-o", output_dir.to_str().unwrap()]);
+    cmd.assert().code(9);
+}
+
+#[test]
+fn test_intune_create_signed_and_verify_signature() {
+    use iamawrapper::signing::SecretKey;
+
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = create_source(&temp_dir);
+    let output_dir = temp_dir.path().join("output");
+
+    let key = SecretKey::generate();
+    let secret = temp_dir.path().join("release.key");
+    let public = temp_dir.path().join("release.pub");
+    fs::write(&secret, key.to_text(None).unwrap().as_str()).unwrap();
+    fs::write(&public, key.public_key().to_text()).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "intune",
+        "create",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-s",
+        "setup.exe",
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--sign-key",
+        secret.to_str().unwrap(),
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(format!(
+        "Signing key: {}",
+        key.key_id()
+    )))
+    .stdout(predicate::str::contains("setup.intunewin.minisig"));
+
+    let package = output_dir.join("setup.intunewin");
+    let signature = output_dir.join("setup.intunewin.minisig");
+    assert!(
+        fs::read_to_string(&signature)
+            .unwrap()
+            .contains("\tfile:setup.intunewin\thashed")
+    );
+    let verify = |public: &std::path::Path| {
+        let mut cmd = cargo_bin_cmd!("iamawrapper");
+        cmd.args([
+            "verify-signature",
+            "-i",
+            package.to_str().unwrap(),
+            "-p",
+            public.to_str().unwrap(),
+        ]);
+        cmd
+    };
+    verify(&public)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("verified"));
+
+    // Another key, or a changed package, fails the check
+    let other = temp_dir.path().join("other.pub");
+    fs::write(&other, SecretKey::generate().public_key().to_text()).unwrap();
+    verify(&other)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(key.key_id()));
+
+    let mut bytes = fs::read(&package).unwrap();
+    bytes.push(0);
+    fs::write(&package, bytes).unwrap();
+    verify(&public)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not match the signature"));
+}
@@ -0,0 +1,197 @@
+//! Integration tests for Debian and RPM package creation.
+
+#![cfg(all(feature = "cli", feature = "linux"))]
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use iamawrapper::linux::ar::read_members;
+use iamawrapper::linux::rpm::{HeaderValue, read_package, tag};
+use iamawrapper::macos::cpio::read_payload;
+use predicates::prelude::*;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn test_linux_deb() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("bin")).unwrap();
+    fs::write(source_dir.join("bin/agent"), "#!/bin/sh\necho agent\n").unwrap();
+    fs::write(source_dir.join("agent.conf"), "interval=60\n").unwrap();
+    let scripts_dir = temp_dir.path().join("scripts");
+    fs::create_dir(&scripts_dir).unwrap();
+    fs::write(scripts_dir.join("postinst"), "#!/bin/sh\nexit 0\n").unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "linux",
+        "deb",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--name",
+        "contoso-agent",
+        "--version",
+        "1.2.0-1",
+        "--arch",
+        "amd64",
+        "--install-location",
+        "/opt/contoso",
+        "--depends",
+        "libc6 (>= 2.31)",
+        "--depends",
+        "curl",
+        "--scripts",
+        scripts_dir.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Package created successfully"))
+        .stdout(predicate::str::contains("2 files included"))
+        .stdout(predicate::str::contains("Maintainer scripts: postinst"));
+
+    let package = output_dir.join("contoso-agent_1.2.0-1_amd64.deb");
+    let members = read_members(&fs::read(&package).unwrap()).unwrap();
+    let names: Vec<_> = members.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["debian-binary", "control.tar.gz", "data.tar.xz"]);
+
+    // Cross-check with dpkg where it is installed
+    if let Ok(output) = Command::new("dpkg-deb")
+        .arg("--info")
+        .arg(&package)
+        .output()
+    {
+        assert!(output.status.success());
+        let info = String::from_utf8_lossy(&output.stdout);
+        assert!(info.contains("Package: contoso-agent"));
+        assert!(info.contains("Depends: libc6 (>= 2.31), curl"));
+        assert!(info.contains("postinst"));
+    }
+}
+
+#[test]
+fn test_linux_deb_invalid_version() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("agent"), "agent").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "linux",
+        "deb",
+        "-c",
+        temp_dir.path().to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+        "--name",
+        "agent",
+        "--version",
+        "v1.0",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid package version"));
+}
+
+#[test]
+fn test_linux_rpm() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("source");
+    fs::create_dir_all(source_dir.join("bin")).unwrap();
+    fs::write(source_dir.join("bin/agent"), "#!/bin/sh\necho agent\n").unwrap();
+    fs::write(source_dir.join("agent.conf"), "interval=60\n").unwrap();
+    let scripts_dir = temp_dir.path().join("scripts");
+    fs::create_dir(&scripts_dir).unwrap();
+    fs::write(scripts_dir.join("post"), "#!/bin/sh\nexit 0\n").unwrap();
+    let output_dir = temp_dir.path().join("output");
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "linux",
+        "rpm",
+        "-c",
+        source_dir.to_str().unwrap(),
+        "-o",
+        output_dir.to_str().unwrap(),
+        "--name",
+        "contoso-agent",
+        "--version",
+        "1.2.0",
+        "--release",
+        "2",
+        "--arch",
+        "x86_64",
+        "--install-location",
+        "/opt/contoso",
+        "--summary",
+        "Contoso agent",
+        "--requires",
+        "glibc >= 2.17",
+        "--scripts",
+        scripts_dir.to_str().unwrap(),
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Package created successfully"))
+        .stdout(predicate::str::contains("2 files included"))
+        .stdout(predicate::str::contains("Scriptlets: post"));
+
+    let package = output_dir.join("contoso-agent-1.2.0-2.x86_64.rpm");
+    let data = fs::read(&package).unwrap();
+    let parts = read_package(&data).unwrap();
+    assert_eq!(parts.lead_name, "contoso-agent-1.2.0-2");
+    assert_eq!(
+        parts.get(tag::SUMMARY).and_then(HeaderValue::as_str),
+        Some("Contoso agent")
+    );
+    assert_eq!(
+        parts.get(tag::POST_IN).and_then(HeaderValue::as_str),
+        Some("#!/bin/sh\nexit 0\n")
+    );
+
+    let paths: Vec<_> = read_payload(parts.payload)
+        .unwrap()
+        .into_iter()
+        .map(|record| record.path)
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "./opt/contoso/agent.conf",
+            "./opt/contoso/bin",
+            "./opt/contoso/bin/agent"
+        ]
+    );
+
+    // Cross-check with libarchive where it is installed
+    if let Ok(output) = Command::new("bsdtar").arg("-tf").arg(&package).output() {
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("./opt/contoso/bin/agent"));
+    }
+}
+
+#[test]
+fn test_linux_rpm_invalid_requires() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("agent"), "agent").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("iamawrapper");
+    cmd.args([
+        "linux",
+        "rpm",
+        "-c",
+        temp_dir.path().to_str().unwrap(),
+        "-o",
+        temp_dir.path().join("out").to_str().unwrap(),
+        "--name",
+        "agent",
+        "--version",
+        "1.0",
+        "--requires",
+        "glibc (>= 2.17)",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid requirement"));
+}